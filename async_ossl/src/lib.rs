@@ -1,6 +1,11 @@
 use openssl::ssl::SslStream;
 use std::net::TcpStream;
 
+mod udp;
+mod websocket;
+pub use udp::*;
+pub use websocket::*;
+
 #[cfg(unix)]
 pub trait AsRawDesc: std::os::unix::io::AsRawFd {}
 #[cfg(windows)]
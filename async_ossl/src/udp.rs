@@ -0,0 +1,1145 @@
+//! A roaming tolerant datagram transport for the mux protocol.
+//!
+//! The byte stream is split into numbered segments that are carried in
+//! encrypted and authenticated UDP datagrams, and which are retransmitted
+//! until the peer acknowledges them.  Sessions are identified by a random
+//! id rather than by the address of the peer, so when the client moves
+//! to a different network, or wakes up from sleep with a new address, the
+//! server simply follows it to its new address and the session survives.
+//!
+//! Both ends share a pre-shared key.  The client starts a session by
+//! sending a HELLO that is sealed with a key derived from the pre-shared
+//! key and the session id.  The server replies with a random value, and
+//! the session itself is sealed with a key derived from the pre-shared
+//! key, the session id and that random value, so that replaying a HELLO
+//! can never cause a key and nonce to be used a second time.  The server
+//! only starts the session once the client has proven that it derived
+//! the same key.
+//!
+//! A server that receives a datagram for a session that it doesn't know
+//! about, perhaps because it was restarted, replies with a RESET that is
+//! authenticated with an HMAC rather than sealed, so that the client can
+//! promptly reconnect.
+//!
+//! The application side of a session is one end of a socketpair, so that
+//! it can be driven by the async reactor in the same way as the other
+//! transports, while a background thread shuttles data between the other
+//! end of the socketpair and the UDP socket.
+use crate::AsRawDesc;
+use anyhow::{anyhow, bail, Context};
+use filedescriptor::{
+    poll, pollfd, socketpair, AsRawSocketDescriptor, FileDescriptor, POLLIN, POLLOUT,
+};
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::TryInto;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// session id, direction and packet counter
+const HEADER_LEN: usize = 17;
+/// kind, cumulative ack and sequence number
+const BODY_LEN: usize = 17;
+const TAG_LEN: usize = 16;
+/// The largest payload that we place in a single datagram; chosen
+/// so that datagrams fit within the path MTU of typical networks
+const MAX_SEGMENT: usize = 1200;
+const MAX_DATAGRAM: usize = 2048;
+/// The maximum number of unacknowledged segments in flight
+const MAX_IN_FLIGHT: usize = 256;
+/// The maximum amount of received data that we will buffer while
+/// the application is not reading it
+const MAX_INBOUND: usize = 1024 * 1024;
+const MIN_RTO: Duration = Duration::from_millis(50);
+const INITIAL_RTO: Duration = Duration::from_millis(500);
+const MAX_RTO: Duration = Duration::from_secs(2);
+/// Sending something at least this often keeps NAT bindings alive
+/// and lets the server learn about a change of client address promptly
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(3);
+const HELLO_INTERVAL: Duration = Duration::from_millis(500);
+/// How old a HELLO may be before the server refuses to start a
+/// session for it; this limits the usefulness of replaying one
+const MAX_HELLO_AGE: Duration = Duration::from_secs(120);
+/// How long the server waits for the client to complete the handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+/// The maximum number of handshakes that the server tracks at once
+const MAX_PENDING: usize = 256;
+const RANDOM_LEN: usize = 32;
+/// session id, the RESET marker and the counter of the packet that
+/// prompted it, followed by an HMAC of those
+const RESET_LEN: usize = HEADER_LEN + 32;
+
+const CLIENT_TO_SERVER: u8 = 0;
+const SERVER_TO_CLIENT: u8 = 1;
+/// Used in place of the direction in the header of a RESET
+const RESET: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Kind {
+    Hello = 1,
+    Data = 2,
+    Ack = 3,
+    Close = 4,
+}
+
+impl Kind {
+    fn from_u8(kind: u8) -> Option<Self> {
+        match kind {
+            1 => Some(Self::Hello),
+            2 => Some(Self::Data),
+            3 => Some(Self::Ack),
+            4 => Some(Self::Close),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Packet {
+    kind: Kind,
+    /// The sequence number of the next segment that the sender
+    /// expects to receive; everything prior to it has been received
+    ack: u64,
+    seq: u64,
+    payload: Vec<u8>,
+}
+
+/// The pre-shared key used to authenticate and encrypt sessions
+#[derive(Clone)]
+pub struct UdpKey([u8; 32]);
+
+impl std::fmt::Debug for UdpKey {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.write_str("UdpKey")
+    }
+}
+
+impl UdpKey {
+    /// Load a key from a file containing 64 hex digits,
+    /// such as is produced by `openssl rand -hex 32`
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("reading key file {}", path.display()))?;
+        Self::from_hex(text.trim()).with_context(|| {
+            format!(
+                "{} must contain a 64 digit hex encoded key, \
+                 such as is produced by `openssl rand -hex 32`",
+                path.display()
+            )
+        })
+    }
+
+    fn from_hex(text: &str) -> anyhow::Result<Self> {
+        if text.len() != 64 || !text.is_ascii() {
+            bail!("expected 64 hex digits");
+        }
+        let mut key = [0u8; 32];
+        for (idx, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&text[idx * 2..idx * 2 + 2], 16)
+                .map_err(|_| anyhow!("invalid hex digit"))?;
+        }
+        Ok(Self(key))
+    }
+
+    fn hmac(&self, parts: &[&[u8]]) -> Result<[u8; 32], ErrorStack> {
+        let pkey = PKey::hmac(&self.0)?;
+        let mut signer = Signer::new(MessageDigest::sha256(), &pkey)?;
+        for part in parts {
+            signer.update(part)?;
+        }
+        let mut mac = [0u8; 32];
+        mac.copy_from_slice(&signer.sign_to_vec()?);
+        Ok(mac)
+    }
+
+    /// The key used to seal the HELLO exchange for session `id`
+    fn hello_key(&self, id: u64) -> Result<[u8; 32], ErrorStack> {
+        self.hmac(&[b"wezterm-udp-hello", &id.to_be_bytes()])
+    }
+
+    /// The key used to seal the session `id`, given the random value
+    /// that the server chose for it
+    fn session_key(&self, id: u64, server_random: &[u8]) -> Result<[u8; 32], ErrorStack> {
+        self.hmac(&[b"wezterm-udp-session", &id.to_be_bytes(), server_random])
+    }
+
+    /// Produces a RESET in response to a datagram whose header is `header`
+    fn reset(&self, header: &[u8]) -> Result<Vec<u8>, ErrorStack> {
+        let mut datagram = header[..HEADER_LEN].to_vec();
+        datagram[8] = RESET;
+        let mac = self.hmac(&[b"wezterm-udp-reset", &datagram])?;
+        datagram.extend_from_slice(&mac);
+        Ok(datagram)
+    }
+
+    /// If `datagram` is an authentic RESET, returns the session id and
+    /// the counter of the packet that prompted it
+    fn open_reset(&self, datagram: &[u8]) -> Option<(u64, u64)> {
+        if datagram.len() != RESET_LEN || datagram[8] != RESET {
+            return None;
+        }
+        let (header, mac) = datagram.split_at(HEADER_LEN);
+        let expected = self.hmac(&[b"wezterm-udp-reset", header]).ok()?;
+        if !openssl::memcmp::eq(&expected, mac) {
+            return None;
+        }
+        let counter = u64::from_be_bytes(header[9..17].try_into().ok()?);
+        Some((session_id(header)?, counter))
+    }
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], ErrorStack> {
+    let mut bytes = [0u8; N];
+    openssl::rand::rand_bytes(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn nonce(direction: u8, counter: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[0] = direction;
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+fn session_id(datagram: &[u8]) -> Option<u64> {
+    Some(u64::from_be_bytes(datagram.get(0..8)?.try_into().ok()?))
+}
+
+fn hello_payload() -> Vec<u8> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    now.to_be_bytes().to_vec()
+}
+
+fn hello_is_recent(payload: &[u8]) -> bool {
+    let sent = match payload.try_into() {
+        Ok(sent) => u64::from_be_bytes(sent),
+        Err(_) => return false,
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    now.max(sent) - now.min(sent) <= MAX_HELLO_AGE.as_secs()
+}
+
+#[derive(Debug)]
+struct Segment {
+    seq: u64,
+    data: Vec<u8>,
+    sent: Option<Instant>,
+    retransmitted: bool,
+}
+
+/// The protocol state for one end of a session.
+/// This performs no I/O of its own.
+struct Session {
+    id: u64,
+    key: [u8; 32],
+    send_direction: u8,
+    send_counter: u64,
+    /// The highest packet counter that we have authenticated
+    recv_counter: Option<u64>,
+    next_seq: u64,
+    unacked: VecDeque<Segment>,
+    recv_next: u64,
+    out_of_order: BTreeMap<u64, Vec<u8>>,
+    /// Data received in order that has yet to be delivered
+    /// to the application
+    inbound: Vec<u8>,
+    need_ack: bool,
+    srtt: Option<Duration>,
+    rto: Duration,
+    last_sent: Instant,
+    last_received: Instant,
+    closed: bool,
+}
+
+impl Session {
+    fn new(key: [u8; 32], id: u64, is_server: bool) -> Self {
+        let now = Instant::now();
+        Self {
+            id,
+            key,
+            send_direction: if is_server {
+                SERVER_TO_CLIENT
+            } else {
+                CLIENT_TO_SERVER
+            },
+            send_counter: 0,
+            recv_counter: None,
+            next_seq: 0,
+            unacked: VecDeque::new(),
+            recv_next: 0,
+            out_of_order: BTreeMap::new(),
+            inbound: vec![],
+            need_ack: false,
+            srtt: None,
+            rto: INITIAL_RTO,
+            last_sent: now,
+            last_received: now,
+            closed: false,
+        }
+    }
+
+    fn recv_direction(&self) -> u8 {
+        self.send_direction ^ 1
+    }
+
+    fn seal(&mut self, kind: Kind, seq: u64, payload: &[u8]) -> Result<Vec<u8>, ErrorStack> {
+        let counter = self.send_counter;
+        self.send_counter += 1;
+
+        let mut datagram = Vec::with_capacity(HEADER_LEN + BODY_LEN + payload.len() + TAG_LEN);
+        datagram.extend_from_slice(&self.id.to_be_bytes());
+        datagram.push(self.send_direction);
+        datagram.extend_from_slice(&counter.to_be_bytes());
+
+        let mut body = Vec::with_capacity(BODY_LEN + payload.len());
+        body.push(kind as u8);
+        body.extend_from_slice(&self.recv_next.to_be_bytes());
+        body.extend_from_slice(&seq.to_be_bytes());
+        body.extend_from_slice(payload);
+
+        let mut tag = [0u8; TAG_LEN];
+        let ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            &self.key,
+            Some(&nonce(self.send_direction, counter)),
+            &datagram,
+            &body,
+            &mut tag,
+        )?;
+        datagram.extend_from_slice(&ciphertext);
+        datagram.extend_from_slice(&tag);
+        Ok(datagram)
+    }
+
+    /// Authenticate and decrypt a datagram, returning its packet
+    /// counter and contents
+    fn open(&self, datagram: &[u8]) -> Option<(u64, Packet)> {
+        if datagram.len() < HEADER_LEN + BODY_LEN + TAG_LEN {
+            return None;
+        }
+        let (header, rest) = datagram.split_at(HEADER_LEN);
+        if session_id(header)? != self.id || header[8] != self.recv_direction() {
+            return None;
+        }
+        let counter = u64::from_be_bytes(header[9..17].try_into().ok()?);
+        let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+        let body = decrypt_aead(
+            Cipher::aes_256_gcm(),
+            &self.key,
+            Some(&nonce(header[8], counter)),
+            header,
+            ciphertext,
+            tag,
+        )
+        .ok()?;
+
+        Some((
+            counter,
+            Packet {
+                kind: Kind::from_u8(body[0])?,
+                ack: u64::from_be_bytes(body[1..9].try_into().ok()?),
+                seq: u64::from_be_bytes(body[9..17].try_into().ok()?),
+                payload: body[17..].to_vec(),
+            },
+        ))
+    }
+
+    fn can_queue(&self) -> bool {
+        self.unacked.len() < MAX_IN_FLIGHT
+    }
+
+    fn queue(&mut self, data: &[u8]) {
+        for chunk in data.chunks(MAX_SEGMENT) {
+            self.unacked.push_back(Segment {
+                seq: self.next_seq,
+                data: chunk.to_vec(),
+                sent: None,
+                retransmitted: false,
+            });
+            self.next_seq += 1;
+        }
+    }
+
+    fn rtt_sample(&mut self, sample: Duration) {
+        let srtt = match self.srtt {
+            Some(srtt) => (srtt * 7 + sample) / 8,
+            None => sample,
+        };
+        self.srtt = Some(srtt);
+        self.rto = (srtt * 2).clamp(MIN_RTO, MAX_RTO);
+    }
+
+    /// Process an authenticated packet.
+    /// Returns true if it is the most recent packet that we have
+    /// seen from the peer, in which case the caller should treat its
+    /// source address as the current address of the peer.
+    fn receive(&mut self, counter: u64, packet: Packet, now: Instant) -> bool {
+        self.last_received = now;
+        let fresh = match self.recv_counter {
+            Some(c) => counter > c,
+            None => true,
+        };
+        if fresh {
+            self.recv_counter = Some(counter);
+        }
+
+        if packet.ack <= self.next_seq {
+            while let Some(seg) = self.unacked.front() {
+                if seg.seq >= packet.ack {
+                    break;
+                }
+                if let Some(seg) = self.unacked.pop_front() {
+                    // Karn's algorithm: only sample segments that
+                    // were not retransmitted
+                    if let (Some(sent), false) = (seg.sent, seg.retransmitted) {
+                        self.rtt_sample(now.saturating_duration_since(sent));
+                    }
+                }
+            }
+        }
+
+        match packet.kind {
+            Kind::Hello => self.need_ack = true,
+            Kind::Ack => {}
+            Kind::Close => self.closed = true,
+            Kind::Data => {
+                self.need_ack = true;
+                if packet.seq == self.recv_next {
+                    // If the application isn't keeping up, drop the
+                    // segment without acknowledging it; the peer will
+                    // send it again later
+                    if self.inbound.len() < MAX_INBOUND {
+                        self.inbound.extend_from_slice(&packet.payload);
+                        self.recv_next += 1;
+                        while let Some(data) = self.out_of_order.remove(&self.recv_next) {
+                            self.inbound.extend_from_slice(&data);
+                            self.recv_next += 1;
+                        }
+                    }
+                } else if packet.seq > self.recv_next
+                    && packet.seq < self.recv_next + MAX_IN_FLIGHT as u64
+                {
+                    self.out_of_order.insert(packet.seq, packet.payload);
+                }
+            }
+        }
+
+        fresh
+    }
+
+    /// Returns the datagrams that should be sent now
+    fn poll_transmit(&mut self, now: Instant) -> Result<Vec<Vec<u8>>, ErrorStack> {
+        let mut datagrams = vec![];
+        let mut timed_out = false;
+
+        for idx in 0..self.unacked.len() {
+            let seg = &mut self.unacked[idx];
+            let due = match seg.sent {
+                None => true,
+                Some(sent) => now.saturating_duration_since(sent) >= self.rto,
+            };
+            if !due {
+                continue;
+            }
+            if seg.sent.is_some() {
+                seg.retransmitted = true;
+                timed_out = true;
+            }
+            seg.sent = Some(now);
+            let (seq, data) = (seg.seq, seg.data.clone());
+            datagrams.push(self.seal(Kind::Data, seq, &data)?);
+        }
+
+        if timed_out {
+            self.rto = (self.rto * 2).min(MAX_RTO);
+        }
+
+        if datagrams.is_empty()
+            && (self.need_ack
+                || now.saturating_duration_since(self.last_sent) >= KEEPALIVE_INTERVAL)
+        {
+            datagrams.push(self.seal(Kind::Ack, self.next_seq, &[])?);
+        }
+
+        if !datagrams.is_empty() {
+            self.need_ack = false;
+            self.last_sent = now;
+        }
+        Ok(datagrams)
+    }
+
+    /// Returns the time at which poll_transmit next needs to be called
+    fn next_deadline(&self, now: Instant) -> Instant {
+        if self.need_ack {
+            return now;
+        }
+        let mut deadline = self.last_sent + KEEPALIVE_INTERVAL;
+        for seg in &self.unacked {
+            deadline = deadline.min(seg.sent.map(|sent| sent + self.rto).unwrap_or(now));
+        }
+        deadline
+    }
+}
+
+/// The application side of a UDP session
+#[derive(Debug)]
+pub struct UdpStream {
+    fd: FileDescriptor,
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for UdpStream {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl std::os::windows::io::AsRawSocket for UdpStream {
+    fn as_raw_socket(&self) -> std::os::windows::io::RawSocket {
+        self.fd.as_raw_socket()
+    }
+}
+
+impl AsRawDesc for UdpStream {}
+
+impl Read for UdpStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.fd.read(buf)
+    }
+}
+
+impl Write for UdpStream {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.fd.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.fd.flush()
+    }
+}
+
+struct Endpoint {
+    session: Session,
+    /// Our end of the socketpair that is connected to the UdpStream
+    local: FileDescriptor,
+    peer: SocketAddr,
+}
+
+/// A session whose HELLO the server has answered, but for which the
+/// client has yet to prove that it derived the session key
+struct Pending {
+    server_random: [u8; RANDOM_LEN],
+    session: Session,
+    started: Instant,
+}
+
+/// Moves data between the UDP socket and the sessions that are using it
+struct Driver {
+    socket: UdpSocket,
+    psk: UdpKey,
+    is_server: bool,
+    idle_timeout: Duration,
+    endpoints: HashMap<u64, Endpoint>,
+    pending: HashMap<u64, Pending>,
+}
+
+impl Driver {
+    fn run(&mut self, accept: &mut dyn FnMut(UdpStream)) -> anyhow::Result<()> {
+        self.socket.set_nonblocking(true)?;
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+
+        loop {
+            if !self.is_server && self.endpoints.is_empty() {
+                return Ok(());
+            }
+
+            let now = Instant::now();
+            self.transmit(now);
+            self.expire(now);
+
+            let mut deadline = now + Duration::from_secs(1);
+            let mut ids = vec![];
+            let mut pfd = vec![pollfd {
+                fd: self.socket.as_socket_descriptor(),
+                events: POLLIN,
+                revents: 0,
+            }];
+            for (id, ep) in &self.endpoints {
+                deadline = deadline.min(ep.session.next_deadline(now));
+                let mut events = 0;
+                if ep.session.can_queue() {
+                    events |= POLLIN;
+                }
+                if !ep.session.inbound.is_empty() {
+                    events |= POLLOUT;
+                }
+                if events != 0 {
+                    ids.push(*id);
+                    pfd.push(pollfd {
+                        fd: ep.local.as_socket_descriptor(),
+                        events,
+                        revents: 0,
+                    });
+                }
+            }
+
+            poll(&mut pfd, Some(deadline.saturating_duration_since(now)))?;
+
+            if pfd[0].revents != 0 {
+                self.receive_datagrams(&mut buf, accept)?;
+            }
+            for (id, p) in ids.iter().zip(&pfd[1..]) {
+                if p.revents != 0 {
+                    self.service_local(*id);
+                }
+            }
+        }
+    }
+
+    fn send(&self, datagram: &[u8], peer: SocketAddr) {
+        // Errors are expected while the network is changing, for
+        // example when a laptop wakes from sleep, so we keep trying
+        // until the session times out
+        if let Err(err) = self.socket.send_to(datagram, peer) {
+            log::debug!("udp send to {}: {:#}", peer, err);
+        }
+    }
+
+    fn transmit(&mut self, now: Instant) {
+        let mut outgoing = vec![];
+        for ep in self.endpoints.values_mut() {
+            match ep.session.poll_transmit(now) {
+                Ok(datagrams) => {
+                    outgoing.extend(datagrams.into_iter().map(|datagram| (datagram, ep.peer)))
+                }
+                Err(err) => {
+                    log::error!("udp session {:016x}: {:#}", ep.session.id, err);
+                    ep.session.closed = true;
+                }
+            }
+        }
+        for (datagram, peer) in outgoing {
+            self.send(&datagram, peer);
+        }
+    }
+
+    fn expire(&mut self, now: Instant) {
+        self.pending
+            .retain(|_, p| now.saturating_duration_since(p.started) < HANDSHAKE_TIMEOUT);
+        let idle_timeout = self.idle_timeout;
+        self.endpoints.retain(|id, ep| {
+            if ep.session.closed {
+                log::info!("udp session {:016x} closed by peer", id);
+                return false;
+            }
+            if now.saturating_duration_since(ep.session.last_received) > idle_timeout {
+                log::warn!(
+                    "udp session {:016x}: nothing heard from {} for {:?}, giving up",
+                    id,
+                    ep.peer,
+                    idle_timeout
+                );
+                return false;
+            }
+            true
+        });
+    }
+
+    fn close(&mut self, id: u64) {
+        if let Some(mut ep) = self.endpoints.remove(&id) {
+            if let Ok(datagram) = ep.session.seal(Kind::Close, ep.session.next_seq, &[]) {
+                self.send(&datagram, ep.peer);
+            }
+        }
+    }
+
+    fn service_local(&mut self, id: u64) {
+        let ep = match self.endpoints.get_mut(&id) {
+            Some(ep) => ep,
+            None => return,
+        };
+        let mut closed = false;
+
+        while !ep.session.inbound.is_empty() {
+            match ep.local.write(&ep.session.inbound) {
+                Ok(0) => {
+                    closed = true;
+                    break;
+                }
+                Ok(n) => {
+                    ep.session.inbound.drain(..n);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    closed = true;
+                    break;
+                }
+            }
+        }
+
+        let mut buf = [0u8; 16 * 1024];
+        while !closed && ep.session.can_queue() {
+            match ep.local.read(&mut buf) {
+                Ok(0) => closed = true,
+                Ok(n) => ep.session.queue(&buf[..n]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => closed = true,
+            }
+        }
+
+        if closed {
+            self.close(id);
+        }
+    }
+
+    fn receive_datagrams(
+        &mut self,
+        buf: &mut [u8],
+        accept: &mut dyn FnMut(UdpStream),
+    ) -> anyhow::Result<()> {
+        loop {
+            let (len, from) = match self.socket.recv_from(buf) {
+                Ok(result) => result,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => {
+                    // Some systems report ICMP errors from earlier sends
+                    // here; they are not fatal to the socket
+                    log::debug!("udp recv: {:#}", err);
+                    return Ok(());
+                }
+            };
+            let datagram = &buf[..len];
+            let id = match session_id(datagram) {
+                Some(id) => id,
+                None => continue,
+            };
+
+            if let Some(ep) = self.endpoints.get_mut(&id) {
+                if let Some((counter, packet)) = ep.session.open(datagram) {
+                    if ep.session.receive(counter, packet, Instant::now()) && ep.peer != from {
+                        log::info!("udp session {:016x} moved from {} to {}", id, ep.peer, from);
+                        ep.peer = from;
+                    }
+                } else if let Some((_, counter)) = self.psk.open_reset(datagram) {
+                    if !self.is_server && counter < ep.session.send_counter {
+                        log::warn!("udp session {:016x} is unknown to the server", id);
+                        ep.session.closed = true;
+                    }
+                }
+                continue;
+            }
+
+            if !self.is_server {
+                continue;
+            }
+
+            if let Some(pending) = self.pending.get_mut(&id) {
+                if let Some((counter, packet)) = pending.session.open(datagram) {
+                    // The client derived the same session key, so
+                    // the session can start
+                    if let Some(mut pending) = self.pending.remove(&id) {
+                        pending.session.receive(counter, packet, Instant::now());
+                        self.start_session(pending.session, from, accept)?;
+                    }
+                    continue;
+                }
+            }
+
+            let mut hello = Session::new(self.psk.hello_key(id)?, id, true);
+            match hello.open(datagram) {
+                Some((_, packet))
+                    if packet.kind == Kind::Hello && hello_is_recent(&packet.payload) =>
+                {
+                    let server_random = match self.pending.get(&id) {
+                        // The client didn't hear our reply; repeat it
+                        Some(pending) => pending.server_random,
+                        None => {
+                            if self.pending.len() >= MAX_PENDING {
+                                log::warn!("too many udp handshakes in progress");
+                                continue;
+                            }
+                            let server_random = random_bytes::<RANDOM_LEN>()?;
+                            let key = self.psk.session_key(id, &server_random)?;
+                            self.pending.insert(
+                                id,
+                                Pending {
+                                    server_random,
+                                    session: Session::new(key, id, true),
+                                    started: Instant::now(),
+                                },
+                            );
+                            server_random
+                        }
+                    };
+                    // Each reply uses a random counter, as the same
+                    // HELLO may be received more than once
+                    hello.send_counter = u64::from_be_bytes(random_bytes::<8>()?);
+                    let reply = hello.seal(Kind::Hello, 0, &server_random)?;
+                    self.send(&reply, from);
+                }
+                _ => {
+                    if datagram.len() >= HEADER_LEN + BODY_LEN + TAG_LEN
+                        && datagram[8] == CLIENT_TO_SERVER
+                    {
+                        // A session that we have forgotten about, perhaps
+                        // because it timed out or the server restarted,
+                        // or a HELLO that is too old.  Let the client know
+                        // so that it can reconnect.
+                        let reset = self.psk.reset(datagram)?;
+                        self.send(&reset, from);
+                    }
+                }
+            }
+        }
+    }
+
+    fn start_session(
+        &mut self,
+        session: Session,
+        peer: SocketAddr,
+        accept: &mut dyn FnMut(UdpStream),
+    ) -> anyhow::Result<()> {
+        let id = session.id;
+        let (mut local, remote) = socketpair()?;
+        local.set_non_blocking(true)?;
+        log::info!("udp session {:016x} started from {}", id, peer);
+        self.endpoints.insert(
+            id,
+            Endpoint {
+                session,
+                local,
+                peer,
+            },
+        );
+        accept(UdpStream { fd: remote });
+        Ok(())
+    }
+}
+
+/// Accepts UDP sessions from clients that know the pre-shared key
+pub struct UdpListener {
+    driver: Driver,
+}
+
+impl UdpListener {
+    pub fn bind(address: &str, psk: UdpKey, idle_timeout: Duration) -> anyhow::Result<Self> {
+        let socket =
+            UdpSocket::bind(address).with_context(|| format!("binding udp socket {}", address))?;
+        Ok(Self {
+            driver: Driver {
+                socket,
+                psk,
+                is_server: true,
+                idle_timeout,
+                endpoints: HashMap::new(),
+                pending: HashMap::new(),
+            },
+        })
+    }
+
+    /// Services sessions until an error occurs, calling `accept` with
+    /// the stream for each new session
+    pub fn run(mut self, mut accept: impl FnMut(UdpStream)) -> anyhow::Result<()> {
+        self.driver.run(&mut accept)
+    }
+}
+
+/// Establish a session with the UdpListener at `address`.
+/// A background thread maintains the session until either side
+/// closes it, or nothing is heard from the server for `idle_timeout`.
+pub fn udp_connect(
+    address: &str,
+    psk: &UdpKey,
+    connect_timeout: Duration,
+    idle_timeout: Duration,
+) -> anyhow::Result<UdpStream> {
+    let peer = address
+        .to_socket_addrs()
+        .with_context(|| format!("resolving {}", address))?
+        .next()
+        .ok_or_else(|| anyhow!("{} did not resolve to any address", address))?;
+    // Deliberately not connected to the peer, so that the system
+    // is free to pick a new source address when the network changes
+    let socket = if peer.is_ipv4() {
+        UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+    } else {
+        UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))
+    }
+    .context("binding udp socket")?;
+
+    let id = u64::from_be_bytes(random_bytes::<8>()?);
+    let mut hello = Session::new(psk.hello_key(id)?, id, false);
+
+    let deadline = Instant::now() + connect_timeout;
+    let mut buf = vec![0u8; MAX_DATAGRAM];
+    let server_random = loop {
+        let now = Instant::now();
+        if now >= deadline {
+            bail!(
+                "timed out waiting for {} to respond; \
+                 check that the server is running and that the keys match",
+                address
+            );
+        }
+        let datagram = hello.seal(Kind::Hello, 0, &hello_payload())?;
+        socket
+            .send_to(&datagram, peer)
+            .with_context(|| format!("sending to {}", address))?;
+
+        socket.set_read_timeout(Some(HELLO_INTERVAL.min(deadline - now)))?;
+        match socket.recv_from(&mut buf) {
+            Ok((len, _from)) => {
+                let datagram = &buf[..len];
+                if let Some((_, packet)) = hello.open(datagram) {
+                    if packet.kind == Kind::Hello && packet.payload.len() == RANDOM_LEN {
+                        let mut server_random = [0u8; RANDOM_LEN];
+                        server_random.copy_from_slice(&packet.payload);
+                        break server_random;
+                    }
+                } else if psk.open_reset(datagram).map(|(reset_id, _)| reset_id) == Some(id) {
+                    bail!(
+                        "{} refused the session; check that the clocks \
+                         of both machines are correct",
+                        address
+                    );
+                }
+            }
+            Err(err)
+                if err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::TimedOut => {}
+            Err(err) => return Err(err).with_context(|| format!("receiving from {}", address)),
+        }
+    };
+
+    let mut session = Session::new(psk.session_key(id, &server_random)?, id, false);
+    // The server starts the session once it hears from us using the
+    // session key, so acknowledge its reply right away
+    session.need_ack = true;
+
+    let (mut local, remote) = socketpair()?;
+    local.set_non_blocking(true)?;
+
+    let mut driver = Driver {
+        socket,
+        psk: psk.clone(),
+        is_server: false,
+        idle_timeout,
+        endpoints: HashMap::new(),
+        pending: HashMap::new(),
+    };
+    driver.endpoints.insert(
+        id,
+        Endpoint {
+            session,
+            local,
+            peer,
+        },
+    );
+
+    let address = address.to_string();
+    std::thread::Builder::new()
+        .name(format!("udp {}", address))
+        .spawn(move || {
+            if let Err(err) = driver.run(&mut |_| {}) {
+                log::error!("udp session with {}: {:#}", address, err);
+            }
+        })?;
+
+    Ok(UdpStream { fd: remote })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pair() -> (Session, Session) {
+        let key = UdpKey([7u8; 32])
+            .session_key(42, &[1u8; RANDOM_LEN])
+            .unwrap();
+        (Session::new(key, 42, false), Session::new(key, 42, true))
+    }
+
+    fn server(psk: &UdpKey) -> Driver {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_nonblocking(true).unwrap();
+        Driver {
+            socket,
+            psk: psk.clone(),
+            is_server: true,
+            idle_timeout: Duration::from_secs(60),
+            endpoints: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Sends `datagram` to the server, lets it process it and
+    /// returns its reply, if any
+    fn exchange(
+        client: &UdpSocket,
+        server: &mut Driver,
+        datagram: &[u8],
+        accepted: &mut usize,
+    ) -> Option<Vec<u8>> {
+        client
+            .send_to(datagram, server.socket.local_addr().unwrap())
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+        server
+            .receive_datagrams(&mut buf, &mut |_| *accepted += 1)
+            .unwrap();
+        match client.recv_from(&mut buf) {
+            Ok((len, _)) => Some(buf[..len].to_vec()),
+            Err(_) => None,
+        }
+    }
+
+    fn deliver(from: &mut Session, to: &mut Session, now: Instant) {
+        for datagram in from.poll_transmit(now).unwrap() {
+            let (counter, packet) = to.open(&datagram).unwrap();
+            to.receive(counter, packet, now);
+        }
+    }
+
+    #[test]
+    fn key_from_hex() {
+        assert!(UdpKey::from_hex(&"ab".repeat(32)).is_ok());
+        assert!(UdpKey::from_hex("abcd").is_err());
+        assert!(UdpKey::from_hex(&"zz".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn tampered_datagrams_are_rejected() {
+        let (mut client, server) = pair();
+        client.queue(b"hello");
+        let mut datagram = client.poll_transmit(Instant::now()).unwrap().remove(0);
+        assert!(server.open(&datagram).is_some());
+
+        let last = datagram.len() - 1;
+        datagram[last] ^= 1;
+        assert!(server.open(&datagram).is_none());
+
+        // A datagram is not accepted by the endpoint that sent it
+        let (mut client, _server) = pair();
+        client.queue(b"hello");
+        let datagram = client.poll_transmit(Instant::now()).unwrap().remove(0);
+        assert!(client.open(&datagram).is_none());
+    }
+
+    #[test]
+    fn retransmits_lost_segments() {
+        let (mut client, mut server) = pair();
+        let now = Instant::now();
+        let data = vec![b'x'; MAX_SEGMENT * 2 + 10];
+        client.queue(&data);
+
+        // Lose the first segment
+        let mut datagrams = client.poll_transmit(now).unwrap();
+        assert_eq!(datagrams.len(), 3);
+        datagrams.remove(0);
+        for datagram in datagrams {
+            let (counter, packet) = server.open(&datagram).unwrap();
+            server.receive(counter, packet, now);
+        }
+        assert!(server.inbound.is_empty());
+        deliver(&mut server, &mut client, now);
+        assert_eq!(client.unacked.len(), 3);
+
+        // Nothing is resent until the retransmission timeout elapses
+        assert!(client.poll_transmit(now).unwrap().is_empty());
+        let later = now + INITIAL_RTO;
+        deliver(&mut client, &mut server, later);
+        assert_eq!(server.inbound, data);
+
+        deliver(&mut server, &mut client, later);
+        assert!(client.unacked.is_empty());
+    }
+
+    #[test]
+    fn replayed_hello_gets_a_fresh_session_key() {
+        let psk = UdpKey([9u8; 32]);
+        let mut server = server(&psk);
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        let mut accepted = 0;
+
+        let id = 1234;
+        let mut hello = Session::new(psk.hello_key(id).unwrap(), id, false);
+        let hello_datagram = hello.seal(Kind::Hello, 0, &hello_payload()).unwrap();
+
+        let server_random = |reply: Vec<u8>| {
+            let (_, packet) = hello.open(&reply).unwrap();
+            assert_eq!(packet.kind, Kind::Hello);
+            packet.payload
+        };
+        let reply = exchange(&client, &mut server, &hello_datagram, &mut accepted).unwrap();
+        let first = server_random(reply);
+
+        // A retransmitted HELLO gets the same random value, but
+        // the session doesn't start until the client proves that
+        // it has the session key
+        let reply = exchange(&client, &mut server, &hello_datagram, &mut accepted).unwrap();
+        assert_eq!(server_random(reply), first);
+        assert_eq!(accepted, 0);
+
+        // Once the handshake has been forgotten, a replay of the
+        // same HELLO results in a different session key
+        server.pending.clear();
+        let reply = exchange(&client, &mut server, &hello_datagram, &mut accepted).unwrap();
+        let second = server_random(reply);
+        assert_ne!(first, second);
+        assert_ne!(
+            psk.session_key(id, &first).unwrap(),
+            psk.session_key(id, &second).unwrap()
+        );
+
+        // A client with the old key can't complete the handshake,
+        // and is told that the session is unknown
+        let mut stale = Session::new(psk.session_key(id, &first).unwrap(), id, false);
+        let ack = stale.seal(Kind::Ack, 0, &[]).unwrap();
+        let reply = exchange(&client, &mut server, &ack, &mut accepted).unwrap();
+        assert_eq!(psk.open_reset(&reply), Some((id, 0)));
+        assert_eq!(accepted, 0);
+
+        let mut session = Session::new(psk.session_key(id, &second).unwrap(), id, false);
+        let ack = session.seal(Kind::Ack, 0, &[]).unwrap();
+        assert!(exchange(&client, &mut server, &ack, &mut accepted).is_none());
+        assert_eq!(accepted, 1);
+        assert!(server.endpoints.contains_key(&id));
+        assert!(server.pending.is_empty());
+    }
+
+    #[test]
+    fn reset_is_authenticated() {
+        let psk = UdpKey([9u8; 32]);
+        let (mut client, _server) = pair();
+        let datagram = client.seal(Kind::Ack, 0, &[]).unwrap();
+        let mut reset = psk.reset(&datagram).unwrap();
+        assert_eq!(psk.open_reset(&reset), Some((42, 0)));
+        assert_eq!(UdpKey([8u8; 32]).open_reset(&reset), None);
+        reset[10] ^= 1;
+        assert_eq!(psk.open_reset(&reset), None);
+    }
+
+    #[test]
+    fn hello_age() {
+        assert!(hello_is_recent(&hello_payload()));
+        assert!(!hello_is_recent(&1u64.to_be_bytes()));
+        assert!(!hello_is_recent(b"short"));
+    }
+}
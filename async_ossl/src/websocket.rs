@@ -0,0 +1,337 @@
+use crate::AsRawDesc;
+use filedescriptor::{
+    poll, pollfd, socketpair, AsRawSocketDescriptor, FileDescriptor, POLLIN, POLLOUT,
+};
+use openssl::ssl::SslStream;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use tungstenite::{Message, WebSocket};
+
+/// The stream beneath a websocket; either a plain tcp connection
+/// or a TLS session on top of one.
+#[derive(Debug)]
+pub enum WebSocketTransport {
+    Plain(TcpStream),
+    Tls(SslStream<TcpStream>),
+}
+
+impl WebSocketTransport {
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            Self::Plain(s) => s,
+            Self::Tls(s) => s.get_ref(),
+        }
+    }
+}
+
+impl Read for WebSocketTransport {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        match self {
+            Self::Plain(s) => s.read(buf),
+            Self::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for WebSocketTransport {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        match self {
+            Self::Plain(s) => s.write(buf),
+            Self::Tls(s) => s.write(buf),
+        }
+    }
+    fn flush(&mut self) -> Result<(), Error> {
+        match self {
+            Self::Plain(s) => s.flush(),
+            Self::Tls(s) => s.flush(),
+        }
+    }
+}
+
+/// Adapts an established websocket into a byte stream so that it
+/// can carry the mux protocol in the same way as the other transports.
+/// Each write is sent as a single binary message, and the payload of
+/// received binary messages is returned by subsequent reads.
+///
+/// The websocket is serviced by a relay thread and the application
+/// side is one end of a socketpair.  tungstenite and openssl can both
+/// hold decoded data that is no longer reflected in the readiness of
+/// the tcp socket; the relay keeps decoding until the websocket would
+/// block, so that the readiness of the socketpair is accurate.
+#[derive(Debug)]
+pub struct AsyncWebSocketStream {
+    fd: FileDescriptor,
+}
+
+impl AsyncWebSocketStream {
+    pub fn new(ws: WebSocket<WebSocketTransport>) -> anyhow::Result<Self> {
+        ws.get_ref().tcp().set_nonblocking(true)?;
+        let (mut local, remote) = socketpair()?;
+        local.set_non_blocking(true)?;
+
+        let peer = ws
+            .get_ref()
+            .tcp()
+            .peer_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_else(|_| "?".to_string());
+
+        let relay = Relay {
+            ws,
+            local,
+            inbound: vec![],
+            drained: false,
+            flushing: false,
+        };
+        std::thread::Builder::new()
+            .name(format!("websocket {}", peer))
+            .spawn(move || relay.run(&peer))?;
+
+        Ok(Self { fd: remote })
+    }
+}
+
+/// Limits how much received payload we buffer while the application
+/// is not reading it
+const MAX_INBOUND: usize = 1024 * 1024;
+
+struct Relay {
+    ws: WebSocket<WebSocketTransport>,
+    /// Our end of the socketpair that is connected to the AsyncWebSocketStream
+    local: FileDescriptor,
+    /// Received payload that has yet to be written to `local`
+    inbound: Vec<u8>,
+    /// true when the websocket has no more decodable data
+    drained: bool,
+    /// true when the websocket has queued data that it could not send yet
+    flushing: bool,
+}
+
+fn would_block(err: &tungstenite::Error) -> bool {
+    matches!(err, tungstenite::Error::Io(err) if err.kind() == ErrorKind::WouldBlock)
+}
+
+impl Relay {
+    fn run(mut self, peer: &str) {
+        if let Err(err) = self.relay() {
+            log::error!("websocket session with {}: {:#}", peer, err);
+        }
+        self.ws.close(None).ok();
+        self.ws.write_pending().ok();
+    }
+
+    fn relay(&mut self) -> anyhow::Result<()> {
+        loop {
+            if self.flushing {
+                self.flush()?;
+            }
+            if !self.receive()? || !self.service_local()? {
+                return Ok(());
+            }
+
+            // Only wait for the socket once we've decoded everything
+            // that was already buffered
+            if !self.drained && self.inbound.len() < MAX_INBOUND {
+                continue;
+            }
+
+            let mut tcp_events = 0;
+            if self.inbound.len() < MAX_INBOUND {
+                tcp_events |= POLLIN;
+            }
+            if self.flushing {
+                tcp_events |= POLLOUT;
+            }
+            let mut local_events = 0;
+            if !self.flushing {
+                local_events |= POLLIN;
+            }
+            if !self.inbound.is_empty() {
+                local_events |= POLLOUT;
+            }
+
+            let mut pfd = [
+                pollfd {
+                    fd: self.ws.get_ref().tcp().as_socket_descriptor(),
+                    events: tcp_events,
+                    revents: 0,
+                },
+                pollfd {
+                    fd: self.local.as_socket_descriptor(),
+                    events: local_events,
+                    revents: 0,
+                },
+            ];
+            poll(&mut pfd, None)?;
+            self.drained = false;
+        }
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        match self.ws.write_pending() {
+            Ok(()) => self.flushing = false,
+            Err(err) if would_block(&err) => self.flushing = true,
+            Err(err) => return Err(err.into()),
+        }
+        Ok(())
+    }
+
+    /// Decodes received messages until the websocket would block.
+    /// Returns false once the websocket has been closed.
+    fn receive(&mut self) -> anyhow::Result<bool> {
+        while self.inbound.len() < MAX_INBOUND {
+            match self.ws.read_message() {
+                Ok(Message::Binary(data)) => self.inbound.extend_from_slice(&data),
+                Ok(Message::Close(_)) => return Ok(false),
+                // Pings are answered by tungstenite; there is nothing
+                // for us to do with any of these other message types
+                Ok(Message::Text(_) | Message::Ping(_) | Message::Pong(_) | Message::Frame(_)) => {}
+                Err(err) if would_block(&err) => {
+                    self.drained = true;
+                    break;
+                }
+                Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                    return Ok(false)
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        // Reading may have queued a pong
+        self.flush()?;
+        Ok(true)
+    }
+
+    /// Moves data between the socketpair and the websocket.
+    /// Returns false once the application side has been closed.
+    fn service_local(&mut self) -> anyhow::Result<bool> {
+        while !self.inbound.is_empty() {
+            match self.local.write(&self.inbound) {
+                Ok(0) => return Ok(false),
+                Ok(n) => {
+                    self.inbound.drain(..n);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => return Ok(false),
+            }
+        }
+
+        let mut buf = [0u8; 16 * 1024];
+        // Don't read more from the application until the websocket
+        // has sent what it has already queued
+        while !self.flushing {
+            match self.local.read(&mut buf) {
+                Ok(0) => return Ok(false),
+                Ok(n) => match self.ws.write_message(Message::Binary(buf[..n].to_vec())) {
+                    Ok(()) => {}
+                    // The message was queued but could not be sent in
+                    // its entirety yet; it will be sent by a later flush
+                    Err(err) if would_block(&err) => self.flushing = true,
+                    Err(err) => return Err(err.into()),
+                },
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for AsyncWebSocketStream {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl std::os::windows::io::AsRawSocket for AsyncWebSocketStream {
+    fn as_raw_socket(&self) -> std::os::windows::io::RawSocket {
+        self.fd.as_raw_socket()
+    }
+}
+
+impl AsRawDesc for AsyncWebSocketStream {}
+
+impl Read for AsyncWebSocketStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.fd.read(buf)
+    }
+}
+
+impl Write for AsyncWebSocketStream {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.fd.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.fd.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::TcpListener;
+    use std::time::Duration;
+    use tungstenite::protocol::Role;
+
+    fn pair() -> (WebSocket<TcpStream>, AsyncWebSocketStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let server = WebSocket::from_raw_socket(server, Role::Server, None);
+        let client =
+            WebSocket::from_raw_socket(WebSocketTransport::Plain(client), Role::Client, None);
+        (server, AsyncWebSocketStream::new(client).unwrap())
+    }
+
+    fn read_exact_when_readable(stream: &mut AsyncWebSocketStream, len: usize) -> Vec<u8> {
+        stream.fd.set_non_blocking(true).unwrap();
+        let mut result = vec![];
+        while result.len() < len {
+            let mut pfd = [pollfd {
+                fd: stream.fd.as_socket_descriptor(),
+                events: POLLIN,
+                revents: 0,
+            }];
+            assert_eq!(
+                poll(&mut pfd, Some(Duration::from_secs(5))).unwrap(),
+                1,
+                "stalled with {:?} received",
+                result
+            );
+            let mut buf = [0u8; 64];
+            match stream.read(&mut buf) {
+                Ok(n) => result.extend_from_slice(&buf[..n]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(err) => panic!("{:#}", err),
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn buffered_messages_are_readable() {
+        let (mut server, mut client) = pair();
+        // These all arrive together, so only the first decode is
+        // prompted by the socket becoming readable
+        for chunk in [&b"one"[..], b"two", b"three"] {
+            server
+                .write_message(Message::Binary(chunk.to_vec()))
+                .unwrap();
+        }
+
+        assert_eq!(read_exact_when_readable(&mut client, 11), b"onetwothree");
+    }
+
+    #[test]
+    fn writes_are_sent_as_binary_messages() {
+        let (mut server, mut client) = pair();
+        client.write_all(b"hello").unwrap();
+
+        assert_eq!(
+            server.read_message().unwrap(),
+            Message::Binary(b"hello".to_vec())
+        );
+    }
+}
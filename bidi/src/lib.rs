@@ -48,6 +48,22 @@ impl ParagraphDirectionHint {
             }
         }
     }
+
+    /// Returns the direction of a paragraph comprised of `text`.
+    /// The auto variants take the direction of the first strong
+    /// directional character outside of any isolate (rules P2 and P3),
+    /// falling back to their own direction if there is none.
+    pub fn resolve_direction(self, text: impl IntoIterator<Item = char>) -> Direction {
+        match self {
+            ParagraphDirectionHint::LeftToRight | ParagraphDirectionHint::RightToLeft => {
+                self.direction()
+            }
+            ParagraphDirectionHint::AutoLeftToRight | ParagraphDirectionHint::AutoRightToLeft => {
+                let types: Vec<BidiClass> = text.into_iter().map(bidi_class_for_char).collect();
+                paragraph_level(&types, false, self.direction()).direction()
+            }
+        }
+    }
 }
 
 #[derive(Debug, Default)]
@@ -2043,6 +2059,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn resolve_direction() {
+        assert_eq!(
+            ParagraphDirectionHint::AutoLeftToRight.resolve_direction("123 שלום abc".chars()),
+            Direction::RightToLeft
+        );
+        assert_eq!(
+            ParagraphDirectionHint::AutoRightToLeft.resolve_direction("abc שלום".chars()),
+            Direction::LeftToRight
+        );
+        assert_eq!(
+            ParagraphDirectionHint::AutoRightToLeft.resolve_direction("123 ...".chars()),
+            Direction::RightToLeft
+        );
+        assert_eq!(
+            ParagraphDirectionHint::LeftToRight.resolve_direction("שלום".chars()),
+            Direction::LeftToRight
+        );
+    }
+
     #[test]
     fn mirror() {
         assert_eq!(lookup_closing('{'), Some(('}', BracketType::Open)));
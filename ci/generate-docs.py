@@ -345,6 +345,7 @@ TOC = [
                 "cli/general.md",
                 children=[
                     Gen("cli", "cli/cli"),
+                    Page("plugin", "cli/plugin.md"),
                     Page("show-keys", "cli/show-keys.md"),
                 ],
             ),
@@ -360,18 +361,34 @@ TOC = [
                         "module: wezterm.color",
                         "config/lua/wezterm.color",
                     ),
+                    Gen(
+                        "module: wezterm.fs",
+                        "config/lua/wezterm.fs",
+                    ),
                     Gen(
                         "module: wezterm.gui",
                         "config/lua/wezterm.gui",
                     ),
+                    Gen(
+                        "module: wezterm.http",
+                        "config/lua/wezterm.http",
+                    ),
                     Gen(
                         "module: wezterm.mux",
                         "config/lua/wezterm.mux",
                     ),
+                    Gen(
+                        "module: wezterm.plugin",
+                        "config/lua/wezterm.plugin",
+                    ),
                     Gen(
                         "module: wezterm.procinfo",
                         "config/lua/wezterm.procinfo",
                     ),
+                    Gen(
+                        "module: wezterm.secrets",
+                        "config/lua/wezterm.secrets",
+                    ),
                     Gen(
                         "module: wezterm.time",
                         "config/lua/wezterm.time",
@@ -392,6 +409,7 @@ TOC = [
                     Page("object: ExecDomain", "config/lua/ExecDomain.md"),
                     Page("object: LocalProcessInfo", "config/lua/LocalProcessInfo.md"),
                     Gen("object: MuxWindow", "config/lua/mux-window"),
+                    Gen("object: MuxDomain", "config/lua/MuxDomain"),
                     Gen("object: MuxTab", "config/lua/MuxTab"),
                     Page("object: PaneInformation", "config/lua/PaneInformation.md"),
                     Page("object: TabInformation", "config/lua/TabInformation.md"),
@@ -400,6 +418,10 @@ TOC = [
                     Gen("object: Time", "config/lua/wezterm.time/Time"),
                     Page("object: TlsDomainClient", "config/lua/TlsDomainClient.md"),
                     Page("object: TlsDomainServer", "config/lua/TlsDomainServer.md"),
+                    Page("object: UdpDomainClient", "config/lua/UdpDomainClient.md"),
+                    Page("object: UdpDomainServer", "config/lua/UdpDomainServer.md"),
+                    Page("object: WssDomainClient", "config/lua/WssDomainClient.md"),
+                    Page("object: WssDomainServer", "config/lua/WssDomainServer.md"),
                     Gen(
                         "object: Pane",
                         "config/lua/pane",
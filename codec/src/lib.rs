@@ -14,9 +14,10 @@
 use anyhow::{bail, Context as _, Error};
 use config::keyassignment::PaneDirection;
 use mux::client::{ClientId, ClientInfo};
+use mux::collab::{ClientCursor, PaneCollaborators};
 use mux::pane::PaneId;
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
-use mux::tab::{PaneNode, SerdeUrl, SplitRequest, TabId};
+use mux::tab::{PaneEntry, PaneNode, SerdeUrl, SplitRequest, TabId};
 use mux::window::WindowId;
 use portable_pty::CommandBuilder;
 use rangeset::*;
@@ -277,17 +278,58 @@ pub struct DecodedPdu {
 /// If the serialized size is larger than this, then we'll consider compressing it
 const COMPRESS_THRESH: usize = 32;
 
-fn serialize<T: serde::Serialize>(t: &T) -> Result<(Vec<u8>, bool), Error> {
+/// Controls how the payload of a PDU is compressed when it is encoded.
+/// The compressed bit in the frame header makes each frame self-describing,
+/// so the two peers are free to use different settings and the decoder
+/// doesn't need to know which one is in effect.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Compression {
+    /// Never compress payloads
+    Disabled,
+    /// Compress payloads larger than COMPRESS_THRESH using zstd
+    /// with the specified compression level
+    Zstd { level: i32 },
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self::Zstd {
+            level: zstd::DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+}
+
+impl Compression {
+    /// Computes the compression that the configuration asks for
+    pub fn from_config(config: &config::ConfigHandle) -> Self {
+        match config.mux_compression {
+            config::MuxCompression::None => Self::Disabled,
+            config::MuxCompression::Zstd => Self::Zstd {
+                level: config.mux_compression_level,
+            },
+        }
+    }
+}
+
+fn serialize<T: serde::Serialize>(
+    t: &T,
+    compression: Compression,
+) -> Result<(Vec<u8>, bool), Error> {
     let mut uncompressed = Vec::new();
     let mut encode = varbincode::Serializer::new(&mut uncompressed);
     t.serialize(&mut encode)?;
 
+    let level = match compression {
+        Compression::Disabled => return Ok((uncompressed, false)),
+        Compression::Zstd { level } => level,
+    };
+
     if uncompressed.len() <= COMPRESS_THRESH {
         return Ok((uncompressed, false));
     }
     // It's a little heavy; let's try compressing it
     let mut compressed = Vec::new();
-    let mut compress = zstd::Encoder::new(&mut compressed, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+    let mut compress = zstd::Encoder::new(&mut compressed, level)?;
     let mut encode = varbincode::Serializer::new(&mut compress);
     t.serialize(&mut encode)?;
     drop(encode);
@@ -332,11 +374,15 @@ macro_rules! pdu {
 
         impl Pdu {
             pub fn encode<W: std::io::Write>(&self, w: W, serial: u64) -> Result<(), Error> {
+                self.encode_with_compression(w, serial, Compression::default())
+            }
+
+            pub fn encode_with_compression<W: std::io::Write>(&self, w: W, serial: u64, compression: Compression) -> Result<(), Error> {
                 match self {
                     Pdu::Invalid{..} => bail!("attempted to serialize Pdu::Invalid"),
                     $(
                         Pdu::$name(s) => {
-                            let (data, is_compressed) = serialize(s)?;
+                            let (data, is_compressed) = serialize(s, compression)?;
                             let encoded_size = encode_raw($vers, serial, &data, is_compressed, w)?;
                             metrics::histogram!("pdu.size", encoded_size as f64, "pdu" => stringify!($name));
                             metrics::histogram!("pdu.size.rate", encoded_size as f64, "pdu" => stringify!($name));
@@ -347,11 +393,15 @@ macro_rules! pdu {
             }
 
             pub async fn encode_async<W: Unpin + AsyncWriteExt>(&self, w: &mut W, serial: u64) -> Result<(), Error> {
+                self.encode_async_with_compression(w, serial, Compression::default()).await
+            }
+
+            pub async fn encode_async_with_compression<W: Unpin + AsyncWriteExt>(&self, w: &mut W, serial: u64, compression: Compression) -> Result<(), Error> {
                 match self {
                     Pdu::Invalid{..} => bail!("attempted to serialize Pdu::Invalid"),
                     $(
                         Pdu::$name(s) => {
-                            let (data, is_compressed) = serialize(s)?;
+                            let (data, is_compressed) = serialize(s, compression)?;
                             let encoded_size = encode_raw_async($vers, serial, &data, is_compressed, w).await?;
                             metrics::histogram!("pdu.size", encoded_size as f64, "pdu" => stringify!($name));
                             metrics::histogram!("pdu.size.rate", encoded_size as f64, "pdu" => stringify!($name));
@@ -417,7 +467,7 @@ macro_rules! pdu {
 /// The overall version of the codec.
 /// This must be bumped when backwards incompatible changes
 /// are made to the types and protocol.
-pub const CODEC_VERSION: usize = 31;
+pub const CODEC_VERSION: usize = 49;
 
 // Defines the Pdu enum.
 // Each struct has an explicit identifying number.
@@ -466,6 +516,32 @@ pdu! {
     MovePaneToNewTab: 48,
     MovePaneToNewTabResponse: 49,
     ActivatePaneDirection: 50,
+    SetCompression: 51,
+    RotateTlsCreds: 52,
+    SpawnWorkspace: 53,
+    ListPaneEntries: 54,
+    ListPaneEntriesResponse: 55,
+    SubscribeToEvents: 56,
+    MuxEvent: 57,
+    SendPasteChunk: 58,
+    SendPasteChunkResponse: 59,
+    CaptureImage: 60,
+    CaptureImageResponse: 61,
+    InjectOutput: 62,
+    QueryClipboard: 63,
+    GetPaneMetrics: 64,
+    GetPaneMetricsResponse: 65,
+    ActivatePane: 66,
+    SpawnBatch: 67,
+    SpawnBatchResponse: 68,
+    ResizePane: 69,
+    MoveTabToDomain: 70,
+    MoveTabToDomainResponse: 71,
+    SetClientCursor: 72,
+    TogglePaneInputTurn: 73,
+    PaneCollaboratorsChanged: 74,
+    DetachClient: 75,
+    SetClientPermission: 76,
 }
 
 impl Pdu {
@@ -541,6 +617,8 @@ impl Pdu {
             | Pdu::SetPalette(SetPalette { pane_id, .. })
             | Pdu::NotifyAlert(NotifyAlert { pane_id, .. })
             | Pdu::SetClipboard(SetClipboard { pane_id, .. })
+            | Pdu::QueryClipboard(QueryClipboard { pane_id, .. })
+            | Pdu::PaneCollaboratorsChanged(PaneCollaboratorsChanged { pane_id, .. })
             | Pdu::PaneRemoved(PaneRemoved { pane_id }) => Some(*pane_id),
             _ => None,
         }
@@ -585,6 +663,11 @@ pub struct GetTlsCredsResponse {
     pub client_cert_pem: String,
 }
 
+/// Requests that the server generate a new CA and server certificate,
+/// invalidating all previously issued client certificates
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct RotateTlsCreds {}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct ListPanes {}
 
@@ -593,6 +676,19 @@ pub struct ListPanesResponse {
     pub tabs: Vec<PaneNode>,
 }
 
+/// Requests a flat list of panes, optionally restricted to those
+/// matching a `mux::query::PaneQuery` expression, which is evaluated
+/// by the server
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListPaneEntries {
+    pub filter: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListPaneEntriesResponse {
+    pub panes: Vec<PaneEntry>,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct SplitPane {
     pub pane_id: PaneId,
@@ -618,6 +714,55 @@ pub struct MovePaneToNewTabResponse {
     pub window_id: WindowId,
 }
 
+/// Re-creates the tab in another domain and closes the original
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MoveTabToDomain {
+    pub tab_id: TabId,
+    pub domain: config::keyassignment::SpawnTabDomain,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MoveTabToDomainResponse {
+    pub tab_id: TabId,
+    pub window_id: WindowId,
+}
+
+/// Tells the server which cell this client is pointing at, so that
+/// the other clients attached to that pane can show it
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetClientCursor {
+    pub cursor: Option<ClientCursor>,
+}
+
+/// Takes the input turn for the pane, or gives it up if this client
+/// already has it
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct TogglePaneInputTurn {
+    pub pane_id: PaneId,
+}
+
+/// Disconnects another client of the server
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct DetachClient {
+    pub client_id: ClientId,
+}
+
+/// Overrides what another client of the server is allowed to do, until
+/// it disconnects.  `None` reverts to the `mux_client_permissions` rules.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetClientPermission {
+    pub client_id: ClientId,
+    pub permission: Option<config::MuxClientPermission>,
+}
+
+/// Sent by the server when the other clients attached to a pane,
+/// where they are pointing, or who has its input turn changes
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct PaneCollaboratorsChanged {
+    pub pane_id: PaneId,
+    pub collaborators: PaneCollaborators,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct SpawnV2 {
     pub domain: config::keyassignment::SpawnTabDomain,
@@ -639,6 +784,60 @@ pub struct KillPane {
     pub pane_id: PaneId,
 }
 
+/// Instantiate the workspace with the given name from the
+/// `workspaces` definitions in the server configuration
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SpawnWorkspace {
+    pub name: String,
+    pub size: TerminalSize,
+}
+
+/// Creates several tabs, each with any number of splits, in a single
+/// request.  If any of them cannot be created, those that were already
+/// created are closed again and the request fails.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SpawnBatch {
+    /// If None, create a new window for the tabs
+    pub window_id: Option<WindowId>,
+    /// The workspace of the new window, if one is created
+    pub workspace: String,
+    pub size: TerminalSize,
+    pub tabs: Vec<SpawnBatchTab>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SpawnBatchTab {
+    pub domain: config::keyassignment::SpawnTabDomain,
+    pub command: Option<CommandBuilder>,
+    pub command_dir: Option<String>,
+    pub splits: Vec<SpawnBatchSplit>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SpawnBatchSplit {
+    /// The index of the pane to split, in the order that the panes
+    /// of the tab were created; the initial pane is 0.
+    /// If None, the most recently created pane is split.
+    pub target: Option<usize>,
+    pub split_request: SplitRequest,
+    pub domain: config::keyassignment::SpawnTabDomain,
+    pub command: Option<CommandBuilder>,
+    pub command_dir: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SpawnBatchResponse {
+    pub window_id: WindowId,
+    pub tabs: Vec<SpawnBatchTabResponse>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SpawnBatchTabResponse {
+    pub tab_id: TabId,
+    /// The panes of the tab, in the order that they were created
+    pub pane_ids: Vec<PaneId>,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct SpawnResponse {
     pub tab_id: TabId,
@@ -659,6 +858,60 @@ pub struct SendPaste {
     pub data: String,
 }
 
+/// Sends a piece of a paste that is delivered across several PDUs,
+/// such as when streaming text from stdin.  The bracketed paste
+/// start and end sequences, if any, are sent with the `is_first`
+/// and `is_last` pieces respectively.
+/// If `bracketed` is None, the server decides based on whether
+/// the application has enabled bracketed paste mode.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SendPasteChunk {
+    pub pane_id: PaneId,
+    pub data: String,
+    pub bracketed: Option<bool>,
+    pub is_first: bool,
+    pub is_last: bool,
+}
+
+/// Reports whether the paste is bracketed; the client should pass
+/// this as `bracketed` with the remaining pieces of the paste
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SendPasteChunkResponse {
+    pub bracketed: bool,
+}
+
+/// Text, which may include escape sequences, to be processed by
+/// the terminal emulator as though the pane had produced it
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct InjectOutput {
+    pub pane_id: PaneId,
+    pub data: String,
+}
+
+/// Requests the latency and throughput measurements for a pane,
+/// or for all panes if `pane_id` is None
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetPaneMetrics {
+    pub pane_id: Option<PaneId>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetPaneMetricsResponse {
+    pub panes: Vec<mux::panestats::PaneMetrics>,
+}
+
+/// Asks the gui that is displaying the pane to render it
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct CaptureImage {
+    pub pane_id: PaneId,
+}
+
+/// The PNG encoded image of the pane
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct CaptureImageResponse {
+    pub data: Vec<u8>,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct SendKeyDown {
     pub pane_id: TabId,
@@ -713,6 +966,14 @@ pub struct SetClipboard {
     pub selection: ClipboardSelection,
 }
 
+/// Sent by the server when an application in the pane has requested
+/// the contents of the clipboard via OSC 52
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct QueryClipboard {
+    pub pane_id: PaneId,
+    pub selection: ClipboardSelection,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct SetWindowWorkspace {
     pub window_id: WindowId,
@@ -742,6 +1003,68 @@ pub struct SetClientId {
     pub client_id: ClientId,
 }
 
+/// Advises the peer of the compression that we'd like it to use
+/// for the PDUs that it sends to us from this point onwards.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetCompression {
+    pub compression: Compression,
+}
+
+/// The classes of event that can be requested via `SubscribeToEvents`
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum MuxEventKind {
+    PaneOutput,
+    Focus,
+    Title,
+    Workspace,
+}
+
+/// Asks the server to send `MuxEvent` PDUs for the specified kinds
+/// of event, replacing any previous subscription.
+/// A session with an active subscription is treated as an event
+/// stream and is no longer sent pane render updates.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SubscribeToEvents {
+    pub kinds: Vec<MuxEventKind>,
+}
+
+/// Sent unilaterally by the server to sessions that have used
+/// `SubscribeToEvents`
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+pub enum MuxEvent {
+    PaneOutput {
+        pane_id: PaneId,
+    },
+    PaneFocused {
+        pane_id: PaneId,
+    },
+    PaneTitleChanged {
+        pane_id: PaneId,
+        title: String,
+    },
+    WindowWorkspaceChanged {
+        window_id: WindowId,
+        workspace: String,
+    },
+    ActiveWorkspaceChanged {
+        client_id: ClientId,
+        workspace: String,
+    },
+}
+
+impl MuxEvent {
+    pub fn kind(&self) -> MuxEventKind {
+        match self {
+            Self::PaneOutput { .. } => MuxEventKind::PaneOutput,
+            Self::PaneFocused { .. } => MuxEventKind::Focus,
+            Self::PaneTitleChanged { .. } => MuxEventKind::Title,
+            Self::WindowWorkspaceChanged { .. } | Self::ActiveWorkspaceChanged { .. } => {
+                MuxEventKind::Workspace
+            }
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct SetFocusedPane {
     pub pane_id: PaneId,
@@ -775,6 +1098,21 @@ pub struct ActivatePaneDirection {
     pub direction: PaneDirection,
 }
 
+/// Makes the pane the active pane in its tab, makes that tab the
+/// active tab in its window, and asks the gui to focus that window
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ActivatePane {
+    pub pane_id: PaneId,
+}
+
+/// Resizes the pane by moving the splits next to it
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ResizePane {
+    pub pane_id: PaneId,
+    pub cols: Option<usize>,
+    pub rows: Option<usize>,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct GetPaneRenderChanges {
     pub pane_id: PaneId,
@@ -1057,6 +1395,34 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_compression_is_self_describing() {
+        let payload = GetLines {
+            pane_id: 1,
+            lines: vec![0..1024; 16],
+        };
+
+        let mut compressed = Vec::new();
+        Pdu::GetLines(payload)
+            .encode_with_compression(&mut compressed, 0x1, Compression::Zstd { level: 19 })
+            .unwrap();
+
+        let payload = GetLines {
+            pane_id: 1,
+            lines: vec![0..1024; 16],
+        };
+        let mut uncompressed = Vec::new();
+        Pdu::GetLines(payload)
+            .encode_with_compression(&mut uncompressed, 0x1, Compression::Disabled)
+            .unwrap();
+
+        assert!(compressed.len() < uncompressed.len());
+        assert_eq!(
+            Pdu::decode(compressed.as_slice()).unwrap(),
+            Pdu::decode(uncompressed.as_slice()).unwrap()
+        );
+    }
+
     #[test]
     fn stream_decode() {
         let mut encoded = Vec::new();
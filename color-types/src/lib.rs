@@ -800,6 +800,73 @@ impl LinearRgba {
             self.3,
         )
     }
+
+    /// Returns the relative luminance of the color, as defined by
+    /// <https://www.w3.org/TR/WCAG21/#dfn-relative-luminance>
+    pub fn relative_luminance(self) -> f32 {
+        0.2126 * self.0 + 0.7152 * self.1 + 0.0722 * self.2
+    }
+
+    /// Returns the WCAG contrast ratio between self and other,
+    /// which ranges from 1.0 to 21.0.  Alpha is ignored.
+    pub fn contrast_ratio(self, other: Self) -> f32 {
+        let a = self.relative_luminance() + 0.05;
+        let b = other.relative_luminance() + 0.05;
+        if a > b {
+            a / b
+        } else {
+            b / a
+        }
+    }
+
+    /// Returns self, moved towards either black or white by the smallest
+    /// amount that gives it a contrast ratio of at least `min_ratio`
+    /// against `bg`.  If that ratio cannot be reached, the color with
+    /// the highest achievable contrast is returned.
+    pub fn ensure_contrast_ratio(self, bg: Self, min_ratio: f32) -> Self {
+        if self.contrast_ratio(bg) >= min_ratio {
+            return self;
+        }
+
+        let white = Self::with_components(1., 1., 1., self.3);
+        let black = Self::with_components(0., 0., 0., self.3);
+        let bg_lum = bg.relative_luminance();
+
+        // Prefer to keep the current polarity of the color relative
+        // to the background, unless only the other direction can
+        // satisfy the requested ratio
+        let lighter = if white.contrast_ratio(bg) >= min_ratio
+            && (self.relative_luminance() >= bg_lum || black.contrast_ratio(bg) < min_ratio)
+        {
+            true
+        } else if black.contrast_ratio(bg) >= min_ratio {
+            false
+        } else {
+            white.contrast_ratio(bg) > black.contrast_ratio(bg)
+        };
+
+        let (target, required_lum) = if lighter {
+            (white, min_ratio * (bg_lum + 0.05) - 0.05)
+        } else {
+            (black, (bg_lum + 0.05) / min_ratio - 0.05)
+        };
+
+        // Luminance is linear in linear RGB, so we can solve for the
+        // amount of the target color that we need to mix in
+        let lum = self.relative_luminance();
+        let target_lum = target.relative_luminance();
+        if (target_lum - lum).abs() < f32::EPSILON {
+            return target;
+        }
+        let t = ((required_lum - lum) / (target_lum - lum)).clamp(0., 1.);
+
+        Self(
+            self.0 + (target.0 - self.0) * t,
+            self.1 + (target.1 - self.1) * t,
+            self.2 + (target.2 - self.2) * t,
+            self.3,
+        )
+    }
 }
 
 #[cfg(test)]
@@ -846,6 +913,28 @@ mod tests {
         assert_eq!(rgba.to_rgba_string(), "rgba(100% 0% 0% 100%)");
     }
 
+    #[test]
+    fn contrast() {
+        let white = LinearRgba::with_components(1., 1., 1., 1.);
+        let black = LinearRgba::with_components(0., 0., 0., 1.);
+        assert!((white.contrast_ratio(black) - 21.).abs() < 0.001);
+
+        let grey = LinearRgba::with_srgba(0x80, 0x80, 0x80, 0xff);
+        let dark_grey = LinearRgba::with_srgba(0x60, 0x60, 0x60, 0xff);
+        assert!(grey.contrast_ratio(dark_grey) < 4.5);
+
+        // Lighter text stays lighter than the background
+        let adjusted = grey.ensure_contrast_ratio(dark_grey, 4.5);
+        assert!(adjusted.contrast_ratio(dark_grey) >= 4.49);
+        assert!(adjusted.relative_luminance() > grey.relative_luminance());
+
+        // Text that already has enough contrast is not changed
+        assert_eq!(white.ensure_contrast_ratio(black, 7.), white);
+
+        // An unreachable ratio yields the most contrasting color
+        assert_eq!(grey.ensure_contrast_ratio(grey, 30.), black);
+    }
+
     #[test]
     fn from_rgb() {
         assert!(SrgbaTuple::from_str("").is_err());
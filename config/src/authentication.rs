@@ -0,0 +1,74 @@
+use luahelper::impl_lua_conversion_dynamic;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// Describes key assignments that require the user to authenticate,
+/// using Touch ID or their password, before they are performed
+#[derive(Debug, Clone, Default, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub struct AuthenticationRule {
+    /// The names of the key assignments, as used with `wezterm.action`,
+    /// eg: `PasteFrom`
+    pub actions: Vec<String>,
+
+    /// If not empty, only assignments performed in panes that belong
+    /// to one of these domains require authentication
+    #[dynamic(default)]
+    pub domains: Vec<String>,
+
+    /// Completes the sentence "wezterm is trying to ..." in the
+    /// authentication prompt
+    #[dynamic(default)]
+    pub reason: Option<String>,
+}
+impl_lua_conversion_dynamic!(AuthenticationRule);
+
+impl AuthenticationRule {
+    /// Returns true if performing the named action in a pane that
+    /// belongs to `domain` requires authentication
+    pub fn matches(&self, action: &str, domain: Option<&str>) -> bool {
+        if !self.actions.iter().any(|a| a == action) {
+            return false;
+        }
+        if self.domains.is_empty() {
+            return true;
+        }
+        match domain {
+            Some(domain) => self.domains.iter().any(|d| d == domain),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keyassignment::{ClipboardPasteSource, KeyAssignment};
+
+    #[test]
+    fn matching() {
+        let rule = AuthenticationRule {
+            actions: vec!["PasteFrom".to_string()],
+            domains: vec!["SSH:prod".to_string()],
+            reason: None,
+        };
+        assert!(rule.matches("PasteFrom", Some("SSH:prod")));
+        assert!(!rule.matches("PasteFrom", Some("local")));
+        assert!(!rule.matches("PasteFrom", None));
+        assert!(!rule.matches("CopyTo", Some("SSH:prod")));
+
+        let rule = AuthenticationRule {
+            actions: vec!["QuitApplication".to_string()],
+            ..Default::default()
+        };
+        assert!(rule.matches("QuitApplication", None));
+        assert!(rule.matches("QuitApplication", Some("local")));
+    }
+
+    #[test]
+    fn assignment_names() {
+        assert_eq!(
+            KeyAssignment::PasteFrom(ClipboardPasteSource::Clipboard).name(),
+            "PasteFrom"
+        );
+        assert_eq!(KeyAssignment::QuitApplication.name(), "QuitApplication");
+    }
+}
@@ -74,6 +74,8 @@ impl Default for VisualBellTarget {
 pub enum AudibleBell {
     SystemBeep,
     Disabled,
+    /// Play the sound file at the specified path
+    SoundFile(String),
 }
 
 impl Default for AudibleBell {
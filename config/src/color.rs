@@ -28,6 +28,27 @@ impl Default for HsbTransform {
     }
 }
 
+/// A color transform that is applied to everything that is rendered
+/// in a window, either to simulate a color vision deficiency, or to
+/// compensate for one by shifting the colors that cannot be told apart
+/// into ones that can (daltonization)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum ColorVisionFilter {
+    None,
+    SimulateProtanopia,
+    SimulateDeuteranopia,
+    SimulateTritanopia,
+    DaltonizeProtanopia,
+    DaltonizeDeuteranopia,
+    DaltonizeTritanopia,
+}
+
+impl Default for ColorVisionFilter {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, FromDynamic, ToDynamic)]
 #[dynamic(try_from = "String", into = "String")]
 pub struct RgbaColor {
@@ -1,25 +1,47 @@
+use crate::authentication::AuthenticationRule;
 use crate::background::{BackgroundLayer, Gradient};
 use crate::bell::{AudibleBell, EasingFunction, VisualBell};
 use crate::color::{
-    ColorSchemeFile, HsbTransform, Palette, SrgbaTuple, TabBarStyle, WindowFrameConfig,
+    ColorSchemeFile, ColorVisionFilter, HsbTransform, Palette, SrgbaTuple, TabBarStyle,
+    WindowFrameConfig,
 };
+use crate::container::ContainerDomain;
 use crate::daemon::DaemonOptions;
+use crate::escape_policy::EscapeSequencePolicy;
 use crate::exec_domain::ExecDomain;
 use crate::font::{
     AllowSquareGlyphOverflow, FontLocatorSelection, FontRasterizerSelection, FontShaperSelection,
     FreeTypeLoadFlags, FreeTypeLoadTarget, StyleRule, TextStyle,
 };
 use crate::frontend::FrontEndSelection;
+use crate::hyperlink_handler::{
+    FilePreview, HyperlinkHandler, HyperlinkHoverDisplay, HyperlinkMenuEntry,
+};
 use crate::keyassignment::{
     KeyAssignment, KeyTable, KeyTableEntry, KeyTables, MouseEventTrigger, SpawnCommand,
 };
 use crate::keys::{Key, LeaderKey, Mouse};
+use crate::kubernetes::KubernetesDomain;
 use crate::lua::make_lua_context;
+use crate::muxaccess::{MuxClientAttachPolicy, MuxClientPermission};
+use crate::notification::NotificationRule;
+use crate::output_watcher::{AutoResponseRule, OutputWatcherRule};
+use crate::password::{default_password_prompts, PasswordSource};
+use crate::redaction::RedactionRule;
+use crate::resource_limits::ResourceLimits;
 use crate::ssh::{SshBackend, SshDomain};
+use crate::tcp::TcpDomain;
+use crate::titlebar::{
+    IntegratedTitleButton, IntegratedTitleButtonAlignment, IntegratedTitleButtonGlyphs,
+};
 use crate::tls::{TlsDomainClient, TlsDomainServer};
+use crate::touchbar::TouchBarElement;
+use crate::udp::{UdpDomainClient, UdpDomainServer};
 use crate::units::Dimension;
 use crate::unix::UnixDomain;
+use crate::workspace::WorkspaceDefinition;
 use crate::wsl::WslDomain;
+use crate::wss::{WssDomainClient, WssDomainServer};
 use crate::{
     default_config_with_overrides_applied, default_one_point_oh, default_one_point_oh_f64,
     default_true, KeyMapPreference, LoadedConfig, MouseEventTriggerMods, RgbaColor, CONFIG_DIR,
@@ -41,6 +63,7 @@ use wezterm_bidi::ParagraphDirectionHint;
 use wezterm_config_derive::ConfigMeta;
 use wezterm_dynamic::{FromDynamic, ToDynamic};
 use wezterm_input_types::{Modifiers, WindowDecorations};
+use wezterm_term::input::MouseButton;
 use wezterm_term::TerminalSize;
 
 #[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
@@ -76,6 +99,17 @@ pub struct Config {
     #[dynamic(default)]
     pub window_decorations: WindowDecorations,
 
+    /// The buttons, and their order, that are shown in the tab bar
+    /// when window_decorations includes INTEGRATED_BUTTONS
+    #[dynamic(default = "default_integrated_title_buttons")]
+    pub integrated_title_buttons: Vec<IntegratedTitleButton>,
+
+    #[dynamic(default)]
+    pub integrated_title_button_alignment: IntegratedTitleButtonAlignment,
+
+    #[dynamic(default)]
+    pub integrated_title_button_glyphs: IntegratedTitleButtonGlyphs,
+
     /// When using FontKitXXX font systems, a set of directories to
     /// search ahead of the standard font locations for fonts.
     /// Relative paths are taken to be relative to the directory
@@ -171,6 +205,15 @@ pub struct Config {
     #[dynamic(default = "default_clean_exits")]
     pub clean_exit_codes: Vec<u32>,
 
+    /// When a process exits unsuccessfully, keep its pane open and
+    /// show an inspector with the exit status and its final output
+    #[dynamic(default)]
+    pub dead_pane_inspector: bool,
+
+    /// How many lines of output the dead pane inspector shows
+    #[dynamic(default = "default_dead_pane_inspector_lines")]
+    pub dead_pane_inspector_lines: usize,
+
     #[dynamic(default = "default_true")]
     pub detect_password_input: bool,
 
@@ -189,6 +232,11 @@ pub struct Config {
     #[dynamic(default)]
     pub enable_kitty_keyboard: bool,
 
+    /// Whether the terminal should interpret ReGIS and Tektronix 4014
+    /// vector graphics
+    #[dynamic(default)]
+    pub enable_vector_graphics: bool,
+
     /// Specifies the width of a new window, expressed in character cells
     #[dynamic(default = "default_initial_cols", validate = "validate_row_or_col")]
     pub initial_cols: u16,
@@ -196,6 +244,29 @@ pub struct Config {
     #[dynamic(default = "default_hyperlink_rules")]
     pub hyperlink_rules: Vec<hyperlink::Rule>,
 
+    /// Where to show the target of the hyperlink under the mouse pointer
+    #[dynamic(default)]
+    pub hyperlink_hover_display: HyperlinkHoverDisplay,
+
+    /// Maps uri schemes to the action taken when a hyperlink with
+    /// that scheme is clicked
+    #[dynamic(default)]
+    pub hyperlink_handlers: HashMap<String, HyperlinkHandler>,
+
+    /// The applications offered by the "Open With" entries of the
+    /// context menu of hyperlinks
+    #[dynamic(default)]
+    pub hyperlink_open_with: Vec<String>,
+
+    /// Additional entries of the context menu of hyperlinks
+    #[dynamic(default)]
+    pub hyperlink_context_menu: Vec<HyperlinkMenuEntry>,
+
+    /// Maps file extensions to an internal preview that is shown
+    /// in place of the system handler when a `file` hyperlink is clicked
+    #[dynamic(default)]
+    pub file_preview_extensions: HashMap<String, FilePreview>,
+
     /// What to set the TERM variable to
     #[dynamic(default = "default_term")]
     pub term: String,
@@ -268,6 +339,41 @@ pub struct Config {
     #[dynamic(default)]
     pub exec_domains: Vec<ExecDomain>,
 
+    /// Domains whose panes are connected to a TCP endpoint,
+    /// optionally using telnet and/or TLS
+    #[dynamic(default)]
+    pub tcp_domains: Vec<TcpDomain>,
+
+    /// Domains whose panes run inside a container of a kubernetes pod
+    #[dynamic(default)]
+    pub kubernetes_domains: Vec<KubernetesDomain>,
+
+    /// The kubectl executable used by kubernetes domains and
+    /// the kubernetes pod selector
+    #[dynamic(default = "default_kubectl_path")]
+    pub kubectl_path: String,
+
+    /// Domains whose panes run inside a docker or podman container
+    #[dynamic(default)]
+    pub container_domains: Vec<ContainerDomain>,
+
+    /// The docker (or compatible, such as podman) executable used by
+    /// container domains and the container selector
+    #[dynamic(default = "default_container_engine_path")]
+    pub container_engine_path: String,
+
+    /// Workspaces that are instantiated from their definition the
+    /// first time that they are switched to
+    #[dynamic(default)]
+    pub workspaces: Vec<WorkspaceDefinition>,
+
+    /// When true, each GUI window keeps showing the workspace that it
+    /// was created for, and switching workspaces focuses (or opens)
+    /// the windows of the new workspace rather than replacing the
+    /// content of the current window
+    #[dynamic(default)]
+    pub workspace_per_window: bool,
+
     /// The set of unix domains
     #[dynamic(default = "UnixDomain::default_unix_domains")]
     pub unix_domains: Vec<UnixDomain>,
@@ -287,6 +393,48 @@ pub struct Config {
     #[dynamic(default)]
     pub tls_clients: Vec<TlsDomainClient>,
 
+    /// When running in server mode, defines configuration for
+    /// each of the websocket endpoints that we'll listen for connections
+    #[dynamic(default)]
+    pub wss_servers: Vec<WssDomainServer>,
+
+    /// The set of websocket domains that we can connect to as a client
+    #[dynamic(default)]
+    pub wss_domains: Vec<WssDomainClient>,
+
+    /// When running in server mode, defines configuration for
+    /// each of the udp endpoints that we'll listen for sessions
+    #[dynamic(default)]
+    pub udp_servers: Vec<UdpDomainServer>,
+
+    /// The set of udp domains that we can connect to as a client
+    #[dynamic(default)]
+    pub udp_domains: Vec<UdpDomainClient>,
+
+    /// When running in server mode, restricts what the clients that
+    /// connect to this instance over the network are allowed to do.
+    /// The first rule that matches the listener and certificate of a
+    /// client applies to it; clients that match no rule are refused.
+    /// When empty, network clients have full access.
+    #[dynamic(default)]
+    pub mux_client_permissions: Vec<MuxClientPermission>,
+
+    /// When set, clients of this instance in server mode that have not
+    /// sent any input for this many seconds are detached
+    #[dynamic(default)]
+    pub mux_client_idle_timeout_secs: Option<u64>,
+
+    /// When set, limits how many clients in server mode can be
+    /// attached to the same workspace at the same time.  Only applies
+    /// when `mux_client_attach_policy` is `Mirror`.
+    #[dynamic(default)]
+    pub mux_max_clients_per_workspace: Option<usize>,
+
+    /// Whether a client that attaches to a workspace shares it with the
+    /// clients that are already attached, or detaches them
+    #[dynamic(default)]
+    pub mux_client_attach_policy: MuxClientAttachPolicy,
+
     /// Constrains the rate at which the multiplexer client will
     /// speculatively fetch line data.
     /// This helps to avoid saturating the link between the client
@@ -309,9 +457,73 @@ pub struct Config {
     #[dynamic(default = "default_mux_output_parser_coalesce_delay_ms")]
     pub mux_output_parser_coalesce_delay_ms: u64,
 
+    /// Controls predictive local echo for panes in remote domains.
+    /// `Adaptive` predicts only when the round trip time exceeds the
+    /// `local_echo_threshold_ms` of the domain, `Always` predicts
+    /// regardless of latency and `Never` disables prediction.
+    #[dynamic(default)]
+    pub local_echo_prediction: LocalEchoPrediction,
+
+    /// The underline style used to mark predicted text that has
+    /// not yet been confirmed by the remote end
+    #[dynamic(default = "default_local_echo_prediction_underline")]
+    pub local_echo_prediction_underline: wezterm_term::Underline,
+
+    /// Predictions that have not been confirmed by the remote end
+    /// within this many milliseconds are discarded
+    #[dynamic(default = "default_local_echo_prediction_timeout_ms")]
+    pub local_echo_prediction_timeout_ms: u64,
+
+    /// When a pane produces output faster than it can be processed,
+    /// pause between batches so that the gui remains responsive
+    #[dynamic(default = "default_true")]
+    pub mux_output_flow_control: bool,
+
+    /// The largest fraction of the main thread's time that may be
+    /// spent applying output from a pane that is flooding us
+    #[dynamic(default = "default_mux_output_flow_control_duty_cycle")]
+    pub mux_output_flow_control_duty_cycle: f64,
+
+    /// The output of panes in tabs that are not active in their
+    /// window is processed at most once per this many ms.
+    /// 0 processes it as eagerly as for visible panes.
+    #[dynamic(default = "default_mux_output_background_throttle_ms")]
+    pub mux_output_background_throttle_ms: u64,
+
+    /// Whether to honor DECSET 2026 synchronized output, holding
+    /// back rendering until the application has finished its update
+    #[dynamic(default = "default_true")]
+    pub enable_synchronized_output: bool,
+
+    /// The longest time, in milliseconds, that a synchronized output
+    /// update may hold back output before it is forcibly flushed
+    #[dynamic(default = "default_synchronized_output_timeout_ms")]
+    pub synchronized_output_timeout_ms: u64,
+
+    /// When true, show keypress-to-render latency, frame time and
+    /// pty throughput measurements over each pane
+    #[dynamic(default)]
+    pub enable_debug_hud: bool,
+
     #[dynamic(default = "default_mux_env_remove")]
     pub mux_env_remove: Vec<String>,
 
+    /// Limits applied to the processes spawned into every pane in
+    /// a local domain, unless overridden by the SpawnCommand
+    #[dynamic(default)]
+    pub default_resource_limits: Option<ResourceLimits>,
+
+    /// Selects the compression that the multiplexer client asks the
+    /// server to use when sending PDUs, and that it uses itself when
+    /// sending PDUs to the server.
+    #[dynamic(default)]
+    pub mux_compression: MuxCompression,
+
+    /// The zstd compression level to use when `mux_compression = "Zstd"`.
+    /// Higher values trade CPU time for a smaller encoded size.
+    #[dynamic(default = "default_mux_compression_level")]
+    pub mux_compression_level: i32,
+
     #[dynamic(default)]
     pub keys: Vec<Key>,
     #[dynamic(default)]
@@ -369,6 +581,18 @@ pub struct Config {
     #[dynamic(default = "default_true")]
     pub use_fancy_tab_bar: bool,
 
+    /// If true, hovering over a tab in the fancy tab bar shows a
+    /// thumbnail of the content of its active pane
+    #[dynamic(default)]
+    pub show_tab_previews: bool,
+
+    /// The width of the thumbnails shown by show_tab_previews
+    #[dynamic(
+        try_from = "crate::units::PixelUnit",
+        default = "default_tab_preview_width"
+    )]
+    pub tab_preview_width: Dimension,
+
     #[dynamic(default)]
     pub tab_bar_at_bottom: bool,
 
@@ -386,6 +610,26 @@ pub struct Config {
     #[dynamic(default = "default_tab_max_width")]
     pub tab_max_width: usize,
 
+    /// A template for the titles of tabs, which is used when the
+    /// format-tab-title event doesn't produce a title
+    #[dynamic(default)]
+    pub tab_title_format: String,
+
+    /// A template for the title of the window, which is used when the
+    /// format-window-title event doesn't produce a title
+    #[dynamic(default)]
+    pub window_title_format: String,
+
+    /// The template used to infer the title of a pane that is running
+    /// a command, when the application hasn't set a title
+    #[dynamic(default = "default_inferred_title_template")]
+    pub inferred_title_template: String,
+
+    /// The template used to infer the title of a pane whose shell is
+    /// in the foreground, when the shell hasn't set a title
+    #[dynamic(default = "default_inferred_title_template")]
+    pub inferred_title_shell_template: String,
+
     /// If true, hide the tab bar if the window only has a single tab.
     #[dynamic(default)]
     pub hide_tab_bar_if_only_one_tab: bool,
@@ -393,6 +637,10 @@ pub struct Config {
     #[dynamic(default)]
     pub enable_scroll_bar: bool,
 
+    /// Whether to draw the positions of named marks in the scroll bar
+    #[dynamic(default = "default_true")]
+    pub show_named_marks_in_scroll_bar: bool,
+
     #[dynamic(try_from = "crate::units::PixelUnit", default = "default_half_cell")]
     pub min_scroll_bar_height: Dimension,
 
@@ -438,6 +686,9 @@ pub struct Config {
     #[dynamic(default)]
     pub foreground_text_hsb: HsbTransform,
 
+    #[dynamic(default)]
+    pub color_vision_filter: ColorVisionFilter,
+
     #[dynamic(default)]
     pub background: Vec<BackgroundLayer>,
 
@@ -571,6 +822,16 @@ pub struct Config {
     #[dynamic(default)]
     pub launch_menu: Vec<SpawnCommand>,
 
+    /// Whether to add the launch_menu entries to the taskbar jump
+    /// list on Windows and the Dock menu on macOS
+    #[dynamic(default = "default_true")]
+    pub shell_menu_include_launch_menu: bool,
+
+    /// How many of the most recently used workspaces to add to the
+    /// taskbar jump list on Windows and the Dock menu on macOS
+    #[dynamic(default = "default_shell_menu_recent_workspaces")]
+    pub shell_menu_recent_workspaces: usize,
+
     #[dynamic(default)]
     pub use_box_model_render: bool,
 
@@ -600,6 +861,20 @@ pub struct Config {
     #[dynamic(default)]
     pub native_macos_fullscreen_mode: bool,
 
+    /// Whether wezterm windows on macOS may be merged into native
+    /// window tab groups
+    #[dynamic(default)]
+    pub native_macos_tabs: bool,
+
+    /// The content of the macOS Touch Bar
+    #[dynamic(default = "default_touch_bar_layout")]
+    pub touch_bar_layout: Vec<TouchBarElement>,
+
+    /// Key assignments that require the user to authenticate
+    /// before they are performed
+    #[dynamic(default)]
+    pub authenticate_actions: Vec<AuthenticationRule>,
+
     #[dynamic(default = "default_word_boundary")]
     pub selection_word_boundary: String,
 
@@ -654,6 +929,21 @@ pub struct Config {
     #[dynamic(default = "default_true")]
     pub unzoom_on_switch_pane: bool,
 
+    /// The smallest number of columns that splitting or resizing
+    /// panes will leave a pane with
+    #[dynamic(default = "default_pane_minimum_size")]
+    pub pane_minimum_cols: usize,
+
+    /// The smallest number of rows that splitting or resizing
+    /// panes will leave a pane with
+    #[dynamic(default = "default_pane_minimum_size")]
+    pub pane_minimum_rows: usize,
+
+    /// What to do when splitting a pane would leave a pane smaller
+    /// than pane_minimum_cols/pane_minimum_rows
+    #[dynamic(default)]
+    pub pane_too_small_action: PaneTooSmallAction,
+
     #[dynamic(default = "default_max_fps")]
     pub max_fps: u8,
 
@@ -667,6 +957,10 @@ pub struct Config {
     pub line_to_ele_shape_cache_size: usize,
     #[dynamic(default = "default_glyph_cache_image_cache_size")]
     pub glyph_cache_image_cache_size: usize,
+    #[dynamic(default = "default_true")]
+    pub enable_glyph_disk_cache: bool,
+    #[dynamic(default = "default_glyph_disk_cache_max_size_mb")]
+    pub glyph_disk_cache_max_size_mb: usize,
 
     #[dynamic(default)]
     pub visual_bell: VisualBell,
@@ -674,21 +968,140 @@ pub struct Config {
     #[dynamic(default)]
     pub audible_bell: AudibleBell,
 
+    #[dynamic(default = "default_audible_bell_volume")]
+    pub audible_bell_volume: f32,
+
+    #[dynamic(default = "default_audible_bell_max_per_second")]
+    pub audible_bell_max_per_second: u32,
+
+    /// The names of the domains whose panes don't make a sound
+    /// when the bell rings
+    #[dynamic(default)]
+    pub audible_bell_muted_domains: Vec<String>,
+
     #[dynamic(default)]
     pub canonicalize_pasted_newlines: Option<NewlineCanon>,
 
+    /// When enabled on Windows and macOS, text copied to the primary
+    /// selection, as the default mouse bindings do, is retained in an
+    /// internal buffer that emulates the X11 primary selection rather
+    /// than being copied to the system clipboard
+    #[dynamic(default)]
+    pub emulate_primary_selection: bool,
+
+    /// The mouse button that the default mouse bindings use to paste
+    /// the primary selection
+    #[dynamic(default = "default_primary_selection_paste_button")]
+    pub primary_selection_paste_button: MouseButton,
+
+    /// When enabled, pasting multi-line text or text that contains
+    /// control characters shows a prompt to review it first
+    #[dynamic(default)]
+    pub paste_review_prompt: bool,
+
+    /// How many of the most recent copies to retain in the clipboard
+    /// history.  The default of 0 disables the history.
+    #[dynamic(default)]
+    pub clipboard_history_size: usize,
+
+    /// When scrolled back, show a divider where new output begins
+    /// and a count of the new lines
+    #[dynamic(default = "default_true")]
+    pub show_new_output_indicator: bool,
+
+    /// How many of the most recent notifications to retain in the
+    /// notification center.  0 disables the notification center.
+    #[dynamic(default = "default_notification_center_history_size")]
+    pub notification_center_history_size: usize,
+
+    /// Rules that decide whether toast notifications and bells are
+    /// delivered, only recorded in the notification center, or
+    /// discarded.  The first matching rule wins.
+    #[dynamic(default)]
+    pub notification_rules: Vec<NotificationRule>,
+
+    /// Rules that emit a lua event when a matching line of output
+    /// is written to a pane
+    #[dynamic(default)]
+    pub output_watchers: Vec<OutputWatcherRule>,
+
+    /// Rules that send a response to a pane when its output
+    /// matches, such as answering a confirmation prompt
+    #[dynamic(default)]
+    pub auto_responses: Vec<AutoResponseRule>,
+
+    /// Regular expressions that recognize a password prompt on the
+    /// line that holds the cursor, which enables `FillPassword`
+    #[dynamic(default = "default_password_prompts")]
+    pub password_prompts: Vec<String>,
+
+    /// Where `FillPassword` obtains passwords; the first matching
+    /// source is used
+    #[dynamic(default)]
+    pub password_sources: Vec<PasswordSource>,
+
+    /// Rules that mask sensitive text, such as access tokens, in the
+    /// display and in copies until it is revealed
+    #[dynamic(default)]
+    pub redaction_rules: Vec<RedactionRule>,
+
+    /// The passphrase that unlocks input that was locked by `LockInput`
+    #[dynamic(default)]
+    pub input_lock_passphrase: Option<String>,
+
     #[dynamic(default = "default_unicode_version")]
     pub unicode_version: u8,
 
     #[dynamic(default)]
     pub treat_east_asian_ambiguous_width_as_wide: bool,
 
+    #[dynamic(default = "default_true")]
+    pub grapheme_clustering: bool,
+
+    #[dynamic(default)]
+    pub enable_screen_reader_support: bool,
+
+    #[dynamic(default = "default_minimum_contrast_ratio")]
+    pub minimum_contrast_ratio: f32,
+
+    #[dynamic(default)]
+    pub high_contrast_mode: bool,
+
+    #[dynamic(default = "default_high_contrast_min_ratio")]
+    pub high_contrast_min_ratio: f32,
+
+    #[dynamic(default)]
+    pub reduce_motion: bool,
+
     #[dynamic(default = "default_true")]
     pub allow_download_protocols: bool,
 
+    /// Controls whether applications may read the clipboard using OSC 52
+    #[dynamic(default)]
+    pub osc52_clipboard_read: Osc52ClipboardRead,
+
+    /// Panes that are connected to a remote host, either via a remote
+    /// domain or as reported by OSC 7, may only read the clipboard if
+    /// the host or domain name is listed here.  Local panes that have
+    /// not reported the local host name via OSC 7 are refused.
+    #[dynamic(default)]
+    pub osc52_clipboard_read_allowed_hosts: Vec<String>,
+
+    /// Clipboard contents larger than this are not reported via OSC 52
+    #[dynamic(default = "default_osc52_clipboard_read_max_bytes")]
+    pub osc52_clipboard_read_max_bytes: usize,
+
+    /// Controls which potentially dangerous escape sequences are
+    /// honored, keyed by domain name
+    #[dynamic(default)]
+    pub escape_sequence_policy: HashMap<String, EscapeSequencePolicy>,
+
     #[dynamic(default = "default_true")]
     pub allow_win32_input_mode: bool,
 
+    #[dynamic(default)]
+    pub conpty_passthrough: bool,
+
     #[dynamic(default)]
     pub default_domain: Option<String>,
 
@@ -706,6 +1119,18 @@ pub struct Config {
 
     #[dynamic(default)]
     pub quote_dropped_files: DroppedFileQuoting,
+
+    #[dynamic(default)]
+    pub dropped_files_action: DroppedFilesAction,
+
+    /// The directory on the remote host into which dropped files are
+    /// uploaded.  Defaults to the working directory of the pane.
+    #[dynamic(default)]
+    pub dropped_files_upload_dir: Option<String>,
+
+    /// How context menus are presented
+    #[dynamic(default)]
+    pub context_menu_style: ContextMenuStyle,
 }
 impl_lua_conversion_dynamic!(Config);
 
@@ -944,15 +1369,40 @@ impl Config {
         for d in &self.exec_domains {
             check_domain(&d.name, "exec domain")?;
         }
+        for d in &self.tcp_domains {
+            check_domain(&d.name, "tcp domain")?;
+        }
+        for d in &self.kubernetes_domains {
+            check_domain(&d.name, "kubernetes domain")?;
+        }
+        for d in &self.container_domains {
+            check_domain(&d.name, "container domain")?;
+        }
         for d in &self.wsl_domains {
             check_domain(&d.name, "wsl domain")?;
         }
         for d in &self.tls_clients {
             check_domain(&d.name, "tls domain")?;
         }
+        for d in &self.wss_domains {
+            check_domain(&d.name, "wss domain")?;
+        }
+        for d in &self.udp_domains {
+            check_domain(&d.name, "udp domain")?;
+        }
         Ok(())
     }
 
+    /// Returns the definition of the named workspace, if any
+    pub fn workspace_definition(&self, name: &str) -> Option<&WorkspaceDefinition> {
+        self.workspaces.iter().find(|w| w.name == name)
+    }
+
+    /// Returns the definition of the named WSL domain, if any
+    pub fn wsl_domain(&self, name: &str) -> Option<&WslDomain> {
+        self.wsl_domains.iter().find(|d| d.name == name)
+    }
+
     pub fn default_config() -> Self {
         Self::default().compute_extra_defaults(None)
     }
@@ -1285,6 +1735,26 @@ impl Config {
     }
 }
 
+fn default_integrated_title_buttons() -> Vec<IntegratedTitleButton> {
+    vec![
+        IntegratedTitleButton::Hide,
+        IntegratedTitleButton::Maximize,
+        IntegratedTitleButton::Close,
+    ]
+}
+
+fn default_primary_selection_paste_button() -> MouseButton {
+    MouseButton::Middle
+}
+
+fn default_touch_bar_layout() -> Vec<TouchBarElement> {
+    vec![TouchBarElement::KeyTable, TouchBarElement::Tabs]
+}
+
+fn default_shell_menu_recent_workspaces() -> usize {
+    5
+}
+
 fn default_check_for_updates() -> bool {
     cfg!(not(feature = "distro-defaults"))
 }
@@ -1313,14 +1783,42 @@ fn default_mux_output_parser_coalesce_delay_ms() -> u64 {
     3
 }
 
+fn default_mux_output_flow_control_duty_cycle() -> f64 {
+    0.5
+}
+
+fn default_mux_output_background_throttle_ms() -> u64 {
+    250
+}
+
+fn default_synchronized_output_timeout_ms() -> u64 {
+    150
+}
+
 fn default_mux_output_parser_buffer_size() -> usize {
     128 * 1024
 }
 
+fn default_osc52_clipboard_read_max_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_mux_compression_level() -> i32 {
+    3
+}
+
 fn default_ratelimit_line_prefetches_per_second() -> u32 {
     10
 }
 
+fn default_audible_bell_volume() -> f32 {
+    1.0
+}
+
+fn default_audible_bell_max_per_second() -> u32 {
+    4
+}
+
 fn default_cursor_blink_rate() -> u64 {
     800
 }
@@ -1401,6 +1899,14 @@ pub fn default_local_echo_threshold_ms() -> Option<u64> {
     Some(100)
 }
 
+fn default_local_echo_prediction_underline() -> wezterm_term::Underline {
+    wezterm_term::Underline::Double
+}
+
+fn default_local_echo_prediction_timeout_ms() -> u64 {
+    2000
+}
+
 fn default_bypass_mouse_reporting_modifiers() -> Modifiers {
     Modifiers::SHIFT
 }
@@ -1426,6 +1932,14 @@ fn default_anim_fps() -> u8 {
     10
 }
 
+fn default_minimum_contrast_ratio() -> f32 {
+    1.0
+}
+
+fn default_high_contrast_min_ratio() -> f32 {
+    7.0
+}
+
 fn default_max_fps() -> u8 {
     60
 }
@@ -1471,6 +1985,10 @@ fn default_tab_max_width() -> usize {
     16
 }
 
+fn default_inferred_title_template() -> String {
+    "{process}".to_string()
+}
+
 fn default_update_interval() -> u64 {
     86400
 }
@@ -1491,6 +2009,19 @@ fn default_inactive_pane_hsb() -> HsbTransform {
     }
 }
 
+#[derive(FromDynamic, ToDynamic, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LocalEchoPrediction {
+    Never,
+    Adaptive,
+    Always,
+}
+
+impl Default for LocalEchoPrediction {
+    fn default() -> Self {
+        Self::Adaptive
+    }
+}
+
 #[derive(FromDynamic, ToDynamic, Clone, Copy, Debug)]
 pub enum DefaultCursorStyle {
     BlinkingBlock,
@@ -1535,6 +2066,10 @@ const fn default_half_cell() -> Dimension {
     Dimension::Cells(0.5)
 }
 
+const fn default_tab_preview_width() -> Dimension {
+    Dimension::Cells(40.)
+}
+
 #[derive(FromDynamic, ToDynamic, Clone, Copy, Debug)]
 pub struct WindowPadding {
     #[dynamic(try_from = "crate::units::PixelUnit", default = "default_one_cell")]
@@ -1567,6 +2102,36 @@ pub enum NewlineCanon {
     CarriageReturnAndLineFeed,
 }
 
+#[derive(FromDynamic, ToDynamic, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MuxCompression {
+    /// Send PDUs uncompressed
+    None,
+    /// Compress larger PDUs using zstd
+    Zstd,
+}
+
+impl Default for MuxCompression {
+    fn default() -> Self {
+        Self::Zstd
+    }
+}
+
+#[derive(FromDynamic, ToDynamic, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Osc52ClipboardRead {
+    /// Requests to read the clipboard are refused
+    Deny,
+    /// The user is asked whether to allow each request
+    Prompt,
+    /// Requests are allowed
+    Allow,
+}
+
+impl Default for Osc52ClipboardRead {
+    fn default() -> Self {
+        Self::Deny
+    }
+}
+
 #[derive(FromDynamic, ToDynamic, Clone, Copy, Debug)]
 pub enum WindowCloseConfirmation {
     AlwaysPrompt,
@@ -1617,6 +2182,66 @@ impl Default for ExitBehavior {
     }
 }
 
+/// What to do when a pane would be smaller than the configured minimum
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq)]
+pub enum PaneTooSmallAction {
+    /// Don't split the pane
+    Refuse,
+    /// Split the pane, and zoom the new pane so that it can be used.
+    /// The active pane is also zoomed if resizing the window makes
+    /// it too small.
+    Zoom,
+}
+
+impl Default for PaneTooSmallAction {
+    fn default() -> Self {
+        PaneTooSmallAction::Refuse
+    }
+}
+
+/// Which exits cause a pane to re-run its command
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq)]
+pub enum RestartCondition {
+    /// Restart whenever the process exits
+    Always,
+    /// Restart only if the process didn't exit cleanly, as
+    /// determined by `clean_exit_codes`
+    OnFailure,
+}
+
+/// Re-runs the command of a pane in-place when it exits
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq)]
+pub struct RestartOnExit {
+    pub when: RestartCondition,
+    /// How long to wait before restarting
+    #[dynamic(default = "default_restart_delay_ms")]
+    pub delay_ms: u64,
+}
+
+fn default_notification_center_history_size() -> usize {
+    100
+}
+
+fn default_dead_pane_inspector_lines() -> usize {
+    20
+}
+
+fn default_pane_minimum_size() -> usize {
+    1
+}
+
+fn default_restart_delay_ms() -> u64 {
+    1000
+}
+
+fn default_kubectl_path() -> String {
+    "kubectl".to_string()
+}
+
+fn default_container_engine_path() -> String {
+    "docker".to_string()
+}
+
 #[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq)]
 pub enum DroppedFileQuoting {
     /// No quoting is performed, the file name is passed through as-is
@@ -1661,10 +2286,48 @@ impl DroppedFileQuoting {
     }
 }
 
+/// What happens when files are dropped onto a pane
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq)]
+pub enum DroppedFilesAction {
+    /// Paste the paths of the files, quoted according to
+    /// `quote_dropped_files`
+    PastePaths,
+    /// Upload the files via SFTP when the pane is in an ssh domain
+    /// and paste the paths of the uploaded files, otherwise paste
+    /// the paths of the files
+    UploadToRemote,
+}
+
+impl Default for DroppedFilesAction {
+    fn default() -> Self {
+        Self::PastePaths
+    }
+}
+
+/// How context menus are presented
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq)]
+pub enum ContextMenuStyle {
+    /// Use the menus of the system where they are available,
+    /// falling back to `Internal` elsewhere
+    Native,
+    /// Draw the menu as a popup within the window
+    Internal,
+}
+
+impl Default for ContextMenuStyle {
+    fn default() -> Self {
+        Self::Native
+    }
+}
+
 fn default_glyph_cache_image_cache_size() -> usize {
     256
 }
 
+fn default_glyph_disk_cache_max_size_mb() -> usize {
+    64
+}
+
 fn default_shape_cache_size() -> usize {
     1024
 }
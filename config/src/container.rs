@@ -0,0 +1,92 @@
+use crate::config::validate_domain_name;
+use luahelper::impl_lua_conversion_dynamic;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// A domain whose panes run a program inside a running docker
+/// or podman container, by way of `docker exec`
+#[derive(Default, Debug, Clone, FromDynamic, ToDynamic)]
+pub struct ContainerDomain {
+    /// The name of this specific domain.  Must be unique amongst
+    /// all types of domain in the configuration file.
+    #[dynamic(validate = "validate_domain_name")]
+    pub name: String,
+    /// The name or id of the container
+    pub container: String,
+    /// The container engine executable, such as `podman`.
+    /// Defaults to the `container_engine_path` config.
+    pub engine: Option<String>,
+    /// The user to run as inside the container
+    pub user: Option<String>,
+    /// The program to run when no command is specified
+    pub default_prog: Option<Vec<String>>,
+}
+impl_lua_conversion_dynamic!(ContainerDomain);
+
+/// The program that is run in a container when none was specified;
+/// not all images have bash, so this falls back to sh
+pub fn default_container_shell() -> Vec<String> {
+    vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        "command -v bash >/dev/null && exec bash || exec sh".to_string(),
+    ]
+}
+
+impl ContainerDomain {
+    /// Builds the command line that runs `args`, or the default
+    /// program, in the container
+    pub fn exec_argv(&self, engine: &str, term: &str, args: &[String]) -> Vec<String> {
+        let mut argv = vec![
+            self.engine.as_deref().unwrap_or(engine).to_string(),
+            "exec".to_string(),
+            "--interactive".to_string(),
+            "--tty".to_string(),
+            "--env".to_string(),
+            format!("TERM={}", term),
+        ];
+        if let Some(user) = &self.user {
+            argv.push("--user".to_string());
+            argv.push(user.clone());
+        }
+        argv.push(self.container.clone());
+        if !args.is_empty() {
+            argv.extend(args.iter().cloned());
+        } else if let Some(prog) = &self.default_prog {
+            argv.extend(prog.iter().cloned());
+        } else {
+            argv.extend(default_container_shell());
+        }
+        argv
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exec_argv() {
+        let dom = ContainerDomain {
+            name: "db".to_string(),
+            container: "postgres".to_string(),
+            engine: Some("podman".to_string()),
+            user: Some("postgres".to_string()),
+            default_prog: Some(vec!["psql".to_string()]),
+        };
+        assert_eq!(
+            dom.exec_argv("docker", "xterm", &[]),
+            vec![
+                "podman",
+                "exec",
+                "--interactive",
+                "--tty",
+                "--env",
+                "TERM=xterm",
+                "--user",
+                "postgres",
+                "postgres",
+                "psql"
+            ]
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{Config, Osc52ClipboardRead};
+use luahelper::impl_lua_conversion_dynamic;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+use wezterm_term::EscapeSequenceKind;
+
+#[derive(FromDynamic, ToDynamic, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EscapeSequencePermission {
+    /// The sequence is honored
+    Allow,
+    /// The sequence is ignored
+    Deny,
+    /// The user is asked whether to honor the sequence
+    Prompt,
+}
+
+impl From<Osc52ClipboardRead> for EscapeSequencePermission {
+    fn from(read: Osc52ClipboardRead) -> Self {
+        match read {
+            Osc52ClipboardRead::Allow => Self::Allow,
+            Osc52ClipboardRead::Deny => Self::Deny,
+            Osc52ClipboardRead::Prompt => Self::Prompt,
+        }
+    }
+}
+
+/// Specifies how potentially dangerous escape sequences are treated
+/// for the panes of a domain.  Fields that are not specified fall
+/// back to the corresponding global options.
+#[derive(FromDynamic, ToDynamic, Clone, Debug, Default)]
+pub struct EscapeSequencePolicy {
+    /// OSC 52 clipboard reads
+    #[dynamic(default)]
+    pub clipboard_read: Option<EscapeSequencePermission>,
+    /// OSC 52 clipboard writes
+    #[dynamic(default)]
+    pub clipboard_write: Option<EscapeSequencePermission>,
+    /// Changes to the window, tab and icon titles
+    #[dynamic(default)]
+    pub set_title: Option<EscapeSequencePermission>,
+    /// iTerm2 file transfers
+    #[dynamic(default)]
+    pub file_transfer: Option<EscapeSequencePermission>,
+    /// Application program commands, such as the kitty image protocol
+    #[dynamic(default)]
+    pub apc: Option<EscapeSequencePermission>,
+}
+impl_lua_conversion_dynamic!(EscapeSequencePolicy);
+
+impl EscapeSequencePolicy {
+    pub fn get(&self, kind: EscapeSequenceKind) -> Option<EscapeSequencePermission> {
+        match kind {
+            EscapeSequenceKind::ClipboardRead => self.clipboard_read,
+            EscapeSequenceKind::ClipboardWrite => self.clipboard_write,
+            EscapeSequenceKind::SetTitle => self.set_title,
+            EscapeSequenceKind::FileTransfer => self.file_transfer,
+            EscapeSequenceKind::Apc => self.apc,
+        }
+    }
+}
+
+impl Config {
+    /// Returns the permission that was explicitly configured for the
+    /// named domain in `escape_sequence_policy`, if any
+    pub fn explicit_escape_sequence_permission(
+        &self,
+        domain_name: &str,
+        kind: EscapeSequenceKind,
+    ) -> Option<EscapeSequencePermission> {
+        self.escape_sequence_policy
+            .get(domain_name)
+            .and_then(|policy| policy.get(kind))
+    }
+
+    /// Returns the permission for sequences of the specified kind
+    /// in panes of the named domain, falling back to the global
+    /// options when the policy doesn't specify one
+    pub fn escape_sequence_permission(
+        &self,
+        domain_name: &str,
+        kind: EscapeSequenceKind,
+    ) -> EscapeSequencePermission {
+        if let Some(permission) = self.explicit_escape_sequence_permission(domain_name, kind) {
+            return permission;
+        }
+        match kind {
+            EscapeSequenceKind::ClipboardRead => self.osc52_clipboard_read.into(),
+            EscapeSequenceKind::FileTransfer if !self.allow_download_protocols => {
+                EscapeSequencePermission::Deny
+            }
+            _ => EscapeSequencePermission::Allow,
+        }
+    }
+}
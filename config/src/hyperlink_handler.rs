@@ -0,0 +1,97 @@
+use crate::Config;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// Where the target of the hyperlink under the mouse pointer is shown
+#[derive(FromDynamic, ToDynamic, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HyperlinkHoverDisplay {
+    /// The target is not shown
+    None,
+    /// The target is shown in the bottom left corner of the window
+    StatusLine,
+    /// The target is shown in a tooltip next to the mouse pointer
+    Tooltip,
+}
+
+impl Default for HyperlinkHoverDisplay {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+/// Specifies what happens when a hyperlink with a particular
+/// scheme is clicked.  The arguments of the command variants may
+/// contain `{uri}` and `{path}` placeholders; if neither is present,
+/// the uri is appended to the arguments.
+#[derive(FromDynamic, ToDynamic, Clone, Debug, PartialEq, Eq)]
+pub enum HyperlinkHandler {
+    /// Open the uri using the system default handler
+    OpenWithSystem,
+    /// Run a command in the background
+    Command(Vec<String>),
+    /// Run a command in a new pane split from the current pane
+    SplitPane(Vec<String>),
+    /// Run a command in a new tab
+    NewTab(Vec<String>),
+    /// Emit the named event, passing the window, pane and uri
+    EmitEvent(String),
+}
+
+/// An entry that is added to the context menu of hyperlinks
+#[derive(FromDynamic, ToDynamic, Clone, Debug, PartialEq, Eq)]
+pub struct HyperlinkMenuEntry {
+    pub label: String,
+    pub handler: HyperlinkHandler,
+    /// The uri schemes of the hyperlinks for which the entry is
+    /// shown.  It is shown for all hyperlinks when empty.
+    #[dynamic(default)]
+    pub schemes: Vec<String>,
+}
+
+impl HyperlinkMenuEntry {
+    pub fn applies_to(&self, uri: &str) -> bool {
+        if self.schemes.is_empty() {
+            return true;
+        }
+        match hyperlink_scheme(uri) {
+            Some(scheme) => self.schemes.iter().any(|s| s.eq_ignore_ascii_case(&scheme)),
+            None => false,
+        }
+    }
+}
+
+/// How a clicked `file` hyperlink is previewed within wezterm
+#[derive(FromDynamic, ToDynamic, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilePreview {
+    /// Show the file as text, with simple syntax highlighting
+    Text,
+    /// Show the file as an image
+    Image,
+}
+
+/// Returns the scheme portion of a uri, lowercased
+pub fn hyperlink_scheme(uri: &str) -> Option<String> {
+    let (scheme, _) = uri.split_once(':')?;
+    if scheme.is_empty()
+        || !scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    {
+        return None;
+    }
+    Some(scheme.to_ascii_lowercase())
+}
+
+impl Config {
+    /// Returns the handler configured for the scheme of the specified uri
+    pub fn hyperlink_handler(&self, uri: &str) -> Option<&HyperlinkHandler> {
+        let scheme = hyperlink_scheme(uri)?;
+        self.hyperlink_handlers.get(&scheme)
+    }
+
+    /// Returns the preview configured for the extension of the
+    /// specified path
+    pub fn file_preview(&self, path: &std::path::Path) -> Option<FilePreview> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        self.file_preview_extensions.get(&ext).copied()
+    }
+}
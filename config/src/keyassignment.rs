@@ -1,5 +1,7 @@
 use crate::default_true;
 use crate::keys::KeyNoAction;
+use crate::resource_limits::ResourceLimits;
+use crate::RestartOnExit;
 use luahelper::impl_lua_conversion_dynamic;
 use ordered_float::NotNan;
 use portable_pty::CommandBuilder;
@@ -7,6 +9,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::path::PathBuf;
+use wezterm_bidi::ParagraphDirectionHint;
 use wezterm_dynamic::{FromDynamic, FromDynamicOptions, ToDynamic, Value};
 use wezterm_input_types::{KeyCode, Modifiers};
 use wezterm_term::input::MouseButton;
@@ -192,6 +195,16 @@ pub struct SpawnCommand {
 
     #[dynamic(default)]
     pub domain: SpawnTabDomain,
+
+    /// Limits on the resources used by the processes spawned into
+    /// the pane.  Only honored by local domains.
+    #[dynamic(default)]
+    pub resource_limits: Option<ResourceLimits>,
+
+    /// Whether to re-run the command when it exits.  Only honored
+    /// by local domains.
+    #[dynamic(default)]
+    pub restart_on_exit: Option<RestartOnExit>,
 }
 impl_lua_conversion_dynamic!(SpawnCommand);
 
@@ -245,6 +258,8 @@ impl SpawnCommand {
             args: if args.is_empty() { None } else { Some(args) },
             set_environment_variables,
             cwd,
+            resource_limits: None,
+            restart_on_exit: None,
         })
     }
 }
@@ -304,6 +319,67 @@ impl Default for ClipboardCopyDestination {
     }
 }
 
+/// What is dragged out of the window by `StartSelectionDrag`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum SelectionDragFormat {
+    /// The selected text
+    Text,
+    /// A text file holding the selected text
+    File,
+}
+
+impl Default for SelectionDragFormat {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+/// How the rows of the selection are broken into lines when copying
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum CopyLineBreaks {
+    /// Rows that were wrapped because they were too long are joined,
+    /// and explicit line breaks are kept
+    JoinWrapped,
+    /// Each row of the selection becomes a line, as it was displayed
+    AsDisplayed,
+    /// The lines of each paragraph are joined with spaces into a
+    /// single line, and paragraphs are separated by a blank line
+    JoinParagraphs,
+}
+
+impl Default for CopyLineBreaks {
+    fn default() -> Self {
+        Self::JoinWrapped
+    }
+}
+
+/// An entry of the context menu of panes and the tab bar
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub struct ContextMenuEntry {
+    pub label: String,
+    pub action: KeyAssignment,
+}
+impl_lua_conversion_dynamic!(ContextMenuEntry);
+
+/// Adjustments made to the selected text when it is copied
+#[derive(Debug, Clone, Default, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub struct CopyOptions {
+    #[dynamic(default)]
+    pub destination: ClipboardCopyDestination,
+    #[dynamic(default)]
+    pub line_breaks: CopyLineBreaks,
+    /// Remove whitespace from the end of each line
+    #[dynamic(default)]
+    pub strip_trailing_whitespace: bool,
+    /// Drop the rows that only hold box drawing table borders, and
+    /// separate the columns of the remaining rows with tabs
+    #[dynamic(default)]
+    pub collapse_table_borders: bool,
+    /// Remove the text that shell integration marked as a prompt
+    #[dynamic(default)]
+    pub strip_prompts: bool,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
 pub enum ClipboardPasteSource {
     Clipboard,
@@ -432,6 +508,39 @@ pub struct QuickSelectArguments {
     pub scope_lines: Option<usize>,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub struct PaneBidiMode {
+    /// Overrides the bidi_enabled config
+    #[dynamic(default)]
+    pub enabled: Option<bool>,
+    /// Overrides the bidi_direction config
+    #[dynamic(default)]
+    pub direction: Option<ParagraphDirectionHint>,
+}
+
+/// The flow control mode of a serial port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum SerialFlowControl {
+    None,
+    Software,
+    Hardware,
+}
+
+impl std::str::FromStr for SerialFlowControl {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "software" | "xonxoff" => Ok(Self::Software),
+            "hardware" | "rtscts" => Ok(Self::Hardware),
+            _ => Err(format!(
+                "invalid flow control {}; expected one of None, Software or Hardware",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
 pub enum KeyAssignment {
     SpawnTab(SpawnTabDomain),
@@ -439,6 +548,9 @@ pub enum KeyAssignment {
     ToggleFullScreen,
     Copy,
     CopyTo(ClipboardCopyDestination),
+    CopyRichTextTo(ClipboardCopyDestination),
+    CopyWithOptions(CopyOptions),
+    ShowCopyMenu,
     Paste,
     PastePrimarySelection,
     PasteFrom(ClipboardPasteSource),
@@ -450,6 +562,10 @@ pub enum KeyAssignment {
     ResetFontAndWindowSize,
     ActivateTab(isize),
     ActivateLastTab,
+    ActivateLastPane,
+    ActivateLastWindow,
+    ShowRecentPanes,
+    ShowMuxClients,
     SendString(String),
     SendKey(KeyNoAction),
     Nop,
@@ -485,10 +601,12 @@ pub enum KeyAssignment {
     SelectTextAtMouseCursor(SelectionMode),
     ExtendSelectionToMouseCursor(SelectionMode),
     OpenLinkAtMouseCursor,
+    ShowContextMenu,
     ClearSelection,
     CompleteSelection(ClipboardCopyDestination),
     CompleteSelectionOrOpenLinkAtMouseCursor(ClipboardCopyDestination),
     StartWindowDrag,
+    StartSelectionDrag(SelectionDragFormat),
 
     AdjustPaneSize(PaneDirection, usize),
     ActivatePaneDirection(PaneDirection),
@@ -527,17 +645,127 @@ pub enum KeyAssignment {
     ClearKeyTableStack,
     DetachDomain(SpawnTabDomain),
     AttachDomain(String),
+    MoveTabToDomain(SpawnTabDomain),
+    TogglePaneInputTurn,
 
     CopyMode(CopyModeAssignment),
     RotatePanes(RotationDirection),
     SplitPane(SplitPane),
     PaneSelect(PaneSelectArguments),
     CharSelect(CharSelectArguments),
+    ShowWorkspaceManager,
+    ShowClipboardHistory,
 
     ResetTerminal,
+    RestartPane,
+    ShowNotificationCenter,
+    ToggleDoNotDisturb,
+    SetMark,
+    JumpBack,
+    JumpForward,
+    SetNamedMark(String),
+    GotoNamedMark(String),
+    ShowNamedMarks,
+    ActivateHintsMode,
+    SetPaneBidiMode(PaneBidiMode),
+    /// Sets the volume of the audible bell for the active pane,
+    /// as a percentage of audible_bell_volume
+    SetPaneBellVolume(u8),
+    ShowSerialPortSelector,
+    SetSerialBaudRate(usize),
+    SetSerialFlowControl(SerialFlowControl),
+    ToggleSerialDtr,
+    ToggleSerialRts,
+    ShowKubernetesPodSelector,
+    ShowContainerSelector,
+    /// Starts piping the output of the active pane into the input of
+    /// the adjacent pane in the specified direction, or stops doing so
+    /// if it is already being piped there
+    TogglePipeToPane(PaneDirection),
+    /// Starts writing the output of the active pane to a file,
+    /// or stops doing so if it is already being logged
+    TogglePaneLogging(PaneLogging),
+    /// Types the password for the prompt in the active pane,
+    /// as obtained from `password_sources`
+    FillPassword,
+    /// Reveals the text in the active pane that is masked by
+    /// `redaction_rules`, or masks it again if it is revealed
+    ToggleRedactionReveal,
+    /// Stops input from being delivered to the active pane, or to
+    /// the window, until `input_lock_passphrase` is typed
+    LockInput(InputLockScope),
+    /// Compares the active pane with the adjacent pane in the
+    /// specified direction, or stops comparing them
+    ToggleComparePanes(ComparePanes),
+    /// Toggles rendering unified diffs in the active pane with
+    /// highlighting of the words that changed
+    TogglePaneDiffMode,
+    /// Collapses or expands the diff hunk under the mouse cursor
+    ToggleDiffHunk,
+    /// Opens the output of the last command in the active pane in
+    /// a pager overlay
+    PageLastCommandOutput,
+    /// Copies the content of the active pane into a new read-only
+    /// pane, split off in the specified direction
+    SnapshotPane(PaneDirection),
 }
 impl_lua_conversion_dynamic!(KeyAssignment);
 
+impl KeyAssignment {
+    /// Returns the name of the assignment, as used with
+    /// `wezterm.action`, eg: `PasteFrom`
+    pub fn name(&self) -> String {
+        format!("{:?}", self)
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub struct PaneLogging {
+    /// Where to write the log.  Defaults to a file in the home
+    /// directory named for the pane and the time that logging started
+    #[dynamic(default)]
+    pub path: Option<String>,
+    #[dynamic(default)]
+    pub with_escapes: bool,
+    #[dynamic(default)]
+    pub max_size: Option<u64>,
+    #[dynamic(default = "default_log_max_files")]
+    pub max_files: usize,
+}
+
+impl Default for PaneLogging {
+    fn default() -> Self {
+        Self {
+            path: None,
+            with_escapes: false,
+            max_size: None,
+            max_files: default_log_max_files(),
+        }
+    }
+}
+
+fn default_log_max_files() -> usize {
+    5
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub struct ComparePanes {
+    /// Where the pane to compare with lies, relative to the active pane
+    pub direction: PaneDirection,
+    /// Whether to highlight the lines that differ between the panes
+    #[dynamic(default = "default_true")]
+    pub highlight_differences: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum InputLockScope {
+    Pane,
+    Window,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
 pub struct SplitPane {
     pub direction: PaneDirection,
@@ -596,6 +824,11 @@ pub enum CopyModeAssignment {
     PriorMatchPage,
     NextMatchPage,
     CycleMatchType,
+    ToggleCaseSensitivity,
+    ToggleRegex,
+    ToggleWholeWord,
+    CopyAllMatches(ClipboardCopyDestination),
+    EmitMatches(String),
     ClearPattern,
     EditPattern,
     AcceptPattern,
@@ -0,0 +1,120 @@
+use crate::config::validate_domain_name;
+use crate::*;
+use luahelper::impl_lua_conversion_dynamic;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// A domain whose panes run a program inside a container of a
+/// kubernetes pod, by way of `kubectl exec`
+#[derive(Default, Debug, Clone, FromDynamic, ToDynamic)]
+pub struct KubernetesDomain {
+    /// The name of this specific domain.  Must be unique amongst
+    /// all types of domain in the configuration file.
+    #[dynamic(validate = "validate_domain_name")]
+    pub name: String,
+    /// The kubeconfig context to use; defaults to the current context
+    pub context: Option<String>,
+    /// The namespace of the pod; defaults to that of the context
+    pub namespace: Option<String>,
+    pub pod: String,
+    /// The container within the pod; defaults to the first container
+    pub container: Option<String>,
+    /// The program to run when no command is specified
+    pub default_prog: Option<Vec<String>>,
+    /// Whether to re-run `kubectl exec` when it fails, which happens
+    /// when the connection to the cluster is lost
+    #[dynamic(default = "default_true")]
+    pub reattach: bool,
+    /// How long to wait before reattaching
+    #[dynamic(default = "default_reattach_delay_ms")]
+    pub reattach_delay_ms: u64,
+}
+impl_lua_conversion_dynamic!(KubernetesDomain);
+
+impl KubernetesDomain {
+    /// Returns the restart policy that implements `reattach`
+    pub fn restart_on_exit(&self) -> Option<RestartOnExit> {
+        if self.reattach {
+            Some(RestartOnExit {
+                when: RestartCondition::OnFailure,
+                delay_ms: self.reattach_delay_ms,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Builds the kubectl command line that runs `args`, or the
+    /// default program, in the container
+    pub fn exec_argv(&self, kubectl: &str, term: &str, args: &[String]) -> Vec<String> {
+        let mut argv = vec![kubectl.to_string()];
+        if let Some(context) = &self.context {
+            argv.push("--context".to_string());
+            argv.push(context.clone());
+        }
+        if let Some(namespace) = &self.namespace {
+            argv.push("--namespace".to_string());
+            argv.push(namespace.clone());
+        }
+        argv.push("exec".to_string());
+        argv.push("--stdin".to_string());
+        argv.push("--tty".to_string());
+        argv.push(self.pod.clone());
+        if let Some(container) = &self.container {
+            argv.push("--container".to_string());
+            argv.push(container.clone());
+        }
+        argv.push("--".to_string());
+        // kubectl doesn't propagate TERM into the container
+        argv.push("env".to_string());
+        argv.push(format!("TERM={}", term));
+        if !args.is_empty() {
+            argv.extend(args.iter().cloned());
+        } else if let Some(prog) = &self.default_prog {
+            argv.extend(prog.iter().cloned());
+        } else {
+            argv.extend(default_container_shell());
+        }
+        argv
+    }
+}
+
+fn default_reattach_delay_ms() -> u64 {
+    1000
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exec_argv() {
+        let dom = KubernetesDomain {
+            name: "k8s".to_string(),
+            context: Some("prod".to_string()),
+            namespace: None,
+            pod: "web-0".to_string(),
+            container: Some("app".to_string()),
+            default_prog: None,
+            reattach: false,
+            reattach_delay_ms: 0,
+        };
+        assert_eq!(
+            dom.exec_argv("kubectl", "xterm", &["top".to_string()]),
+            vec![
+                "kubectl",
+                "--context",
+                "prod",
+                "exec",
+                "--stdin",
+                "--tty",
+                "web-0",
+                "--container",
+                "app",
+                "--",
+                "env",
+                "TERM=xterm",
+                "top"
+            ]
+        );
+    }
+}
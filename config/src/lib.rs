@@ -7,7 +7,7 @@ use ordered_float::NotNan;
 use smol::channel::{Receiver, Sender};
 use smol::prelude::*;
 use std::cell::RefCell;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::OsString;
 use std::fs::DirBuilder;
 #[cfg(unix)]
@@ -19,50 +19,86 @@ use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use wezterm_dynamic::{FromDynamic, FromDynamicOptions, ToDynamic, UnknownFieldAction, Value};
 
+mod authentication;
 mod background;
 mod bell;
 mod color;
 mod config;
+mod container;
 mod daemon;
+mod escape_policy;
 mod exec_domain;
 mod font;
 mod frontend;
+mod hyperlink_handler;
 pub mod keyassignment;
 mod keys;
+mod kubernetes;
 pub mod lua;
 pub mod meta;
+mod muxaccess;
+mod notification;
+mod output_watcher;
+mod password;
+mod redaction;
+mod resource_limits;
 mod scheme_data;
 mod ssh;
+mod tcp;
 mod terminal;
+mod titlebar;
 mod tls;
+mod touchbar;
+mod udp;
 mod units;
 mod unix;
 mod version;
+mod workspace;
 mod wsl;
+mod wss;
 
 pub use crate::config::*;
+pub use authentication::*;
 pub use background::*;
 pub use bell::*;
 pub use color::*;
+pub use container::*;
 pub use daemon::*;
+pub use escape_policy::*;
 pub use exec_domain::*;
 pub use font::*;
 pub use frontend::*;
+pub use hyperlink_handler::*;
 pub use keys::*;
+pub use kubernetes::*;
+pub use muxaccess::*;
+pub use notification::*;
+pub use output_watcher::*;
+pub use password::*;
+pub use redaction::*;
+pub use resource_limits::*;
 pub use ssh::*;
+pub use tcp::*;
 pub use terminal::*;
+pub use titlebar::*;
 pub use tls::*;
+pub use touchbar::*;
+pub use udp::*;
 pub use units::*;
 pub use unix::*;
 pub use version::*;
+pub use workspace::*;
 pub use wsl::*;
+pub use wss::*;
 
 type ErrorCallback = fn(&str);
 
 lazy_static! {
     pub static ref HOME_DIR: PathBuf = dirs_next::home_dir().expect("can't find HOME dir");
     pub static ref CONFIG_DIR: PathBuf = xdg_config_home();
+    pub static ref DATA_DIR: PathBuf = data_dir();
     pub static ref RUNTIME_DIR: PathBuf = compute_runtime_dir().unwrap();
+    pub static ref CACHE_DIR: PathBuf = cache_dir();
     static ref CONFIG: Configuration = Configuration::new();
     static ref CONFIG_FILE_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
     static ref CONFIG_SKIP: AtomicBool = AtomicBool::new(false);
@@ -195,6 +231,20 @@ where
     ConfigSubscription(CONFIG.subscribe(subscriber))
 }
 
+/// Watch path for changes using the same watcher that is used to
+/// reload the configuration; changes to path don't cause the
+/// configuration to be reloaded unless it is also one of the config
+/// files.  If path is a directory, changes to its immediate children
+/// are reported.
+/// subscriber is called from the watcher thread with the list of
+/// paths that changed, and should return false to stop watching.
+pub fn subscribe_to_path_changes<F>(path: PathBuf, subscriber: F)
+where
+    F: Fn(Vec<PathBuf>) -> bool + 'static + Send,
+{
+    CONFIG.subscribe_to_path_changes(path, subscriber)
+}
+
 /// Spawn a future that will run with an optional Lua state from the most
 /// recently loaded lua configuration.
 /// The `func` argument is passed the lua state and must return a Future.
@@ -330,6 +380,13 @@ pub fn create_user_owned_dirs(p: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn data_dir() -> PathBuf {
+    match dirs_next::data_dir() {
+        Some(p) => p.join("wezterm"),
+        None => HOME_DIR.join(".local").join("share").join("wezterm"),
+    }
+}
+
 fn xdg_config_home() -> PathBuf {
     match std::env::var_os("XDG_CONFIG_HOME").map(|s| PathBuf::from(s).join("wezterm")) {
         Some(p) => p,
@@ -337,6 +394,13 @@ fn xdg_config_home() -> PathBuf {
     }
 }
 
+fn cache_dir() -> PathBuf {
+    match dirs_next::cache_dir() {
+        Some(p) => p.join("wezterm"),
+        None => HOME_DIR.join(".cache").join("wezterm"),
+    }
+}
+
 pub fn set_config_file_override(path: &Path) {
     CONFIG_FILE_OVERRIDE
         .lock()
@@ -403,6 +467,9 @@ struct ConfigInner {
     generation: usize,
     watcher: Option<notify::RecommendedWatcher>,
     subscribers: HashMap<usize, Box<dyn Fn() -> bool + Send>>,
+    /// The paths that cause the config to be reloaded
+    config_watch_paths: HashSet<PathBuf>,
+    path_subscribers: Vec<(PathBuf, Box<dyn Fn(Vec<PathBuf>) -> bool + Send>)>,
 }
 
 impl ConfigInner {
@@ -413,6 +480,8 @@ impl ConfigInner {
             generation: 0,
             watcher: None,
             subscribers: HashMap::new(),
+            config_watch_paths: HashSet::new(),
+            path_subscribers: vec![],
         }
     }
 
@@ -435,11 +504,55 @@ impl ConfigInner {
     }
 
     fn watch_path(&mut self, path: PathBuf) {
+        self.config_watch_paths.insert(path.clone());
+        self.watch(&path);
+    }
+
+    fn subscribe_to_path_changes(
+        &mut self,
+        path: PathBuf,
+        subscriber: Box<dyn Fn(Vec<PathBuf>) -> bool + Send>,
+    ) {
+        self.watch(&path);
+        self.path_subscribers.push((path, subscriber));
+    }
+
+    /// Dispatches the changed paths to the path subscribers.
+    /// Returns true if the configuration needs to be reloaded, which
+    /// is the case if any of the paths belong to the configuration,
+    /// or if they don't belong to any of the subscribers.
+    fn paths_changed(&mut self, paths: &[PathBuf]) -> bool {
+        fn is_watched(watched: &Path, changed: &Path) -> bool {
+            changed == watched || changed.parent() == Some(watched)
+        }
+
+        let needs_reload = paths.iter().any(|changed| {
+            self.config_watch_paths
+                .iter()
+                .any(|watched| is_watched(watched, changed))
+                || !self
+                    .path_subscribers
+                    .iter()
+                    .any(|(watched, _)| is_watched(watched, changed))
+        });
+
+        self.path_subscribers.retain(|(watched, subscriber)| {
+            let changed: Vec<PathBuf> = paths
+                .iter()
+                .filter(|changed| is_watched(watched, changed))
+                .cloned()
+                .collect();
+            changed.is_empty() || subscriber(changed)
+        });
+
+        needs_reload
+    }
+
+    fn watch(&mut self, path: &Path) {
         if self.watcher.is_none() {
             let (tx, rx) = std::sync::mpsc::channel();
             const DELAY: Duration = Duration::from_millis(200);
             let watcher = notify::recommended_watcher(tx).unwrap();
-            let path = path.clone();
 
             std::thread::spawn(move || {
                 // block until we get an event
@@ -468,8 +581,8 @@ impl ConfigInner {
                                 }
                                 paths.sort();
                                 paths.dedup();
-                                log::debug!("paths {:?} changed, reload config", path);
-                                reload();
+                                log::debug!("paths {:?} changed", paths);
+                                CONFIG.paths_changed(paths);
                             }
                         }
                         Err(_) => {
@@ -483,7 +596,7 @@ impl ConfigInner {
         if let Some(watcher) = self.watcher.as_mut() {
             use notify::Watcher;
             watcher
-                .watch(&path, notify::RecursiveMode::NonRecursive)
+                .watch(path, notify::RecursiveMode::NonRecursive)
                 .ok();
         }
     }
@@ -644,6 +757,24 @@ impl Configuration {
         inner.unsub(sub_id);
     }
 
+    fn subscribe_to_path_changes<F>(&self, path: PathBuf, subscriber: F)
+    where
+        F: Fn(Vec<PathBuf>) -> bool + 'static + Send,
+    {
+        let mut inner = self.inner.lock().unwrap();
+        inner.subscribe_to_path_changes(path, Box::new(subscriber));
+    }
+
+    fn paths_changed(&self, paths: Vec<PathBuf>) {
+        let needs_reload = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.paths_changed(&paths)
+        };
+        if needs_reload {
+            self.reload();
+        }
+    }
+
     /// Reset the configuration to defaults
     pub fn use_defaults(&self) {
         let mut inner = self.inner.lock().unwrap();
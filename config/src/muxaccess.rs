@@ -0,0 +1,146 @@
+use luahelper::impl_lua_conversion_dynamic;
+use serde::{Deserialize, Serialize};
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// Describes what a mux client is allowed to do when it connects to
+/// this instance in server mode.  Rules are matched against how the
+/// server authenticated the client, rather than the username and
+/// hostname that the client reports, as the latter can be chosen by
+/// the client.
+#[derive(Debug, Clone, Default, PartialEq, Eq, FromDynamic, ToDynamic, Serialize, Deserialize)]
+pub struct MuxClientPermission {
+    /// If set, the rule only applies to clients that connected via
+    /// the tls, wss or udp server that has this `bind_address`
+    #[dynamic(default)]
+    pub listener: Option<String>,
+
+    /// When true, all of the requests of the client are refused
+    #[dynamic(default)]
+    pub deny: bool,
+
+    /// When true, the client can see the panes but cannot send input,
+    /// resize, spawn, close or otherwise change anything
+    #[dynamic(default)]
+    pub read_only: bool,
+
+    /// If not empty, the client can only see and interact with the
+    /// panes of windows that belong to one of these workspaces
+    #[dynamic(default)]
+    pub workspaces: Vec<String>,
+}
+impl_lua_conversion_dynamic!(MuxClientPermission);
+
+impl MuxClientPermission {
+    /// The permission of a client that matches none of the rules
+    pub fn denied() -> Self {
+        Self {
+            deny: true,
+            ..Default::default()
+        }
+    }
+
+    /// Returns true if this rule applies to a client that connected
+    /// via `listener`
+    pub fn matches(&self, listener: &str) -> bool {
+        match &self.listener {
+            Some(wanted) => wanted == listener,
+            None => true,
+        }
+    }
+
+    /// Returns true if panes in `workspace` are visible to the client
+    pub fn allows_workspace(&self, workspace: &str) -> bool {
+        !self.deny && (self.workspaces.is_empty() || self.workspaces.iter().any(|w| w == workspace))
+    }
+}
+
+/// What happens to the clients that are already attached to a
+/// workspace when another client attaches to it
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq)]
+pub enum MuxClientAttachPolicy {
+    /// All of the clients see the same panes and can interact with them
+    Mirror,
+    /// The clients that were already attached are detached
+    Steal,
+}
+
+impl Default for MuxClientAttachPolicy {
+    fn default() -> Self {
+        Self::Mirror
+    }
+}
+
+/// Returns the permission of a client that connected via `listener`:
+/// the first rule in `rules` that applies to it.  When there are rules
+/// but none of them apply, the client is denied, so a rule without
+/// `listener` must be added at the end of the list to give other
+/// clients access.  None means that the client is unrestricted.
+///
+/// The clients of a listener can't be told apart: the client
+/// certificates of the TLS and websocket servers all carry the name
+/// of the user running the server, and the udp and websocket servers
+/// authenticate with a key or token that is shared by their clients.
+pub fn mux_client_permission(
+    rules: &[MuxClientPermission],
+    listener: &str,
+) -> Option<MuxClientPermission> {
+    if rules.is_empty() {
+        return None;
+    }
+    Some(
+        rules
+            .iter()
+            .find(|rule| rule.matches(listener))
+            .cloned()
+            .unwrap_or_else(MuxClientPermission::denied),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matching() {
+        let rules = vec![
+            MuxClientPermission {
+                listener: Some("0.0.0.0:8080".to_string()),
+                ..Default::default()
+            },
+            MuxClientPermission {
+                listener: Some("0.0.0.0:8081".to_string()),
+                read_only: true,
+                workspaces: vec!["demo".to_string()],
+                ..Default::default()
+            },
+        ];
+
+        let rule = mux_client_permission(&rules, "0.0.0.0:8080").unwrap();
+        assert_eq!(rule, rules[0]);
+        assert!(!rule.read_only);
+        assert!(rule.allows_workspace("default"));
+
+        let rule = mux_client_permission(&rules, "0.0.0.0:8081").unwrap();
+        assert!(rule.read_only);
+        assert!(rule.allows_workspace("demo"));
+        assert!(!rule.allows_workspace("default"));
+
+        // Clients that match none of the rules are refused
+        let rule = mux_client_permission(&rules, "0.0.0.0:9090").unwrap();
+        assert!(rule.deny);
+        assert!(!rule.allows_workspace("demo"));
+        assert_eq!(rule, MuxClientPermission::denied());
+
+        // unless a catch-all rule gives them access
+        let mut rules = rules;
+        rules.push(MuxClientPermission {
+            read_only: true,
+            ..Default::default()
+        });
+        let rule = mux_client_permission(&rules, "0.0.0.0:9090").unwrap();
+        assert!(!rule.deny);
+        assert!(rule.read_only);
+
+        assert!(mux_client_permission(&[], "0.0.0.0:9090").is_none());
+    }
+}
@@ -0,0 +1,264 @@
+use crate::Config;
+use std::convert::TryFrom;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// The kinds of event that are recorded in the notification center
+#[derive(FromDynamic, ToDynamic, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotificationSource {
+    /// Toast notifications requested by applications
+    Toast,
+    /// The bell
+    Bell,
+    /// A command reported a non-zero exit status
+    Command,
+    /// The connection to a multiplexer domain was lost
+    Connection,
+}
+
+impl NotificationSource {
+    pub const ALL: &'static [Self] = &[Self::Toast, Self::Bell, Self::Command, Self::Connection];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Toast => "Toast",
+            Self::Bell => "Bell",
+            Self::Command => "Command",
+            Self::Connection => "Connection",
+        }
+    }
+}
+
+#[derive(FromDynamic, ToDynamic, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotificationAction {
+    /// The notification is delivered as usual
+    Allow,
+    /// The notification is only recorded in the notification center;
+    /// no system toast is shown and the bell is neither audible
+    /// nor visual
+    NotificationCenterOnly,
+    /// The notification is discarded
+    Suppress,
+}
+
+/// A time of day, expressed as `"HH:MM"` in the local timezone
+#[derive(FromDynamic, ToDynamic, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[dynamic(try_from = "String", into = "String")]
+pub struct TimeOfDay {
+    minutes: u16,
+}
+
+impl TimeOfDay {
+    pub fn new(hour: u16, minute: u16) -> Self {
+        Self {
+            minutes: hour * 60 + minute,
+        }
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = anyhow::Error;
+    fn try_from(s: String) -> anyhow::Result<TimeOfDay> {
+        let parse = || -> Option<TimeOfDay> {
+            let (hour, minute) = s.split_once(':')?;
+            let hour: u16 = hour.parse().ok()?;
+            let minute: u16 = minute.parse().ok()?;
+            if hour < 24 && minute < 60 {
+                Some(TimeOfDay::new(hour, minute))
+            } else {
+                None
+            }
+        };
+        parse().ok_or_else(|| anyhow::anyhow!("failed to parse {} as HH:MM", s))
+    }
+}
+
+impl From<&TimeOfDay> for String {
+    fn from(t: &TimeOfDay) -> String {
+        format!("{:02}:{:02}", t.minutes / 60, t.minutes % 60)
+    }
+}
+
+/// A range of times of day; the range wraps around midnight
+/// if `end` is earlier than `start`
+#[derive(FromDynamic, ToDynamic, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeRange {
+    pub start: TimeOfDay,
+    pub end: TimeOfDay,
+}
+
+impl TimeRange {
+    pub fn contains(&self, t: TimeOfDay) -> bool {
+        if self.start <= self.end {
+            t >= self.start && t < self.end
+        } else {
+            t >= self.start || t < self.end
+        }
+    }
+}
+
+/// A rule that decides how matching notifications are delivered.
+/// Conditions that are not specified match anything.
+#[derive(FromDynamic, ToDynamic, Clone, Debug, PartialEq, Eq)]
+pub struct NotificationRule {
+    /// The kinds of notification that the rule applies to;
+    /// if empty, it applies to all of them
+    #[dynamic(default)]
+    pub sources: Vec<NotificationSource>,
+    /// Whether the window containing the pane is focused
+    #[dynamic(default)]
+    pub window_focused: Option<bool>,
+    /// Whether the window containing the pane is full screen
+    #[dynamic(default)]
+    pub window_fullscreen: Option<bool>,
+    /// The local time of day
+    #[dynamic(default)]
+    pub time_of_day: Option<TimeRange>,
+    /// The name of the foreground process of the pane; either its
+    /// full path or just the file name
+    #[dynamic(default)]
+    pub foreground_process: Option<String>,
+    pub action: NotificationAction,
+}
+
+/// The circumstances of a notification that rules are matched against
+#[derive(Debug, Clone)]
+pub struct NotificationContext<'a> {
+    pub source: NotificationSource,
+    pub window_focused: bool,
+    pub window_fullscreen: bool,
+    pub time_of_day: TimeOfDay,
+    pub foreground_process: Option<&'a str>,
+}
+
+impl NotificationRule {
+    pub fn matches(&self, ctx: &NotificationContext) -> bool {
+        if !self.sources.is_empty() && !self.sources.contains(&ctx.source) {
+            return false;
+        }
+        if matches!(self.window_focused, Some(focused) if focused != ctx.window_focused) {
+            return false;
+        }
+        if matches!(self.window_fullscreen, Some(fullscreen) if fullscreen != ctx.window_fullscreen)
+        {
+            return false;
+        }
+        if matches!(self.time_of_day, Some(range) if !range.contains(ctx.time_of_day)) {
+            return false;
+        }
+        if let Some(wanted) = &self.foreground_process {
+            let matched = ctx
+                .foreground_process
+                .map(|proc_name| {
+                    let base = std::path::Path::new(proc_name)
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .unwrap_or(proc_name);
+                    proc_name == wanted || base == wanted
+                })
+                .unwrap_or(false);
+            if !matched {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl Config {
+    /// Returns the action of the first of the `notification_rules`
+    /// that matches, or `Allow` if none of them match.
+    /// When do-not-disturb is active, notifications that would be
+    /// allowed are only recorded in the notification center.
+    pub fn notification_action(
+        &self,
+        ctx: &NotificationContext,
+        do_not_disturb: bool,
+    ) -> NotificationAction {
+        let action = self
+            .notification_rules
+            .iter()
+            .find(|rule| rule.matches(ctx))
+            .map(|rule| rule.action)
+            .unwrap_or(NotificationAction::Allow);
+        if do_not_disturb && action == NotificationAction::Allow {
+            NotificationAction::NotificationCenterOnly
+        } else {
+            action
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ctx(source: NotificationSource) -> NotificationContext<'static> {
+        NotificationContext {
+            source,
+            window_focused: false,
+            window_fullscreen: false,
+            time_of_day: TimeOfDay::new(12, 0),
+            foreground_process: Some("/usr/bin/vim"),
+        }
+    }
+
+    fn rule(action: NotificationAction) -> NotificationRule {
+        NotificationRule {
+            sources: vec![],
+            window_focused: None,
+            window_fullscreen: None,
+            time_of_day: None,
+            foreground_process: None,
+            action,
+        }
+    }
+
+    #[test]
+    fn time_of_day() {
+        let t = TimeOfDay::try_from("07:30".to_string()).unwrap();
+        assert_eq!(t, TimeOfDay::new(7, 30));
+        assert_eq!(String::from(&t), "07:30");
+        assert!(TimeOfDay::try_from("24:00".to_string()).is_err());
+        assert!(TimeOfDay::try_from("noon".to_string()).is_err());
+
+        let overnight = TimeRange {
+            start: TimeOfDay::new(22, 0),
+            end: TimeOfDay::new(7, 0),
+        };
+        assert!(overnight.contains(TimeOfDay::new(23, 0)));
+        assert!(overnight.contains(TimeOfDay::new(6, 59)));
+        assert!(!overnight.contains(TimeOfDay::new(7, 0)));
+        assert!(!overnight.contains(TimeOfDay::new(12, 0)));
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let mut config = Config::default_config();
+        let mut vim = rule(NotificationAction::Suppress);
+        vim.foreground_process.replace("vim".to_string());
+        vim.sources.push(NotificationSource::Bell);
+        let mut unfocused = rule(NotificationAction::NotificationCenterOnly);
+        unfocused.window_focused.replace(false);
+        config.notification_rules = vec![vim, unfocused];
+
+        assert_eq!(
+            config.notification_action(&ctx(NotificationSource::Bell), false),
+            NotificationAction::Suppress
+        );
+        assert_eq!(
+            config.notification_action(&ctx(NotificationSource::Toast), false),
+            NotificationAction::NotificationCenterOnly
+        );
+
+        let mut focused = ctx(NotificationSource::Toast);
+        focused.window_focused = true;
+        assert_eq!(
+            config.notification_action(&focused, false),
+            NotificationAction::Allow
+        );
+        assert_eq!(
+            config.notification_action(&focused, true),
+            NotificationAction::NotificationCenterOnly
+        );
+    }
+}
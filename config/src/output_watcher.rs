@@ -0,0 +1,29 @@
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// Emits a lua event whenever a line of output that matches
+/// `regex` is written to a pane
+#[derive(FromDynamic, ToDynamic, Clone, Debug, PartialEq, Eq)]
+pub struct OutputWatcherRule {
+    /// The regular expression that is matched against each
+    /// line of output
+    pub regex: String,
+    /// The name of the event that is emitted, with the pane
+    /// and a table of the captures as its arguments
+    pub event: String,
+}
+
+/// Sends `response` to a pane whenever its output matches `regex`
+#[derive(FromDynamic, ToDynamic, Clone, Debug, PartialEq, Eq)]
+pub struct AutoResponseRule {
+    /// The regular expression that is matched against the output,
+    /// including the line that holds the cursor
+    pub regex: String,
+    /// The text that is sent to the pane as though it were typed
+    pub response: String,
+    /// How long to wait before sending the response
+    #[dynamic(default)]
+    pub delay_ms: u64,
+    /// The most responses that the rule makes in each pane
+    #[dynamic(default)]
+    pub max_count: Option<usize>,
+}
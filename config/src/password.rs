@@ -0,0 +1,40 @@
+use crate::default_true;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// Where the `FillPassword` key assignment obtains a password
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum PasswordBackend {
+    /// The OS keychain: Keychain on macOS, the Credential Manager
+    /// on Windows and the Secret Service on other systems
+    Keychain { service: String, account: String },
+    /// The first line of `pass show NAME`
+    Pass { name: String },
+    /// The output of `op read REFERENCE`, the 1Password CLI
+    OnePassword { reference: String },
+    /// The first line of the output of an arbitrary command
+    Command { args: Vec<String> },
+}
+
+/// Selects the backend that provides the password for a prompt
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub struct PasswordSource {
+    /// If set, only prompts that match this regular expression use
+    /// this source
+    #[dynamic(default)]
+    pub prompt: Option<String>,
+    /// If not empty, only prompts in panes that belong to one of
+    /// these domains use this source
+    #[dynamic(default)]
+    pub domains: Vec<String>,
+    pub backend: PasswordBackend,
+    /// Whether to press Enter after typing the password
+    #[dynamic(default = "default_true")]
+    pub submit: bool,
+}
+
+pub fn default_password_prompts() -> Vec<String> {
+    [r"(?i)password[^:\n]*:\s*$", r"(?i)passphrase[^:\n]*:\s*$"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
@@ -0,0 +1,16 @@
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// Masks the text that matches `regex` when it is displayed,
+/// copied or exported, until it is revealed
+#[derive(FromDynamic, ToDynamic, Clone, Debug, PartialEq, Eq)]
+pub struct RedactionRule {
+    /// The regular expression that is matched against each line
+    pub regex: String,
+    /// The character that replaces each cell of the matching text
+    #[dynamic(default = "default_mask")]
+    pub mask: char,
+}
+
+fn default_mask() -> char {
+    '*'
+}
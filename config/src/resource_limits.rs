@@ -0,0 +1,28 @@
+use crate::default_true;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// Limits on the resources used by the processes that are spawned
+/// into a local pane.  The processes are placed into a cgroup on
+/// Linux, or a Job Object on Windows.
+#[derive(FromDynamic, ToDynamic, Clone, Debug, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// The most memory that the processes may use, in megabytes
+    pub memory_max_mb: Option<u64>,
+    /// The most CPU time that the processes may use, as a
+    /// percentage of a single CPU; 200 allows the use of two CPUs
+    pub cpu_max_percent: Option<u32>,
+    /// Whether all of the processes are killed when the pane closes,
+    /// rather than only the process that was spawned into it
+    #[dynamic(default = "default_true")]
+    pub kill_on_close: bool,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            memory_max_mb: None,
+            cpu_max_percent: None,
+            kill_on_close: true,
+        }
+    }
+}
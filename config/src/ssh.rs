@@ -100,6 +100,23 @@ pub struct SshDomain {
 
     #[dynamic(default)]
     pub assume_shell: Shell,
+
+    /// Identifies an entry in the OS keychain that holds the password
+    /// or key passphrase to use when authenticating with the remote host
+    pub keychain_secret: Option<KeychainSecret>,
+
+    /// When `term` is set to `wezterm`, install the bundled terminfo
+    /// entry on the remote host if it doesn't already have it
+    #[dynamic(default = "default_true")]
+    pub install_terminfo: bool,
+}
+
+/// Identifies an entry in the OS keychain (Keychain on macOS,
+/// Secret Service on Linux, Credential Manager on Windows)
+#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+pub struct KeychainSecret {
+    pub service: String,
+    pub account: String,
 }
 
 #[derive(Clone, Debug)]
@@ -0,0 +1,57 @@
+use crate::config::validate_domain_name;
+use crate::*;
+use luahelper::impl_lua_conversion_dynamic;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// A domain whose panes are connected directly to a TCP socket,
+/// rather than to a local or remote process
+#[derive(Default, Debug, Clone, FromDynamic, ToDynamic)]
+pub struct TcpDomain {
+    /// The name of this specific domain.  Must be unique amongst
+    /// all types of domain in the configuration file.
+    #[dynamic(validate = "validate_domain_name")]
+    pub name: String,
+
+    /// identifies the host:port pair of the remote endpoint.
+    /// When `telnet` is enabled the port defaults to 23.
+    pub remote_address: String,
+
+    /// Whether to perform telnet option negotiation with the
+    /// remote endpoint.  When false, the raw bytes from the socket
+    /// are passed through to the terminal.
+    #[dynamic(default)]
+    pub telnet: bool,
+
+    /// Whether to wrap the connection in TLS
+    #[dynamic(default)]
+    pub tls: bool,
+
+    /// the path to an x509 PEM encoded CA chain file used to
+    /// verify the certificate presented by the remote endpoint
+    pub pem_ca: Option<PathBuf>,
+
+    /// explicitly control whether the certificate presented by the
+    /// remote endpoint must match the hostname portion of
+    /// `remote_address`.  This weakens the security of the TLS channel
+    /// and should only be used with equipment whose certificate cannot
+    /// be changed.
+    #[dynamic(default)]
+    pub accept_invalid_hostnames: bool,
+
+    /// The terminal type that is reported to a telnet server
+    #[dynamic(default = "default_tcp_term")]
+    pub term: String,
+
+    /// How long to wait for the connection to be established
+    #[dynamic(default = "default_tcp_connect_timeout")]
+    pub connect_timeout: Duration,
+}
+impl_lua_conversion_dynamic!(TcpDomain);
+
+fn default_tcp_term() -> String {
+    "xterm-256color".to_string()
+}
+
+fn default_tcp_connect_timeout() -> Duration {
+    Duration::from_secs(10)
+}
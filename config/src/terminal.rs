@@ -67,6 +67,10 @@ impl wezterm_term::TerminalConfiguration for TermConfig {
         self.configuration().enable_kitty_graphics
     }
 
+    fn enable_vector_graphics(&self) -> bool {
+        self.configuration().enable_vector_graphics
+    }
+
     fn enable_kitty_keyboard(&self) -> bool {
         self.configuration().enable_kitty_keyboard
     }
@@ -101,6 +105,10 @@ impl wezterm_term::TerminalConfiguration for TermConfig {
         self.configuration().normalize_output_to_unicode_nfc
     }
 
+    fn grapheme_clustering(&self) -> bool {
+        self.configuration().grapheme_clustering
+    }
+
     fn bidi_mode(&self) -> BidiMode {
         let config = self.configuration();
         BidiMode {
@@ -0,0 +1,79 @@
+use crate::keyassignment::KeyAssignment;
+use luahelper::impl_lua_conversion_dynamic;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// A button shown in the tab bar when `window_decorations` includes
+/// `INTEGRATED_BUTTONS`
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum IntegratedTitleButton {
+    /// Minimizes the window
+    Hide,
+    /// Toggles between the maximized and normal window state
+    Maximize,
+    /// Closes the window
+    Close,
+    /// A button that performs an action when clicked
+    Custom(CustomTitleButton),
+}
+impl_lua_conversion_dynamic!(IntegratedTitleButton);
+
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub struct CustomTitleButton {
+    pub glyph: String,
+    pub action: KeyAssignment,
+}
+impl_lua_conversion_dynamic!(CustomTitleButton);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum IntegratedTitleButtonAlignment {
+    Left,
+    Right,
+}
+impl_lua_conversion_dynamic!(IntegratedTitleButtonAlignment);
+
+impl Default for IntegratedTitleButtonAlignment {
+    fn default() -> Self {
+        Self::Right
+    }
+}
+
+/// The glyphs used to draw the built-in integrated title buttons
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub struct IntegratedTitleButtonGlyphs {
+    #[dynamic(default = "default_hide_glyph")]
+    pub hide: String,
+    #[dynamic(default = "default_maximize_glyph")]
+    pub maximize: String,
+    #[dynamic(default = "default_restore_glyph")]
+    pub restore: String,
+    #[dynamic(default = "default_close_glyph")]
+    pub close: String,
+}
+impl_lua_conversion_dynamic!(IntegratedTitleButtonGlyphs);
+
+impl Default for IntegratedTitleButtonGlyphs {
+    fn default() -> Self {
+        Self {
+            hide: default_hide_glyph(),
+            maximize: default_maximize_glyph(),
+            restore: default_restore_glyph(),
+            close: default_close_glyph(),
+        }
+    }
+}
+
+fn default_hide_glyph() -> String {
+    "\u{2212}".to_string()
+}
+
+fn default_maximize_glyph() -> String {
+    "\u{25a1}".to_string()
+}
+
+fn default_restore_glyph() -> String {
+    "\u{2750}".to_string()
+}
+
+fn default_close_glyph() -> String {
+    "\u{2715}".to_string()
+}
@@ -94,6 +94,25 @@ pub struct TlsDomainClient {
     /// instead.
     #[dynamic(default)]
     pub overlay_lag_indicator: bool,
+
+    /// If true, and the certificate presented by the server cannot be
+    /// verified against the configured CAs, prompt to trust it on first
+    /// use and pin its fingerprint.  Subsequent connections will fail
+    /// if the server presents a different certificate that can't be
+    /// verified either.  A certificate that can be verified replaces
+    /// the pinned one.
+    #[dynamic(default)]
+    pub pin_server_fingerprint: bool,
+
+    /// When the client certificate that was obtained via
+    /// `bootstrap_via_ssh` is due to expire within this duration,
+    /// automatically request a fresh one from the server.
+    #[dynamic(default = "default_client_cert_renewal_threshold")]
+    pub client_cert_renewal_threshold: Duration,
+}
+
+fn default_client_cert_renewal_threshold() -> Duration {
+    Duration::from_secs(7 * 86400)
 }
 
 impl TlsDomainClient {
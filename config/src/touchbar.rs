@@ -0,0 +1,24 @@
+use crate::keyassignment::KeyAssignment;
+use luahelper::impl_lua_conversion_dynamic;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// An element of the macOS Touch Bar layout
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum TouchBarElement {
+    /// Shows the name of the active key table, if any.
+    /// Pressing it pops the key table.
+    KeyTable,
+    /// A button for each tab in the window; pressing one
+    /// activates that tab
+    Tabs,
+    /// A button that performs an action when pressed
+    Button(TouchBarButton),
+}
+impl_lua_conversion_dynamic!(TouchBarElement);
+
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub struct TouchBarButton {
+    pub label: String,
+    pub action: KeyAssignment,
+}
+impl_lua_conversion_dynamic!(TouchBarButton);
@@ -0,0 +1,72 @@
+use crate::config::validate_domain_name;
+use crate::*;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+#[derive(Default, Debug, Clone, FromDynamic, ToDynamic)]
+pub struct UdpDomainServer {
+    /// The address:port combination on which the server will listen
+    /// for udp sessions
+    pub bind_address: String,
+
+    /// The path to a file holding the pre-shared key that clients must
+    /// also possess.  The file holds 64 hex digits, such as is produced
+    /// by `openssl rand -hex 32`.
+    pub key_file: PathBuf,
+
+    /// How long the server will keep a session alive without hearing
+    /// anything from the client.  This needs to be long enough to span
+    /// the period that a laptop might be asleep.
+    #[dynamic(default = "default_udp_server_idle_timeout")]
+    pub idle_timeout: Duration,
+}
+
+#[derive(Default, Debug, Clone, FromDynamic, ToDynamic)]
+pub struct UdpDomainClient {
+    /// The name of this specific domain.  Must be unique amongst
+    /// all types of domain in the configuration file.
+    #[dynamic(validate = "validate_domain_name")]
+    pub name: String,
+
+    /// identifies the host:port pair of the remote server.
+    pub remote_address: String,
+
+    /// The path to a file holding the pre-shared key that was
+    /// configured for the corresponding `udp_servers` entry
+    pub key_file: PathBuf,
+
+    /// If true, connect to this domain automatically at startup
+    #[dynamic(default)]
+    pub connect_automatically: bool,
+
+    /// How long to wait for the server to respond when
+    /// establishing a session
+    #[dynamic(default = "default_udp_connect_timeout")]
+    pub connect_timeout: Duration,
+
+    /// How long to keep trying to reach the server before
+    /// considering the session to be lost.
+    #[dynamic(default = "default_udp_client_idle_timeout")]
+    pub idle_timeout: Duration,
+
+    #[dynamic(default = "default_local_echo_threshold_ms")]
+    pub local_echo_threshold_ms: Option<u64>,
+
+    /// Show time since last response when waiting for a response.
+    /// It is recommended to use
+    /// <https://wezfurlong.org/wezterm/config/lua/pane/get_metadata.html#since_last_response_ms>
+    /// instead.
+    #[dynamic(default)]
+    pub overlay_lag_indicator: bool,
+}
+
+fn default_udp_connect_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_udp_client_idle_timeout() -> Duration {
+    Duration::from_secs(5 * 60)
+}
+
+fn default_udp_server_idle_timeout() -> Duration {
+    Duration::from_secs(24 * 60 * 60)
+}
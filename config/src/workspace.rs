@@ -0,0 +1,45 @@
+use crate::keyassignment::{SpawnCommand, SpawnTabDomain, SplitPane};
+use luahelper::impl_lua_conversion_dynamic;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// Describes a workspace that is instantiated the first time that it
+/// is switched to, or via `wezterm cli spawn --new-workspace-from`.
+#[derive(Debug, Clone, Default, FromDynamic, ToDynamic)]
+pub struct WorkspaceDefinition {
+    /// The name of the workspace
+    pub name: String,
+
+    /// The default working directory for the programs spawned
+    /// into this workspace
+    pub cwd: Option<PathBuf>,
+
+    /// Environment variables that are set for all of the programs
+    /// spawned into this workspace
+    #[dynamic(default)]
+    pub set_environment_variables: HashMap<String, String>,
+
+    /// The domain into which the tabs are spawned
+    #[dynamic(default)]
+    pub domain: SpawnTabDomain,
+
+    /// The tabs to create in the initial window of the workspace.
+    /// If empty, a single tab running the default program is created.
+    #[dynamic(default)]
+    pub tabs: Vec<WorkspaceTab>,
+}
+impl_lua_conversion_dynamic!(WorkspaceDefinition);
+
+#[derive(Debug, Clone, Default, FromDynamic, ToDynamic)]
+pub struct WorkspaceTab {
+    /// The program to run in the initial pane of the tab
+    #[dynamic(default)]
+    pub command: SpawnCommand,
+
+    /// Additional panes to create in the tab.  Each one splits the
+    /// pane that was created immediately before it.
+    #[dynamic(default)]
+    pub splits: Vec<SplitPane>,
+}
+impl_lua_conversion_dynamic!(WorkspaceTab);
@@ -12,6 +12,14 @@ pub struct WslDomain {
     pub username: Option<String>,
     pub default_cwd: Option<PathBuf>,
     pub default_prog: Option<Vec<String>>,
+    /// Environment variables to set in the distribution; they are
+    /// passed through to it via WSLENV
+    #[dynamic(default)]
+    pub set_environment_variables: HashMap<String, String>,
+    /// Whether to translate between windows and WSL paths when
+    /// spawning, pasting or dropping files into panes in this domain
+    #[dynamic(default = "default_true")]
+    pub translate_paths: bool,
 }
 impl_lua_conversion_dynamic!(WslDomain);
 
@@ -29,12 +37,113 @@ impl WslDomain {
                     username: None,
                     default_cwd: None,
                     default_prog: None,
+                    set_environment_variables: HashMap::new(),
+                    translate_paths: true,
                 });
             }
         }
 
         domains
     }
+
+    fn distribution_name(&self) -> &str {
+        self.distribution.as_deref().unwrap_or(self.name.as_str())
+    }
+
+    /// Translates a windows path into the path by which it can be
+    /// accessed from inside this distribution; `C:\Users` becomes
+    /// `/mnt/c/Users`, and `\\wsl$\Ubuntu\home` becomes `/home`
+    /// for the `Ubuntu` distribution.
+    /// Returns None if `path` is not an absolute windows path that
+    /// is accessible to the distribution.
+    pub fn windows_path_to_wsl(&self, path: &str) -> Option<String> {
+        let bytes = path.as_bytes();
+        if bytes.len() >= 2
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && (bytes.len() == 2 || bytes[2] == b'\\' || bytes[2] == b'/')
+        {
+            let drive = (bytes[0] as char).to_ascii_lowercase();
+            return Some(format!("/mnt/{}{}", drive, path[2..].replace('\\', "/")));
+        }
+
+        let unc = path.replace('\\', "/");
+        let lower = unc.to_ascii_lowercase();
+        for prefix in &["//wsl$/", "//wsl.localhost/"] {
+            if lower.starts_with(prefix) {
+                let remainder = &unc[prefix.len()..];
+                let (distro, rest) = match remainder.find('/') {
+                    Some(idx) => (&remainder[..idx], &remainder[idx..]),
+                    None => (remainder, ""),
+                };
+                if !distro.eq_ignore_ascii_case(self.distribution_name()) {
+                    return None;
+                }
+                return Some(if rest.is_empty() {
+                    "/".to_string()
+                } else {
+                    rest.to_string()
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Translates an absolute path inside this distribution into a
+    /// windows path; `/mnt/c/Users` becomes `C:\Users` and `/home`
+    /// becomes `\\wsl$\Ubuntu\home` for the `Ubuntu` distribution.
+    /// Returns None if `path` is not an absolute unix path.
+    pub fn wsl_path_to_windows(&self, path: &str) -> Option<String> {
+        if !path.starts_with('/') {
+            return None;
+        }
+
+        if let Some(rest) = path.strip_prefix("/mnt/") {
+            let bytes = rest.as_bytes();
+            if !bytes.is_empty()
+                && bytes[0].is_ascii_alphabetic()
+                && (bytes.len() == 1 || bytes[1] == b'/')
+            {
+                let drive = (bytes[0] as char).to_ascii_uppercase();
+                let rest = rest[1..].replace('/', "\\");
+                return Some(if rest.is_empty() {
+                    format!("{}:\\", drive)
+                } else {
+                    format!("{}:{}", drive, rest)
+                });
+            }
+        }
+
+        Some(format!(
+            "\\\\wsl$\\{}{}",
+            self.distribution_name(),
+            path.replace('/', "\\")
+        ))
+    }
+}
+
+/// Translates a working directory that was obtained from a pane in the
+/// `source` domain into one that can be used to spawn a command in the
+/// `target` domain, where `None` represents a domain that is not a WSL
+/// domain
+pub fn translate_wsl_cwd(
+    path: &str,
+    source: Option<&WslDomain>,
+    target: Option<&WslDomain>,
+) -> String {
+    let windows_path = match source {
+        Some(source) if source.translate_paths => source
+            .wsl_path_to_windows(path)
+            .unwrap_or_else(|| path.to_string()),
+        _ => path.to_string(),
+    };
+    match target {
+        Some(target) if target.translate_paths => target
+            .windows_path_to_wsl(&windows_path)
+            .unwrap_or(windows_path),
+        _ => windows_path,
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -209,3 +318,63 @@ fn test_parse_wsl_distro_list() {
         ]
     );
 }
+
+#[cfg(test)]
+#[test]
+fn test_wsl_path_translation() {
+    let ubuntu = WslDomain {
+        name: "WSL:Ubuntu".to_string(),
+        distribution: Some("Ubuntu".to_string()),
+        translate_paths: true,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        ubuntu.windows_path_to_wsl("C:\\Users\\me").as_deref(),
+        Some("/mnt/c/Users/me")
+    );
+    assert_eq!(ubuntu.windows_path_to_wsl("d:").as_deref(), Some("/mnt/d"));
+    assert_eq!(
+        ubuntu
+            .windows_path_to_wsl("\\\\wsl$\\ubuntu\\home\\me")
+            .as_deref(),
+        Some("/home/me")
+    );
+    assert_eq!(
+        ubuntu
+            .windows_path_to_wsl("\\\\wsl.localhost\\Ubuntu")
+            .as_deref(),
+        Some("/")
+    );
+    assert_eq!(ubuntu.windows_path_to_wsl("\\\\wsl$\\Debian\\home"), None);
+    assert_eq!(ubuntu.windows_path_to_wsl("relative\\path"), None);
+
+    assert_eq!(
+        ubuntu.wsl_path_to_windows("/mnt/c/Users/me").as_deref(),
+        Some("C:\\Users\\me")
+    );
+    assert_eq!(
+        ubuntu.wsl_path_to_windows("/mnt/d").as_deref(),
+        Some("D:\\")
+    );
+    assert_eq!(
+        ubuntu.wsl_path_to_windows("/home/me").as_deref(),
+        Some("\\\\wsl$\\Ubuntu\\home\\me")
+    );
+    assert_eq!(ubuntu.wsl_path_to_windows("relative"), None);
+
+    // Moving between a windows pane and a WSL pane
+    assert_eq!(
+        translate_wsl_cwd("C:\\Users\\me", None, Some(&ubuntu)),
+        "/mnt/c/Users/me"
+    );
+    assert_eq!(
+        translate_wsl_cwd("/mnt/c/Users/me", Some(&ubuntu), None),
+        "C:\\Users\\me"
+    );
+    // Staying in the same distribution is a round trip
+    assert_eq!(
+        translate_wsl_cwd("/home/me", Some(&ubuntu), Some(&ubuntu)),
+        "/home/me"
+    );
+}
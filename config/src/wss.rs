@@ -0,0 +1,163 @@
+use crate::config::validate_domain_name;
+use crate::*;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+#[derive(Default, Debug, Clone, FromDynamic, ToDynamic)]
+pub struct WssDomainServer {
+    /// The address:port combination on which the server will listen
+    /// for websocket connections
+    pub bind_address: String,
+
+    /// The HTTP request path on which the websocket upgrade is accepted.
+    /// This is useful when the server is placed behind a reverse proxy
+    /// that routes based on the path.
+    #[dynamic(default = "default_wss_path")]
+    pub path: String,
+
+    /// If true (the default), the listener speaks TLS and requires the
+    /// client to present a certificate in the same way as the `tls_servers`
+    /// listener.  If false, the listener accepts plain websocket connections
+    /// and is intended to be placed behind a TLS terminating reverse proxy;
+    /// in that case the bind address must be a loopback address and
+    /// `auth_token_file` must be set.
+    #[dynamic(default = "default_true")]
+    pub use_tls: bool,
+
+    /// The path to a file holding a secret token that clients must
+    /// present in the `Authorization` header of the websocket upgrade
+    /// request.  Required when `use_tls` is false.
+    pub auth_token_file: Option<PathBuf>,
+
+    /// the path to an x509 PEM encoded private key file
+    pub pem_private_key: Option<PathBuf>,
+
+    /// the path to an x509 PEM encoded certificate file
+    pub pem_cert: Option<PathBuf>,
+
+    /// the path to an x509 PEM encoded CA chain file
+    pub pem_ca: Option<PathBuf>,
+
+    /// A set of paths to load additional CA certificates.
+    /// Each entry can be either the path to a directory
+    /// or to a PEM encoded CA file.  If an entry is a directory,
+    /// then its contents will be loaded as CA certs and added
+    /// to the trust store.
+    #[dynamic(default)]
+    pub pem_root_certs: Vec<PathBuf>,
+}
+
+impl WssDomainServer {
+    /// Returns the equivalent tls server configuration, which is used
+    /// to set up the TLS layer beneath the websocket
+    pub fn tls_server(&self) -> TlsDomainServer {
+        TlsDomainServer {
+            bind_address: self.bind_address.clone(),
+            pem_private_key: self.pem_private_key.clone(),
+            pem_cert: self.pem_cert.clone(),
+            pem_ca: self.pem_ca.clone(),
+            pem_root_certs: self.pem_root_certs.clone(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, FromDynamic, ToDynamic)]
+pub struct WssDomainClient {
+    /// The name of this specific domain.  Must be unique amongst
+    /// all types of domain in the configuration file.
+    #[dynamic(validate = "validate_domain_name")]
+    pub name: String,
+
+    /// The websocket url of the remote server, for example
+    /// "wss://server.example.com:8443/wezterm".
+    /// Both "ws://" and "wss://" schemes are supported.
+    pub url: String,
+
+    /// If set, connect via this HTTP proxy, specified as "host:port",
+    /// by issuing an HTTP CONNECT request.
+    pub http_proxy: Option<String>,
+
+    /// The path to a file holding the token that was configured as the
+    /// `auth_token_file` of the corresponding `wss_servers` entry
+    pub auth_token_file: Option<PathBuf>,
+
+    /// the path to an x509 PEM encoded private key file
+    pub pem_private_key: Option<PathBuf>,
+
+    /// the path to an x509 PEM encoded certificate file
+    pub pem_cert: Option<PathBuf>,
+
+    /// the path to an x509 PEM encoded CA chain file
+    pub pem_ca: Option<PathBuf>,
+
+    /// A set of paths to load additional CA certificates.
+    /// Each entry can be either the path to a directory or to a PEM encoded
+    /// CA file.  If an entry is a directory, then its contents will be
+    /// loaded as CA certs and added to the trust store.
+    #[dynamic(default)]
+    pub pem_root_certs: Vec<PathBuf>,
+
+    /// explicitly control whether the client checks that the certificate
+    /// presented by the server matches the hostname portion of `url`.
+    /// The default is true.  This option is made available for
+    /// troubleshooting purposes and should not be used outside of a
+    /// controlled environment as it weakens the security of the TLS channel.
+    #[dynamic(default)]
+    pub accept_invalid_hostnames: bool,
+
+    /// If true, connect to this domain automatically at startup
+    #[dynamic(default)]
+    pub connect_automatically: bool,
+
+    #[dynamic(default = "default_read_timeout")]
+    pub read_timeout: Duration,
+
+    #[dynamic(default = "default_write_timeout")]
+    pub write_timeout: Duration,
+
+    #[dynamic(default = "default_local_echo_threshold_ms")]
+    pub local_echo_threshold_ms: Option<u64>,
+
+    /// Show time since last response when waiting for a response.
+    /// It is recommended to use
+    /// <https://wezfurlong.org/wezterm/config/lua/pane/get_metadata.html#since_last_response_ms>
+    /// instead.
+    #[dynamic(default)]
+    pub overlay_lag_indicator: bool,
+}
+
+impl WssDomainClient {
+    /// Construct a client domain for a url given on the command line,
+    /// such as via `wezterm connect wss://host:port/path`
+    pub fn with_url(url: &str) -> Self {
+        Self {
+            name: url.to_string(),
+            url: url.to_string(),
+            read_timeout: default_read_timeout(),
+            write_timeout: default_write_timeout(),
+            local_echo_threshold_ms: default_local_echo_threshold_ms(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns true if `name` looks like a websocket url rather than
+    /// the name of a configured domain
+    pub fn is_url(name: &str) -> bool {
+        name.starts_with("wss://") || name.starts_with("ws://")
+    }
+}
+
+/// Reads the websocket authentication token from `path`, ignoring
+/// any surrounding whitespace
+pub fn read_wss_auth_token(path: &Path) -> anyhow::Result<String> {
+    let token = std::fs::read_to_string(path)
+        .with_context(|| format!("reading auth token file {}", path.display()))?;
+    let token = token.trim();
+    if token.is_empty() {
+        anyhow::bail!("auth token file {} is empty", path.display());
+    }
+    Ok(token.to_string())
+}
+
+fn default_wss_path() -> String {
+    "/".to_string()
+}
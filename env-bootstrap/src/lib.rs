@@ -167,11 +167,14 @@ fn register_lua_modules() {
         mux_lua::register,
         procinfo_funcs::register,
         filesystem::register,
+        http_funcs::register,
+        secrets_funcs::register,
         json::register,
         ssh_funcs::register,
         spawn_funcs::register,
         share_data::register,
         time_funcs::register,
+        plugin::register,
     ] {
         config::lua::add_context_setup_func(func);
     }
@@ -1,12 +1,39 @@
 use anyhow::anyhow;
-use config::lua::get_or_create_module;
-use config::lua::mlua::{self, Lua};
+use config::lua::mlua::{self, Lua, UserData, UserDataMethods};
+use config::lua::{emit_event, get_or_create_module, get_or_create_sub_module, wrap_callback};
+use config::ConfigSubscription;
+use luahelper::impl_lua_conversion_dynamic;
 use smol::prelude::*;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+lazy_static::lazy_static! {
+    static ref CONFIG_SUBSCRIPTION: Mutex<Option<ConfigSubscription>> = Mutex::new(None);
+}
 
 pub fn register(lua: &Lua) -> anyhow::Result<()> {
+    {
+        let mut sub = CONFIG_SUBSCRIPTION.lock().unwrap();
+        if sub.is_none() {
+            sub.replace(config::subscribe_to_config_reload(config_was_reloaded));
+        }
+    }
+    lua.set_named_registry_value(PENDING_WATCHES, Vec::<PathWatch>::new())?;
+    lua.set_named_registry_value(WATCHES_ARE_LIVE, false)?;
+
     let wezterm_mod = get_or_create_module(lua, "wezterm")?;
     wezterm_mod.set("read_dir", lua.create_async_function(read_dir)?)?;
     wezterm_mod.set("glob", lua.create_async_function(glob)?)?;
+
+    let fs_mod = get_or_create_sub_module(lua, "fs")?;
+    fs_mod.set("read_dir", lua.create_async_function(read_dir)?)?;
+    fs_mod.set("glob", lua.create_async_function(glob)?)?;
+    fs_mod.set("stat", lua.create_async_function(stat)?)?;
+    fs_mod.set("watch", lua.create_function(watch)?)?;
     Ok(())
 }
 
@@ -52,3 +79,161 @@ async fn glob<'lua>(
     .map_err(|e| mlua::Error::external(e))?;
     Ok(entries)
 }
+
+#[derive(Debug, FromDynamic, ToDynamic)]
+struct FileStat {
+    is_file: bool,
+    is_dir: bool,
+    is_symlink: bool,
+    size: u64,
+    readonly: bool,
+    /// The times are expressed as seconds since the unix epoch
+    modified: Option<f64>,
+    accessed: Option<f64>,
+    created: Option<f64>,
+}
+impl_lua_conversion_dynamic!(FileStat);
+
+fn unix_seconds(time: std::io::Result<SystemTime>) -> Option<f64> {
+    time.ok()?
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs_f64())
+}
+
+async fn stat<'lua>(_: &'lua Lua, path: String) -> mlua::Result<FileStat> {
+    let link_meta = smol::fs::symlink_metadata(&path)
+        .await
+        .map_err(|e| mlua::Error::external(format!("{path}: {e:#}")))?;
+    // Describe the target of a symlink, unless it is dangling
+    let meta = if link_meta.file_type().is_symlink() {
+        smol::fs::metadata(&path)
+            .await
+            .unwrap_or_else(|_| link_meta.clone())
+    } else {
+        link_meta.clone()
+    };
+
+    Ok(FileStat {
+        is_file: meta.is_file(),
+        is_dir: meta.is_dir(),
+        is_symlink: link_meta.file_type().is_symlink(),
+        size: meta.len(),
+        readonly: meta.permissions().readonly(),
+        modified: unix_seconds(meta.modified()),
+        accessed: unix_seconds(meta.accessed()),
+        created: unix_seconds(meta.created()),
+    })
+}
+
+/// Keeps track of a `wezterm.fs.watch` request
+#[derive(Debug, Clone)]
+struct PathWatch {
+    path: PathBuf,
+    /// The name of the registry entry that will resolve to
+    /// their callback function
+    user_event_id: String,
+}
+
+impl PathWatch {
+    /// Starts watching the path.  The watch is dropped once the
+    /// configuration generation changes, as the callback belongs
+    /// to the lua context of that generation.
+    fn subscribe(self, generation: usize) {
+        let dead = Arc::new(AtomicBool::new(false));
+        let user_event_id = self.user_event_id;
+        config::subscribe_to_path_changes(self.path, move |paths| {
+            let dead = Arc::clone(&dead);
+            let user_event_id = user_event_id.clone();
+            promise::spawn::spawn_into_main_thread(async move {
+                promise::spawn::spawn(async move {
+                    if config::configuration().generation() != generation {
+                        dead.store(true, Ordering::Relaxed);
+                        return Ok(());
+                    }
+                    config::with_lua_config_on_main_thread(move |lua| async move {
+                        if let Some(lua) = lua {
+                            let paths: Vec<String> = paths
+                                .iter()
+                                .filter_map(|p| p.to_str().map(|s| s.to_string()))
+                                .collect();
+                            let args = lua.pack_multi(paths)?;
+                            emit_event(&lua, (user_event_id, args)).await?;
+                        }
+                        Ok(())
+                    })
+                    .await
+                })
+                .detach();
+            })
+            .detach();
+            !dead.load(Ordering::Relaxed)
+        });
+    }
+}
+
+impl UserData for PathWatch {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(_methods: &mut M) {}
+}
+
+const PENDING_WATCHES: &str = "wezterm-fs-pending-watches";
+const WATCHES_ARE_LIVE: &str = "wezterm-fs-watches-are-live";
+
+/// Called by the config subsystem when the config is reloaded.
+/// We use it to start the watches that were requested while the
+/// configuration was being evaluated.
+fn config_was_reloaded() -> bool {
+    if promise::spawn::is_scheduler_configured() {
+        promise::spawn::spawn_into_main_thread(async move {
+            promise::spawn::spawn(async move {
+                config::with_lua_config_on_main_thread(|lua| async move {
+                    subscribe_all(lua)?;
+                    Ok(())
+                })
+                .await
+            })
+            .detach();
+        })
+        .detach();
+    }
+
+    true
+}
+
+fn subscribe_all(lua: Option<Rc<Lua>>) -> mlua::Result<()> {
+    if let Some(lua) = lua {
+        let is_live: bool = lua.named_registry_value(WATCHES_ARE_LIVE)?;
+        if !is_live {
+            let watches: Vec<PathWatch> = lua.named_registry_value(PENDING_WATCHES)?;
+            let generation = config::configuration().generation();
+            for watch in watches {
+                watch.subscribe(generation);
+            }
+            lua.set_named_registry_value(WATCHES_ARE_LIVE, true)?;
+        }
+    }
+    Ok(())
+}
+
+fn watch<'lua>(lua: &'lua Lua, (path, func): (String, mlua::Function)) -> mlua::Result<()> {
+    let path = std::env::current_dir()
+        .map_err(|e| mlua::Error::external(e))?
+        .join(path);
+    let watch = PathWatch {
+        path,
+        user_event_id: wrap_callback(lua, func)?,
+    };
+
+    // While the configuration is being evaluated, we don't yet know
+    // whether this lua context will be adopted, so defer watching
+    // until it is loaded
+    let is_live: bool = lua.named_registry_value(WATCHES_ARE_LIVE)?;
+    if is_live {
+        watch.subscribe(config::configuration().generation());
+    } else {
+        let mut watches: Vec<PathWatch> = lua.named_registry_value(PENDING_WATCHES)?;
+        watches.push(watch);
+        lua.set_named_registry_value(PENDING_WATCHES, watches)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use anyhow::Context;
+use config::lua::mlua::{self, Lua};
+use config::lua::{get_or_create_module, get_or_create_sub_module};
+use http_req::request::{Method, Request};
+use http_req::uri::Uri;
+use luahelper::impl_lua_conversion_dynamic;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::Duration;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// The timeout that applies if the user doesn't specify one
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn register(lua: &Lua) -> anyhow::Result<()> {
+    let http_mod = get_or_create_sub_module(lua, "http")?;
+    http_mod.set("get", lua.create_async_function(get)?)?;
+    http_mod.set("post", lua.create_async_function(post)?)?;
+    // Ensure that the wezterm module exists, in case we were
+    // registered before it
+    get_or_create_module(lua, "wezterm")?;
+    Ok(())
+}
+
+#[derive(Debug, Default, FromDynamic, ToDynamic)]
+struct RequestOptions {
+    #[dynamic(default)]
+    headers: HashMap<String, String>,
+    /// The overall time limit for the request, in seconds
+    timeout: Option<f64>,
+}
+impl_lua_conversion_dynamic!(RequestOptions);
+
+struct Response {
+    status: u16,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn to_lua<'lua>(self, lua: &'lua Lua) -> mlua::Result<mlua::Table<'lua>> {
+        let tbl = lua.create_table()?;
+        tbl.set("status", self.status)?;
+        tbl.set("headers", self.headers)?;
+        tbl.set("body", lua.create_string(&self.body)?)?;
+        Ok(tbl)
+    }
+}
+
+/// Performs the request on a background thread, so that the lua
+/// code that is waiting on it doesn't block the gui
+async fn perform(
+    method: Method,
+    url: String,
+    body: Option<Vec<u8>>,
+    options: RequestOptions,
+) -> mlua::Result<Response> {
+    let timeout = match options.timeout {
+        Some(seconds) if seconds.is_finite() && seconds > 0. => Duration::from_secs_f64(seconds),
+        Some(seconds) => {
+            return Err(mlua::Error::external(format!(
+                "{seconds} is not a valid timeout"
+            )))
+        }
+        None => DEFAULT_TIMEOUT,
+    };
+
+    smol::unblock(move || -> anyhow::Result<Response> {
+        let uri = Uri::try_from(url.as_str()).with_context(|| format!("parsing url {url}"))?;
+        let mut request = Request::new(&uri);
+        request
+            .method(method)
+            .header(
+                "User-Agent",
+                &format!("wezterm/{}", config::wezterm_version()),
+            )
+            .timeout(Some(timeout));
+        for (name, value) in &options.headers {
+            request.header(name, value);
+        }
+        if let Some(body) = &body {
+            request
+                .header("Content-Length", &body.len().to_string())
+                .body(body);
+        }
+
+        let mut response_body = vec![];
+        let response = request
+            .send(&mut response_body)
+            .with_context(|| format!("requesting {url}"))?;
+
+        Ok(Response {
+            status: response.status_code().into(),
+            headers: response
+                .headers()
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: response_body,
+        })
+    })
+    .await
+    .map_err(|err| mlua::Error::external(format!("{err:#}")))
+}
+
+async fn get<'lua>(
+    lua: &'lua Lua,
+    (url, options): (String, Option<RequestOptions>),
+) -> mlua::Result<mlua::Table<'lua>> {
+    perform(Method::GET, url, None, options.unwrap_or_default())
+        .await?
+        .to_lua(lua)
+}
+
+async fn post<'lua>(
+    lua: &'lua Lua,
+    (url, body, options): (String, mlua::String<'lua>, Option<RequestOptions>),
+) -> mlua::Result<mlua::Table<'lua>> {
+    let body = body.as_bytes().to_vec();
+    perform(Method::POST, url, Some(body), options.unwrap_or_default())
+        .await?
+        .to_lua(lua)
+}
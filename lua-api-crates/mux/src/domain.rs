@@ -0,0 +1,87 @@
+use super::*;
+use mux::domain::{DomainId, DomainState};
+use mux::domainstats::{domain_metrics, has_metrics, DomainMetrics};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug)]
+pub struct MuxDomain(pub DomainId);
+
+impl MuxDomain {
+    pub fn resolve<'a>(&self, mux: &'a Rc<Mux>) -> mlua::Result<Arc<dyn Domain>> {
+        mux.get_domain(self.0)
+            .ok_or_else(|| mlua::Error::external(format!("domain id {} not found in mux", self.0)))
+    }
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.
+}
+
+/// The lua representation of `DomainMetrics`; durations
+/// are expressed in milliseconds
+#[derive(Debug, FromDynamic, ToDynamic)]
+struct DomainStats {
+    rtt_samples: usize,
+    rtt_last_ms: Option<f64>,
+    rtt_p50_ms: f64,
+    rtt_p95_ms: f64,
+    rtt_p99_ms: f64,
+    rtt_max_ms: f64,
+    sent_bytes_per_second: u64,
+    sent_bytes_total: u64,
+    received_bytes_per_second: u64,
+    received_bytes_total: u64,
+}
+impl_lua_conversion_dynamic!(DomainStats);
+
+impl From<DomainMetrics> for DomainStats {
+    fn from(metrics: DomainMetrics) -> Self {
+        Self {
+            rtt_samples: metrics.rtt.samples,
+            rtt_last_ms: metrics.last_rtt.map(millis),
+            rtt_p50_ms: millis(metrics.rtt.p50),
+            rtt_p95_ms: millis(metrics.rtt.p95),
+            rtt_p99_ms: millis(metrics.rtt.p99),
+            rtt_max_ms: millis(metrics.rtt.max),
+            sent_bytes_per_second: metrics.sent_bytes_per_second,
+            sent_bytes_total: metrics.sent_bytes_total,
+            received_bytes_per_second: metrics.received_bytes_per_second,
+            received_bytes_total: metrics.received_bytes_total,
+        }
+    }
+}
+
+impl UserData for MuxDomain {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_method(mlua::MetaMethod::ToString, |_, this, _: ()| {
+            Ok(format!("MuxDomain(domain_id:{}, pid:{})", this.0, unsafe {
+                libc::getpid()
+            }))
+        });
+        methods.add_method("domain_id", |_, this, _: ()| Ok(this.0));
+        methods.add_method("name", |_, this, _: ()| {
+            let mux = get_mux()?;
+            let domain = this.resolve(&mux)?;
+            Ok(domain.domain_name().to_string())
+        });
+        methods.add_method("is_attached", |_, this, _: ()| {
+            let mux = get_mux()?;
+            let domain = this.resolve(&mux)?;
+            Ok(domain.state() == DomainState::Attached)
+        });
+        methods.add_method("is_spawnable", |_, this, _: ()| {
+            let mux = get_mux()?;
+            let domain = this.resolve(&mux)?;
+            Ok(domain.spawnable())
+        });
+        methods.add_method("stats", |_, this, _: ()| {
+            let mux = get_mux()?;
+            this.resolve(&mux)?;
+            if !has_metrics(this.0) {
+                return Ok(None);
+            }
+            Ok(Some(DomainStats::from(domain_metrics(this.0))))
+        });
+    }
+}
@@ -0,0 +1,262 @@
+use super::*;
+
+#[derive(Debug, Clone, Copy, FromDynamic, ToDynamic)]
+enum LayoutDirection {
+    /// The children are placed side by side
+    Horizontal,
+    /// The children are stacked on top of each other
+    Vertical,
+}
+
+impl Default for LayoutDirection {
+    fn default() -> Self {
+        Self::Horizontal
+    }
+}
+
+/// A node of the split tree that is passed to `spawn_layout`.
+/// A node with children is divided among them, and a node without
+/// children is a pane running the specified command.
+#[derive(Debug, Default, FromDynamic, ToDynamic)]
+pub struct LayoutNode {
+    #[dynamic(flatten)]
+    cmd_builder: CommandBuilderFrag,
+    domain: Option<SpawnTabDomain>,
+    #[dynamic(default)]
+    direction: LayoutDirection,
+    #[dynamic(default)]
+    children: Vec<LayoutNode>,
+    /// The size of this node relative to its siblings
+    #[dynamic(default = "default_layout_size")]
+    size: f32,
+}
+impl_lua_conversion_dynamic!(LayoutNode);
+
+fn default_layout_size() -> f32 {
+    1.0
+}
+
+impl LayoutNode {
+    fn validate(&self) -> mlua::Result<()> {
+        if !self.size.is_finite() || self.size <= 0.0 {
+            return Err(mlua::Error::external(format!(
+                "layout size must be a positive number, but is {}",
+                self.size
+            )));
+        }
+        for child in &self.children {
+            child.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the pane that occupies the top/left corner of this node.
+    /// That pane is the one that is created when the space for the node
+    /// is made, and the rest of the node is split from it.
+    fn first_leaf_mut(&mut self) -> &mut LayoutNode {
+        match self.children.first_mut() {
+            Some(child) => child.first_leaf_mut(),
+            None => self,
+        }
+    }
+}
+
+#[derive(Debug, FromDynamic, ToDynamic)]
+pub struct SpawnLayout {
+    width: Option<usize>,
+    height: Option<usize>,
+    workspace: Option<String>,
+    #[dynamic(flatten)]
+    layout: LayoutNode,
+}
+impl_lua_conversion_dynamic!(SpawnLayout);
+
+impl SpawnLayout {
+    pub async fn spawn(self) -> mlua::Result<(MuxTab, Vec<MuxPane>, MuxWindow)> {
+        let mux = get_mux()?;
+
+        let size = match (self.width, self.height) {
+            (Some(cols), Some(rows)) => TerminalSize {
+                rows,
+                cols,
+                ..Default::default()
+            },
+            _ => config::configuration().initial_size(0),
+        };
+        let workspace = self.workspace.unwrap_or_else(|| mux.active_workspace());
+
+        spawn_layout(
+            None,
+            self.layout,
+            SpawnTabDomain::DefaultDomain,
+            size,
+            None,
+            workspace,
+        )
+        .await
+    }
+}
+
+/// Spawns a tab populated with the panes described by `layout`, and
+/// returns the panes in the order that they appear in the layout.
+/// `default_domain` is used for the first pane if the layout doesn't
+/// specify its domain.
+pub async fn spawn_layout(
+    window_id: Option<WindowId>,
+    mut layout: LayoutNode,
+    default_domain: SpawnTabDomain,
+    size: TerminalSize,
+    current_pane_id: Option<PaneId>,
+    workspace: String,
+) -> mlua::Result<(MuxTab, Vec<MuxPane>, MuxWindow)> {
+    layout.validate()?;
+    let mux = get_mux()?;
+
+    let first = layout.first_leaf_mut();
+    let domain = first.domain.take().unwrap_or(default_domain);
+    let (cmd_builder, cwd) = std::mem::take(&mut first.cmd_builder).to_command_builder();
+    let (tab, pane, window_id) = mux
+        .spawn_tab_or_window(
+            window_id,
+            domain,
+            cmd_builder,
+            cwd,
+            size,
+            current_pane_id,
+            workspace,
+        )
+        .await
+        .map_err(|e| mlua::Error::external(format!("{:#?}", e)))?;
+
+    match split_layout(&mux, &tab, pane.pane_id(), layout).await {
+        Ok(panes) => Ok((MuxTab(tab.tab_id()), panes, MuxWindow(window_id))),
+        Err(err) => {
+            // Don't leave a partially constructed layout behind
+            mux.remove_tab(tab.tab_id());
+            Err(err)
+        }
+    }
+}
+
+/// Splits the initial pane of the tab until it matches the layout.
+/// The tree is walked using a stack, rather than recursively, as
+/// the splits are async.
+async fn split_layout(
+    mux: &Rc<Mux>,
+    tab: &Rc<Tab>,
+    pane_id: PaneId,
+    layout: LayoutNode,
+) -> mlua::Result<Vec<MuxPane>> {
+    let tab_size = tab.get_size();
+    let mut panes = vec![];
+    let mut stack = vec![(pane_id, layout, tab_size.cols, tab_size.rows)];
+
+    while let Some((pane_id, mut node, cols, rows)) = stack.pop() {
+        if node.children.is_empty() {
+            panes.push(MuxPane(pane_id));
+            continue;
+        }
+
+        let (direction, available) = match node.direction {
+            LayoutDirection::Horizontal => (SplitDirection::Horizontal, cols),
+            LayoutDirection::Vertical => (SplitDirection::Vertical, rows),
+        };
+        let weights: Vec<f32> = node.children.iter().map(|child| child.size).collect();
+        let sizes = distribute(available, &weights).ok_or_else(|| {
+            mlua::Error::external(format!(
+                "not enough space to fit {} panes into {} cells",
+                weights.len(),
+                available
+            ))
+        })?;
+
+        let mut children = std::mem::take(&mut node.children);
+        let mut child_panes = vec![pane_id];
+        for idx in 1..children.len() {
+            // Split the space for this child and the ones after it from
+            // the pane of the prior child, which keeps its exact size
+            let remaining = sizes[idx..].iter().sum::<usize>() + sizes.len() - idx - 1;
+            let leaf = children[idx].first_leaf_mut();
+            let domain = leaf
+                .domain
+                .take()
+                .unwrap_or(SpawnTabDomain::CurrentPaneDomain);
+            let (command, command_dir) = std::mem::take(&mut leaf.cmd_builder).to_command_builder();
+
+            let (pane, _size) = mux
+                .split_pane(
+                    *child_panes.last().unwrap(),
+                    SplitRequest {
+                        direction,
+                        target_is_second: true,
+                        top_level: false,
+                        size: SplitSize::Cells(remaining),
+                    },
+                    SplitSource::Spawn {
+                        command,
+                        command_dir,
+                    },
+                    domain,
+                )
+                .await
+                .map_err(|e| mlua::Error::external(format!("{:#?}", e)))?;
+            child_panes.push(pane.pane_id());
+        }
+
+        // Pushed in reverse, so that the panes are returned in the
+        // order that they appear in the layout
+        for ((child, pane_id), size) in children.into_iter().zip(child_panes).zip(sizes).rev() {
+            let (cols, rows) = match node.direction {
+                LayoutDirection::Horizontal => (size, rows),
+                LayoutDirection::Vertical => (cols, size),
+            };
+            stack.push((pane_id, child, cols, rows));
+        }
+    }
+
+    Ok(panes)
+}
+
+/// Divides `total` cells among children with the relative `weights`,
+/// allowing one cell for the separator between each of them.
+/// The cells left over from rounding down are given to the children
+/// with the largest remainders, so that the sizes add up exactly.
+fn distribute(total: usize, weights: &[f32]) -> Option<Vec<usize>> {
+    let num_children = weights.len();
+    let available = total.checked_sub(num_children.checked_sub(1)?)?;
+    if available < num_children {
+        return None;
+    }
+
+    let sum: f64 = weights.iter().map(|&w| w as f64).sum();
+    let exact: Vec<f64> = weights
+        .iter()
+        .map(|&w| available as f64 * w as f64 / sum)
+        .collect();
+    let mut sizes: Vec<usize> = exact.iter().map(|e| (e.floor() as usize).max(1)).collect();
+    let mut assigned: usize = sizes.iter().sum();
+
+    let mut by_remainder: Vec<usize> = (0..num_children).collect();
+    by_remainder.sort_by(|&a, &b| {
+        (exact[b] - exact[b].floor())
+            .partial_cmp(&(exact[a] - exact[a].floor()))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    for &idx in by_remainder.iter().cycle() {
+        if assigned >= available {
+            break;
+        }
+        sizes[idx] += 1;
+        assigned += 1;
+    }
+
+    // Children that were too small to get a cell were given one
+    // anyway; take it back from the largest
+    while assigned > available {
+        let largest = (0..num_children).max_by_key(|&idx| sizes[idx])?;
+        sizes[largest] -= 1;
+        assigned -= 1;
+    }
+
+    Some(sizes)
+}
@@ -1,8 +1,8 @@
 use config::keyassignment::SpawnTabDomain;
-use config::lua::mlua::{self, Lua, UserData, UserDataMethods, Value as LuaValue};
-use config::lua::{get_or_create_module, get_or_create_sub_module};
+use config::lua::mlua::{self, Lua, ToLua, UserData, UserDataMethods, Value as LuaValue};
+use config::lua::{get_or_create_module, get_or_create_sub_module, wrap_callback};
 use luahelper::impl_lua_conversion_dynamic;
-use mux::domain::SplitSource;
+use mux::domain::{Domain, SplitSource};
 use mux::pane::{Pane, PaneId};
 use mux::tab::{SplitDirection, SplitRequest, SplitSize, Tab, TabId};
 use mux::window::{Window, WindowId};
@@ -11,13 +11,20 @@ use portable_pty::CommandBuilder;
 use std::cell::{Ref, RefMut};
 use std::collections::HashMap;
 use std::rc::Rc;
+use termwiz::cell::{Intensity, Underline};
+use termwiz::surface::line::CellRef;
+use termwiz::surface::Line;
 use wezterm_dynamic::{FromDynamic, ToDynamic};
+use wezterm_term::color::ColorPalette;
 use wezterm_term::TerminalSize;
 
+mod domain;
+mod layout;
 mod pane;
 mod tab;
 mod window;
 
+pub use domain::MuxDomain;
 pub use pane::MuxPane;
 pub use tab::MuxTab;
 pub use window::MuxWindow;
@@ -27,9 +34,16 @@ fn get_mux() -> mlua::Result<Rc<Mux>> {
         .ok_or_else(|| mlua::Error::external("cannot get Mux: not running on the mux thread?"))
 }
 
+/// Passes panes to the handlers of output watcher events
+fn pane_to_lua<'lua>(lua: &'lua Lua, pane_id: PaneId) -> mlua::Result<LuaValue<'lua>> {
+    MuxPane(pane_id).to_lua(lua)
+}
+
 pub fn register(lua: &Lua) -> anyhow::Result<()> {
     let mux_mod = get_or_create_sub_module(lua, "mux")?;
 
+    mux::outputwatch::set_pane_to_lua(pane_to_lua);
+
     mux_mod.set(
         "get_active_workspace",
         lua.create_function(|_, _: ()| {
@@ -92,11 +106,50 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
         })?,
     )?;
 
+    mux_mod.set(
+        "get_domain",
+        lua.create_function(|_, domain: Option<LuaValue>| {
+            let mux = get_mux()?;
+            let domain = match domain {
+                None | Some(LuaValue::Nil) => Some(mux.default_domain()),
+                Some(LuaValue::String(name)) => mux.get_domain_by_name(name.to_str()?),
+                Some(LuaValue::Integer(id)) => mux.get_domain(id as _),
+                Some(_) => {
+                    return Err(mlua::Error::external(
+                        "get_domain expects a domain name or domain id",
+                    ))
+                }
+            };
+            Ok(domain.map(|dom| MuxDomain(dom.domain_id())))
+        })?,
+    )?;
+
+    mux_mod.set(
+        "all_domains",
+        lua.create_function(|_, _: ()| {
+            let mux = get_mux()?;
+            let mut domains = mux
+                .iter_domains()
+                .into_iter()
+                .map(|dom| MuxDomain(dom.domain_id()))
+                .collect::<Vec<MuxDomain>>();
+            domains.sort_by_key(|dom| dom.0);
+            Ok(domains)
+        })?,
+    )?;
+
     mux_mod.set(
         "spawn_window",
         lua.create_async_function(|_, spawn: SpawnWindow| async move { spawn.spawn().await })?,
     )?;
 
+    mux_mod.set(
+        "spawn_layout",
+        lua.create_async_function(
+            |_, spawn: layout::SpawnLayout| async move { spawn.spawn().await },
+        )?,
+    )?;
+
     mux_mod.set(
         "all_windows",
         lua.create_function(|_, _: ()| {
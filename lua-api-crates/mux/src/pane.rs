@@ -25,6 +25,12 @@ impl UserData for MuxPane {
             args.run(this).await
         });
 
+        methods.add_method("resize", |_, this, size: ResizePane| {
+            let mux = get_mux()?;
+            mux.resize_pane(this.0, size.cols, size.rows)
+                .map_err(|e| mlua::Error::external(format!("{:#}", e)))
+        });
+
         methods.add_method("send_paste", |_, this, text: String| {
             let mux = get_mux()?;
             let pane = this.resolve(&mux)?;
@@ -117,6 +123,12 @@ impl UserData for MuxPane {
             Ok(pane.copy_user_vars())
         });
 
+        methods.add_method("get_badge", |_, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            Ok(pane.get_badge())
+        });
+
         methods.add_method("has_unseen_output", |_, this, _: ()| {
             let mux = get_mux()?;
             let pane = this.resolve(&mux)?;
@@ -129,6 +141,19 @@ impl UserData for MuxPane {
             Ok(pane.is_alt_screen_active())
         });
 
+        methods.add_method("get_ambiguous_width_is_wide", |_, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            Ok(pane.get_ambiguous_width_is_wide())
+        });
+
+        methods.add_method("set_ambiguous_width_is_wide", |_, this, wide: bool| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            pane.set_ambiguous_width_is_wide(wide)
+                .map_err(|e| mlua::Error::external(format!("{:#}", e)))
+        });
+
         // When called with no arguments, returns the lines from the
         // viewport as plain text (no escape sequences).
         // When called with an optional integer argument, returns the
@@ -181,6 +206,23 @@ impl UserData for MuxPane {
             },
         );
 
+        methods.add_method(
+            "get_lines_with_attrs",
+            |_, this, (start, count): (Option<isize>, Option<usize>)| {
+                let mux = get_mux()?;
+                let pane = this.resolve(&mux)?;
+                let dims = pane.get_dimensions();
+                let palette = pane.palette();
+                let top_row = dims.physical_top + start.unwrap_or(0);
+                let count = count.unwrap_or(dims.viewport_rows);
+                let (_first_row, lines) = pane.get_lines(top_row..top_row + count as isize);
+                Ok(lines
+                    .iter()
+                    .map(|line| CellRun::runs_for_line(line, &palette))
+                    .collect::<Vec<_>>())
+            },
+        );
+
         methods.add_method("get_domain_name", |_, this, _: ()| {
             let mux = get_mux()?;
             let pane = this.resolve(&mux)?;
@@ -208,9 +250,130 @@ impl UserData for MuxPane {
             pane.perform_actions(actions);
             Ok(())
         });
+
+        methods.add_method("add_output_watcher", |lua, this, args: mlua::Table| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            let regex: String = args.get("regex")?;
+            let callback: mlua::Function = args.get("callback")?;
+            let user_event_id = wrap_callback(lua, callback)?;
+            mux::outputwatch::add_watcher(&*pane, &regex, user_event_id)
+                .map_err(|err| mlua::Error::external(format!("{:#}", err)))
+        });
+
+        methods.add_method("remove_output_watcher", |_, this, id: usize| {
+            Ok(mux::outputwatch::remove_watcher(this.0, id))
+        });
+
+        methods.add_method("add_auto_response", |_, this, rule: AutoResponse| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            let rule = config::AutoResponseRule {
+                regex: rule.regex,
+                response: rule.response,
+                delay_ms: rule.delay_ms,
+                max_count: rule.max_count,
+            };
+            mux::autorespond::add_response(&*pane, &rule)
+                .map_err(|err| mlua::Error::external(format!("{:#}", err)))
+        });
+
+        methods.add_method("remove_auto_response", |_, this, id: usize| {
+            Ok(mux::autorespond::remove_response(this.0, id))
+        });
+
+        methods.add_method(
+            "pipe_to",
+            |_, this, (target, args): (MuxPane, Option<mlua::Table>)| {
+                let mux = get_mux()?;
+                this.resolve(&mux)?;
+                target.resolve(&mux)?;
+                let strip_escapes = match args {
+                    Some(args) => args.get::<_, Option<bool>>("strip_escapes")?,
+                    None => None,
+                };
+                mux::panepipe::pipe_to(this.0, target.0, strip_escapes.unwrap_or(false))
+                    .map_err(|err| mlua::Error::external(format!("{:#}", err)))
+            },
+        );
+
+        methods.add_method("remove_pipe", |_, this, id: usize| {
+            Ok(mux::panepipe::remove_pipe(this.0, id))
+        });
+
+        methods.add_method(
+            "start_logging",
+            |_, this, (path, args): (String, Option<StartLogging>)| {
+                let mux = get_mux()?;
+                this.resolve(&mux)?;
+                let args = args.unwrap_or_default();
+                let path = mux::panelog::resolve_log_path(this.0, Some(&path));
+                let options = mux::panelog::LogOptions {
+                    with_escapes: args.with_escapes,
+                    max_size: args.max_size,
+                    max_files: args.max_files,
+                };
+                mux::panelog::start_logging(this.0, &path, options)
+                    .map_err(|err| mlua::Error::external(format!("{:#}", err)))
+            },
+        );
+
+        methods.add_method("stop_logging", |_, this, _: ()| {
+            Ok(mux::panelog::stop_logging(this.0))
+        });
+
+        methods.add_method("get_log_path", |_, this, _: ()| {
+            Ok(mux::panelog::log_path(this.0).map(|path| path.display().to_string()))
+        });
+
+        methods.add_method(
+            "set_escape_sequence_policy",
+            |_, this, policy: Option<config::EscapeSequencePolicy>| {
+                let mux = get_mux()?;
+                this.resolve(&mux)?;
+                mux::escape_permissions::set_pane_policy(this.0, policy);
+                Ok(())
+            },
+        );
     }
 }
 
+#[derive(Debug, FromDynamic, ToDynamic)]
+struct AutoResponse {
+    regex: String,
+    response: String,
+    #[dynamic(default)]
+    delay_ms: u64,
+    #[dynamic(default)]
+    max_count: Option<usize>,
+}
+impl_lua_conversion_dynamic!(AutoResponse);
+
+#[derive(Debug, FromDynamic, ToDynamic)]
+struct StartLogging {
+    #[dynamic(default)]
+    with_escapes: bool,
+    #[dynamic(default)]
+    max_size: Option<u64>,
+    #[dynamic(default = "default_log_max_files")]
+    max_files: usize,
+}
+impl_lua_conversion_dynamic!(StartLogging);
+
+impl Default for StartLogging {
+    fn default() -> Self {
+        Self {
+            with_escapes: false,
+            max_size: None,
+            max_files: default_log_max_files(),
+        }
+    }
+}
+
+fn default_log_max_files() -> usize {
+    mux::panelog::LogOptions::default().max_files
+}
+
 #[derive(Debug, Default, FromDynamic, ToDynamic)]
 struct SplitPane {
     #[dynamic(flatten)]
@@ -238,19 +401,42 @@ impl SplitPane {
             command_dir,
         };
 
+        let direction = match self.direction {
+            HandySplitDirection::Right | HandySplitDirection::Left => SplitDirection::Horizontal,
+            HandySplitDirection::Top | HandySplitDirection::Bottom => SplitDirection::Vertical,
+        };
+
+        let mux = get_mux()?;
         let size = if self.size == 0.0 {
             SplitSize::Percent(50)
         } else if self.size < 1.0 {
-            SplitSize::Percent((self.size * 100.).floor() as u8)
+            // Compute the number of cells from the space that is being
+            // split, rather than rounding to a whole percentage
+            let available = if self.top_level {
+                let (_domain_id, _window_id, tab_id) =
+                    mux.resolve_pane_id(pane.0).ok_or_else(|| {
+                        mlua::Error::external(format!("pane id {} not found in mux", pane.0))
+                    })?;
+                let tab = mux.get_tab(tab_id).ok_or_else(|| {
+                    mlua::Error::external(format!("tab id {} not found in mux", tab_id))
+                })?;
+                let size = tab.get_size();
+                match direction {
+                    SplitDirection::Horizontal => size.cols,
+                    SplitDirection::Vertical => size.rows,
+                }
+            } else {
+                let dims = pane.resolve(&mux)?.get_dimensions();
+                match direction {
+                    SplitDirection::Horizontal => dims.cols,
+                    SplitDirection::Vertical => dims.viewport_rows,
+                }
+            };
+            SplitSize::Cells(((available as f32 * self.size).round() as usize).max(1))
         } else {
             SplitSize::Cells(self.size as usize)
         };
 
-        let direction = match self.direction {
-            HandySplitDirection::Right | HandySplitDirection::Left => SplitDirection::Horizontal,
-            HandySplitDirection::Top | HandySplitDirection::Bottom => SplitDirection::Vertical,
-        };
-
         let request = SplitRequest {
             direction,
             target_is_second: match self.direction {
@@ -261,7 +447,6 @@ impl SplitPane {
             size,
         };
 
-        let mux = get_mux()?;
         let (pane, _size) = mux
             .split_pane(pane.0, request, source, self.domain)
             .await
@@ -270,3 +455,65 @@ impl SplitPane {
         Ok(MuxPane(pane.pane_id()))
     }
 }
+
+#[derive(Debug, Default, FromDynamic, ToDynamic)]
+struct ResizePane {
+    cols: Option<usize>,
+    rows: Option<usize>,
+}
+impl_lua_conversion_dynamic!(ResizePane);
+
+/// A sequence of cells on a line that share the same attributes
+#[derive(Debug, FromDynamic, ToDynamic)]
+struct CellRun {
+    text: String,
+    /// The cell index of the first cell in the run
+    column: usize,
+    /// The number of cells occupied by the run
+    width: usize,
+    fg: String,
+    bg: String,
+    intensity: Intensity,
+    underline: Underline,
+    hyperlink: Option<String>,
+}
+impl_lua_conversion_dynamic!(CellRun);
+
+impl CellRun {
+    fn new(cell: &CellRef, palette: &ColorPalette) -> Self {
+        let attrs = cell.attrs();
+        Self {
+            text: String::new(),
+            column: cell.cell_index(),
+            width: 0,
+            fg: palette.resolve_fg(attrs.foreground()).to_rgb_string(),
+            bg: palette.resolve_bg(attrs.background()).to_rgb_string(),
+            intensity: attrs.intensity(),
+            underline: attrs.underline(),
+            hyperlink: attrs.hyperlink().map(|link| link.uri().to_string()),
+        }
+    }
+
+    fn runs_for_line(line: &Line, palette: &ColorPalette) -> Vec<Self> {
+        let mut runs: Vec<Self> = vec![];
+        for cell in line.visible_cells() {
+            let run = Self::new(&cell, palette);
+            match runs.last_mut() {
+                Some(last) if last.same_attrs(&run) => {}
+                _ => runs.push(run),
+            }
+            let last = runs.last_mut().expect("just pushed");
+            last.text.push_str(cell.str());
+            last.width += cell.width();
+        }
+        runs
+    }
+
+    fn same_attrs(&self, other: &Self) -> bool {
+        self.fg == other.fg
+            && self.bg == other.bg
+            && self.intensity == other.intensity
+            && self.underline == other.underline
+            && self.hyperlink == other.hyperlink
+    }
+}
@@ -47,6 +47,32 @@ impl UserData for MuxWindow {
         methods.add_async_method("spawn_tab", |_, this, spawn: SpawnTab| async move {
             spawn.spawn(this).await
         });
+        methods.add_async_method(
+            "spawn_layout",
+            |_, this, layout: layout::LayoutNode| async move {
+                let mux = get_mux()?;
+                let (size, pane) = {
+                    let window = this.resolve(&mux)?;
+                    let size = window
+                        .get_by_idx(0)
+                        .map(|tab| tab.get_size())
+                        .unwrap_or_else(|| config::configuration().initial_size(0));
+                    let pane = window
+                        .get_active()
+                        .and_then(|tab| tab.get_active_pane().map(|pane| pane.pane_id()));
+                    (size, pane)
+                };
+                layout::spawn_layout(
+                    Some(this.0),
+                    layout,
+                    SpawnTabDomain::CurrentPaneDomain,
+                    size,
+                    pane,
+                    String::new(),
+                )
+                .await
+            },
+        );
         methods.add_method("get_title", |_, this, _: ()| {
             let mux = get_mux()?;
             let window = this.resolve(&mux)?;
@@ -0,0 +1,649 @@
+//! Implements `wezterm.plugin`, which installs plugins from git
+//! repositories and loads them into the config.
+//!
+//! Plugins are cloned into the data directory.  The commit that was
+//! chosen for each plugin is recorded in `plugins.lock` alongside the
+//! config file, so that the same versions are used until the plugins
+//! are explicitly updated with `wezterm plugin update`.
+use anyhow::{anyhow, bail, Context};
+use config::lua::get_or_create_sub_module;
+use config::lua::mlua::{self, Lua, Table, Value};
+use git2::build::CheckoutBuilder;
+use git2::{Oid, Repository};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The version of the plugin API that wezterm provides.  Plugins declare
+/// the version that they were written against in their manifest.
+pub const API_VERSION: u32 = 1;
+
+const MANIFEST_NAME: &str = "wezterm-plugin.toml";
+const LOCK_FILE_NAME: &str = "plugins.lock";
+const LOADED_KEY: &str = "wezterm-plugin-loaded";
+
+fn plugins_dir() -> PathBuf {
+    config::DATA_DIR.join("plugins")
+}
+
+fn lock_file_path() -> PathBuf {
+    config::CONFIG_DIR.join(LOCK_FILE_NAME)
+}
+
+/// Returns the name of the directories of the plugin at `url`.
+/// Characters other than ASCII alphanumerics, `-` and `.` are encoded
+/// as `_` followed by their hex value, so that distinct urls always
+/// map to distinct directories.  A leading `.` is encoded too, so the
+/// name can be neither `.` nor `..`.
+fn component_for_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    let url = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let mut component = String::with_capacity(url.len());
+    for (idx, b) in url.bytes().enumerate() {
+        if b.is_ascii_alphanumeric() || b == b'-' || (b == b'.' && idx > 0) {
+            component.push(b as char);
+        } else {
+            component.push_str(&format!("_{:02X}", b));
+        }
+    }
+    component
+}
+
+fn plugin_dir(url: &str) -> PathBuf {
+    plugins_dir().join(component_for_url(url))
+}
+
+/// Returns the directory in which the plugin at `url` may keep its
+/// own data, creating it if needed.  It is only accessible to the
+/// current user and is kept when the plugin is updated.
+/// Note that this is not a sandbox: any lua code can ask for the
+/// directory of any plugin.
+pub fn data_dir(url: &str) -> anyhow::Result<PathBuf> {
+    let dir = config::DATA_DIR
+        .join("plugin-data")
+        .join(component_for_url(url));
+    config::create_user_owned_dirs(&dir)?;
+    Ok(dir)
+}
+
+/// The optional `wezterm-plugin.toml` at the top of a plugin repository
+#[derive(Debug, Deserialize, PartialEq)]
+struct Manifest {
+    #[serde(default = "default_api_version")]
+    api_version: u32,
+    /// Maps the urls of the plugins that this plugin requires to
+    /// their version requirements
+    #[serde(default)]
+    dependencies: BTreeMap<String, String>,
+}
+
+fn default_api_version() -> u32 {
+    API_VERSION
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            api_version: API_VERSION,
+            dependencies: BTreeMap::new(),
+        }
+    }
+}
+
+impl Manifest {
+    fn parse(s: &str) -> anyhow::Result<Self> {
+        let manifest: Self = toml::from_str(s)?;
+        if manifest.api_version > API_VERSION {
+            bail!(
+                "requires plugin API version {}, but this version of wezterm provides {}",
+                manifest.api_version,
+                API_VERSION
+            );
+        }
+        Ok(manifest)
+    }
+
+    fn load(dir: &Path) -> anyhow::Result<Self> {
+        let path = dir.join(MANIFEST_NAME);
+        match std::fs::read_to_string(&path) {
+            Ok(s) => Self::parse(&s).with_context(|| format!("in {}", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+}
+
+/// The version of a plugin that is recorded in the lock file
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LockedPlugin {
+    pub url: String,
+    /// The version requirement that the plugin was required with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// The tag that satisfied `version`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    pub commit: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+struct LockFile {
+    #[serde(default, rename = "plugin")]
+    plugins: Vec<LockedPlugin>,
+}
+
+impl LockFile {
+    fn load() -> anyhow::Result<Self> {
+        let path = lock_file_path();
+        match std::fs::read_to_string(&path) {
+            Ok(s) => toml::from_str(&s).with_context(|| format!("parsing {}", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err).with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let path = lock_file_path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let s = toml::to_string_pretty(self)?;
+        std::fs::write(&path, s).with_context(|| format!("writing {}", path.display()))
+    }
+
+    fn get(&self, url: &str) -> Option<&LockedPlugin> {
+        self.plugins.iter().find(|p| p.url == url)
+    }
+
+    fn set(&mut self, entry: LockedPlugin) {
+        match self.plugins.iter_mut().find(|p| p.url == entry.url) {
+            Some(p) => *p = entry,
+            None => {
+                self.plugins.push(entry);
+                self.plugins.sort_by(|a, b| a.url.cmp(&b.url));
+            }
+        }
+    }
+
+    fn remove(&mut self, url: &str) -> bool {
+        let len = self.plugins.len();
+        self.plugins.retain(|p| p.url != url);
+        self.plugins.len() != len
+    }
+}
+
+fn tag_version(tag: &str) -> Option<Version> {
+    Version::parse(tag.strip_prefix('v').unwrap_or(tag)).ok()
+}
+
+/// Returns the tag with the highest version that satisfies `req`
+fn select_tag<'a>(tags: impl Iterator<Item = &'a str>, req: &VersionReq) -> Option<&'a str> {
+    tags.filter_map(|tag| {
+        tag_version(tag)
+            .filter(|version| req.matches(version))
+            .map(|version| (version, tag))
+    })
+    .max_by(|a, b| a.0.cmp(&b.0))
+    .map(|(_, tag)| tag)
+}
+
+fn parse_requirement(url: &str, version: &str) -> anyhow::Result<VersionReq> {
+    VersionReq::parse(version)
+        .with_context(|| format!("invalid version requirement {} for plugin {}", version, url))
+}
+
+/// Checks that the already loaded version of a plugin satisfies
+/// another requirement for it
+fn check_compatible(url: &str, tag: Option<&str>, version: Option<&str>) -> anyhow::Result<()> {
+    let version = match version {
+        Some(version) => version,
+        None => return Ok(()),
+    };
+    let req = parse_requirement(url, version)?;
+    match tag {
+        Some(tag) if tag_version(tag).filter(|v| req.matches(v)).is_some() => Ok(()),
+        Some(tag) => bail!(
+            "plugin {} is required as {}, but version {} is already in use",
+            url,
+            version,
+            tag
+        ),
+        None => bail!(
+            "plugin {} is required as {}, but its default branch is already in use",
+            url,
+            version
+        ),
+    }
+}
+
+fn open_or_clone(url: &str, dir: &Path) -> anyhow::Result<Repository> {
+    if dir.exists() {
+        return Repository::open(dir).with_context(|| format!("opening {}", dir.display()));
+    }
+    if let Some(parent) = dir.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    log::info!("cloning plugin {} into {}", url, dir.display());
+    Repository::clone(url, dir).with_context(|| format!("cloning plugin {}", url))
+}
+
+fn fetch(repo: &Repository) -> anyhow::Result<()> {
+    let mut remote = repo.find_remote("origin")?;
+    remote.fetch(
+        &[
+            "+refs/heads/*:refs/remotes/origin/*",
+            "+refs/tags/*:refs/tags/*",
+        ],
+        None,
+        None,
+    )?;
+    Ok(())
+}
+
+fn default_branch_commit(repo: &Repository) -> anyhow::Result<Oid> {
+    for name in [
+        "refs/remotes/origin/HEAD",
+        "refs/remotes/origin/main",
+        "refs/remotes/origin/master",
+    ] {
+        if let Ok(reference) = repo.find_reference(name) {
+            return Ok(reference.resolve()?.peel_to_commit()?.id());
+        }
+    }
+    Ok(repo.head()?.peel_to_commit()?.id())
+}
+
+fn checkout(repo: &Repository, commit: Oid) -> anyhow::Result<()> {
+    if repo.head().ok().and_then(|head| head.target()) == Some(commit) {
+        return Ok(());
+    }
+    repo.set_head_detached(commit)?;
+    repo.checkout_head(Some(CheckoutBuilder::new().force()))?;
+    Ok(())
+}
+
+/// Works out which commit of the plugin satisfies `version`; the
+/// highest tagged version that matches it, or the head of the default
+/// branch when there is no requirement.  When `fetched` is false, the
+/// remote is only consulted if the clone has no matching tag.
+fn resolve(
+    repo: &Repository,
+    url: &str,
+    version: Option<&str>,
+    mut fetched: bool,
+) -> anyhow::Result<LockedPlugin> {
+    let (tag, commit) = match version {
+        Some(version) => {
+            let req = parse_requirement(url, version)?;
+            let tag = loop {
+                let tags = repo.tag_names(None)?;
+                if let Some(tag) = select_tag(tags.iter().flatten(), &req) {
+                    break tag.to_string();
+                }
+                if fetched {
+                    bail!("plugin {} has no version that satisfies {}", url, version);
+                }
+                fetch(repo)?;
+                fetched = true;
+            };
+            let commit = repo
+                .revparse_single(&format!("refs/tags/{}", tag))?
+                .peel_to_commit()?
+                .id();
+            (Some(tag), commit)
+        }
+        None => (None, default_branch_commit(repo)?),
+    };
+    Ok(LockedPlugin {
+        url: url.to_string(),
+        version: version.map(str::to_string),
+        tag,
+        commit: commit.to_string(),
+    })
+}
+
+/// Makes sure that the plugin at `url` is checked out at the commit
+/// recorded in the lock file, or at a commit that satisfies `version`
+/// if it isn't locked with that requirement yet
+fn install(url: &str, version: Option<&str>) -> anyhow::Result<(PathBuf, LockedPlugin)> {
+    let dir = plugin_dir(url);
+    let repo = open_or_clone(url, &dir)?;
+    let mut lock = LockFile::load()?;
+    let entry = match lock.get(url) {
+        Some(locked) if locked.version.as_deref() == version => locked.clone(),
+        _ => {
+            let entry = resolve(&repo, url, version, false)?;
+            lock.set(entry.clone());
+            lock.save()?;
+            entry
+        }
+    };
+    let commit = Oid::from_str(&entry.commit)?;
+    if repo.find_commit(commit).is_err() {
+        // The clone was replaced since the lock file was written
+        fetch(&repo)?;
+    }
+    checkout(&repo, commit)?;
+    Ok((dir, entry))
+}
+
+/// Information about an installed plugin
+#[derive(Debug, Clone)]
+pub struct PluginInfo {
+    pub url: String,
+    pub plugin_dir: PathBuf,
+    pub version: Option<String>,
+    pub tag: Option<String>,
+    pub commit: String,
+}
+
+impl From<LockedPlugin> for PluginInfo {
+    fn from(locked: LockedPlugin) -> Self {
+        Self {
+            plugin_dir: plugin_dir(&locked.url),
+            url: locked.url,
+            version: locked.version,
+            tag: locked.tag,
+            commit: locked.commit,
+        }
+    }
+}
+
+/// Returns the plugins that are recorded in the lock file
+pub fn list_plugins() -> anyhow::Result<Vec<PluginInfo>> {
+    Ok(LockFile::load()?
+        .plugins
+        .into_iter()
+        .map(PluginInfo::from)
+        .collect())
+}
+
+/// Fetches the plugin at `url` and moves it to the newest commit that
+/// satisfies the version requirement that it was installed with
+pub fn update_plugin(url: &str) -> anyhow::Result<PluginInfo> {
+    let mut lock = LockFile::load()?;
+    let locked = lock
+        .get(url)
+        .cloned()
+        .ok_or_else(|| anyhow!("plugin {} is not installed", url))?;
+    let repo = open_or_clone(url, &plugin_dir(url))?;
+    fetch(&repo)?;
+    let entry = resolve(&repo, url, locked.version.as_deref(), true)?;
+    checkout(&repo, Oid::from_str(&entry.commit)?)?;
+    if entry != locked {
+        log::info!("updated plugin {} to {}", url, entry.commit);
+        lock.set(entry.clone());
+        lock.save()?;
+    }
+    Ok(entry.into())
+}
+
+/// Updates all of the plugins in the lock file
+pub fn update_all() -> anyhow::Result<Vec<PluginInfo>> {
+    let mut updated = vec![];
+    for plugin in list_plugins()? {
+        updated.push(update_plugin(&plugin.url)?);
+    }
+    Ok(updated)
+}
+
+/// Removes the plugin at `url`, and its data directory unless
+/// `keep_data` is true
+pub fn remove_plugin(url: &str, keep_data: bool) -> anyhow::Result<()> {
+    let mut lock = LockFile::load()?;
+    let dir = plugin_dir(url);
+    if !lock.remove(url) && !dir.exists() {
+        bail!("plugin {} is not installed", url);
+    }
+    lock.save()?;
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).with_context(|| format!("removing {}", dir.display()))?;
+    }
+    if !keep_data {
+        let data = data_dir(url)?;
+        std::fs::remove_dir_all(&data).with_context(|| format!("removing {}", data.display()))?;
+    }
+    Ok(())
+}
+
+/// Returns the table that tracks the plugins that have been loaded
+/// into this lua context
+fn loaded_table(lua: &Lua) -> mlua::Result<Table> {
+    let table: Option<Table> = lua.named_registry_value(LOADED_KEY)?;
+    match table {
+        Some(table) => Ok(table),
+        None => {
+            let table = lua.create_table()?;
+            lua.set_named_registry_value(LOADED_KEY, table.clone())?;
+            Ok(table)
+        }
+    }
+}
+
+fn add_to_package_path(lua: &Lua, dir: &Path) -> anyhow::Result<()> {
+    let package: Table = lua.globals().get("package")?;
+    let path: String = package.get("path")?;
+    let plugin = dir.join("plugin");
+    package.set(
+        "path",
+        format!(
+            "{}/?.lua;{}/?/init.lua;{}",
+            plugin.display(),
+            plugin.display(),
+            path
+        ),
+    )?;
+    Ok(())
+}
+
+fn load_plugin<'lua>(
+    lua: &'lua Lua,
+    url: &str,
+    version: Option<&str>,
+) -> anyhow::Result<Value<'lua>> {
+    let loaded = loaded_table(lua)?;
+    if let Some(entry) = loaded.get::<_, Option<Table>>(url)? {
+        if entry.get::<_, bool>("loading")? {
+            bail!("plugin {} depends on itself", url);
+        }
+        let tag: Option<String> = entry.get("tag")?;
+        check_compatible(url, tag.as_deref(), version)?;
+        return Ok(entry.get("module")?);
+    }
+
+    let (dir, locked) = install(url, version)?;
+    let entry = lua.create_table()?;
+    entry.set("loading", true)?;
+    entry.set("tag", locked.tag.clone())?;
+    loaded.set(url, entry.clone())?;
+
+    let result = (|| -> anyhow::Result<Value<'lua>> {
+        let manifest = Manifest::load(&dir).with_context(|| format!("plugin {}", url))?;
+        for (dep_url, dep_version) in &manifest.dependencies {
+            load_plugin(lua, dep_url, Some(dep_version))
+                .with_context(|| format!("loading {}, required by plugin {}", dep_url, url))?;
+        }
+
+        add_to_package_path(lua, &dir)?;
+        let init = dir.join("plugin").join("init.lua");
+        let code = std::fs::read_to_string(&init)
+            .with_context(|| format!("reading {}", init.display()))?;
+
+        // The chunk receives a table describing the plugin as `...`
+        let context = lua.create_table()?;
+        context.set("url", url)?;
+        context.set("plugin_dir", dir.to_string_lossy().to_string())?;
+        context.set("data_dir", data_dir(url)?.to_string_lossy().to_string())?;
+        context.set("tag", locked.tag.clone())?;
+        context.set("commit", locked.commit.clone())?;
+
+        let name = init.to_string_lossy().to_string();
+        let module: Value = lua.load(&code).set_name(&name)?.call(context)?;
+        Ok(module)
+    })();
+
+    match result {
+        Ok(module) => {
+            entry.set("module", module.clone())?;
+            entry.set("loading", false)?;
+            Ok(module)
+        }
+        Err(err) => {
+            loaded.set(url, Value::Nil)?;
+            Err(err)
+        }
+    }
+}
+
+fn plugin_info_to_lua<'lua>(lua: &'lua Lua, info: PluginInfo) -> mlua::Result<Table<'lua>> {
+    let table = lua.create_table()?;
+    table.set("url", info.url)?;
+    table.set("plugin_dir", info.plugin_dir.to_string_lossy().to_string())?;
+    table.set("version", info.version)?;
+    table.set("tag", info.tag)?;
+    table.set("commit", info.commit)?;
+    Ok(table)
+}
+
+pub fn register(lua: &Lua) -> anyhow::Result<()> {
+    let plugin_mod = get_or_create_sub_module(lua, "plugin")?;
+    plugin_mod.set("API_VERSION", API_VERSION)?;
+
+    plugin_mod.set(
+        "require",
+        lua.create_function(|lua, (url, opts): (String, Option<Table>)| {
+            let version: Option<String> = match &opts {
+                Some(opts) => opts.get("version")?,
+                None => None,
+            };
+            load_plugin(lua, &url, version.as_deref()).map_err(mlua::Error::external)
+        })?,
+    )?;
+
+    plugin_mod.set(
+        "list",
+        lua.create_function(|lua, _: ()| {
+            let plugins = list_plugins().map_err(mlua::Error::external)?;
+            plugins
+                .into_iter()
+                .map(|info| plugin_info_to_lua(lua, info))
+                .collect::<mlua::Result<Vec<_>>>()
+        })?,
+    )?;
+
+    plugin_mod.set(
+        "update_all",
+        lua.create_function(|lua, _: ()| {
+            let plugins = update_all().map_err(mlua::Error::external)?;
+            plugins
+                .into_iter()
+                .map(|info| plugin_info_to_lua(lua, info))
+                .collect::<mlua::Result<Vec<_>>>()
+        })?,
+    )?;
+
+    plugin_mod.set(
+        "data_dir",
+        lua.create_function(|_, url: String| {
+            let dir = data_dir(&url).map_err(mlua::Error::external)?;
+            Ok(dir.to_string_lossy().to_string())
+        })?,
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn component() {
+        assert_eq!(
+            component_for_url("https://github.com/owner/repo.git"),
+            "github.com_2Fowner_2Frepo"
+        );
+        assert_eq!(
+            component_for_url("https://github.com/owner/repo/"),
+            "github.com_2Fowner_2Frepo"
+        );
+        assert_eq!(
+            component_for_url("file:///home/me/my plugin"),
+            "_2Fhome_2Fme_2Fmy_20plugin"
+        );
+        assert_eq!(component_for_url(".."), "_2E.");
+        assert_ne!(component_for_url("a-b/c"), component_for_url("a_b/c"));
+        assert_ne!(component_for_url("a_b/c"), component_for_url("a_b_c"));
+    }
+
+    #[test]
+    fn tag_selection() {
+        let tags = [
+            "v1.0.0",
+            "v1.2.0",
+            "1.3.1",
+            "v2.0.0",
+            "nightly",
+            "v1.4.0-beta.1",
+        ];
+        let select = |req: &str| select_tag(tags.iter().copied(), &VersionReq::parse(req).unwrap());
+        assert_eq!(select("^1.0"), Some("1.3.1"));
+        assert_eq!(select("~1.2"), Some("v1.2.0"));
+        assert_eq!(select(">=2"), Some("v2.0.0"));
+        assert_eq!(select("^3"), None);
+    }
+
+    #[test]
+    fn compatible() {
+        assert!(check_compatible("p", Some("v1.2.0"), None).is_ok());
+        assert!(check_compatible("p", Some("v1.2.0"), Some("^1.1")).is_ok());
+        assert!(check_compatible("p", Some("v1.2.0"), Some("^2")).is_err());
+        assert!(check_compatible("p", None, Some("^1")).is_err());
+    }
+
+    #[test]
+    fn manifest() {
+        let manifest = Manifest::parse(
+            r#"
+api_version = 1
+
+[dependencies]
+"https://github.com/owner/lib" = "^1.0"
+"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.api_version, 1);
+        assert_eq!(
+            manifest.dependencies.get("https://github.com/owner/lib"),
+            Some(&"^1.0".to_string())
+        );
+        assert_eq!(Manifest::parse("").unwrap(), Manifest::default());
+        assert!(Manifest::parse("api_version = 99").is_err());
+    }
+
+    #[test]
+    fn lock_file() {
+        let mut lock = LockFile::default();
+        lock.set(LockedPlugin {
+            url: "https://b".to_string(),
+            version: None,
+            tag: None,
+            commit: "2222".to_string(),
+        });
+        lock.set(LockedPlugin {
+            url: "https://a".to_string(),
+            version: Some("^1".to_string()),
+            tag: Some("v1.0.0".to_string()),
+            commit: "1111".to_string(),
+        });
+        let s = toml::to_string_pretty(&lock).unwrap();
+        let parsed: LockFile = toml::from_str(&s).unwrap();
+        assert_eq!(parsed, lock);
+        assert_eq!(parsed.plugins[0].url, "https://a");
+        assert!(lock.remove("https://a"));
+        assert!(!lock.remove("https://a"));
+        assert_eq!(lock.plugins.len(), 1);
+    }
+}
@@ -0,0 +1,27 @@
+use config::lua::get_or_create_sub_module;
+use config::lua::mlua::{self, Lua};
+
+pub fn register(lua: &Lua) -> anyhow::Result<()> {
+    let secrets_mod = get_or_create_sub_module(lua, "secrets")?;
+    secrets_mod.set("get", lua.create_async_function(get)?)?;
+    Ok(())
+}
+
+/// Looks up a password in the OS keychain.
+/// Accessing the keychain may block while the OS prompts the user
+/// to grant access, so the lookup happens on a background thread.
+async fn get<'lua>(
+    _: &'lua Lua,
+    (service, account): (String, String),
+) -> mlua::Result<Option<String>> {
+    smol::unblock(
+        move || match keyring::Entry::new(&service, &account).get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(err) => Err(mlua::Error::external(format!(
+                "keychain service={service} account={account}: {err:#}"
+            ))),
+        },
+    )
+    .await
+}
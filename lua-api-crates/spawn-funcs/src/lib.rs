@@ -1,6 +1,9 @@
 use bstr::BString;
 use config::lua::get_or_create_module;
 use config::lua::mlua::{self, Lua};
+use smol::prelude::*;
+use smol::process::Stdio;
+use std::time::Duration;
 
 pub fn register(lua: &Lua) -> anyhow::Result<()> {
     let wezterm_mod = get_or_create_module(lua, "wezterm")?;
@@ -31,8 +34,28 @@ fn open_with<'lua>(_: &'lua Lua, (url, app): (String, Option<String>)) -> mlua::
 
 async fn run_child_process<'lua>(
     _: &'lua Lua,
-    args: Vec<String>,
+    (args, options): (Vec<String>, Option<mlua::Table<'lua>>),
 ) -> mlua::Result<(bool, BString, BString)> {
+    let (timeout, on_stdout, on_stderr) = match &options {
+        Some(options) => (
+            options.get::<_, Option<f64>>("timeout")?,
+            options.get::<_, Option<mlua::Function>>("on_stdout")?,
+            options.get::<_, Option<mlua::Function>>("on_stderr")?,
+        ),
+        None => (None, None, None),
+    };
+    let timeout = match timeout {
+        Some(seconds) if seconds.is_finite() && seconds >= 0. => {
+            Some(Duration::from_secs_f64(seconds))
+        }
+        Some(seconds) => {
+            return Err(mlua::Error::external(format!(
+                "{seconds} is not a valid timeout"
+            )));
+        }
+        None => None,
+    };
+
     let mut cmd = smol::process::Command::new(&args[0]);
 
     if args.len() > 1 {
@@ -45,13 +68,75 @@ async fn run_child_process<'lua>(
         cmd.creation_flags(winapi::um::winbase::CREATE_NO_WINDOW);
     }
 
-    let output = cmd.output().await.map_err(|e| mlua::Error::external(e))?;
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = cmd.spawn().map_err(|e| mlua::Error::external(e))?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+
+    let run = async {
+        let (stdout, stderr) = smol::future::try_zip(
+            read_output(stdout, on_stdout.as_ref()),
+            read_output(stderr, on_stderr.as_ref()),
+        )
+        .await?;
+        let status = child.status().await.map_err(|e| mlua::Error::external(e))?;
+        Ok::<_, mlua::Error>(Some((status.success(), stdout, stderr)))
+    };
 
-    Ok((
-        output.status.success(),
-        output.stdout.into(),
-        output.stderr.into(),
-    ))
+    let result = match timeout {
+        Some(duration) => {
+            run.or(async {
+                smol::Timer::after(duration).await;
+                Ok(None)
+            })
+            .await?
+        }
+        None => run.await?,
+    };
+
+    match result {
+        Some(result) => Ok(result),
+        None => {
+            child.kill().ok();
+            Err(mlua::Error::external(format!(
+                "{:?} timed out after {:?}",
+                args,
+                timeout.unwrap_or_default()
+            )))
+        }
+    }
+}
+
+/// Reads pipe until EOF, returning everything that was read.
+/// If callback is set, it is called with each line of output as it
+/// is read, without the line ending.
+async fn read_output<'lua, R: AsyncRead + Unpin>(
+    pipe: R,
+    callback: Option<&mlua::Function<'lua>>,
+) -> mlua::Result<BString> {
+    let mut reader = smol::io::BufReader::new(pipe);
+    let mut output = vec![];
+    loop {
+        let mut line = vec![];
+        let len = reader
+            .read_until(b'\n', &mut line)
+            .await
+            .map_err(|e| mlua::Error::external(e))?;
+        if len == 0 {
+            break;
+        }
+        if let Some(callback) = callback {
+            let text = line.strip_suffix(b"\n").unwrap_or(&line);
+            let text = text.strip_suffix(b"\r").unwrap_or(text);
+            callback
+                .call_async::<_, ()>(BString::from(text.to_vec()))
+                .await?;
+        }
+        output.extend_from_slice(&line);
+    }
+    Ok(output.into())
 }
 
 async fn background_child_process<'lua>(_: &'lua Lua, args: Vec<String>) -> mlua::Result<()> {
@@ -67,7 +152,7 @@ async fn background_child_process<'lua>(_: &'lua Lua, args: Vec<String>) -> mlua
         cmd.creation_flags(winapi::um::winbase::CREATE_NO_WINDOW);
     }
 
-    cmd.stdin(smol::process::Stdio::null())
+    cmd.stdin(Stdio::null())
         .spawn()
         .map_err(|e| mlua::Error::external(e))?;
 
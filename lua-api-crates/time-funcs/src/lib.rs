@@ -1,9 +1,13 @@
 use chrono::prelude::*;
-use config::lua::mlua::{self, Lua, MetaMethod, UserData, UserDataMethods};
+use config::lua::mlua::{
+    self, Lua, MetaMethod, MultiValue, RegistryKey, UserData, UserDataMethods,
+};
 use config::lua::{emit_event, get_or_create_module, get_or_create_sub_module, wrap_callback};
 use config::ConfigSubscription;
 use std::rc::Rc;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 lazy_static::lazy_static! {
     static ref CONFIG_SUBSCRIPTION: Mutex<Option<ConfigSubscription>> = Mutex::new(None);
@@ -12,7 +16,7 @@ lazy_static::lazy_static! {
 /// We contrive to call this from the main thread in response to the
 /// config being reloaded.
 /// It spawns a task for each of the timers that have been configured
-/// by the user via `wezterm.time.call_after`.
+/// by the user via `wezterm.time.call_after` and `wezterm.time.call_every`.
 fn schedule_all(lua: Option<Rc<mlua::Lua>>) -> mlua::Result<()> {
     if let Some(lua) = lua {
         let scheduled_events: Vec<ScheduledEvent> = lua.named_registry_value(SCHEDULED_EVENTS)?;
@@ -20,10 +24,41 @@ fn schedule_all(lua: Option<Rc<mlua::Lua>>) -> mlua::Result<()> {
         for event in scheduled_events {
             event.schedule(generation);
         }
+        // Timers created from now on, such as from event handlers,
+        // belong to the live lua context and can be scheduled immediately
+        lua.set_named_registry_value(SCHEDULER_IS_LIVE, true)?;
     }
     Ok(())
 }
 
+/// Schedules event, or, if the configuration is still being evaluated,
+/// arranges for it to be scheduled once the configuration is loaded.
+/// We can't schedule it right away in that case, because the lua
+/// context that is being evaluated may not be the one that is adopted
+/// as the configuration; for example, when computing window overrides.
+fn add_scheduled_event(lua: &Lua, event: ScheduledEvent) -> mlua::Result<()> {
+    let is_live: bool = lua.named_registry_value(SCHEDULER_IS_LIVE)?;
+    if is_live {
+        event.schedule(config::configuration().generation());
+    } else {
+        let mut scheduled_events: Vec<ScheduledEvent> =
+            lua.named_registry_value(SCHEDULED_EVENTS)?;
+        scheduled_events.push(event);
+        lua.set_named_registry_value(SCHEDULED_EVENTS, scheduled_events)?;
+    }
+    Ok(())
+}
+
+fn seconds_to_duration(seconds: f64) -> mlua::Result<Duration> {
+    if seconds.is_finite() && seconds >= 0. {
+        Ok(Duration::from_secs_f64(seconds))
+    } else {
+        Err(mlua::Error::external(format!(
+            "{seconds} is not a valid number of seconds"
+        )))
+    }
+}
+
 /// Helper to schedule !Send futures to run with access to the lua
 /// config on the main thread
 fn schedule_trampoline() {
@@ -51,14 +86,18 @@ pub fn config_was_reloaded() -> bool {
     true
 }
 
-/// Keeps track of `call_after` state
+/// Keeps track of `call_after` and `call_every` state
 #[derive(Debug, Clone)]
 struct ScheduledEvent {
     /// The name of the registry entry that will resolve to
     /// their callback function
     user_event_id: String,
     /// The delay after which to run their callback
-    interval_seconds: u64,
+    interval: Duration,
+    /// Whether to keep calling their callback every interval
+    repeat: bool,
+    /// Set when the user cancels the timer
+    cancelled: Arc<AtomicBool>,
 }
 
 impl ScheduledEvent {
@@ -90,13 +129,20 @@ impl ScheduledEvent {
     }
 
     async fn run(self, lua: &Lua, generation: usize) -> mlua::Result<()> {
-        let duration = std::time::Duration::from_secs(self.interval_seconds);
-        smol::Timer::after(duration).await;
-        // Skip doing anything of consequence if the generation has
-        // changed.
-        if config::configuration().generation() == generation {
+        loop {
+            smol::Timer::after(self.interval).await;
+            // Skip doing anything of consequence if the generation has
+            // changed or the timer was cancelled.
+            if config::configuration().generation() != generation
+                || self.cancelled.load(Ordering::Relaxed)
+            {
+                break;
+            }
             let args = lua.pack_multi(())?;
-            emit_event(&lua, (self.user_event_id, args)).await?;
+            emit_event(&lua, (self.user_event_id.clone(), args)).await?;
+            if !self.repeat {
+                break;
+            }
         }
         Ok(())
     }
@@ -106,7 +152,123 @@ impl UserData for ScheduledEvent {
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(_methods: &mut M) {}
 }
 
+/// Returned to lua by `call_after` and `call_every` so that the
+/// timer can be cancelled
+#[derive(Debug, Clone)]
+struct TimerHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl UserData for TimerHandle {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("cancel", |_, this, _: ()| {
+            this.cancelled.store(true, Ordering::Relaxed);
+            Ok(())
+        });
+        methods.add_method("is_cancelled", |_, this, _: ()| {
+            Ok(this.cancelled.load(Ordering::Relaxed))
+        });
+    }
+}
+
+fn create_timer<'lua>(
+    lua: &'lua Lua,
+    seconds: f64,
+    func: mlua::Function,
+    repeat: bool,
+) -> mlua::Result<TimerHandle> {
+    let interval = seconds_to_duration(seconds)?;
+    if repeat && interval.is_zero() {
+        return Err(mlua::Error::external(
+            "call_every requires an interval greater than zero",
+        ));
+    }
+    let cancelled = Arc::new(AtomicBool::new(false));
+    add_scheduled_event(
+        lua,
+        ScheduledEvent {
+            user_event_id: wrap_callback(lua, func)?,
+            interval,
+            repeat,
+            cancelled: Arc::clone(&cancelled),
+        },
+    )?;
+    Ok(TimerHandle { cancelled })
+}
+
+/// State for a function returned by `wezterm.time.debounce`
+struct Debounce {
+    func: RegistryKey,
+    interval: Duration,
+    /// The cancellation flag for the call that is waiting to run
+    pending: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+impl Debounce {
+    /// Arranges to call func with args once interval has elapsed,
+    /// cancelling any call that is already waiting
+    fn call(self: &Arc<Self>, lua: &Lua, args: MultiValue) -> mlua::Result<()> {
+        let args = args
+            .into_iter()
+            .map(|value| lua.create_registry_value(value))
+            .collect::<mlua::Result<Vec<RegistryKey>>>()?;
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        if let Some(prior) = self.pending.lock().unwrap().replace(Arc::clone(&cancelled)) {
+            prior.store(true, Ordering::Relaxed);
+        }
+
+        let state = Arc::clone(self);
+        let generation = config::configuration().generation();
+        promise::spawn::spawn(async move {
+            smol::Timer::after(state.interval).await;
+            if cancelled.load(Ordering::Relaxed)
+                || config::configuration().generation() != generation
+            {
+                return Ok(());
+            }
+            config::with_lua_config_on_main_thread(move |lua| async move {
+                if let Some(lua) = lua {
+                    let func: mlua::Function = lua.registry_value(&state.func)?;
+                    let args = args
+                        .iter()
+                        .map(|key| lua.registry_value(key))
+                        .collect::<mlua::Result<Vec<mlua::Value>>>()?;
+                    func.call_async::<_, ()>(MultiValue::from_vec(args)).await?;
+                }
+                Ok(())
+            })
+            .await
+        })
+        .detach();
+        Ok(())
+    }
+}
+
+/// State for a function returned by `wezterm.time.throttle`
+struct Throttle {
+    func: RegistryKey,
+    interval: Duration,
+    last_call: Mutex<Option<Instant>>,
+}
+
+impl Throttle {
+    /// Returns true if enough time has passed since the prior call
+    /// that func should be called now
+    fn should_call(&self) -> bool {
+        let mut last_call = self.last_call.lock().unwrap();
+        match *last_call {
+            Some(last) if last.elapsed() < self.interval => false,
+            _ => {
+                last_call.replace(Instant::now());
+                true
+            }
+        }
+    }
+}
+
 const SCHEDULED_EVENTS: &str = "wezterm-scheduled-events";
+const SCHEDULER_IS_LIVE: &str = "wezterm-scheduler-is-live";
 
 pub fn register(lua: &Lua) -> anyhow::Result<()> {
     {
@@ -116,6 +278,7 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
         }
     }
     lua.set_named_registry_value(SCHEDULED_EVENTS, Vec::<ScheduledEvent>::new())?;
+    lua.set_named_registry_value(SCHEDULER_IS_LIVE, false)?;
     let time_mod = get_or_create_sub_module(lua, "time")?;
 
     time_mod.set(
@@ -145,16 +308,49 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
 
     time_mod.set(
         "call_after",
-        lua.create_function(|lua, (interval_seconds, func): (u64, mlua::Function)| {
-            let user_event_id = wrap_callback(lua, func)?;
-            let mut scheduled_events: Vec<ScheduledEvent> =
-                lua.named_registry_value(SCHEDULED_EVENTS)?;
-            scheduled_events.push(ScheduledEvent {
-                user_event_id,
-                interval_seconds,
+        lua.create_function(|lua, (interval_seconds, func): (f64, mlua::Function)| {
+            create_timer(lua, interval_seconds, func, false)
+        })?,
+    )?;
+
+    time_mod.set(
+        "call_every",
+        lua.create_function(|lua, (interval_seconds, func): (f64, mlua::Function)| {
+            create_timer(lua, interval_seconds, func, true)
+        })?,
+    )?;
+
+    time_mod.set(
+        "debounce",
+        lua.create_function(|lua, (interval_seconds, func): (f64, mlua::Function)| {
+            let state = Arc::new(Debounce {
+                func: lua.create_registry_value(func)?,
+                interval: seconds_to_duration(interval_seconds)?,
+                pending: Mutex::new(None),
             });
-            lua.set_named_registry_value(SCHEDULED_EVENTS, scheduled_events)?;
-            Ok(())
+            lua.create_function(move |lua, args: MultiValue| state.call(lua, args))
+        })?,
+    )?;
+
+    time_mod.set(
+        "throttle",
+        lua.create_function(|lua, (interval_seconds, func): (f64, mlua::Function)| {
+            let state = Arc::new(Throttle {
+                func: lua.create_registry_value(func)?,
+                interval: seconds_to_duration(interval_seconds)?,
+                last_call: Mutex::new(None),
+            });
+            lua.create_async_function(move |lua, args: MultiValue| {
+                let state = Arc::clone(&state);
+                async move {
+                    if !state.should_call() {
+                        return Ok(false);
+                    }
+                    let func: mlua::Function = lua.registry_value(&state.func)?;
+                    func.call_async::<_, ()>(args).await?;
+                    Ok(true)
+                }
+            })
         })?,
     )?;
 
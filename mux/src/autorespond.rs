@@ -0,0 +1,334 @@
+//! Auto-responses send text to a pane when its output matches a
+//! regular expression, which allows for simple expect-style automation
+//! such as answering a confirmation prompt.
+//!
+//! Responses come from the `auto_responses` configuration, which
+//! applies to every pane, and from `pane:add_auto_response`, which
+//! applies to a single pane.  Unlike output watchers, the line that
+//! holds the cursor is examined as well as completed lines, because
+//! prompts usually leave the cursor at the end of the line.  Each
+//! match in the output produces a single response.
+use crate::pane::{Pane, PaneId};
+use crate::Mux;
+use config::{configuration, AutoResponseRule};
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use wezterm_term::StableRowIndex;
+
+/// The most rows that are examined after a single batch of output
+const MAX_ROWS_PER_CHECK: StableRowIndex = 10_000;
+
+struct Responder {
+    regex: Regex,
+    response: String,
+    delay: Duration,
+    max_count: Option<usize>,
+}
+
+impl Responder {
+    fn new(rule: &AutoResponseRule) -> anyhow::Result<Self> {
+        Ok(Self {
+            regex: Regex::new(&rule.regex)?,
+            response: rule.response.clone(),
+            delay: Duration::from_millis(rule.delay_ms),
+            max_count: rule.max_count,
+        })
+    }
+}
+
+/// A responder that was added to a single pane
+struct PaneResponder {
+    id: usize,
+    responder: Responder,
+    count: usize,
+}
+
+#[derive(Default)]
+struct PaneState {
+    responders: Vec<PaneResponder>,
+    /// How many responses each of the configured rules has made
+    /// in this pane, for the configuration generation below
+    config_counts: Vec<usize>,
+    config_generation: usize,
+    /// The first row that has yet to be completely examined
+    next_row: Option<StableRowIndex>,
+    /// The first row of the logical line that held the cursor when
+    /// it was last examined, and how much of its text was matched
+    partial: Option<(StableRowIndex, usize)>,
+}
+
+/// The compiled `auto_responses` from the configuration
+struct ConfigRules {
+    generation: usize,
+    responders: Vec<Responder>,
+}
+
+lazy_static::lazy_static! {
+    static ref STATES: Mutex<HashMap<PaneId, PaneState>> = Mutex::new(HashMap::new());
+    static ref CONFIG_RULES: Mutex<Option<ConfigRules>> = Mutex::new(None);
+}
+static NEXT_RESPONDER_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Adds a rule to the pane that responds to its subsequent output.
+/// Returns an id that can be passed to `remove_response`.
+pub fn add_response(pane: &dyn Pane, rule: &AutoResponseRule) -> anyhow::Result<usize> {
+    let responder = Responder::new(rule)?;
+    let id = NEXT_RESPONDER_ID.fetch_add(1, Ordering::Relaxed);
+    let mut states = STATES.lock().unwrap();
+    let state = states.entry(pane.pane_id()).or_default();
+    state
+        .next_row
+        .get_or_insert_with(|| pane.get_cursor_position().y);
+    state.responders.push(PaneResponder {
+        id,
+        responder,
+        count: 0,
+    });
+    Ok(id)
+}
+
+/// Removes a rule that was added by `add_response`.
+/// Returns false if there was no such rule.
+pub fn remove_response(pane_id: PaneId, id: usize) -> bool {
+    let mut states = STATES.lock().unwrap();
+    match states.get_mut(&pane_id) {
+        Some(state) => {
+            let before = state.responders.len();
+            state.responders.retain(|r| r.id != id);
+            state.responders.len() != before
+        }
+        None => false,
+    }
+}
+
+pub(crate) fn remove_pane(pane_id: PaneId) {
+    STATES.lock().unwrap().remove(&pane_id);
+}
+
+fn compile_rules(rules: &[AutoResponseRule]) -> Vec<Responder> {
+    rules
+        .iter()
+        .filter_map(|rule| match Responder::new(rule) {
+            Ok(responder) => Some(responder),
+            Err(err) => {
+                log::error!("auto_responses: invalid regex {}: {:#}", rule.regex, err);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Returns the number of matches of `regex` in `text` that end
+/// beyond `skip_to`, along with the end of the last of them
+fn new_matches(regex: &Regex, text: &str, skip_to: usize) -> (usize, usize) {
+    let mut count = 0;
+    let mut end = skip_to;
+    for m in regex.find_iter(text) {
+        if m.end() > skip_to {
+            count += 1;
+            end = end.max(m.end());
+        }
+    }
+    (count, end)
+}
+
+/// Examines the text for matches of the responder, returning the
+/// responses to send and updating `count` and `matched_end`
+fn respond_to(
+    responder: &Responder,
+    count: &mut usize,
+    text: &str,
+    skip_to: usize,
+    matched_end: &mut usize,
+    responses: &mut Vec<(String, Duration)>,
+) {
+    let (matches, end) = new_matches(&responder.regex, text, skip_to);
+    *matched_end = (*matched_end).max(end);
+    for _ in 0..matches {
+        if responder
+            .max_count
+            .map(|max| *count >= max)
+            .unwrap_or(false)
+        {
+            break;
+        }
+        *count += 1;
+        responses.push((responder.response.clone(), responder.delay));
+    }
+}
+
+/// Called after output has been applied to the pane; examines the
+/// output that has arrived since the prior call
+pub(crate) fn check_output(pane: &dyn Pane) {
+    let config = configuration();
+    let generation = config.generation();
+    let pane_id = pane.pane_id();
+    let cursor_row = pane.get_cursor_position().y;
+
+    let responses = {
+        let mut rules = CONFIG_RULES.lock().unwrap();
+        if rules.as_ref().map(|r| r.generation) != Some(generation) {
+            rules.replace(ConfigRules {
+                generation,
+                responders: compile_rules(&config.auto_responses),
+            });
+        }
+        let rules = &rules.as_ref().unwrap().responders;
+
+        let mut states = STATES.lock().unwrap();
+        let state = states.entry(pane_id).or_default();
+        if state.config_generation != generation || state.config_counts.len() != rules.len() {
+            state.config_generation = generation;
+            state.config_counts = vec![0; rules.len()];
+        }
+
+        if (state.responders.is_empty() && rules.is_empty()) || pane.is_alt_screen_active() {
+            // Keep track of our position so that we don't respond
+            // to existing output when rules are added later
+            state.next_row.replace(cursor_row);
+            state.partial.take();
+            return;
+        }
+
+        let dims = pane.get_dimensions();
+        let start = state
+            .next_row
+            .unwrap_or(dims.scrollback_top)
+            .max(dims.scrollback_top)
+            .max(cursor_row - MAX_ROWS_PER_CHECK);
+
+        let mut next_row = cursor_row;
+        let mut partial = None;
+        let mut responses = vec![];
+        for line in pane.get_logical_lines(start..cursor_row + 1) {
+            if line.first_row < start {
+                // The start of a wrapped line that was already examined
+                continue;
+            }
+            let text = line.logical.as_str();
+            let text = text.trim_end();
+            let skip_to = match state.partial {
+                Some((row, end)) if row == line.first_row => end,
+                _ => 0,
+            };
+
+            let mut matched_end = skip_to;
+            for (responder, count) in rules.iter().zip(state.config_counts.iter_mut()) {
+                respond_to(
+                    responder,
+                    count,
+                    text,
+                    skip_to,
+                    &mut matched_end,
+                    &mut responses,
+                );
+            }
+            for r in state.responders.iter_mut() {
+                respond_to(
+                    &r.responder,
+                    &mut r.count,
+                    text,
+                    skip_to,
+                    &mut matched_end,
+                    &mut responses,
+                );
+            }
+
+            let end = line.first_row + line.physical_lines.len() as StableRowIndex;
+            if end > cursor_row {
+                // The cursor is still within this line; remember how much
+                // of it we have seen so that we respond to each prompt once
+                next_row = next_row.min(line.first_row);
+                partial.replace((line.first_row, matched_end));
+            }
+        }
+        state.next_row.replace(next_row);
+        state.partial = partial;
+        responses
+    };
+
+    for (response, delay) in responses {
+        send_response(pane_id, response, delay);
+    }
+}
+
+fn send_response(pane_id: PaneId, response: String, delay: Duration) {
+    promise::spawn::spawn_into_main_thread(async move {
+        if !delay.is_zero() {
+            smol::Timer::after(delay).await;
+        }
+        if let Some(pane) = Mux::get().and_then(|mux| mux.get_pane(pane_id)) {
+            if let Err(err) = pane.writer().write_all(response.as_bytes()) {
+                log::error!("auto response to pane {}: {:#}", pane_id, err);
+            }
+        }
+    })
+    .detach();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn responds_once_per_match() {
+        let responder = Responder::new(&AutoResponseRule {
+            regex: r"\(y/n\)\?$".to_string(),
+            response: "y\r".to_string(),
+            delay_ms: 0,
+            max_count: Some(2),
+        })
+        .unwrap();
+
+        let mut count = 0;
+        let mut responses = vec![];
+        let mut matched_end = 0;
+        respond_to(
+            &responder,
+            &mut count,
+            "Are you sure (y/n)?",
+            0,
+            &mut matched_end,
+            &mut responses,
+        );
+        assert_eq!(responses.len(), 1);
+        assert_eq!(matched_end, 19);
+
+        // The same prompt seen again as more output arrives
+        // doesn't produce another response
+        let skip_to = matched_end;
+        respond_to(
+            &responder,
+            &mut count,
+            "Are you sure (y/n)?",
+            skip_to,
+            &mut matched_end,
+            &mut responses,
+        );
+        assert_eq!(responses.len(), 1);
+
+        respond_to(
+            &responder,
+            &mut count,
+            "Again (y/n)?",
+            0,
+            &mut matched_end,
+            &mut responses,
+        );
+        respond_to(
+            &responder,
+            &mut count,
+            "And again (y/n)?",
+            0,
+            &mut matched_end,
+            &mut responses,
+        );
+        // max_count has been reached
+        assert_eq!(responses.len(), 2);
+        assert_eq!(count, 2);
+    }
+}
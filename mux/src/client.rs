@@ -1,6 +1,8 @@
+use crate::collab::ClientCursor;
 use crate::PaneId;
 use chrono::serde::ts_seconds;
 use chrono::{DateTime, Utc};
+use config::MuxClientPermission;
 use serde::*;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -37,9 +39,26 @@ impl ClientId {
     }
 }
 
+/// How a client is connected to the mux.  Unlike the `ClientId`, which
+/// the client reports about itself, this is established by the server,
+/// so it is what `mux_client_permissions` rules are matched against.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone)]
+pub enum ClientTransport {
+    /// The local unix domain socket, which can only be reached by
+    /// the user that is running the server, or the gui itself
+    Local,
+    /// A network listener, such as a TLS, websocket or udp server
+    Remote {
+        /// The `bind_address` of the listener
+        listener: String,
+    },
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
 pub struct ClientInfo {
     pub client_id: Arc<ClientId>,
+    /// How the client is connected
+    pub transport: ClientTransport,
     /// The time this client last connected
     #[serde(with = "ts_seconds")]
     pub connected_at: DateTime<Utc>,
@@ -50,16 +69,23 @@ pub struct ClientInfo {
     pub last_input: DateTime<Utc>,
     /// The currently-focused pane
     pub focused_pane_id: Option<PaneId>,
+    /// The cell that the client is pointing at
+    pub cursor: Option<ClientCursor>,
+    /// What the client is allowed to do, if it is restricted
+    pub permission: Option<MuxClientPermission>,
 }
 
 impl ClientInfo {
-    pub fn new(client_id: Arc<ClientId>) -> Self {
+    pub fn new(client_id: Arc<ClientId>, transport: ClientTransport) -> Self {
         Self {
             client_id,
+            transport,
             connected_at: Utc::now(),
             active_workspace: None,
             last_input: Utc::now(),
             focused_pane_id: None,
+            cursor: None,
+            permission: None,
         }
     }
 
@@ -70,4 +96,9 @@ impl ClientInfo {
     pub fn update_focused_pane(&mut self, pane_id: PaneId) {
         self.focused_pane_id.replace(pane_id);
     }
+
+    /// Updates the cursor, returning the prior cursor
+    pub fn update_cursor(&mut self, cursor: Option<ClientCursor>) -> Option<ClientCursor> {
+        std::mem::replace(&mut self.cursor, cursor)
+    }
 }
@@ -0,0 +1,99 @@
+//! Decides when clients of a mux server are detached: because they
+//! have been idle for longer than `mux_client_idle_timeout_secs`, or
+//! because of the `mux_client_attach_policy` and
+//! `mux_max_clients_per_workspace` options when another client
+//! attaches to their workspace.
+//!
+//! Attaching to and detaching from the server are surfaced to lua as
+//! the `mux-client-attached` and `mux-client-detached` events.
+use crate::client::ClientId;
+use chrono::{DateTime, Utc};
+use config::MuxClientAttachPolicy;
+
+/// What to do when a client attaches to a workspace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachOutcome {
+    /// The workspace is shared with the clients already attached to it
+    Share,
+    /// The clients already attached to the workspace are detached
+    DetachOthers,
+    /// The workspace is full, so the attaching client is detached
+    Refuse,
+}
+
+/// Applies the attach policy to a client that attaches to a
+/// workspace that already has `num_others` clients attached
+pub fn attach_outcome(
+    policy: MuxClientAttachPolicy,
+    max_clients: Option<usize>,
+    num_others: usize,
+) -> AttachOutcome {
+    if num_others == 0 {
+        return AttachOutcome::Share;
+    }
+    match policy {
+        MuxClientAttachPolicy::Steal => AttachOutcome::DetachOthers,
+        MuxClientAttachPolicy::Mirror => match max_clients {
+            Some(max) if num_others >= max => AttachOutcome::Refuse,
+            _ => AttachOutcome::Share,
+        },
+    }
+}
+
+/// Returns true if a client whose last input was at `last_input` has
+/// been idle for longer than `timeout_secs`
+pub fn is_idle(last_input: DateTime<Utc>, now: DateTime<Utc>, timeout_secs: Option<u64>) -> bool {
+    match timeout_secs {
+        Some(secs) => (now - last_input).num_seconds() >= secs as i64,
+        None => false,
+    }
+}
+
+/// Emits `event` to the lua config, passing a table describing the
+/// client along with `detail`
+pub fn emit_client_event(event: &'static str, client_id: &ClientId, detail: String) {
+    let client_id = client_id.clone();
+    promise::spawn::spawn(async move {
+        config::with_lua_config_on_main_thread(move |lua| async move {
+            if let Some(lua) = lua {
+                let client = lua.create_table()?;
+                client.set("username", client_id.username)?;
+                client.set("hostname", client_id.hostname)?;
+                client.set("pid", client_id.pid)?;
+                let args = lua.pack_multi((client, detail))?;
+                config::lua::emit_event(&lua, (event.to_string(), args)).await?;
+            }
+            Ok(())
+        })
+        .await
+    })
+    .detach();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn attach_policies() {
+        use MuxClientAttachPolicy::*;
+        assert_eq!(attach_outcome(Mirror, None, 0), AttachOutcome::Share);
+        assert_eq!(attach_outcome(Mirror, None, 3), AttachOutcome::Share);
+        assert_eq!(attach_outcome(Mirror, Some(2), 1), AttachOutcome::Share);
+        assert_eq!(attach_outcome(Mirror, Some(2), 2), AttachOutcome::Refuse);
+        assert_eq!(attach_outcome(Steal, None, 0), AttachOutcome::Share);
+        assert_eq!(
+            attach_outcome(Steal, Some(2), 1),
+            AttachOutcome::DetachOthers
+        );
+    }
+
+    #[test]
+    fn idle() {
+        let now = Utc::now();
+        let then = now - chrono::Duration::seconds(600);
+        assert!(!is_idle(then, now, None));
+        assert!(is_idle(then, now, Some(300)));
+        assert!(!is_idle(then, now, Some(900)));
+    }
+}
@@ -0,0 +1,169 @@
+//! Keeps track of the clients that share a pane: where each of them
+//! is pointing, and which of them currently has the turn to type
+//! into it
+use crate::client::ClientId;
+use crate::pane::PaneId;
+use serde::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+use wezterm_term::StableRowIndex;
+
+/// The cell that a client is pointing at with its mouse, which is
+/// shown to the other clients that are attached to the same pane
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct ClientCursor {
+    pub pane_id: PaneId,
+    pub x: usize,
+    pub y: StableRowIndex,
+}
+
+/// Another client that is attached to a pane
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone)]
+pub struct Collaborator {
+    pub client_id: ClientId,
+    /// The cell that the client is pointing at, if it is pointing
+    /// somewhere in the pane
+    pub cursor: Option<(usize, StableRowIndex)>,
+}
+
+/// Which client may send input to a pane
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone)]
+pub enum InputTurn {
+    /// Nobody has taken the turn; any client may type
+    Anyone,
+    /// The client that asked has the turn
+    You,
+    /// Some other client has the turn
+    Client(ClientId),
+}
+
+impl Default for InputTurn {
+    fn default() -> Self {
+        Self::Anyone
+    }
+}
+
+/// The collaboration state of a pane, from the perspective of one client
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct PaneCollaborators {
+    pub clients: Vec<Collaborator>,
+    pub input_turn: InputTurn,
+}
+
+/// Records which client holds the input turn for each pane
+#[derive(Default)]
+pub struct InputTurns {
+    owners: HashMap<PaneId, Arc<ClientId>>,
+}
+
+impl InputTurns {
+    /// Gives `client_id` the turn for `pane_id`, taking it from whoever
+    /// had it, or gives it up if the client already holds it.
+    /// Returns true if the client holds the turn afterwards.
+    pub fn toggle(&mut self, pane_id: PaneId, client_id: Arc<ClientId>) -> bool {
+        if self.owners.get(&pane_id) == Some(&client_id) {
+            self.owners.remove(&pane_id);
+            false
+        } else {
+            self.owners.insert(pane_id, client_id);
+            true
+        }
+    }
+
+    pub fn owner(&self, pane_id: PaneId) -> Option<&Arc<ClientId>> {
+        self.owners.get(&pane_id)
+    }
+
+    /// Returns true if `client_id` may send input to `pane_id`
+    pub fn may_send_input(&self, pane_id: PaneId, client_id: Option<&ClientId>) -> bool {
+        match self.owners.get(&pane_id) {
+            Some(owner) => Some(&**owner) == client_id,
+            None => true,
+        }
+    }
+
+    /// Returns the turn for `pane_id` as seen by `client_id`
+    pub fn turn_for(&self, pane_id: PaneId, client_id: Option<&ClientId>) -> InputTurn {
+        match self.owners.get(&pane_id) {
+            None => InputTurn::Anyone,
+            Some(owner) if Some(&**owner) == client_id => InputTurn::You,
+            Some(owner) => InputTurn::Client((**owner).clone()),
+        }
+    }
+
+    /// Gives up all of the turns held by `client_id`, returning the
+    /// affected panes
+    pub fn remove_client(&mut self, client_id: &ClientId) -> Vec<PaneId> {
+        let panes: Vec<PaneId> = self
+            .owners
+            .iter()
+            .filter(|(_, owner)| &***owner == client_id)
+            .map(|(pane_id, _)| *pane_id)
+            .collect();
+        for pane_id in &panes {
+            self.owners.remove(pane_id);
+        }
+        panes
+    }
+
+    pub fn remove_pane(&mut self, pane_id: PaneId) {
+        self.owners.remove(&pane_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn client(id: usize) -> Arc<ClientId> {
+        Arc::new(ClientId {
+            hostname: "host".to_string(),
+            username: "user".to_string(),
+            pid: 1,
+            epoch: 0,
+            id,
+        })
+    }
+
+    #[test]
+    fn turn_taking() {
+        let alice = client(1);
+        let bob = client(2);
+        let mut turns = InputTurns::default();
+
+        assert!(turns.may_send_input(1, Some(&alice)));
+        assert!(turns.may_send_input(1, None));
+        assert_eq!(turns.turn_for(1, Some(&alice)), InputTurn::Anyone);
+
+        assert!(turns.toggle(1, alice.clone()));
+        assert!(turns.may_send_input(1, Some(&alice)));
+        assert!(!turns.may_send_input(1, Some(&bob)));
+        assert!(!turns.may_send_input(1, None));
+        assert!(turns.may_send_input(2, Some(&bob)));
+        assert_eq!(turns.turn_for(1, Some(&alice)), InputTurn::You);
+        assert_eq!(
+            turns.turn_for(1, Some(&bob)),
+            InputTurn::Client((*alice).clone())
+        );
+
+        // Bob takes the turn from alice
+        assert!(turns.toggle(1, bob.clone()));
+        assert!(!turns.may_send_input(1, Some(&alice)));
+
+        // and gives it up again
+        assert!(!turns.toggle(1, bob.clone()));
+        assert!(turns.may_send_input(1, Some(&alice)));
+
+        turns.toggle(1, bob.clone());
+        turns.toggle(2, bob.clone());
+        turns.toggle(3, alice.clone());
+        let mut removed = turns.remove_client(&bob);
+        removed.sort();
+        assert_eq!(removed, vec![1, 2]);
+        assert!(turns.owner(1).is_none());
+        assert_eq!(turns.owner(3), Some(&alice));
+
+        turns.remove_pane(3);
+        assert!(turns.owner(3).is_none());
+    }
+}
@@ -13,9 +13,11 @@ use crate::Mux;
 use anyhow::{bail, Context, Error};
 use async_trait::async_trait;
 use config::keyassignment::{SpawnCommand, SpawnTabDomain};
-use config::{configuration, ExecDomain, ValueOrFunc, WslDomain};
+use config::{
+    configuration, ContainerDomain, ExecDomain, KubernetesDomain, ValueOrFunc, WslDomain,
+};
 use downcast_rs::{impl_downcast, Downcast};
-use portable_pty::{native_pty_system, CommandBuilder, PtySystem};
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySystem};
 use std::collections::HashMap;
 use std::ffi::OsString;
 use std::io::Write;
@@ -176,6 +178,12 @@ pub struct LocalDomain {
     pty_system: Box<dyn PtySystem>,
     id: DomainId,
     name: String,
+    /// Set for kubernetes domains that were not defined in the
+    /// configuration, such as those created by the pod selector
+    kubernetes: Option<KubernetesDomain>,
+    /// Set for container domains that were not defined in the
+    /// configuration, such as those created by the container selector
+    container: Option<ContainerDomain>,
 }
 
 impl LocalDomain {
@@ -183,6 +191,21 @@ impl LocalDomain {
         Ok(Self::with_pty_system(name, native_pty_system()))
     }
 
+    /// Spawns `cmd` into a new pty, used to restart the command of
+    /// a pane that was previously spawned by this domain
+    pub(crate) fn respawn_command(
+        &self,
+        size: TerminalSize,
+        cmd: CommandBuilder,
+    ) -> anyhow::Result<(Box<dyn Child + Send + Sync>, Box<dyn MasterPty>)> {
+        let pair = self
+            .pty_system
+            .openpty(crate::terminal_size_to_pty_size(size)?)?;
+        let child = pair.slave.spawn_command(cmd)?;
+        log::trace!("respawned: {:?}", child);
+        Ok((child, pair.master))
+    }
+
     fn resolve_exec_domain(&self) -> Option<ExecDomain> {
         config::configuration()
             .exec_domains
@@ -191,6 +214,24 @@ impl LocalDomain {
             .cloned()
     }
 
+    fn resolve_kubernetes_domain(&self) -> Option<KubernetesDomain> {
+        config::configuration()
+            .kubernetes_domains
+            .iter()
+            .find(|d| d.name == self.name)
+            .cloned()
+            .or_else(|| self.kubernetes.clone())
+    }
+
+    fn resolve_container_domain(&self) -> Option<ContainerDomain> {
+        config::configuration()
+            .container_domains
+            .iter()
+            .find(|d| d.name == self.name)
+            .cloned()
+            .or_else(|| self.container.clone())
+    }
+
     fn resolve_wsl_domain(&self) -> Option<WslDomain> {
         config::configuration()
             .wsl_domains
@@ -205,9 +246,23 @@ impl LocalDomain {
             pty_system,
             id,
             name: name.to_string(),
+            kubernetes: None,
+            container: None,
         }
     }
 
+    pub fn new_kubernetes(kubernetes: KubernetesDomain) -> anyhow::Result<Self> {
+        let mut domain = Self::new(&kubernetes.name)?;
+        domain.kubernetes.replace(kubernetes);
+        Ok(domain)
+    }
+
+    pub fn new_container(container: ContainerDomain) -> anyhow::Result<Self> {
+        let mut domain = Self::new(&container.name)?;
+        domain.container.replace(container);
+        Ok(domain)
+    }
+
     pub fn new_wsl(wsl: WslDomain) -> Result<Self, Error> {
         Self::new(&wsl.name)
     }
@@ -216,16 +271,35 @@ impl LocalDomain {
         Self::new(&exec_domain.name)
     }
 
+    /// Returns true if the terminal needs to compensate for the way
+    /// that conpty re-renders the output of applications; that isn't
+    /// necessary when conpty is in passthrough mode
     #[cfg(unix)]
-    fn is_conpty(&self) -> bool {
+    fn needs_conpty_quirks(&self) -> bool {
         false
     }
 
     #[cfg(windows)]
-    fn is_conpty(&self) -> bool {
+    fn needs_conpty_quirks(&self) -> bool {
         self.pty_system
             .downcast_ref::<portable_pty::win::conpty::ConPtySystem>()
-            .is_some()
+            .map(|conpty| !conpty.passthrough())
+            .unwrap_or(false)
+    }
+
+    /// Applies the `conpty_passthrough` configuration to the pty system,
+    /// ahead of opening a new pty
+    #[cfg(unix)]
+    fn configure_pty_system(&self) {}
+
+    #[cfg(windows)]
+    fn configure_pty_system(&self) {
+        if let Some(conpty) = self
+            .pty_system
+            .downcast_ref::<portable_pty::win::conpty::ConPtySystem>()
+        {
+            conpty.set_passthrough(config::configuration().conpty_passthrough);
+        }
     }
 
     async fn fixup_command(&self, cmd: &mut CommandBuilder) -> anyhow::Result<()> {
@@ -250,8 +324,16 @@ impl LocalDomain {
             ];
 
             if let Some(cwd) = cmd.get_cwd() {
+                let translated = if wsl.translate_paths {
+                    cwd.to_str().and_then(|cwd| wsl.windows_path_to_wsl(cwd))
+                } else {
+                    None
+                };
                 argv.push("--cd".into());
-                argv.push(cwd.into());
+                match translated {
+                    Some(cwd) => argv.push(cwd.into()),
+                    None => argv.push(cwd.into()),
+                }
             }
 
             if let Some(user) = &wsl.username {
@@ -266,11 +348,83 @@ impl LocalDomain {
                 }
             }
 
-            // TODO: process env list and update WLSENV so that they
-            // get passed through
+            if !wsl.set_environment_variables.is_empty() {
+                // WSLENV lists the names of the variables that should be
+                // passed through from windows to the distribution
+                let mut wslenv = cmd
+                    .get_env("WSLENV")
+                    .and_then(|v| v.to_str())
+                    .unwrap_or("")
+                    .to_string();
+                let mut names: Vec<&String> = wsl.set_environment_variables.keys().collect();
+                names.sort();
+                for name in names {
+                    cmd.env(name, &wsl.set_environment_variables[name]);
+                    let already_listed = wslenv
+                        .split(':')
+                        .any(|entry| entry.split('/').next() == Some(name.as_str()));
+                    if !already_listed {
+                        if !wslenv.is_empty() {
+                            wslenv.push(':');
+                        }
+                        wslenv.push_str(name);
+                        wslenv.push_str("/u");
+                    }
+                }
+                cmd.env("WSLENV", wslenv);
+            }
 
             cmd.clear_cwd();
             *cmd.get_argv_mut() = argv;
+        } else if let Some(k8s) = self.resolve_kubernetes_domain() {
+            let mut args = vec![];
+            for arg in cmd.get_argv() {
+                args.push(
+                    arg.to_str()
+                        .ok_or_else(|| anyhow::anyhow!("command argument is not utf8"))?
+                        .to_string(),
+                );
+            }
+            let config = configuration();
+            let argv = k8s.exec_argv(&config.kubectl_path, &config.term, &args);
+            // The local cwd is meaningless inside the container
+            cmd.clear_cwd();
+            *cmd.get_argv_mut() = argv.into_iter().map(OsString::from).collect();
+        } else if let Some(container) = self.resolve_container_domain() {
+            let config = configuration();
+            let engine = container
+                .engine
+                .as_deref()
+                .unwrap_or(&config.container_engine_path);
+            // Check that the container is running, so that we can
+            // report that clearly, rather than showing a failed exec
+            let output = std::process::Command::new(engine)
+                .args(["inspect", "--format", "{{.State.Running}}"])
+                .arg(&container.container)
+                .output()
+                .with_context(|| format!("running {}", engine))?;
+            if String::from_utf8_lossy(&output.stdout).trim() != "true" {
+                bail!(
+                    "container {} is not running{}",
+                    container.container,
+                    match String::from_utf8_lossy(&output.stderr).trim() {
+                        "" => String::new(),
+                        err => format!(": {}", err),
+                    }
+                );
+            }
+
+            let mut args = vec![];
+            for arg in cmd.get_argv() {
+                args.push(
+                    arg.to_str()
+                        .ok_or_else(|| anyhow::anyhow!("command argument is not utf8"))?
+                        .to_string(),
+                );
+            }
+            let argv = container.exec_argv(engine, &config.term, &args);
+            cmd.clear_cwd();
+            *cmd.get_argv_mut() = argv.into_iter().map(OsString::from).collect();
         } else if let Some(ed) = self.resolve_exec_domain() {
             let mut args = vec![];
             let mut set_environment_variables = HashMap::new();
@@ -294,6 +448,8 @@ impl LocalDomain {
                 args: if args.is_empty() { None } else { Some(args) },
                 set_environment_variables,
                 cwd,
+                resource_limits: None,
+                restart_on_exit: None,
             };
 
             let spawn_command = config::with_lua_config_on_main_thread(|lua| async {
@@ -456,6 +612,12 @@ impl WriterWrapper {
             writer: Arc::new(Mutex::new(writer)),
         }
     }
+
+    /// Redirects all clones of this writer to a new pty,
+    /// used when a pane is restarted
+    pub fn replace(&self, writer: Box<dyn Write + Send>) {
+        *self.writer.lock().unwrap() = writer;
+    }
 }
 
 impl std::io::Write for WriterWrapper {
@@ -478,6 +640,7 @@ impl Domain for LocalDomain {
     ) -> anyhow::Result<Rc<dyn Pane>> {
         let pane_id = alloc_pane_id();
         let cmd = self.build_command(command, command_dir, pane_id).await?;
+        self.configure_pty_system();
         let pair = self
             .pty_system
             .openpty(crate::terminal_size_to_pty_size(size)?)?;
@@ -494,10 +657,12 @@ impl Domain for LocalDomain {
             },
             self.name
         );
+        let restart_cmd = cmd.clone();
         let child = pair.slave.spawn_command(cmd)?;
         log::trace!("spawned: {:?}", child);
 
         let writer = WriterWrapper::new(pair.master.take_writer()?);
+        let restart_writer = writer.clone();
 
         let mut terminal = wezterm_term::Terminal::new(
             size,
@@ -506,11 +671,11 @@ impl Domain for LocalDomain {
             config::wezterm_version(),
             Box::new(writer.clone()),
         );
-        if self.is_conpty() {
+        if self.needs_conpty_quirks() {
             terminal.enable_conpty_quirks();
         }
 
-        let pane: Rc<dyn Pane> = Rc::new(LocalPane::new(
+        let pane = LocalPane::new(
             pane_id,
             terminal,
             child,
@@ -518,7 +683,38 @@ impl Domain for LocalDomain {
             Box::new(writer),
             self.id,
             command_description,
-        ));
+        );
+        pane.set_restart_command(restart_cmd, restart_writer);
+        let pane: Rc<dyn Pane> = Rc::new(pane);
+
+        if let Some(container) = self.resolve_container_domain() {
+            // Label the pane with the container name until the
+            // program in the container sets a title of its own
+            pane.perform_actions(vec![termwiz::escape::Action::OperatingSystemCommand(
+                Box::new(
+                    termwiz::escape::OperatingSystemCommand::SetIconNameAndWindowTitle(
+                        container.container.clone(),
+                    ),
+                ),
+            )]);
+        }
+
+        if let Some(reattach) = self
+            .resolve_kubernetes_domain()
+            .and_then(|k8s| k8s.restart_on_exit())
+        {
+            pane.set_restart_on_exit(Some(reattach))?;
+        }
+
+        if let Some(limits) = &configuration().default_resource_limits {
+            if let Err(err) = pane.set_resource_limits(limits) {
+                log::error!(
+                    "Unable to apply resource limits to pane {}: {:#}",
+                    pane_id,
+                    err
+                );
+            }
+        }
 
         let mux = Mux::get().unwrap();
         mux.add_pane(&pane)?;
@@ -0,0 +1,218 @@
+//! Per-domain network measurements that help to tell whether slowness
+//! in a remote domain is caused by the network or by rendering: the
+//! round trip time of the connection and the rate at which data is
+//! sent to and received from the remote end.
+//!
+//! Mux client domains time each request to the server and count the
+//! bytes on their stream.  SSH domains count the bytes that flow
+//! through their ptys and time the gap between sending input and
+//! receiving the output that follows it.  The results can be retrieved
+//! via `domain:stats()` in lua, and are shown by the debug HUD.
+use crate::domain::DomainId;
+use crate::panestats::{LatencySummary, Samples};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+lazy_static::lazy_static! {
+    static ref STATS: Mutex<HashMap<DomainId, DomainStats>> = Mutex::new(HashMap::new());
+}
+
+/// Counts bytes in one second windows
+struct Throughput {
+    window_start: Instant,
+    window_bytes: u64,
+    bytes_per_second: u64,
+    total_bytes: u64,
+}
+
+impl Throughput {
+    fn new() -> Self {
+        Self {
+            window_start: Instant::now(),
+            window_bytes: 0,
+            bytes_per_second: 0,
+            total_bytes: 0,
+        }
+    }
+
+    fn roll_window(&mut self) {
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= Duration::from_secs(2) {
+            // Nothing was transferred during the last full window
+            self.bytes_per_second = 0;
+            self.window_bytes = 0;
+            self.window_start = Instant::now();
+        } else if elapsed >= Duration::from_secs(1) {
+            self.bytes_per_second = self.window_bytes;
+            self.window_bytes = 0;
+            self.window_start = Instant::now();
+        }
+    }
+
+    fn record(&mut self, size: usize) {
+        self.roll_window();
+        self.window_bytes += size as u64;
+        self.total_bytes += size as u64;
+    }
+}
+
+struct DomainStats {
+    rtt: Samples,
+    last_rtt: Option<Duration>,
+    sent: Throughput,
+    received: Throughput,
+}
+
+impl DomainStats {
+    fn new() -> Self {
+        Self {
+            rtt: Samples::default(),
+            last_rtt: None,
+            sent: Throughput::new(),
+            received: Throughput::new(),
+        }
+    }
+}
+
+/// A snapshot of the measurements for a domain
+#[derive(Clone, PartialEq, Debug)]
+pub struct DomainMetrics {
+    pub domain_id: DomainId,
+    /// Round trip time of the connection to the remote end
+    pub rtt: LatencySummary,
+    /// The most recently measured round trip time
+    pub last_rtt: Option<Duration>,
+    /// The number of bytes sent during the last second
+    pub sent_bytes_per_second: u64,
+    /// The number of bytes sent since the domain was created
+    pub sent_bytes_total: u64,
+    /// The number of bytes received during the last second
+    pub received_bytes_per_second: u64,
+    /// The number of bytes received since the domain was created
+    pub received_bytes_total: u64,
+}
+
+fn with_stats<F: FnOnce(&mut DomainStats)>(domain_id: DomainId, func: F) {
+    let mut stats = STATS.lock().unwrap();
+    func(stats.entry(domain_id).or_insert_with(DomainStats::new));
+}
+
+/// Records a round trip time measurement for the domain
+pub fn record_rtt(domain_id: DomainId, rtt: Duration) {
+    with_stats(domain_id, |stats| {
+        stats.rtt.record(rtt);
+        stats.last_rtt.replace(rtt);
+    });
+}
+
+/// Records that `size` bytes were sent to the remote end of the domain
+pub fn record_sent(domain_id: DomainId, size: usize) {
+    with_stats(domain_id, |stats| stats.sent.record(size));
+}
+
+/// Records that `size` bytes were received from the remote end of the domain
+pub fn record_received(domain_id: DomainId, size: usize) {
+    with_stats(domain_id, |stats| stats.received.record(size));
+}
+
+/// Returns true if anything has been measured for the domain.
+/// Local domains are never measured.
+pub fn has_metrics(domain_id: DomainId) -> bool {
+    STATS.lock().unwrap().contains_key(&domain_id)
+}
+
+/// Returns a snapshot of the measurements for a domain
+pub fn domain_metrics(domain_id: DomainId) -> DomainMetrics {
+    let mut stats = STATS.lock().unwrap();
+    let stats = stats.entry(domain_id).or_insert_with(DomainStats::new);
+    stats.sent.roll_window();
+    stats.received.roll_window();
+    DomainMetrics {
+        domain_id,
+        rtt: stats.rtt.summarize(),
+        last_rtt: stats.last_rtt,
+        sent_bytes_per_second: stats.sent.bytes_per_second,
+        sent_bytes_total: stats.sent.total_bytes,
+        received_bytes_per_second: stats.received.bytes_per_second,
+        received_bytes_total: stats.received.total_bytes,
+    }
+}
+
+/// Wraps a reader, recording the bytes read from it as having
+/// been received by a domain
+pub struct MeteredReader<R> {
+    inner: R,
+    domain_id: DomainId,
+}
+
+impl<R: Read> MeteredReader<R> {
+    pub fn new(inner: R, domain_id: DomainId) -> Self {
+        Self { inner, domain_id }
+    }
+}
+
+impl<R: Read> Read for MeteredReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.inner.read(buf)?;
+        if size > 0 {
+            record_received(self.domain_id, size);
+        }
+        Ok(size)
+    }
+}
+
+/// Wraps a writer, recording the bytes written to it as having
+/// been sent by a domain
+pub struct MeteredWriter<W> {
+    inner: W,
+    domain_id: DomainId,
+}
+
+impl<W: Write> MeteredWriter<W> {
+    pub fn new(inner: W, domain_id: DomainId) -> Self {
+        Self { inner, domain_id }
+    }
+}
+
+impl<W: Write> Write for MeteredWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let size = self.inner.write(buf)?;
+        if size > 0 {
+            record_sent(self.domain_id, size);
+        }
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn metered_io() {
+        // Use an id that won't collide with other tests
+        let domain_id = DomainId::MAX;
+        assert!(!has_metrics(domain_id));
+
+        let mut reader = MeteredReader::new(&b"hello"[..], domain_id);
+        let mut buf = vec![];
+        reader.read_to_end(&mut buf).unwrap();
+
+        let mut writer = MeteredWriter::new(vec![], domain_id);
+        writer.write_all(b"hi").unwrap();
+
+        record_rtt(domain_id, Duration::from_millis(20));
+
+        let metrics = domain_metrics(domain_id);
+        assert_eq!(metrics.received_bytes_total, 5);
+        assert_eq!(metrics.sent_bytes_total, 2);
+        assert_eq!(metrics.rtt.samples, 1);
+        assert_eq!(metrics.last_rtt, Some(Duration::from_millis(20)));
+    }
+}
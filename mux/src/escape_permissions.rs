@@ -0,0 +1,149 @@
+//! Decides whether potentially dangerous escape sequences emitted
+//! by the applications in a pane are honored, according to the
+//! `escape_sequence_policy` configuration, any policy that was set
+//! for the pane and any decisions that the user has asked us to
+//! remember for the pane.
+use crate::domain::DomainId;
+use crate::pane::PaneId;
+use crate::{Mux, MuxNotification};
+use config::{EscapeSequencePermission, EscapeSequencePolicy};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use wezterm_term::{EscapeSequenceKind, EscapeSequencePermissions};
+
+lazy_static::lazy_static! {
+    static ref DECISIONS: Mutex<HashMap<(PaneId, EscapeSequenceKind), bool>> =
+        Mutex::new(HashMap::new());
+    /// Tracks the prompts that are outstanding, so that a flood of
+    /// sequences doesn't produce a flood of prompts
+    static ref PENDING: Mutex<HashSet<(PaneId, EscapeSequenceKind)>> =
+        Mutex::new(HashSet::new());
+    /// Policies set for individual panes, which take precedence
+    /// over the policy of their domain
+    static ref PANE_POLICIES: Mutex<HashMap<PaneId, EscapeSequencePolicy>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Sets the policy for the pane, or clears it if `policy` is None.
+/// Kinds that the pane policy doesn't specify follow the policy
+/// of the domain.
+pub fn set_pane_policy(pane_id: PaneId, policy: Option<EscapeSequencePolicy>) {
+    let mut policies = PANE_POLICIES.lock().unwrap();
+    match policy {
+        Some(policy) => {
+            policies.insert(pane_id, policy);
+        }
+        None => {
+            policies.remove(&pane_id);
+        }
+    }
+}
+
+/// Returns the policy that was set for the pane, if any
+pub fn pane_policy(pane_id: PaneId) -> Option<EscapeSequencePolicy> {
+    PANE_POLICIES.lock().unwrap().get(&pane_id).cloned()
+}
+
+/// Returns the permission that was explicitly set for sequences of
+/// the specified kind, either for the pane or for its domain
+pub fn explicit_permission(
+    pane_id: PaneId,
+    domain_name: &str,
+    kind: EscapeSequenceKind,
+) -> Option<EscapeSequencePermission> {
+    PANE_POLICIES
+        .lock()
+        .unwrap()
+        .get(&pane_id)
+        .and_then(|policy| policy.get(kind))
+        .or_else(|| config::configuration().explicit_escape_sequence_permission(domain_name, kind))
+}
+
+/// Returns the decision that was remembered for the pane, if any
+pub fn remembered_decision(pane_id: PaneId, kind: EscapeSequenceKind) -> Option<bool> {
+    DECISIONS.lock().unwrap().get(&(pane_id, kind)).copied()
+}
+
+/// Records the answer to a prompt.  If `remember` is true, the
+/// answer applies to all subsequent sequences of that kind in
+/// the pane, otherwise it only concludes the outstanding prompt.
+pub fn resolve_prompt(pane_id: PaneId, kind: EscapeSequenceKind, allow: bool, remember: bool) {
+    PENDING.lock().unwrap().remove(&(pane_id, kind));
+    if remember {
+        DECISIONS.lock().unwrap().insert((pane_id, kind), allow);
+    }
+}
+
+/// Concludes a prompt that nothing is able to show, because no
+/// window is displaying the pane, denying the sequence
+pub fn deny_unhandled_prompt(pane_id: PaneId, kind: EscapeSequenceKind) {
+    log::warn!(
+        "escape_sequence_policy is Prompt for {:?} in pane {}, but there is no \
+         window showing the pane in which to ask; the sequence was denied",
+        kind,
+        pane_id
+    );
+    resolve_prompt(pane_id, kind, false, false);
+}
+
+/// Removes the remembered decisions and policy for a pane
+pub fn forget_decisions(pane_id: PaneId) {
+    DECISIONS
+        .lock()
+        .unwrap()
+        .retain(|(id, _), _| *id != pane_id);
+    PENDING.lock().unwrap().retain(|(id, _)| *id != pane_id);
+    PANE_POLICIES.lock().unwrap().remove(&pane_id);
+}
+
+/// Returns the effective permission for sequences of the specified
+/// kind in the pane, taking remembered decisions into account
+pub fn permission(
+    pane_id: PaneId,
+    domain_name: &str,
+    kind: EscapeSequenceKind,
+) -> EscapeSequencePermission {
+    match remembered_decision(pane_id, kind) {
+        Some(true) => EscapeSequencePermission::Allow,
+        Some(false) => EscapeSequencePermission::Deny,
+        None => match pane_policy(pane_id).and_then(|policy| policy.get(kind)) {
+            Some(permission) => permission,
+            None => config::configuration().escape_sequence_permission(domain_name, kind),
+        },
+    }
+}
+
+pub(crate) struct MuxEscapeSequencePermissions {
+    pub pane_id: PaneId,
+    pub domain_id: DomainId,
+}
+
+impl EscapeSequencePermissions for MuxEscapeSequencePermissions {
+    fn is_allowed(&self, kind: EscapeSequenceKind) -> bool {
+        let mux = match Mux::get() {
+            Some(mux) => mux,
+            None => return false,
+        };
+        let domain_name = mux
+            .get_domain(self.domain_id)
+            .map(|domain| domain.domain_name().to_string())
+            .unwrap_or_default();
+
+        match permission(self.pane_id, &domain_name, kind) {
+            EscapeSequencePermission::Allow => true,
+            EscapeSequencePermission::Deny => false,
+            EscapeSequencePermission::Prompt => {
+                // The sequence is ignored while we wait for the
+                // user; only a remembered decision will allow
+                // later sequences of this kind.
+                if PENDING.lock().unwrap().insert((self.pane_id, kind)) {
+                    mux.notify(MuxNotification::EscapeSequencePermission {
+                        pane_id: self.pane_id,
+                        kind,
+                    });
+                }
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Flow control for panes that produce output faster than it can
+//! be applied to the terminal model and rendered, such as `yes` or
+//! `cat`ing a large file.
+//!
+//! When a pane is flooding us, the thread that parses its output
+//! pauses after each batch so that the main thread spends no more
+//! than a configured fraction of its time applying that output,
+//! leaving it free to process input (so that CTRL-C takes effect
+//! promptly) and to render frames.  The pause lets the pty reader
+//! fill its buffer, which in turn blocks the application, rather
+//! than allowing a large backlog to build up.
+//!
+//! Panes in tabs that are not active in their window have their
+//! output processed in larger, less frequent batches, as nobody can
+//! see it being rendered.  They catch up as soon as they become
+//! visible again.
+use crate::pane::PaneId;
+use std::collections::HashSet;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use termwiz::escape::{Action, ControlCode};
+
+lazy_static::lazy_static! {
+    static ref BACKGROUND: (Mutex<HashSet<PaneId>>, Condvar) =
+        (Mutex::new(HashSet::new()), Condvar::new());
+}
+
+/// How much larger the batches of a background pane may be than
+/// those of a visible pane
+pub(crate) const BACKGROUND_BATCH_MULTIPLIER: usize = 8;
+
+/// Replaces the set of panes that are in background tabs, waking
+/// any parser threads that are waiting for their pane to become
+/// visible
+pub(crate) fn set_background_panes(panes: HashSet<PaneId>) {
+    let (lock, cvar) = &*BACKGROUND;
+    let mut background = lock.lock().unwrap();
+    if *background != panes {
+        *background = panes;
+        cvar.notify_all();
+    }
+}
+
+/// Returns true if the pane is in a tab that isn't visible
+pub(crate) fn is_background(pane_id: PaneId) -> bool {
+    BACKGROUND.0.lock().unwrap().contains(&pane_id)
+}
+
+/// Blocks until `deadline`, or until the pane is no longer in the
+/// background, whichever comes first
+pub(crate) fn wait_while_background(pane_id: PaneId, deadline: Instant) {
+    let (lock, cvar) = &*BACKGROUND;
+    let mut background = lock.lock().unwrap();
+    while background.contains(&pane_id) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        background = cvar.wait_timeout(background, remaining).unwrap().0;
+    }
+}
+
+/// The number of consecutive full batches after which we consider
+/// the pane to be flooding us
+const FLOOD_BATCHES: usize = 3;
+
+/// The longest that we'll pause after a single batch
+const MAX_PAUSE: Duration = Duration::from_millis(50);
+
+#[derive(Default)]
+pub(crate) struct FlowControl {
+    full_batches: usize,
+}
+
+impl FlowControl {
+    /// Records the size of a batch that is about to be sent to the
+    /// mux; a batch that filled the read buffer indicates that
+    /// more data was already waiting to be read
+    pub fn record_batch(&mut self, batch_size: usize, buffer_size: usize) {
+        if batch_size >= buffer_size {
+            self.full_batches = self.full_batches.saturating_add(1);
+        } else {
+            self.full_batches = 0;
+        }
+    }
+
+    pub fn is_flooding(&self) -> bool {
+        self.full_batches >= FLOOD_BATCHES
+    }
+
+    /// Returns how long to pause after applying a batch took
+    /// `elapsed`, so that applying output takes no more than
+    /// `duty_cycle` of the main thread's time
+    pub fn pause_after(&self, elapsed: Duration, duty_cycle: f64) -> Duration {
+        if !self.is_flooding() {
+            return Duration::ZERO;
+        }
+        let duty_cycle = duty_cycle.clamp(0.05, 1.0);
+        elapsed
+            .mul_f64((1.0 - duty_cycle) / duty_cycle)
+            .min(MAX_PAUSE)
+    }
+
+    /// While flooding, splits `actions` after its final line feed
+    /// and returns the incomplete trailing line, which should be
+    /// sent with the next batch.  This coalesces lines so that
+    /// frames rendered during a flood don't show partial lines.
+    pub fn split_partial_line(&self, actions: &mut Vec<Action>) -> Vec<Action> {
+        if !self.is_flooding() {
+            return vec![];
+        }
+        match actions
+            .iter()
+            .rposition(|action| matches!(action, Action::Control(ControlCode::LineFeed)))
+        {
+            Some(idx) => actions.split_off(idx + 1),
+            None => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn flooding() -> FlowControl {
+        let mut flow = FlowControl::default();
+        for _ in 0..FLOOD_BATCHES {
+            flow.record_batch(10, 10);
+        }
+        flow
+    }
+
+    #[test]
+    fn flood_detection() {
+        let mut flow = flooding();
+        assert!(flow.is_flooding());
+        flow.record_batch(5, 10);
+        assert!(!flow.is_flooding());
+        assert_eq!(
+            flow.pause_after(Duration::from_millis(10), 0.5),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn pause() {
+        let flow = flooding();
+        assert_eq!(
+            flow.pause_after(Duration::from_millis(10), 0.5),
+            Duration::from_millis(10)
+        );
+        assert_eq!(
+            flow.pause_after(Duration::from_millis(10), 1.0),
+            Duration::ZERO
+        );
+        assert_eq!(flow.pause_after(Duration::from_secs(1), 0.5), MAX_PAUSE);
+    }
+
+    #[test]
+    fn partial_line() {
+        let flow = flooding();
+        let mut actions = vec![
+            Action::Print('a'),
+            Action::Control(ControlCode::LineFeed),
+            Action::Print('b'),
+        ];
+        let rest = flow.split_partial_line(&mut actions);
+        assert_eq!(
+            actions,
+            vec![Action::Print('a'), Action::Control(ControlCode::LineFeed)]
+        );
+        assert_eq!(rest, vec![Action::Print('b')]);
+
+        let mut actions = vec![Action::Print('a')];
+        assert!(flow.split_partial_line(&mut actions).is_empty());
+        assert_eq!(actions, vec![Action::Print('a')]);
+    }
+}
@@ -0,0 +1,93 @@
+//! Tracks the order in which panes, tabs and windows were most
+//! recently focused, so that the user can jump back to them.
+use crate::pane::PaneId;
+use crate::tab::TabId;
+use crate::window::WindowId;
+
+/// The number of entries remembered in each of the lists
+const MAX_ENTRIES: usize = 64;
+
+#[derive(Default, Debug)]
+pub struct FocusHistory {
+    panes: Vec<PaneId>,
+    tabs: Vec<TabId>,
+    windows: Vec<WindowId>,
+}
+
+/// Moves `item` to the front of `list`
+fn promote<T: PartialEq>(list: &mut Vec<T>, item: T) {
+    list.retain(|i| *i != item);
+    list.insert(0, item);
+    list.truncate(MAX_ENTRIES);
+}
+
+impl FocusHistory {
+    /// Records that the pane, which is in the specified tab and
+    /// window, was focused
+    pub fn record(&mut self, window_id: WindowId, tab_id: TabId, pane_id: PaneId) {
+        promote(&mut self.panes, pane_id);
+        promote(&mut self.tabs, tab_id);
+        promote(&mut self.windows, window_id);
+    }
+
+    /// Removes the entries for things that no longer exist
+    pub fn prune(
+        &mut self,
+        pane_exists: impl Fn(PaneId) -> bool,
+        tab_exists: impl Fn(TabId) -> bool,
+        window_exists: impl Fn(WindowId) -> bool,
+    ) {
+        self.panes.retain(|&id| pane_exists(id));
+        self.tabs.retain(|&id| tab_exists(id));
+        self.windows.retain(|&id| window_exists(id));
+    }
+
+    /// Returns the panes, most recently focused first
+    pub fn panes(&self) -> &[PaneId] {
+        &self.panes
+    }
+
+    /// Returns the tabs, most recently focused first
+    pub fn tabs(&self) -> &[TabId] {
+        &self.tabs
+    }
+
+    /// Returns the windows, most recently focused first
+    pub fn windows(&self) -> &[WindowId] {
+        &self.windows
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn most_recent_first() {
+        let mut history = FocusHistory::default();
+        history.record(0, 0, 0);
+        history.record(0, 0, 1);
+        history.record(0, 1, 2);
+        history.record(1, 2, 3);
+        history.record(0, 0, 1);
+
+        assert_eq!(history.panes(), &[1, 3, 2, 0]);
+        assert_eq!(history.tabs(), &[0, 2, 1]);
+        assert_eq!(history.windows(), &[0, 1]);
+
+        history.prune(|id| id != 3, |id| id != 2, |id| id != 1);
+        assert_eq!(history.panes(), &[1, 2, 0]);
+        assert_eq!(history.tabs(), &[0, 1]);
+        assert_eq!(history.windows(), &[0]);
+    }
+
+    #[test]
+    fn bounded() {
+        let mut history = FocusHistory::default();
+        for id in 0..MAX_ENTRIES * 2 {
+            history.record(0, 0, id);
+        }
+        assert_eq!(history.panes().len(), MAX_ENTRIES);
+        assert_eq!(history.panes()[0], MAX_ENTRIES * 2 - 1);
+    }
+}
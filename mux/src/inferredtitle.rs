@@ -0,0 +1,112 @@
+//! Infers a title for panes whose application hasn't set one via
+//! an escape sequence, by expanding the `inferred_title_template`
+//! or `inferred_title_shell_template` configuration with information
+//! about the foreground process of the pane.
+use procinfo::LocalProcessInfo;
+use std::path::Path;
+
+/// Returns the basename of the executable of the process, falling
+/// back to its name if the executable is not known
+fn process_name(info: &LocalProcessInfo) -> String {
+    info.executable
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| info.name.clone())
+}
+
+/// Abbreviates a path in the home directory to begin with `~`
+fn tilde_path(path: &Path, home: &Path) -> String {
+    match path.strip_prefix(home) {
+        Ok(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Ok(rest) => format!("~{}{}", std::path::MAIN_SEPARATOR, rest.display()),
+        Err(_) => path.display().to_string(),
+    }
+}
+
+/// Expands the placeholders in `template`:
+///
+/// * `{process}` - the basename of the foreground process
+/// * `{args}` - the arguments of the foreground process
+/// * `{cwd}` - the working directory, abbreviated with `~`
+/// * `{cwd_basename}` - the last component of the working directory
+///
+/// Other text, including unrecognized placeholders, is kept as-is.
+pub fn expand_title_template(template: &str, info: &LocalProcessInfo, home: &Path) -> String {
+    let mut result = String::new();
+    let mut remain = template;
+    while let Some(start) = remain.find('{') {
+        let end = match remain[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        result.push_str(&remain[..start]);
+        let value = match &remain[start + 1..end] {
+            "process" => process_name(info),
+            "args" => info
+                .argv
+                .iter()
+                .skip(1)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" "),
+            "cwd" => tilde_path(&info.cwd, home),
+            "cwd_basename" => info
+                .cwd
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| info.cwd.display().to_string()),
+            _ => remain[start..=end].to_string(),
+        };
+        result.push_str(&value);
+        remain = &remain[end + 1..];
+    }
+    result.push_str(remain);
+    result.trim().to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use procinfo::LocalProcessStatus;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    fn info() -> LocalProcessInfo {
+        LocalProcessInfo {
+            pid: 42,
+            ppid: 1,
+            name: "vi".to_string(),
+            executable: PathBuf::from("/usr/bin/vim"),
+            argv: vec!["vim".to_string(), "-R".to_string(), "notes.md".to_string()],
+            cwd: PathBuf::from("/home/user/src/wezterm"),
+            status: LocalProcessStatus::Run,
+            start_time: 0,
+            #[cfg(windows)]
+            console: 0,
+            children: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn expand() {
+        let home = Path::new("/home/user");
+        assert_eq!(expand_title_template("{process}", &info(), home), "vim");
+        assert_eq!(
+            expand_title_template("{process} {args} in {cwd}", &info(), home),
+            "vim -R notes.md in ~/src/wezterm"
+        );
+        assert_eq!(
+            expand_title_template("{cwd_basename} {unknown}", &info(), home),
+            "wezterm {unknown}"
+        );
+        assert_eq!(
+            expand_title_template(
+                "{cwd} {process",
+                &info(),
+                Path::new("/home/user/src/wezterm")
+            ),
+            "~ {process"
+        );
+    }
+}
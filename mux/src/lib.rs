@@ -1,20 +1,24 @@
-use crate::client::{ClientId, ClientInfo};
+use crate::client::{ClientId, ClientInfo, ClientTransport};
 use crate::pane::{Pane, PaneId};
-use crate::tab::{SplitRequest, Tab, TabId};
+use crate::tab::{PaneNode, SplitDirection, SplitRequest, SplitSize, Tab, TabId};
 use crate::window::{Window, WindowId};
 use anyhow::{anyhow, Context, Error};
-use config::keyassignment::SpawnTabDomain;
-use config::{configuration, ExitBehavior};
+use chrono::{DateTime, Utc};
+use config::keyassignment::{
+    PaneDirection, SpawnCommand, SpawnTabDomain, SplitSize as ConfigSplitSize,
+};
+use config::{configuration, ExitBehavior, MuxClientPermission, WorkspaceDefinition, WorkspaceTab};
 use domain::{Domain, DomainId, DomainState, SplitSource};
 use filedescriptor::{poll, pollfd, socketpair, AsRawSocketDescriptor, FileDescriptor, POLLIN};
+use flowcontrol::FlowControl;
 #[cfg(unix)]
 use libc::{SOL_SOCKET, SO_RCVBUF, SO_SNDBUF};
 use log::error;
 use metrics::histogram;
 use percent_encoding::percent_decode_str;
 use portable_pty::{CommandBuilder, ExitStatus, PtySize};
-use std::cell::{Ref, RefCell, RefMut};
-use std::collections::HashMap;
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
 use std::io::{Read, Write};
 use std::rc::Rc;
@@ -25,19 +29,39 @@ use std::time::{Duration, Instant};
 use termwiz::escape::csi::{DecPrivateMode, DecPrivateModeCode, Device, Mode};
 use termwiz::escape::{Action, CSI};
 use thiserror::*;
-use wezterm_term::{Clipboard, ClipboardSelection, DownloadHandler, TerminalSize};
+use wezterm_term::{
+    Clipboard, ClipboardSelection, DownloadHandler, EscapeSequenceKind, EscapeSequencePermissions,
+    TerminalSize,
+};
 #[cfg(windows)]
 use winapi::um::winsock2::{SOL_SOCKET, SO_RCVBUF, SO_SNDBUF};
 
 pub mod activity;
+pub mod autorespond;
 pub mod client;
+pub mod clientpolicy;
+pub mod collab;
 pub mod connui;
 pub mod domain;
+pub mod domainstats;
+pub mod escape_permissions;
+mod flowcontrol;
+pub mod focushistory;
+pub mod inferredtitle;
+pub mod localecho;
 pub mod localpane;
+pub mod outputwatch;
 pub mod pane;
+pub mod panelog;
+pub mod panepipe;
+pub mod panestats;
+pub mod query;
 pub mod renderable;
+pub mod resourcegroup;
 pub mod ssh;
 pub mod tab;
+pub mod tcp;
+pub mod terminfo;
 pub mod termwiztermtab;
 pub mod tmux;
 pub mod tmux_commands;
@@ -45,6 +69,10 @@ mod tmux_pty;
 pub mod window;
 
 use crate::activity::Activity;
+use crate::clientpolicy::{attach_outcome, emit_client_event, AttachOutcome};
+use crate::collab::{ClientCursor, Collaborator, InputTurns, PaneCollaborators};
+use crate::escape_permissions::MuxEscapeSequencePermissions;
+use crate::focushistory::FocusHistory;
 
 pub const DEFAULT_WORKSPACE: &str = "default";
 
@@ -53,6 +81,12 @@ pub enum MuxNotification {
     PaneOutput(PaneId),
     PaneAdded(PaneId),
     PaneRemoved(PaneId),
+    /// A client focused a different pane
+    PaneFocused(PaneId),
+    /// A client asked for the pane to be activated, eg: via
+    /// `wezterm cli activate-pane`; the gui should bring the window
+    /// that contains it to the front
+    PaneActivationRequested(PaneId),
     WindowCreated(WindowId),
     WindowRemoved(WindowId),
     WindowInvalidated(WindowId),
@@ -68,6 +102,18 @@ pub enum MuxNotification {
         selection: ClipboardSelection,
         clipboard: Option<String>,
     },
+    /// An application in the pane has requested the contents of the
+    /// clipboard via OSC 52.  The gui decides whether to allow it.
+    QueryClipboard {
+        pane_id: PaneId,
+        selection: ClipboardSelection,
+    },
+    /// The escape_sequence_policy requires that the user be asked
+    /// whether sequences of this kind should be honored in the pane
+    EscapeSequencePermission {
+        pane_id: PaneId,
+        kind: EscapeSequenceKind,
+    },
     SaveToDownloads {
         name: Option<String>,
         data: Arc<Vec<u8>>,
@@ -76,6 +122,30 @@ pub enum MuxNotification {
         tab_id: TabId,
         window_id: WindowId,
     },
+    /// The process in the pane exited unsuccessfully and
+    /// `dead_pane_inspector` is enabled, so the gui should show the
+    /// inspector over the pane
+    InspectDeadPane {
+        pane_id: PaneId,
+        status: String,
+    },
+    /// The connection to a client domain was lost
+    ConnectionError {
+        domain_id: DomainId,
+        message: String,
+    },
+    /// The set of clients attached to the pane, where they are
+    /// pointing, or who holds its input turn has changed
+    PaneCollaboratorsChanged(PaneId),
+    /// Asks the session of the client to disconnect it
+    ClientDetachRequested(Arc<ClientId>),
+    /// Asks the gui to render the pane as it is currently displayed
+    /// and to send the PNG encoded image to `reply`.
+    /// If no gui window is displaying the pane, `reply` is dropped.
+    CaptureImage {
+        pane_id: PaneId,
+        reply: smol::channel::Sender<anyhow::Result<Vec<u8>>>,
+    },
 }
 
 static SUB_ID: AtomicUsize = AtomicUsize::new(0);
@@ -92,6 +162,12 @@ pub struct Mux {
     clients: RefCell<HashMap<ClientId, ClientInfo>>,
     identity: RefCell<Option<Arc<ClientId>>>,
     num_panes_by_workspace: RefCell<HashMap<String, usize>>,
+    workspace_last_activity: RefCell<HashMap<String, DateTime<Utc>>>,
+    background_panes_pending: Cell<bool>,
+    focus_history: RefCell<FocusHistory>,
+    input_turns: RefCell<InputTurns>,
+    client_permissions: RefCell<HashMap<ClientId, MuxClientPermission>>,
+    detach_reasons: RefCell<HashMap<ClientId, &'static str>>,
 }
 
 const BUFSIZE: usize = 1024 * 1024;
@@ -113,6 +189,8 @@ fn send_actions_to_mux(pane_id: PaneId, dead: &Arc<AtomicBool>, actions: Vec<Act
                     "send_actions_to_mux.perform_actions.latency",
                     start.elapsed()
                 );
+                outputwatch::check_output(&*pane);
+                autorespond::check_output(&*pane);
                 mux.notify(MuxNotification::PaneOutput(pane_id));
             } else {
                 // Something else removed the pane from
@@ -126,15 +204,86 @@ fn send_actions_to_mux(pane_id: PaneId, dead: &Arc<AtomicBool>, actions: Vec<Act
     histogram!("send_actions_to_mux.rate", 1.);
 }
 
+/// Ends a synchronized output (mode 2026) update on behalf of an
+/// application that failed to do so before the deadline expired.
+/// The reset is fed through the terminal model so that its idea
+/// of the mode matches what we have done with the output.
+fn force_end_synchronized_output(
+    pane_id: PaneId,
+    dead: &Arc<AtomicBool>,
+    actions: &mut Vec<Action>,
+) {
+    log::debug!(
+        "pane {}: synchronized output deadline expired; flushing",
+        pane_id
+    );
+    actions.push(Action::CSI(CSI::Mode(Mode::ResetDecPrivateMode(
+        DecPrivateMode::Code(DecPrivateModeCode::SynchronizedOutput),
+    ))));
+    send_actions_to_mux(pane_id, dead, std::mem::take(actions));
+}
+
 fn parse_buffered_data(pane_id: PaneId, dead: &Arc<AtomicBool>, mut rx: FileDescriptor) {
     let mut buf = vec![0; configuration().mux_output_parser_buffer_size];
     let mut parser = termwiz::escape::parser::Parser::new();
     let mut actions = vec![];
     let mut hold = false;
+    let mut hold_deadline: Option<Instant> = None;
     let mut action_size = 0;
     let mut delay_ms = configuration().mux_output_parser_coalesce_delay_ms;
+    let mut sync_enabled = configuration().enable_synchronized_output;
+    let mut sync_timeout = Duration::from_millis(configuration().synchronized_output_timeout_ms);
+    let mut flow = FlowControl::default();
+    let mut flow_control = configuration().mux_output_flow_control;
+    let mut duty_cycle = configuration().mux_output_flow_control_duty_cycle;
+    let mut background_interval =
+        Duration::from_millis(configuration().mux_output_background_throttle_ms);
+    let mut last_flush = Instant::now();
 
     loop {
+        let background = !background_interval.is_zero() && flowcontrol::is_background(pane_id);
+        if background && actions.is_empty() && hold_deadline.is_none() {
+            // Nobody can see this pane, so process its output in
+            // larger batches at most once per interval.  We're woken
+            // as soon as its tab is activated, so that it catches up
+            // immediately.
+            flowcontrol::wait_while_background(pane_id, last_flush + background_interval);
+        }
+
+        if let Some(deadline) = hold_deadline {
+            // Don't let an application that never ends its synchronized
+            // update hold its output back indefinitely
+            let mut pfd = [pollfd {
+                fd: rx.as_socket_descriptor(),
+                events: POLLIN,
+                revents: 0,
+            }];
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if !matches!(poll(&mut pfd, Some(remaining)), Ok(1)) {
+                force_end_synchronized_output(pane_id, dead, &mut actions);
+                hold = false;
+                hold_deadline = None;
+                action_size = 0;
+                continue;
+            }
+        } else if !hold && !actions.is_empty() {
+            // We're holding a partial line back from the last batch;
+            // send it if the rest of the line doesn't arrive promptly
+            let mut pfd = [pollfd {
+                fd: rx.as_socket_descriptor(),
+                events: POLLIN,
+                revents: 0,
+            }];
+            if !matches!(
+                poll(&mut pfd, Some(Duration::from_millis(delay_ms.max(1)))),
+                Ok(1)
+            ) {
+                send_actions_to_mux(pane_id, dead, std::mem::take(&mut actions));
+                action_size = 0;
+                continue;
+            }
+        }
+
         match rx.read(&mut buf) {
             Ok(size) if size == 0 => {
                 dead.store(true, Ordering::Relaxed);
@@ -150,8 +299,11 @@ fn parse_buffered_data(pane_id: PaneId, dead: &Arc<AtomicBool>, mut rx: FileDesc
                     match &action {
                         Action::CSI(CSI::Mode(Mode::SetDecPrivateMode(DecPrivateMode::Code(
                             DecPrivateModeCode::SynchronizedOutput,
-                        )))) => {
+                        )))) if sync_enabled => {
                             hold = true;
+                            if hold_deadline.is_none() {
+                                hold_deadline = Some(Instant::now() + sync_timeout);
+                            }
 
                             // Flush prior actions
                             if !actions.is_empty() {
@@ -163,10 +315,12 @@ fn parse_buffered_data(pane_id: PaneId, dead: &Arc<AtomicBool>, mut rx: FileDesc
                             DecPrivateMode::Code(DecPrivateModeCode::SynchronizedOutput),
                         ))) => {
                             hold = false;
+                            hold_deadline = None;
                             flush = true;
                         }
                         Action::CSI(CSI::Device(dev)) if matches!(**dev, Device::SoftReset) => {
                             hold = false;
+                            hold_deadline = None;
                             flush = true;
                         }
                         _ => {}
@@ -179,12 +333,27 @@ fn parse_buffered_data(pane_id: PaneId, dead: &Arc<AtomicBool>, mut rx: FileDesc
                     }
                 });
                 action_size += size;
+
+                if hold && hold_deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                    // The application is streaming output faster than
+                    // we can time out the poll above
+                    force_end_synchronized_output(pane_id, dead, &mut actions);
+                    hold = false;
+                    hold_deadline = None;
+                    action_size = 0;
+                }
+
                 if !actions.is_empty() && !hold {
                     // If we haven't accumulated too much data,
                     // pause for a short while to increase the chances
                     // that we coalesce a full "frame" from an unoptimized
                     // TUI program
-                    if action_size < buf.len() {
+                    let batch_limit = if background {
+                        buf.len() * flowcontrol::BACKGROUND_BATCH_MULTIPLIER
+                    } else {
+                        buf.len()
+                    };
+                    if action_size < batch_limit {
                         if delay_ms > 0 {
                             let mut pfd = [pollfd {
                                 fd: rx.as_socket_descriptor(),
@@ -202,13 +371,35 @@ fn parse_buffered_data(pane_id: PaneId, dead: &Arc<AtomicBool>, mut rx: FileDesc
                         }
                     }
 
-                    send_actions_to_mux(pane_id, dead, std::mem::take(&mut actions));
+                    let mut batch = std::mem::take(&mut actions);
+                    if flow_control {
+                        flow.record_batch(action_size, buf.len());
+                        actions = flow.split_partial_line(&mut batch);
+                    }
+
+                    let start = Instant::now();
+                    send_actions_to_mux(pane_id, dead, batch);
                     action_size = 0;
+                    last_flush = Instant::now();
+
+                    if flow_control {
+                        let pause = flow.pause_after(start.elapsed(), duty_cycle);
+                        if !pause.is_zero() {
+                            histogram!("parse_buffered_data.flow_control.pause", pause);
+                            std::thread::sleep(pause);
+                        }
+                    }
                 }
 
                 let config = configuration();
                 buf.resize(config.mux_output_parser_buffer_size, 0);
                 delay_ms = config.mux_output_parser_coalesce_delay_ms;
+                sync_enabled = config.enable_synchronized_output;
+                sync_timeout = Duration::from_millis(config.synchronized_output_timeout_ms);
+                flow_control = config.mux_output_flow_control;
+                duty_cycle = config.mux_output_flow_control_duty_cycle;
+                background_interval =
+                    Duration::from_millis(config.mux_output_background_throttle_ms);
             }
         }
     }
@@ -286,6 +477,7 @@ fn read_from_pane_pty(pane_id: PaneId, banner: Option<String>, mut reader: Box<d
             }
             Ok(size) => {
                 histogram!("read_from_pane_pty.bytes.rate", size as f64);
+                panestats::record_pty_read(pane_id, size);
                 if let Err(err) = tx.write_all(&buf[..size]) {
                     error!(
                         "read_pty failed to write to parser: pane {} {:?}",
@@ -293,6 +485,8 @@ fn read_from_pane_pty(pane_id: PaneId, banner: Option<String>, mut reader: Box<d
                     );
                     break;
                 }
+                panelog::record_output(pane_id, &buf[..size]);
+                panepipe::forward(pane_id, &buf[..size]);
             }
         }
     }
@@ -311,7 +505,13 @@ fn read_from_pane_pty(pane_id: PaneId, banner: Option<String>, mut reader: Box<d
         ExitBehavior::Close => {
             promise::spawn::spawn_into_main_thread(async move {
                 let mux = Mux::get().unwrap();
-                mux.remove_pane(pane_id);
+                match mux.get_pane(pane_id) {
+                    // Let the pane decide whether it is dead; it may
+                    // be about to restart, or this may be the pty
+                    // that it was using before it restarted
+                    Some(pane) if pane.can_restart() => mux.prune_dead_windows(),
+                    _ => mux.remove_pane(pane_id),
+                }
             })
             .detach();
         }
@@ -396,7 +596,45 @@ impl Mux {
             clients: RefCell::new(HashMap::new()),
             identity: RefCell::new(None),
             num_panes_by_workspace: RefCell::new(HashMap::new()),
+            workspace_last_activity: RefCell::new(HashMap::new()),
+            background_panes_pending: Cell::new(false),
+            focus_history: RefCell::new(FocusHistory::default()),
+            input_turns: RefCell::new(InputTurns::default()),
+            client_permissions: RefCell::new(HashMap::new()),
+            detach_reasons: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Arranges for the set of panes in background tabs to be
+    /// recomputed once the current mutation of the mux is complete
+    fn schedule_background_pane_update(&self) {
+        if self.background_panes_pending.replace(true) {
+            return;
         }
+        promise::spawn::spawn_into_main_thread(async {
+            if let Some(mux) = Mux::get() {
+                mux.background_panes_pending.set(false);
+                mux.update_background_panes();
+            }
+        })
+        .detach();
+    }
+
+    /// Tells the output parsers which panes are in tabs that are
+    /// not active in their window
+    fn update_background_panes(&self) {
+        let mut background = HashSet::new();
+        for window in self.windows.borrow().values() {
+            let active = window.get_active_idx();
+            for (idx, tab) in window.iter().enumerate() {
+                if idx != active {
+                    for pos in tab.iter_panes_ignoring_zoom() {
+                        background.insert(pos.pane.pane_id());
+                    }
+                }
+            }
+        }
+        flowcontrol::set_background_panes(background);
     }
 
     fn recompute_pane_count(&self) {
@@ -411,9 +649,27 @@ impl Mux {
     }
 
     pub fn client_had_input(&self, client_id: &ClientId) {
+        let mut workspace = None;
         if let Some(info) = self.clients.borrow_mut().get_mut(client_id) {
             info.update_last_input();
+            workspace = info.active_workspace.clone();
         }
+        self.record_workspace_activity(workspace.as_deref().unwrap_or(DEFAULT_WORKSPACE));
+    }
+
+    fn record_workspace_activity(&self, workspace: &str) {
+        self.workspace_last_activity
+            .borrow_mut()
+            .insert(workspace.to_string(), Utc::now());
+    }
+
+    /// Returns the time at which a client last switched to or
+    /// sent input to the specified workspace
+    pub fn workspace_last_activity(&self, workspace: &str) -> Option<DateTime<Utc>> {
+        self.workspace_last_activity
+            .borrow()
+            .get(workspace)
+            .cloned()
     }
 
     pub fn record_input_for_current_identity(&self) {
@@ -429,6 +685,7 @@ impl Mux {
     }
 
     pub fn record_focus_for_client(&self, client_id: &ClientId, pane_id: PaneId) {
+        let prior_workspace = self.client_workspace(client_id);
         let mut prior = None;
         if let Some(info) = self.clients.borrow_mut().get_mut(client_id) {
             prior = info.focused_pane_id;
@@ -447,22 +704,349 @@ impl Mux {
         if let Some(pane) = self.get_pane(pane_id) {
             pane.focus_changed(true);
         }
+        if let Some((_domain_id, window_id, tab_id)) = self.resolve_pane_id(pane_id) {
+            self.focus_history
+                .borrow_mut()
+                .record(window_id, tab_id, pane_id);
+        }
+        self.notify(MuxNotification::PaneFocused(pane_id));
+        if let Some(prior_id) = prior {
+            self.notify(MuxNotification::PaneCollaboratorsChanged(prior_id));
+        }
+        self.notify(MuxNotification::PaneCollaboratorsChanged(pane_id));
+
+        if let Some(workspace) = self.client_workspace(client_id) {
+            if prior_workspace.as_deref() != Some(workspace.as_str()) {
+                self.apply_attach_policy(client_id, &workspace);
+            }
+        }
     }
 
-    pub fn register_client(&self, client_id: Arc<ClientId>) {
+    /// Returns the client that has the pane focused, preferring the
+    /// one that most recently sent input if there are several.  That
+    /// is the client that should answer requests that the application
+    /// in the pane makes of the user, such as reading the clipboard.
+    pub fn client_focusing_pane(&self, pane_id: PaneId) -> Option<Arc<ClientId>> {
         self.clients
-            .borrow_mut()
-            .insert((*client_id).clone(), ClientInfo::new(client_id));
+            .borrow()
+            .values()
+            .filter(|info| info.focused_pane_id == Some(pane_id))
+            .max_by_key(|info| info.last_input)
+            .map(|info| Arc::clone(&info.client_id))
+    }
+
+    /// Returns the workspace of the pane that the client has focused
+    fn client_workspace(&self, client_id: &ClientId) -> Option<String> {
+        let pane_id = self.clients.borrow().get(client_id)?.focused_pane_id?;
+        let (_domain_id, window_id, _tab_id) = self.resolve_pane_id(pane_id)?;
+        let workspace = self.get_window(window_id)?.get_workspace().to_string();
+        Some(workspace)
+    }
+
+    /// Called when a client focuses a pane in a different workspace
+    /// than before; applies `mux_client_attach_policy` and
+    /// `mux_max_clients_per_workspace` to it and to the other clients
+    /// that are attached to that workspace
+    fn apply_attach_policy(&self, client_id: &ClientId, workspace: &str) {
+        emit_client_event("mux-client-attached", client_id, workspace.to_string());
+
+        let mut others: Vec<ClientInfo> = self
+            .clients
+            .borrow()
+            .values()
+            .filter(|info| *info.client_id != *client_id)
+            .cloned()
+            .collect();
+        others.retain(|info| self.client_workspace(&info.client_id).as_deref() == Some(workspace));
+        others.sort_by_key(|info| info.connected_at);
+
+        let config = configuration();
+        match attach_outcome(
+            config.mux_client_attach_policy,
+            config.mux_max_clients_per_workspace,
+            others.len(),
+        ) {
+            AttachOutcome::Share => {}
+            AttachOutcome::DetachOthers => {
+                for info in others {
+                    self.request_detach(&info.client_id, "stolen");
+                }
+            }
+            AttachOutcome::Refuse => self.request_detach(client_id, "workspace-full"),
+        }
+    }
+
+    /// Records the cell that the client is pointing at, and lets the
+    /// other clients attached to the affected panes know about it
+    pub fn set_client_cursor(&self, client_id: &ClientId, cursor: Option<ClientCursor>) {
+        let prior = match self.clients.borrow_mut().get_mut(client_id) {
+            Some(info) => info.update_cursor(cursor),
+            None => return,
+        };
+        if prior == cursor {
+            return;
+        }
+        let prior_pane = prior.map(|c| c.pane_id);
+        let pane = cursor.map(|c| c.pane_id);
+        if let Some(pane_id) = prior_pane {
+            self.notify(MuxNotification::PaneCollaboratorsChanged(pane_id));
+        }
+        if let Some(pane_id) = pane {
+            if pane != prior_pane {
+                self.notify(MuxNotification::PaneCollaboratorsChanged(pane_id));
+            }
+        }
+    }
+
+    /// Gives the client the input turn for the pane, taking it from
+    /// whichever client had it, or gives it up if the client already
+    /// holds it.  Returns true if the client holds the turn afterwards.
+    pub fn toggle_pane_input_turn(
+        &self,
+        pane_id: PaneId,
+        client_id: Arc<ClientId>,
+    ) -> anyhow::Result<bool> {
+        self.get_pane(pane_id)
+            .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+        let has_turn = self.input_turns.borrow_mut().toggle(pane_id, client_id);
+        self.notify(MuxNotification::PaneCollaboratorsChanged(pane_id));
+        Ok(has_turn)
+    }
+
+    /// Returns an error if another client holds the input turn for the pane
+    pub fn check_pane_input_turn(
+        &self,
+        pane_id: PaneId,
+        client_id: Option<&ClientId>,
+    ) -> anyhow::Result<()> {
+        let turns = self.input_turns.borrow();
+        if turns.may_send_input(pane_id, client_id) {
+            return Ok(());
+        }
+        match turns.owner(pane_id) {
+            Some(owner) => anyhow::bail!(
+                "{}@{} has the input turn for pane {}",
+                owner.username,
+                owner.hostname,
+                pane_id
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the other clients that have the pane focused or are
+    /// pointing at it, and who has its input turn, as seen by `client_id`
+    pub fn pane_collaborators(
+        &self,
+        pane_id: PaneId,
+        client_id: Option<&ClientId>,
+    ) -> PaneCollaborators {
+        let mut clients: Vec<Collaborator> = self
+            .clients
+            .borrow()
+            .values()
+            .filter(|info| Some(&*info.client_id) != client_id)
+            .filter_map(|info| {
+                let cursor = info
+                    .cursor
+                    .filter(|c| c.pane_id == pane_id)
+                    .map(|c| (c.x, c.y));
+                if cursor.is_none() && info.focused_pane_id != Some(pane_id) {
+                    return None;
+                }
+                Some(Collaborator {
+                    client_id: (*info.client_id).clone(),
+                    cursor,
+                })
+            })
+            .collect();
+        clients.sort_by(|a, b| {
+            (a.client_id.epoch, a.client_id.pid, a.client_id.id).cmp(&(
+                b.client_id.epoch,
+                b.client_id.pid,
+                b.client_id.id,
+            ))
+        });
+        PaneCollaborators {
+            clients,
+            input_turn: self.input_turns.borrow().turn_for(pane_id, client_id),
+        }
+    }
+
+    /// Returns the panes, most recently focused first
+    pub fn recent_panes(&self) -> Vec<PaneId> {
+        self.prune_focus_history();
+        self.focus_history.borrow().panes().to_vec()
+    }
+
+    /// Returns the tabs, most recently focused first
+    pub fn recent_tabs(&self) -> Vec<TabId> {
+        self.prune_focus_history();
+        self.focus_history.borrow().tabs().to_vec()
+    }
+
+    /// Returns the windows, most recently focused first
+    pub fn recent_windows(&self) -> Vec<WindowId> {
+        self.prune_focus_history();
+        self.focus_history.borrow().windows().to_vec()
+    }
+
+    fn prune_focus_history(&self) {
+        let panes = self.panes.borrow();
+        let tabs = self.tabs.borrow();
+        let windows = self.windows.borrow();
+        self.focus_history.borrow_mut().prune(
+            |id| panes.contains_key(&id),
+            |id| tabs.contains_key(&id),
+            |id| windows.contains_key(&id),
+        );
+    }
+
+    /// Makes the pane the active pane in its tab, makes that tab the
+    /// active tab in its window, and asks the gui to focus that window
+    pub fn activate_pane(&self, pane_id: PaneId) -> anyhow::Result<()> {
+        let pane = self
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+        let (_domain_id, window_id, tab_id) = self
+            .resolve_pane_id(pane_id)
+            .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+        let tab = self
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("no such tab {}", tab_id))?;
+        tab.set_active_pane(&pane);
+        {
+            let mut window = self
+                .get_window_mut(window_id)
+                .ok_or_else(|| anyhow!("no such window {}", window_id))?;
+            let tab_idx = window
+                .idx_by_id(tab_id)
+                .ok_or_else(|| anyhow!("tab {} isn't really in window {}", tab_id, window_id))?;
+            window.save_and_then_set_active(tab_idx);
+        }
+        self.notify(MuxNotification::PaneActivationRequested(pane_id));
+        Ok(())
+    }
+
+    /// Registers a client that is connected via `transport`.  A client
+    /// id that is already registered can only be reused from the same
+    /// transport, so that a client can't take over the identity, and
+    /// any permission set with `set_client_permission`, of a client
+    /// that connected some other way.
+    pub fn register_client(
+        &self,
+        client_id: Arc<ClientId>,
+        transport: ClientTransport,
+    ) -> anyhow::Result<()> {
+        let mut clients = self.clients.borrow_mut();
+        if let Some(info) = clients.get(&client_id) {
+            if info.transport != transport {
+                anyhow::bail!(
+                    "client {:?} is already connected via {:?}",
+                    client_id,
+                    info.transport
+                );
+            }
+        }
+        clients.insert((*client_id).clone(), ClientInfo::new(client_id, transport));
+        Ok(())
     }
 
     pub fn iter_clients(&self) -> Vec<ClientInfo> {
         self.clients
             .borrow()
             .values()
-            .map(|info| info.clone())
+            .map(|info| {
+                let mut info = info.clone();
+                info.permission = self.client_permission(Some(&info.client_id), &info.transport);
+                info
+            })
             .collect()
     }
 
+    /// Returns what the client is allowed to do: either the permission
+    /// that was set for it with `set_client_permission`, or the
+    /// `mux_client_permissions` rule that matches its transport.
+    /// None means that it is unrestricted.
+    pub fn client_permission(
+        &self,
+        client_id: Option<&ClientId>,
+        transport: &ClientTransport,
+    ) -> Option<MuxClientPermission> {
+        if let Some(client_id) = client_id {
+            if let Some(permission) = self.client_permissions.borrow().get(client_id) {
+                return Some(permission.clone());
+            }
+        }
+        match transport {
+            ClientTransport::Local => None,
+            ClientTransport::Remote { listener } => {
+                let config = configuration();
+                config::mux_client_permission(&config.mux_client_permissions, listener)
+            }
+        }
+    }
+
+    /// Overrides the `mux_client_permissions` rules for a connected
+    /// client until it disconnects.  `None` reverts to the rules.
+    pub fn set_client_permission(
+        &self,
+        client_id: &ClientId,
+        permission: Option<MuxClientPermission>,
+    ) -> anyhow::Result<()> {
+        if !self.clients.borrow().contains_key(client_id) {
+            anyhow::bail!("no such client {:?}", client_id);
+        }
+        let mut permissions = self.client_permissions.borrow_mut();
+        match permission {
+            Some(permission) => {
+                permissions.insert(client_id.clone(), permission);
+            }
+            None => {
+                permissions.remove(client_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Disconnects a client of this mux server
+    pub fn detach_client(&self, client_id: &ClientId) -> anyhow::Result<()> {
+        if !self.clients.borrow().contains_key(client_id) {
+            anyhow::bail!("no such client {:?}", client_id);
+        }
+        self.request_detach(client_id, "requested");
+        Ok(())
+    }
+
+    /// Detaches the client if it has not sent any input for longer
+    /// than `mux_client_idle_timeout_secs`.  Returns true if it was
+    /// detached.
+    pub fn detach_client_if_idle(&self, client_id: &ClientId) -> bool {
+        let last_input = match self.clients.borrow().get(client_id) {
+            Some(info) => info.last_input,
+            None => return false,
+        };
+        let timeout = configuration().mux_client_idle_timeout_secs;
+        if clientpolicy::is_idle(last_input, Utc::now(), timeout) {
+            self.request_detach(client_id, "idle");
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Asks the session of the client to disconnect it, remembering
+    /// the reason for the `mux-client-detached` event
+    fn request_detach(&self, client_id: &ClientId, reason: &'static str) {
+        self.detach_reasons
+            .borrow_mut()
+            .entry(client_id.clone())
+            .or_insert(reason);
+        self.notify(MuxNotification::ClientDetachRequested(Arc::new(
+            client_id.clone(),
+        )));
+    }
+
     /// Returns a list of the unique workspace names known to the mux.
     /// This is taken from all known windows.
     pub fn iter_workspaces(&self) -> Vec<String> {
@@ -517,6 +1101,46 @@ impl Mux {
             info.active_workspace.replace(workspace.to_string());
             self.notify(MuxNotification::ActiveWorkspaceChanged(ident.clone()));
         }
+        drop(clients);
+        self.record_workspace_activity(workspace);
+    }
+
+    /// Moves all of the windows in `old_workspace` into `new_workspace`,
+    /// and updates any clients that had `old_workspace` active
+    pub fn rename_workspace(&self, old_workspace: &str, new_workspace: &str) {
+        if old_workspace == new_workspace {
+            return;
+        }
+        for window_id in self.iter_windows_in_workspace(old_workspace) {
+            if let Some(mut window) = self.get_window_mut(window_id) {
+                window.set_workspace(new_workspace);
+            }
+        }
+        self.recompute_pane_count();
+
+        let idents: Vec<Arc<ClientId>> = self
+            .clients
+            .borrow()
+            .values()
+            .filter(|info| info.active_workspace.as_deref() == Some(old_workspace))
+            .map(|info| info.client_id.clone())
+            .collect();
+        for ident in idents {
+            self.set_active_workspace_for_client(&ident, new_workspace);
+        }
+
+        let mut last_activity = self.workspace_last_activity.borrow_mut();
+        if let Some(when) = last_activity.remove(old_workspace) {
+            last_activity.insert(new_workspace.to_string(), when);
+        }
+    }
+
+    /// Closes all of the windows in the specified workspace
+    pub fn kill_workspace(&self, workspace: &str) {
+        for window_id in self.iter_windows_in_workspace(workspace) {
+            self.kill_window(window_id);
+        }
+        self.workspace_last_activity.borrow_mut().remove(workspace);
     }
 
     /// Assigns the active workspace name for the current identity
@@ -546,7 +1170,24 @@ impl Mux {
     }
 
     pub fn unregister_client(&self, client_id: &ClientId) {
-        self.clients.borrow_mut().remove(client_id);
+        let info = self.clients.borrow_mut().remove(client_id);
+        self.client_permissions.borrow_mut().remove(client_id);
+        let reason = self
+            .detach_reasons
+            .borrow_mut()
+            .remove(client_id)
+            .unwrap_or("disconnected");
+        let mut panes = self.input_turns.borrow_mut().remove_client(client_id);
+        if let Some(info) = info {
+            emit_client_event("mux-client-detached", client_id, reason.to_string());
+            panes.extend(info.focused_pane_id);
+            panes.extend(info.cursor.map(|c| c.pane_id));
+        }
+        panes.sort();
+        panes.dedup();
+        for pane_id in panes {
+            self.notify(MuxNotification::PaneCollaboratorsChanged(pane_id));
+        }
     }
 
     pub fn subscribe<F>(&self, subscriber: F)
@@ -560,6 +1201,16 @@ impl Mux {
     }
 
     pub fn notify(&self, notification: MuxNotification) {
+        if matches!(
+            notification,
+            MuxNotification::WindowInvalidated(_)
+                | MuxNotification::WindowRemoved(_)
+                | MuxNotification::TabAddedToWindow { .. }
+                | MuxNotification::PaneAdded(_)
+                | MuxNotification::PaneRemoved(_)
+        ) {
+            self.schedule_background_pane_update();
+        }
         let mut subscribers = self.subscribers.borrow_mut();
         subscribers.retain(|_, notify| notify(notification.clone()));
     }
@@ -637,6 +1288,13 @@ impl Mux {
         let downloader: Arc<dyn DownloadHandler> = Arc::new(MuxDownloader {});
         pane.set_download_handler(&downloader);
 
+        let permissions: Arc<dyn EscapeSequencePermissions> =
+            Arc::new(MuxEscapeSequencePermissions {
+                pane_id: pane.pane_id(),
+                domain_id: pane.domain_id(),
+            });
+        pane.set_escape_sequence_permissions(&permissions);
+
         self.panes
             .borrow_mut()
             .insert(pane.pane_id(), Rc::clone(pane));
@@ -668,6 +1326,13 @@ impl Mux {
         if let Some(pane) = self.panes.borrow_mut().remove(&pane_id).clone() {
             log::debug!("killing pane {}", pane_id);
             pane.kill();
+            escape_permissions::forget_decisions(pane_id);
+            panestats::remove_pane(pane_id);
+            panepipe::remove_pane(pane_id);
+            panelog::stop_logging(pane_id);
+            outputwatch::remove_pane(pane_id);
+            autorespond::remove_pane(pane_id);
+            self.input_turns.borrow_mut().remove_pane(pane_id);
             self.recompute_pane_count();
             self.notify(MuxNotification::PaneRemoved(pane_id));
         }
@@ -966,6 +1631,7 @@ impl Mux {
         &self,
         command_dir: Option<String>,
         pane: Option<Rc<dyn Pane>>,
+        target_domain: &Arc<dyn Domain>,
     ) -> Option<String> {
         command_dir.or_else(|| {
             match pane {
@@ -987,12 +1653,53 @@ impl Mux {
                         } else {
                             path
                         }
+                    })
+                    .map(|path| {
+                        // When moving between a WSL domain and some other
+                        // domain, the path needs to be translated into a
+                        // form that makes sense on the other side
+                        let config = config::configuration();
+                        let source = self
+                            .get_domain(pane.domain_id())
+                            .and_then(|domain| config.wsl_domain(domain.domain_name()).cloned());
+                        let target = config.wsl_domain(target_domain.domain_name());
+                        if source.as_ref().map(|d| d.name.as_str())
+                            == target.map(|d| d.name.as_str())
+                        {
+                            return path;
+                        }
+                        config::translate_wsl_cwd(&path, source.as_ref(), target)
                     }),
                 None => None,
             }
         })
     }
 
+    /// Resizes the pane to the specified number of columns and/or rows
+    /// by moving the splits next to it; see Tab::resize_pane
+    pub fn resize_pane(
+        &self,
+        pane_id: PaneId,
+        cols: Option<usize>,
+        rows: Option<usize>,
+    ) -> anyhow::Result<()> {
+        let (_domain_id, window_id, tab_id) = self
+            .resolve_pane_id(pane_id)
+            .ok_or_else(|| anyhow!("pane_id {} invalid", pane_id))?;
+        let tab = self
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab_id {} invalid", tab_id))?;
+        let pane_index = tab
+            .iter_panes_ignoring_zoom()
+            .into_iter()
+            .find(|p| p.pane.pane_id() == pane_id)
+            .map(|p| p.index)
+            .ok_or_else(|| anyhow!("pane_id {} not found in tab {}", pane_id, tab_id))?;
+        tab.resize_pane(pane_index, cols, rows);
+        self.notify(MuxNotification::WindowInvalidated(window_id));
+        Ok(())
+    }
+
     pub async fn split_pane(
         &self,
         // TODO: disambiguate with TabId
@@ -1024,7 +1731,7 @@ impl Mux {
                 command_dir,
             } => SplitSource::Spawn {
                 command,
-                command_dir: self.resolve_cwd(command_dir, Some(Rc::clone(&current_pane))),
+                command_dir: self.resolve_cwd(command_dir, Some(Rc::clone(&current_pane)), &domain),
             },
             other => other,
         };
@@ -1096,6 +1803,145 @@ impl Mux {
         Ok((tab, window_id))
     }
 
+    /// Re-creates the tab in `domain`, with the same arrangement of
+    /// splits and the same working directories, and then closes the
+    /// original tab.  This is used to push a tab from the local domain
+    /// into a multiplexer domain so that it outlives the GUI, and to
+    /// adopt a multiplexer tab back into the local domain.
+    /// The programs running in the panes are not moved; each of the new
+    /// panes runs the default program of the domain.
+    pub async fn move_tab_to_domain(
+        &self,
+        tab_id: TabId,
+        domain: SpawnTabDomain,
+    ) -> anyhow::Result<(Rc<Tab>, WindowId)> {
+        let src_tab = self
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab_id {} invalid", tab_id))?;
+        let window_id = self
+            .window_containing_tab(tab_id)
+            .ok_or_else(|| anyhow!("no window contains tab {}", tab_id))?;
+        let workspace = self
+            .get_window(window_id)
+            .map(|w| w.get_workspace().to_string())
+            .ok_or_else(|| anyhow!("window_id {} invalid", window_id))?;
+        let src_panes = src_tab.iter_panes_ignoring_zoom();
+        let active_pane_id = src_tab.get_active_pane().map(|p| p.pane_id());
+
+        let domain = self
+            .resolve_spawn_tab_domain(active_pane_id, &domain)
+            .context("resolve_spawn_tab_domain")?;
+        if src_panes
+            .iter()
+            .all(|p| p.pane.domain_id() == domain.domain_id())
+        {
+            anyhow::bail!(
+                "tab {} is already in domain `{}`",
+                tab_id,
+                domain.domain_name()
+            );
+        }
+        let domain_name = SpawnTabDomain::DomainName(domain.domain_name().to_string());
+
+        let cwd_of = |pane_id: PaneId| {
+            let pane = src_panes
+                .iter()
+                .find(|p| p.pane.pane_id() == pane_id)
+                .map(|p| Rc::clone(&p.pane));
+            self.resolve_cwd(None, pane, &domain)
+        };
+        fn first_pane_id(node: &PaneNode) -> Option<PaneId> {
+            match node {
+                PaneNode::Empty => None,
+                PaneNode::Leaf(entry) => Some(entry.pane_id),
+                PaneNode::Split { left, .. } => first_pane_id(left),
+            }
+        }
+
+        let tree = src_tab.codec_pane_tree();
+        let first = first_pane_id(&tree).ok_or_else(|| anyhow!("tab {} has no panes", tab_id))?;
+        let (tab, pane, window_id) = self
+            .spawn_tab_or_window(
+                Some(window_id),
+                domain_name.clone(),
+                None,
+                cwd_of(first),
+                src_tab.get_size(),
+                None,
+                workspace,
+            )
+            .await?;
+
+        // Split the new panes in the same way as the originals.
+        // The tree is walked using a stack, rather than recursively,
+        // as the splits are async.
+        let mut stack = vec![(pane, tree)];
+        let mut new_active = None;
+        let result: anyhow::Result<()> = async {
+            while let Some((pane, node)) = stack.pop() {
+                match node {
+                    PaneNode::Empty => {}
+                    PaneNode::Leaf(entry) => {
+                        if entry.is_active_pane {
+                            new_active.replace(pane);
+                        }
+                    }
+                    PaneNode::Split { left, right, node } => {
+                        let second = first_pane_id(&right)
+                            .ok_or_else(|| anyhow!("split of tab {} has no panes", tab_id))?;
+                        let (new_pane, _size) = self
+                            .split_pane(
+                                pane.pane_id(),
+                                SplitRequest {
+                                    direction: node.direction,
+                                    target_is_second: true,
+                                    top_level: false,
+                                    size: SplitSize::Cells(match node.direction {
+                                        SplitDirection::Horizontal => node.second.cols,
+                                        SplitDirection::Vertical => node.second.rows,
+                                    }),
+                                },
+                                SplitSource::Spawn {
+                                    command: None,
+                                    command_dir: cwd_of(second),
+                                },
+                                domain_name.clone(),
+                            )
+                            .await?;
+                        stack.push((new_pane, *right));
+                        stack.push((pane, *left));
+                    }
+                }
+            }
+            Ok(())
+        }
+        .await;
+
+        if let Err(err) = result {
+            // Don't leave a partially constructed tab behind
+            self.remove_tab(tab.tab_id());
+            return Err(err);
+        }
+
+        if let Some(pane) = new_active {
+            tab.set_active_pane(&pane);
+        }
+
+        // Put the new tab where the original was, and then close the original
+        if let Some(mut window) = self.get_window_mut(window_id) {
+            if let Some(src_idx) = window.idx_by_id(tab_id) {
+                if let Some(idx) = window.idx_by_id(tab.tab_id()) {
+                    let tab = window.remove_by_idx(idx);
+                    window.insert(src_idx, &tab);
+                    window.save_and_then_set_active(src_idx);
+                }
+            }
+        }
+        self.remove_tab(tab_id);
+
+        Ok((tab, window_id))
+    }
+
     pub async fn spawn_tab_or_window(
         &self,
         window_id: Option<WindowId>,
@@ -1155,6 +2001,7 @@ impl Mux {
                 }
                 None => None,
             },
+            &domain,
         );
 
         let tab = domain
@@ -1186,6 +2033,132 @@ impl Mux {
 
         Ok((tab, pane, window_id))
     }
+
+    /// Create a new window in the workspace described by `definition`,
+    /// populated with its tabs and panes.
+    /// Returns the first tab and pane that were created.
+    pub async fn spawn_workspace_from_definition(
+        &self,
+        definition: &WorkspaceDefinition,
+        size: TerminalSize,
+    ) -> anyhow::Result<(Rc<Tab>, Rc<dyn Pane>, WindowId)> {
+        fn command_dir(
+            definition: &WorkspaceDefinition,
+            spawn: &SpawnCommand,
+        ) -> anyhow::Result<Option<String>> {
+            match spawn.cwd.as_ref().or(definition.cwd.as_ref()) {
+                Some(cwd) => {
+                    Ok(Some(cwd.to_str().map(|s| s.to_owned()).ok_or_else(
+                        || anyhow!("cwd {} is not unicode", cwd.display()),
+                    )?))
+                }
+                None => Ok(None),
+            }
+        }
+
+        fn command_builder(
+            definition: &WorkspaceDefinition,
+            spawn: &SpawnCommand,
+        ) -> Option<CommandBuilder> {
+            if spawn.args.is_none()
+                && spawn.set_environment_variables.is_empty()
+                && definition.set_environment_variables.is_empty()
+            {
+                return None;
+            }
+            let mut builder = match &spawn.args {
+                Some(args) => CommandBuilder::from_argv(args.iter().map(Into::into).collect()),
+                None => CommandBuilder::new_default_prog(),
+            };
+            for (k, v) in definition
+                .set_environment_variables
+                .iter()
+                .chain(spawn.set_environment_variables.iter())
+            {
+                builder.env(k, v);
+            }
+            Some(builder)
+        }
+
+        let default_tab = [WorkspaceTab::default()];
+        let tabs = if definition.tabs.is_empty() {
+            &default_tab[..]
+        } else {
+            &definition.tabs[..]
+        };
+
+        let mut first = None;
+        let mut window_id = None;
+        for tab_def in tabs {
+            let domain = match &tab_def.command.domain {
+                SpawnTabDomain::DefaultDomain => definition.domain.clone(),
+                other => other.clone(),
+            };
+            let (tab, pane, new_window_id) = self
+                .spawn_tab_or_window(
+                    window_id,
+                    domain.clone(),
+                    command_builder(definition, &tab_def.command),
+                    command_dir(definition, &tab_def.command)?,
+                    size,
+                    None,
+                    definition.name.clone(),
+                )
+                .await?;
+            window_id.replace(new_window_id);
+
+            let mut pane_id = pane.pane_id();
+            for split in &tab_def.splits {
+                let request = SplitRequest {
+                    direction: match split.direction {
+                        PaneDirection::Down | PaneDirection::Up => SplitDirection::Vertical,
+                        PaneDirection::Left | PaneDirection::Right => SplitDirection::Horizontal,
+                        PaneDirection::Next | PaneDirection::Prev => {
+                            anyhow::bail!("Invalid direction {:?} for split", split.direction)
+                        }
+                    },
+                    target_is_second: matches!(
+                        split.direction,
+                        PaneDirection::Down | PaneDirection::Right
+                    ),
+                    size: match split.size {
+                        ConfigSplitSize::Percent(n) => SplitSize::Percent(n),
+                        ConfigSplitSize::Cells(n) => SplitSize::Cells(n),
+                    },
+                    top_level: split.top_level,
+                };
+                let split_domain = match &split.command.domain {
+                    SpawnTabDomain::DefaultDomain => domain.clone(),
+                    other => other.clone(),
+                };
+                let (split_pane, _size) = self
+                    .split_pane(
+                        pane_id,
+                        request,
+                        SplitSource::Spawn {
+                            command: command_builder(definition, &split.command),
+                            command_dir: command_dir(definition, &split.command)?,
+                        },
+                        split_domain,
+                    )
+                    .await?;
+                pane_id = split_pane.pane_id();
+            }
+
+            if first.is_none() {
+                first.replace((tab, pane));
+            }
+        }
+
+        let (tab, pane) = first.ok_or_else(|| anyhow!("workspace has no tabs"))?;
+        let window_id = window_id.ok_or_else(|| anyhow!("workspace has no window"))?;
+        if let Some(mut window) = self.get_window_mut(window_id) {
+            if let Some(idx) = window.idx_by_id(tab.tab_id()) {
+                window.set_active_without_saving(idx);
+            }
+        }
+        Ok((tab, pane, window_id))
+    }
 }
 
 pub struct IdentityHolder {
@@ -1239,6 +2212,16 @@ impl Clipboard for MuxClipboard {
         });
         Ok(())
     }
+
+    fn query_contents(&self, selection: ClipboardSelection) -> anyhow::Result<()> {
+        let mux =
+            Mux::get().ok_or_else(|| anyhow::anyhow!("MuxClipboard::query_contents: no Mux?"))?;
+        mux.notify(MuxNotification::QueryClipboard {
+            pane_id: self.pane_id,
+            selection,
+        });
+        Ok(())
+    }
 }
 
 struct MuxDownloader {}
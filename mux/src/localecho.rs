@@ -0,0 +1,397 @@
+//! Predictive local echo for panes whose terminal model is local,
+//! but whose process runs on the far side of a network connection,
+//! such as those in ssh domains.
+//!
+//! When the link is slow, typed characters are drawn at the cursor
+//! straight away, marked with an underline, and the cursor is advanced
+//! past them.  The predictions are never written into the terminal
+//! model; they are overlaid onto copies of the lines as they are
+//! rendered.  A prediction is dropped once the remote output confirms
+//! it, and all outstanding predictions are discarded if the remote
+//! output contradicts them or fails to confirm them in time.
+//!
+//! As in mosh, predictions belong to an epoch, and a new epoch begins
+//! whenever we become unsure of how the remote end treats our input,
+//! such as after a misprediction or a key that we don't predict.
+//! The predictions of an epoch are tentative, and are not shown,
+//! until the remote end has echoed one of them.  That way we don't
+//! flash the input of, for example, a password prompt that we failed
+//! to recognize.
+use config::{configuration, LocalEchoPrediction};
+use std::time::{Duration, Instant};
+use termwiz::cell::{Cell, CellAttributes};
+use termwiz::surface::{Line, SequenceNo};
+use wezterm_term::{KeyCode, KeyModifiers, StableRowIndex, Terminal};
+
+#[derive(Debug)]
+struct Prediction {
+    row: StableRowIndex,
+    x: usize,
+    c: char,
+    width: usize,
+    when: Instant,
+    epoch: u64,
+}
+
+#[derive(Debug)]
+pub struct LocalEchoPredictor {
+    threshold: Option<Duration>,
+    predictions: Vec<Prediction>,
+    /// Smoothed time between sending input and receiving output
+    srtt: Option<Duration>,
+    /// When we sent the oldest input that has not yet
+    /// been followed by some output
+    awaiting_since: Option<Instant>,
+    /// The epoch of new predictions
+    epoch: u64,
+    /// The most recent epoch in which a prediction was confirmed;
+    /// the predictions of this and earlier epochs are shown
+    confirmed_epoch: Option<u64>,
+}
+
+impl LocalEchoPredictor {
+    pub fn new(threshold_ms: Option<u64>) -> Self {
+        Self {
+            threshold: threshold_ms.map(Duration::from_millis),
+            predictions: vec![],
+            srtt: None,
+            awaiting_since: None,
+            epoch: 0,
+            confirmed_epoch: None,
+        }
+    }
+
+    fn should_predict(&self) -> bool {
+        match configuration().local_echo_prediction {
+            LocalEchoPrediction::Never => false,
+            LocalEchoPrediction::Always => true,
+            LocalEchoPrediction::Adaptive => match (self.threshold, self.srtt) {
+                (Some(threshold), Some(srtt)) => srtt >= threshold,
+                _ => false,
+            },
+        }
+    }
+
+    fn timeout() -> Duration {
+        Duration::from_millis(configuration().local_echo_prediction_timeout_ms)
+    }
+
+    /// Returns the predictions that have not yet expired and that
+    /// belong to a confirmed epoch
+    fn live(&self) -> impl Iterator<Item = &Prediction> {
+        let timeout = Self::timeout();
+        let confirmed_epoch = self.confirmed_epoch;
+        self.predictions.iter().filter(move |p| {
+            let confirmed = match confirmed_epoch {
+                Some(epoch) => p.epoch <= epoch,
+                None => false,
+            };
+            confirmed && p.when.elapsed() < timeout
+        })
+    }
+
+    /// Discards the outstanding predictions and begins a new epoch.
+    /// Returns true if there were any predictions.
+    fn restart(&mut self) -> bool {
+        let had_predictions = !self.predictions.is_empty();
+        self.predictions.clear();
+        self.epoch += 1;
+        had_predictions
+    }
+
+    pub fn has_predictions(&self) -> bool {
+        self.live().next().is_some()
+    }
+
+    /// Where the cursor will be once the remote end has echoed
+    /// the predicted text
+    fn predicted_cursor(&self, term: &Terminal) -> (StableRowIndex, usize) {
+        match self.predictions.last() {
+            Some(p) => (p.row, p.x + p.width),
+            None => {
+                let pos = term.cursor_pos();
+                (term.screen().visible_row_to_stable_row(pos.y), pos.x)
+            }
+        }
+    }
+
+    /// Called as a key press is sent to the remote end.
+    /// Returns true if the set of predictions changed.
+    pub fn key_down(&mut self, key: KeyCode, mods: KeyModifiers, term: &Terminal) -> bool {
+        if self.awaiting_since.is_none() {
+            self.awaiting_since.replace(Instant::now());
+        }
+
+        let timeout = Self::timeout();
+        self.predictions.retain(|p| p.when.elapsed() < timeout);
+
+        if !self.should_predict() || !safe_to_predict(term) {
+            return self.restart();
+        }
+        if mods != KeyModifiers::NONE && mods != KeyModifiers::SHIFT {
+            return self.restart();
+        }
+
+        match key {
+            KeyCode::Char(c) if !c.is_control() => {
+                let (row, x) = self.predicted_cursor(term);
+                let width = Cell::new(c, CellAttributes::default()).width();
+                // We don't try to predict how the line will wrap
+                if x + width > term.get_size().cols {
+                    return self.restart();
+                }
+                self.predictions.push(Prediction {
+                    row,
+                    x,
+                    c,
+                    width,
+                    when: Instant::now(),
+                    epoch: self.epoch,
+                });
+                true
+            }
+            // We can only be sure of the effect of backspace when it
+            // erases text that we predicted ourselves
+            KeyCode::Backspace => self.predictions.pop().is_some(),
+            _ => self.restart(),
+        }
+    }
+
+    /// Called when input is sent that we don't attempt to predict,
+    /// such as a paste
+    pub fn reset(&mut self) -> bool {
+        self.restart()
+    }
+
+    /// Called after output from the remote end has been applied to the
+    /// terminal, to confirm or discard the outstanding predictions.
+    /// Returns the time since the input that preceded the output was
+    /// sent, if this is the first output since then.
+    pub fn output_received(&mut self, term: &Terminal) -> Option<Duration> {
+        let now = Instant::now();
+        let sample = self
+            .awaiting_since
+            .take()
+            .map(|since| now.saturating_duration_since(since));
+        if let Some(sample) = sample {
+            self.srtt = Some(match self.srtt {
+                Some(srtt) => (srtt * 7 + sample) / 8,
+                None => sample,
+            });
+        }
+
+        // Predictions are confirmed in the order that they were made
+        while let Some(p) = self.predictions.first() {
+            if cell_text(term, p.row, p.x) == Some(p.c.to_string()) {
+                self.confirmed_epoch = Some(p.epoch);
+                self.predictions.remove(0);
+            } else {
+                break;
+            }
+        }
+
+        let contradicted = match self.predictions.first() {
+            Some(p) => {
+                let cursor_row = term.screen().visible_row_to_stable_row(term.cursor_pos().y);
+                let occupied = cell_text(term, p.row, p.x)
+                    .map(|text| text != " ")
+                    .unwrap_or(true);
+                // Something other than our prediction was written where
+                // we expected the echo, or the remote end moved the cursor
+                // to a different line
+                occupied || cursor_row != p.row || p.when.elapsed() >= Self::timeout()
+            }
+            None => false,
+        };
+        if contradicted {
+            self.restart();
+        }
+        sample
+    }
+
+    /// Returns true if `row` has predictions that need to be overlaid
+    pub fn affects_row(&self, row: StableRowIndex) -> bool {
+        self.live().any(|p| p.row == row)
+    }
+
+    /// Overlay the predictions for `row` onto `line`
+    pub fn apply(&self, row: StableRowIndex, line: &mut Line, seqno: SequenceNo) {
+        let attrs = CellAttributes::default()
+            .set_underline(configuration().local_echo_prediction_underline)
+            .clone();
+        for p in self.live().filter(|p| p.row == row) {
+            line.set_cell(p.x, Cell::new(p.c, attrs.clone()), seqno);
+        }
+    }
+
+    /// Returns the predicted cursor column for `row`, if any
+    pub fn cursor_x(&self, row: StableRowIndex) -> Option<usize> {
+        self.live()
+            .filter(|p| p.row == row)
+            .last()
+            .map(|p| p.x + p.width)
+    }
+}
+
+/// Returns the text of the cell at the specified location
+fn cell_text(term: &Terminal, row: StableRowIndex, x: usize) -> Option<String> {
+    let screen = term.screen();
+    let phys = screen.stable_row_to_phys(row)?;
+    let line = screen.lines_in_phys_range(phys..phys + 1).pop()?;
+    let text = line.get_cell(x).map(|cell| cell.str().to_string());
+    Some(text.unwrap_or_else(|| " ".to_string()))
+}
+
+/// Full screen applications typically use keys as commands rather
+/// than echoing them, and we must not reveal passwords, so we don't
+/// predict in those situations.
+fn safe_to_predict(term: &Terminal) -> bool {
+    if term.is_alt_screen_active() {
+        return false;
+    }
+    let pos = term.cursor_pos();
+    let row = term.screen().visible_row_to_stable_row(pos.y);
+    let screen = term.screen();
+    match screen.stable_row_to_phys(row) {
+        Some(phys) => !screen
+            .lines_in_phys_range(phys..phys + 1)
+            .iter()
+            .any(|line| line.as_str().to_lowercase().contains("password")),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use wezterm_term::TerminalSize;
+
+    fn new_term() -> Terminal {
+        Terminal::new(
+            TerminalSize {
+                rows: 5,
+                cols: 20,
+                pixel_width: 160,
+                pixel_height: 80,
+                dpi: 0,
+            },
+            Arc::new(config::TermConfig::new()),
+            "WezTerm",
+            "test",
+            Box::new(Vec::new()),
+        )
+    }
+
+    /// A predictor that has observed enough latency to start predicting
+    fn new_predictor() -> LocalEchoPredictor {
+        let mut predictor = LocalEchoPredictor::new(Some(50));
+        predictor.srtt.replace(Duration::from_millis(100));
+        predictor
+    }
+
+    fn type_text(predictor: &mut LocalEchoPredictor, term: &Terminal, text: &str) {
+        for c in text.chars() {
+            assert!(predictor.key_down(KeyCode::Char(c), KeyModifiers::NONE, term));
+        }
+    }
+
+    fn remote_output(predictor: &mut LocalEchoPredictor, term: &mut Terminal, text: &str) {
+        term.advance_bytes(text);
+        predictor.output_received(term);
+    }
+
+    #[test]
+    fn confirmed() {
+        let mut term = new_term();
+        let mut predictor = new_predictor();
+        term.advance_bytes("$ ");
+
+        type_text(&mut predictor, &term, "ls");
+        // Nothing is shown until the remote end has echoed a prediction
+        assert!(!predictor.affects_row(0));
+        assert_eq!(predictor.cursor_x(0), None);
+
+        // The echo arrives one character at a time; the first
+        // confirms the epoch, so the rest of it is shown
+        remote_output(&mut predictor, &mut term, "l");
+        assert_eq!(predictor.predictions.len(), 1);
+        assert_eq!(predictor.cursor_x(0), Some(4));
+
+        let mut line = Line::from_text("$ l", &CellAttributes::default(), 0, None);
+        predictor.apply(0, &mut line, 1);
+        assert_eq!(line.as_str().trim_end(), "$ ls");
+
+        // Later input in the same epoch is shown straight away
+        type_text(&mut predictor, &term, " -l");
+        assert_eq!(predictor.cursor_x(0), Some(7));
+
+        remote_output(&mut predictor, &mut term, "s -l");
+        assert!(!predictor.has_predictions());
+        assert_eq!(predictor.cursor_x(0), None);
+    }
+
+    #[test]
+    fn new_epoch_is_tentative() {
+        let mut term = new_term();
+        let mut predictor = new_predictor();
+        term.advance_bytes("$ ");
+
+        type_text(&mut predictor, &term, "a");
+        remote_output(&mut predictor, &mut term, "a");
+
+        // We can't predict what Enter leads to, such as a password
+        // prompt, so the following input is tentative again
+        assert!(!predictor.key_down(KeyCode::Enter, KeyModifiers::NONE, &term));
+        remote_output(&mut predictor, &mut term, "\r\nPassphrase: ");
+        type_text(&mut predictor, &term, "secret");
+        assert!(!predictor.affects_row(1));
+        assert_eq!(predictor.cursor_x(1), None);
+    }
+
+    #[test]
+    fn mispredicted() {
+        let mut term = new_term();
+        let mut predictor = new_predictor();
+        term.advance_bytes("$ ");
+
+        type_text(&mut predictor, &term, "ab");
+        // The remote end echoed something else in place of our text
+        remote_output(&mut predictor, &mut term, "x");
+        assert!(!predictor.has_predictions());
+        assert!(!predictor.affects_row(0));
+        assert_eq!(predictor.cursor_x(0), None);
+    }
+
+    #[test]
+    fn rollback_after_cursor_jump() {
+        let mut term = new_term();
+        let mut predictor = new_predictor();
+        term.advance_bytes("$ ");
+
+        type_text(&mut predictor, &term, "ab");
+        // The first character is echoed, then the remote end moves
+        // the cursor to a different line without echoing the second
+        remote_output(&mut predictor, &mut term, "a\r\n");
+        assert!(!predictor.has_predictions());
+        assert_eq!(predictor.cursor_x(0), None);
+        assert_eq!(predictor.cursor_x(1), None);
+    }
+
+    #[test]
+    fn backspace_only_erases_predictions() {
+        let mut term = new_term();
+        let mut predictor = new_predictor();
+        term.advance_bytes("$ ");
+        type_text(&mut predictor, &term, "a");
+        remote_output(&mut predictor, &mut term, "a");
+
+        type_text(&mut predictor, &term, "bc");
+        assert_eq!(predictor.cursor_x(0), Some(5));
+        assert!(predictor.key_down(KeyCode::Backspace, KeyModifiers::NONE, &term));
+        assert_eq!(predictor.cursor_x(0), Some(4));
+        assert!(predictor.key_down(KeyCode::Backspace, KeyModifiers::NONE, &term));
+        assert!(!predictor.key_down(KeyCode::Backspace, KeyModifiers::NONE, &term));
+        assert!(!predictor.has_predictions());
+    }
+}
@@ -1,21 +1,24 @@
-use crate::domain::DomainId;
+use crate::domain::{DomainId, LocalDomain, WriterWrapper};
+use crate::inferredtitle::expand_title_template;
+use crate::localecho::LocalEchoPredictor;
 use crate::pane::{
     CloseReason, ForEachPaneLogicalLine, LogicalLine, Pane, PaneId, Pattern, SearchResult,
     WithPaneLines,
 };
 use crate::renderable::*;
+use crate::resourcegroup::ResourceGroup;
 use crate::tmux::{TmuxDomain, TmuxDomainState};
 use crate::{Domain, Mux, MuxNotification};
 use anyhow::Error;
 use async_trait::async_trait;
 use config::keyassignment::ScrollbackEraseMode;
-use config::{configuration, ExitBehavior};
-use portable_pty::{Child, ChildKiller, ExitStatus, MasterPty, PtySize};
+use config::{configuration, ExitBehavior, ResourceLimits, RestartCondition, RestartOnExit};
+use portable_pty::{Child, ChildKiller, CommandBuilder, ExitStatus, MasterPty, PtySize};
 use procinfo::LocalProcessInfo;
 use rangeset::RangeSet;
 use smol::channel::{bounded, Receiver, TryRecvError};
 use std::borrow::Cow;
-use std::cell::{RefCell, RefMut};
+use std::cell::{Cell, RefCell, RefMut};
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryInto;
 use std::io::{Result as IoResult, Write};
@@ -27,11 +30,13 @@ use termwiz::escape::{Action, DeviceControlMode};
 use termwiz::input::KeyboardEncoding;
 use termwiz::surface::{Line, SequenceNo};
 use url::Url;
+use wezterm_bidi::ParagraphDirectionHint;
 use wezterm_dynamic::Value;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{
-    Alert, AlertHandler, Clipboard, DownloadHandler, KeyCode, KeyModifiers, MouseEvent,
-    SemanticZone, StableRowIndex, Terminal, TerminalConfiguration, TerminalSize,
+    Alert, AlertHandler, Clipboard, DownloadHandler, EscapeSequencePermissions, KeyCode,
+    KeyModifiers, MouseEvent, SemanticZone, StableRowIndex, Terminal, TerminalConfiguration,
+    TerminalSize,
 };
 
 #[derive(Debug)]
@@ -65,6 +70,20 @@ pub struct LocalPane {
     tmux_domain: RefCell<Option<Arc<TmuxDomainState>>>,
     proc_list: RefCell<Option<CachedProcInfo>>,
     command_description: String,
+    resource_group: RefCell<Option<ResourceGroup>>,
+    restart: RefCell<Option<RestartCommand>>,
+    restart_on_exit: Cell<Option<RestartOnExit>>,
+    restarted: Cell<bool>,
+    /// The most recently inferred title, and when it was inferred
+    inferred_title: RefCell<Option<(Instant, String)>>,
+    /// Set for panes whose process is remote, such as in ssh domains
+    local_echo: RefCell<Option<LocalEchoPredictor>>,
+}
+
+/// What we need in order to re-run the command of the pane in-place
+struct RestartCommand {
+    command: CommandBuilder,
+    writer: WriterWrapper,
 }
 
 #[async_trait(?Send)]
@@ -76,15 +95,7 @@ impl Pane for LocalPane {
     fn get_metadata(&self) -> Value {
         let mut map: BTreeMap<Value, Value> = BTreeMap::new();
 
-        #[cfg(unix)]
-        if let Some(tio) = self.pty.borrow().get_termios() {
-            use nix::sys::termios::LocalFlags;
-            // Detect whether we might be in password input mode.
-            // If local echo is disabled and canonical input mode
-            // is enabled, then we assume that we're in some kind
-            // of password-entry mode.
-            let pw_input = !tio.local_flags.contains(LocalFlags::ECHO)
-                && tio.local_flags.contains(LocalFlags::ICANON);
+        if let Some(pw_input) = self.password_input() {
             map.insert(
                 Value::String("password_input".to_string()),
                 Value::Bool(pw_input),
@@ -99,6 +110,14 @@ impl Pane for LocalPane {
         if self.tmux_domain.borrow().is_some() {
             cursor.visibility = termwiz::surface::CursorVisibility::Hidden;
         }
+        if let Some(x) = self
+            .local_echo
+            .borrow()
+            .as_ref()
+            .and_then(|predictor| predictor.cursor_x(cursor.y))
+        {
+            cursor.x = x;
+        }
         cursor
     }
 
@@ -131,7 +150,22 @@ impl Pane for LocalPane {
     }
 
     fn with_lines_mut(&self, lines: Range<StableRowIndex>, with_lines: &mut dyn WithPaneLines) {
-        terminal_with_lines_mut(&mut self.terminal.borrow_mut(), lines, with_lines)
+        let local_echo = self.local_echo.borrow();
+        match local_echo.as_ref() {
+            Some(predictor) if predictor.has_predictions() => {
+                let mut terminal = self.terminal.borrow_mut();
+                // Use a seqno that the renderer hasn't seen for the
+                // lines that we overlay, so that they are redrawn
+                let seqno = terminal.current_seqno() + 1;
+                let mut overlay = PredictionOverlay {
+                    predictor,
+                    seqno,
+                    inner: with_lines,
+                };
+                terminal_with_lines_mut(&mut terminal, lines, &mut overlay)
+            }
+            _ => terminal_with_lines_mut(&mut self.terminal.borrow_mut(), lines, with_lines),
+        }
     }
 
     fn get_lines(&self, lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>) {
@@ -150,6 +184,13 @@ impl Pane for LocalPane {
         self.terminal.borrow().user_vars().clone()
     }
 
+    fn get_badge(&self) -> Option<String> {
+        self.terminal
+            .borrow()
+            .badge()
+            .map(|badge| badge.to_string())
+    }
+
     fn kill(&self) {
         let mut proc = self.process.borrow_mut();
         log::debug!(
@@ -157,6 +198,9 @@ impl Pane for LocalPane {
             self.pane_id,
             proc
         );
+        // Dropping the group takes care of any descendants that
+        // have outlived the shell
+        self.resource_group.borrow_mut().take();
         match &mut *proc {
             ProcessState::Running {
                 signaller, killed, ..
@@ -174,6 +218,7 @@ impl Pane for LocalPane {
     fn is_dead(&self) -> bool {
         let mut proc = self.process.borrow_mut();
         let mut notify = None;
+        let mut inspect_status = None;
 
         const EXIT_BEHAVIOR: &str = "This message is shown because \
             \x1b]8;;https://wezfurlong.org/wezterm/\
@@ -200,35 +245,62 @@ impl Pane for LocalPane {
                             .contains(&status.exit_code()),
                     };
 
-                    match (configuration().exit_behavior, success, killed) {
-                        (ExitBehavior::Close, _, _) => *proc = ProcessState::Dead,
-                        (ExitBehavior::CloseOnCleanExit, false, false) => {
-                            notify = Some(format!(
-                                "\r\n⚠️  Process {} didn't exit cleanly\r\n{}.\r\n{}=\"CloseOnCleanExit\"\r\n",
-                                self.command_description,
-                                status,
-                                EXIT_BEHAVIOR
-                            ));
-                            *proc = ProcessState::DeadPendingClose { killed: false }
-                        }
-                        (ExitBehavior::CloseOnCleanExit, ..) => *proc = ProcessState::Dead,
-                        (ExitBehavior::Hold, success, false) => {
-                            if success {
-                                notify = Some(format!(
-                                    "\r\n👍 Process {} completed.\r\n{}=\"Hold\"\r\n",
-                                    self.command_description, EXIT_BEHAVIOR
-                                ));
-                            } else {
+                    let restart = match self.restart_on_exit.get() {
+                        Some(restart) if !*killed => match (restart.when, success) {
+                            (RestartCondition::Always, _)
+                            | (RestartCondition::OnFailure, false) => Some(restart),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+
+                    let inspect = !success && !*killed && configuration().dead_pane_inspector;
+
+                    if let Some(restart) = restart {
+                        notify = Some(format!(
+                            "\r\n🔁 Process {} exited ({}); restarting in {}ms\r\n",
+                            self.command_description, status, restart.delay_ms
+                        ));
+                        *proc = ProcessState::DeadPendingClose { killed: false };
+                        schedule_restart(self.pane_id, Duration::from_millis(restart.delay_ms));
+                    } else if inspect {
+                        notify = Some(format!(
+                            "\r\n⚠️  Process {} didn't exit cleanly\r\n{}.\r\n",
+                            self.command_description, status
+                        ));
+                        *proc = ProcessState::DeadPendingClose { killed: false };
+                        inspect_status = Some(status.to_string());
+                    } else {
+                        match (configuration().exit_behavior, success, killed) {
+                            (ExitBehavior::Close, _, _) => *proc = ProcessState::Dead,
+                            (ExitBehavior::CloseOnCleanExit, false, false) => {
                                 notify = Some(format!(
-                                    "\r\n⚠️  Process {} didn't exit cleanly\r\n{}.\r\n{}=\"Hold\"\r\n",
+                                    "\r\n⚠️  Process {} didn't exit cleanly\r\n{}.\r\n{}=\"CloseOnCleanExit\"\r\n",
                                     self.command_description,
                                     status,
                                     EXIT_BEHAVIOR
                                 ));
+                                *proc = ProcessState::DeadPendingClose { killed: false }
+                            }
+                            (ExitBehavior::CloseOnCleanExit, ..) => *proc = ProcessState::Dead,
+                            (ExitBehavior::Hold, success, false) => {
+                                if success {
+                                    notify = Some(format!(
+                                        "\r\n👍 Process {} completed.\r\n{}=\"Hold\"\r\n",
+                                        self.command_description, EXIT_BEHAVIOR
+                                    ));
+                                } else {
+                                    notify = Some(format!(
+                                        "\r\n⚠️  Process {} didn't exit cleanly\r\n{}.\r\n{}=\"Hold\"\r\n",
+                                        self.command_description,
+                                        status,
+                                        EXIT_BEHAVIOR
+                                    ));
+                                }
+                                *proc = ProcessState::DeadPendingClose { killed: false }
                             }
-                            *proc = ProcessState::DeadPendingClose { killed: false }
+                            (ExitBehavior::Hold, _, true) => *proc = ProcessState::Dead,
                         }
-                        (ExitBehavior::Hold, _, true) => *proc = ProcessState::Dead,
                     }
                     log::debug!("child terminated, new state is {:?}", proc);
                 }
@@ -245,6 +317,17 @@ impl Pane for LocalPane {
         if let Some(notify) = notify {
             emit_output_for_pane(self.pane_id, &notify);
         }
+        if let Some(status) = inspect_status {
+            // Deferred so that the banner is output first, and because
+            // we may have been called while the mux is busy
+            let pane_id = self.pane_id;
+            promise::spawn::spawn_into_main_thread(async move {
+                if let Some(mux) = Mux::get() {
+                    mux.notify(MuxNotification::InspectDeadPane { pane_id, status });
+                }
+            })
+            .detach();
+        }
 
         match &*proc {
             ProcessState::Running { .. } => false,
@@ -261,6 +344,12 @@ impl Pane for LocalPane {
         self.terminal.borrow_mut().set_download_handler(handler);
     }
 
+    fn set_escape_sequence_permissions(&self, permissions: &Arc<dyn EscapeSequencePermissions>) {
+        self.terminal
+            .borrow_mut()
+            .set_escape_sequence_permissions(permissions);
+    }
+
     fn set_config(&self, config: Arc<dyn TerminalConfiguration>) {
         self.terminal.borrow_mut().set_config(config);
     }
@@ -269,8 +358,35 @@ impl Pane for LocalPane {
         Some(self.terminal.borrow().get_config())
     }
 
+    fn set_bidi_mode(&self, enabled: Option<bool>, direction: Option<ParagraphDirectionHint>) {
+        self.terminal
+            .borrow_mut()
+            .set_bidi_mode_override(enabled, direction);
+        if let Some(mux) = Mux::get() {
+            mux.notify(MuxNotification::PaneOutput(self.pane_id));
+        }
+    }
+
+    fn get_ambiguous_width_is_wide(&self) -> Option<bool> {
+        Some(self.terminal.borrow().ambiguous_width_is_wide())
+    }
+
+    fn set_ambiguous_width_is_wide(&self, wide: bool) -> anyhow::Result<()> {
+        self.terminal.borrow_mut().set_ambiguous_width_is_wide(wide);
+        if let Some(mux) = Mux::get() {
+            mux.notify(MuxNotification::PaneOutput(self.pane_id));
+        }
+        Ok(())
+    }
+
     fn perform_actions(&self, actions: Vec<termwiz::escape::Action>) {
-        self.terminal.borrow_mut().perform_actions(actions)
+        let mut terminal = self.terminal.borrow_mut();
+        terminal.perform_actions(actions);
+        if let Some(predictor) = self.local_echo.borrow_mut().as_mut() {
+            if let Some(rtt) = predictor.output_received(&terminal) {
+                crate::domainstats::record_rtt(self.domain_id, rtt);
+            }
+        }
     }
 
     fn mouse_event(&self, event: MouseEvent) -> Result<(), Error> {
@@ -286,9 +402,19 @@ impl Pane for LocalPane {
                 self.terminal.borrow_mut().send_paste("detach\n")?;
             }
             return Ok(());
-        } else {
-            self.terminal.borrow_mut().key_down(key, mods)
         }
+
+        self.terminal.borrow_mut().key_down(key, mods)?;
+        let changed = match self.local_echo.borrow_mut().as_mut() {
+            // Never predict what is typed at a password prompt
+            Some(predictor) if self.password_input() == Some(true) => predictor.reset(),
+            Some(predictor) => predictor.key_down(key, mods, &self.terminal.borrow()),
+            None => false,
+        };
+        if changed {
+            self.local_echo_changed();
+        }
+        Ok(())
     }
 
     fn key_up(&self, key: KeyCode, mods: KeyModifiers) -> Result<(), Error> {
@@ -321,20 +447,36 @@ impl Pane for LocalPane {
         if self.tmux_domain.borrow().is_some() {
             Ok(())
         } else {
+            self.reset_local_echo();
             self.terminal.borrow_mut().send_paste(text)
         }
     }
 
+    fn send_paste_chunk(
+        &self,
+        text: &str,
+        bracketed: Option<bool>,
+        first: bool,
+        last: bool,
+    ) -> Result<bool, Error> {
+        Mux::get().unwrap().record_input_for_current_identity();
+        if self.tmux_domain.borrow().is_some() {
+            Ok(false)
+        } else {
+            self.reset_local_echo();
+            self.terminal
+                .borrow_mut()
+                .send_paste_chunk(text, bracketed, first, last)
+        }
+    }
+
     fn get_title(&self) -> String {
         let title = self.terminal.borrow_mut().get_title().to_string();
         // If the title is the default pane title, then try to spice
-        // things up a bit by returning the process basename instead
+        // things up a bit by inferring one from the foreground process
         if title == "wezterm" {
-            if let Some(proc_name) = self.get_foreground_process_name() {
-                let proc_name = std::path::Path::new(&proc_name);
-                if let Some(name) = proc_name.file_name() {
-                    return name.to_string_lossy().to_string();
-                }
+            if let Some(title) = self.get_inferred_title() {
+                return title;
             }
         }
 
@@ -384,6 +526,103 @@ impl Pane for LocalPane {
         }
     }
 
+    fn is_synchronized_output_active(&self) -> bool {
+        self.terminal.borrow().is_synchronized_output_active()
+    }
+
+    fn set_restart_on_exit(&self, restart: Option<RestartOnExit>) -> anyhow::Result<()> {
+        if self.restart.borrow().is_none() {
+            anyhow::bail!("pane {} cannot be restarted", self.pane_id);
+        }
+        self.restart_on_exit.set(restart);
+        Ok(())
+    }
+
+    fn can_restart(&self) -> bool {
+        self.restart.borrow().is_some()
+            && (self.restart_on_exit.get().is_some() || self.restarted.get())
+    }
+
+    fn restart(&self) -> anyhow::Result<()> {
+        let restart = self.restart.borrow();
+        let restart = restart
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("pane {} cannot be restarted", self.pane_id))?;
+        let mux = Mux::get().ok_or_else(|| anyhow::anyhow!("no mux"))?;
+        let domain = mux
+            .get_domain(self.domain_id)
+            .ok_or_else(|| anyhow::anyhow!("domain {} not found", self.domain_id))?;
+        let domain = domain
+            .downcast_ref::<LocalDomain>()
+            .ok_or_else(|| anyhow::anyhow!("domain {} is not a local domain", self.domain_id))?;
+
+        match &mut *self.process.borrow_mut() {
+            ProcessState::Running { signaller, .. } => {
+                let _ = signaller.kill();
+            }
+            ProcessState::DeadPendingClose { killed: false } => {}
+            _ => anyhow::bail!("pane {} is closing", self.pane_id),
+        }
+
+        let size = self.terminal.borrow().get_size();
+        let (child, master) = domain.respawn_command(size, restart.command.clone())?;
+        restart.writer.replace(master.take_writer()?);
+        let reader = master.try_clone_reader()?;
+
+        let (process, signaller, pid) = split_child(child);
+        if let (Some(group), Some(pid)) = (self.resource_group.borrow().as_ref(), pid) {
+            if let Err(err) = group.add_process(pid) {
+                log::error!(
+                    "Unable to apply resource limits to restarted pane {}: {:#}",
+                    self.pane_id,
+                    err
+                );
+            }
+        }
+        *self.process.borrow_mut() = ProcessState::Running {
+            child_waiter: process,
+            pid,
+            signaller,
+            killed: false,
+        };
+        *self.pty.borrow_mut() = master;
+        self.proc_list.borrow_mut().take();
+        self.restarted.set(true);
+
+        // Leave the scrollback intact, but undo any modes that the
+        // previous process left behind and separate its output from
+        // that of the new process
+        let cols = size.cols;
+        let separator = format!(
+            "\x1b[?1049l\x1b[!p\r\n\x1b[2m{}\x1b[0m\r\n",
+            "─".repeat(cols)
+        );
+        let mut parser = termwiz::escape::parser::Parser::new();
+        let mut actions = vec![];
+        parser.parse(separator.as_bytes(), |action| actions.push(action));
+        self.terminal.borrow_mut().perform_actions(actions);
+
+        let pane_id = self.pane_id;
+        std::thread::spawn(move || crate::read_from_pane_pty(pane_id, None, reader));
+        mux.notify(MuxNotification::PaneOutput(pane_id));
+        Ok(())
+    }
+
+    fn set_resource_limits(&self, limits: &ResourceLimits) -> anyhow::Result<()> {
+        let mut group = self.resource_group.borrow_mut();
+        if let Some(group) = group.as_mut() {
+            return group.set_limits(limits);
+        }
+        let pid = match &*self.process.borrow() {
+            ProcessState::Running { pid: Some(pid), .. } => *pid,
+            _ => anyhow::bail!("pane {} has no running process", self.pane_id),
+        };
+        let new_group = ResourceGroup::new(self.pane_id, limits)?;
+        new_group.add_process(pid)?;
+        group.replace(new_group);
+        Ok(())
+    }
+
     fn get_current_working_dir(&self) -> Option<Url> {
         self.terminal
             .borrow()
@@ -401,6 +640,31 @@ impl Pane for LocalPane {
         self.divine_foreground_process()
     }
 
+    fn get_inferred_title(&self) -> Option<String> {
+        if let Some((updated, title)) = &*self.inferred_title.borrow() {
+            if updated.elapsed() < Duration::from_millis(300) {
+                return Some(title.clone()).filter(|title| !title.is_empty());
+            }
+        }
+
+        let info = self.get_foreground_process_info()?;
+        let is_shell = matches!(
+            &*self.process.borrow(),
+            ProcessState::Running { pid: Some(pid), .. } if *pid == info.pid
+        );
+        let config = configuration();
+        let template = if is_shell {
+            &config.inferred_title_shell_template
+        } else {
+            &config.inferred_title_template
+        };
+        let title = expand_title_template(template, &info, &config::HOME_DIR);
+        self.inferred_title
+            .borrow_mut()
+            .replace((Instant::now(), title.clone()));
+        Some(title).filter(|title| !title.is_empty())
+    }
+
     fn get_foreground_process_name(&self) -> Option<String> {
         #[cfg(unix)]
         if let Some(pid) = self.pty.borrow().process_group_leader() {
@@ -671,6 +935,41 @@ impl Pane for LocalPane {
     }
 }
 
+/// Presents the lines of a pane with its local echo
+/// predictions overlaid on copies of the affected lines
+struct PredictionOverlay<'a> {
+    predictor: &'a LocalEchoPredictor,
+    seqno: SequenceNo,
+    inner: &'a mut dyn WithPaneLines,
+}
+
+impl<'a> WithPaneLines for PredictionOverlay<'a> {
+    fn with_lines_mut(&mut self, first_row: StableRowIndex, lines: &mut [&mut Line]) {
+        let mut overlaid: Vec<(usize, Line)> = vec![];
+        for (idx, line) in lines.iter().enumerate() {
+            let row = first_row + idx as StableRowIndex;
+            if self.predictor.affects_row(row) {
+                let mut line = (**line).clone();
+                self.predictor.apply(row, &mut line, self.seqno);
+                overlaid.push((idx, line));
+            }
+        }
+        if overlaid.is_empty() {
+            return self.inner.with_lines_mut(first_row, lines);
+        }
+
+        let mut overlaid = overlaid.iter_mut().peekable();
+        let mut refs: Vec<&mut Line> = Vec::with_capacity(lines.len());
+        for (idx, line) in lines.iter_mut().enumerate() {
+            match overlaid.next_if(|(overlay_idx, _)| *overlay_idx == idx) {
+                Some((_, overlay)) => refs.push(overlay),
+                None => refs.push(&mut **line),
+            }
+        }
+        self.inner.with_lines_mut(first_row, &mut refs);
+    }
+}
+
 struct LocalPaneDCSHandler {
     pane_id: PaneId,
     tmux_domain: Option<Arc<TmuxDomainState>>,
@@ -795,6 +1094,34 @@ impl AlertHandler for LocalPaneNotifHandler {
     }
 }
 
+/// Restarts the command of the pane after `delay`, unless the pane
+/// was closed or manually restarted in the meantime
+fn schedule_restart(pane_id: PaneId, delay: Duration) {
+    promise::spawn::spawn_into_main_thread(async move {
+        smol::Timer::after(delay).await;
+        let mux = match Mux::get() {
+            Some(mux) => mux,
+            None => return,
+        };
+        let pane = match mux.get_pane(pane_id) {
+            Some(pane) => pane,
+            None => return,
+        };
+        if let Some(local) = pane.downcast_ref::<LocalPane>() {
+            if local.is_holding() {
+                if let Err(err) = local.restart() {
+                    log::error!("Failed to restart pane {}: {:#}", pane_id, err);
+                    emit_output_for_pane(
+                        pane_id,
+                        &format!("\r\n⚠️  Failed to restart: {:#}\r\n", err),
+                    );
+                }
+            }
+        }
+    })
+    .detach();
+}
+
 /// This is a little gross; on some systems, our pipe reader will continue
 /// to be blocked in read even after the child process has died.
 /// We need to wake up and notice that the child terminated in order
@@ -861,7 +1188,85 @@ impl LocalPane {
             tmux_domain: RefCell::new(None),
             proc_list: RefCell::new(None),
             command_description,
+            resource_group: RefCell::new(None),
+            restart: RefCell::new(None),
+            restart_on_exit: Cell::new(None),
+            restarted: Cell::new(false),
+            inferred_title: RefCell::new(None),
+            local_echo: RefCell::new(None),
+        }
+    }
+
+    /// Returns true if the pty appears to be reading a password,
+    /// or None if we can't tell
+    fn password_input(&self) -> Option<bool> {
+        #[cfg(unix)]
+        if let Some(tio) = self.pty.borrow().get_termios() {
+            use nix::sys::termios::LocalFlags;
+            // If local echo is disabled and canonical input mode
+            // is enabled, then we assume that we're in some kind
+            // of password-entry mode.
+            return Some(
+                !tio.local_flags.contains(LocalFlags::ECHO)
+                    && tio.local_flags.contains(LocalFlags::ICANON),
+            );
+        }
+        None
+    }
+
+    /// Enables predictive local echo for this pane.  This is used
+    /// when the process in the pane is on the far side of a network
+    /// connection; see `local_echo_prediction`.
+    pub fn enable_local_echo_prediction(&self, threshold_ms: Option<u64>) {
+        self.local_echo
+            .borrow_mut()
+            .replace(LocalEchoPredictor::new(threshold_ms));
+    }
+
+    /// Arranges for the pane to be redrawn to reflect a change in
+    /// its predictions, and again once they would have expired
+    fn local_echo_changed(&self) {
+        let pane_id = self.pane_id;
+        if let Some(mux) = Mux::get() {
+            mux.notify(MuxNotification::PaneOutput(pane_id));
         }
+        let timeout = Duration::from_millis(configuration().local_echo_prediction_timeout_ms);
+        promise::spawn::spawn(async move {
+            smol::Timer::after(timeout).await;
+            if let Some(mux) = Mux::get() {
+                mux.notify(MuxNotification::PaneOutput(pane_id));
+            }
+        })
+        .detach();
+    }
+
+    fn reset_local_echo(&self) {
+        let changed = self
+            .local_echo
+            .borrow_mut()
+            .as_mut()
+            .map(|predictor| predictor.reset())
+            .unwrap_or(false);
+        if changed {
+            self.local_echo_changed();
+        }
+    }
+
+    /// Records the command that was spawned into the pane and the
+    /// writer shared with its terminal, so that it can be restarted
+    pub(crate) fn set_restart_command(&self, command: CommandBuilder, writer: WriterWrapper) {
+        self.restart
+            .borrow_mut()
+            .replace(RestartCommand { command, writer });
+    }
+
+    /// Returns true if the process has exited but the pane
+    /// hasn't been closed
+    fn is_holding(&self) -> bool {
+        matches!(
+            &*self.process.borrow(),
+            ProcessState::DeadPendingClose { killed: false }
+        )
     }
 
     fn divine_current_working_dir(&self) -> Option<Url> {
@@ -0,0 +1,287 @@
+//! Output watchers match the lines of output written to a pane
+//! against regular expressions, and emit a lua event with the
+//! captures of each match.
+//!
+//! Watchers come from the `output_watchers` configuration, which
+//! applies to every pane, and from `pane:add_output_watcher`, which
+//! applies to a single pane.  A line is only examined once the cursor
+//! has moved below it, so that each line is matched once it is
+//! complete rather than as it is being written.
+use crate::pane::{Pane, PaneId};
+use config::lua::mlua::{self, Lua};
+use config::{configuration, OutputWatcherRule};
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use wezterm_term::StableRowIndex;
+
+/// The most rows that are examined after a single batch of output.
+/// When more output than this arrives at once, only the most
+/// recent rows are examined.
+const MAX_ROWS_PER_CHECK: StableRowIndex = 10_000;
+
+/// Produces the lua representation of a pane, which is passed
+/// to the event handlers
+pub type PaneToLua = for<'lua> fn(&'lua Lua, PaneId) -> mlua::Result<mlua::Value<'lua>>;
+
+struct Watcher {
+    id: usize,
+    regex: Regex,
+    /// The name of the event that resolves to the callback
+    user_event_id: String,
+    /// The callback belongs to the lua context of this configuration
+    /// generation, and is discarded when the configuration is reloaded
+    generation: usize,
+}
+
+#[derive(Default)]
+struct PaneWatchers {
+    watchers: Vec<Watcher>,
+    /// The first row that has yet to be examined
+    next_row: Option<StableRowIndex>,
+}
+
+/// The compiled `output_watchers` from the configuration
+struct ConfigRules {
+    generation: usize,
+    rules: Vec<(Regex, String)>,
+}
+
+struct Match {
+    event: String,
+    captures: Vec<(Option<String>, Option<String>)>,
+}
+
+lazy_static::lazy_static! {
+    static ref WATCHERS: Mutex<HashMap<PaneId, PaneWatchers>> = Mutex::new(HashMap::new());
+    static ref CONFIG_RULES: Mutex<Option<ConfigRules>> = Mutex::new(None);
+    static ref PANE_TO_LUA: Mutex<Option<PaneToLua>> = Mutex::new(None);
+}
+static NEXT_WATCHER_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Called by the lua api to define how panes are passed to the
+/// event handlers
+pub fn set_pane_to_lua(func: PaneToLua) {
+    PANE_TO_LUA.lock().unwrap().replace(func);
+}
+
+/// Adds a watcher to the pane that emits `user_event_id` for each
+/// line of subsequent output that matches `regex`.
+/// Returns an id that can be passed to `remove_watcher`.
+pub fn add_watcher(pane: &dyn Pane, regex: &str, user_event_id: String) -> anyhow::Result<usize> {
+    let regex = Regex::new(regex)?;
+    let id = NEXT_WATCHER_ID.fetch_add(1, Ordering::Relaxed);
+    let mut watchers = WATCHERS.lock().unwrap();
+    let state = watchers.entry(pane.pane_id()).or_default();
+    state
+        .next_row
+        .get_or_insert_with(|| pane.get_cursor_position().y);
+    state.watchers.push(Watcher {
+        id,
+        regex,
+        user_event_id,
+        generation: configuration().generation(),
+    });
+    Ok(id)
+}
+
+/// Removes a watcher that was added by `add_watcher`.
+/// Returns false if there was no such watcher.
+pub fn remove_watcher(pane_id: PaneId, id: usize) -> bool {
+    let mut watchers = WATCHERS.lock().unwrap();
+    match watchers.get_mut(&pane_id) {
+        Some(state) => {
+            let before = state.watchers.len();
+            state.watchers.retain(|w| w.id != id);
+            state.watchers.len() != before
+        }
+        None => false,
+    }
+}
+
+pub(crate) fn remove_pane(pane_id: PaneId) {
+    WATCHERS.lock().unwrap().remove(&pane_id);
+}
+
+fn compile_rules(rules: &[OutputWatcherRule]) -> Vec<(Regex, String)> {
+    rules
+        .iter()
+        .filter_map(|rule| match Regex::new(&rule.regex) {
+            Ok(regex) => Some((regex, rule.event.clone())),
+            Err(err) => {
+                log::error!("output_watchers: invalid regex {}: {:#}", rule.regex, err);
+                None
+            }
+        })
+        .collect()
+}
+
+fn collect_matches(regex: &Regex, event: &str, text: &str, matches: &mut Vec<Match>) {
+    for captures in regex.captures_iter(text) {
+        matches.push(Match {
+            event: event.to_string(),
+            captures: regex
+                .capture_names()
+                .zip(captures.iter())
+                .map(|(name, value)| {
+                    (
+                        name.map(|name| name.to_string()),
+                        value.map(|value| value.as_str().to_string()),
+                    )
+                })
+                .collect(),
+        });
+    }
+}
+
+/// Called after output has been applied to the pane; examines the
+/// lines that have been completed since the prior call
+pub(crate) fn check_output(pane: &dyn Pane) {
+    let config = configuration();
+    let generation = config.generation();
+    let pane_id = pane.pane_id();
+    let cursor_row = pane.get_cursor_position().y;
+
+    let matches = {
+        let mut rules = CONFIG_RULES.lock().unwrap();
+        if rules.as_ref().map(|r| r.generation) != Some(generation) {
+            rules.replace(ConfigRules {
+                generation,
+                rules: compile_rules(&config.output_watchers),
+            });
+        }
+        let rules = &rules.as_ref().unwrap().rules;
+
+        let mut watchers = WATCHERS.lock().unwrap();
+        let state = watchers.entry(pane_id).or_default();
+        state.watchers.retain(|w| w.generation == generation);
+
+        if (state.watchers.is_empty() && rules.is_empty()) || pane.is_alt_screen_active() {
+            // Keep track of our position so that we don't match
+            // existing output when watchers are added later
+            state.next_row.replace(cursor_row);
+            return;
+        }
+
+        let dims = pane.get_dimensions();
+        let start = state
+            .next_row
+            .unwrap_or(dims.scrollback_top)
+            .max(dims.scrollback_top)
+            .max(cursor_row - MAX_ROWS_PER_CHECK);
+        if start >= cursor_row {
+            return;
+        }
+
+        let mut next_row = cursor_row;
+        let mut matches = vec![];
+        for line in pane.get_logical_lines(start..cursor_row) {
+            let end = line.first_row + line.physical_lines.len() as StableRowIndex;
+            if end > cursor_row {
+                // The cursor is still within this line; wait for it
+                // to be completed
+                next_row = next_row.min(line.first_row);
+                continue;
+            }
+            if line.first_row < start {
+                // The start of a wrapped line that was already examined
+                continue;
+            }
+            let text = line.logical.as_str();
+            let text = text.trim_end();
+            for watcher in &state.watchers {
+                collect_matches(&watcher.regex, &watcher.user_event_id, text, &mut matches);
+            }
+            for (regex, event) in rules {
+                collect_matches(regex, event, text, &mut matches);
+            }
+        }
+        state.next_row.replace(next_row);
+        matches
+    };
+
+    if !matches.is_empty() {
+        emit_matches(pane_id, matches);
+    }
+}
+
+fn emit_matches(pane_id: PaneId, matches: Vec<Match>) {
+    let pane_to_lua = *PANE_TO_LUA.lock().unwrap();
+    promise::spawn::spawn(async move {
+        config::with_lua_config_on_main_thread(move |lua| async move {
+            if let Some(lua) = lua {
+                for m in matches {
+                    let pane = match pane_to_lua {
+                        Some(func) => func(&lua, pane_id)?,
+                        None => mlua::Value::Integer(pane_id as mlua::Integer),
+                    };
+                    // captures[0] is the whole match, followed by the
+                    // numbered groups; named groups are also available
+                    // by their name
+                    let captures = lua.create_table()?;
+                    for (idx, (name, value)) in m.captures.into_iter().enumerate() {
+                        if let Some(value) = value {
+                            if let Some(name) = name {
+                                captures.set(name, value.clone())?;
+                            }
+                            captures.set(idx, value)?;
+                        }
+                    }
+                    let args = lua.pack_multi((pane, captures))?;
+                    config::lua::emit_event(&lua, (m.event, args)).await?;
+                }
+            }
+            Ok(())
+        })
+        .await
+    })
+    .detach();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn captures() {
+        let regex = Regex::new(r"(?P<scheme>https?)://(\S+)").unwrap();
+        let mut matches = vec![];
+        collect_matches(
+            &regex,
+            "url",
+            "Local: http://localhost:3000 Network: https://10.0.0.1:3000",
+            &mut matches,
+        );
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].event, "url");
+        assert_eq!(
+            matches[0].captures,
+            vec![
+                (None, Some("http://localhost:3000".to_string())),
+                (Some("scheme".to_string()), Some("http".to_string())),
+                (None, Some("localhost:3000".to_string())),
+            ]
+        );
+        assert_eq!(
+            matches[1].captures[0].1.as_deref(),
+            Some("https://10.0.0.1:3000")
+        );
+    }
+
+    #[test]
+    fn invalid_rules_are_skipped() {
+        let rules = compile_rules(&[
+            OutputWatcherRule {
+                regex: "(".to_string(),
+                event: "bad".to_string(),
+            },
+            OutputWatcherRule {
+                regex: "panic:".to_string(),
+                event: "panicked".to_string(),
+            },
+        ]);
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].1, "panicked");
+    }
+}
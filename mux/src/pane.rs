@@ -1,3 +1,4 @@
+use crate::collab::PaneCollaborators;
 use crate::domain::DomainId;
 use crate::renderable::*;
 use crate::Mux;
@@ -14,11 +15,12 @@ use termwiz::hyperlink::Rule;
 use termwiz::input::KeyboardEncoding;
 use termwiz::surface::{Line, SequenceNo};
 use url::Url;
+use wezterm_bidi::ParagraphDirectionHint;
 use wezterm_dynamic::Value;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{
-    Clipboard, DownloadHandler, KeyCode, KeyModifiers, MouseEvent, SemanticZone, StableRowIndex,
-    TerminalConfiguration, TerminalSize,
+    Clipboard, DownloadHandler, EscapeSequencePermissions, KeyCode, KeyModifiers, MouseEvent,
+    SemanticZone, StableRowIndex, TerminalConfiguration, TerminalSize,
 };
 
 static PANE_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
@@ -255,6 +257,29 @@ pub trait Pane: Downcast {
 
     fn get_title(&self) -> String;
     fn send_paste(&self, text: &str) -> anyhow::Result<()>;
+    /// Send a piece of a paste that spans several calls; see
+    /// `wezterm_term::Terminal::send_paste_chunk` for the meaning of
+    /// the parameters.  Returns whether the paste is bracketed.
+    fn send_paste_chunk(
+        &self,
+        text: &str,
+        bracketed: Option<bool>,
+        first: bool,
+        last: bool,
+    ) -> anyhow::Result<bool> {
+        use std::io::Write;
+        let bracketed = bracketed.unwrap_or(false);
+        let mut writer = self.writer();
+        if bracketed && first {
+            writer.write_all(b"\x1b[200~")?;
+        }
+        writer.write_all(text.as_bytes())?;
+        if bracketed && last {
+            writer.write_all(b"\x1b[201~")?;
+        }
+        writer.flush()?;
+        Ok(bracketed)
+    }
     fn reader(&self) -> anyhow::Result<Option<Box<dyn std::io::Read + Send>>>;
     fn writer(&self) -> RefMut<dyn std::io::Write>;
     fn resize(&self, size: TerminalSize) -> anyhow::Result<()>;
@@ -281,6 +306,12 @@ pub trait Pane: Downcast {
         HashMap::new()
     }
 
+    /// Returns the badge text that was set by the application
+    /// using the iTerm2 SetBadgeFormat escape sequence
+    fn get_badge(&self) -> Option<String> {
+        None
+    }
+
     fn erase_scrollback(&self, _erase_mode: ScrollbackEraseMode) {}
 
     /// Called to advise on whether this tab has focus
@@ -330,13 +361,83 @@ pub trait Pane: Downcast {
     fn is_mouse_grabbed(&self) -> bool;
     fn is_alt_screen_active(&self) -> bool;
 
+    /// Returns true if the application is part way through a
+    /// synchronized update, and rendering should be deferred
+    /// until it completes
+    fn is_synchronized_output_active(&self) -> bool {
+        false
+    }
+
+    /// Places the processes of the pane into a group that applies
+    /// the specified limits, or updates the limits of that group
+    /// if it already exists.  Only local panes support this.
+    fn set_resource_limits(&self, _limits: &config::ResourceLimits) -> anyhow::Result<()> {
+        anyhow::bail!("resource limits are not supported by this pane")
+    }
+
+    /// Sets whether the pane re-runs its command when it exits.
+    /// Only local panes support this.
+    fn set_restart_on_exit(&self, _restart: Option<config::RestartOnExit>) -> anyhow::Result<()> {
+        anyhow::bail!("restarting is not supported by this pane")
+    }
+
+    /// Re-runs the command of the pane in a new pty, killing the
+    /// current process if it is still running, while retaining
+    /// the scrollback
+    fn restart(&self) -> anyhow::Result<()> {
+        anyhow::bail!("restarting is not supported by this pane")
+    }
+
+    /// Returns true if the pane may re-run its command, in which
+    /// case EOF on its pty doesn't imply that the pane is finished
+    fn can_restart(&self) -> bool {
+        false
+    }
+
     fn set_clipboard(&self, _clipboard: &Arc<dyn Clipboard>) {}
     fn set_download_handler(&self, _handler: &Arc<dyn DownloadHandler>) {}
+    fn set_escape_sequence_permissions(&self, _permissions: &Arc<dyn EscapeSequencePermissions>) {}
     fn set_config(&self, _config: Arc<dyn TerminalConfiguration>) {}
     fn get_config(&self) -> Option<Arc<dyn TerminalConfiguration>> {
         None
     }
 
+    /// Overrides whether bidi is enabled and the paragraph direction
+    /// for this pane, applying them to its existing content.
+    /// `None` reverts to the configured value.
+    fn set_bidi_mode(&self, _enabled: Option<bool>, _direction: Option<ParagraphDirectionHint>) {}
+
+    /// Returns whether East Asian Ambiguous width characters are
+    /// treated as double width in this pane, or None if the pane
+    /// doesn't support changing it
+    fn get_ambiguous_width_is_wide(&self) -> Option<bool> {
+        None
+    }
+
+    /// Changes whether East Asian Ambiguous width characters are
+    /// treated as double width in this pane, laying out its existing
+    /// content again to match
+    fn set_ambiguous_width_is_wide(&self, _wide: bool) -> anyhow::Result<()> {
+        anyhow::bail!("changing the ambiguous width is not supported by this pane")
+    }
+
+    /// Returns the other clients that are attached to this pane and
+    /// who holds its input turn, or None if the pane isn't shared
+    /// through a mux server
+    fn get_collaborators(&self) -> Option<PaneCollaborators> {
+        None
+    }
+
+    /// Tells the other clients attached to this pane which cell we
+    /// are pointing at, or that we are no longer pointing at it
+    fn set_collaborator_cursor(&self, _cursor: Option<(usize, StableRowIndex)>) {}
+
+    /// Takes the input turn for this pane, so that only this client
+    /// can type into it, or gives it up if we already have it
+    fn toggle_input_turn(&self) -> anyhow::Result<()> {
+        anyhow::bail!("input turns are only supported by panes in a mux domain")
+    }
+
     fn get_current_working_dir(&self) -> Option<Url>;
     fn get_foreground_process_name(&self) -> Option<String> {
         None
@@ -345,6 +446,12 @@ pub trait Pane: Downcast {
         None
     }
 
+    /// Returns a title derived from the foreground process and its
+    /// working directory, for use when the application hasn't set one
+    fn get_inferred_title(&self) -> Option<String> {
+        None
+    }
+
     fn trickle_paste(&self, text: String) -> anyhow::Result<()> {
         if text.len() <= PASTE_CHUNK_SIZE {
             // Send it all now
@@ -0,0 +1,203 @@
+//! Pane logging writes the output of a pane to a file as it is read
+//! from the pty, to keep an audit trail of eg: ssh sessions.
+//!
+//! The log is written by the pty reader thread of the pane, so it
+//! doesn't add to the work done by the mux thread.  When a maximum
+//! size is set the log is rotated in the same way as logrotate does:
+//! `file` is renamed to `file.1`, `file.1` to `file.2` and so on,
+//! and the oldest file is removed.
+use crate::pane::PaneId;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use termwiz::escape::parser::Parser;
+
+/// Controls how the output of a pane is logged
+#[derive(Debug, Clone)]
+pub struct LogOptions {
+    /// When false, escape sequences and carriage returns are removed
+    /// so that only the text is logged
+    pub with_escapes: bool,
+    /// Rotate the log once it would exceed this many bytes
+    pub max_size: Option<u64>,
+    /// How many rotated logs to keep
+    pub max_files: usize,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        Self {
+            with_escapes: false,
+            max_size: None,
+            max_files: 5,
+        }
+    }
+}
+
+struct PaneLog {
+    path: PathBuf,
+    options: LogOptions,
+    file: File,
+    size: u64,
+    parser: Parser,
+}
+
+impl PaneLog {
+    fn open(path: PathBuf, options: LogOptions) -> anyhow::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            options,
+            file,
+            size,
+            parser: Parser::new(),
+        })
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        name.into()
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        if self.options.max_files > 0 {
+            std::fs::remove_file(self.rotated_path(self.options.max_files)).ok();
+            for n in (1..self.options.max_files).rev() {
+                let from = self.rotated_path(n);
+                if from.exists() {
+                    std::fs::rename(&from, self.rotated_path(n + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let text;
+        let data = if self.options.with_escapes {
+            data
+        } else {
+            text = crate::panepipe::text_only(&mut self.parser, data);
+            &text[..]
+        };
+        if data.is_empty() {
+            return Ok(());
+        }
+        if let Some(max_size) = self.options.max_size {
+            if self.size > 0 && self.size + data.len() as u64 > max_size {
+                self.rotate()?;
+            }
+        }
+        self.file.write_all(data)?;
+        self.size += data.len() as u64;
+        Ok(())
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref LOGS: Mutex<HashMap<PaneId, PaneLog>> = Mutex::new(HashMap::new());
+}
+
+/// Resolves the path at which to log the output of a pane.
+/// A leading `~` and relative paths refer to the home directory.
+/// When no path is given, a file named for the pane and the current
+/// time is used.
+pub fn resolve_log_path(pane_id: PaneId, path: Option<&str>) -> PathBuf {
+    match path {
+        Some(path) => match path.strip_prefix("~/") {
+            Some(rest) => config::HOME_DIR.join(rest),
+            None => config::HOME_DIR.join(path),
+        },
+        None => config::HOME_DIR.join(format!(
+            "wezterm-pane-{}-{}.log",
+            pane_id,
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        )),
+    }
+}
+
+/// Starts writing the subsequent output of the pane to `path`,
+/// appending to it if it already exists.  Replaces any log that
+/// the pane was already writing.
+pub fn start_logging(pane_id: PaneId, path: &Path, options: LogOptions) -> anyhow::Result<()> {
+    let log = PaneLog::open(path.to_path_buf(), options)?;
+    LOGS.lock().unwrap().insert(pane_id, log);
+    Ok(())
+}
+
+/// Stops logging the output of the pane.
+/// Returns false if it wasn't being logged.
+pub fn stop_logging(pane_id: PaneId) -> bool {
+    LOGS.lock().unwrap().remove(&pane_id).is_some()
+}
+
+/// Returns the path of the log that the pane is writing, if any
+pub fn log_path(pane_id: PaneId) -> Option<PathBuf> {
+    LOGS.lock()
+        .unwrap()
+        .get(&pane_id)
+        .map(|log| log.path.clone())
+}
+
+/// Called by the pty reader thread of the pane with the output that it read
+pub(crate) fn record_output(pane_id: PaneId, data: &[u8]) {
+    let mut logs = LOGS.lock().unwrap();
+    if let Some(log) = logs.get_mut(&pane_id) {
+        if let Err(err) = log.write(data) {
+            log::error!(
+                "pane {}: stopped logging to {}: {:#}",
+                pane_id,
+                log.path.display(),
+                err
+            );
+            logs.remove(&pane_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rotation() {
+        let dir = std::env::temp_dir().join(format!("wezterm-panelog-{}", std::process::id()));
+        let path = dir.join("pane.log");
+        let mut log = PaneLog::open(
+            path.clone(),
+            LogOptions {
+                with_escapes: false,
+                max_size: Some(8),
+                max_files: 2,
+            },
+        )
+        .unwrap();
+
+        log.write(b"\x1b[1mone\x1b[0m\r\n").unwrap();
+        log.write(b"two\r\n").unwrap();
+        log.write(b"three\r\n").unwrap();
+        log.write(b"four\r\n").unwrap();
+
+        let read = |p: PathBuf| std::fs::read_to_string(p).unwrap();
+        assert_eq!(read(path.clone()), "four\n");
+        assert_eq!(read(log.rotated_path(1)), "three\n");
+        assert_eq!(read(log.rotated_path(2)), "one\ntwo\n");
+        assert!(!log.rotated_path(3).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
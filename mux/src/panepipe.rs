@@ -0,0 +1,216 @@
+//! Pane pipes copy the output of one pane into the input of another,
+//! so that eg: a pane that is following a log file can feed a `grep`
+//! or `jq` that is running in another pane.
+//!
+//! Output is copied as it is read from the pty of the source pane.
+//! Each pipe has a bounded queue that is drained into the target pane
+//! on the mux thread; if the target doesn't keep up, the queue fills
+//! and reading from the source pane pauses until there is room,
+//! rather than buffering an unbounded amount of output.
+use crate::pane::PaneId;
+use crate::Mux;
+use smol::channel::{bounded, Receiver, Sender};
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use termwiz::escape::parser::Parser;
+use termwiz::escape::{Action, ControlCode};
+
+/// How many reads from the source pane may be queued for the
+/// target pane before the source is paused
+const PIPE_QUEUE_LEN: usize = 16;
+
+struct Pipe {
+    id: usize,
+    target: PaneId,
+    tx: Sender<Vec<u8>>,
+}
+
+lazy_static::lazy_static! {
+    /// The pipes, keyed by the pane that is their source
+    static ref PIPES: Mutex<HashMap<PaneId, Vec<Pipe>>> = Mutex::new(HashMap::new());
+}
+static NEXT_PIPE_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns true if output piped into `target` would find its way
+/// back to `source`, which would feed back on itself without end
+fn would_loop(pipes: &HashMap<PaneId, Vec<Pipe>>, source: PaneId, target: PaneId) -> bool {
+    let mut pending = vec![target];
+    let mut visited = vec![];
+    while let Some(pane_id) = pending.pop() {
+        if pane_id == source {
+            return true;
+        }
+        if visited.contains(&pane_id) {
+            continue;
+        }
+        visited.push(pane_id);
+        if let Some(outputs) = pipes.get(&pane_id) {
+            pending.extend(outputs.iter().map(|pipe| pipe.target));
+        }
+    }
+    false
+}
+
+/// Starts copying the output of `source` into the input of `target`.
+/// When `strip_escapes` is true, escape sequences and carriage returns
+/// are removed so that only the text is copied.
+/// Returns an id that can be passed to `remove_pipe`.
+pub fn pipe_to(source: PaneId, target: PaneId, strip_escapes: bool) -> anyhow::Result<usize> {
+    let mut pipes = PIPES.lock().unwrap();
+    if would_loop(&pipes, source, target) {
+        anyhow::bail!(
+            "piping pane {} into pane {} would feed its output back into itself",
+            source,
+            target
+        );
+    }
+
+    let id = NEXT_PIPE_ID.fetch_add(1, Ordering::Relaxed);
+    let (tx, rx) = bounded(PIPE_QUEUE_LEN);
+    pipes
+        .entry(source)
+        .or_default()
+        .push(Pipe { id, target, tx });
+    drop(pipes);
+
+    promise::spawn::spawn_into_main_thread(drain_pipe(id, source, target, rx, strip_escapes))
+        .detach();
+    Ok(id)
+}
+
+async fn drain_pipe(
+    id: usize,
+    source: PaneId,
+    target: PaneId,
+    rx: Receiver<Vec<u8>>,
+    strip_escapes: bool,
+) {
+    let mut parser = if strip_escapes {
+        Some(Parser::new())
+    } else {
+        None
+    };
+    while let Ok(data) = rx.recv().await {
+        let data = match parser.as_mut() {
+            Some(parser) => text_only(parser, &data),
+            None => data,
+        };
+        let pane = match Mux::get().and_then(|mux| mux.get_pane(target)) {
+            Some(pane) => pane,
+            None => break,
+        };
+        if let Err(err) = pane.writer().write_all(&data) {
+            log::error!("pipe {}: writing to pane {}: {:#}", id, target, err);
+            break;
+        }
+    }
+    remove_pipe(source, id);
+}
+
+/// Returns the printable text and line breaks from `data`
+pub(crate) fn text_only(parser: &mut Parser, data: &[u8]) -> Vec<u8> {
+    let mut text = String::new();
+    parser.parse(data, |action| match action {
+        Action::Print(c) => text.push(c),
+        Action::PrintString(s) => text.push_str(&s),
+        Action::Control(ControlCode::LineFeed) => text.push('\n'),
+        Action::Control(ControlCode::HorizontalTab) => text.push('\t'),
+        _ => {}
+    });
+    text.into_bytes()
+}
+
+/// Called by the pty reader thread of `source` with the output that
+/// it read.  Blocks while the queue of any of its pipes is full.
+pub(crate) fn forward(source: PaneId, data: &[u8]) {
+    let senders: Vec<Sender<Vec<u8>>> = match PIPES.lock().unwrap().get(&source) {
+        Some(pipes) => pipes.iter().map(|pipe| pipe.tx.clone()).collect(),
+        None => return,
+    };
+    for tx in senders {
+        // An error means that the pipe was removed in the meantime
+        smol::block_on(tx.send(data.to_vec())).ok();
+    }
+}
+
+/// Stops the pipe with the specified id from the `source` pane.
+/// Returns false if there was no such pipe.
+pub fn remove_pipe(source: PaneId, id: usize) -> bool {
+    let mut pipes = PIPES.lock().unwrap();
+    let outputs = match pipes.get_mut(&source) {
+        Some(outputs) => outputs,
+        None => return false,
+    };
+    let len = outputs.len();
+    outputs.retain(|pipe| pipe.id != id);
+    let found = outputs.len() != len;
+    if outputs.is_empty() {
+        pipes.remove(&source);
+    }
+    found
+}
+
+/// Returns the ids and targets of the pipes whose source is `source`
+pub fn pipes_from(source: PaneId) -> Vec<(usize, PaneId)> {
+    PIPES
+        .lock()
+        .unwrap()
+        .get(&source)
+        .map(|pipes| pipes.iter().map(|pipe| (pipe.id, pipe.target)).collect())
+        .unwrap_or_default()
+}
+
+/// Discards the pipes into and out of a pane that has been removed
+pub(crate) fn remove_pane(pane_id: PaneId) {
+    let mut pipes = PIPES.lock().unwrap();
+    pipes.remove(&pane_id);
+    pipes.retain(|_, outputs| {
+        outputs.retain(|pipe| pipe.target != pane_id);
+        !outputs.is_empty()
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn strips_escapes() {
+        let mut parser = Parser::new();
+        assert_eq!(
+            text_only(&mut parser, b"\x1b[31merror\x1b[0m: bad\r\nok\t1\r\n"),
+            b"error: bad\nok\t1\n"
+        );
+        // Sequences may be split across reads
+        assert_eq!(text_only(&mut parser, b"a\x1b[3"), b"a");
+        assert_eq!(text_only(&mut parser, b"2mb"), b"b");
+    }
+
+    #[test]
+    fn detects_loops() {
+        let mut pipes = HashMap::new();
+        let (tx, _rx) = bounded(1);
+        pipes.insert(
+            1,
+            vec![Pipe {
+                id: 0,
+                target: 2,
+                tx: tx.clone(),
+            }],
+        );
+        pipes.insert(
+            2,
+            vec![Pipe {
+                id: 1,
+                target: 3,
+                tx,
+            }],
+        );
+        assert!(would_loop(&pipes, 3, 1));
+        assert!(would_loop(&pipes, 1, 1));
+        assert!(!would_loop(&pipes, 1, 3));
+        assert!(!would_loop(&pipes, 4, 1));
+    }
+}
@@ -0,0 +1,227 @@
+//! Per-pane performance measurements that help to diagnose reports
+//! of slowness: the latency between a keypress and the frame that
+//! shows its effect, the time taken to render frames, and the rate
+//! at which output is read from the pty.
+//!
+//! The gui records input and render events and the mux records pty
+//! reads; the results can be shown by the debug HUD or retrieved via
+//! `wezterm cli debug metrics`.
+use crate::pane::PaneId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How many of the most recent samples are kept for each measurement
+const MAX_SAMPLES: usize = 512;
+
+lazy_static::lazy_static! {
+    static ref STATS: Mutex<HashMap<PaneId, PaneStats>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Default)]
+pub(crate) struct Samples(VecDeque<Duration>);
+
+impl Samples {
+    pub(crate) fn record(&mut self, sample: Duration) {
+        if self.0.len() == MAX_SAMPLES {
+            self.0.pop_front();
+        }
+        self.0.push_back(sample);
+    }
+
+    pub(crate) fn summarize(&self) -> LatencySummary {
+        let mut sorted: Vec<Duration> = self.0.iter().copied().collect();
+        sorted.sort();
+        let percentile = |p: usize| {
+            if sorted.is_empty() {
+                Duration::ZERO
+            } else {
+                sorted[(sorted.len() - 1) * p / 100]
+            }
+        };
+        LatencySummary {
+            samples: sorted.len(),
+            p50: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+            max: sorted.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+struct PaneStats {
+    /// When the oldest keypress that hasn't yet been rendered was sent
+    pending_input: Option<Instant>,
+    /// Whether the pane has produced output since `pending_input`
+    output_since_input: bool,
+    input_latency: Samples,
+    frame_time: Samples,
+    /// Start of the current one second throughput window
+    window_start: Instant,
+    window_bytes: u64,
+    bytes_per_second: u64,
+    total_bytes: u64,
+}
+
+impl PaneStats {
+    fn new() -> Self {
+        Self {
+            pending_input: None,
+            output_since_input: false,
+            input_latency: Samples::default(),
+            frame_time: Samples::default(),
+            window_start: Instant::now(),
+            window_bytes: 0,
+            bytes_per_second: 0,
+            total_bytes: 0,
+        }
+    }
+
+    fn roll_throughput_window(&mut self) {
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= Duration::from_secs(2) {
+            // Nothing was read during the last full window
+            self.bytes_per_second = 0;
+            self.window_bytes = 0;
+            self.window_start = Instant::now();
+        } else if elapsed >= Duration::from_secs(1) {
+            self.bytes_per_second = self.window_bytes;
+            self.window_bytes = 0;
+            self.window_start = Instant::now();
+        }
+    }
+}
+
+/// Percentiles computed over the most recent samples of a measurement
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug, Default)]
+pub struct LatencySummary {
+    pub samples: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// A snapshot of the measurements for a pane
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct PaneMetrics {
+    pub pane_id: PaneId,
+    /// Time from a keypress to the first frame rendered after the
+    /// pane produced output in response to it
+    pub input_latency: LatencySummary,
+    /// Time taken to render the frames that included the pane
+    pub frame_time: LatencySummary,
+    /// The number of bytes read from the pty during the last second
+    pub pty_bytes_per_second: u64,
+    /// The number of bytes read from the pty since the pane was created
+    pub pty_bytes_total: u64,
+}
+
+fn with_stats<F: FnOnce(&mut PaneStats)>(pane_id: PaneId, func: F) {
+    let mut stats = STATS.lock().unwrap();
+    func(stats.entry(pane_id).or_insert_with(PaneStats::new));
+}
+
+/// Records that input was sent to the pane
+pub fn record_input(pane_id: PaneId) {
+    with_stats(pane_id, |stats| {
+        if stats.pending_input.is_none() {
+            stats.pending_input.replace(Instant::now());
+            stats.output_since_input = false;
+        }
+    });
+}
+
+/// Records that the pane produced output, which will be shown
+/// by the next frame that is rendered
+pub fn record_output(pane_id: PaneId) {
+    with_stats(pane_id, |stats| {
+        if stats.pending_input.is_some() {
+            stats.output_since_input = true;
+        }
+    });
+}
+
+/// Records that a frame including the pane was rendered, taking
+/// `elapsed` to do so
+pub fn record_render(pane_id: PaneId, elapsed: Duration) {
+    with_stats(pane_id, |stats| {
+        stats.frame_time.record(elapsed);
+        if stats.output_since_input {
+            if let Some(start) = stats.pending_input.take() {
+                stats.input_latency.record(start.elapsed());
+            }
+            stats.output_since_input = false;
+        }
+    });
+}
+
+/// Records that `size` bytes were read from the pty of the pane
+pub fn record_pty_read(pane_id: PaneId, size: usize) {
+    with_stats(pane_id, |stats| {
+        stats.roll_throughput_window();
+        stats.window_bytes += size as u64;
+        stats.total_bytes += size as u64;
+    });
+}
+
+/// Discards the measurements for a pane that has been removed
+pub fn remove_pane(pane_id: PaneId) {
+    STATS.lock().unwrap().remove(&pane_id);
+}
+
+/// Returns a snapshot of the measurements for a pane
+pub fn pane_metrics(pane_id: PaneId) -> PaneMetrics {
+    let mut stats = STATS.lock().unwrap();
+    let stats = stats.entry(pane_id).or_insert_with(PaneStats::new);
+    stats.roll_throughput_window();
+    PaneMetrics {
+        pane_id,
+        input_latency: stats.input_latency.summarize(),
+        frame_time: stats.frame_time.summarize(),
+        pty_bytes_per_second: stats.bytes_per_second,
+        pty_bytes_total: stats.total_bytes,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn percentiles() {
+        let mut samples = Samples::default();
+        for ms in 1..=100 {
+            samples.record(Duration::from_millis(ms));
+        }
+        let summary = samples.summarize();
+        assert_eq!(summary.samples, 100);
+        assert_eq!(summary.p50, Duration::from_millis(50));
+        assert_eq!(summary.p95, Duration::from_millis(95));
+        assert_eq!(summary.max, Duration::from_millis(100));
+
+        assert_eq!(Samples::default().summarize(), LatencySummary::default());
+    }
+
+    #[test]
+    fn input_latency_needs_output() {
+        // Use an id that won't collide with other tests
+        let pane_id = PaneId::MAX;
+        record_input(pane_id);
+        record_render(pane_id, Duration::from_millis(1));
+        assert_eq!(pane_metrics(pane_id).input_latency.samples, 0);
+
+        record_output(pane_id);
+        record_render(pane_id, Duration::from_millis(1));
+        let metrics = pane_metrics(pane_id);
+        assert_eq!(metrics.input_latency.samples, 1);
+        assert_eq!(metrics.frame_time.samples, 2);
+
+        record_pty_read(pane_id, 10);
+        assert_eq!(pane_metrics(pane_id).pty_bytes_total, 10);
+
+        remove_pane(pane_id);
+        assert_eq!(pane_metrics(pane_id).frame_time.samples, 0);
+    }
+}
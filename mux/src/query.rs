@@ -0,0 +1,419 @@
+//! A small query language used to select panes, for example by
+//! `wezterm cli list --filter`.
+//!
+//! ```text
+//! workspace=dev && title~"vim"
+//! !(is_zoomed=true) || cwd~'^file://host/home/'
+//! ```
+//!
+//! A query is made up of comparisons of the form `FIELD OP VALUE`,
+//! combined with `&&`, `||`, `!` and parentheses.  `&&` binds more
+//! tightly than `||`.
+//!
+//! The `process` field is the name of the foreground process in the
+//! pane, which is only available where the pane is local; see
+//! `PaneQuery::matches_with`.
+//!
+//! The supported operators are `=` and `!=` for exact string
+//! comparison, and `~` and `!~` for regular expression matching.
+//! Values may be bare words or quoted with either single or double
+//! quotes, in which case a backslash before the quote character
+//! escapes it; other backslashes are preserved so that they can be
+//! used in regular expressions.
+use crate::pane::PaneId;
+use crate::tab::PaneEntry;
+use anyhow::{anyhow, bail, Context};
+use regex::Regex;
+use std::str::FromStr;
+
+/// The pane attributes that can be referenced in a query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaneField {
+    WindowId,
+    TabId,
+    PaneId,
+    Workspace,
+    Title,
+    Cwd,
+    IsActive,
+    IsZoomed,
+    Process,
+}
+
+impl PaneField {
+    fn from_name(name: &str) -> anyhow::Result<Self> {
+        Ok(match name {
+            "window_id" => Self::WindowId,
+            "tab_id" => Self::TabId,
+            "pane_id" => Self::PaneId,
+            "workspace" => Self::Workspace,
+            "title" => Self::Title,
+            "cwd" => Self::Cwd,
+            "is_active" => Self::IsActive,
+            "is_zoomed" => Self::IsZoomed,
+            "process" => Self::Process,
+            _ => bail!(
+                "unknown field `{}`; expected one of window_id, tab_id, pane_id, \
+                 workspace, title, cwd, is_active, is_zoomed, process",
+                name
+            ),
+        })
+    }
+
+    fn value(&self, entry: &PaneEntry, process_name: &dyn Fn(PaneId) -> Option<String>) -> String {
+        match self {
+            Self::WindowId => entry.window_id.to_string(),
+            Self::TabId => entry.tab_id.to_string(),
+            Self::PaneId => entry.pane_id.to_string(),
+            Self::Workspace => entry.workspace.clone(),
+            Self::Title => entry.title.clone(),
+            Self::Cwd => entry
+                .working_dir
+                .as_ref()
+                .map(|url| url.url.as_str().to_string())
+                .unwrap_or_default(),
+            Self::IsActive => entry.is_active_pane.to_string(),
+            Self::IsZoomed => entry.is_zoomed_pane.to_string(),
+            Self::Process => process_name(entry.pane_id).unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PaneQuery {
+    Equal(PaneField, String),
+    NotEqual(PaneField, String),
+    Matches(PaneField, Regex),
+    NotMatches(PaneField, Regex),
+    Not(Box<PaneQuery>),
+    And(Box<PaneQuery>, Box<PaneQuery>),
+    Or(Box<PaneQuery>, Box<PaneQuery>),
+}
+
+impl PaneQuery {
+    /// Evaluates the query against `entry`, treating the `process`
+    /// field as empty
+    pub fn matches(&self, entry: &PaneEntry) -> bool {
+        self.matches_with(entry, &|_| None)
+    }
+
+    /// Evaluates the query against `entry`, using `process_name` to
+    /// resolve the foreground process name of the pane if the query
+    /// references the `process` field
+    pub fn matches_with(
+        &self,
+        entry: &PaneEntry,
+        process_name: &dyn Fn(PaneId) -> Option<String>,
+    ) -> bool {
+        match self {
+            Self::Equal(field, value) => field.value(entry, process_name) == *value,
+            Self::NotEqual(field, value) => field.value(entry, process_name) != *value,
+            Self::Matches(field, re) => re.is_match(&field.value(entry, process_name)),
+            Self::NotMatches(field, re) => !re.is_match(&field.value(entry, process_name)),
+            Self::Not(q) => !q.matches_with(entry, process_name),
+            Self::And(a, b) => {
+                a.matches_with(entry, process_name) && b.matches_with(entry, process_name)
+            }
+            Self::Or(a, b) => {
+                a.matches_with(entry, process_name) || b.matches_with(entry, process_name)
+            }
+        }
+    }
+}
+
+impl FromStr for PaneQuery {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let query = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            bail!("unexpected {:?} in query", token);
+        }
+        Ok(query)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Equal,
+    NotEqual,
+    Match,
+    NotMatch,
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+fn tokenize(s: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            '=' => {
+                chars.next();
+                // Be forgiving of `==`
+                if chars.peek() == Some(&'=') {
+                    chars.next();
+                }
+                tokens.push(Token::Equal);
+            }
+            '~' => {
+                chars.next();
+                tokens.push(Token::Match);
+            }
+            '!' => {
+                chars.next();
+                match chars.peek() {
+                    Some('=') => {
+                        chars.next();
+                        tokens.push(Token::NotEqual);
+                    }
+                    Some('~') => {
+                        chars.next();
+                        tokens.push(Token::NotMatch);
+                    }
+                    _ => tokens.push(Token::Not),
+                }
+            }
+            '&' | '|' => {
+                chars.next();
+                if chars.next() != Some(c) {
+                    bail!("expected `{}{}` in query", c, c);
+                }
+                tokens.push(if c == '&' { Token::And } else { Token::Or });
+            }
+            '"' | '\'' => {
+                let quote = c;
+                chars.next();
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => match chars.next() {
+                            Some(c) if c == quote => word.push(c),
+                            // Preserve other escapes for the benefit of regexes
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => bail!("unterminated string in query"),
+                        },
+                        Some(c) if c == quote => break,
+                        Some(c) => word.push(c),
+                        None => bail!("unterminated string in query"),
+                    }
+                }
+                tokens.push(Token::Word(word));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "()=~!&|\"'".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> anyhow::Result<PaneQuery> {
+        let mut query = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            let rhs = self.parse_and()?;
+            query = PaneQuery::Or(Box::new(query), Box::new(rhs));
+        }
+        Ok(query)
+    }
+
+    fn parse_and(&mut self) -> anyhow::Result<PaneQuery> {
+        let mut query = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            let rhs = self.parse_unary()?;
+            query = PaneQuery::And(Box::new(query), Box::new(rhs));
+        }
+        Ok(query)
+    }
+
+    fn parse_unary(&mut self) -> anyhow::Result<PaneQuery> {
+        match self.next() {
+            Some(Token::Not) => Ok(PaneQuery::Not(Box::new(self.parse_unary()?))),
+            Some(Token::LParen) => {
+                let query = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(query),
+                    _ => bail!("expected `)` in query"),
+                }
+            }
+            Some(Token::Word(name)) => {
+                let field = PaneField::from_name(&name)?;
+                let op = self
+                    .next()
+                    .ok_or_else(|| anyhow!("expected an operator after `{}`", name))?;
+                let value = match self.next() {
+                    Some(Token::Word(value)) => value,
+                    _ => bail!("expected a value after `{}` {:?}", name, op),
+                };
+                let regex = || {
+                    Regex::new(&value)
+                        .with_context(|| format!("invalid regex `{}` for `{}`", value, name))
+                };
+                Ok(match op {
+                    Token::Equal => PaneQuery::Equal(field, value.clone()),
+                    Token::NotEqual => PaneQuery::NotEqual(field, value.clone()),
+                    Token::Match => PaneQuery::Matches(field, regex()?),
+                    Token::NotMatch => PaneQuery::NotMatches(field, regex()?),
+                    _ => bail!("expected one of `=`, `!=`, `~` or `!~` after `{}`", name),
+                })
+            }
+            Some(token) => bail!("unexpected {:?} in query", token),
+            None => bail!("unexpected end of query"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::renderable::StableCursorPosition;
+    use wezterm_term::TerminalSize;
+
+    fn entry(pane_id: usize, workspace: &str, title: &str) -> PaneEntry {
+        PaneEntry {
+            window_id: 0,
+            tab_id: 0,
+            pane_id,
+            title: title.to_string(),
+            size: TerminalSize::default(),
+            working_dir: None,
+            is_active_pane: pane_id == 0,
+            is_zoomed_pane: false,
+            workspace: workspace.to_string(),
+            cursor_pos: StableCursorPosition::default(),
+            physical_top: 0,
+            top_row: 0,
+            left_col: 0,
+        }
+    }
+
+    fn matching(query: &str) -> Vec<usize> {
+        let query: PaneQuery = query.parse().unwrap();
+        [
+            entry(0, "default", "zsh"),
+            entry(1, "dev", "vim main.rs"),
+            entry(2, "dev", "cargo build"),
+            entry(3, "my work", "nvim"),
+        ]
+        .iter()
+        .filter(|e| query.matches(e))
+        .map(|e| e.pane_id)
+        .collect()
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(matching("workspace=dev"), vec![1, 2]);
+        assert_eq!(matching("workspace != dev"), vec![0, 3]);
+        assert_eq!(matching("title~vim"), vec![1, 3]);
+        assert_eq!(matching("title!~'^(vim|zsh)'"), vec![2, 3]);
+        assert_eq!(matching("pane_id=2"), vec![2]);
+        assert_eq!(matching("is_active=true"), vec![0]);
+        assert_eq!(matching(r#"workspace="my work""#), vec![3]);
+        assert_eq!(matching(r#"title~'\.rs$'"#), vec![1]);
+        assert_eq!(matching(r#"title='vim main\'s'"#), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn combinators() {
+        assert_eq!(matching(r#"workspace=dev && title~"vim""#), vec![1]);
+        assert_eq!(matching("workspace=default || title=nvim"), vec![0, 3]);
+        assert_eq!(matching("!workspace=dev"), vec![0, 3]);
+        assert_eq!(
+            matching("workspace=default || workspace=dev && title~cargo"),
+            vec![0, 2]
+        );
+        assert_eq!(
+            matching("(workspace=default || workspace=dev) && !title~cargo"),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn process() {
+        let query: PaneQuery = "process~'/n?vim$'".parse().unwrap();
+        let process_name = |pane_id| match pane_id {
+            1 => Some("/usr/bin/vim".to_string()),
+            2 => Some("/usr/bin/cargo".to_string()),
+            _ => None,
+        };
+        let entries = [
+            entry(0, "default", "zsh"),
+            entry(1, "dev", "x"),
+            entry(2, "dev", "y"),
+        ];
+        let matched: Vec<usize> = entries
+            .iter()
+            .filter(|e| query.matches_with(e, &process_name))
+            .map(|e| e.pane_id)
+            .collect();
+        assert_eq!(matched, vec![1]);
+        assert!(!query.matches(&entries[1]));
+    }
+
+    #[test]
+    fn errors() {
+        for bad in [
+            "",
+            "workspace",
+            "workspace=",
+            "bogus=1",
+            "workspace=dev &&",
+            "workspace=dev & title=x",
+            "(workspace=dev",
+            "title~'('",
+            "title='unterminated",
+            "workspace=dev title=x",
+        ] {
+            assert!(bad.parse::<PaneQuery>().is_err(), "{} should fail", bad);
+        }
+    }
+}
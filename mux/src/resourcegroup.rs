@@ -0,0 +1,285 @@
+//! Places the processes of a local pane into a cgroup (Linux) or
+//! a Job Object (Windows) so that their memory and CPU usage can be
+//! limited, and so that all of them can be cleaned up when the pane
+//! is closed.
+//!
+//! Processes are added to the group just after they are spawned,
+//! so anything that they spawn before that happens is not included.
+use anyhow::Context;
+use config::ResourceLimits;
+
+/// A group of processes that share resource limits.
+/// Dropping the group kills its processes if `kill_on_close` was set.
+pub struct ResourceGroup {
+    #[cfg(target_os = "linux")]
+    path: std::path::PathBuf,
+    #[cfg(windows)]
+    job: winapi::um::winnt::HANDLE,
+    kill_on_close: bool,
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::*;
+    use std::path::{Path, PathBuf};
+
+    const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+    const CPU_PERIOD_US: u64 = 100_000;
+
+    /// Returns the cgroup v2 that wezterm itself is running in
+    fn own_cgroup() -> anyhow::Result<PathBuf> {
+        let data =
+            std::fs::read_to_string("/proc/self/cgroup").context("reading /proc/self/cgroup")?;
+        let path = data
+            .lines()
+            .find_map(|line| line.strip_prefix("0::"))
+            .ok_or_else(|| anyhow::anyhow!("cgroup v2 is not available"))?;
+        Ok(Path::new(CGROUP_ROOT).join(path.trim_start_matches('/')))
+    }
+
+    fn write(path: &Path, name: &str, value: &str) -> anyhow::Result<()> {
+        let path = path.join(name);
+        std::fs::write(&path, value)
+            .with_context(|| format!("writing {value} to {}", path.display()))
+    }
+
+    impl ResourceGroup {
+        pub fn new(pane_id: crate::pane::PaneId, limits: &ResourceLimits) -> anyhow::Result<Self> {
+            // We can't create a child of our own cgroup, as processes may
+            // only live in the leaves of the hierarchy, so create a
+            // sibling.  This works for the user-delegated subtree that
+            // systemd sets up for graphical sessions.
+            let own = own_cgroup()?;
+            let parent = own
+                .parent()
+                .ok_or_else(|| anyhow::anyhow!("wezterm is in the root cgroup"))?;
+
+            // Make the controllers that we need available to the new
+            // cgroup; this may fail if they are already enabled or not
+            // delegated to us, in which case setting the limits below
+            // will report a more useful error.
+            write(parent, "cgroup.subtree_control", "+memory +cpu").ok();
+
+            let path = parent.join(format!("wezterm-pane-{}-{}", std::process::id(), pane_id));
+            std::fs::create_dir(&path)
+                .with_context(|| format!("creating cgroup {}", path.display()))?;
+
+            let mut group = Self {
+                path,
+                kill_on_close: limits.kill_on_close,
+            };
+            group.set_limits(limits)?;
+            Ok(group)
+        }
+
+        pub fn set_limits(&mut self, limits: &ResourceLimits) -> anyhow::Result<()> {
+            self.kill_on_close = limits.kill_on_close;
+            let memory = match limits.memory_max_mb {
+                Some(mb) => (mb * 1024 * 1024).to_string(),
+                None => "max".to_string(),
+            };
+            write(&self.path, "memory.max", &memory)?;
+
+            let cpu = match limits.cpu_max_percent {
+                Some(percent) => {
+                    format!("{} {}", percent as u64 * CPU_PERIOD_US / 100, CPU_PERIOD_US)
+                }
+                None => format!("max {}", CPU_PERIOD_US),
+            };
+            write(&self.path, "cpu.max", &cpu)?;
+            Ok(())
+        }
+
+        pub fn add_process(&self, pid: u32) -> anyhow::Result<()> {
+            write(&self.path, "cgroup.procs", &pid.to_string())
+        }
+
+        pub(super) fn kill(&self) {
+            // cgroup.kill requires Linux 5.14; fall back to signalling
+            // each of the processes
+            if write(&self.path, "cgroup.kill", "1").is_err() {
+                if let Ok(procs) = std::fs::read_to_string(self.path.join("cgroup.procs")) {
+                    for pid in procs.lines().filter_map(|pid| pid.parse::<i32>().ok()) {
+                        unsafe {
+                            libc::kill(pid, libc::SIGKILL);
+                        }
+                    }
+                }
+            }
+        }
+
+        pub(super) fn remove(&self) {
+            // The cgroup can only be removed once its processes have
+            // exited, so retry for a little while in the background
+            let path = self.path.clone();
+            std::thread::spawn(move || {
+                for _ in 0..20 {
+                    if std::fs::remove_dir(&path).is_ok() {
+                        return;
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                }
+                log::warn!("unable to remove cgroup {}", path.display());
+            });
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::*;
+    use std::ptr::null_mut;
+    use winapi::shared::minwindef::{DWORD, FALSE};
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::jobapi2::{
+        AssignProcessToJobObject, CreateJobObjectW, SetInformationJobObject, TerminateJobObject,
+    };
+    use winapi::um::processthreadsapi::OpenProcess;
+    use winapi::um::winnt::{
+        JobObjectExtendedLimitInformation, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_LIMIT_JOB_MEMORY, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE, PROCESS_SET_QUOTA,
+        PROCESS_TERMINATE,
+    };
+
+    // JOBOBJECT_CPU_RATE_CONTROL_INFORMATION isn't provided by winapi
+    const JOB_OBJECT_CPU_RATE_CONTROL_INFORMATION_CLASS: u32 = 15;
+    const JOB_OBJECT_CPU_RATE_CONTROL_ENABLE: DWORD = 0x1;
+    const JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP: DWORD = 0x4;
+
+    #[repr(C)]
+    struct CpuRateControlInformation {
+        control_flags: DWORD,
+        cpu_rate: DWORD,
+    }
+
+    impl ResourceGroup {
+        pub fn new(_pane_id: crate::pane::PaneId, limits: &ResourceLimits) -> anyhow::Result<Self> {
+            let job = unsafe { CreateJobObjectW(null_mut(), null_mut()) };
+            if job.is_null() {
+                return Err(std::io::Error::last_os_error()).context("CreateJobObjectW");
+            }
+            let mut group = Self {
+                job,
+                kill_on_close: limits.kill_on_close,
+            };
+            group.set_limits(limits)?;
+            Ok(group)
+        }
+
+        pub fn set_limits(&mut self, limits: &ResourceLimits) -> anyhow::Result<()> {
+            self.kill_on_close = limits.kill_on_close;
+            let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { std::mem::zeroed() };
+            if self.kill_on_close {
+                info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            }
+            if let Some(mb) = limits.memory_max_mb {
+                info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_JOB_MEMORY;
+                info.JobMemoryLimit = (mb * 1024 * 1024) as usize;
+            }
+            let ok = unsafe {
+                SetInformationJobObject(
+                    self.job,
+                    JobObjectExtendedLimitInformation,
+                    &mut info as *mut _ as *mut _,
+                    std::mem::size_of_val(&info) as DWORD,
+                )
+            };
+            if ok == 0 {
+                return Err(std::io::Error::last_os_error())
+                    .context("SetInformationJobObject(ExtendedLimitInformation)");
+            }
+
+            // The rate is expressed in 1/100ths of a percent of all of
+            // the CPUs in the system, whereas our limit is relative to
+            // a single CPU
+            let mut rate = CpuRateControlInformation {
+                control_flags: 0,
+                cpu_rate: 0,
+            };
+            if let Some(percent) = limits.cpu_max_percent {
+                let cpus = std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1) as u32;
+                rate.control_flags =
+                    JOB_OBJECT_CPU_RATE_CONTROL_ENABLE | JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP;
+                rate.cpu_rate = (percent * 100 / cpus).clamp(1, 10_000);
+            }
+            let ok = unsafe {
+                SetInformationJobObject(
+                    self.job,
+                    JOB_OBJECT_CPU_RATE_CONTROL_INFORMATION_CLASS,
+                    &mut rate as *mut _ as *mut _,
+                    std::mem::size_of_val(&rate) as DWORD,
+                )
+            };
+            if ok == 0 {
+                return Err(std::io::Error::last_os_error())
+                    .context("SetInformationJobObject(CpuRateControlInformation)");
+            }
+            Ok(())
+        }
+
+        pub fn add_process(&self, pid: u32) -> anyhow::Result<()> {
+            unsafe {
+                let proc = OpenProcess(PROCESS_SET_QUOTA | PROCESS_TERMINATE, FALSE, pid);
+                if proc.is_null() {
+                    return Err(std::io::Error::last_os_error()).context("OpenProcess");
+                }
+                let ok = AssignProcessToJobObject(self.job, proc);
+                let err = std::io::Error::last_os_error();
+                CloseHandle(proc);
+                if ok == 0 {
+                    return Err(err).context("AssignProcessToJobObject");
+                }
+            }
+            Ok(())
+        }
+
+        pub(super) fn kill(&self) {
+            unsafe {
+                TerminateJobObject(self.job, 1);
+            }
+        }
+
+        pub(super) fn remove(&self) {
+            unsafe {
+                CloseHandle(self.job);
+            }
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod imp {
+    use super::*;
+
+    impl ResourceGroup {
+        pub fn new(
+            _pane_id: crate::pane::PaneId,
+            _limits: &ResourceLimits,
+        ) -> anyhow::Result<Self> {
+            anyhow::bail!("resource limits are not supported on this system")
+        }
+
+        pub fn set_limits(&mut self, _limits: &ResourceLimits) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        pub fn add_process(&self, _pid: u32) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        pub(super) fn kill(&self) {}
+
+        pub(super) fn remove(&self) {}
+    }
+}
+
+impl Drop for ResourceGroup {
+    fn drop(&mut self) {
+        if self.kill_on_close {
+            self.kill();
+        }
+        self.remove();
+    }
+}
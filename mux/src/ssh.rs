@@ -1,5 +1,6 @@
 use crate::connui::ConnectionUI;
 use crate::domain::{alloc_domain_id, Domain, DomainId, DomainState, WriterWrapper};
+use crate::domainstats::{MeteredReader, MeteredWriter};
 use crate::localpane::LocalPane;
 use crate::pane::{alloc_pane_id, Pane, PaneId};
 use crate::Mux;
@@ -56,6 +57,27 @@ impl LineEditorHost for PasswordPromptHost {
     }
 }
 
+/// Retrieves the password or passphrase that the ssh config says
+/// is stored in the OS keychain, if any
+fn keychain_secret(ssh_config: &ConfigMap) -> anyhow::Result<Option<String>> {
+    let (service, account) = match (
+        ssh_config.get("wezterm_keychain_service"),
+        ssh_config.get("wezterm_keychain_account"),
+    ) {
+        (Some(service), Some(account)) => (service, account),
+        _ => return Ok(None),
+    };
+    match keyring::Entry::new(service, account).get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => {
+            anyhow::bail!("no keychain entry for service={service} account={account}")
+        }
+        Err(err) => {
+            Err(err).with_context(|| format!("keychain service={service} account={account}"))
+        }
+    }
+}
+
 pub fn ssh_connect_with_ui(
     ssh_config: wezterm_ssh::ConfigMap,
     ui: &mut ConnectionUI,
@@ -66,6 +88,15 @@ pub fn ssh_connect_with_ui(
             .get("hostname")
             .expect("ssh config to always set hostname");
         ui.output_str(&format!("Connecting to {} using SSH\n", remote_address));
+        // The stored secret is used to answer the first password
+        // prompt; if it is rejected, we fall back to asking the user
+        let mut stored_secret = match keychain_secret(&ssh_config) {
+            Ok(secret) => secret,
+            Err(err) => {
+                ui.output_str(&format!("{:#}\n", err));
+                None
+            }
+        };
         let (session, events) = Session::connect(ssh_config.clone())?;
 
         while let Ok(event) = smol::block_on(events.recv()) {
@@ -96,6 +127,12 @@ pub fn ssh_connect_with_ui(
                     }
                     let mut answers = vec![];
                     for prompt in &auth.prompts {
+                        if !prompt.echo {
+                            if let Some(secret) = stored_secret.take() {
+                                answers.push(secret);
+                                continue;
+                            }
+                        }
                         let mut prompt_lines = prompt.prompt.split('\n').collect::<Vec<_>>();
                         let editor_prompt = prompt_lines.pop().unwrap();
                         for line in &prompt_lines {
@@ -178,6 +215,16 @@ pub fn ssh_domain_to_ssh_config(ssh_dom: &SshDomain) -> anyhow::Result<ConfigMap
     if ssh_dom.no_agent_auth {
         ssh_config.insert("identitiesonly".to_string(), "yes".to_string());
     }
+    if let Some(secret) = &ssh_dom.keychain_secret {
+        ssh_config.insert(
+            "wezterm_keychain_service".to_string(),
+            secret.service.to_string(),
+        );
+        ssh_config.insert(
+            "wezterm_keychain_account".to_string(),
+            secret.account.to_string(),
+        );
+    }
     Ok(ssh_config)
 }
 
@@ -192,6 +239,12 @@ impl RemoteSshDomain {
         })
     }
 
+    /// Returns the ssh session, once it has been established by
+    /// spawning the first pane in the domain
+    pub fn session(&self) -> Option<Session> {
+        self.session.borrow().as_ref().cloned()
+    }
+
     pub fn ssh_config(&self) -> anyhow::Result<ConfigMap> {
         ssh_domain_to_ssh_config(&self.dom)
     }
@@ -290,6 +343,7 @@ fn connect_ssh_session(
     size: Arc<Mutex<TerminalSize>>,
     command_line: Option<String>,
     env: HashMap<String, String>,
+    install_terminfo: bool,
 ) -> anyhow::Result<()> {
     struct StdoutShim<'a> {
         size: Arc<Mutex<TerminalSize>>,
@@ -505,8 +559,16 @@ fn connect_ssh_session(
             SessionEvent::Authenticated => {
                 // Our session has been authenticated: we can now
                 // set up the real pty for the pane
+                let term = config::configuration().term.clone();
+                if install_terminfo && term == "wezterm" {
+                    if let Err(err) =
+                        smol::block_on(crate::terminfo::install_remote_terminfo(&session))
+                    {
+                        shim.output_line(&format!("{:#}", err))?;
+                    }
+                }
                 match smol::block_on(session.request_pty(
-                    &config::configuration().term,
+                    &term,
                     crate::terminal_size_to_pty_size(*size.lock().unwrap())?,
                     command_line.as_ref().map(|s| s.as_str()),
                     Some(env),
@@ -642,6 +704,7 @@ impl Domain for RemoteSshDomain {
             // to perform the blocking (from its perspective) terminal
             // UI to carry out any authentication.
             let mut stdout_write = BufWriter::new(stdout_write);
+            let install_terminfo = self.dom.install_terminfo;
             std::thread::spawn(move || {
                 if let Err(err) = connect_ssh_session(
                     session,
@@ -655,6 +718,7 @@ impl Domain for RemoteSshDomain {
                     size,
                     command_line,
                     env,
+                    install_terminfo,
                 ) {
                     let _ = write!(stdout_write, "{:#}", err);
                     log::error!("Failed to connect ssh: {:#}", err);
@@ -667,7 +731,11 @@ impl Domain for RemoteSshDomain {
         // eg: tmux integration to be tunnelled via the remote
         // session without duplicating a lot of logic over here.
 
-        let writer = WriterWrapper::new(writer);
+        let pty: Box<dyn portable_pty::MasterPty> = Box::new(MeteredPty {
+            inner: pty,
+            domain_id: self.id,
+        });
+        let writer = WriterWrapper::new(Box::new(MeteredWriter::new(writer, self.id)));
 
         let terminal = wezterm_term::Terminal::new(
             size,
@@ -677,7 +745,7 @@ impl Domain for RemoteSshDomain {
             Box::new(writer.clone()),
         );
 
-        let pane: Rc<dyn Pane> = Rc::new(LocalPane::new(
+        let pane = LocalPane::new(
             pane_id,
             terminal,
             child,
@@ -685,7 +753,9 @@ impl Domain for RemoteSshDomain {
             Box::new(writer),
             self.id,
             "RemoteSshDomain".to_string(),
-        ));
+        );
+        pane.enable_local_echo_prediction(self.dom.local_echo_threshold_ms);
+        let pane: Rc<dyn Pane> = Rc::new(pane);
         let mux = Mux::get().unwrap();
         mux.add_pane(&pane)?;
 
@@ -987,6 +1057,38 @@ impl portable_pty::MasterPty for WrappedSshPty {
     }
 }
 
+/// Records the traffic that flows through a pty as having
+/// been received by its domain
+struct MeteredPty {
+    inner: Box<dyn portable_pty::MasterPty>,
+    domain_id: DomainId,
+}
+
+impl portable_pty::MasterPty for MeteredPty {
+    fn resize(&self, new_size: PtySize) -> anyhow::Result<()> {
+        self.inner.resize(new_size)
+    }
+
+    fn get_size(&self) -> anyhow::Result<PtySize> {
+        self.inner.get_size()
+    }
+
+    fn try_clone_reader(&self) -> anyhow::Result<Box<(dyn Read + Send + 'static)>> {
+        let reader = self.inner.try_clone_reader()?;
+        Ok(Box::new(MeteredReader::new(reader, self.domain_id)))
+    }
+
+    fn take_writer(&self) -> anyhow::Result<Box<(dyn Write + Send + 'static)>> {
+        let writer = self.inner.take_writer()?;
+        Ok(Box::new(MeteredWriter::new(writer, self.domain_id)))
+    }
+
+    #[cfg(unix)]
+    fn process_group_leader(&self) -> Option<i32> {
+        self.inner.process_group_leader()
+    }
+}
+
 impl std::io::Write for PtyWriter {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         // Check for a new writer first: on Windows, the socket
@@ -3,8 +3,8 @@ use crate::pane::*;
 use crate::renderable::StableCursorPosition;
 use crate::{Mux, WindowId};
 use bintree::PathBranch;
-use config::configuration;
 use config::keyassignment::PaneDirection;
+use config::{configuration, PaneTooSmallAction};
 use rangeset::intersects_range;
 use serde::{Deserialize, Serialize};
 use std::cell::{RefCell, RefMut};
@@ -307,29 +307,72 @@ where
     }
 }
 
+/// Returns the configured minimum (x, y) size of a pane
+fn configured_min_pane_size() -> (usize, usize) {
+    let config = configuration();
+    (
+        config.pane_minimum_cols.max(1),
+        config.pane_minimum_rows.max(1),
+    )
+}
+
+/// Returns the minimum (x, y) sizes of the first and second children
+/// of the split at the top of `tree`, when it is `width` x `height`.
+/// The configured minimum pane size is honored if there is enough
+/// space to do so; otherwise each pane needs only a single cell.
+fn compute_min_child_sizes(
+    tree: &Tree,
+    width: usize,
+    height: usize,
+) -> ((usize, usize), (usize, usize)) {
+    let (left, right, direction) = match tree {
+        Tree::Node {
+            left,
+            right,
+            data: Some(data),
+        } => (left, right, data.direction),
+        _ => return ((1, 1), (1, 1)),
+    };
+    let pane_min = configured_min_pane_size();
+    let first = compute_min_size(left, pane_min);
+    let second = compute_min_size(right, pane_min);
+    let fits = match direction {
+        SplitDirection::Horizontal => first.0 + second.0 + 1 <= width,
+        SplitDirection::Vertical => first.1 + second.1 + 1 <= height,
+    };
+    if fits {
+        (first, second)
+    } else {
+        (
+            compute_min_size(left, (1, 1)),
+            compute_min_size(right, (1, 1)),
+        )
+    }
+}
+
 /// Computes the minimum (x, y) size based on the panes in this portion
-/// of the tree.
-fn compute_min_size(tree: &mut Tree) -> (usize, usize) {
+/// of the tree, given the minimum size of each pane.
+fn compute_min_size(tree: &Tree, pane_min: (usize, usize)) -> (usize, usize) {
     match tree {
-        Tree::Node { data: None, .. } | Tree::Empty => (1, 1),
+        Tree::Node { data: None, .. } | Tree::Empty => pane_min,
         Tree::Node {
             left,
             right,
             data: Some(data),
         } => {
-            let (left_x, left_y) = compute_min_size(&mut *left);
-            let (right_x, right_y) = compute_min_size(&mut *right);
+            let (left_x, left_y) = compute_min_size(left, pane_min);
+            let (right_x, right_y) = compute_min_size(right, pane_min);
             match data.direction {
                 SplitDirection::Vertical => (left_x.max(right_x), left_y + right_y + 1),
                 SplitDirection::Horizontal => (left_x + right_x + 1, left_y.max(right_y)),
             }
         }
-        Tree::Leaf(_) => (1, 1),
+        Tree::Leaf(_) => pane_min,
     }
 }
 
 fn adjust_x_size(tree: &mut Tree, mut x_adjust: isize, cell_dimensions: &TerminalSize) {
-    let (min_x, _) = compute_min_size(tree);
+    let (min_x, _) = compute_min_size(tree, (1, 1));
     while x_adjust != 0 {
         match tree {
             Tree::Empty | Tree::Leaf(_) => return,
@@ -399,7 +442,7 @@ fn adjust_x_size(tree: &mut Tree, mut x_adjust: isize, cell_dimensions: &Termina
 }
 
 fn adjust_y_size(tree: &mut Tree, mut y_adjust: isize, cell_dimensions: &TerminalSize) {
-    let (_, min_y) = compute_min_size(tree);
+    let (_, min_y) = compute_min_size(tree, (1, 1));
     while y_adjust != 0 {
         match tree {
             Tree::Empty | Tree::Leaf(_) => return,
@@ -925,7 +968,7 @@ impl Tab {
         {
             let mut root = self.pane.borrow_mut();
             let dims = cell_dimensions(&size);
-            let (min_x, min_y) = compute_min_size(root.as_mut().unwrap());
+            let (min_x, min_y) = compute_min_size(root.as_ref().unwrap(), (1, 1));
             let current_size = *self.size.borrow();
 
             // Constrain the new size to the minimum possible dimensions
@@ -960,26 +1003,54 @@ impl Tab {
         }
 
         // And finally restore the zoom, if appropriate
-        self.set_zoomed(was_zoomed);
+        self.set_zoomed(was_zoomed || self.should_zoom_too_small_active_pane());
+    }
+
+    /// Returns true if the active pane is smaller than the configured
+    /// minimum, and should be zoomed so that it is usable
+    fn should_zoom_too_small_active_pane(&self) -> bool {
+        if configuration().pane_too_small_action != PaneTooSmallAction::Zoom {
+            return false;
+        }
+        let (min_cols, min_rows) = configured_min_pane_size();
+        self.iter_panes_ignoring_zoom()
+            .into_iter()
+            .find(|p| p.is_active)
+            .map(|p| p.width < min_cols || p.height < min_rows)
+            .unwrap_or(false)
     }
 
     fn apply_pane_size(&self, pane_size: TerminalSize, cursor: &mut Cursor) {
         let cell_width = pane_size.pixel_width / pane_size.cols;
         let cell_height = pane_size.pixel_height / pane_size.rows;
+        let ((first_min_x, first_min_y), (second_min_x, second_min_y)) =
+            compute_min_child_sizes(cursor.subtree(), pane_size.cols, pane_size.rows);
         if let Ok(Some(node)) = cursor.node_mut() {
             // Adjust the size of the node; we preserve the size of the first
             // child and adjust the second, so if we are split down the middle
             // and the window is made wider, the right column will grow in
             // size, leaving the left at its current width.
+            // If that would make the second child smaller than its panes
+            // allow, the first child gives up the difference.
             if node.direction == SplitDirection::Horizontal {
                 node.first.rows = pane_size.rows;
                 node.second.rows = pane_size.rows;
 
+                node.first.cols = node
+                    .first
+                    .cols
+                    .min(pane_size.cols.saturating_sub(1 + second_min_x))
+                    .max(first_min_x);
                 node.second.cols = pane_size.cols.saturating_sub(1 + node.first.cols);
             } else {
                 node.first.cols = pane_size.cols;
                 node.second.cols = pane_size.cols;
 
+                node.first.rows = node
+                    .first
+                    .rows
+                    .min(pane_size.rows.saturating_sub(1 + second_min_y))
+                    .max(first_min_y);
                 node.second.rows = pane_size.rows.saturating_sub(1 + node.first.rows);
             }
             node.first.pixel_width = node.first.cols * cell_width;
@@ -1087,6 +1158,14 @@ impl Tab {
 
     fn adjust_node_at_cursor(&self, cursor: &mut Cursor, delta: isize) {
         let cell_dimensions = self.cell_dimensions();
+        let (width, height) = match cursor.subtree() {
+            Tree::Node {
+                data: Some(data), ..
+            } => (data.width(), data.height()),
+            _ => return,
+        };
+        let ((first_min_x, first_min_y), (second_min_x, second_min_y)) =
+            compute_min_child_sizes(cursor.subtree(), width, height);
         if let Ok(Some(node)) = cursor.node_mut() {
             match node.direction {
                 SplitDirection::Horizontal => {
@@ -1095,8 +1174,8 @@ impl Tab {
                     let mut cols = node.first.cols as isize;
                     cols = cols
                         .saturating_add(delta)
-                        .max(1)
-                        .min((width as isize).saturating_sub(2));
+                        .min((width as isize).saturating_sub(1 + second_min_x as isize))
+                        .max(first_min_x as isize);
                     node.first.cols = cols as usize;
                     node.first.pixel_width =
                         node.first.cols.saturating_mul(cell_dimensions.pixel_width);
@@ -1111,8 +1190,8 @@ impl Tab {
                     let mut rows = node.first.rows as isize;
                     rows = rows
                         .saturating_add(delta)
-                        .max(1)
-                        .min((height as isize).saturating_sub(2));
+                        .min((height as isize).saturating_sub(1 + second_min_y as isize))
+                        .max(first_min_y as isize);
                     node.first.rows = rows as usize;
                     node.first.pixel_height =
                         node.first.rows.saturating_mul(cell_dimensions.pixel_height);
@@ -1232,6 +1311,77 @@ impl Tab {
         }
     }
 
+    /// Resizes the pane at pane_index so that it has the specified
+    /// number of columns and/or rows, by moving the nearest split
+    /// along each axis.  The size is limited by the minimum size of
+    /// the panes on the other side of that split.
+    pub fn resize_pane(&self, pane_index: usize, cols: Option<usize>, rows: Option<usize>) {
+        if let Some(cols) = cols {
+            self.resize_pane_along(pane_index, SplitDirection::Horizontal, cols);
+        }
+        if let Some(rows) = rows {
+            self.resize_pane_along(pane_index, SplitDirection::Vertical, rows);
+        }
+    }
+
+    fn resize_pane_along(&self, pane_index: usize, split_direction: SplitDirection, size: usize) {
+        if self.zoomed.borrow().is_some() {
+            return;
+        }
+        let mut root = self.pane.borrow_mut();
+        let mut cursor = root.take().unwrap().cursor();
+        let mut index = 0;
+
+        // Position cursor on the leaf
+        loop {
+            if cursor.is_leaf() {
+                if index == pane_index {
+                    break;
+                }
+                index += 1;
+            }
+            match cursor.preorder_next() {
+                Ok(c) => cursor = c,
+                Err(c) => {
+                    root.replace(c.tree());
+                    return;
+                }
+            }
+        }
+
+        // Go up to the nearest split along the desired axis.  Any splits
+        // in between are along the other axis, so the size of the side of
+        // the split that contains the pane is the size of the pane.
+        loop {
+            let is_second = cursor.is_right();
+            match cursor.go_up() {
+                Ok(mut c) => {
+                    if let Ok(Some(node)) = c.node_mut() {
+                        if node.direction == split_direction {
+                            let side = if is_second { node.second } else { node.first };
+                            let current = match split_direction {
+                                SplitDirection::Horizontal => side.cols,
+                                SplitDirection::Vertical => side.rows,
+                            };
+                            let delta = size as isize - current as isize;
+                            // Growing the second side means moving the
+                            // split towards the top/left
+                            let delta = if is_second { -delta } else { delta };
+                            self.adjust_node_at_cursor(&mut c, delta);
+                            self.cascade_size_from_cursor(root, c);
+                            return;
+                        }
+                    }
+                    cursor = c;
+                }
+                Err(c) => {
+                    root.replace(c.tree());
+                    return;
+                }
+            }
+        }
+    }
+
     /// Activate an adjacent pane in the specified direction.
     /// In cases where there are multiple adjacent panes in the
     /// intended direction, we take the pane that has the largest
@@ -1243,34 +1393,38 @@ impl Tab {
             }
             self.toggle_zoom();
         }
+        if !self.iter_panes().iter().any(|pane| pane.is_active) {
+            // No active pane somehow...
+            self.set_active_idx(0);
+            return;
+        }
+
+        if let Some(target) = self.get_pane_direction(direction) {
+            self.set_active_idx(target);
+        }
+    }
+
+    /// Returns the index of the pane that is in the specified
+    /// direction from the active pane, if any
+    pub fn get_pane_direction(&self, direction: PaneDirection) -> Option<usize> {
         let panes = self.iter_panes();
 
-        let active = match panes.iter().find(|pane| pane.is_active) {
-            Some(p) => p,
-            None => {
-                // No active pane somehow...
-                self.set_active_idx(0);
-                return;
-            }
-        };
+        let active = panes.iter().find(|pane| pane.is_active)?;
 
         if matches!(direction, PaneDirection::Next | PaneDirection::Prev) {
             let max_pane_id = panes.iter().map(|p| p.index).max().unwrap_or(active.index);
 
-            if direction == PaneDirection::Next {
-                self.set_active_idx(if active.index == max_pane_id {
+            return Some(if direction == PaneDirection::Next {
+                if active.index == max_pane_id {
                     0
                 } else {
                     active.index + 1
-                });
+                }
+            } else if active.index == 0 {
+                max_pane_id
             } else {
-                self.set_active_idx(if active.index == 0 {
-                    max_pane_id
-                } else {
-                    active.index - 1
-                });
-            }
-            return;
+                active.index - 1
+            });
         }
 
         let mut best = None;
@@ -1340,9 +1494,7 @@ impl Tab {
         }
         drop(recency);
 
-        if let Some((_, target)) = best.take() {
-            self.set_active_idx(target.index);
-        }
+        best.map(|(_, target)| target.index)
     }
 
     pub fn prune_dead_panes(&self) -> bool {
@@ -1732,6 +1884,23 @@ impl Tab {
         pane_index: usize,
         request: SplitRequest,
         pane: Rc<dyn Pane>,
+    ) -> anyhow::Result<usize> {
+        let mut zoom_new_pane = false;
+        let pane_index =
+            self.split_and_insert_impl(pane_index, request, pane, &mut zoom_new_pane)?;
+        if zoom_new_pane {
+            // The new pane is active
+            self.set_zoomed(true);
+        }
+        Ok(pane_index)
+    }
+
+    fn split_and_insert_impl(
+        &self,
+        pane_index: usize,
+        request: SplitRequest,
+        pane: Rc<dyn Pane>,
+        zoom_new_pane: &mut bool,
     ) -> anyhow::Result<usize> {
         if self.zoomed.borrow().is_some() {
             anyhow::bail!("cannot split while zoomed");
@@ -1764,6 +1933,27 @@ impl Tab {
                 anyhow::bail!("No space for split!");
             }
 
+            let (min_cols, min_rows) = configured_min_pane_size();
+            let too_small = match split_info.direction {
+                SplitDirection::Horizontal => {
+                    split_info.first.cols < min_cols || split_info.second.cols < min_cols
+                }
+                SplitDirection::Vertical => {
+                    split_info.first.rows < min_rows || split_info.second.rows < min_rows
+                }
+            };
+            if too_small {
+                match configuration().pane_too_small_action {
+                    PaneTooSmallAction::Refuse => anyhow::bail!(
+                        "No space for split: the panes would be smaller than \
+                         pane_minimum_cols={} x pane_minimum_rows={}",
+                        min_cols,
+                        min_rows
+                    ),
+                    PaneTooSmallAction::Zoom => *zoom_new_pane = true,
+                }
+            }
+
             let needs_resize = if request.top_level {
                 self.pane.borrow().as_ref().unwrap().num_leaves() > 1
             } else {
@@ -1779,6 +1969,8 @@ impl Tab {
                 } else {
                     self.resize(split_info.second.clone());
                 }
+                // The resize may have zoomed a pane that it made too small
+                self.set_zoomed(false);
             }
 
             let mut root = self.pane.borrow_mut();
@@ -1898,6 +2090,23 @@ impl PaneNode {
         }
     }
 
+    /// Consumes the tree, returning its leaf entries in preorder
+    pub fn into_pane_entries(self) -> Vec<PaneEntry> {
+        let mut entries = vec![];
+        fn collect(node: PaneNode, entries: &mut Vec<PaneEntry>) {
+            match node {
+                PaneNode::Empty => {}
+                PaneNode::Split { left, right, .. } => {
+                    collect(*left, entries);
+                    collect(*right, entries);
+                }
+                PaneNode::Leaf(entry) => entries.push(entry),
+            }
+        }
+        collect(self, &mut entries);
+        entries
+    }
+
     pub fn window_and_tab_ids(&self) -> Option<(WindowId, TabId)> {
         match self {
             PaneNode::Empty => None,
@@ -2281,4 +2490,51 @@ mod test {
         assert_eq!(400, panes[2].pixel_width);
         assert_eq!(600, panes[2].pixel_height);
     }
+
+    #[test]
+    fn resize_pane_honors_minimum_sizes() {
+        let size = TerminalSize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+            dpi: 96,
+        };
+
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+
+        for (pane_index, pane_id) in [(0, 2), (1, 3)] {
+            let request = SplitRequest {
+                direction: SplitDirection::Horizontal,
+                ..Default::default()
+            };
+            let split_size = tab.compute_split_size(pane_index, request).unwrap();
+            tab.split_and_insert(
+                pane_index,
+                request,
+                FakePane::new(pane_id, split_size.second),
+            )
+            .unwrap();
+        }
+        let widths = || tab.iter_panes().iter().map(|p| p.width).collect::<Vec<_>>();
+        assert_eq!(widths(), vec![39, 19, 20]);
+
+        // The panes to the right give up space, keeping the size of
+        // the first of them where possible
+        tab.resize_pane(0, Some(60), None);
+        assert_eq!(widths(), vec![60, 17, 1]);
+
+        // Growing the second side of a split moves it towards the left
+        tab.resize_pane(2, Some(10), None);
+        assert_eq!(widths(), vec![60, 8, 10]);
+
+        // Each of the panes to the right keeps at least one cell
+        tab.resize_pane(0, Some(100), None);
+        assert_eq!(widths(), vec![76, 1, 1]);
+
+        // There is no split along the other axis to move
+        tab.resize_pane(0, None, Some(10));
+        assert_eq!(tab.iter_panes()[0].height, 24);
+    }
 }
@@ -0,0 +1,734 @@
+//! This module implements a pty system that connects panes to a TCP
+//! endpoint, optionally speaking the telnet protocol and/or wrapping
+//! the connection in TLS.
+//! As with the serial port implementation, there is no process on the
+//! other end of the connection, so only `CommandBuilder::new_default_prog`
+//! can be spawned into it.
+use anyhow::{anyhow, ensure, Context};
+use config::TcpDomain;
+use openssl::ssl::{SslConnector, SslMethod, SslStream};
+use portable_pty::{
+    Child, ChildKiller, CommandBuilder, ExitStatus, MasterPty, PtyPair, PtySize, PtySystem,
+    SlavePty,
+};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::io::{Read, Result as IoResult, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+const OPT_BINARY: u8 = 0;
+const OPT_ECHO: u8 = 1;
+const OPT_SGA: u8 = 3;
+const OPT_TTYPE: u8 = 24;
+const OPT_NAWS: u8 = 31;
+
+const TTYPE_IS: u8 = 0;
+const TTYPE_SEND: u8 = 1;
+
+/// How long a read on the socket may block.  This needs to be short
+/// because reads and writes share a lock on the stream; see the
+/// similar comment in the serial port implementation.
+const READ_TIMEOUT: Duration = Duration::from_millis(50);
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(SslStream<TcpStream>),
+}
+
+impl Stream {
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            Self::Plain(s) => s,
+            Self::Tls(s) => s.get_ref(),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        match self {
+            Self::Plain(s) => s.read(buf),
+            Self::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        match self {
+            Self::Plain(s) => s.write(buf),
+            Self::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        match self {
+            Self::Plain(s) => s.flush(),
+            Self::Tls(s) => s.flush(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseState {
+    Data,
+    Iac,
+    Negotiate(u8),
+    Sub,
+    SubIac,
+}
+
+/// Implements the client side of telnet option negotiation.
+/// We offer to report the terminal type and window size, and
+/// accept the server's offer to echo and suppress go-ahead.
+#[derive(Debug)]
+struct Telnet {
+    state: ParseState,
+    sub: Vec<u8>,
+    /// options that we have agreed to perform
+    local: HashSet<u8>,
+    /// options that the server has agreed to perform
+    remote: HashSet<u8>,
+    term: String,
+    size: PtySize,
+}
+
+fn push_escaped(out: &mut Vec<u8>, byte: u8) {
+    out.push(byte);
+    if byte == IAC {
+        out.push(IAC);
+    }
+}
+
+impl Telnet {
+    fn new(term: &str, size: PtySize) -> Self {
+        Self {
+            state: ParseState::Data,
+            sub: vec![],
+            local: HashSet::new(),
+            remote: HashSet::new(),
+            term: term.to_string(),
+            size,
+        }
+    }
+
+    /// Whether the server has taken responsibility for echoing
+    fn remote_echo(&self) -> bool {
+        self.remote.contains(&OPT_ECHO)
+    }
+
+    fn naws(&self) -> Vec<u8> {
+        let mut out = vec![IAC, SB, OPT_NAWS];
+        for value in [self.size.cols, self.size.rows] {
+            push_escaped(&mut out, (value >> 8) as u8);
+            push_escaped(&mut out, (value & 0xff) as u8);
+        }
+        out.extend_from_slice(&[IAC, SE]);
+        out
+    }
+
+    /// Records the new size, returning the notification to send
+    /// to the server, if it asked to be told about the size
+    fn resize(&mut self, size: PtySize) -> Option<Vec<u8>> {
+        self.size = size;
+        if self.local.contains(&OPT_NAWS) {
+            Some(self.naws())
+        } else {
+            None
+        }
+    }
+
+    fn negotiate(&mut self, command: u8, option: u8, reply: &mut Vec<u8>) {
+        match command {
+            DO => {
+                if matches!(option, OPT_TTYPE | OPT_NAWS | OPT_BINARY) {
+                    if self.local.insert(option) {
+                        reply.extend_from_slice(&[IAC, WILL, option]);
+                    }
+                    if option == OPT_NAWS {
+                        reply.extend_from_slice(&self.naws());
+                    }
+                } else {
+                    reply.extend_from_slice(&[IAC, WONT, option]);
+                }
+            }
+            DONT => {
+                if self.local.remove(&option) {
+                    reply.extend_from_slice(&[IAC, WONT, option]);
+                }
+            }
+            WILL => {
+                if matches!(option, OPT_ECHO | OPT_SGA | OPT_BINARY) {
+                    if self.remote.insert(option) {
+                        reply.extend_from_slice(&[IAC, DO, option]);
+                    }
+                } else {
+                    reply.extend_from_slice(&[IAC, DONT, option]);
+                }
+            }
+            WONT => {
+                if self.remote.remove(&option) {
+                    reply.extend_from_slice(&[IAC, DONT, option]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn subnegotiate(&mut self, reply: &mut Vec<u8>) {
+        if self.sub.as_slice() == [OPT_TTYPE, TTYPE_SEND] {
+            reply.extend_from_slice(&[IAC, SB, OPT_TTYPE, TTYPE_IS]);
+            reply.extend_from_slice(self.term.as_bytes());
+            reply.extend_from_slice(&[IAC, SE]);
+        }
+        self.sub.clear();
+    }
+
+    /// Separates the data received from the server from the telnet
+    /// commands embedded in it.  The data is appended to `data` and
+    /// any responses that should be sent to the server to `reply`.
+    fn parse(&mut self, input: &[u8], data: &mut Vec<u8>, reply: &mut Vec<u8>) {
+        for &byte in input {
+            self.state = match (self.state, byte) {
+                (ParseState::Data, IAC) => ParseState::Iac,
+                (ParseState::Data, b) => {
+                    data.push(b);
+                    ParseState::Data
+                }
+                (ParseState::Iac, IAC) => {
+                    data.push(IAC);
+                    ParseState::Data
+                }
+                (ParseState::Iac, DO | DONT | WILL | WONT) => ParseState::Negotiate(byte),
+                (ParseState::Iac, SB) => ParseState::Sub,
+                // NOP, GA and the other commands have no effect on us
+                (ParseState::Iac, _) => ParseState::Data,
+                (ParseState::Negotiate(command), option) => {
+                    self.negotiate(command, option, reply);
+                    ParseState::Data
+                }
+                (ParseState::Sub, IAC) => ParseState::SubIac,
+                (ParseState::Sub, b) => {
+                    self.sub.push(b);
+                    ParseState::Sub
+                }
+                (ParseState::SubIac, SE) => {
+                    self.subnegotiate(reply);
+                    ParseState::Data
+                }
+                (ParseState::SubIac, b) => {
+                    self.sub.push(b);
+                    ParseState::Sub
+                }
+            };
+        }
+    }
+
+    /// Encodes data that is to be sent to the server.
+    /// CR is sent as CR LF, as required by the network virtual terminal,
+    /// unless binary mode has been negotiated.
+    fn encode(&self, input: &[u8]) -> Vec<u8> {
+        let binary = self.local.contains(&OPT_BINARY);
+        let mut out = Vec::with_capacity(input.len());
+        for &byte in input {
+            push_escaped(&mut out, byte);
+            if byte == b'\r' && !binary {
+                out.push(b'\n');
+            }
+        }
+        out
+    }
+}
+
+/// Produces the text to display when echoing locally
+fn local_echo(input: &[u8], echo: &mut Vec<u8>) {
+    for &byte in input {
+        match byte {
+            b'\r' => echo.extend_from_slice(b"\r\n"),
+            0x08 | 0x7f => echo.extend_from_slice(b"\x08 \x08"),
+            b if b < 0x20 => {}
+            b => echo.push(b),
+        }
+    }
+}
+
+struct Shared {
+    stream: Mutex<Stream>,
+    telnet: Option<Mutex<Telnet>>,
+    /// Locally echoed input that is waiting to be returned by the reader
+    echo: Mutex<Vec<u8>>,
+    closed: Mutex<bool>,
+}
+
+impl Shared {
+    fn send(&self, data: &[u8]) -> IoResult<()> {
+        let mut stream = self.stream.lock().unwrap();
+        stream.write_all(data)?;
+        stream.flush()
+    }
+
+    fn close(&self) {
+        *self.closed.lock().unwrap() = true;
+        let _ = self.stream.lock().unwrap().tcp().shutdown(Shutdown::Both);
+    }
+
+    fn is_closed(&self) -> bool {
+        *self.closed.lock().unwrap()
+    }
+}
+
+pub struct TcpTty {
+    domain: TcpDomain,
+}
+
+impl TcpTty {
+    pub fn new(domain: TcpDomain) -> Self {
+        Self { domain }
+    }
+
+    /// Returns the host:port to connect to, applying the telnet
+    /// default port when none was specified
+    fn address(&self) -> String {
+        let address = &self.domain.remote_address;
+        let has_port = match address.rfind(']') {
+            Some(bracket) => address[bracket..].contains(':'),
+            None => address.matches(':').count() == 1,
+        };
+        if !has_port && self.domain.telnet {
+            if address.contains(':') && !address.starts_with('[') {
+                format!("[{}]:23", address)
+            } else {
+                format!("{}:23", address)
+            }
+        } else {
+            address.to_string()
+        }
+    }
+
+    fn connect(&self) -> anyhow::Result<Stream> {
+        let address = self.address();
+        let mut last_err = None;
+        let mut tcp = None;
+        for addr in address
+            .to_socket_addrs()
+            .with_context(|| format!("resolving {}", address))?
+        {
+            match TcpStream::connect_timeout(&addr, self.domain.connect_timeout) {
+                Ok(stream) => {
+                    tcp.replace(stream);
+                    break;
+                }
+                Err(err) => last_err = Some(err),
+            }
+        }
+        let tcp = match (tcp, last_err) {
+            (Some(tcp), _) => tcp,
+            (None, Some(err)) => {
+                return Err(err).with_context(|| format!("connecting to {}", address))
+            }
+            (None, None) => anyhow::bail!("{} did not resolve to any addresses", address),
+        };
+        tcp.set_nodelay(true)?;
+        tcp.set_read_timeout(Some(READ_TIMEOUT))?;
+
+        if !self.domain.tls {
+            return Ok(Stream::Plain(tcp));
+        }
+
+        let mut connector = SslConnector::builder(SslMethod::tls())?;
+        if let Some(ca) = &self.domain.pem_ca {
+            connector
+                .set_ca_file(ca)
+                .with_context(|| format!("loading CA file {}", ca.display()))?;
+        }
+        let connector = connector.build();
+        let config = connector
+            .configure()?
+            .verify_hostname(!self.domain.accept_invalid_hostnames);
+
+        let host = address
+            .rsplit_once(':')
+            .map(|(host, _port)| host)
+            .unwrap_or(&address)
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+
+        // The handshake must not be subject to the short read timeout
+        tcp.set_read_timeout(None)?;
+        let stream = config
+            .connect(host, tcp)
+            .map_err(|err| anyhow!("TLS handshake with {}: {}", address, err))?;
+        stream.get_ref().set_read_timeout(Some(READ_TIMEOUT))?;
+        Ok(Stream::Tls(stream))
+    }
+}
+
+impl PtySystem for TcpTty {
+    fn openpty(&self, size: PtySize) -> anyhow::Result<PtyPair> {
+        let stream = self.connect()?;
+        let shared = Arc::new(Shared {
+            stream: Mutex::new(stream),
+            telnet: if self.domain.telnet {
+                Some(Mutex::new(Telnet::new(&self.domain.term, size)))
+            } else {
+                None
+            },
+            echo: Mutex::new(vec![]),
+            closed: Mutex::new(false),
+        });
+
+        Ok(PtyPair {
+            slave: Box::new(Slave {
+                shared: Arc::clone(&shared),
+            }),
+            master: Box::new(Master {
+                shared,
+                size: Mutex::new(size),
+                took_writer: RefCell::new(false),
+            }),
+        })
+    }
+}
+
+struct Slave {
+    shared: Arc<Shared>,
+}
+
+impl SlavePty for Slave {
+    fn spawn_command(&self, cmd: CommandBuilder) -> anyhow::Result<Box<dyn Child + Send + Sync>> {
+        ensure!(
+            cmd.is_default_prog(),
+            "can only use default prog commands with tcp domains"
+        );
+        Ok(Box::new(TcpChild {
+            shared: Arc::clone(&self.shared),
+        }))
+    }
+}
+
+/// There isn't a child process on the end of the connection; the
+/// "child" is considered to have exited once the connection is closed
+struct TcpChild {
+    shared: Arc<Shared>,
+}
+
+// An anemic impl of Debug to satisfy some indirect trait bounds
+impl std::fmt::Debug for TcpChild {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("TcpChild").finish()
+    }
+}
+
+impl Child for TcpChild {
+    fn try_wait(&mut self) -> IoResult<Option<ExitStatus>> {
+        if self.shared.is_closed() {
+            Ok(Some(ExitStatus::with_exit_code(0)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn wait(&mut self) -> IoResult<ExitStatus> {
+        while !self.shared.is_closed() {
+            std::thread::sleep(Duration::from_millis(500));
+        }
+        Ok(ExitStatus::with_exit_code(0))
+    }
+
+    fn process_id(&self) -> Option<u32> {
+        None
+    }
+
+    #[cfg(windows)]
+    fn as_raw_handle(&self) -> Option<std::os::windows::io::RawHandle> {
+        None
+    }
+}
+
+impl ChildKiller for TcpChild {
+    fn kill(&mut self) -> IoResult<()> {
+        self.shared.close();
+        Ok(())
+    }
+
+    fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
+        Box::new(TcpChildKiller {
+            shared: Arc::clone(&self.shared),
+        })
+    }
+}
+
+struct TcpChildKiller {
+    shared: Arc<Shared>,
+}
+
+impl std::fmt::Debug for TcpChildKiller {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("TcpChildKiller").finish()
+    }
+}
+
+impl ChildKiller for TcpChildKiller {
+    fn kill(&mut self) -> IoResult<()> {
+        self.shared.close();
+        Ok(())
+    }
+
+    fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
+        Box::new(TcpChildKiller {
+            shared: Arc::clone(&self.shared),
+        })
+    }
+}
+
+struct Master {
+    shared: Arc<Shared>,
+    size: Mutex<PtySize>,
+    took_writer: RefCell<bool>,
+}
+
+impl MasterPty for Master {
+    fn resize(&self, size: PtySize) -> anyhow::Result<()> {
+        *self.size.lock().unwrap() = size;
+        let notification = match &self.shared.telnet {
+            Some(telnet) => telnet.lock().unwrap().resize(size),
+            None => None,
+        };
+        if let Some(notification) = notification {
+            self.shared.send(&notification)?;
+        }
+        Ok(())
+    }
+
+    fn get_size(&self) -> anyhow::Result<PtySize> {
+        Ok(*self.size.lock().unwrap())
+    }
+
+    fn try_clone_reader(&self) -> anyhow::Result<Box<dyn std::io::Read + Send>> {
+        Ok(Box::new(Reader {
+            shared: Arc::clone(&self.shared),
+            pending: vec![],
+        }))
+    }
+
+    fn take_writer(&self) -> anyhow::Result<Box<dyn std::io::Write + Send>> {
+        if *self.took_writer.borrow() {
+            anyhow::bail!("cannot take writer more than once");
+        }
+        *self.took_writer.borrow_mut() = true;
+        Ok(Box::new(Writer {
+            shared: Arc::clone(&self.shared),
+        }))
+    }
+
+    #[cfg(unix)]
+    fn process_group_leader(&self) -> Option<libc::pid_t> {
+        // N/A: there is no local process
+        None
+    }
+}
+
+struct Writer {
+    shared: Arc<Shared>,
+}
+
+impl Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        match &self.shared.telnet {
+            Some(telnet) => {
+                let (data, echo) = {
+                    let telnet = telnet.lock().unwrap();
+                    (telnet.encode(buf), !telnet.remote_echo())
+                };
+                if echo {
+                    local_echo(buf, &mut self.shared.echo.lock().unwrap());
+                }
+                self.shared.send(&data)?;
+            }
+            None => self.shared.send(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.shared.stream.lock().unwrap().flush()
+    }
+}
+
+struct Reader {
+    shared: Arc<Shared>,
+    /// Data that has been decoded but did not fit into the
+    /// caller's buffer
+    pending: Vec<u8>,
+}
+
+impl Reader {
+    fn take_pending(&mut self, buf: &mut [u8]) -> usize {
+        let len = self.pending.len().min(buf.len());
+        buf[..len].copy_from_slice(&self.pending[..len]);
+        self.pending.drain(..len);
+        len
+    }
+}
+
+impl Read for Reader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let mut raw = [0u8; 4096];
+        loop {
+            if !self.pending.is_empty() {
+                return Ok(self.take_pending(buf));
+            }
+            self.pending.append(&mut self.shared.echo.lock().unwrap());
+            if !self.pending.is_empty() {
+                continue;
+            }
+
+            let result = self.shared.stream.lock().unwrap().read(&mut raw);
+            let size = match result {
+                Ok(0) => {
+                    self.shared.close();
+                    return Ok(0);
+                }
+                Ok(size) => size,
+                Err(e)
+                    if e.kind() == std::io::ErrorKind::WouldBlock
+                        || e.kind() == std::io::ErrorKind::TimedOut =>
+                {
+                    if self.shared.is_closed() {
+                        return Ok(0);
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    log::error!("tcp read error: {}", e);
+                    self.shared.close();
+                    return Err(e);
+                }
+            };
+
+            match &self.shared.telnet {
+                Some(telnet) => {
+                    let mut reply = vec![];
+                    telnet
+                        .lock()
+                        .unwrap()
+                        .parse(&raw[..size], &mut self.pending, &mut reply);
+                    if !reply.is_empty() {
+                        self.shared.send(&reply)?;
+                    }
+                }
+                None => self.pending.extend_from_slice(&raw[..size]),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn size(cols: u16, rows: u16) -> PtySize {
+        PtySize {
+            cols,
+            rows,
+            pixel_width: 0,
+            pixel_height: 0,
+        }
+    }
+
+    #[test]
+    fn parse_data_and_escaped_iac() {
+        let mut telnet = Telnet::new("xterm", size(80, 24));
+        let mut data = vec![];
+        let mut reply = vec![];
+        telnet.parse(b"hello\xff\xffworld", &mut data, &mut reply);
+        assert_eq!(data, b"hello\xffworld");
+        assert!(reply.is_empty());
+    }
+
+    #[test]
+    fn negotiation() {
+        let mut telnet = Telnet::new("xterm", size(80, 24));
+        let mut data = vec![];
+        let mut reply = vec![];
+
+        telnet.parse(&[IAC, WILL, OPT_ECHO, IAC, DO, 42], &mut data, &mut reply);
+        assert!(data.is_empty());
+        assert_eq!(reply, vec![IAC, DO, OPT_ECHO, IAC, WONT, 42]);
+        assert!(telnet.remote_echo());
+
+        // A repeated offer is not acknowledged again
+        reply.clear();
+        telnet.parse(&[IAC, WILL, OPT_ECHO], &mut data, &mut reply);
+        assert!(reply.is_empty());
+
+        reply.clear();
+        telnet.parse(&[IAC, WONT, OPT_ECHO], &mut data, &mut reply);
+        assert_eq!(reply, vec![IAC, DONT, OPT_ECHO]);
+        assert!(!telnet.remote_echo());
+    }
+
+    #[test]
+    fn naws_and_ttype() {
+        let mut telnet = Telnet::new("xterm", size(300, 24));
+        let mut data = vec![];
+        let mut reply = vec![];
+
+        telnet.parse(&[IAC, DO, OPT_NAWS], &mut data, &mut reply);
+        assert_eq!(
+            reply,
+            vec![IAC, WILL, OPT_NAWS, IAC, SB, OPT_NAWS, 1, 44, 0, 24, IAC, SE]
+        );
+
+        // 255 columns must be escaped in the size report
+        assert_eq!(
+            telnet.resize(size(255, 24)).unwrap(),
+            vec![IAC, SB, OPT_NAWS, 0, IAC, IAC, 0, 24, IAC, SE]
+        );
+
+        reply.clear();
+        telnet.parse(
+            &[IAC, SB, OPT_TTYPE, TTYPE_SEND, IAC, SE],
+            &mut data,
+            &mut reply,
+        );
+        let mut expected = vec![IAC, SB, OPT_TTYPE, TTYPE_IS];
+        expected.extend_from_slice(b"xterm");
+        expected.extend_from_slice(&[IAC, SE]);
+        assert_eq!(reply, expected);
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn encode() {
+        let telnet = Telnet::new("xterm", size(80, 24));
+        assert_eq!(telnet.encode(b"ls\r\xff"), b"ls\r\n\xff\xff");
+    }
+
+    #[test]
+    fn default_telnet_port() {
+        let tty = |remote_address: &str, telnet| {
+            TcpTty::new(TcpDomain {
+                remote_address: remote_address.to_string(),
+                telnet,
+                ..TcpDomain::default()
+            })
+            .address()
+        };
+        assert_eq!(tty("mud.example.com", true), "mud.example.com:23");
+        assert_eq!(tty("mud.example.com:4000", true), "mud.example.com:4000");
+        assert_eq!(tty("::1", true), "[::1]:23");
+        assert_eq!(tty("[::1]:4000", true), "[::1]:4000");
+        assert_eq!(tty("router:7000", false), "router:7000");
+    }
+}
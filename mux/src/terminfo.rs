@@ -0,0 +1,45 @@
+//! Installs the terminfo entry bundled with wezterm on remote hosts,
+//! so that `term = "wezterm"` works in ssh domains without any
+//! manual setup.
+use anyhow::Context;
+use std::io::Write;
+use wezterm_ssh::Session;
+
+/// The source of the `wezterm` terminfo entry
+pub const WEZTERM_TERMINFO: &str = include_str!("../../termwiz/data/wezterm.terminfo");
+
+/// Compiles the terminfo source that is read from stdin into
+/// `~/.terminfo`, unless the host already knows about `wezterm`.
+/// This is run via `sh -c` so that it doesn't matter which shell
+/// the remote user has.
+const REMOTE_INSTALL_SCRIPT: &str = "sh -c '\
+    infocmp wezterm >/dev/null 2>&1 && exit 0; \
+    command -v tic >/dev/null 2>&1 || exit 1; \
+    t=$(mktemp) || exit 1; \
+    cat >\"$t\"; \
+    tic -x -o \"$HOME/.terminfo\" \"$t\"; r=$?; \
+    rm -f \"$t\"; \
+    exit $r'";
+
+/// Installs the `wezterm` terminfo entry on the host at the other
+/// end of `session`, if it isn't already present there.
+pub async fn install_remote_terminfo(session: &Session) -> anyhow::Result<()> {
+    let mut exec = session
+        .exec(REMOTE_INSTALL_SCRIPT, None)
+        .await
+        .context("running terminfo installation script")?;
+
+    // The script doesn't read its input if the entry is already
+    // installed, so a write error here isn't interesting
+    exec.stdin.write_all(WEZTERM_TERMINFO.as_bytes()).ok();
+    drop(exec.stdin);
+
+    let status = exec.child.async_wait().await?;
+    if !status.success() {
+        anyhow::bail!(
+            "failed to install the wezterm terminfo entry on the remote host; \
+             is tic installed there?"
+        );
+    }
+    Ok(())
+}
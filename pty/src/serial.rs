@@ -16,7 +16,9 @@ use serial::{
 };
 use std::cell::RefCell;
 use std::ffi::{OsStr, OsString};
+use std::fs::File;
 use std::io::{Read, Result as IoResult, Write};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
@@ -29,6 +31,8 @@ pub struct SerialTty {
     parity: Parity,
     stop_bits: StopBits,
     flow_control: FlowControl,
+    capture: Option<PathBuf>,
+    control: SerialPortControl,
 }
 
 impl SerialTty {
@@ -40,9 +44,23 @@ impl SerialTty {
             parity: Parity::ParityNone,
             stop_bits: StopBits::Stop1,
             flow_control: FlowControl::FlowSoftware,
+            capture: None,
+            control: SerialPortControl::default(),
         }
     }
 
+    /// Returns a handle that can be used to adjust the port settings
+    /// and modem control lines after the port has been opened
+    pub fn control(&self) -> SerialPortControl {
+        self.control.clone()
+    }
+
+    /// Arrange for all data received from the port to be appended
+    /// to the specified file
+    pub fn set_capture_file<P: AsRef<Path>>(&mut self, path: P) {
+        self.capture.replace(path.as_ref().to_path_buf());
+    }
+
     pub fn set_baud_rate(&mut self, baud: BaudRate) {
         self.baud = baud;
     }
@@ -87,6 +105,18 @@ impl PtySystem for SerialTty {
         port.set_timeout(Duration::from_millis(50))?;
 
         let port: Handle = Arc::new(Mutex::new(port));
+        self.control.attach(&self.port, &port, settings);
+
+        let capture = match &self.capture {
+            Some(path) => Some(Arc::new(Mutex::new(
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("opening serial capture file {}", path.display()))?,
+            ))),
+            None => None,
+        };
 
         Ok(PtyPair {
             slave: Box::new(Slave {
@@ -94,6 +124,7 @@ impl PtySystem for SerialTty {
             }),
             master: Box::new(Master {
                 port,
+                capture,
                 took_writer: RefCell::new(false),
             }),
         })
@@ -190,6 +221,7 @@ impl ChildKiller for SerialChildKiller {
 
 struct Master {
     port: Handle,
+    capture: Option<Arc<Mutex<File>>>,
     took_writer: RefCell<bool>,
 }
 
@@ -223,7 +255,10 @@ impl MasterPty for Master {
         // that expose the underlying file descriptor, and that direct
         // reads from that return the raw data that we want
         let fd = FileDescriptor::dup(&*self.port.lock().unwrap())?;
-        Ok(Box::new(Reader { fd }))
+        Ok(Box::new(Reader {
+            fd,
+            capture: self.capture.clone(),
+        }))
     }
 
     fn take_writer(&self) -> anyhow::Result<Box<dyn std::io::Write + Send>> {
@@ -244,6 +279,7 @@ impl MasterPty for Master {
 
 struct Reader {
     fd: FileDescriptor,
+    capture: Option<Arc<Mutex<File>>>,
 }
 
 impl Read for Reader {
@@ -279,6 +315,12 @@ impl Read for Reader {
                     ));
                 }
                 Ok(size) => {
+                    if let Some(capture) = &self.capture {
+                        let mut file = capture.lock().unwrap();
+                        if let Err(err) = file.write_all(&buf[..size]) {
+                            log::error!("writing to serial capture file: {:#}", err);
+                        }
+                    }
                     return Ok(size);
                 }
                 Err(e) => {
@@ -292,3 +334,192 @@ impl Read for Reader {
         }
     }
 }
+
+#[derive(Default)]
+struct ControlState {
+    port_name: OsString,
+    port: Option<Handle>,
+    settings: Option<PortSettings>,
+    dtr: bool,
+    rts: bool,
+}
+
+/// A clonable handle that allows changing the settings of an opened
+/// serial port at runtime
+#[derive(Clone, Default)]
+pub struct SerialPortControl {
+    state: Arc<Mutex<ControlState>>,
+}
+
+impl SerialPortControl {
+    fn attach(&self, name: &OsStr, port: &Handle, settings: PortSettings) {
+        let mut state = self.state.lock().unwrap();
+        state.port_name = name.to_owned();
+        state.port.replace(Arc::clone(port));
+        state.settings.replace(settings);
+        // Opening the port asserts both lines
+        state.dtr = true;
+        state.rts = true;
+    }
+
+    fn with_port<F>(&self, func: F) -> anyhow::Result<()>
+    where
+        F: FnOnce(&mut ControlState, &mut SystemPort) -> anyhow::Result<()>,
+    {
+        let mut state = self.state.lock().unwrap();
+        let port = state
+            .port
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("serial port has not been opened"))?;
+        let mut port = port.lock().unwrap();
+        func(&mut state, &mut port)
+    }
+
+    pub fn set_baud_rate(&self, baud: BaudRate) -> anyhow::Result<()> {
+        self.with_port(|state, port| {
+            port.reconfigure(&|settings| settings.set_baud_rate(baud))?;
+            if let Some(settings) = state.settings.as_mut() {
+                settings.baud_rate = baud;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn set_flow_control(&self, flow_control: FlowControl) -> anyhow::Result<()> {
+        self.with_port(|state, port| {
+            port.reconfigure(&|settings| {
+                settings.set_flow_control(flow_control);
+                Ok(())
+            })?;
+            if let Some(settings) = state.settings.as_mut() {
+                settings.flow_control = flow_control;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn set_dtr(&self, level: bool) -> anyhow::Result<()> {
+        self.with_port(|state, port| {
+            port.set_dtr(level)?;
+            state.dtr = level;
+            Ok(())
+        })
+    }
+
+    pub fn set_rts(&self, level: bool) -> anyhow::Result<()> {
+        self.with_port(|state, port| {
+            port.set_rts(level)?;
+            state.rts = level;
+            Ok(())
+        })
+    }
+
+    /// Inverts the DTR line, returning its new level
+    pub fn toggle_dtr(&self) -> anyhow::Result<bool> {
+        let level = !self.state.lock().unwrap().dtr;
+        self.set_dtr(level)?;
+        Ok(level)
+    }
+
+    /// Inverts the RTS line, returning its new level
+    pub fn toggle_rts(&self) -> anyhow::Result<bool> {
+        let level = !self.state.lock().unwrap().rts;
+        self.set_rts(level)?;
+        Ok(level)
+    }
+
+    /// Returns a short human readable summary of the current settings,
+    /// such as `/dev/ttyUSB0 115200 8N1 flow=none DTR RTS`
+    pub fn describe(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let settings = match &state.settings {
+            Some(settings) => settings,
+            None => return format!("{} (not open)", state.port_name.to_string_lossy()),
+        };
+        let bits = match settings.char_size {
+            CharSize::Bits5 => 5,
+            CharSize::Bits6 => 6,
+            CharSize::Bits7 => 7,
+            CharSize::Bits8 => 8,
+        };
+        let parity = match settings.parity {
+            Parity::ParityNone => 'N',
+            Parity::ParityOdd => 'O',
+            Parity::ParityEven => 'E',
+        };
+        let stop = match settings.stop_bits {
+            StopBits::Stop1 => 1,
+            StopBits::Stop2 => 2,
+        };
+        let flow = match settings.flow_control {
+            FlowControl::FlowNone => "none",
+            FlowControl::FlowSoftware => "software",
+            FlowControl::FlowHardware => "hardware",
+        };
+        format!(
+            "{} {} {}{}{} flow={}{}{}",
+            state.port_name.to_string_lossy(),
+            settings.baud_rate.speed(),
+            bits,
+            parity,
+            stop,
+            flow,
+            if state.dtr { " DTR" } else { "" },
+            if state.rts { " RTS" } else { "" },
+        )
+    }
+}
+
+/// Returns the names of the serial ports that appear to be present
+/// on this system
+pub fn available_ports() -> Vec<String> {
+    let mut ports = vec![];
+
+    #[cfg(target_os = "linux")]
+    if let Ok(entries) = std::fs::read_dir("/sys/class/tty") {
+        for entry in entries.flatten() {
+            // Only entries that are backed by a real device are
+            // interesting; the kernel creates a great many ttyS
+            // nodes that have nothing behind them.
+            if !entry.path().join("device/driver").exists() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with("ttyS") {
+                // Legacy 8250 ports report a driver even when no
+                // hardware is present; skip those without an irq
+                let irq = std::fs::read_to_string(entry.path().join("irq")).unwrap_or_default();
+                if irq.trim() == "0" {
+                    continue;
+                }
+            }
+            ports.push(format!("/dev/{}", name));
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    if let Ok(entries) = std::fs::read_dir("/dev") {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with("cu.") {
+                ports.push(format!("/dev/{}", name));
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        use winreg::enums::HKEY_LOCAL_MACHINE;
+        use winreg::RegKey;
+        if let Ok(key) =
+            RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey("HARDWARE\\DEVICEMAP\\SERIALCOMM")
+        {
+            for (_name, value) in key.enum_values().flatten() {
+                ports.push(value.to_string());
+            }
+        }
+    }
+
+    ports.sort();
+    ports
+}
@@ -1,27 +1,83 @@
 use crate::cmdbuilder::CommandBuilder;
-use crate::win::psuedocon::PsuedoCon;
+use crate::win::psuedocon::{
+    PsuedoCon, PSEUDOCONSOLE_PASSTHROUGH_MODE, PSEUDOCONSOLE_RESIZE_QUIRK,
+    PSEUDOCONSOLE_WIN32_INPUT_MODE,
+};
 use crate::{Child, MasterPty, PtyPair, PtySize, PtySystem, SlavePty};
 use anyhow::Error;
 use filedescriptor::{FileDescriptor, Pipe};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use winapi::um::wincon::COORD;
 
 #[derive(Default)]
-pub struct ConPtySystem {}
+pub struct ConPtySystem {
+    passthrough: AtomicBool,
+    /// Set when conpty refused to create a console in passthrough mode
+    passthrough_unsupported: AtomicBool,
+}
+
+impl ConPtySystem {
+    /// Controls whether subsequently opened ptys request passthrough
+    /// mode, in which the VT sequences that are output by the hosted
+    /// application are passed through unmodified, rather than being
+    /// rendered into the console buffer and re-encoded by conpty.
+    /// Passthrough mode requires a version of conpty that supports it;
+    /// when it is unavailable, the regular mode is used instead.
+    pub fn set_passthrough(&self, passthrough: bool) {
+        self.passthrough.store(passthrough, Ordering::Relaxed);
+    }
+
+    /// Returns true if ptys are opened in passthrough mode; that is,
+    /// if it was requested and the system hasn't refused it
+    pub fn passthrough(&self) -> bool {
+        self.passthrough.load(Ordering::Relaxed)
+            && !self.passthrough_unsupported.load(Ordering::Relaxed)
+    }
+}
+
+/// Converts a pty size to the console coordinates used by conpty,
+/// which must be at least 1x1 and fit in an i16
+fn size_to_coord(size: &PtySize) -> COORD {
+    COORD {
+        X: size.cols.clamp(1, i16::MAX as u16) as i16,
+        Y: size.rows.clamp(1, i16::MAX as u16) as i16,
+    }
+}
 
 impl PtySystem for ConPtySystem {
     fn openpty(&self, size: PtySize) -> anyhow::Result<PtyPair> {
         let stdin = Pipe::new()?;
         let stdout = Pipe::new()?;
 
-        let con = PsuedoCon::new(
-            COORD {
-                X: size.cols as i16,
-                Y: size.rows as i16,
-            },
-            stdin.read,
-            stdout.write,
-        )?;
+        let flags = PSEUDOCONSOLE_RESIZE_QUIRK | PSEUDOCONSOLE_WIN32_INPUT_MODE;
+        let coord = size_to_coord(&size);
+        let con = if self.passthrough() {
+            match PsuedoCon::new(
+                coord,
+                &stdin.read,
+                &stdout.write,
+                flags | PSEUDOCONSOLE_PASSTHROUGH_MODE,
+            ) {
+                Ok(con) => con,
+                Err(err) => {
+                    log::warn!(
+                        "conpty passthrough mode is not available ({:#}), \
+                         falling back to the regular mode",
+                        err
+                    );
+                    self.passthrough_unsupported.store(true, Ordering::Relaxed);
+                    PsuedoCon::new(coord, &stdin.read, &stdout.write, flags)?
+                }
+            }
+        } else {
+            PsuedoCon::new(coord, &stdin.read, &stdout.write, flags)?
+        };
+
+        // The pseudo console has its own references to these
+        // ends of the pipes; we don't need them any more
+        drop(stdin.read);
+        drop(stdout.write);
 
         let master = ConPtyMasterPty {
             inner: Arc::new(Mutex::new(Inner {
@@ -58,16 +114,22 @@ impl Inner {
         pixel_width: u16,
         pixel_height: u16,
     ) -> Result<(), Error> {
-        self.con.resize(COORD {
-            X: num_cols as i16,
-            Y: num_rows as i16,
-        })?;
-        self.size = PtySize {
+        let size = PtySize {
             rows: num_rows,
             cols: num_cols,
             pixel_width,
             pixel_height,
         };
+        // conpty only knows about rows and columns.  Asking it to
+        // resize to its current size still causes it to repaint the
+        // screen, which disrupts win32 console applications that are
+        // in the middle of updating it, so skip those requests.
+        let coord = size_to_coord(&size);
+        let prior = size_to_coord(&self.size);
+        if (coord.X, coord.Y) != (prior.X, prior.Y) {
+            self.con.resize(coord)?;
+        }
+        self.size = size;
         Ok(())
     }
 }
@@ -175,6 +175,11 @@ pub trait TerminalConfiguration: std::fmt::Debug {
         false
     }
 
+    /// Whether ReGIS and Tektronix 4014 vector graphics are enabled
+    fn enable_vector_graphics(&self) -> bool {
+        false
+    }
+
     fn enable_kitty_keyboard(&self) -> bool {
         false
     }
@@ -199,6 +204,15 @@ pub trait TerminalConfiguration: std::fmt::Debug {
         false
     }
 
+    /// Whether printed text is stored as extended grapheme clusters,
+    /// one per cell, unless an escape sequence (DECSET 2027) has
+    /// changed the mode.  When false, each codepoint is stored in its
+    /// own cell, with zero width codepoints combined into the cell
+    /// that precedes them.
+    fn grapheme_clustering(&self) -> bool {
+        true
+    }
+
     fn debug_key_events(&self) -> bool {
         false
     }
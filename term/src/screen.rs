@@ -4,6 +4,7 @@ use crate::config::BidiMode;
 use log::debug;
 use std::collections::VecDeque;
 use std::sync::Arc;
+use termwiz::cell::UnicodeVersion;
 use termwiz::input::KeyboardEncoding;
 use termwiz::surface::SequenceNo;
 
@@ -958,6 +959,18 @@ impl Screen {
         func(&mut lines)
     }
 
+    /// Recomputes the cell widths of every line, including the
+    /// scrollback, for `unicode_version`
+    pub fn relayout_for_unicode_version(
+        &mut self,
+        unicode_version: UnicodeVersion,
+        seqno: SequenceNo,
+    ) {
+        for line in self.lines.iter_mut() {
+            line.relayout_for_unicode_version(unicode_version, seqno);
+        }
+    }
+
     pub fn for_each_phys_line<F>(&self, mut f: F)
     where
         F: FnMut(usize, &Line),
@@ -16,6 +16,14 @@ pub trait Clipboard {
         selection: ClipboardSelection,
         data: Option<String>,
     ) -> anyhow::Result<()>;
+
+    /// Called when the application has requested the contents of
+    /// the selection via OSC 52.  The implementation is responsible
+    /// for deciding whether to honor the request, and for sending
+    /// the response to the application.
+    fn query_contents(&self, _selection: ClipboardSelection) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
 
 impl Clipboard for Box<dyn Clipboard> {
@@ -26,6 +34,10 @@ impl Clipboard for Box<dyn Clipboard> {
     ) -> anyhow::Result<()> {
         self.as_ref().set_contents(selection, data)
     }
+
+    fn query_contents(&self, selection: ClipboardSelection) -> anyhow::Result<()> {
+        self.as_ref().query_contents(selection)
+    }
 }
 
 pub trait DeviceControlHandler {
@@ -59,6 +71,25 @@ pub enum Alert {
     /// When something bumps the seqno in the terminal model and
     /// the terminal is not focused
     OutputSinceFocusLost,
+    /// The expanded text of the iTerm2 badge has changed
+    BadgeChanged(Option<String>),
+    /// The application has asked for the attention of the user
+    RequestAttention(AttentionRequest),
+    /// The shell reported the exit status of a command via OSC 133;D
+    CommandFinished {
+        status: i32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum AttentionRequest {
+    /// Keep drawing attention to the window until it is focused
+    UntilFocused,
+    /// Draw attention to the window briefly
+    Once,
+    /// Cancel a previous request
+    Cancel,
 }
 
 pub trait AlertHandler {
@@ -69,6 +100,43 @@ pub trait DownloadHandler {
     fn save_to_downloads(&self, name: Option<String>, data: Vec<u8>);
 }
 
+/// The classes of escape sequences that can be restricted
+/// by an `EscapeSequencePermissions` implementation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EscapeSequenceKind {
+    /// OSC 52 requests to read the clipboard
+    ClipboardRead,
+    /// OSC 52 requests to set or clear the clipboard
+    ClipboardWrite,
+    /// OSC 0, 1 and 2 (and friends) that change the title
+    SetTitle,
+    /// Non-inline iTerm2 file transfers
+    FileTransfer,
+    /// Application program commands, such as the kitty image protocol
+    Apc,
+}
+
+impl EscapeSequenceKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ClipboardRead => "clipboard_read",
+            Self::ClipboardWrite => "clipboard_write",
+            Self::SetTitle => "set_title",
+            Self::FileTransfer => "file_transfer",
+            Self::Apc => "apc",
+        }
+    }
+}
+
+pub trait EscapeSequencePermissions {
+    /// Returns true if a sequence of the specified kind should be
+    /// honored.  Implementations that need to ask the user should
+    /// return false and arrange for the prompt to happen
+    /// asynchronously; the decision will apply to subsequent
+    /// sequences.
+    fn is_allowed(&self, kind: EscapeSequenceKind) -> bool;
+}
+
 /// Represents an instance of a terminal emulator.
 pub struct Terminal {
     /// The terminal model/state
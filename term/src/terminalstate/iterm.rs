@@ -1,14 +1,81 @@
+use crate::terminal::Alert;
 use crate::terminalstate::image::*;
-use crate::TerminalState;
+use crate::{EscapeSequenceKind, TerminalState};
 use ::image::imageops::FilterType;
 use ::image::ImageFormat;
 use log::error;
 use termwiz::escape::osc::ITermFileData;
 use termwiz::image::ImageDataType;
 
+/// Expands the `\(name)` interpolations in an iTerm2 badge format
+/// string, using `resolve` to look up the value of each variable.
+/// Unknown variables expand to nothing.
+fn expand_badge_format(format: &str, resolve: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::new();
+    let mut remain = format;
+    while let Some(start) = remain.find("\\(") {
+        result.push_str(&remain[..start]);
+        let after = &remain[start + 2..];
+        match after.find(')') {
+            Some(end) => {
+                if let Some(value) = resolve(after[..end].trim()) {
+                    result.push_str(&value);
+                }
+                remain = &after[end + 1..];
+            }
+            None => {
+                result.push_str(&remain[start..]);
+                remain = "";
+            }
+        }
+    }
+    result.push_str(remain);
+    result
+}
+
 impl TerminalState {
+    /// Returns the value of an iTerm2 session variable, as used by
+    /// badges and the ReportVariable escape sequence.  User variables
+    /// are available as `user.NAME`.
+    pub(crate) fn resolve_variable(&self, name: &str) -> Option<String> {
+        if let Some(name) = name.strip_prefix("user.") {
+            return self.user_vars.get(name).cloned();
+        }
+        let name = name.strip_prefix("session.").unwrap_or(name);
+        match name {
+            "name" | "title" => Some(self.get_title().to_string()),
+            "path" => self.current_dir.as_ref().map(|url| url.path().to_string()),
+            "hostname" => self
+                .current_dir
+                .as_ref()
+                .and_then(|url| url.host_str().map(|host| host.to_string())),
+            "columns" => Some(self.screen().physical_cols.to_string()),
+            "rows" => Some(self.screen().physical_rows.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Re-evaluates the badge format, and notifies the alert handler
+    /// if its expansion has changed
+    pub(crate) fn update_badge(&mut self) {
+        let badge = self
+            .badge_format
+            .as_ref()
+            .map(|format| expand_badge_format(format, |name| self.resolve_variable(name)))
+            .filter(|badge| !badge.is_empty());
+        if badge != self.badge {
+            self.badge = badge.clone();
+            if let Some(handler) = self.alert_handler.as_mut() {
+                handler.alert(Alert::BadgeChanged(badge));
+            }
+        }
+    }
+
     pub(crate) fn set_image(&mut self, image: ITermFileData) {
         if !image.inline {
+            if !self.escape_sequence_allowed(EscapeSequenceKind::FileTransfer) {
+                return;
+            }
             if let Some(handler) = &self.download_handler {
                 handler.save_to_downloads(image.name, image.data);
             } else {
@@ -21,7 +21,7 @@ use termwiz::image::ImageData;
 use termwiz::input::KeyboardEncoding;
 use termwiz::surface::{CursorShape, CursorVisibility, SequenceNo};
 use url::Url;
-use wezterm_bidi::ParagraphDirectionHint;
+use wezterm_bidi::{Direction, ParagraphDirectionHint};
 
 mod image;
 mod iterm;
@@ -30,8 +30,10 @@ mod kitty;
 mod mouse;
 pub(crate) mod performer;
 mod sixel;
+mod vector;
 use crate::terminalstate::image::*;
 use crate::terminalstate::kitty::*;
+use crate::terminalstate::vector::VectorGraphics;
 
 lazy_static::lazy_static! {
     static ref DB: Database = {
@@ -227,6 +229,26 @@ impl ScreenOrAlt {
         self.alt_screen_is_active
     }
 
+    /// Applies `bidi_mode` to every line of both screens
+    pub fn apply_bidi_mode(&mut self, bidi_mode: BidiMode, seqno: SequenceNo) {
+        self.screen
+            .for_each_phys_line_mut(|_, line| bidi_mode.apply_to_line(line, seqno));
+        self.alt_screen
+            .for_each_phys_line_mut(|_, line| bidi_mode.apply_to_line(line, seqno));
+    }
+
+    /// Recomputes the cell widths of both screens for `unicode_version`
+    pub fn relayout_for_unicode_version(
+        &mut self,
+        unicode_version: UnicodeVersion,
+        seqno: SequenceNo,
+    ) {
+        self.screen
+            .relayout_for_unicode_version(unicode_version, seqno);
+        self.alt_screen
+            .relayout_for_unicode_version(unicode_version, seqno);
+    }
+
     pub fn saved_cursor(&mut self) -> &mut Option<SavedCursor> {
         if self.alt_screen_is_active {
             &mut self.alt_saved_cursor
@@ -268,6 +290,10 @@ pub struct TerminalState {
     /// Reverse Wraparound Mode
     reverse_wraparound_mode: bool,
 
+    /// Whether the application is part way through a DECSET 2026
+    /// synchronized update
+    synchronized_output: bool,
+
     /// Reverse video mode
     reverse_video_mode: bool,
 
@@ -344,6 +370,7 @@ pub struct TerminalState {
     device_control_handler: Option<Box<dyn DeviceControlHandler>>,
     alert_handler: Option<Box<dyn AlertHandler>>,
     download_handler: Option<Arc<dyn DownloadHandler>>,
+    permissions: Option<Arc<dyn EscapeSequencePermissions>>,
 
     current_dir: Option<Url>,
 
@@ -357,13 +384,28 @@ pub struct TerminalState {
 
     user_vars: HashMap<String, String>,
 
+    /// The iTerm2 badge format, and its most recent expansion
+    badge_format: Option<String>,
+    badge: Option<String>,
+
     kitty_img: KittyImageState,
+    /// ReGIS and Tektronix 4014 drawing state
+    vector: VectorGraphics,
     seqno: SequenceNo,
 
     /// The unicode version that is in effect
     unicode_version: UnicodeVersion,
     unicode_version_stack: Vec<UnicodeVersionStackEntry>,
 
+    /// Whether printed text is segmented into extended grapheme
+    /// clusters (DECSET 2027) rather than individual codepoints
+    grapheme_clustering: bool,
+    /// The position of the most recently printed cell, for as long
+    /// as nothing other than printable text has been processed since.
+    /// Text that continues its grapheme is added to that cell, even
+    /// when it arrives in a later batch of output.
+    last_printed_cell: Option<(usize, VisibleRowIndex)>,
+
     enable_conpty_quirks: bool,
     /// On Windows, the ConPTY layer emits an OSC sequence to
     /// set the title shortly after it starts up.
@@ -505,6 +547,7 @@ impl TerminalState {
         let color_map = default_color_map();
 
         let unicode_version = config.unicode_version();
+        let grapheme_clustering = config.grapheme_clustering();
 
         TerminalState {
             config,
@@ -520,6 +563,7 @@ impl TerminalState {
             // a dec terminal is false, because it is more useful this way.
             dec_auto_wrap: true,
             reverse_wraparound_mode: false,
+            synchronized_output: false,
             reverse_video_mode: false,
             dec_origin_mode: false,
             insert: false,
@@ -556,16 +600,22 @@ impl TerminalState {
             device_control_handler: None,
             alert_handler: None,
             download_handler: None,
+            permissions: None,
             current_dir: None,
             term_program: term_program.to_string(),
             term_version: term_version.to_string(),
             writer: Box::new(std::io::BufWriter::new(writer)),
             image_cache: lru::LruCache::new(16),
             user_vars: HashMap::new(),
+            badge_format: None,
+            badge: None,
             kitty_img: Default::default(),
+            vector: Default::default(),
             seqno,
             unicode_version,
             unicode_version_stack: vec![],
+            grapheme_clustering,
+            last_printed_cell: None,
             suppress_initial_title_change: false,
             enable_conpty_quirks: false,
             accumulating_title: None,
@@ -613,6 +663,29 @@ impl TerminalState {
         self.download_handler.replace(handler.clone());
     }
 
+    pub fn set_escape_sequence_permissions(
+        &mut self,
+        permissions: &Arc<dyn EscapeSequencePermissions>,
+    ) {
+        self.permissions.replace(Arc::clone(permissions));
+    }
+
+    /// Returns true if a sequence of the specified kind should be
+    /// honored.  Everything is allowed unless a permissions handler
+    /// has been set.
+    fn escape_sequence_allowed(&self, kind: EscapeSequenceKind) -> bool {
+        match self.permissions.as_ref() {
+            Some(permissions) => {
+                let allowed = permissions.is_allowed(kind);
+                if !allowed {
+                    log::debug!("ignoring {} escape sequence", kind.name());
+                }
+                allowed
+            }
+            None => true,
+        }
+    }
+
     /// Returns the title text associated with the terminal session.
     /// The title can be changed by the application using a number
     /// of escape sequences:
@@ -688,6 +761,13 @@ impl TerminalState {
         Ok(())
     }
 
+    fn query_clipboard_contents(&self, selection: ClipboardSelection) -> anyhow::Result<()> {
+        if let Some(clip) = self.clipboard.as_ref() {
+            clip.query_contents(selection)?;
+        }
+        Ok(())
+    }
+
     pub fn erase_scrollback_and_viewport(&mut self) {
         // Since we may be called outside of perform_actions,
         // we need to ensure that we increment the seqno in
@@ -729,6 +809,37 @@ impl TerminalState {
         self.screen.is_alt_screen_active()
     }
 
+    /// Returns true if East Asian Ambiguous width characters are
+    /// currently treated as double width
+    pub fn ambiguous_width_is_wide(&self) -> bool {
+        self.unicode_version.ambiguous_are_wide
+    }
+
+    /// Changes whether East Asian Ambiguous width characters are
+    /// treated as double width.  The existing content of the screen
+    /// and scrollback is laid out again to match, so that the cells
+    /// remain aligned with what the application will subsequently
+    /// output.
+    pub fn set_ambiguous_width_is_wide(&mut self, wide: bool) {
+        if self.unicode_version.ambiguous_are_wide == wide {
+            return;
+        }
+        self.unicode_version.ambiguous_are_wide = wide;
+        for entry in self.unicode_version_stack.iter_mut() {
+            entry.vers.ambiguous_are_wide = wide;
+        }
+        let seqno = self.seqno;
+        let unicode_version = self.unicode_version;
+        self.screen
+            .relayout_for_unicode_version(unicode_version, seqno);
+    }
+
+    /// Returns true if the application has begun, but not yet
+    /// finished, a synchronized update (DECSET 2026)
+    pub fn is_synchronized_output_active(&self) -> bool {
+        self.synchronized_output
+    }
+
     /// Returns true if the associated application has enabled
     /// bracketed paste mode, which can be helpful to the hosting
     /// GUI application to decide about fragmenting a large paste.
@@ -785,12 +896,32 @@ impl TerminalState {
     /// If bracketed paste mode is enabled, the paste is enclosed
     /// in the bracketing, otherwise it is fed to the writer as-is.
     pub fn send_paste(&mut self, text: &str) -> Result<(), Error> {
+        self.send_paste_chunk(text, None, true, true)?;
+        Ok(())
+    }
+
+    /// Send a piece of a paste that is being delivered across several
+    /// calls, such as when streaming a large amount of text.
+    /// The bracketing start sequence is only sent with the `first` chunk
+    /// and the end sequence only with the `last` chunk.
+    /// `bracketed` overrides whether the paste is bracketed; if `None`,
+    /// the paste is bracketed if bracketed paste mode is enabled.
+    /// Returns whether the paste is bracketed, so that the caller can
+    /// pass the same value with the remaining chunks.
+    pub fn send_paste_chunk(
+        &mut self,
+        text: &str,
+        bracketed: Option<bool>,
+        first: bool,
+        last: bool,
+    ) -> Result<bool, Error> {
+        let bracketed = bracketed.unwrap_or(self.bracketed_paste);
         let mut buf = String::new();
-        if self.bracketed_paste {
+        if bracketed && first {
             buf.push_str("\x1b[200~");
         }
 
-        let canon = if self.bracketed_paste {
+        let canon = if bracketed {
             NewlineCanon::None
         } else {
             self.config.canonicalize_pasted_newlines()
@@ -799,13 +930,13 @@ impl TerminalState {
         let canon = canon.canonicalize(text);
         buf.push_str(&canon);
 
-        if self.bracketed_paste {
+        if bracketed && last {
             buf.push_str("\x1b[201~");
         }
 
         self.writer.write_all(buf.as_bytes())?;
         self.writer.flush()?;
-        Ok(())
+        Ok(bracketed)
     }
 
     /// Informs the terminal that the viewport of the window has resized to the
@@ -813,6 +944,7 @@ impl TerminalState {
     /// We need to resize both the primary and alt screens, adjusting
     /// the cursor positions of both accordingly.
     pub fn resize(&mut self, size: TerminalSize) {
+        self.last_printed_cell.take();
         let (cursor_main, cursor_alt) = if self.screen.alt_screen_is_active {
             (
                 self.screen
@@ -871,6 +1003,9 @@ impl TerminalState {
                 saved.wrap_next = false;
             }
         }
+
+        // The badge may refer to the dimensions of the terminal
+        self.update_badge();
     }
 
     pub fn get_size(&self) -> TerminalSize {
@@ -913,6 +1048,12 @@ impl TerminalState {
         &self.user_vars
     }
 
+    /// Returns the expanded text of the badge that was set via
+    /// the iTerm2 SetBadgeFormat escape sequence
+    pub fn badge(&self) -> Option<&str> {
+        self.badge.as_deref()
+    }
+
     fn clear_semantic_attribute_due_to_movement(&mut self) {
         if self.clear_semantic_attribute_on_newline {
             self.clear_semantic_attribute_on_newline = false;
@@ -1049,6 +1190,17 @@ impl TerminalState {
             y + 1
         };
         self.set_cursor_pos(&Position::Absolute(x as i64), &Position::Absolute(y as i64));
+
+        // A blank line that the cursor moves onto begins a new
+        // paragraph, which takes on the current bidi mode
+        let seqno = self.seqno;
+        let bidi_mode = self.get_bidi_mode();
+        let screen = self.screen_mut();
+        let line_idx = screen.phys_row(y);
+        let line = screen.line_mut(line_idx);
+        if line.bidi_info() != (bidi_mode.enabled, bidi_mode.hint) && line.is_whitespace() {
+            bidi_mode.apply_to_line(line, seqno);
+        }
     }
 
     /// Moves the cursor down one line in the same column.
@@ -1222,11 +1374,15 @@ impl TerminalState {
 
                 self.reverse_wraparound_mode = false;
                 self.reverse_video_mode = false;
+                self.synchronized_output = false;
                 self.bidi_enabled.take();
                 self.bidi_hint.take();
             }
             Device::RequestPrimaryDeviceAttributes => {
                 let mut ident = "\x1b[?65".to_string(); // Vt500
+                if self.config.enable_vector_graphics() {
+                    ident.push_str(";3"); // ReGIS graphics
+                }
                 ident.push_str(";4"); // Sixel graphics
                 ident.push_str(";6"); // Selective erase
                 ident.push_str(";18"); // windowing extensions
@@ -1482,19 +1638,83 @@ impl TerminalState {
             Mode::SetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::SynchronizedOutput,
             )) => {
-                // This is handled in wezterm's mux
+                // Output is held back by wezterm's mux; we only track
+                // the state here so that the renderer can avoid
+                // painting a partially drawn frame
+                self.synchronized_output = true;
             }
             Mode::ResetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::SynchronizedOutput,
             )) => {
-                // This is handled in wezterm's mux
+                self.synchronized_output = false;
             }
             Mode::QueryDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::SynchronizedOutput,
             )) => {
-                // This is handled in wezterm's mux; if we get here, then it isn't enabled,
-                // so we always report false
-                self.decqrm_response(mode, true, false);
+                self.decqrm_response(mode, true, self.synchronized_output);
+            }
+
+            Mode::SetDecPrivateMode(DecPrivateMode::Code(
+                DecPrivateModeCode::BidiAutoDetection,
+            )) => {
+                let hint = match self.get_bidi_mode().hint.direction() {
+                    Direction::LeftToRight => ParagraphDirectionHint::AutoLeftToRight,
+                    Direction::RightToLeft => ParagraphDirectionHint::AutoRightToLeft,
+                };
+                self.bidi_hint.replace(hint);
+                self.apply_bidi_mode_to_cursor_line();
+            }
+            Mode::ResetDecPrivateMode(DecPrivateMode::Code(
+                DecPrivateModeCode::BidiAutoDetection,
+            )) => {
+                let hint = match self.get_bidi_mode().hint.direction() {
+                    Direction::LeftToRight => ParagraphDirectionHint::LeftToRight,
+                    Direction::RightToLeft => ParagraphDirectionHint::RightToLeft,
+                };
+                self.bidi_hint.replace(hint);
+                self.apply_bidi_mode_to_cursor_line();
+            }
+            Mode::QueryDecPrivateMode(DecPrivateMode::Code(
+                DecPrivateModeCode::BidiAutoDetection,
+            )) => {
+                let auto = matches!(
+                    self.get_bidi_mode().hint,
+                    ParagraphDirectionHint::AutoLeftToRight
+                        | ParagraphDirectionHint::AutoRightToLeft
+                );
+                self.decqrm_response(mode, true, auto);
+            }
+
+            Mode::SetDecPrivateMode(DecPrivateMode::Code(
+                DecPrivateModeCode::GraphemeClustering,
+            )) => {
+                self.grapheme_clustering = true;
+            }
+            Mode::ResetDecPrivateMode(DecPrivateMode::Code(
+                DecPrivateModeCode::GraphemeClustering,
+            )) => {
+                self.grapheme_clustering = false;
+            }
+            Mode::QueryDecPrivateMode(DecPrivateMode::Code(
+                DecPrivateModeCode::GraphemeClustering,
+            )) => {
+                self.decqrm_response(mode, true, self.grapheme_clustering);
+            }
+
+            Mode::SetDecPrivateMode(DecPrivateMode::Code(
+                DecPrivateModeCode::AmbiguousWidthIsWide,
+            )) => {
+                self.set_ambiguous_width_is_wide(true);
+            }
+            Mode::ResetDecPrivateMode(DecPrivateMode::Code(
+                DecPrivateModeCode::AmbiguousWidthIsWide,
+            )) => {
+                self.set_ambiguous_width_is_wide(false);
+            }
+            Mode::QueryDecPrivateMode(DecPrivateMode::Code(
+                DecPrivateModeCode::AmbiguousWidthIsWide,
+            )) => {
+                self.decqrm_response(mode, true, self.unicode_version.ambiguous_are_wide);
             }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SmoothScroll))
@@ -1536,9 +1756,11 @@ impl TerminalState {
 
             Mode::SetMode(TerminalMode::Code(TerminalModeCode::BiDirectionalSupportMode)) => {
                 self.bidi_enabled.replace(true);
+                self.apply_bidi_mode_to_cursor_line();
             }
             Mode::ResetMode(TerminalMode::Code(TerminalModeCode::BiDirectionalSupportMode)) => {
                 self.bidi_enabled.replace(false);
+                self.apply_bidi_mode_to_cursor_line();
             }
             Mode::QueryMode(TerminalMode::Code(TerminalModeCode::BiDirectionalSupportMode)) => {
                 self.decqrm_response(
@@ -1639,6 +1861,18 @@ impl TerminalState {
                 self.decqrm_response(mode, true, self.sixel_display_mode);
             }
 
+            Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::TektronixMode)) => {
+                if self.config.enable_vector_graphics() {
+                    self.set_tek_mode(true);
+                }
+            }
+            Mode::ResetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::TektronixMode)) => {
+                self.set_tek_mode(false);
+            }
+            Mode::QueryDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::TektronixMode)) => {
+                self.decqrm_response(mode, self.config.enable_vector_graphics(), self.tek_mode());
+            }
+
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::DecAnsiMode)) => {
                 self.dec_ansi_mode = true;
             }
@@ -1913,6 +2147,96 @@ impl TerminalState {
         checksum
     }
 
+    /// Resolves the edges of a DEC rectangular area operation into
+    /// ranges of screen rows and columns.  The edges are relative to
+    /// the margins when DECOM is set, and are clamped to the page or
+    /// margins.  Returns None if the rectangle is empty.
+    fn resolve_rectangle(
+        &self,
+        top: OneBased,
+        left: OneBased,
+        bottom: Option<OneBased>,
+        right: Option<OneBased>,
+    ) -> Option<(Range<VisibleRowIndex>, Range<usize>)> {
+        let (rows, cols) = if self.dec_origin_mode {
+            (
+                self.top_and_bottom_margins.clone(),
+                self.left_and_right_margins.clone(),
+            )
+        } else {
+            let screen = self.screen();
+            (
+                0..screen.physical_rows as VisibleRowIndex,
+                0..screen.physical_cols,
+            )
+        };
+
+        let top = rows.start + top.as_zero_based() as VisibleRowIndex;
+        let left = cols.start + left.as_zero_based() as usize;
+        let bottom = match bottom {
+            Some(bottom) => (rows.start + bottom.as_one_based() as VisibleRowIndex).min(rows.end),
+            None => rows.end,
+        };
+        let right = match right {
+            Some(right) => (cols.start + right.as_one_based() as usize).min(cols.end),
+            None => cols.end,
+        };
+
+        if top >= bottom || left >= right {
+            None
+        } else {
+            Some((top..bottom, left..right))
+        }
+    }
+
+    /// Replaces each cell in the rectangle with `cell`
+    fn fill_rectangle(&mut self, rows: Range<VisibleRowIndex>, cols: Range<usize>, cell: &Cell) {
+        let seqno = self.seqno;
+        let screen = self.screen_mut();
+        for y in rows {
+            let line_idx = screen.phys_row(y);
+            screen
+                .line_mut(line_idx)
+                .fill_range(cols.clone(), cell, seqno);
+        }
+    }
+
+    /// Copies the rectangle so that its top left corner is at the
+    /// destination, clipping it to the destination area
+    fn copy_rectangle(
+        &mut self,
+        rows: Range<VisibleRowIndex>,
+        cols: Range<usize>,
+        dest_rows: Range<VisibleRowIndex>,
+        dest_cols: Range<usize>,
+    ) {
+        let seqno = self.seqno;
+        let screen = self.screen_mut();
+
+        // Capture the source first, as it may overlap the destination
+        let source: Vec<Vec<Cell>> = rows
+            .map(|y| {
+                let line_idx = screen.phys_row(y);
+                let line = screen.line_mut(line_idx);
+                cols.clone()
+                    .map(|x| {
+                        line.get_cell(x)
+                            .map(|cell| cell.as_cell())
+                            .unwrap_or_else(Cell::blank)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for (cells, y) in source.into_iter().zip(dest_rows) {
+            let line_idx = screen.phys_row(y);
+            let line = screen.line_mut(line_idx);
+            for (cell, x) in cells.into_iter().zip(dest_cols.clone()) {
+                line.set_cell(x, cell, seqno);
+            }
+        }
+    }
+
     fn perform_csi_window(&mut self, window: Window) {
         match window {
             Window::ReportTextAreaSizeCells => {
@@ -1973,6 +2297,47 @@ impl TerminalState {
                 write!(self.writer, "\x1bP{}!~{:04x}\x1b\\", request_id, checksum).ok();
                 self.writer.flush().ok();
             }
+            Window::CopyRectangularArea {
+                top,
+                left,
+                bottom,
+                right,
+                dest_top,
+                dest_left,
+                ..
+            } => {
+                // We only have a single page, so the page numbers
+                // are ignored
+                if let (Some((rows, cols)), Some((dest_rows, dest_cols))) = (
+                    self.resolve_rectangle(top, left, bottom, right),
+                    self.resolve_rectangle(dest_top, dest_left, None, None),
+                ) {
+                    self.copy_rectangle(rows, cols, dest_rows, dest_cols);
+                }
+            }
+            Window::FillRectangularArea {
+                ch,
+                top,
+                left,
+                bottom,
+                right,
+            } => {
+                if let Some((rows, cols)) = self.resolve_rectangle(top, left, bottom, right) {
+                    let cell = Cell::new(ch, self.pen.clone_sgr_only());
+                    self.fill_rectangle(rows, cols, &cell);
+                }
+            }
+            Window::EraseRectangularArea {
+                top,
+                left,
+                bottom,
+                right,
+            } => {
+                if let Some((rows, cols)) = self.resolve_rectangle(top, left, bottom, right) {
+                    let cell = Cell::blank_with_attrs(self.pen.clone_sgr_only());
+                    self.fill_rectangle(rows, cols, &cell);
+                }
+            }
             Window::ResizeWindowCells { .. } => {
                 // We don't allow the application to change the window size; that's
                 // up to the user!
@@ -2033,6 +2398,34 @@ impl TerminalState {
         mode
     }
 
+    /// Applies the current bidi mode to the line containing the cursor,
+    /// so that a change to the mode takes effect for the current
+    /// paragraph as well as those that follow it
+    pub(crate) fn apply_bidi_mode_to_cursor_line(&mut self) {
+        let seqno = self.seqno;
+        let bidi_mode = self.get_bidi_mode();
+        let y = self.cursor.y;
+        let screen = self.screen_mut();
+        let line_idx = screen.phys_row(y);
+        bidi_mode.apply_to_line(screen.line_mut(line_idx), seqno);
+    }
+
+    /// Overrides whether bidi is enabled and the paragraph direction,
+    /// in the same way as BDSM and SCP, and applies the resulting mode
+    /// to the existing content of the screen and scrollback.
+    /// `None` reverts to the configured value.
+    pub fn set_bidi_mode_override(
+        &mut self,
+        enabled: Option<bool>,
+        hint: Option<ParagraphDirectionHint>,
+    ) {
+        self.bidi_enabled = enabled;
+        self.bidi_hint = hint;
+        let seqno = self.seqno;
+        let bidi_mode = self.get_bidi_mode();
+        self.screen.apply_bidi_mode(bidi_mode, seqno);
+    }
+
     fn perform_csi_edit(&mut self, edit: Edit) {
         let seqno = self.seqno;
         match edit {
@@ -2136,6 +2529,16 @@ impl TerminalState {
             }
             Edit::ScrollDown(n) => self.scroll_down(n as usize),
             Edit::ScrollUp(n) => self.scroll_up(n as usize),
+            Edit::ScrollLeft(n) => {
+                let margins = self.left_and_right_margins.clone();
+                self.shift_columns(margins, n as usize, true);
+            }
+            Edit::ScrollRight(n) => {
+                let margins = self.left_and_right_margins.clone();
+                self.shift_columns(margins, n as usize, false);
+            }
+            Edit::InsertColumn(n) => self.insert_or_delete_columns(n as usize, false),
+            Edit::DeleteColumn(n) => self.insert_or_delete_columns(n as usize, true),
             Edit::EraseInDisplay(erase) => self.erase_in_display(erase),
             Edit::Repeat(n) => {
                 let mut y = self.cursor.y;
@@ -2196,14 +2599,72 @@ impl TerminalState {
         }
     }
 
+    /// Shifts the `cols` of the rows within the top and bottom margins
+    /// by `n` columns, to the left if `left` is true, otherwise to the
+    /// right.  Columns shifted past the edge of `cols` are lost, and
+    /// blank columns are added on the other side.
+    fn shift_columns(&mut self, cols: Range<usize>, n: usize, left: bool) {
+        let seqno = self.seqno;
+        let blank_attr = self.pen.clone_sgr_only();
+        let n = n.min(cols.end - cols.start);
+        let rows = self.top_and_bottom_margins.clone();
+        let screen = self.screen_mut();
+        for y in rows {
+            for _ in 0..n {
+                if left {
+                    screen.erase_cell(cols.start, y, cols.end, seqno, blank_attr.clone());
+                } else {
+                    screen.insert_cell(cols.start, y, cols.end, seqno);
+                }
+            }
+        }
+    }
+
+    /// DECIC and DECDC; these have no effect outside the margins
+    fn insert_or_delete_columns(&mut self, n: usize, delete: bool) {
+        if self.top_and_bottom_margins.contains(&self.cursor.y)
+            && self.left_and_right_margins.contains(&self.cursor.x)
+        {
+            let cols = self.cursor.x..self.left_and_right_margins.end;
+            self.shift_columns(cols, n, delete);
+        }
+    }
+
+    /// https://vt100.net/docs/vt510-rm/DECBI.html
+    /// Moves the cursor back one column, scrolling the margins to the
+    /// right if the cursor is at the left margin.
+    fn dec_back_index(&mut self) {
+        if self.cursor.x == self.left_and_right_margins.start {
+            if self.top_and_bottom_margins.contains(&self.cursor.y) {
+                let margins = self.left_and_right_margins.clone();
+                self.shift_columns(margins, 1, false);
+            }
+        } else if self.cursor.x > 0 {
+            self.set_cursor_position_absolute(self.cursor.x - 1, self.cursor.y);
+        }
+    }
+
+    /// https://vt100.net/docs/vt510-rm/DECFI.html
+    /// Moves the cursor forward one column, scrolling the margins to the
+    /// left if the cursor is at the right margin.
+    fn dec_forward_index(&mut self) {
+        if self.cursor.x == self.left_and_right_margins.end - 1 {
+            if self.top_and_bottom_margins.contains(&self.cursor.y) {
+                let margins = self.left_and_right_margins.clone();
+                self.shift_columns(margins, 1, true);
+            }
+        } else if self.cursor.x < self.screen().physical_cols - 1 {
+            self.set_cursor_position_absolute(self.cursor.x + 1, self.cursor.y);
+        }
+    }
+
     /// https://vt100.net/docs/vt510-rm/DECSLRM.html
     fn set_left_and_right_margins(&mut self, left: OneBased, right: OneBased) {
         // The terminal only recognizes this control function if vertical split
         // screen mode (DECLRMM) is set.
         if self.left_and_right_margin_mode {
-            let rows = self.screen().physical_rows as u32;
             let cols = self.screen().physical_cols as u32;
-            let left = left.as_zero_based().min(rows - 1).max(0) as usize;
+            let left = left.as_zero_based().min(cols - 1).max(0) as usize;
             let right = right.as_zero_based().min(cols - 1).max(0) as usize;
 
             // The value of the left margin (Pl) must be less than the right margin (Pr).
@@ -2351,7 +2812,12 @@ impl TerminalState {
                 } else {
                     col
                 };
-                self.cursor.x = col.min(self.screen().physical_cols - 1);
+                let limit = if self.dec_origin_mode {
+                    self.left_and_right_margins.end
+                } else {
+                    self.screen().physical_cols
+                };
+                self.cursor.x = col.min(limit - 1);
                 self.cursor.seqno = seqno;
                 self.wrap_next = false;
             }
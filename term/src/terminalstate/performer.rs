@@ -1,26 +1,32 @@
-use crate::terminal::Alert;
+use crate::terminal::{Alert, AttentionRequest};
 use crate::terminalstate::{
     default_color_map, CharSet, MouseEncoding, TabStop, UnicodeVersionStackEntry,
 };
-use crate::{ClipboardSelection, Position, TerminalState, VisibleRowIndex, DCS, ST};
+use crate::{
+    ClipboardSelection, EscapeSequenceKind, Position, TerminalState, VisibleRowIndex, DCS, ST,
+};
 use finl_unicode::grapheme_clusters::Graphemes;
 use log::{debug, error};
 use num_traits::FromPrimitive;
 use ordered_float::NotNan;
 use std::fmt::Write;
 use std::ops::{Deref, DerefMut};
-use termwiz::cell::{grapheme_column_width, Cell, CellAttributes, SemanticType};
+use termwiz::cell::{
+    grapheme_column_width, Blink, Cell, CellAttributes, Intensity, SemanticType, Underline,
+};
+use termwiz::color::{ColorAttribute, ColorSpec};
 use termwiz::escape::csi::{
-    CharacterPath, EraseInDisplay, Keyboard, KittyKeyboardFlags, KittyKeyboardMode,
+    CharacterPath, EraseInDisplay, Keyboard, KittyKeyboardFlags, KittyKeyboardMode, Sgr,
 };
 use termwiz::escape::osc::{
     ChangeColorPair, ColorOrQuery, FinalTermSemanticPrompt, ITermProprietary,
-    ITermUnicodeVersionOp, Selection,
+    ITermRequestAttention, ITermUnicodeVersionOp, Selection,
 };
 use termwiz::escape::{
     Action, ControlCode, DeviceControlMode, Esc, EscCode, OperatingSystemCommand, CSI,
 };
 use termwiz::input::KeyboardEncoding;
+use termwiz::surface::CursorShape;
 use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
 use url::Url;
 use wezterm_bidi::ParagraphDirectionHint;
@@ -49,6 +55,7 @@ impl<'a> DerefMut for Performer<'a> {
 impl<'a> Drop for Performer<'a> {
     fn drop(&mut self) {
         self.flush_print();
+        self.flush_vector_graphics();
     }
 }
 
@@ -128,11 +135,29 @@ impl<'a> Performer<'a> {
             p.as_str()
         };
 
-        for g in Graphemes::new(text) {
-            let g = self.remap_grapheme(g);
+        let text = self.continue_last_printed_cell(text);
+
+        // In grapheme clustering mode (DECSET 2027) each extended grapheme
+        // cluster occupies a cell, otherwise each codepoint does
+        let clustering = self.grapheme_clustering;
+        let mut graphemes = Graphemes::new(text);
+        let mut chars = text.char_indices();
+        loop {
+            let g = if clustering {
+                graphemes.next()
+            } else {
+                chars.next().map(|(idx, c)| &text[idx..idx + c.len_utf8()])
+            };
+            let g = match g {
+                Some(g) => self.remap_grapheme(g),
+                None => break,
+            };
 
             let print_width = grapheme_column_width(g, Some(self.unicode_version));
             if print_width == 0 {
+                if self.continue_last_printed_cell(g).is_empty() {
+                    continue;
+                }
                 // We got a zero-width grapheme.
                 // We used to force them into a cell to guarantee that we
                 // preserved them in the model, but it introduces presentation
@@ -183,6 +208,7 @@ impl<'a> Performer<'a> {
             );
             self.screen_mut()
                 .set_cell_grapheme(x, y, g, print_width, pen, seqno);
+            self.last_printed_cell.replace((x, y));
 
             if !wrappable {
                 self.cursor.x += print_width;
@@ -196,6 +222,65 @@ impl<'a> Performer<'a> {
         self.print.clear();
     }
 
+    /// Adds the start of `text` to the most recently printed cell when it
+    /// continues the grapheme in that cell, which happens when a grapheme
+    /// is split across batches of output.  In grapheme clustering mode the
+    /// cell takes on the width of the extended grapheme cluster.  Otherwise
+    /// only zero width codepoints are added and the width is unchanged.
+    /// Returns the remainder of `text`.
+    fn continue_last_printed_cell<'t>(&mut self, text: &'t str) -> &'t str {
+        let (x, y) = match self.last_printed_cell {
+            Some(pos) => pos,
+            None => return text,
+        };
+        let (prior, attrs, prior_width) = match self.screen_mut().get_cell(x, y) {
+            Some(cell) => (cell.str().to_string(), cell.attrs().clone(), cell.width()),
+            None => return text,
+        };
+        let unicode_version = Some(self.unicode_version);
+
+        let consumed = if self.grapheme_clustering {
+            let combined = format!("{}{}", prior, text);
+            match Graphemes::new(&combined).next() {
+                Some(first) if first.len() > prior.len() => first.len() - prior.len(),
+                _ => 0,
+            }
+        } else {
+            let mut buf = [0u8; 4];
+            text.char_indices()
+                .find(|(_, c)| grapheme_column_width(c.encode_utf8(&mut buf), unicode_version) != 0)
+                .map(|(idx, _)| idx)
+                .unwrap_or(text.len())
+        };
+        if consumed == 0 {
+            return text;
+        }
+
+        let grapheme = format!("{}{}", prior, &text[..consumed]);
+        let width = if self.grapheme_clustering {
+            grapheme_column_width(&grapheme, unicode_version).max(1)
+        } else {
+            prior_width
+        };
+        let seqno = self.seqno;
+        self.screen_mut()
+            .set_cell_grapheme(x, y, &grapheme, width, attrs, seqno);
+
+        if width != prior_width {
+            // Position the cursor as though the whole grapheme had
+            // been printed at once
+            if x + width >= self.left_and_right_margins.end {
+                self.cursor.x = x;
+                self.wrap_next = self.dec_auto_wrap;
+            } else {
+                self.cursor.x = x + width;
+                self.wrap_next = false;
+            }
+        }
+
+        &text[consumed..]
+    }
+
     /// ConPTY, at the time of writing, does something horrible to rewrite
     /// `ESC k TITLE ST` into something completely different and out-of-order,
     /// and critically, removes the ST.
@@ -225,6 +310,15 @@ impl<'a> Performer<'a> {
                 _ => {}
             }
         }
+        match &action {
+            Action::Print(_) | Action::PrintString(_) => {}
+            _ => {
+                // Anything other than text ends the grapheme that
+                // was most recently printed
+                self.flush_print();
+                self.last_printed_cell.take();
+            }
+        }
         match action {
             Action::Print(c) => self.print(c),
             Action::PrintString(s) => {
@@ -241,6 +335,9 @@ impl<'a> Performer<'a> {
             Action::XtGetTcap(names) => self.xt_get_tcap(names),
             Action::KittyImage(img) => {
                 self.flush_print();
+                if !self.escape_sequence_allowed(EscapeSequenceKind::Apc) {
+                    return;
+                }
                 if let Err(err) = self.kitty_img(*img) {
                     log::error!("kitty_img: {:#}", err);
                 }
@@ -295,6 +392,50 @@ impl<'a> Performer<'a> {
                                 .ok();
                                 self.writer.flush().ok();
                             }
+                            &[b'm'] => {
+                                // SGR - graphic rendition
+                                let sgr = pen_to_sgr(&self.pen);
+                                write!(self.writer, "{}1$r{}m{}", DCS, sgr, ST).ok();
+                                self.writer.flush().ok();
+                            }
+                            &[b' ', b'q'] => {
+                                // DECSCUSR - cursor style
+                                let style = match self.cursor.shape {
+                                    CursorShape::Default => 0,
+                                    CursorShape::BlinkingBlock => 1,
+                                    CursorShape::SteadyBlock => 2,
+                                    CursorShape::BlinkingUnderline => 3,
+                                    CursorShape::SteadyUnderline => 4,
+                                    CursorShape::BlinkingBar => 5,
+                                    CursorShape::SteadyBar => 6,
+                                };
+                                write!(self.writer, "{}1$r{} q{}", DCS, style, ST).ok();
+                                self.writer.flush().ok();
+                            }
+                            &[b'"', b'q'] => {
+                                // DECSCA - character protection; we don't
+                                // support protected characters
+                                write!(self.writer, "{}1$r0\"q{}", DCS, ST).ok();
+                                self.writer.flush().ok();
+                            }
+                            &[b't'] => {
+                                // DECSLPP - lines per page
+                                let rows = self.screen().physical_rows;
+                                write!(self.writer, "{}1$r{}t{}", DCS, rows, ST).ok();
+                                self.writer.flush().ok();
+                            }
+                            &[b'*', b'|'] => {
+                                // DECSNLS - lines per screen
+                                let rows = self.screen().physical_rows;
+                                write!(self.writer, "{}1$r{}*|{}", DCS, rows, ST).ok();
+                                self.writer.flush().ok();
+                            }
+                            &[b'$', b'|'] => {
+                                // DECSCPP - columns per page
+                                let cols = self.screen().physical_cols;
+                                write!(self.writer, "{}1$r{}$|{}", DCS, cols, ST).ok();
+                                self.writer.flush().ok();
+                            }
                             _ => {
                                 log::warn!("unhandled DECRQSS {:?}", s);
                                 // Reply that the request is invalid
@@ -306,6 +447,18 @@ impl<'a> Performer<'a> {
                     _ => log::warn!("unhandled {:?}", s),
                 }
             }
+            DeviceControlMode::Enter(mode)
+                if mode.byte == b'p'
+                    && mode.params != [1000]
+                    && mode.intermediates.is_empty()
+                    && self.config.enable_vector_graphics() =>
+            {
+                // ReGIS
+                self.flush_print();
+                self.regis_enter();
+            }
+            DeviceControlMode::Data(byte) if self.regis_active() => self.regis_data(*byte),
+            DeviceControlMode::Exit if self.regis_active() => self.regis_exit(),
             _ => match self.device_control_handler.as_mut() {
                 Some(handler) => handler.handle_device_control(ctrl),
                 None => log::warn!("unhandled {:?}", ctrl),
@@ -315,6 +468,9 @@ impl<'a> Performer<'a> {
 
     /// Draw a character to the screen
     fn print(&mut self, c: char) {
+        if self.tek_print(c) {
+            return;
+        }
         // We buffer up the chars to increase the chances of correctly grouping graphemes into cells
         if let Some(title) = self.accumulating_title.as_mut() {
             title.push(c);
@@ -327,6 +483,9 @@ impl<'a> Performer<'a> {
         let seqno = self.seqno;
         self.pop_tmux_title_state();
         self.flush_print();
+        if self.tek_control(control) {
+            return;
+        }
         match control {
             ControlCode::LineFeed | ControlCode::VerticalTab | ControlCode::FormFeed => {
                 if self.left_and_right_margins.contains(&self.cursor.x) {
@@ -456,16 +615,19 @@ impl<'a> Performer<'a> {
             CSI::Window(window) => self.state.perform_csi_window(*window),
             CSI::SelectCharacterPath(CharacterPath::ImplementationDefault, _) => {
                 self.state.bidi_hint.take();
+                self.state.apply_bidi_mode_to_cursor_line();
             }
             CSI::SelectCharacterPath(CharacterPath::LeftToRightOrTopToBottom, _) => {
                 self.state
                     .bidi_hint
                     .replace(ParagraphDirectionHint::LeftToRight);
+                self.state.apply_bidi_mode_to_cursor_line();
             }
             CSI::SelectCharacterPath(CharacterPath::RightToLeftOrBottomToTop, _) => {
                 self.state
                     .bidi_hint
                     .replace(ParagraphDirectionHint::RightToLeft);
+                self.state.apply_bidi_mode_to_cursor_line();
             }
             CSI::Keyboard(Keyboard::SetKittyState { flags, mode }) => {
                 if self.config.enable_kitty_keyboard() {
@@ -554,6 +716,8 @@ impl<'a> Performer<'a> {
             }
             Esc::Code(EscCode::ReverseIndex) => self.c1_reverse_index(),
             Esc::Code(EscCode::Index) => self.c1_index(),
+            Esc::Code(EscCode::DecBackIndex) => self.dec_back_index(),
+            Esc::Code(EscCode::DecForwardIndex) => self.dec_forward_index(),
             Esc::Code(EscCode::NextLine) => self.c1_nel(),
             Esc::Code(EscCode::HorizontalTabSet) => self.c1_hts(),
             Esc::Code(EscCode::DecLineDrawingG0) => {
@@ -631,6 +795,7 @@ impl<'a> Performer<'a> {
                 self.dec_auto_wrap = true;
                 self.reverse_wraparound_mode = false;
                 self.reverse_video_mode = false;
+                self.synchronized_output = false;
                 self.dec_origin_mode = false;
                 self.use_private_color_registers_for_each_graphic = false;
                 self.color_map = default_color_map();
@@ -657,8 +822,10 @@ impl<'a> Performer<'a> {
                 self.left_and_right_margins = 0..self.screen().physical_cols;
                 self.unicode_version = self.config.unicode_version();
                 self.unicode_version_stack.clear();
+                self.grapheme_clustering = self.config.grapheme_clustering();
                 self.suppress_initial_title_change = false;
                 self.accumulating_title.take();
+                self.reset_vector_graphics();
 
                 self.screen.full_reset();
                 self.screen.activate_primary_screen(seqno);
@@ -676,6 +843,21 @@ impl<'a> Performer<'a> {
     fn osc_dispatch(&mut self, osc: OperatingSystemCommand) {
         self.pop_tmux_title_state();
         self.flush_print();
+        let kind = match &osc {
+            OperatingSystemCommand::SetIconNameSun(_)
+            | OperatingSystemCommand::SetIconName(_)
+            | OperatingSystemCommand::SetIconNameAndWindowTitle(_)
+            | OperatingSystemCommand::SetWindowTitleSun(_)
+            | OperatingSystemCommand::SetWindowTitle(_) => Some(EscapeSequenceKind::SetTitle),
+            OperatingSystemCommand::SetSelection(..)
+            | OperatingSystemCommand::ClearSelection(_) => Some(EscapeSequenceKind::ClipboardWrite),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            if !self.escape_sequence_allowed(kind) {
+                return;
+            }
+        }
         match osc {
             OperatingSystemCommand::SetIconNameSun(title)
             | OperatingSystemCommand::SetIconName(title) => {
@@ -721,7 +903,12 @@ impl<'a> Performer<'a> {
                 let selection = selection_to_selection(selection);
                 self.set_clipboard_contents(selection, None).ok();
             }
-            OperatingSystemCommand::QuerySelection(_) => {}
+            OperatingSystemCommand::QuerySelection(selection) => {
+                let selection = selection_to_selection(selection);
+                if let Err(err) = self.query_clipboard_contents(selection) {
+                    error!("failed to query clipboard in response to OSC 52: {:#}", err);
+                }
+            }
             OperatingSystemCommand::SetSelection(selection, selection_data) => {
                 let selection = selection_to_selection(selection);
                 match self.set_clipboard_contents(selection, Some(selection_data)) {
@@ -762,6 +949,33 @@ impl<'a> Performer<'a> {
                     self.writer.flush().ok();
                 }
                 ITermProprietary::File(image) => self.set_image(*image),
+                ITermProprietary::SetBadgeFormat(format) => {
+                    self.badge_format = if format.is_empty() {
+                        None
+                    } else {
+                        Some(format)
+                    };
+                }
+                ITermProprietary::ReportVariable(name) => {
+                    let value = self.resolve_variable(&name).unwrap_or_default();
+                    let response = OperatingSystemCommand::ITermProprietary(
+                        ITermProprietary::ReportVariable(value),
+                    );
+                    write!(self.writer, "{}", response).ok();
+                    self.writer.flush().ok();
+                }
+                ITermProprietary::RequestAttention(attention) => {
+                    let request = match attention {
+                        ITermRequestAttention::Yes => AttentionRequest::UntilFocused,
+                        ITermRequestAttention::Once | ITermRequestAttention::Fireworks => {
+                            AttentionRequest::Once
+                        }
+                        ITermRequestAttention::No => AttentionRequest::Cancel,
+                    };
+                    if let Some(handler) = self.alert_handler.as_mut() {
+                        handler.alert(Alert::RequestAttention(request));
+                    }
+                }
                 ITermProprietary::SetUserVar { name, value } => {
                     self.user_vars.insert(name.clone(), value.clone());
                     if let Some(handler) = self.alert_handler.as_mut() {
@@ -830,8 +1044,12 @@ impl<'a> Performer<'a> {
             }
 
             OperatingSystemCommand::FinalTermSemanticPrompt(
-                FinalTermSemanticPrompt::CommandStatus { .. },
-            ) => {}
+                FinalTermSemanticPrompt::CommandStatus { status, .. },
+            ) => {
+                if let Some(handler) = self.alert_handler.as_mut() {
+                    handler.alert(Alert::CommandFinished { status });
+                }
+            }
 
             OperatingSystemCommand::SystemNotification(message) => {
                 if let Some(handler) = self.alert_handler.as_mut() {
@@ -1015,6 +1233,8 @@ impl<'a> Performer<'a> {
                 self.make_all_lines_dirty();
             }
         }
+        // Most of the inputs to the badge arrive via OSC
+        self.update_badge();
     }
 }
 
@@ -1030,3 +1250,61 @@ fn selection_to_selection(sel: Selection) -> ClipboardSelection {
         _ => ClipboardSelection::Clipboard,
     }
 }
+
+/// Returns the SGR parameters that reproduce the graphic rendition
+/// of `pen`, for the DECRQSS response
+fn pen_to_sgr(pen: &CellAttributes) -> String {
+    fn color(attr: ColorAttribute) -> Option<ColorSpec> {
+        match attr {
+            ColorAttribute::Default => None,
+            ColorAttribute::PaletteIndex(idx) => Some(ColorSpec::PaletteIndex(idx)),
+            ColorAttribute::TrueColorWithPaletteFallback(color, _)
+            | ColorAttribute::TrueColorWithDefaultFallback(color) => {
+                Some(ColorSpec::TrueColor(color))
+            }
+        }
+    }
+
+    let mut sgr = vec![];
+    if pen.intensity() != Intensity::Normal {
+        sgr.push(Sgr::Intensity(pen.intensity()));
+    }
+    if pen.italic() {
+        sgr.push(Sgr::Italic(true));
+    }
+    if pen.underline() != Underline::None {
+        sgr.push(Sgr::Underline(pen.underline()));
+    }
+    if pen.blink() != Blink::None {
+        sgr.push(Sgr::Blink(pen.blink()));
+    }
+    if pen.reverse() {
+        sgr.push(Sgr::Inverse(true));
+    }
+    if pen.invisible() {
+        sgr.push(Sgr::Invisible(true));
+    }
+    if pen.strikethrough() {
+        sgr.push(Sgr::StrikeThrough(true));
+    }
+    if pen.overline() {
+        sgr.push(Sgr::Overline(true));
+    }
+    if let Some(fg) = color(pen.foreground()) {
+        sgr.push(Sgr::Foreground(fg));
+    }
+    if let Some(bg) = color(pen.background()) {
+        sgr.push(Sgr::Background(bg));
+    }
+    if let Some(underline) = color(pen.underline_color()) {
+        sgr.push(Sgr::UnderlineColor(underline));
+    }
+
+    let mut result = "0".to_string();
+    for item in sgr {
+        let item = item.to_string();
+        result.push(';');
+        result.push_str(item.trim_end_matches('m'));
+    }
+    result
+}
@@ -0,0 +1,557 @@
+//! ReGIS and Tektronix 4014 vector graphics.
+//! Drawing commands are rasterized onto a canvas that covers the
+//! screen; the canvas is attached to the cells as an image so that
+//! it is composited along with the text by the renderer.
+use crate::terminalstate::image::*;
+use crate::terminalstate::ImageAttachParams;
+use crate::TerminalState;
+use ::image::RgbaImage;
+use std::collections::HashMap;
+use termwiz::color::RgbColor;
+use termwiz::escape::ControlCode;
+use termwiz::image::ImageDataType;
+
+/// The addressable area of the ReGIS screen
+const REGIS_WIDTH: f32 = 800.;
+const REGIS_HEIGHT: f32 = 480.;
+/// The addressable area of the Tektronix 4014 screen
+const TEK_WIDTH: f32 = 1024.;
+const TEK_HEIGHT: f32 = 780.;
+/// ReGIS command strings larger than this are discarded
+const MAX_REGIS_BYTES: usize = 1024 * 1024;
+/// Used for drawing until a color is selected
+const DEFAULT_FOREGROUND: [u8; 4] = [0xcc, 0xcc, 0xcc, 0xff];
+
+#[derive(Debug, Default)]
+pub(crate) struct VectorGraphics {
+    canvas: Option<RgbaImage>,
+    /// The canvas has changed since it was last attached to the cells
+    dirty: bool,
+    /// Accumulates the ReGIS commands of the current DCS
+    regis: Option<Vec<u8>>,
+    regis_state: RegisState,
+    /// Some while Tektronix 4014 mode is active
+    tek: Option<TekState>,
+}
+
+#[derive(Debug)]
+struct RegisState {
+    position: (f32, f32),
+    foreground: [u8; 4],
+    background: Option<[u8; 4]>,
+}
+
+impl Default for RegisState {
+    fn default() -> Self {
+        Self {
+            position: (0., 0.),
+            foreground: DEFAULT_FOREGROUND,
+            background: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct TekState {
+    /// true in graph mode, false in alpha mode
+    graph: bool,
+    point_plot: bool,
+    /// The next address moves the beam rather than drawing
+    dark_vector: bool,
+    hi_y: u16,
+    lo_y: u16,
+    hi_x: u16,
+    got_lo_y: bool,
+    position: (u16, u16),
+}
+
+fn rgba(color: RgbColor) -> [u8; 4] {
+    let (r, g, b) = color.to_tuple_rgb8();
+    [r, g, b, 0xff]
+}
+
+fn put_pixel(canvas: &mut RgbaImage, x: i64, y: i64, color: [u8; 4]) {
+    if x >= 0 && y >= 0 && (x as u32) < canvas.width() && (y as u32) < canvas.height() {
+        canvas.get_pixel_mut(x as u32, y as u32).0 = color;
+    }
+}
+
+fn draw_line(canvas: &mut RgbaImage, from: (f32, f32), to: (f32, f32), color: [u8; 4]) {
+    let (mut x0, mut y0) = (from.0.round() as i64, from.1.round() as i64);
+    let (x1, y1) = (to.0.round() as i64, to.1.round() as i64);
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let sy = if y0 < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    loop {
+        put_pixel(canvas, x0, y0, color);
+        if x0 == x1 && y0 == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x0 += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y0 += sy;
+        }
+    }
+}
+
+/// Converts between logical coordinates and canvas pixels
+struct Scale {
+    x: f32,
+    y: f32,
+}
+
+impl Scale {
+    fn new(canvas: &RgbaImage, width: f32, height: f32) -> Self {
+        Self {
+            x: canvas.width() as f32 / width,
+            y: canvas.height() as f32 / height,
+        }
+    }
+
+    fn pixel(&self, point: (f32, f32)) -> (f32, f32) {
+        (point.0 * self.x, point.1 * self.y)
+    }
+}
+
+/// Draws an arc of `sweep` degrees counter-clockwise around `center`,
+/// starting at `start`.  Coordinates are logical; the arc is drawn
+/// as a polygon in canvas pixels.
+fn draw_arc(
+    canvas: &mut RgbaImage,
+    scale: &Scale,
+    center: (f32, f32),
+    start: (f32, f32),
+    sweep: f32,
+    color: [u8; 4],
+) {
+    let radius = ((start.0 - center.0).powi(2) + (start.1 - center.1).powi(2)).sqrt();
+    if radius == 0. {
+        let (x, y) = scale.pixel(center);
+        put_pixel(canvas, x.round() as i64, y.round() as i64, color);
+        return;
+    }
+    // ReGIS y coordinates increase downwards, so negate the angle
+    // to sweep counter-clockwise on the screen
+    let start_angle = (start.1 - center.1).atan2(start.0 - center.0);
+    let pixel_radius = radius * scale.x.max(scale.y);
+    let steps = ((pixel_radius * sweep.abs().to_radians()) as usize).clamp(8, 4096);
+    let point = |angle: f32| {
+        scale.pixel((
+            center.0 + radius * angle.cos(),
+            center.1 + radius * angle.sin(),
+        ))
+    };
+    let mut prior = point(start_angle);
+    for step in 1..=steps {
+        let angle = start_angle - (sweep.to_radians() * step as f32 / steps as f32);
+        let next = point(angle);
+        draw_line(canvas, prior, next, color);
+        prior = next;
+    }
+}
+
+/// Splits a ReGIS option list such as `I(R)A90` into letters and
+/// their arguments
+fn regis_options(options: &str) -> Vec<(char, String)> {
+    let mut result = vec![];
+    let mut chars = options.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !c.is_ascii_alphabetic() {
+            continue;
+        }
+        let mut arg = String::new();
+        match chars.peek() {
+            Some('(') | Some('[') => {
+                let close = if chars.next() == Some('(') { ')' } else { ']' };
+                for c in chars.by_ref() {
+                    if c == close {
+                        break;
+                    }
+                    arg.push(c);
+                }
+            }
+            _ => {
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_digit() || c == '+' || c == '-' || c == '.' {
+                        arg.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+        result.push((c.to_ascii_uppercase(), arg));
+    }
+    result
+}
+
+/// Parses a ReGIS coordinate such as `[100,+20]`, resolving
+/// relative and omitted values against `position`
+fn regis_point(coords: &str, position: (f32, f32)) -> (f32, f32) {
+    let mut parts = coords.split(',');
+    let mut resolve = |current: f32| -> f32 {
+        let part = parts.next().unwrap_or("").trim();
+        if part.is_empty() {
+            current
+        } else if part.starts_with('+') || part.starts_with('-') {
+            current + part.parse::<f32>().unwrap_or(0.)
+        } else {
+            part.parse().unwrap_or(current)
+        }
+    };
+    let x = resolve(position.0);
+    let y = resolve(position.1);
+    (x, y)
+}
+
+/// Resolves a ReGIS color specifier; either a color register
+/// number or one of the color letters
+fn regis_color(arg: &str, color_map: &HashMap<u16, RgbColor>) -> Option<[u8; 4]> {
+    if let Ok(idx) = arg.parse::<u16>() {
+        return color_map.get(&idx).cloned().map(rgba);
+    }
+    let (r, g, b) = match arg.trim().chars().next()?.to_ascii_uppercase() {
+        'D' => (0, 0, 0),
+        'R' => (0xff, 0, 0),
+        'G' => (0, 0xff, 0),
+        'B' => (0, 0, 0xff),
+        'C' => (0, 0xff, 0xff),
+        'Y' => (0xff, 0xff, 0),
+        'M' => (0xff, 0, 0xff),
+        'W' => (0xff, 0xff, 0xff),
+        _ => return None,
+    };
+    Some([r, g, b, 0xff])
+}
+
+fn clear_canvas(canvas: &mut RgbaImage, background: Option<[u8; 4]>) {
+    let color = background.unwrap_or([0, 0, 0, 0]);
+    for pixel in canvas.pixels_mut() {
+        pixel.0 = color;
+    }
+}
+
+/// Interprets a ReGIS command string, drawing onto the canvas.
+/// This covers the position, vector, circle, screen and write
+/// commands; text, macrographs and the other commands are ignored.
+fn run_regis(
+    data: &str,
+    state: &mut RegisState,
+    canvas: &mut RgbaImage,
+    color_map: &HashMap<u16, RgbColor>,
+) {
+    let scale = Scale::new(canvas, REGIS_WIDTH, REGIS_HEIGHT);
+    let mut command = ' ';
+    let mut circle_at_point = false;
+    let mut arc_sweep: Option<f32> = None;
+    let mut chars = data.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                let coords: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                let point = regis_point(&coords, state.position);
+                match command {
+                    'P' => state.position = point,
+                    'V' => {
+                        draw_line(
+                            canvas,
+                            scale.pixel(state.position),
+                            scale.pixel(point),
+                            state.foreground,
+                        );
+                        state.position = point;
+                    }
+                    'C' => {
+                        let sweep = arc_sweep.unwrap_or(360.);
+                        if circle_at_point {
+                            draw_arc(
+                                canvas,
+                                &scale,
+                                point,
+                                state.position,
+                                sweep,
+                                state.foreground,
+                            );
+                        } else {
+                            draw_arc(
+                                canvas,
+                                &scale,
+                                state.position,
+                                point,
+                                sweep,
+                                state.foreground,
+                            );
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            '(' => {
+                let mut depth = 1;
+                let mut options = String::new();
+                for c in chars.by_ref() {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                    options.push(c);
+                }
+                for (option, arg) in regis_options(&options) {
+                    match (command, option) {
+                        ('C', 'C') => circle_at_point = true,
+                        ('C', 'A') => arc_sweep = arg.parse().ok(),
+                        ('W', 'I') => {
+                            if let Some(color) = regis_color(&arg, color_map) {
+                                state.foreground = color;
+                            }
+                        }
+                        ('S', 'I') => state.background = regis_color(&arg, color_map),
+                        ('S', 'E') => clear_canvas(canvas, state.background),
+                        _ => log::trace!("ReGIS: ignoring {}({}{})", command, option, arg),
+                    }
+                }
+            }
+            '\'' | '"' => {
+                // Quoted text, used by the T and L commands
+                let text: String = chars.by_ref().take_while(|&q| q != c).collect();
+                log::trace!("ReGIS: ignoring text {:?} for {}", text, command);
+            }
+            '@' => {
+                // Macrograph invocation or definition
+                chars.next();
+            }
+            c if c.is_ascii_alphabetic() => {
+                command = c.to_ascii_uppercase();
+                circle_at_point = false;
+                arc_sweep = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl TekState {
+    fn pixel(&self, canvas: &RgbaImage, point: (u16, u16)) -> (f32, f32) {
+        let scale = Scale::new(canvas, TEK_WIDTH, TEK_HEIGHT);
+        // The origin is at the bottom left of the Tek screen
+        scale.pixel((point.0 as f32, TEK_HEIGHT - point.1 as f32))
+    }
+
+    /// Processes a byte of an address while in graph mode
+    fn address_byte(&mut self, canvas: &mut RgbaImage, byte: u16, color: [u8; 4]) {
+        match byte {
+            0x20..=0x3f => {
+                if self.got_lo_y {
+                    self.hi_x = byte & 0x1f;
+                } else {
+                    self.hi_y = byte & 0x1f;
+                }
+            }
+            0x60..=0x7f => {
+                // A second low y byte means that the first was the
+                // 4014 extra byte, which we don't use
+                self.lo_y = byte & 0x1f;
+                self.got_lo_y = true;
+            }
+            0x40..=0x5f => {
+                let point = (
+                    (self.hi_x << 5) | (byte & 0x1f),
+                    (self.hi_y << 5) | self.lo_y,
+                );
+                self.got_lo_y = false;
+                if self.point_plot {
+                    let (x, y) = self.pixel(canvas, point);
+                    put_pixel(canvas, x.round() as i64, y.round() as i64, color);
+                } else if self.dark_vector {
+                    self.dark_vector = false;
+                } else {
+                    let from = self.pixel(canvas, self.position);
+                    let to = self.pixel(canvas, point);
+                    draw_line(canvas, from, to, color);
+                }
+                self.position = point;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl TerminalState {
+    /// Returns the canvas, (re)creating it to match the size of the screen
+    fn vector_canvas(&mut self) -> &mut RgbaImage {
+        let cols = self.screen().physical_cols;
+        let rows = self.screen().physical_rows;
+        let width = (self.pixel_width / cols.max(1) * cols).max(1) as u32;
+        let height = (self.pixel_height / rows.max(1) * rows).max(1) as u32;
+        let canvas = self
+            .vector
+            .canvas
+            .get_or_insert_with(|| RgbaImage::new(width, height));
+        if canvas.width() != width || canvas.height() != height {
+            *canvas = RgbaImage::new(width, height);
+        }
+        canvas
+    }
+
+    pub(crate) fn regis_enter(&mut self) {
+        self.vector.regis.replace(vec![]);
+    }
+
+    /// Returns true if a ReGIS command string is being accumulated
+    pub(crate) fn regis_active(&self) -> bool {
+        self.vector.regis.is_some()
+    }
+
+    pub(crate) fn regis_data(&mut self, byte: u8) {
+        if let Some(data) = self.vector.regis.as_mut() {
+            if data.len() < MAX_REGIS_BYTES {
+                data.push(byte);
+            } else {
+                log::error!("ReGIS data exceeds {} bytes; ignoring it", MAX_REGIS_BYTES);
+                self.vector.regis.take();
+            }
+        }
+    }
+
+    pub(crate) fn regis_exit(&mut self) {
+        if let Some(data) = self.vector.regis.take() {
+            let data = String::from_utf8_lossy(&data).to_string();
+            self.vector_canvas();
+            let canvas = self.vector.canvas.as_mut().unwrap();
+            run_regis(&data, &mut self.vector.regis_state, canvas, &self.color_map);
+            self.vector.dirty = true;
+        }
+    }
+
+    pub(crate) fn tek_mode(&self) -> bool {
+        self.vector.tek.is_some()
+    }
+
+    pub(crate) fn set_tek_mode(&mut self, enable: bool) {
+        if enable {
+            self.vector.tek.get_or_insert_with(TekState::default);
+        } else {
+            self.vector.tek.take();
+        }
+    }
+
+    /// Handles a printable character in Tektronix mode.
+    /// Returns false if the character should be printed as text,
+    /// which is the case in alpha mode.
+    pub(crate) fn tek_print(&mut self, c: char) -> bool {
+        match self.vector.tek.as_ref() {
+            Some(tek) if tek.graph => {}
+            _ => return false,
+        }
+        self.vector_canvas();
+        let canvas = self.vector.canvas.as_mut().unwrap();
+        if let Some(tek) = self.vector.tek.as_mut() {
+            tek.address_byte(canvas, c as u32 as u16, DEFAULT_FOREGROUND);
+        }
+        self.vector.dirty = true;
+        true
+    }
+
+    /// Handles a control code in Tektronix mode.
+    /// Returns false if the control should be processed as normal.
+    pub(crate) fn tek_control(&mut self, code: ControlCode) -> bool {
+        let tek = match self.vector.tek.as_mut() {
+            Some(tek) => tek,
+            None => return false,
+        };
+        match code {
+            ControlCode::GroupSeparator => {
+                tek.graph = true;
+                tek.point_plot = false;
+                tek.dark_vector = true;
+                tek.got_lo_y = false;
+            }
+            ControlCode::FileSeparator => {
+                tek.graph = true;
+                tek.point_plot = true;
+                tek.got_lo_y = false;
+            }
+            ControlCode::UnitSeparator => {
+                tek.graph = false;
+            }
+            ControlCode::RecordSeparator => {
+                // Incremental plot mode isn't supported
+                tek.graph = false;
+            }
+            ControlCode::CarriageReturn if tek.graph => {
+                tek.graph = false;
+            }
+            ControlCode::EndOfText => {
+                // ESC ETX returns to VT mode
+                self.vector.tek.take();
+            }
+            ControlCode::FormFeed => {
+                // ESC FF erases the screen
+                tek.graph = false;
+                clear_canvas(self.vector_canvas(), None);
+                self.vector.dirty = true;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Attaches the canvas to the cells if it was changed
+    pub(crate) fn flush_vector_graphics(&mut self) {
+        if !self.vector.dirty {
+            return;
+        }
+        self.vector.dirty = false;
+        let canvas = match self.vector.canvas.as_ref() {
+            Some(canvas) => canvas,
+            None => return,
+        };
+        let (width, height) = canvas.dimensions();
+        let image_data = ImageDataType::new_single_frame(width, height, canvas.as_raw().to_vec());
+        let image_data = self.raw_image_to_image_data(image_data);
+
+        let old_cursor = self.cursor;
+        self.cursor.x = 0;
+        self.cursor.y = 0;
+        if let Err(err) = self.assign_image_to_cells(ImageAttachParams {
+            image_width: width,
+            image_height: height,
+            source_width: width,
+            source_height: height,
+            rows: Some(self.screen().physical_rows),
+            columns: Some(self.screen().physical_cols),
+            source_origin_x: 0,
+            source_origin_y: 0,
+            padding_left: 0,
+            padding_top: 0,
+            data: image_data,
+            style: ImageAttachStyle::Sixel,
+            z_index: 0,
+            image_id: None,
+            placement_id: None,
+            do_not_move_cursor: true,
+        }) {
+            log::error!("set vector graphics image: {:#}", err);
+        }
+        self.cursor = old_cursor;
+    }
+
+    pub(crate) fn reset_vector_graphics(&mut self) {
+        self.vector = VectorGraphics::default();
+    }
+}
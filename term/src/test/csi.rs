@@ -399,3 +399,164 @@ fn test_ed_erase_scrollback() {
     term.print("b");
     assert_all_contents(&term, file!(), line!(), &["111", "222", "ab"]);
 }
+
+#[test]
+fn test_horizontal_scrolling() {
+    let mut term = TestTerm::new(3, 6, 0);
+    term.print("abcdef\r\nghijkl\r\nmnopqr");
+
+    // Set the left and right margins to columns 2-5
+    term.print("\x1b[?69h\x1b[2;5s");
+    term.assert_cursor_pos(0, 0, None, None);
+
+    // SL - scroll left within the margins
+    term.print("\x1b[ @");
+    assert_visible_contents(&term, file!(), line!(), &["acde f", "gijk l", "mopq r"]);
+
+    // SR - scroll right within the margins
+    term.print("\x1b[2 A");
+    assert_visible_contents(&term, file!(), line!(), &["a  cdf", "g  ijl", "m  opr"]);
+
+    // DECDC - delete a column at the cursor
+    term.cup(3, 0);
+    term.print("\x1b['~");
+    assert_visible_contents(&term, file!(), line!(), &["a  d f", "g  j l", "m  p r"]);
+
+    // DECIC - insert a column at the cursor
+    term.print("\x1b['}");
+    assert_visible_contents(&term, file!(), line!(), &["a   df", "g   jl", "m   pr"]);
+
+    // DECFI at the right margin scrolls the margins left
+    term.cup(4, 0);
+    term.print("\x1b9");
+    assert_visible_contents(&term, file!(), line!(), &["a  d f", "g  j l", "m  p r"]);
+
+    // DECBI at the left margin scrolls the margins right
+    term.cup(1, 0);
+    term.print("\x1b6");
+    assert_visible_contents(&term, file!(), line!(), &["a   df", "g   jl", "m   pr"]);
+
+    // HPA is relative to the left margin in origin mode
+    term.print("\x1b[?6h\x1b[2`");
+    term.assert_cursor_pos(2, 0, None, None);
+    term.print("\x1b[10`");
+    term.assert_cursor_pos(4, 0, None, None);
+}
+
+#[test]
+fn test_ambiguous_width_mode() {
+    let mut term = TestTerm::new(2, 6, 0);
+    term.print("a\u{2605}b");
+    term.assert_cursor_pos(3, 0, None, None);
+    assert!(!term.ambiguous_width_is_wide());
+
+    // Switching the mode lays out the existing content again
+    term.print("\x1b[?8428h");
+    assert!(term.ambiguous_width_is_wide());
+    let line = &term.screen().visible_lines()[0];
+    assert_eq!(line.get_cell(1).map(|c| c.width()), Some(2));
+    assert_eq!(
+        line.get_cell(3).map(|c| c.str().to_string()),
+        Some("b".to_string())
+    );
+
+    term.print("\r\n\u{2605}c");
+    term.assert_cursor_pos(3, 1, None, None);
+
+    term.print("\x1b[?8428l");
+    assert!(!term.ambiguous_width_is_wide());
+    assert_visible_contents(&term, file!(), line!(), &["a\u{2605}b", "\u{2605}c"]);
+    let line = &term.screen().visible_lines()[1];
+    assert_eq!(
+        line.get_cell(1).map(|c| c.str().to_string()),
+        Some("c".to_string())
+    );
+}
+
+#[test]
+fn test_grapheme_clustering_mode() {
+    fn cell_text(term: &TestTerm, x: usize, y: usize) -> Option<String> {
+        term.screen().visible_lines()[y]
+            .get_cell(x)
+            .map(|c| c.str().to_string())
+    }
+
+    let mut term = TestTerm::new(2, 10, 0);
+
+    // A ZWJ sequence that is split across batches of output
+    // still occupies a single cell
+    term.print("\u{1f469}");
+    term.print("\u{200d}\u{1f467}x");
+    term.assert_cursor_pos(3, 0, None, None);
+    assert_eq!(
+        cell_text(&term, 0, 0),
+        Some("\u{1f469}\u{200d}\u{1f467}".to_string())
+    );
+    assert_eq!(cell_text(&term, 2, 0), Some("x".to_string()));
+
+    // Without grapheme clustering, each codepoint occupies its own
+    // cell and zero width codepoints combine with the prior cell
+    term.print("\x1b[?2027l\r\n\u{1f469}\u{200d}");
+    term.print("\u{1f467}e");
+    term.print("\u{301}");
+    term.assert_cursor_pos(5, 1, None, None);
+    assert_eq!(
+        cell_text(&term, 0, 1),
+        Some("\u{1f469}\u{200d}".to_string())
+    );
+    assert_eq!(cell_text(&term, 2, 1), Some("\u{1f467}".to_string()));
+    assert_eq!(cell_text(&term, 4, 1), Some("e\u{301}".to_string()));
+}
+
+#[test]
+fn test_bidi_modes() {
+    use wezterm_bidi::ParagraphDirectionHint;
+
+    fn bidi_info(term: &TestTerm, y: usize) -> (bool, ParagraphDirectionHint) {
+        term.screen().visible_lines()[y].bidi_info()
+    }
+
+    let mut term = TestTerm::new(3, 10, 0);
+    term.print("abc");
+    assert_eq!(
+        bidi_info(&term, 0),
+        (false, ParagraphDirectionHint::LeftToRight)
+    );
+
+    // BDSM and SCP apply to the line containing the cursor, and to
+    // the blank lines that it subsequently moves onto
+    term.print("\x1b[8h\x1b[2 k");
+    assert_eq!(
+        bidi_info(&term, 0),
+        (true, ParagraphDirectionHint::RightToLeft)
+    );
+    term.print("\r\n");
+    assert_eq!(
+        bidi_info(&term, 1),
+        (true, ParagraphDirectionHint::RightToLeft)
+    );
+
+    // Auto-detection falls back to the selected direction
+    term.print("\x1b[?2501h");
+    assert_eq!(
+        bidi_info(&term, 1),
+        (true, ParagraphDirectionHint::AutoRightToLeft)
+    );
+    term.print("\x1b[?2501l");
+    assert_eq!(
+        bidi_info(&term, 1),
+        (true, ParagraphDirectionHint::RightToLeft)
+    );
+
+    // An override applies to all of the existing lines
+    term.set_bidi_mode_override(None, None);
+    assert_eq!(
+        bidi_info(&term, 0),
+        (false, ParagraphDirectionHint::LeftToRight)
+    );
+    term.set_bidi_mode_override(Some(true), Some(ParagraphDirectionHint::AutoLeftToRight));
+    assert_eq!(
+        bidi_info(&term, 2),
+        (true, ParagraphDirectionHint::AutoLeftToRight)
+    );
+}
@@ -55,6 +55,10 @@ impl TerminalConfiguration for TestTermConfig {
     fn color_palette(&self) -> ColorPalette {
         ColorPalette::default()
     }
+
+    fn enable_vector_graphics(&self) -> bool {
+        true
+    }
 }
 
 impl TestTerm {
@@ -756,6 +760,41 @@ fn test_dec_double_width() {
     assert!(lines[3].is_single_width());
 }
 
+/// Test DECCRA, DECFRA and DECERA rectangular area operations
+#[test]
+fn test_dec_rectangular_area_ops() {
+    let mut term = TestTerm::new(4, 6, 0);
+    term.print("abcdef\r\nghijkl\r\nmnopqr\r\nstuvwx");
+
+    // Fill rows 2-3, columns 2-4 with `*`
+    term.print("\x1b[42;2;2;3;4$x");
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["abcdef", "g***kl", "m***qr", "stuvwx"],
+    );
+
+    // Copy the top left 2x2 area to the bottom right, clipping it
+    // to the page
+    term.print("\x1b[1;1;2;2;1;4;6;1$v");
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["abcdef", "g***kl", "m***qr", "stuvwa"],
+    );
+
+    // Erase from row 3, column 5 to the bottom right of the page
+    term.print("\x1b[3;5$z");
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["abcdef", "g***kl", "m***", "stuv"],
+    );
+}
+
 /// This test skips over an edge case with cursor positioning,
 /// while sizing down, but tries to trip over the same edge
 /// case while sizing back up again
@@ -1266,3 +1305,71 @@ fn test_hyperlinks() {
         Compare::TEXT | Compare::ATTRS,
     );
 }
+
+#[test]
+fn test_iterm_badge() {
+    let mut term = TestTerm::new(3, 10, 0);
+    assert_eq!(term.badge(), None);
+
+    // host=\(user.host) \(session.columns)x\(session.rows)
+    term.print(
+        "\x1b]1337;SetBadgeFormat=\
+         aG9zdD1cKHVzZXIuaG9zdCkgXChzZXNzaW9uLmNvbHVtbnMpeFwoc2Vzc2lvbi5yb3dzKQ==\x1b\\",
+    );
+    assert_eq!(term.badge(), Some("host= 10x3"));
+
+    term.print("\x1b]1337;SetUserVar=host=cHJvZA==\x1b\\");
+    assert_eq!(term.badge(), Some("host=prod 10x3"));
+
+    term.resize(TerminalSize {
+        rows: 4,
+        cols: 12,
+        pixel_width: 0,
+        pixel_height: 0,
+        dpi: 0,
+    });
+    assert_eq!(term.badge(), Some("host=prod 12x4"));
+
+    term.print("\x1b]1337;SetBadgeFormat=\x1b\\");
+    assert_eq!(term.badge(), None);
+}
+
+#[test]
+fn test_vector_graphics() {
+    fn has_canvas(term: &TestTerm) -> bool {
+        term.screen().visible_lines().iter().all(|line| {
+            line.visible_cells()
+                .all(|cell| cell.attrs().images().is_some())
+        })
+    }
+
+    // ReGIS: a diagonal line across the screen
+    let mut term = TestTerm::new(2, 4, 0);
+    term.print("\x1bPpS(E)P[0,0]V[799,479]\x1b\\");
+    assert!(has_canvas(&term));
+    assert_visible_contents(&term, file!(), line!(), &["", ""]);
+
+    // Tek 4014: a vector from (0,0) to (1023,779), then alpha mode text
+    let mut term = TestTerm::new(2, 4, 0);
+    term.print("\x1b[?38h\x1d `@8k?_\x1fhi\x1b[?38l");
+    assert!(has_canvas(&term));
+    assert_visible_contents(&term, file!(), line!(), &["hi", ""]);
+
+    term.print("\r\nok");
+    assert_visible_contents(&term, file!(), line!(), &["hi", "ok"]);
+}
+
+#[test]
+fn test_synchronized_output_state() {
+    let mut term = TestTerm::new(2, 4, 0);
+    assert!(!term.is_synchronized_output_active());
+
+    term.print("\x1b[?2026h");
+    assert!(term.is_synchronized_output_active());
+    term.print("\x1b[?2026l");
+    assert!(!term.is_synchronized_output_active());
+
+    // DECSTR ends an update that the application didn't finish
+    term.print("\x1b[?2026h\x1b[!p");
+    assert!(!term.is_synchronized_output_active());
+}
@@ -136,6 +136,12 @@ impl CellCluster {
         }
 
         if let Some(hint) = bidi_hint {
+            // Detect the direction of the line as a whole, rather than
+            // separately for each run of attributes
+            let hint = match hint.resolve_direction(clusters.iter().flat_map(|c| c.text.chars())) {
+                Direction::LeftToRight => ParagraphDirectionHint::LeftToRight,
+                Direction::RightToLeft => ParagraphDirectionHint::RightToLeft,
+            };
             let mut resolved_clusters = vec![];
 
             let mut context = BidiContext::new();
@@ -4,7 +4,7 @@ use crate::color::{AnsiColor, ColorSpec, RgbColor, SrgbaTuple};
 use crate::input::{Modifiers, MouseButtons};
 use num_derive::*;
 use num_traits::{FromPrimitive, ToPrimitive};
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::fmt::{Display, Error as FmtError, Formatter};
 
 pub use vtparse::CsiParam;
@@ -488,6 +488,40 @@ pub enum Window {
         bottom: OneBased,
         right: OneBased,
     },
+    /// DECCRA; copies the rectangular area to the destination.
+    /// An omitted bottom or right edge extends to the edge of the page.
+    CopyRectangularArea {
+        top: OneBased,
+        left: OneBased,
+        bottom: Option<OneBased>,
+        right: Option<OneBased>,
+        source_page: u32,
+        dest_top: OneBased,
+        dest_left: OneBased,
+        dest_page: u32,
+    },
+    /// DECFRA; fills the rectangular area with the specified character
+    FillRectangularArea {
+        ch: char,
+        top: OneBased,
+        left: OneBased,
+        bottom: Option<OneBased>,
+        right: Option<OneBased>,
+    },
+    /// DECERA; erases the rectangular area
+    EraseRectangularArea {
+        top: OneBased,
+        left: OneBased,
+        bottom: Option<OneBased>,
+        right: Option<OneBased>,
+    },
+}
+
+fn edge_or_empty(x: &Option<OneBased>) -> String {
+    match x {
+        Some(x) => format!("{}", x),
+        None => "".to_owned(),
+    }
 }
 
 fn numstr_or_empty(x: &Option<i64>) -> String {
@@ -560,6 +594,55 @@ impl Display for Window {
                 "{};{};{};{};{};{}*y",
                 request_id, page_number, top, left, bottom, right,
             ),
+            Window::CopyRectangularArea {
+                top,
+                left,
+                bottom,
+                right,
+                source_page,
+                dest_top,
+                dest_left,
+                dest_page,
+            } => write!(
+                f,
+                "{};{};{};{};{};{};{};{}$v",
+                top,
+                left,
+                edge_or_empty(bottom),
+                edge_or_empty(right),
+                source_page,
+                dest_top,
+                dest_left,
+                dest_page
+            ),
+            Window::FillRectangularArea {
+                ch,
+                top,
+                left,
+                bottom,
+                right,
+            } => write!(
+                f,
+                "{};{};{};{};{}$x",
+                *ch as u32,
+                top,
+                left,
+                edge_or_empty(bottom),
+                edge_or_empty(right)
+            ),
+            Window::EraseRectangularArea {
+                top,
+                left,
+                bottom,
+                right,
+            } => write!(
+                f,
+                "{};{};{};{}$z",
+                top,
+                left,
+                edge_or_empty(bottom),
+                edge_or_empty(right)
+            ),
         }
     }
 }
@@ -803,6 +886,9 @@ pub enum DecPrivateModeCode {
     StartBlinkingCursor = 12,
     ShowCursor = 25,
 
+    /// DECTEK - switch to Tektronix 4014 mode
+    TektronixMode = 38,
+
     ReverseWraparound = 45,
 
     /// https://vt100.net/docs/vt510-rm/DECLRMM.html
@@ -846,8 +932,17 @@ pub enum DecPrivateModeCode {
     /// <https://gist.github.com/christianparpart/d8a62cc1ab659194337d73e399004036>
     SynchronizedOutput = 2026,
 
+    /// <https://github.com/contour-terminal/terminal-unicode-core>
+    GraphemeClustering = 2027,
+
+    /// Auto-detect the direction of each paragraph for bidi
+    BidiAutoDetection = 2501,
+
     MinTTYApplicationEscapeKeyMode = 7727,
 
+    /// Treat East Asian Ambiguous width characters as double width
+    AmbiguousWidthIsWide = 8428,
+
     /// xterm: adjust cursor positioning after emitting sixel
     SixelScrollsRight = 8452,
 
@@ -1121,6 +1216,31 @@ pub enum Edit {
 
     /// REP - Repeat the preceding character n times
     Repeat(u32),
+
+    /// SL - SCROLL LEFT
+    /// Moves the data within the left and right margins n columns to the
+    /// left; blank columns are inserted at the right margin.
+    /// <https://vt100.net/docs/vt510-rm/SL.html>
+    ScrollLeft(u32),
+
+    /// SR - SCROLL RIGHT
+    /// Moves the data within the left and right margins n columns to the
+    /// right; blank columns are inserted at the left margin.
+    /// <https://vt100.net/docs/vt510-rm/SR.html>
+    ScrollRight(u32),
+
+    /// DECIC - Insert Column
+    /// Inserts n blank columns at the cursor position within the margins,
+    /// shifting the columns to the right of the cursor towards the right
+    /// margin.
+    /// <https://vt100.net/docs/vt510-rm/DECIC.html>
+    InsertColumn(u32),
+
+    /// DECDC - Delete Column
+    /// Deletes n columns at the cursor position within the margins,
+    /// shifting the columns to the right of them towards the cursor.
+    /// <https://vt100.net/docs/vt510-rm/DECDC.html>
+    DeleteColumn(u32),
 }
 
 trait EncodeCSIParam {
@@ -1171,6 +1291,10 @@ impl Display for Edit {
             Edit::ScrollUp(n) => n.write_csi(f, "S")?,
             Edit::EraseInDisplay(n) => n.write_csi(f, "J")?,
             Edit::Repeat(n) => n.write_csi(f, "b")?,
+            Edit::ScrollLeft(n) => n.write_csi(f, " @")?,
+            Edit::ScrollRight(n) => n.write_csi(f, " A")?,
+            Edit::InsertColumn(n) => n.write_csi(f, "'}")?,
+            Edit::DeleteColumn(n) => n.write_csi(f, "'~")?,
         }
         Ok(())
     }
@@ -1724,6 +1848,21 @@ impl<'a> CSIParser<'a> {
             ('k', [.., CsiParam::P(b' ')]) => self.select_character_path(params),
             ('q', [.., CsiParam::P(b' ')]) => self.cursor_style(params),
             ('y', [.., CsiParam::P(b'*')]) => self.checksum_area(params),
+            ('v', [.., CsiParam::P(b'$')]) => self.copy_area(params),
+            ('x', [.., CsiParam::P(b'$')]) => self.fill_area(params),
+            ('z', [.., CsiParam::P(b'$')]) => self.erase_area(params),
+            ('@', [.., CsiParam::P(b' ')]) => {
+                parse!(Edit, ScrollLeft, &params[..params.len() - 1])
+            }
+            ('A', [.., CsiParam::P(b' ')]) => {
+                parse!(Edit, ScrollRight, &params[..params.len() - 1])
+            }
+            ('}', [.., CsiParam::P(b'\'')]) => {
+                parse!(Edit, InsertColumn, &params[..params.len() - 1])
+            }
+            ('~', [.., CsiParam::P(b'\'')]) => {
+                parse!(Edit, DeleteColumn, &params[..params.len() - 1])
+            }
 
             ('c', [CsiParam::P(b'='), ..]) => self
                 .req_tertiary_device_attributes(params)
@@ -1961,6 +2100,84 @@ impl<'a> CSIParser<'a> {
         })))
     }
 
+    /// Parses the rectangle edges starting at `idx`; the bottom and
+    /// right edges default to the edge of the page when omitted or 0
+    fn rect_edges(
+        params: &Cracked,
+        idx: usize,
+    ) -> Result<(OneBased, OneBased, Option<OneBased>, Option<OneBased>), ()> {
+        let edge = |idx: usize| -> Result<Option<OneBased>, ()> {
+            match params.opt_int(idx) {
+                None | Some(0) => Ok(None),
+                Some(_) => OneBased::from_optional_esc_param(params.get(idx)).map(Some),
+            }
+        };
+        Ok((
+            OneBased::from_optional_esc_param(params.get(idx))?,
+            OneBased::from_optional_esc_param(params.get(idx + 1))?,
+            edge(idx + 2)?,
+            edge(idx + 3)?,
+        ))
+    }
+
+    fn page_number(params: &Cracked, idx: usize) -> Result<u32, ()> {
+        match params.opt_int(idx) {
+            None | Some(0) => Ok(1),
+            Some(n) => n.try_into().map_err(|_| ()),
+        }
+    }
+
+    fn copy_area(&mut self, params: &'a [CsiParam]) -> Result<CSI, ()> {
+        let params = Cracked::parse(&params[..params.len() - 1])?;
+
+        let (top, left, bottom, right) = Self::rect_edges(&params, 0)?;
+        let source_page = Self::page_number(&params, 4)?;
+        let dest_top = OneBased::from_optional_esc_param(params.get(5))?;
+        let dest_left = OneBased::from_optional_esc_param(params.get(6))?;
+        let dest_page = Self::page_number(&params, 7)?;
+        Ok(CSI::Window(Box::new(Window::CopyRectangularArea {
+            top,
+            left,
+            bottom,
+            right,
+            source_page,
+            dest_top,
+            dest_left,
+            dest_page,
+        })))
+    }
+
+    fn fill_area(&mut self, params: &'a [CsiParam]) -> Result<CSI, ()> {
+        let params = Cracked::parse(&params[..params.len() - 1])?;
+
+        let ch = params.int(0)?;
+        let ch = u32::try_from(ch)
+            .ok()
+            .and_then(char::from_u32)
+            .filter(|c| !c.is_control())
+            .ok_or(())?;
+        let (top, left, bottom, right) = Self::rect_edges(&params, 1)?;
+        Ok(CSI::Window(Box::new(Window::FillRectangularArea {
+            ch,
+            top,
+            left,
+            bottom,
+            right,
+        })))
+    }
+
+    fn erase_area(&mut self, params: &'a [CsiParam]) -> Result<CSI, ()> {
+        let params = Cracked::parse(&params[..params.len() - 1])?;
+
+        let (top, left, bottom, right) = Self::rect_edges(&params, 0)?;
+        Ok(CSI::Window(Box::new(Window::EraseRectangularArea {
+            top,
+            left,
+            bottom,
+            right,
+        })))
+    }
+
     fn dsr(&mut self, params: &'a [CsiParam]) -> Result<CSI, ()> {
         match params {
             [CsiParam::Integer(5)] => {
@@ -3072,6 +3289,121 @@ mod test {
         assert_eq!(res, vec![CSI::Device(Box::new(Device::SoftReset))],);
     }
 
+    #[test]
+    fn rectangular_area_ops() {
+        let res: Vec<_> = CSI::parse(
+            &[
+                CsiParam::Integer(88),
+                CsiParam::P(b';'),
+                CsiParam::Integer(1),
+                CsiParam::P(b';'),
+                CsiParam::Integer(2),
+                CsiParam::P(b';'),
+                CsiParam::Integer(3),
+                CsiParam::P(b';'),
+                CsiParam::Integer(4),
+                CsiParam::P(b'$'),
+            ],
+            false,
+            'x',
+        )
+        .collect();
+        assert_eq!(encode(&res), "\x1b[88;1;2;3;4$x");
+        assert_eq!(
+            res,
+            vec![CSI::Window(Box::new(Window::FillRectangularArea {
+                ch: 'X',
+                top: OneBased::new(1),
+                left: OneBased::new(2),
+                bottom: Some(OneBased::new(3)),
+                right: Some(OneBased::new(4)),
+            }))]
+        );
+
+        let res: Vec<_> = CSI::parse(
+            &[
+                CsiParam::Integer(2),
+                CsiParam::P(b';'),
+                CsiParam::Integer(3),
+                CsiParam::P(b';'),
+                CsiParam::P(b';'),
+                CsiParam::P(b'$'),
+            ],
+            false,
+            'z',
+        )
+        .collect();
+        assert_eq!(encode(&res), "\x1b[2;3;;$z");
+        assert_eq!(
+            res,
+            vec![CSI::Window(Box::new(Window::EraseRectangularArea {
+                top: OneBased::new(2),
+                left: OneBased::new(3),
+                bottom: None,
+                right: None,
+            }))]
+        );
+
+        let res: Vec<_> = CSI::parse(
+            &[
+                CsiParam::Integer(1),
+                CsiParam::P(b';'),
+                CsiParam::Integer(1),
+                CsiParam::P(b';'),
+                CsiParam::Integer(2),
+                CsiParam::P(b';'),
+                CsiParam::Integer(5),
+                CsiParam::P(b';'),
+                CsiParam::Integer(1),
+                CsiParam::P(b';'),
+                CsiParam::Integer(4),
+                CsiParam::P(b';'),
+                CsiParam::Integer(6),
+                CsiParam::P(b';'),
+                CsiParam::Integer(1),
+                CsiParam::P(b'$'),
+            ],
+            false,
+            'v',
+        )
+        .collect();
+        assert_eq!(encode(&res), "\x1b[1;1;2;5;1;4;6;1$v");
+        assert_eq!(
+            res,
+            vec![CSI::Window(Box::new(Window::CopyRectangularArea {
+                top: OneBased::new(1),
+                left: OneBased::new(1),
+                bottom: Some(OneBased::new(2)),
+                right: Some(OneBased::new(5)),
+                source_page: 1,
+                dest_top: OneBased::new(4),
+                dest_left: OneBased::new(6),
+                dest_page: 1,
+            }))]
+        );
+    }
+
+    #[test]
+    fn horizontal_scrolling() {
+        let res: Vec<_> =
+            CSI::parse(&[CsiParam::Integer(2), CsiParam::P(b' ')], false, '@').collect();
+        assert_eq!(encode(&res), "\x1b[2 @");
+        assert_eq!(res, vec![CSI::Edit(Edit::ScrollLeft(2))]);
+
+        let res: Vec<_> = CSI::parse(&[CsiParam::P(b' ')], false, 'A').collect();
+        assert_eq!(encode(&res), "\x1b[ A");
+        assert_eq!(res, vec![CSI::Edit(Edit::ScrollRight(1))]);
+
+        let res: Vec<_> =
+            CSI::parse(&[CsiParam::Integer(3), CsiParam::P(b'\'')], false, '}').collect();
+        assert_eq!(encode(&res), "\x1b[3'}");
+        assert_eq!(res, vec![CSI::Edit(Edit::InsertColumn(3))]);
+
+        let res: Vec<_> = CSI::parse(&[CsiParam::P(b'\'')], false, '~').collect();
+        assert_eq!(encode(&res), "\x1b['~");
+        assert_eq!(res, vec![CSI::Edit(Edit::DeleteColumn(1))]);
+    }
+
     #[test]
     fn device_attr() {
         let res: Vec<_> = CSI::parse(
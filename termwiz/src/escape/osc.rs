@@ -842,6 +842,8 @@ pub enum ITermProprietary {
         value: String,
     },
     SetBadgeFormat(String),
+    /// Request the attention of the user, eg: by bouncing the dock icon
+    RequestAttention(ITermRequestAttention),
     /// Download file data from the application.
     File(Box<ITermFileData>),
 
@@ -849,6 +851,39 @@ pub enum ITermProprietary {
     UnicodeVersion(ITermUnicodeVersionOp),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ITermRequestAttention {
+    /// Keep requesting attention until the application is activated
+    Yes,
+    /// Request attention a single time
+    Once,
+    /// Cancel a previous request
+    No,
+    /// Show an animation at the cursor position
+    Fireworks,
+}
+
+impl ITermRequestAttention {
+    fn from_keyword(s: &str) -> Option<Self> {
+        match s {
+            "yes" => Some(Self::Yes),
+            "once" => Some(Self::Once),
+            "no" => Some(Self::No),
+            "fireworks" => Some(Self::Fireworks),
+            _ => None,
+        }
+    }
+
+    fn keyword(self) -> &'static str {
+        match self {
+            Self::Yes => "yes",
+            Self::Once => "once",
+            Self::No => "no",
+            Self::Fireworks => "fireworks",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ITermUnicodeVersionOp {
     Set(u8),
@@ -1154,6 +1189,25 @@ impl ITermProprietary {
                 base64::decode(osc[2])?,
             )?));
         }
+        if osc.len() == 2 && keyword == "SetBadgeFormat" {
+            if let Some(p1) = p1 {
+                return Ok(ITermProprietary::SetBadgeFormat(String::from_utf8(
+                    base64::decode(p1)?,
+                )?));
+            }
+        }
+        if osc.len() == 2 && keyword == "ReportVariable" {
+            if let Some(p1) = p1 {
+                return Ok(ITermProprietary::ReportVariable(String::from_utf8(
+                    base64::decode(p1)?,
+                )?));
+            }
+        }
+        if osc.len() == 2 && keyword == "RequestAttention" {
+            if let Some(attention) = p1.and_then(ITermRequestAttention::from_keyword) {
+                return Ok(ITermProprietary::RequestAttention(attention));
+            }
+        }
 
         if osc.len() == 3 && keyword == "ReportCellSize" && p1.is_some() {
             if let Some(p1) = p1 {
@@ -1257,6 +1311,7 @@ impl Display for ITermProprietary {
                 write!(f, "SetUserVar={}={}", name, base64::encode(value))?
             }
             SetBadgeFormat(s) => write!(f, "SetBadgeFormat={}", base64::encode(s))?,
+            RequestAttention(attention) => write!(f, "RequestAttention={}", attention.keyword())?,
             File(file) => file.fmt(f)?,
             UnicodeVersion(ITermUnicodeVersionOp::Set(n)) => write!(f, "UnicodeVersion={}", n)?,
             UnicodeVersion(ITermUnicodeVersionOp::Push(Some(label))) => {
@@ -1644,6 +1699,36 @@ mod test {
             ))
         );
 
+        assert_eq!(
+            parse(
+                &["1337", "SetBadgeFormat=aGVsbG8="],
+                "\x1b]1337;SetBadgeFormat=aGVsbG8=\x1b\\"
+            ),
+            OperatingSystemCommand::ITermProprietary(ITermProprietary::SetBadgeFormat(
+                "hello".into()
+            ))
+        );
+
+        assert_eq!(
+            parse(
+                &["1337", "ReportVariable=dXNlci5mb28="],
+                "\x1b]1337;ReportVariable=dXNlci5mb28=\x1b\\"
+            ),
+            OperatingSystemCommand::ITermProprietary(ITermProprietary::ReportVariable(
+                "user.foo".into()
+            ))
+        );
+
+        assert_eq!(
+            parse(
+                &["1337", "RequestAttention=once"],
+                "\x1b]1337;RequestAttention=once\x1b\\"
+            ),
+            OperatingSystemCommand::ITermProprietary(ITermProprietary::RequestAttention(
+                ITermRequestAttention::Once
+            ))
+        );
+
         assert_eq!(
             parse(
                 &["1337", "ReportCellSize=12.0", "15.5"],
@@ -1,4 +1,4 @@
-use crate::cell::{Cell, CellAttributes, SemanticType, UnicodeVersion};
+use crate::cell::{grapheme_column_width, Cell, CellAttributes, SemanticType, UnicodeVersion};
 use crate::cellcluster::CellCluster;
 use crate::hyperlink::Rule;
 use crate::surface::line::cellref::CellRef;
@@ -358,7 +358,7 @@ impl Line {
     /// before falling back to the specified direction.
     pub fn set_direction(&mut self, direction: Direction, auto_detect: bool, seqno: SequenceNo) {
         self.bits
-            .set(LineBits::RTL, direction == Direction::LeftToRight);
+            .set(LineBits::RTL, direction == Direction::RightToLeft);
         self.bits.set(LineBits::AUTO_DETECT_DIRECTION, auto_detect);
         self.update_last_change_seqno(seqno);
     }
@@ -381,6 +381,21 @@ impl Line {
         self.update_last_change_seqno(seqno);
     }
 
+    /// Returns the direction in which the line is laid out.  When the
+    /// direction hint of the line calls for auto-detection, it is the
+    /// direction of the first strong directional character in the line.
+    pub fn resolved_direction(&self) -> Direction {
+        let (_, hint) = self.bidi_info();
+        match hint {
+            ParagraphDirectionHint::LeftToRight | ParagraphDirectionHint::RightToLeft => {
+                hint.direction()
+            }
+            ParagraphDirectionHint::AutoLeftToRight | ParagraphDirectionHint::AutoRightToLeft => {
+                hint.resolve_direction(self.as_str().chars())
+            }
+        }
+    }
+
     /// Returns a tuple of (BIDI_ENABLED, Direction), indicating whether
     /// the line should have the bidi algorithm applied and its base
     /// direction, respectively.
@@ -1112,6 +1127,62 @@ impl Line {
         self.invalidate_zones();
     }
 
+    /// Recompute the width of each cell according to `unicode_version`,
+    /// shifting the cells that follow a cell whose width changed so that
+    /// they remain adjacent to it.
+    /// The line retains its length; cells that no longer fit are
+    /// discarded from the end of the line.
+    /// Returns true if any cell changed width.
+    pub fn relayout_for_unicode_version(
+        &mut self,
+        unicode_version: UnicodeVersion,
+        seqno: SequenceNo,
+    ) -> bool {
+        // Zero-width graphemes are never stored in their own cell,
+        // so every cell occupies at least one column
+        let width_of = |text: &str| grapheme_column_width(text, Some(unicode_version)).max(1);
+        if self
+            .visible_cells()
+            .all(|cell| cell.width() == width_of(cell.str()))
+        {
+            return false;
+        }
+
+        let len = self.len();
+        let wrapped = self.last_cell_was_wrapped();
+        let mut cells = Vec::with_capacity(len);
+        for cell in self.visible_cells() {
+            let width = width_of(cell.str());
+            if cells.len() + width > len {
+                break;
+            }
+            let mut attrs = cell.attrs().clone();
+            attrs.set_wrapped(false);
+            if attrs
+                .hyperlink()
+                .map(|link| link.is_implicit())
+                .unwrap_or(false)
+            {
+                // Implicit hyperlinks are recomputed by the next scan
+                attrs.set_hyperlink(None);
+            }
+            cells.push(Cell::new_grapheme_with_width(
+                cell.str(),
+                width,
+                attrs.clone(),
+            ));
+            for _ in 1..width {
+                cells.push(Cell::new(' ', attrs.clone()));
+            }
+        }
+
+        self.cells = CellStorage::V(VecStorage::new(cells));
+        self.resize(len, seqno);
+        self.set_last_cell_was_wrapped(wrapped, seqno);
+        self.bits &= !(LineBits::SCANNED_IMPLICIT_HYPERLINKS | LineBits::HAS_IMPLICIT_HYPERLINKS);
+        true
+    }
+
     /// mutable access the cell data, but the caller must take care
     /// to only mutate attributes rather than the cell textual content.
     /// Use set_cell if you need to modify the textual content of the
@@ -1,7 +1,7 @@
 #![cfg(test)]
 
 use super::*;
-use crate::cell::{Cell, CellAttributes};
+use crate::cell::{Cell, CellAttributes, UnicodeVersion};
 use crate::hyperlink::{Hyperlink, Rule};
 use crate::surface::line::clusterline::ClusteredLine;
 use crate::surface::SEQ_ZERO;
@@ -608,3 +608,35 @@ Line {
 "#
     );
 }
+
+#[test]
+fn relayout_for_unicode_version() {
+    let narrow = UnicodeVersion {
+        version: 14,
+        ambiguous_are_wide: false,
+    };
+    let wide = UnicodeVersion {
+        version: 14,
+        ambiguous_are_wide: true,
+    };
+
+    let mut line = Line::from_text("a★b", &CellAttributes::default(), SEQ_ZERO, Some(narrow));
+    line.resize(5, SEQ_ZERO);
+    line.set_last_cell_was_wrapped(true, SEQ_ZERO);
+    assert!(!line.relayout_for_unicode_version(narrow, 1));
+
+    assert!(line.relayout_for_unicode_version(wide, 1));
+    assert_eq!(line.len(), 5);
+    assert_eq!(line.as_str(), "a★b ");
+    assert_eq!(line.get_cell(1).map(|c| c.width()), Some(2));
+    assert_eq!(
+        line.get_cell(3).map(|c| c.str().to_string()),
+        Some("b".to_string())
+    );
+    assert!(line.last_cell_was_wrapped());
+    assert_eq!(line.current_seqno(), 1);
+
+    assert!(line.relayout_for_unicode_version(narrow, 2));
+    assert_eq!(line.as_str(), "a★b  ");
+    assert_eq!(line.len(), 5);
+}
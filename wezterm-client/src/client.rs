@@ -2,34 +2,45 @@ use crate::domain::{ClientDomain, ClientDomainConfig};
 use crate::pane::ClientPane;
 use crate::UnixStream;
 use anyhow::{anyhow, bail, Context};
-use async_ossl::AsyncSslStream;
+use async_ossl::{AsyncSslStream, AsyncWebSocketStream, UdpKey, WebSocketTransport};
 use async_trait::async_trait;
 use codec::*;
-use config::{configuration, SshDomain, TlsDomainClient, UnixDomain, UnixTarget};
+use config::{
+    configuration, read_wss_auth_token, SshDomain, TlsDomainClient, UdpDomainClient, UnixDomain,
+    UnixTarget, WssDomainClient,
+};
 use filedescriptor::FileDescriptor;
 use futures::FutureExt;
 use mux::client::ClientId;
 use mux::connui::ConnectionUI;
 use mux::domain::DomainId;
+use mux::domainstats;
 use mux::pane::PaneId;
 use mux::ssh::ssh_connect_with_ui;
 use mux::Mux;
-use openssl::ssl::{SslConnector, SslFiletype, SslMethod};
+use openssl::asn1::Asn1Time;
+use openssl::hash::MessageDigest;
+use openssl::ssl::{SslConnector, SslFiletype, SslMethod, SslStream, SslVerifyMode};
 use openssl::x509::X509;
 use portable_pty::Child;
 use smol::channel::{bounded, unbounded, Receiver, Sender};
 use smol::prelude::*;
 use smol::{block_on, Async};
 use std::collections::HashMap;
-use std::io::{Read, Write};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::marker::Unpin;
 use std::net::TcpStream;
 #[cfg(unix)]
 use std::os::unix::process::CommandExt;
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use tungstenite::client::IntoClientRequest;
 
 #[derive(Error, Debug)]
 #[error("Timeout")]
@@ -45,6 +56,8 @@ enum ReaderMessage {
         promise: Sender<anyhow::Result<Pdu>>,
     },
     Readable,
+    /// Deliver MuxEvent PDUs received from the server via this channel
+    SetEventSender(Sender<MuxEvent>),
 }
 
 #[derive(Clone)]
@@ -254,6 +267,7 @@ async fn client_thread_async(
     rx: &mut Receiver<ReaderMessage>,
 ) -> anyhow::Result<()> {
     let mut next_serial = 1u64;
+    let mut compression = Compression::default();
 
     struct Promises {
         map: HashMap<u64, Sender<anyhow::Result<Pdu>>>,
@@ -277,7 +291,15 @@ async fn client_thread_async(
         map: HashMap::new(),
     };
 
-    let mut stream = reconnectable.take_stream().unwrap();
+    let mut event_sender: Option<Sender<MuxEvent>> = None;
+
+    // Requests that are awaiting a response, and when they were sent
+    let mut sent_at: HashMap<u64, Instant> = HashMap::new();
+
+    let mut stream = MeteredStream {
+        inner: reconnectable.take_stream().unwrap(),
+        domain_id: local_domain_id,
+    };
 
     loop {
         let rx_msg = rx.recv();
@@ -290,17 +312,34 @@ async fn client_thread_async(
                 let serial = next_serial;
                 next_serial += 1;
                 promises.map.insert(serial, promise);
+                sent_at.insert(serial, Instant::now());
 
-                pdu.encode_async(&mut stream, serial)
+                pdu.encode_async_with_compression(&mut stream, serial, compression)
                     .await
                     .context("encoding a PDU to send to the server")?;
                 stream.flush().await.context("flushing PDU to server")?;
+
+                // We're asking the server to change its compression;
+                // use the same setting for what we send from here on
+                if let Pdu::SetCompression(SetCompression { compression: c }) = &pdu {
+                    compression = *c;
+                }
+            }
+            Ok(ReaderMessage::SetEventSender(sender)) => {
+                event_sender.replace(sender);
             }
             Ok(ReaderMessage::Readable) => {
                 match Pdu::decode_async(&mut stream, Some(next_serial)).await {
                     Ok(decoded) => {
                         log::trace!("decoded serial {}", decoded.serial);
-                        if decoded.serial == 0 {
+                        if let Pdu::MuxEvent(event) = decoded.pdu {
+                            if let Some(sender) = &event_sender {
+                                if sender.try_send(event).is_err() {
+                                    // Nobody is listening any more
+                                    event_sender.take();
+                                }
+                            }
+                        } else if decoded.serial == 0 {
                             process_unilateral(local_domain_id, decoded)
                                 .context("processing unilateral PDU from server")
                                 .map_err(|e| {
@@ -308,6 +347,11 @@ async fn client_thread_async(
                                     e
                                 })?;
                         } else if let Some(promise) = promises.map.remove(&decoded.serial) {
+                            if let (Some(domain_id), Some(when)) =
+                                (local_domain_id, sent_at.remove(&decoded.serial))
+                            {
+                                domainstats::record_rtt(domain_id, when.elapsed());
+                            }
                             if promise.try_send(Ok(decoded.pdu)).is_err() {
                                 return Err(NotReconnectableError::ClientWasDestroyed.into());
                             }
@@ -433,6 +477,56 @@ where
     }
 }
 
+/// Wraps the stream of a client domain, recording the bytes
+/// that pass through it in the stats for the domain
+#[derive(Debug)]
+struct MeteredStream {
+    inner: Box<dyn AsyncReadAndWrite>,
+    domain_id: Option<DomainId>,
+}
+
+impl MeteredStream {
+    async fn wait_for_readable(&self) -> anyhow::Result<()> {
+        self.inner.wait_for_readable().await
+    }
+}
+
+impl AsyncRead for MeteredStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let (Poll::Ready(Ok(size)), Some(domain_id)) = (&result, self.domain_id) {
+            domainstats::record_received(domain_id, *size);
+        }
+        result
+    }
+}
+
+impl AsyncWrite for MeteredStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let (Poll::Ready(Ok(size)), Some(domain_id)) = (&result, self.domain_id) {
+            domainstats::record_sent(domain_id, *size);
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
 #[derive(Debug)]
 struct Reconnectable {
     config: ClientDomainConfig,
@@ -503,6 +597,10 @@ impl Reconnectable {
         Ok(self.tls_creds_path()?.join("cert.pem"))
     }
 
+    fn tls_known_server_fingerprint_path(&self) -> anyhow::Result<PathBuf> {
+        Ok(self.tls_creds_path()?.join("known_server_fingerprint"))
+    }
+
     fn take_stream(&mut self) -> Option<Box<dyn AsyncReadAndWrite>> {
         self.stream.take()
     }
@@ -518,6 +616,8 @@ impl Reconnectable {
             // the set of tabs and we'd have confusing and inconsistent state
             ClientDomainConfig::Unix(_) => false,
             ClientDomainConfig::Tls(_) => true,
+            ClientDomainConfig::Wss(_) => true,
+            ClientDomainConfig::Udp(_) => true,
             // It *does* make sense to reconnect with an ssh session, but we
             // need to grow some smarts about whether the disconnect was because
             // we sent CTRL-D to close the last session, or whether it was a network
@@ -539,6 +639,8 @@ impl Reconnectable {
             }
             ClientDomainConfig::Tls(tls) => self.tls_connect(tls, initial, ui),
             ClientDomainConfig::Ssh(ssh) => self.ssh_connect(ssh, initial, ui),
+            ClientDomainConfig::Wss(wss) => self.wss_connect(wss, initial, ui),
+            ClientDomainConfig::Udp(udp) => self.udp_connect(udp, initial, ui),
         }
     }
 
@@ -708,30 +810,41 @@ impl Reconnectable {
         // we can connect using those same credentials and avoid running through
         // the SSH authentication flow.
         if let Some(Ok(_)) = tls_client.ssh_parameters() {
-            match self.try_connect(&tls_client, ui, &remote_address, remote_host_name) {
-                Ok(stream) => {
-                    self.stream.replace(stream);
-                    return Ok(());
-                }
-                Err(err) => {
-                    if let Some(ioerr) = err.root_cause().downcast_ref::<std::io::Error>() {
-                        match ioerr.kind() {
-                            std::io::ErrorKind::ConnectionRefused => {
-                                // Server isn't up yet; let's proceed with bootstrap
-                            }
-                            _ => {
-                                // If it is an IO error that implies that we had an issue
-                                // reaching or otherwise talking to the remote host.
-                                // Re-attempting the SSH bootstrap most likely will not
-                                // succeed so we let this bubble up.
-                                return Err(err);
+            if self.client_cert_needs_renewal(&tls_client) {
+                // Client certificates are only issued via the local unix
+                // domain socket of the server, so renewing one means
+                // bootstrapping via SSH again
+                ui.output_str("TLS client certificate is due for renewal\n");
+                self.tls_creds.take();
+            } else {
+                match self.try_connect(&tls_client, ui, &remote_address, remote_host_name) {
+                    Ok(stream) => {
+                        self.stream.replace(stream);
+                        return Ok(());
+                    }
+                    Err(err) => {
+                        if let Some(ioerr) = err.root_cause().downcast_ref::<std::io::Error>() {
+                            match ioerr.kind() {
+                                std::io::ErrorKind::ConnectionRefused => {
+                                    // Server isn't up yet; let's proceed with bootstrap
+                                }
+                                _ => {
+                                    // If it is an IO error that implies that we had an issue
+                                    // reaching or otherwise talking to the remote host.
+                                    // Re-attempting the SSH bootstrap most likely will not
+                                    // succeed so we let this bubble up.
+                                    return Err(err);
+                                }
                             }
                         }
+                        ui.output_str(&format!(
+                            "Failed to reuse creds: {:?}\nWill retry bootstrap via SSH\n",
+                            err
+                        ));
+                        // The server may have rotated its CA, in which case
+                        // the creds we obtained previously are no longer useful
+                        self.tls_creds.take();
                     }
-                    ui.output_str(&format!(
-                        "Failed to reuse creds: {:?}\nWill retry bootstrap via SSH\n",
-                        err
-                    ));
                 }
             }
         }
@@ -861,11 +974,6 @@ impl Reconnectable {
                 key_file.display()
             ))?;
 
-        fn load_cert(name: &Path) -> anyhow::Result<X509> {
-            let cert_bytes = std::fs::read(name)?;
-            log::trace!("loaded {}", name.display());
-            Ok(X509::from_pem(&cert_bytes)?)
-        }
         for name in &tls_client.pem_root_certs {
             if name.is_dir() {
                 for entry in std::fs::read_dir(name)? {
@@ -884,6 +992,20 @@ impl Reconnectable {
             }
         }
 
+        // When pinning, we allow the handshake to proceed even if the
+        // server certificate cannot be verified, and then decide whether
+        // to trust it based on its fingerprint once we're connected.
+        let verify_failed = Arc::new(AtomicBool::new(false));
+        if tls_client.pin_server_fingerprint {
+            let verify_failed = Arc::clone(&verify_failed);
+            connector.set_verify_callback(SslVerifyMode::PEER, move |preverify_ok, _ctx| {
+                if !preverify_ok {
+                    verify_failed.store(true, Ordering::SeqCst);
+                }
+                true
+            });
+        }
+
         let connector = connector.build();
         let connector = connector
             .configure()?
@@ -896,26 +1018,323 @@ impl Reconnectable {
         stream.set_write_timeout(Some(tls_client.write_timeout))?;
         stream.set_read_timeout(Some(tls_client.read_timeout))?;
 
-        let stream = Box::new(Async::new(AsyncSslStream::new(
-            connector
-                .connect(
-                    tls_client
-                        .expected_cn
-                        .as_ref()
-                        .map(String::as_str)
-                        .unwrap_or(remote_host_name),
-                    stream,
+        let stream = connector
+            .connect(
+                tls_client
+                    .expected_cn
+                    .as_ref()
+                    .map(String::as_str)
+                    .unwrap_or(remote_host_name),
+                stream,
+            )
+            .with_context(|| {
+                format!(
+                    "SslConnector for {} with host name {}",
+                    remote_address, remote_host_name,
                 )
-                .with_context(|| {
-                    format!(
-                        "SslConnector for {} with host name {}",
-                        remote_address, remote_host_name,
-                    )
-                })?,
-        ))?);
+            })?;
+
+        if tls_client.pin_server_fingerprint {
+            self.check_server_fingerprint(
+                &stream,
+                verify_failed.load(Ordering::SeqCst),
+                remote_address,
+                ui,
+            )?;
+        }
+
+        let stream = Box::new(Async::new(AsyncSslStream::new(stream))?);
         ui.output_str("TLS Connected!\n");
         Ok(stream)
     }
+
+    /// Decide whether to trust a server certificate that didn't pass
+    /// verification, by comparing its fingerprint against the one that
+    /// we pinned on first use, or by asking the user if we haven't pinned
+    /// one yet.  A certificate that did pass verification is trusted and
+    /// becomes the pinned one, as the server issues itself a new
+    /// certificate each time that it starts or its credentials are rotated.
+    fn check_server_fingerprint(
+        &self,
+        stream: &SslStream<TcpStream>,
+        verify_failed: bool,
+        remote_address: &str,
+        ui: &mut ConnectionUI,
+    ) -> anyhow::Result<()> {
+        let cert = stream
+            .ssl()
+            .peer_certificate()
+            .ok_or_else(|| anyhow!("{} did not present a certificate", remote_address))?;
+        let fingerprint = cert
+            .digest(MessageDigest::sha256())?
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(":");
+
+        let known_path = self.tls_known_server_fingerprint_path()?;
+        let pin = || -> anyhow::Result<()> {
+            std::fs::write(&known_path, format!("{}\n", fingerprint))
+                .with_context(|| format!("writing {}", known_path.display()))?;
+            log::info!(
+                "pinned server fingerprint {} for {}",
+                fingerprint,
+                remote_address
+            );
+            Ok(())
+        };
+
+        if !verify_failed {
+            return match std::fs::read_to_string(&known_path) {
+                Ok(known) if known.trim() == fingerprint => Ok(()),
+                _ => pin(),
+            };
+        }
+
+        match std::fs::read_to_string(&known_path) {
+            Ok(known) => {
+                if known.trim() != fingerprint {
+                    bail!(
+                        "The certificate presented by {} has fingerprint {}, \
+                         which does not match the pinned fingerprint {} in {}. \
+                         If the server certificate was intentionally replaced, \
+                         remove that file and try again.",
+                        remote_address,
+                        fingerprint,
+                        known.trim(),
+                        known_path.display()
+                    );
+                }
+                Ok(())
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                ui.output_str(&format!(
+                    "The certificate presented by {} could not be verified.\n\
+                     Its SHA-256 fingerprint is {}\n",
+                    remote_address, fingerprint
+                ));
+                match ui.input("Trust this certificate and continue connecting? (yes/no) ") {
+                    Ok(answer) if answer == "y" || answer == "yes" => pin(),
+                    _ => bail!("Server certificate for {} was rejected", remote_address),
+                }
+            }
+            Err(err) => Err(err).with_context(|| format!("reading {}", known_path.display())),
+        }
+    }
+
+    /// Returns true if the client certificate that was issued to us by
+    /// the server via `bootstrap_via_ssh` is nearing expiry
+    fn client_cert_needs_renewal(&self, tls_client: &TlsDomainClient) -> bool {
+        if tls_client.pem_cert.is_some() {
+            return false;
+        }
+        let remaining = (|| -> anyhow::Result<Duration> {
+            let cert = X509::from_pem(&std::fs::read(self.tls_creds_cert_path()?)?)?;
+            let remaining = Asn1Time::days_from_now(0)?.diff(cert.not_after())?;
+            Ok(Duration::from_secs(
+                (remaining.days as i64 * 86400 + remaining.secs as i64).max(0) as u64,
+            ))
+        })();
+        match remaining {
+            Ok(remaining) if remaining <= tls_client.client_cert_renewal_threshold => {
+                log::info!(
+                    "TLS client certificate expires in {:?}; requesting a new one",
+                    remaining
+                );
+                true
+            }
+            // If there is no certificate yet, connecting will fail
+            // and we'll bootstrap anyway
+            _ => false,
+        }
+    }
+
+    pub fn wss_connect(
+        &mut self,
+        wss_client: WssDomainClient,
+        _initial: bool,
+        ui: &mut ConnectionUI,
+    ) -> anyhow::Result<()> {
+        let url = url::Url::parse(&wss_client.url)
+            .with_context(|| format!("parsing websocket url {}", wss_client.url))?;
+        let use_tls = match url.scheme() {
+            "wss" => true,
+            "ws" => false,
+            scheme => bail!(
+                "websocket url {} has unsupported scheme {}; expected ws or wss",
+                wss_client.url,
+                scheme
+            ),
+        };
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow!("websocket url {} has no host", wss_client.url))?
+            .to_string();
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| anyhow!("websocket url {} has no port", wss_client.url))?;
+        let remote_address = format!("{}:{}", host, port);
+
+        let stream = match &wss_client.http_proxy {
+            Some(proxy) => {
+                ui.output_str(&format!(
+                    "Connecting to {} via proxy {}\n",
+                    remote_address, proxy
+                ));
+                let stream = TcpStream::connect(proxy)
+                    .with_context(|| format!("connecting to proxy {}", proxy))?;
+                http_connect(&stream, &remote_address)
+                    .with_context(|| format!("CONNECT {} via proxy {}", remote_address, proxy))?;
+                stream
+            }
+            None => {
+                ui.output_str(&format!("Connecting to {}\n", remote_address));
+                TcpStream::connect(&remote_address)
+                    .with_context(|| format!("connecting to {}", remote_address))?
+            }
+        };
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(wss_client.write_timeout))?;
+        stream.set_read_timeout(Some(wss_client.read_timeout))?;
+
+        let transport = if use_tls {
+            let mut connector = SslConnector::builder(SslMethod::tls())?;
+            if let Some(cert_file) = &wss_client.pem_cert {
+                connector
+                    .set_certificate_file(cert_file, SslFiletype::PEM)
+                    .context(format!(
+                        "set_certificate_file to {} for websocket client",
+                        cert_file.display()
+                    ))?;
+            }
+            if let Some(chain_file) = &wss_client.pem_ca {
+                connector
+                    .set_certificate_chain_file(chain_file)
+                    .context(format!(
+                        "set_certificate_chain_file to {} for websocket client",
+                        chain_file.display()
+                    ))?;
+            }
+            if let Some(key_file) = &wss_client.pem_private_key {
+                connector
+                    .set_private_key_file(key_file, SslFiletype::PEM)
+                    .context(format!(
+                        "set_private_key_file to {} for websocket client",
+                        key_file.display()
+                    ))?;
+            }
+            for name in &wss_client.pem_root_certs {
+                if name.is_dir() {
+                    for entry in std::fs::read_dir(name)? {
+                        if let Ok(cert) = load_cert(&entry?.path()) {
+                            connector.cert_store_mut().add_cert(cert).ok();
+                        }
+                    }
+                } else {
+                    connector.cert_store_mut().add_cert(load_cert(name)?)?;
+                }
+            }
+            let connector = connector.build();
+            let connector = connector
+                .configure()?
+                .verify_hostname(!wss_client.accept_invalid_hostnames);
+            WebSocketTransport::Tls(
+                connector
+                    .connect(&host, stream)
+                    .with_context(|| format!("SslConnector for {}", remote_address))?,
+            )
+        } else {
+            WebSocketTransport::Plain(stream)
+        };
+
+        let mut request = url
+            .as_str()
+            .into_client_request()
+            .map_err(|err| anyhow!("invalid websocket url {}: {}", url, err))?;
+        if let Some(path) = &wss_client.auth_token_file {
+            let token = read_wss_auth_token(path)?;
+            request.headers_mut().insert(
+                tungstenite::http::header::AUTHORIZATION,
+                format!("Bearer {}", token).parse()?,
+            );
+        }
+
+        let (ws, _response) = tungstenite::client::client(request, transport)
+            .map_err(|err| anyhow!("websocket handshake with {} failed: {}", url, err))?;
+
+        let stream: Box<dyn AsyncReadAndWrite> =
+            Box::new(Async::new(AsyncWebSocketStream::new(ws)?)?);
+        ui.output_str("Websocket Connected!\n");
+        self.stream.replace(stream);
+        Ok(())
+    }
+
+    pub fn udp_connect(
+        &mut self,
+        udp_client: UdpDomainClient,
+        _initial: bool,
+        ui: &mut ConnectionUI,
+    ) -> anyhow::Result<()> {
+        let key = UdpKey::load(&udp_client.key_file)?;
+        ui.output_str(&format!("Connecting to {}\n", udp_client.remote_address));
+        let stream = async_ossl::udp_connect(
+            &udp_client.remote_address,
+            &key,
+            udp_client.connect_timeout,
+            udp_client.idle_timeout,
+        )?;
+        let stream: Box<dyn AsyncReadAndWrite> = Box::new(Async::new(stream)?);
+        ui.output_str("UDP session established!\n");
+        self.stream.replace(stream);
+        Ok(())
+    }
+}
+
+fn load_cert(name: &Path) -> anyhow::Result<X509> {
+    let cert_bytes = std::fs::read(name)?;
+    log::trace!("loaded {}", name.display());
+    Ok(X509::from_pem(&cert_bytes)?)
+}
+
+/// Ask the HTTP proxy on the other end of `stream` to establish
+/// a tunnel to `remote_address`.
+fn http_connect(stream: &TcpStream, remote_address: &str) -> anyhow::Result<()> {
+    let mut writer = stream;
+    write!(
+        writer,
+        "CONNECT {addr} HTTP/1.1\r\nHost: {addr}\r\n\r\n",
+        addr = remote_address
+    )?;
+    writer.flush()?;
+
+    // Read byte-at-a-time so that we don't consume any of the
+    // data that follows the response headers
+    let mut reader = BufReader::with_capacity(1, stream);
+    let mut status = String::new();
+    reader.read_line(&mut status)?;
+    let code = status.split_whitespace().nth(1).unwrap_or("");
+    if code != "200" {
+        bail!("proxy responded with: {}", status.trim());
+    }
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            bail!("proxy closed the connection");
+        }
+        if line == "\r\n" || line == "\n" {
+            return Ok(());
+        }
+    }
+}
+
+/// Lets the gui know that the connection to the domain was lost
+fn notify_connection_error(domain_id: DomainId, message: String) {
+    promise::spawn::spawn_into_main_thread(async move {
+        if let Some(mux) = Mux::get() {
+            mux.notify(mux::MuxNotification::ConnectionError { domain_id, message });
+        }
+    })
+    .detach();
 }
 
 impl Client {
@@ -943,15 +1362,28 @@ impl Client {
                         if let std::io::ErrorKind::UnexpectedEof = ioerr.kind() {
                             // Don't reconnect for a simple EOF
                             log::error!("server closed connection ({})", e);
+                            notify_connection_error(
+                                local_domain_id,
+                                format!("server closed connection ({})", e),
+                            );
                             break;
                         }
                     }
 
                     if let Some(err) = e.root_cause().downcast_ref::<NotReconnectableError>() {
                         log::error!("{}; won't try to reconnect", err);
+                        notify_connection_error(
+                            local_domain_id,
+                            format!("{}; won't try to reconnect", err),
+                        );
                         break;
                     }
 
+                    notify_connection_error(
+                        local_domain_id,
+                        format!("client disconnected {}; will reconnect", e),
+                    );
+
                     let mut ui = ConnectionUI::new();
                     ui.title("wezterm: Reconnecting...");
 
@@ -1020,6 +1452,11 @@ impl Client {
         }
     }
 
+    /// Returns the identity that this client reports to the server
+    pub fn client_id(&self) -> &ClientId {
+        &self.client_id
+    }
+
     pub async fn verify_version_compat(
         &self,
         ui: &ConnectionUI,
@@ -1042,6 +1479,10 @@ impl Client {
                     client_id: self.client_id.clone(),
                 })
                 .await?;
+                self.set_compression(SetCompression {
+                    compression: Compression::from_config(&configuration()),
+                })
+                .await?;
                 Ok(info)
             }
             Ok(info) => {
@@ -1161,6 +1602,30 @@ impl Client {
         Ok(Self::new(Some(local_domain_id), reconnectable))
     }
 
+    pub fn new_wss(
+        local_domain_id: DomainId,
+        wss_client: &WssDomainClient,
+        ui: &mut ConnectionUI,
+    ) -> anyhow::Result<Self> {
+        let mut reconnectable =
+            Reconnectable::new(ClientDomainConfig::Wss(wss_client.clone()), None);
+        let no_auto_start = true;
+        reconnectable.connect(true, ui, no_auto_start)?;
+        Ok(Self::new(Some(local_domain_id), reconnectable))
+    }
+
+    pub fn new_udp(
+        local_domain_id: DomainId,
+        udp_client: &UdpDomainClient,
+        ui: &mut ConnectionUI,
+    ) -> anyhow::Result<Self> {
+        let mut reconnectable =
+            Reconnectable::new(ClientDomainConfig::Udp(udp_client.clone()), None);
+        let no_auto_start = true;
+        reconnectable.connect(true, ui, no_auto_start)?;
+        Ok(Self::new(Some(local_domain_id), reconnectable))
+    }
+
     pub fn new_ssh(
         local_domain_id: DomainId,
         ssh_dom: &SshDomain,
@@ -1182,15 +1647,43 @@ impl Client {
         rx.recv().await.context("send_pdu recv")?
     }
 
+    /// Asks the server to send the specified kinds of event,
+    /// returning a channel through which they will be delivered
+    pub async fn subscribe_to_events(
+        &self,
+        kinds: Vec<MuxEventKind>,
+    ) -> anyhow::Result<Receiver<MuxEvent>> {
+        let (tx, rx) = unbounded();
+        self.sender
+            .send(ReaderMessage::SetEventSender(tx))
+            .await
+            .map_err(|_| ChannelSendError)
+            .context("subscribe_to_events send")?;
+        self.subscribe_events(SubscribeToEvents { kinds }).await?;
+        Ok(rx)
+    }
+
     rpc!(ping, Ping = (), Pong);
     rpc!(list_panes, ListPanes = (), ListPanesResponse);
+    rpc!(list_pane_entries, ListPaneEntries, ListPaneEntriesResponse);
+    rpc!(subscribe_events, SubscribeToEvents, UnitResponse);
+    rpc!(send_paste_chunk, SendPasteChunk, SendPasteChunkResponse);
+    rpc!(capture_image, CaptureImage, CaptureImageResponse);
+    rpc!(inject_output, InjectOutput, UnitResponse);
     rpc!(spawn_v2, SpawnV2, SpawnResponse);
+    rpc!(spawn_workspace, SpawnWorkspace, SpawnResponse);
+    rpc!(spawn_batch, SpawnBatch, SpawnBatchResponse);
     rpc!(split_pane, SplitPane, SpawnResponse);
     rpc!(
         move_pane_to_new_tab,
         MovePaneToNewTab,
         MovePaneToNewTabResponse
     );
+    rpc!(move_tab_to_domain, MoveTabToDomain, MoveTabToDomainResponse);
+    rpc!(set_client_cursor, SetClientCursor, UnitResponse);
+    rpc!(toggle_pane_input_turn, TogglePaneInputTurn, UnitResponse);
+    rpc!(detach_client, DetachClient, UnitResponse);
+    rpc!(set_client_permission, SetClientPermission, UnitResponse);
     rpc!(write_to_pane, WriteToPane, UnitResponse);
     rpc!(send_paste, SendPaste, UnitResponse);
     rpc!(key_down, SendKeyDown, UnitResponse);
@@ -1198,6 +1691,8 @@ impl Client {
     rpc!(resize, Resize, UnitResponse);
     rpc!(set_zoomed, SetPaneZoomed, UnitResponse);
     rpc!(activate_pane_direction, ActivatePaneDirection, UnitResponse);
+    rpc!(activate_pane, ActivatePane, UnitResponse);
+    rpc!(resize_pane, ResizePane, UnitResponse);
     rpc!(
         get_pane_render_changes,
         GetPaneRenderChanges,
@@ -1206,6 +1701,7 @@ impl Client {
     rpc!(get_lines, GetLines, GetLinesResponse);
     rpc!(get_codec_version, GetCodecVersion, GetCodecVersionResponse);
     rpc!(get_tls_creds, GetTlsCreds = (), GetTlsCredsResponse);
+    rpc!(rotate_tls_creds, RotateTlsCreds = (), UnitResponse);
     rpc!(
         search_scrollback,
         SearchScrollbackRequest,
@@ -1213,7 +1709,9 @@ impl Client {
     );
     rpc!(kill_pane, KillPane, UnitResponse);
     rpc!(set_client_id, SetClientId, UnitResponse);
+    rpc!(set_compression, SetCompression, UnitResponse);
     rpc!(list_clients, GetClientList, GetClientListResponse);
+    rpc!(get_pane_metrics, GetPaneMetrics, GetPaneMetricsResponse);
     rpc!(set_window_workspace, SetWindowWorkspace, UnitResponse);
     rpc!(set_focused_pane_id, SetFocusedPane, UnitResponse);
     rpc!(get_image_cell, GetImageCell, GetImageCellResponse);
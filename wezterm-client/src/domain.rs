@@ -4,7 +4,7 @@ use anyhow::{anyhow, bail};
 use async_trait::async_trait;
 use codec::{ListPanesResponse, SpawnV2, SplitPane};
 use config::keyassignment::SpawnTabDomain;
-use config::{SshDomain, TlsDomainClient, UnixDomain};
+use config::{SshDomain, TlsDomainClient, UdpDomainClient, UnixDomain, WssDomainClient};
 use mux::connui::{ConnectionUI, ConnectionUIParams};
 use mux::domain::{alloc_domain_id, Domain, DomainId, DomainState, SplitSource};
 use mux::pane::{Pane, PaneId};
@@ -173,6 +173,8 @@ pub enum ClientDomainConfig {
     Unix(UnixDomain),
     Tls(TlsDomainClient),
     Ssh(SshDomain),
+    Wss(WssDomainClient),
+    Udp(UdpDomainClient),
 }
 
 impl ClientDomainConfig {
@@ -181,6 +183,8 @@ impl ClientDomainConfig {
             ClientDomainConfig::Unix(unix) => &unix.name,
             ClientDomainConfig::Tls(tls) => &tls.name,
             ClientDomainConfig::Ssh(ssh) => &ssh.name,
+            ClientDomainConfig::Wss(wss) => &wss.name,
+            ClientDomainConfig::Udp(udp) => &udp.name,
         }
     }
 
@@ -189,6 +193,8 @@ impl ClientDomainConfig {
             ClientDomainConfig::Unix(unix) => unix.local_echo_threshold_ms,
             ClientDomainConfig::Tls(tls) => tls.local_echo_threshold_ms,
             ClientDomainConfig::Ssh(ssh) => ssh.local_echo_threshold_ms,
+            ClientDomainConfig::Wss(wss) => wss.local_echo_threshold_ms,
+            ClientDomainConfig::Udp(udp) => udp.local_echo_threshold_ms,
         }
     }
 
@@ -197,6 +203,8 @@ impl ClientDomainConfig {
             ClientDomainConfig::Unix(unix) => unix.overlay_lag_indicator,
             ClientDomainConfig::Tls(tls) => tls.overlay_lag_indicator,
             ClientDomainConfig::Ssh(ssh) => ssh.overlay_lag_indicator,
+            ClientDomainConfig::Wss(wss) => wss.overlay_lag_indicator,
+            ClientDomainConfig::Udp(udp) => udp.overlay_lag_indicator,
         }
     }
 
@@ -211,6 +219,8 @@ impl ClientDomainConfig {
                     format!("SSH mux {}", ssh.remote_address)
                 }
             }
+            ClientDomainConfig::Wss(wss) => format!("Websocket mux {}", wss.url),
+            ClientDomainConfig::Udp(udp) => format!("UDP mux {}", udp.remote_address),
         }
     }
 
@@ -219,6 +229,8 @@ impl ClientDomainConfig {
             ClientDomainConfig::Unix(unix) => unix.connect_automatically,
             ClientDomainConfig::Tls(tls) => tls.connect_automatically,
             ClientDomainConfig::Ssh(ssh) => ssh.connect_automatically,
+            ClientDomainConfig::Wss(wss) => wss.connect_automatically,
+            ClientDomainConfig::Udp(udp) => udp.connect_automatically,
         }
     }
 }
@@ -744,6 +756,8 @@ impl Domain for ClientDomain {
                     }
                     ClientDomainConfig::Tls(tls) => Client::new_tls(domain_id, tls, &mut cloned_ui),
                     ClientDomainConfig::Ssh(ssh) => Client::new_ssh(domain_id, ssh, &mut cloned_ui),
+                    ClientDomainConfig::Wss(wss) => Client::new_wss(domain_id, wss, &mut cloned_ui),
+                    ClientDomainConfig::Udp(udp) => Client::new_udp(domain_id, udp, &mut cloned_ui),
                 })
                 .await?;
 
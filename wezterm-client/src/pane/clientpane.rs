@@ -5,6 +5,7 @@ use anyhow::bail;
 use async_trait::async_trait;
 use codec::*;
 use config::configuration;
+use mux::collab::{ClientCursor, PaneCollaborators};
 use mux::domain::DomainId;
 use mux::pane::{
     alloc_pane_id, CloseReason, ForEachPaneLogicalLine, LogicalLine, Pane, PaneId, Pattern,
@@ -42,6 +43,9 @@ pub struct ClientPane {
     mouse_grabbed: RefCell<bool>,
     ignore_next_kill: RefCell<bool>,
     user_vars: RefCell<HashMap<String, String>>,
+    badge: RefCell<Option<String>>,
+    collaborators: RefCell<Option<PaneCollaborators>>,
+    collaborator_cursor: RefCell<Option<(usize, StableRowIndex)>>,
 }
 
 impl ClientPane {
@@ -103,6 +107,9 @@ impl ClientPane {
             mouse_grabbed: RefCell::new(false),
             ignore_next_kill: RefCell::new(false),
             user_vars: RefCell::new(HashMap::new()),
+            badge: RefCell::new(None),
+            collaborators: RefCell::new(None),
+            collaborator_cursor: RefCell::new(None),
         }
     }
 
@@ -140,6 +147,22 @@ impl ClientPane {
                     log::error!("ClientPane: Ignoring SetClipboard request {:?}", clipboard);
                 }
             },
+            Pdu::QueryClipboard(QueryClipboard { selection, .. }) => {
+                match self.clipboard.borrow().as_ref() {
+                    Some(clip) => {
+                        log::debug!(
+                            "Pdu::QueryClipboard pane={} remote={} {:?}",
+                            self.local_pane_id,
+                            self.remote_pane_id,
+                            selection,
+                        );
+                        clip.query_contents(selection)?;
+                    }
+                    None => {
+                        log::error!("ClientPane: Ignoring QueryClipboard request");
+                    }
+                }
+            }
             Pdu::SetPalette(SetPalette { palette, .. }) => {
                 *self.palette.borrow_mut() = palette;
                 let mux = Mux::get().unwrap();
@@ -156,6 +179,9 @@ impl ClientPane {
                             .borrow_mut()
                             .insert(name.clone(), value.clone());
                     }
+                    Alert::BadgeChanged(badge) => {
+                        *self.badge.borrow_mut() = badge.clone();
+                    }
                     _ => {}
                 }
                 mux.notify(MuxNotification::Alert {
@@ -163,6 +189,11 @@ impl ClientPane {
                     alert,
                 });
             }
+            Pdu::PaneCollaboratorsChanged(PaneCollaboratorsChanged { collaborators, .. }) => {
+                *self.collaborators.borrow_mut() = Some(collaborators);
+                let mux = Mux::get().unwrap();
+                mux.notify(MuxNotification::PaneOutput(self.local_pane_id));
+            }
             Pdu::PaneRemoved(PaneRemoved { pane_id }) => {
                 log::trace!("remote pane {} has been removed", pane_id);
                 self.renderable.borrow().inner.borrow_mut().dead = true;
@@ -265,6 +296,22 @@ impl Pane for ClientPane {
         inner.title.clone()
     }
 
+    fn perform_actions(&self, actions: Vec<termwiz::escape::Action>) {
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        let data = actions.iter().map(|action| action.to_string()).collect();
+        promise::spawn::spawn(async move {
+            client
+                .client
+                .inject_output(InjectOutput {
+                    pane_id: remote_pane_id,
+                    data,
+                })
+                .await
+        })
+        .detach();
+    }
+
     fn send_paste(&self, text: &str) -> anyhow::Result<()> {
         let client = Arc::clone(&self.client);
         let remote_pane_id = self.remote_pane_id;
@@ -557,6 +604,61 @@ impl Pane for ClientPane {
     fn copy_user_vars(&self) -> HashMap<String, String> {
         self.user_vars.borrow().clone()
     }
+
+    fn get_badge(&self) -> Option<String> {
+        self.badge.borrow().clone()
+    }
+
+    fn get_collaborators(&self) -> Option<PaneCollaborators> {
+        self.collaborators.borrow().clone()
+    }
+
+    fn set_collaborator_cursor(&self, cursor: Option<(usize, StableRowIndex)>) {
+        // Don't chatter with the server while nobody else is looking
+        let shared = self
+            .collaborators
+            .borrow()
+            .as_ref()
+            .map_or(false, |c| !c.clients.is_empty());
+        let cursor = if shared { cursor } else { None };
+        if *self.collaborator_cursor.borrow() == cursor {
+            return;
+        }
+        *self.collaborator_cursor.borrow_mut() = cursor;
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        promise::spawn::spawn(async move {
+            client
+                .client
+                .set_client_cursor(SetClientCursor {
+                    cursor: cursor.map(|(x, y)| ClientCursor {
+                        pane_id: remote_pane_id,
+                        x,
+                        y,
+                    }),
+                })
+                .await
+        })
+        .detach();
+    }
+
+    fn toggle_input_turn(&self) -> anyhow::Result<()> {
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        promise::spawn::spawn(async move {
+            if let Err(err) = client
+                .client
+                .toggle_pane_input_turn(TogglePaneInputTurn {
+                    pane_id: remote_pane_id,
+                })
+                .await
+            {
+                log::error!("Unable to toggle the input turn: {:#}", err);
+            }
+        })
+        .detach();
+        Ok(())
+    }
 }
 
 struct PaneWriter {
@@ -2,7 +2,7 @@ use crate::domain::ClientInner;
 use crate::pane::clientpane::ClientPane;
 use anyhow::anyhow;
 use codec::*;
-use config::{configuration, ConfigHandle};
+use config::{configuration, ConfigHandle, LocalEchoPrediction};
 use lru::LruCache;
 use mux::pane::PaneId;
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
@@ -16,7 +16,7 @@ use std::ops::Range;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use termwiz::cell::{Cell, CellAttributes, Underline};
+use termwiz::cell::{Cell, CellAttributes};
 use termwiz::color::AnsiColor;
 use termwiz::image::{ImageCell, ImageData};
 use termwiz::surface::{SequenceNo, SEQ_ZERO};
@@ -77,6 +77,17 @@ pub struct RenderableInner {
     last_input_rtt: u64,
 
     pub input_serial: InputSerial,
+    /// The rows that hold predicted text, and when the
+    /// most recent prediction was made on each of them
+    predicted_rows: HashMap<StableRowIndex, Instant>,
+}
+
+/// The attributes used for predicted text; the underline
+/// distinguishes it from text that the remote end has confirmed
+fn prediction_attributes() -> CellAttributes {
+    CellAttributes::default()
+        .set_underline(configuration().local_echo_prediction_underline)
+        .clone()
 }
 
 pub struct RenderableState {
@@ -113,6 +124,7 @@ impl RenderableInner {
             last_late_dirty: now,
             last_input_rtt: 0,
             input_serial: InputSerial::empty(),
+            predicted_rows: HashMap::new(),
             seqno: SEQ_ZERO,
         }
     }
@@ -131,12 +143,37 @@ impl RenderableInner {
     }
 
     /// Predictive echo can be noisy when the link is working well,
-    /// so we only employ it when it looks like the latency is high.
+    /// so by default we only employ it when it looks like the latency
+    /// is high.
     fn should_predict(&self) -> bool {
-        self.client
-            .local_echo_threshold_ms
-            .map(|thresh| self.last_input_rtt >= thresh)
-            .unwrap_or(false)
+        match configuration().local_echo_prediction {
+            LocalEchoPrediction::Never => false,
+            LocalEchoPrediction::Always => true,
+            LocalEchoPrediction::Adaptive => self
+                .client
+                .local_echo_threshold_ms
+                .map(|thresh| self.last_input_rtt >= thresh)
+                .unwrap_or(false),
+        }
+    }
+
+    /// Discard predictions that the remote end has failed to confirm
+    /// in a timely fashion, by refetching the lines that hold them
+    fn expire_predictions(&mut self) {
+        if self.predicted_rows.is_empty() {
+            return;
+        }
+        let timeout = Duration::from_millis(configuration().local_echo_prediction_timeout_ms);
+        let expired: Vec<StableRowIndex> = self
+            .predicted_rows
+            .iter()
+            .filter(|(_, when)| when.elapsed() >= timeout)
+            .map(|(row, _)| *row)
+            .collect();
+        for row in expired {
+            self.predicted_rows.remove(&row);
+            self.make_stale(row);
+        }
     }
 
     /// Compute a "prediction" and apply it to the line data that we
@@ -186,12 +223,7 @@ impl RenderableInner {
                 }
             }
             KeyCode::Char(c) => {
-                let cell = Cell::new(
-                    c,
-                    CellAttributes::default()
-                        .set_underline(Underline::Double)
-                        .clone(),
-                );
+                let cell = Cell::new(c, prediction_attributes());
 
                 let width = cell.width();
                 line.set_cell(self.cursor_position.x, cell, SEQ_ZERO);
@@ -231,11 +263,13 @@ impl RenderableInner {
             Some(LineEntry::Stale(mut line)) | Some(LineEntry::Line(mut line)) => {
                 self.apply_prediction(c, &mut line);
                 self.lines.put(row, LineEntry::Line(line));
+                self.predicted_rows.insert(row, Instant::now());
             }
             Some(LineEntry::LineAndFetching(mut line, instant)) => {
                 self.apply_prediction(c, &mut line);
                 self.lines
                     .put(row, LineEntry::LineAndFetching(line, instant));
+                self.predicted_rows.insert(row, Instant::now());
             }
             Some(entry) => {
                 self.lines.put(row, entry);
@@ -245,9 +279,7 @@ impl RenderableInner {
     }
 
     fn apply_paste_prediction(&mut self, row: usize, text: &str, line: &mut Line) {
-        let attrs = CellAttributes::default()
-            .set_underline(Underline::Double)
-            .clone();
+        let attrs = prediction_attributes();
 
         let text_line = Line::from_text(text, &attrs, SEQ_ZERO, None);
 
@@ -279,11 +311,13 @@ impl RenderableInner {
                 Some(LineEntry::Stale(mut line)) | Some(LineEntry::Line(mut line)) => {
                     self.apply_paste_prediction(idx, paste_line, &mut line);
                     self.lines.put(row, LineEntry::Line(line));
+                    self.predicted_rows.insert(row, Instant::now());
                 }
                 Some(LineEntry::LineAndFetching(mut line, instant)) => {
                     self.apply_paste_prediction(idx, paste_line, &mut line);
                     self.lines
                         .put(row, LineEntry::LineAndFetching(line, instant));
+                    self.predicted_rows.insert(row, Instant::now());
                 }
                 Some(entry) => {
                     self.lines.put(row, entry);
@@ -346,6 +380,22 @@ impl RenderableInner {
         {
             self.cursor_position = delta.cursor_position;
         }
+
+        // Once the remote end has responded to our most recent input,
+        // its content for the rows that we predicted is authoritative.
+        // Refetch any that it didn't report as dirty so that we discard
+        // predictions that turned out to be wrong, such as keys that
+        // were interpreted as commands rather than echoed.
+        if delta
+            .input_serial
+            .map(|serial| serial >= self.input_serial)
+            .unwrap_or(false)
+        {
+            for (row, _) in self.predicted_rows.drain() {
+                dirty.add(row);
+            }
+        }
+
         self.dimensions = delta.dimensions;
         self.title = delta.title;
         self.working_dir = delta.working_dir.map(Into::into);
@@ -810,6 +860,7 @@ impl RenderableState {
                 inner.dead = true;
             }
         }
+        inner.expire_predictions();
 
         let mut result = RangeSet::new();
         for r in lines {
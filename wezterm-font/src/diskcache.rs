@@ -0,0 +1,334 @@
+//! Persists rasterized glyphs across restarts, so that showing text
+//! that uses a lot of distinct glyphs (eg: CJK text) immediately after
+//! startup doesn't have to wait for each of them to be rasterized again.
+//!
+//! There is one file per font face, size and dpi, whose name is a hash
+//! of everything that affects rasterization.  Glyphs are appended to
+//! the file as they are rasterized, and the whole file is read the first
+//! time that the face is used.  Files that were written by a different
+//! version of the cache format are discarded, and the oldest files are
+//! removed when the cache grows beyond `glyph_disk_cache_max_size_mb`.
+use crate::locator::FontDataSource;
+use crate::parser::ParsedFont;
+use crate::rasterizer::RasterizedGlyph;
+use crate::units::PixelLength;
+use config::ConfigHandle;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::{File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+use std::time::UNIX_EPOCH;
+
+/// Bump this whenever the format of the files changes
+const CACHE_VERSION: u32 = 1;
+const MAGIC: &[u8; 8] = b"WZGLYPH\0";
+const HEADER_LEN: usize = 12;
+/// glyph_pos, width, height, bearing_x, bearing_y, has_color, data length
+const RECORD_HEADER_LEN: usize = 4 + 4 + 4 + 8 + 8 + 1 + 4;
+
+static PRUNE: Once = Once::new();
+
+fn cache_dir() -> PathBuf {
+    config::CACHE_DIR.join("glyphs")
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    // FNV-1a
+    data.iter().fold(0x811c9dc5u32, |hash, &b| {
+        (hash ^ b as u32).wrapping_mul(0x01000193)
+    })
+}
+
+fn encode_glyph(glyph_pos: u32, glyph: &RasterizedGlyph) -> Vec<u8> {
+    let mut record = Vec::with_capacity(RECORD_HEADER_LEN + glyph.data.len() + 4);
+    record.extend_from_slice(&glyph_pos.to_le_bytes());
+    record.extend_from_slice(&(glyph.width as u32).to_le_bytes());
+    record.extend_from_slice(&(glyph.height as u32).to_le_bytes());
+    record.extend_from_slice(&glyph.bearing_x.get().to_le_bytes());
+    record.extend_from_slice(&glyph.bearing_y.get().to_le_bytes());
+    record.push(glyph.has_color as u8);
+    record.extend_from_slice(&(glyph.data.len() as u32).to_le_bytes());
+    record.extend_from_slice(&glyph.data);
+    let sum = checksum(&record);
+    record.extend_from_slice(&sum.to_le_bytes());
+    record
+}
+
+/// Decodes the record at the start of `data`, returning the glyph and
+/// the length of the record, or None if it is truncated or corrupt
+fn decode_glyph(data: &[u8]) -> Option<(u32, RasterizedGlyph, usize)> {
+    let u32_at = |offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(
+            data.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    let f64_at = |offset: usize| -> Option<f64> {
+        Some(f64::from_le_bytes(
+            data.get(offset..offset + 8)?.try_into().ok()?,
+        ))
+    };
+
+    let glyph_pos = u32_at(0)?;
+    let width = u32_at(4)? as usize;
+    let height = u32_at(8)? as usize;
+    let bearing_x = f64_at(12)?;
+    let bearing_y = f64_at(20)?;
+    let has_color = *data.get(28)? != 0;
+    let data_len = u32_at(29)? as usize;
+    if data_len != width * height * 4 {
+        return None;
+    }
+    let end = RECORD_HEADER_LEN + data_len;
+    if u32_at(end)? != checksum(data.get(..end)?) {
+        return None;
+    }
+    Some((
+        glyph_pos,
+        RasterizedGlyph {
+            data: data[RECORD_HEADER_LEN..end].to_vec(),
+            height,
+            width,
+            bearing_x: PixelLength::new(bearing_x),
+            bearing_y: PixelLength::new(bearing_y),
+            has_color,
+        },
+        end + 4,
+    ))
+}
+
+fn header() -> Vec<u8> {
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+    header
+}
+
+/// Returns a hash of everything that affects how the glyphs of the
+/// font are rasterized, or None if the font should not be cached
+fn face_key(font: &ParsedFont, font_size: f64, dpi: u32, config: &ConfigHandle) -> Option<u64> {
+    let stamp = match &font.handle.source {
+        FontDataSource::OnDisk(path) => {
+            let meta = std::fs::metadata(path).ok()?;
+            let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+            format!("{} {}", meta.len(), modified.as_nanos())
+        }
+        FontDataSource::BuiltIn { .. } => String::new(),
+        // Fonts in memory have no stable identity
+        FontDataSource::Memory { .. } => return None,
+    };
+    let mut hasher = DefaultHasher::new();
+    (
+        CACHE_VERSION,
+        config::wezterm_version(),
+        format!("{:?}", font),
+        stamp,
+        format!(
+            "{:?} {:?} {:?} {:?} {:?}",
+            config.font_rasterizer,
+            config.freetype_load_target,
+            config.freetype_render_target,
+            config.freetype_load_flags,
+            config.freetype_interpreter_version
+        ),
+        font_size.to_bits(),
+        dpi,
+    )
+        .hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+/// Removes the least recently written files until the total size of
+/// the cache is within the limit
+fn prune(dir: &Path, limit: u64) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    let mut files: Vec<_> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let meta = entry.metadata().ok()?;
+            Some((meta.modified().ok()?, meta.len(), entry.path()))
+        })
+        .collect();
+    files.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let mut total = 0;
+    for (_, len, path) in files {
+        total += len;
+        if total > limit {
+            if let Err(err) = std::fs::remove_file(&path) {
+                log::warn!(
+                    "glyph cache: unable to remove {}: {:#}",
+                    path.display(),
+                    err
+                );
+            }
+        }
+    }
+}
+
+/// The persisted glyphs of one font face at a particular size and dpi
+pub struct GlyphDiskCache {
+    path: PathBuf,
+    glyphs: HashMap<u32, RasterizedGlyph>,
+    file: Option<File>,
+    len: u64,
+    limit: u64,
+}
+
+impl GlyphDiskCache {
+    /// Opens the cache for the font, reading any glyphs that were
+    /// persisted by a previous run.  Returns None if the cache is
+    /// disabled or if the font cannot be cached.
+    pub fn open(
+        font: &ParsedFont,
+        font_size: f64,
+        dpi: u32,
+        config: &ConfigHandle,
+    ) -> Option<Self> {
+        if !config.enable_glyph_disk_cache {
+            return None;
+        }
+        let key = face_key(font, font_size, dpi, config)?;
+        let dir = cache_dir();
+        let limit = config.glyph_disk_cache_max_size_mb as u64 * 1024 * 1024;
+        PRUNE.call_once(|| prune(&dir, limit));
+
+        let path = dir.join(format!("{:016x}.glyphs", key));
+        let mut cache = Self {
+            path,
+            glyphs: HashMap::new(),
+            file: None,
+            len: 0,
+            limit,
+        };
+        if let Err(err) = cache.load() {
+            log::warn!(
+                "glyph cache: unable to use {}: {:#}",
+                cache.path.display(),
+                err
+            );
+            return None;
+        }
+        Some(cache)
+    }
+
+    fn load(&mut self) -> anyhow::Result<()> {
+        let data = match std::fs::read(&self.path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(err) => return Err(err.into()),
+        };
+
+        if data.is_empty() {
+            // The header is written along with the first glyph
+            return Ok(());
+        }
+
+        let mut offset = 0;
+        if data.len() >= HEADER_LEN && data[..HEADER_LEN] == header()[..] {
+            offset = HEADER_LEN;
+            while let Some((glyph_pos, glyph, len)) = decode_glyph(&data[offset..]) {
+                self.glyphs.insert(glyph_pos, glyph);
+                offset += len;
+            }
+        }
+
+        if offset != data.len() {
+            // Written by a different version, or truncated by a crash;
+            // rewrite it from what could be read
+            let mut content = header();
+            for (glyph_pos, glyph) in &self.glyphs {
+                content.extend_from_slice(&encode_glyph(*glyph_pos, glyph));
+            }
+            std::fs::create_dir_all(self.path.parent().unwrap())?;
+            std::fs::write(&self.path, &content)?;
+            offset = content.len();
+        }
+
+        self.len = offset as u64;
+        log::trace!(
+            "glyph cache: read {} glyphs from {}",
+            self.glyphs.len(),
+            self.path.display()
+        );
+        Ok(())
+    }
+
+    pub fn get(&self, glyph_pos: u32) -> Option<RasterizedGlyph> {
+        self.glyphs.get(&glyph_pos).cloned()
+    }
+
+    /// Appends a newly rasterized glyph to the cache
+    pub fn insert(&mut self, glyph_pos: u32, glyph: &RasterizedGlyph) {
+        let record = encode_glyph(glyph_pos, glyph);
+        if self.len == u64::MAX || self.len + record.len() as u64 > self.limit {
+            return;
+        }
+        if let Err(err) = self.append(&record) {
+            log::warn!(
+                "glyph cache: unable to write to {}: {:#}",
+                self.path.display(),
+                err
+            );
+            // Don't try again
+            self.len = u64::MAX;
+            return;
+        }
+        self.len += record.len() as u64;
+        self.glyphs.insert(glyph_pos, glyph.clone());
+    }
+
+    fn append(&mut self, record: &[u8]) -> anyhow::Result<()> {
+        if self.file.is_none() {
+            std::fs::create_dir_all(self.path.parent().unwrap())?;
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            if self.len == 0 {
+                file.write_all(&header())?;
+                self.len = HEADER_LEN as u64;
+            }
+            self.file.replace(file);
+        }
+        // A single write, so that records appended by other
+        // instances are not interleaved with this one
+        self.file.as_mut().unwrap().write_all(record)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let glyph = RasterizedGlyph {
+            data: (0..2 * 3 * 4).collect(),
+            height: 3,
+            width: 2,
+            bearing_x: PixelLength::new(1.5),
+            bearing_y: PixelLength::new(-2.0),
+            has_color: true,
+        };
+        let mut record = encode_glyph(42, &glyph);
+        let (glyph_pos, decoded, len) = decode_glyph(&record).unwrap();
+        assert_eq!(glyph_pos, 42);
+        assert_eq!(len, record.len());
+        assert_eq!(decoded.data, glyph.data);
+        assert_eq!((decoded.width, decoded.height), (2, 3));
+        assert_eq!(decoded.bearing_x, glyph.bearing_x);
+        assert!(decoded.has_color);
+
+        // Truncated or corrupt records are rejected
+        assert!(decode_glyph(&record[..record.len() - 1]).is_none());
+        record[RECORD_HEADER_LEN] ^= 1;
+        assert!(decode_glyph(&record).is_none());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::db::FontDatabase;
+use crate::diskcache::GlyphDiskCache;
 use crate::locator::{new_locator, FontLocator};
 use crate::parser::ParsedFont;
 use crate::rasterizer::{new_rasterizer, FontRasterizer};
@@ -22,6 +23,7 @@ use wezterm_bidi::Direction;
 use wezterm_term::CellAttributes;
 use wezterm_toast_notification::ToastNotification;
 
+mod diskcache;
 mod hbwrap;
 
 pub mod db;
@@ -54,6 +56,7 @@ lazy_static::lazy_static! {
 
 pub struct LoadedFont {
     rasterizers: RefCell<HashMap<FallbackIdx, Box<dyn FontRasterizer>>>,
+    disk_caches: RefCell<HashMap<FallbackIdx, Option<GlyphDiskCache>>>,
     handles: RefCell<Vec<ParsedFont>>,
     shaper: RefCell<Box<dyn FontShaper>>,
     metrics: FontMetrics,
@@ -61,6 +64,11 @@ pub struct LoadedFont {
     dpi: u32,
     font_config: Weak<FontConfigInner>,
     pending_fallback: Arc<Mutex<Vec<ParsedFont>>>,
+    /// The configured fallback fonts that have not been resolved yet;
+    /// they are resolved along with the first glyph that is missing
+    /// from the primary fonts, rather than when the font is loaded
+    deferred_fallback: Arc<Mutex<Vec<FontAttributes>>>,
+    pixel_size: u16,
     text_style: TextStyle,
     id: LoadedFontId,
     /// Glyphs for which no font was found and for which we should
@@ -232,6 +240,8 @@ impl LoadedFont {
                 font_config.schedule_fallback_resolve(
                     no_glyphs,
                     &self.pending_fallback,
+                    std::mem::take(&mut *self.deferred_fallback.lock().unwrap()),
+                    self.pixel_size,
                     completion,
                 );
                 async_resolve = true;
@@ -265,6 +275,32 @@ impl LoadedFont {
         &self,
         glyph_pos: u32,
         fallback: FallbackIdx,
+    ) -> anyhow::Result<RasterizedGlyph> {
+        let mut disk_caches = self.disk_caches.borrow_mut();
+        let disk_cache = disk_caches.entry(fallback).or_insert_with(|| {
+            let config = self.font_config.upgrade()?.config.borrow().clone();
+            GlyphDiskCache::open(
+                self.handles.borrow().get(fallback)?,
+                self.font_size,
+                self.dpi,
+                &config,
+            )
+        });
+        if let Some(glyph) = disk_cache.as_ref().and_then(|cache| cache.get(glyph_pos)) {
+            return Ok(glyph);
+        }
+
+        let glyph = self.rasterize_glyph_uncached(glyph_pos, fallback)?;
+        if let Some(cache) = disk_cache {
+            cache.insert(glyph_pos, &glyph);
+        }
+        Ok(glyph)
+    }
+
+    fn rasterize_glyph_uncached(
+        &self,
+        glyph_pos: u32,
+        fallback: FallbackIdx,
     ) -> anyhow::Result<RasterizedGlyph> {
         let mut rasterizers = self.rasterizers.borrow_mut();
         if let Some(raster) = rasterizers.get(&fallback) {
@@ -291,6 +327,8 @@ impl LoadedFont {
 struct FallbackResolveInfo {
     no_glyphs: Vec<char>,
     pending: Arc<Mutex<Vec<ParsedFont>>>,
+    deferred: Vec<FontAttributes>,
+    pixel_size: u16,
     completion: Box<dyn FnOnce() + Send>,
     font_dirs: Arc<FontDatabase>,
     built_in: Arc<FontDatabase>,
@@ -299,7 +337,77 @@ struct FallbackResolveInfo {
 }
 
 impl FallbackResolveInfo {
-    fn process(self) {
+    /// Resolves the configured fallback fonts whose resolution was
+    /// deferred when the font was loaded, in the same way that
+    /// `resolve_font_helper_impl` would have done
+    fn resolve_deferred(&self) -> Vec<ParsedFont> {
+        let mut candidates = vec![];
+        for attr in &self.deferred {
+            candidates.append(&mut self.font_dirs.candidates(attr));
+        }
+
+        let mut loaded_ignored = HashSet::new();
+        let located =
+            match self
+                .locator
+                .load_fonts(&self.deferred, &mut loaded_ignored, self.pixel_size)
+            {
+                Ok(located) => located,
+                Err(err) => {
+                    log::error!("Error: {:#} while resolving fallback fonts", err);
+                    vec![]
+                }
+            };
+        for font in &located {
+            candidates.push(font);
+        }
+
+        for attr in &self.deferred {
+            candidates.append(&mut self.built_in.candidates(attr));
+        }
+
+        let mut handles = vec![];
+        for attr in &self.deferred {
+            let named_candidates: Vec<&ParsedFont> = candidates
+                .iter()
+                .filter_map(|&p| if p.matches_name(attr) { Some(p) } else { None })
+                .collect();
+            if let Some(idx) =
+                ParsedFont::best_matching_index(attr, &named_candidates, self.pixel_size)
+            {
+                handles.push(named_candidates[idx].clone().synthesize(attr));
+            }
+        }
+        handles
+    }
+
+    fn process(mut self) {
+        // The configured fallback fonts take precedence over the fonts
+        // that are found via the system, so they are added first and
+        // only the glyphs that they don't cover are searched for
+        let deferred_handles = if self.deferred.is_empty() {
+            vec![]
+        } else {
+            let handles = self.resolve_deferred();
+            let mut remaining = RangeSet::new();
+            for c in &self.no_glyphs {
+                remaining.add(*c as u32);
+            }
+            for handle in &handles {
+                if let Ok(cov) = handle.coverage_intersection(&remaining) {
+                    remaining = remaining.difference(&cov);
+                }
+            }
+            self.no_glyphs.retain(|c| remaining.contains(*c as u32));
+            handles
+        };
+        if self.no_glyphs.is_empty() {
+            let mut pending = self.pending.lock().unwrap();
+            pending.extend(deferred_handles);
+            (self.completion)();
+            return;
+        }
+
         let fallback_str = self.no_glyphs.iter().collect::<String>();
         let mut extra_handles = vec![];
 
@@ -381,8 +489,9 @@ impl FallbackResolveInfo {
             Err(_) => false,
         });
 
-        if !extra_handles.is_empty() {
+        if !extra_handles.is_empty() || !deferred_handles.is_empty() {
             let mut pending = self.pending.lock().unwrap();
+            pending.extend(deferred_handles);
             pending.append(&mut extra_handles);
             (self.completion)();
         }
@@ -505,6 +614,8 @@ impl FontConfigInner {
         &self,
         no_glyphs: Vec<char>,
         pending: &Arc<Mutex<Vec<ParsedFont>>>,
+        deferred: Vec<FontAttributes>,
+        pixel_size: u16,
         completion: F,
     ) {
         if no_glyphs.is_empty() {
@@ -515,6 +626,8 @@ impl FontConfigInner {
             completion: Box::new(completion),
             no_glyphs,
             pending: Arc::clone(pending),
+            deferred,
+            pixel_size,
             font_dirs: Arc::clone(&*self.font_dirs.borrow()),
             built_in: Arc::clone(&*self.built_in.borrow()),
             locator: Arc::clone(&self.locator),
@@ -591,7 +704,8 @@ impl FontConfigInner {
         let pixel_size = (font_size * dpi as f64 / 72.0) as u16;
 
         let attributes = text_style.font_with_fallback();
-        let (handles, _loaded) = self.resolve_font_helper_impl(&attributes, pixel_size)?;
+        let (handles, _loaded, _deferred) =
+            self.resolve_font_helper_impl(&attributes, pixel_size, false)?;
 
         let shaper = new_shaper(&*config, &handles)?;
 
@@ -604,6 +718,7 @@ impl FontConfigInner {
 
         let loaded = Rc::new(LoadedFont {
             rasterizers: RefCell::new(HashMap::new()),
+            disk_caches: RefCell::new(HashMap::new()),
             handles: RefCell::new(handles),
             shaper: RefCell::new(shaper),
             metrics,
@@ -611,6 +726,8 @@ impl FontConfigInner {
             dpi,
             font_config: Rc::downgrade(myself),
             pending_fallback: Arc::new(Mutex::new(vec![])),
+            deferred_fallback: Arc::new(Mutex::new(vec![])),
+            pixel_size,
             text_style: text_style.clone(),
             id: alloc_font_id(),
             tried_glyphs: RefCell::new(HashSet::new()),
@@ -667,11 +784,20 @@ impl FontConfigInner {
         Ok(loaded)
     }
 
+    /// Resolves the attributes to fonts.  If `defer_fallback` is true,
+    /// and any of the preferred fonts could be loaded, the fallback
+    /// fonts are not resolved but are returned instead, so that they
+    /// can be resolved when they are first needed.
     fn resolve_font_helper_impl(
         &self,
         attributes: &[FontAttributes],
         pixel_size: u16,
-    ) -> anyhow::Result<(Vec<ParsedFont>, HashSet<FontAttributes>)> {
+        defer_fallback: bool,
+    ) -> anyhow::Result<(
+        Vec<ParsedFont>,
+        HashSet<FontAttributes>,
+        Vec<FontAttributes>,
+    )> {
         let preferred_attributes = attributes
             .iter()
             .filter(|a| !a.is_fallback)
@@ -686,6 +812,10 @@ impl FontConfigInner {
         let mut handles = vec![];
 
         for &attrs in &[&preferred_attributes, &fallback_attributes] {
+            if defer_fallback && attrs.iter().all(|a| a.is_fallback) && !handles.is_empty() {
+                return Ok((handles, loaded, fallback_attributes.clone()));
+            }
+
             let mut candidates = vec![];
 
             let font_dirs = self.font_dirs.borrow();
@@ -752,7 +882,7 @@ impl FontConfigInner {
             }
         }
 
-        Ok((handles, loaded))
+        Ok((handles, loaded, vec![]))
     }
 
     fn resolve_font_helper(
@@ -760,10 +890,11 @@ impl FontConfigInner {
         style: &TextStyle,
         config: &ConfigHandle,
         pixel_size: u16,
-    ) -> anyhow::Result<(Box<dyn FontShaper>, Vec<ParsedFont>)> {
+    ) -> anyhow::Result<(Box<dyn FontShaper>, Vec<ParsedFont>, Vec<FontAttributes>)> {
         let attributes = style.font_with_fallback();
 
-        let (handles, loaded) = self.resolve_font_helper_impl(&attributes, pixel_size)?;
+        let (handles, loaded, deferred) =
+            self.resolve_font_helper_impl(&attributes, pixel_size, true)?;
 
         for attr in &attributes {
             if !attr.is_synthetic && !attr.is_fallback && !loaded.contains(attr) {
@@ -813,7 +944,7 @@ impl FontConfigInner {
             }
         }
 
-        Ok((new_shaper(&*config, &handles)?, handles))
+        Ok((new_shaper(&*config, &handles)?, handles, deferred))
     }
 
     /// Given a text style, load (with caching) the font that best
@@ -837,7 +968,9 @@ impl FontConfigInner {
         let dpi = *self.dpi.borrow() as u32;
         let pixel_size = (font_size * dpi as f64 / 72.0) as u16;
 
-        let (mut shaper, mut handles) = self.resolve_font_helper(style, &config, pixel_size)?;
+        let (mut shaper, mut handles, mut deferred) =
+            self.resolve_font_helper(style, &config, pixel_size)?;
+        let mut pixel_size = pixel_size;
 
         let mut metrics = shaper.metrics(font_size, dpi).with_context(|| {
             format!(
@@ -865,10 +998,12 @@ impl FontConfigInner {
                             scaled_font_size,
                             metrics,
                         );
-                        let (alt_shaper, alt_handles) =
+                        let (alt_shaper, alt_handles, alt_deferred) =
                             self.resolve_font_helper(style, &config, scaled_pixel_size)?;
                         shaper = alt_shaper;
                         handles = alt_handles;
+                        deferred = alt_deferred;
+                        pixel_size = scaled_pixel_size;
 
                         metrics = shaper.metrics(scaled_font_size, dpi).with_context(|| {
                             format!(
@@ -886,6 +1021,7 @@ impl FontConfigInner {
 
         let loaded = Rc::new(LoadedFont {
             rasterizers: RefCell::new(HashMap::new()),
+            disk_caches: RefCell::new(HashMap::new()),
             handles: RefCell::new(handles),
             shaper: RefCell::new(shaper),
             metrics,
@@ -893,6 +1029,8 @@ impl FontConfigInner {
             dpi,
             font_config: Rc::downgrade(myself),
             pending_fallback: Arc::new(Mutex::new(vec![])),
+            deferred_fallback: Arc::new(Mutex::new(deferred)),
+            pixel_size,
             text_style: style.clone(),
             id: alloc_font_id(),
             tried_glyphs: RefCell::new(HashSet::new()),
@@ -151,6 +151,11 @@ pub struct StartCommand {
     #[arg(long = "no-auto-connect")]
     pub no_auto_connect: bool,
 
+    /// Print the time taken by each phase of startup, up to the
+    /// first frame being painted, to stderr
+    #[arg(long = "profile-startup")]
+    pub profile_startup: bool,
+
     /// If enabled, don't try to ask an existing wezterm GUI instance
     /// to start the command.  Instead, always start the GUI in this
     /// invocation of wezterm so that you can wait for the command
@@ -273,6 +278,16 @@ pub struct SerialCommand {
     #[arg(long = "baud")]
     pub baud: Option<usize>,
 
+    /// Set the flow control mode; one of `none`, `software`
+    /// or `hardware`.  The default is `software`.
+    #[arg(long = "flow-control")]
+    pub flow_control: Option<config::keyassignment::SerialFlowControl>,
+
+    /// Append all of the data received from the serial port
+    /// to the specified file
+    #[arg(long = "capture", value_hint=ValueHint::FilePath)]
+    pub capture: Option<std::path::PathBuf>,
+
     /// Override the default windowing system class.
     /// The default is "org.wezfurlong.wezterm".
     /// Under X11 and Windows this changes the window class.
@@ -303,7 +318,10 @@ pub struct SerialCommand {
 #[command(trailing_var_arg = true)]
 pub struct ConnectCommand {
     /// Name of the multiplexer domain section from the configuration
-    /// to which you'd like to connect
+    /// to which you'd like to connect.
+    /// A websocket url such as `wss://host:port/path` may be used
+    /// to connect to a websocket mux server that is not otherwise
+    /// defined in the configuration.
     pub domain_name: String,
 
     /// Override the default windowing system class.
@@ -0,0 +1,146 @@
+//! Computes the color transforms that implement `color_vision_filter`.
+//! All of the transforms operate on linear RGB, and are linear, so each
+//! filter boils down to a single matrix that is applied in the shader.
+use config::ColorVisionFilter;
+
+/// A 3x3 matrix in row major order
+pub type Matrix = [[f32; 3]; 3];
+
+pub const IDENTITY: Matrix = [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]];
+
+// Simulation matrices for full severity dichromacy, from
+// Machado, Oliveira and Fernandes, "A Physiologically-based Model for
+// Simulation of Color Vision Deficiency" (2009)
+const PROTANOPIA: Matrix = [
+    [0.152286, 1.052583, -0.204868],
+    [0.114503, 0.786281, 0.099216],
+    [-0.003882, -0.048116, 1.051998],
+];
+const DEUTERANOPIA: Matrix = [
+    [0.367322, 0.860646, -0.227968],
+    [0.280085, 0.672501, 0.047413],
+    [-0.011820, 0.042940, 0.968881],
+];
+const TRITANOPIA: Matrix = [
+    [1.255528, -0.076749, -0.178779],
+    [-0.078411, 0.930809, 0.147602],
+    [0.004733, 0.691367, 0.303900],
+];
+
+// Daltonization redistributes the information that is lost by the
+// simulation into the channels that can still be perceived
+const RED_GREEN_ERROR_SHIFT: Matrix = [[0., 0., 0.], [0.7, 1., 0.], [0.7, 0., 1.]];
+const BLUE_YELLOW_ERROR_SHIFT: Matrix = [[1., 0., 0.7], [0., 1., 0.7], [0., 0., 0.]];
+
+fn mul(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut result = [[0.; 3]; 3];
+    for (row, result_row) in result.iter_mut().enumerate() {
+        for (col, value) in result_row.iter_mut().enumerate() {
+            *value = (0..3).map(|i| a[row][i] * b[i][col]).sum();
+        }
+    }
+    result
+}
+
+/// Returns `I + shift * (I - simulation)`, which transforms a color
+/// by adding the shifted difference between it and its simulation
+fn daltonize(simulation: &Matrix, shift: &Matrix) -> Matrix {
+    let mut error = IDENTITY;
+    for (error_row, sim_row) in error.iter_mut().zip(simulation.iter()) {
+        for (value, sim) in error_row.iter_mut().zip(sim_row.iter()) {
+            *value -= sim;
+        }
+    }
+    let mut result = mul(shift, &error);
+    for (i, row) in result.iter_mut().enumerate() {
+        row[i] += 1.;
+    }
+    result
+}
+
+/// Returns the matrix that implements the filter, or None if
+/// colors should be left alone
+pub fn color_vision_matrix(filter: ColorVisionFilter) -> Option<Matrix> {
+    match filter {
+        ColorVisionFilter::None => None,
+        ColorVisionFilter::SimulateProtanopia => Some(PROTANOPIA),
+        ColorVisionFilter::SimulateDeuteranopia => Some(DEUTERANOPIA),
+        ColorVisionFilter::SimulateTritanopia => Some(TRITANOPIA),
+        ColorVisionFilter::DaltonizeProtanopia => {
+            Some(daltonize(&PROTANOPIA, &RED_GREEN_ERROR_SHIFT))
+        }
+        ColorVisionFilter::DaltonizeDeuteranopia => {
+            Some(daltonize(&DEUTERANOPIA, &RED_GREEN_ERROR_SHIFT))
+        }
+        ColorVisionFilter::DaltonizeTritanopia => {
+            Some(daltonize(&TRITANOPIA, &BLUE_YELLOW_ERROR_SHIFT))
+        }
+    }
+}
+
+/// Converts a row major matrix into the column major form
+/// that is expected for a glsl `mat3` uniform
+pub fn to_column_major(m: &Matrix) -> Matrix {
+    let mut result = [[0.; 3]; 3];
+    for (row, m_row) in m.iter().enumerate() {
+        for (col, value) in m_row.iter().enumerate() {
+            result[col][row] = *value;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn apply(m: &Matrix, c: [f32; 3]) -> [f32; 3] {
+        let mut result = [0.; 3];
+        for (value, m_row) in result.iter_mut().zip(m.iter()) {
+            *value = m_row.iter().zip(c.iter()).map(|(a, b)| a * b).sum();
+        }
+        result
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        for (x, y) in a.iter().zip(b.iter()) {
+            assert!((x - y).abs() < 0.001, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn greys_are_preserved() {
+        for filter in vec![
+            ColorVisionFilter::SimulateProtanopia,
+            ColorVisionFilter::SimulateDeuteranopia,
+            ColorVisionFilter::SimulateTritanopia,
+            ColorVisionFilter::DaltonizeProtanopia,
+            ColorVisionFilter::DaltonizeDeuteranopia,
+            ColorVisionFilter::DaltonizeTritanopia,
+        ] {
+            let m = color_vision_matrix(filter).unwrap();
+            assert_close(apply(&m, [1., 1., 1.]), [1., 1., 1.]);
+            assert_close(apply(&m, [0.5, 0.5, 0.5]), [0.5, 0.5, 0.5]);
+        }
+        assert!(color_vision_matrix(ColorVisionFilter::None).is_none());
+    }
+
+    #[test]
+    fn daltonize_separates_red_and_green() {
+        let sim = color_vision_matrix(ColorVisionFilter::SimulateDeuteranopia).unwrap();
+        let fix = color_vision_matrix(ColorVisionFilter::DaltonizeDeuteranopia).unwrap();
+
+        let distance = |a: [f32; 3], b: [f32; 3]| -> f32 {
+            (0..3).map(|i| (a[i] - b[i]).powi(2)).sum::<f32>().sqrt()
+        };
+
+        let red = [0.8, 0.2, 0.1];
+        let green = [0.3, 0.6, 0.1];
+        let before = distance(apply(&sim, red), apply(&sim, green));
+        let after = distance(
+            apply(&sim, apply(&fix, red)),
+            apply(&sim, apply(&fix, green)),
+        );
+        assert!(after > before, "{} should be > {}", after, before);
+    }
+}
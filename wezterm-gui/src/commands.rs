@@ -3,6 +3,7 @@ use config::{ConfigHandle, DeferredKeyCode};
 use ordered_float::NotNan;
 use std::borrow::Cow;
 use std::convert::TryFrom;
+use wezterm_bidi::ParagraphDirectionHint;
 use window::{KeyCode, Modifiers};
 use KeyAssignment::*;
 
@@ -218,6 +219,22 @@ static DEFS: &[CommandDef] = &[
         keys: &[(Modifiers::SUPER, "c"), (Modifiers::NONE, "Copy")],
         args: &[ArgType::ActivePane],
     },
+    CommandDef {
+        brief: "Copy to clipboard with colors",
+        doc: "Copies text to the clipboard along with HTML and RTF versions \
+              of it that keep its colors and styles",
+        exp: |exp| exp.push(CopyRichTextTo(ClipboardCopyDestination::Clipboard)),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Copy to clipboard with options",
+        doc: "Shows a menu of adjustments to make to the selected text, \
+              such as stripping prompts or table borders, before copying it",
+        exp: |exp| exp.push(ShowCopyMenu),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
     CommandDef {
         brief: "Paste from clipboard",
         doc: "Pastes text from the clipboard",
@@ -682,6 +699,37 @@ static DEFS: &[CommandDef] = &[
         keys: &[],
         args: &[ArgType::ActiveWindow],
     },
+    CommandDef {
+        brief: "Activate the last active pane",
+        doc: "Activates the most recently focused pane in the current tab. \
+              If there was no prior active pane, has no effect.",
+        exp: |exp| exp.push(ActivateLastPane),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Activate the last active window",
+        doc: "If there was no prior active window, has no effect.",
+        exp: |exp| exp.push(ActivateLastWindow),
+        keys: &[],
+        args: &[ArgType::ActiveWindow],
+    },
+    CommandDef {
+        brief: "Switch to a recent pane",
+        doc: "Shows the panes with the most recently used first, \
+              with a preview of their output",
+        exp: |exp| exp.push(ShowRecentPanes),
+        keys: &[],
+        args: &[ArgType::ActiveWindow],
+    },
+    CommandDef {
+        brief: "Show connected mux clients",
+        doc: "Lists the clients connected to this instance and to the \
+              attached mux servers, and allows detaching or restricting them",
+        exp: |exp| exp.push(ShowMuxClients),
+        keys: &[],
+        args: &[ArgType::ActiveWindow],
+    },
     CommandDef {
         brief: "Clear the key table stack",
         doc: "Removes all entries from the stack",
@@ -745,6 +793,269 @@ static DEFS: &[CommandDef] = &[
         keys: &[],
         args: &[ArgType::ActiveWindow],
     },
+    CommandDef {
+        brief: "Manage workspaces",
+        doc: "Shows the workspace manager",
+        exp: |exp| exp.push(ShowWorkspaceManager),
+        keys: &[],
+        args: &[ArgType::ActiveWindow],
+    },
+    CommandDef {
+        brief: "Paste from clipboard history",
+        doc: "Shows the clipboard history picker",
+        exp: |exp| exp.push(ShowClipboardHistory),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Show notification center",
+        doc: "Shows the history of notifications, bells, failed commands and connection errors",
+        exp: |exp| exp.push(ShowNotificationCenter),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Toggle do not disturb",
+        doc: "Toggles whether notifications and bells are only recorded in the notification center",
+        exp: |exp| exp.push(ToggleDoNotDisturb),
+        keys: &[],
+        args: &[ArgType::ActiveWindow],
+    },
+    CommandDef {
+        brief: "Mark the scroll position",
+        doc: "Records the scroll position of the active pane in its jumplist",
+        exp: |exp| exp.push(SetMark),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Jump back",
+        doc: "Scrolls to the previous position in the jumplist of the active pane",
+        exp: |exp| exp.push(JumpBack),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Jump forward",
+        doc: "Scrolls to the next position in the jumplist of the active pane",
+        exp: |exp| exp.push(JumpForward),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Show named marks",
+        doc: "Shows the named marks of the active pane, to scroll to one of them",
+        exp: |exp| exp.push(ShowNamedMarks),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Activate hints mode",
+        doc: "Labels the links, paths, tab bar buttons and pane dividers so that they can be activated using the keyboard",
+        exp: |exp| exp.push(ActivateHintsMode),
+        keys: &[],
+        args: &[ArgType::ActiveWindow],
+    },
+    CommandDef {
+        brief: "Auto-detect the text direction of the active pane",
+        doc: "Enables bidi in the active pane and detects the direction of each line from its text",
+        exp: |exp| {
+            exp.push(SetPaneBidiMode(PaneBidiMode {
+                enabled: Some(true),
+                direction: Some(ParagraphDirectionHint::AutoLeftToRight),
+            }));
+        },
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Reset the text direction of the active pane",
+        doc: "Reverts the bidi mode of the active pane to that of the configuration",
+        exp: |exp| exp.push(SetPaneBidiMode(PaneBidiMode::default())),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Mute the bell in the active pane",
+        doc: "Prevents the bell from making a sound in the active pane",
+        exp: |exp| exp.push(SetPaneBellVolume(0)),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Unmute the bell in the active pane",
+        doc: "Restores the configured volume of the bell in the active pane",
+        exp: |exp| exp.push(SetPaneBellVolume(100)),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Open a serial port",
+        doc: "Shows the available serial ports and opens the chosen port in a new tab",
+        exp: |exp| exp.push(ShowSerialPortSelector),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Toggle the DTR line of the serial port",
+        doc: "Inverts the DTR modem control line of the serial port of the active pane",
+        exp: |exp| exp.push(ToggleSerialDtr),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Toggle the RTS line of the serial port",
+        doc: "Inverts the RTS modem control line of the serial port of the active pane",
+        exp: |exp| exp.push(ToggleSerialRts),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Open a shell in a kubernetes pod",
+        doc: "Shows the kubernetes contexts, namespaces and pods and opens the chosen pod in a new tab",
+        exp: |exp| exp.push(ShowKubernetesPodSelector),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Open a shell in a container",
+        doc: "Shows the running docker or podman containers and opens the chosen container in a new tab",
+        exp: |exp| exp.push(ShowContainerSelector),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Pipe output into the pane to the right",
+        doc: "Starts or stops copying the output of the active pane into the input of the pane to its right",
+        exp: |exp| exp.push(TogglePipeToPane(PaneDirection::Right)),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Pipe output into the pane below",
+        doc: "Starts or stops copying the output of the active pane into the input of the pane below it",
+        exp: |exp| exp.push(TogglePipeToPane(PaneDirection::Down)),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Toggle logging the output of the active pane",
+        doc: "Starts or stops writing the output of the active pane to a file in the home directory",
+        exp: |exp| exp.push(TogglePaneLogging(PaneLogging::default())),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Fill in the password",
+        doc: "Types the password for the prompt in the active pane from the configured password manager",
+        exp: |exp| exp.push(FillPassword),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Reveal or mask redacted text",
+        doc: "Toggles whether the text in the active pane that matches redaction_rules is shown",
+        exp: |exp| exp.push(ToggleRedactionReveal),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Lock input to the pane",
+        doc: "Stops input from being delivered to the active pane until the passphrase is typed",
+        exp: |exp| exp.push(LockInput(InputLockScope::Pane)),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Lock input to the window",
+        doc: "Stops input from being delivered to the window until the passphrase is typed",
+        exp: |exp| exp.push(LockInput(InputLockScope::Window)),
+        keys: &[],
+        args: &[],
+    },
+    CommandDef {
+        brief: "Page the output of the last command",
+        doc: "Opens the output of the last command in a pager, with search, wrapping and saving",
+        exp: |exp| exp.push(PageLastCommandOutput),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Snapshot the pane to the right",
+        doc: "Copies the content of the active pane into a new read-only pane to its right",
+        exp: |exp| exp.push(SnapshotPane(PaneDirection::Right)),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Snapshot the pane below",
+        doc: "Copies the content of the active pane into a new read-only pane below it",
+        exp: |exp| exp.push(SnapshotPane(PaneDirection::Down)),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Toggle diff rendering",
+        doc: "Toggles highlighting unified diffs in the active pane, similar to delta",
+        exp: |exp| exp.push(TogglePaneDiffMode),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Compare with the pane to the left",
+        doc: "Locks scrolling with the pane to the left and highlights the lines that differ, or stops comparing",
+        exp: |exp| {
+            exp.push(ToggleComparePanes(ComparePanes {
+                direction: PaneDirection::Left,
+                highlight_differences: true,
+            }))
+        },
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Compare with the pane to the right",
+        doc: "Locks scrolling with the pane to the right and highlights the lines that differ, or stops comparing",
+        exp: |exp| {
+            exp.push(ToggleComparePanes(ComparePanes {
+                direction: PaneDirection::Right,
+                highlight_differences: true,
+            }))
+        },
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Compare with the pane above",
+        doc: "Locks scrolling with the pane above and highlights the lines that differ, or stops comparing",
+        exp: |exp| {
+            exp.push(ToggleComparePanes(ComparePanes {
+                direction: PaneDirection::Up,
+                highlight_differences: true,
+            }))
+        },
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Compare with the pane below",
+        doc: "Locks scrolling with the pane below and highlights the lines that differ, or stops comparing",
+        exp: |exp| {
+            exp.push(ToggleComparePanes(ComparePanes {
+                direction: PaneDirection::Down,
+                highlight_differences: true,
+            }))
+        },
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
+    CommandDef {
+        brief: "Restart the active pane",
+        doc: "Re-runs the command of the active pane, keeping its scrollback",
+        exp: |exp| exp.push(RestartPane),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
     CommandDef {
         brief: "Detach the domain of the active pane",
         doc: "Detaches (disconnects from) the domain of the active pane",
@@ -752,4 +1063,13 @@ static DEFS: &[CommandDef] = &[
         keys: &[],
         args: &[ArgType::ActivePane],
     },
+    CommandDef {
+        brief: "Take or give up the input turn",
+        doc: "When the active pane is shared with other clients through a mux \
+              server, takes the input turn so that only you can type into it, \
+              or gives it up if you already have it",
+        exp: |exp| exp.push(TogglePaneInputTurn),
+        keys: &[],
+        args: &[ArgType::ActivePane],
+    },
 ];
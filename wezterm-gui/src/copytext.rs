@@ -0,0 +1,240 @@
+//! Produces the text that is copied from the selection, applying the
+//! adjustments described by `CopyOptions`.
+use config::keyassignment::{CopyLineBreaks, CopyOptions};
+use termwiz::cell::SemanticType;
+use termwiz::surface::Line;
+
+/// A row of the selection
+pub struct SelectedRow {
+    /// The selected cells of the row
+    pub line: Line,
+    /// True if the text continues on the next row because the row
+    /// was wrapped rather than ended with a line break
+    pub wrapped: bool,
+}
+
+fn row_text(row: &SelectedRow, strip_prompts: bool) -> String {
+    let mut text = String::new();
+    for cell in row.line.visible_cells() {
+        if strip_prompts && cell.attrs().semantic_type() == SemanticType::Prompt {
+            continue;
+        }
+        text.push_str(cell.str());
+    }
+    text
+}
+
+fn is_box_drawing(c: char) -> bool {
+    ('\u{2500}'..='\u{257f}').contains(&c)
+}
+
+/// Drops lines that only hold table borders, and separates the cells
+/// of the other lines of a table with tabs
+fn collapse_table_borders(lines: Vec<String>) -> Vec<String> {
+    lines
+        .into_iter()
+        .filter_map(|line| {
+            if !line.contains(is_box_drawing) {
+                return Some(line);
+            }
+            if line.chars().all(|c| is_box_drawing(c) || c.is_whitespace()) {
+                return None;
+            }
+            let cells: Vec<&str> = line.split(is_box_drawing).map(str::trim).collect();
+            // Borders at the edges of the row produce empty cells
+            let first = cells.iter().position(|c| !c.is_empty()).unwrap_or(0);
+            let last = cells.iter().rposition(|c| !c.is_empty()).unwrap_or(0);
+            Some(cells[first..=last].join("\t"))
+        })
+        .collect()
+}
+
+fn join_paragraphs(lines: Vec<String>) -> Vec<String> {
+    let mut paragraphs: Vec<String> = vec![];
+    let mut current: Vec<&str> = vec![];
+    for line in &lines {
+        let line = line.trim();
+        if line.is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(current.join(" "));
+                current.clear();
+            }
+        } else {
+            current.push(line);
+        }
+    }
+    if !current.is_empty() {
+        paragraphs.push(current.join(" "));
+    }
+    paragraphs
+        .join("\n\n")
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Returns the text of the selected rows, adjusted according to `options`
+pub fn format_selection(rows: &[SelectedRow], options: &CopyOptions) -> String {
+    let mut lines: Vec<String> = vec![];
+    let mut continued = false;
+    for row in rows {
+        let text = row_text(row, options.strip_prompts);
+        if options.strip_prompts
+            && text.trim().is_empty()
+            && !row_text(row, false).trim().is_empty()
+        {
+            // The row only held a prompt
+            continued = row.wrapped;
+            continue;
+        }
+
+        let joins_next = row.wrapped && options.line_breaks != CopyLineBreaks::AsDisplayed;
+        // Only trim trailing whitespace from the last row of a
+        // wrapped sequence, as that space is part of the text
+        let text = if joins_next {
+            text
+        } else {
+            text.trim_end().to_string()
+        };
+
+        match lines.last_mut() {
+            Some(last) if continued => last.push_str(&text),
+            _ => lines.push(text),
+        }
+        continued = joins_next;
+    }
+
+    if options.collapse_table_borders {
+        lines = collapse_table_borders(lines);
+    }
+    if options.line_breaks == CopyLineBreaks::JoinParagraphs {
+        lines = join_paragraphs(lines);
+    }
+    if options.strip_trailing_whitespace {
+        for line in &mut lines {
+            let len = line.trim_end().len();
+            line.truncate(len);
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::cell::CellAttributes;
+    use termwiz::surface::SEQ_ZERO;
+
+    fn row(text: &str, wrapped: bool) -> SelectedRow {
+        SelectedRow {
+            line: Line::from_text(text, &CellAttributes::default(), SEQ_ZERO, None),
+            wrapped,
+        }
+    }
+
+    fn prompt_row(prompt: &str, input: &str) -> SelectedRow {
+        let mut attrs = CellAttributes::default();
+        attrs.set_semantic_type(SemanticType::Prompt);
+        let mut line = Line::from_text(prompt, &attrs, SEQ_ZERO, None);
+        line.append_line(
+            Line::from_text(input, &CellAttributes::default(), SEQ_ZERO, None),
+            SEQ_ZERO,
+        );
+        SelectedRow {
+            line,
+            wrapped: false,
+        }
+    }
+
+    fn options(line_breaks: CopyLineBreaks) -> CopyOptions {
+        CopyOptions {
+            line_breaks,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn line_breaks() {
+        let rows = vec![
+            row("hello ", true),
+            row("world  ", false),
+            row("", false),
+            row("second  ", false),
+            row("paragraph", false),
+        ];
+        assert_eq!(
+            format_selection(&rows, &options(CopyLineBreaks::JoinWrapped)),
+            "hello world\n\nsecond\nparagraph"
+        );
+        assert_eq!(
+            format_selection(&rows, &options(CopyLineBreaks::AsDisplayed)),
+            "hello\nworld\n\nsecond\nparagraph"
+        );
+        assert_eq!(
+            format_selection(&rows, &options(CopyLineBreaks::JoinParagraphs)),
+            "hello world\n\nsecond paragraph"
+        );
+    }
+
+    #[test]
+    fn trailing_whitespace() {
+        let rows = vec![row("wrapped   ", true), row("x", false)];
+        assert_eq!(
+            format_selection(&rows, &options(CopyLineBreaks::AsDisplayed)),
+            "wrapped\nx"
+        );
+        let rows = vec![row("a  ", true)];
+        assert_eq!(format_selection(&rows, &CopyOptions::default()), "a  ");
+        let opts = CopyOptions {
+            strip_trailing_whitespace: true,
+            ..Default::default()
+        };
+        assert_eq!(format_selection(&rows, &opts), "a");
+    }
+
+    #[test]
+    fn tables() {
+        let rows = vec![
+            row("┌──────┬─────┐", false),
+            row("│ name │ age │", false),
+            row("├──────┼─────┤", false),
+            row("│ bob  │ 42  │", false),
+            row("└──────┴─────┘", false),
+            row("plain text", false),
+        ];
+        let opts = CopyOptions {
+            collapse_table_borders: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_selection(&rows, &opts),
+            "name\tage\nbob\t42\nplain text"
+        );
+    }
+
+    #[test]
+    fn prompts() {
+        let rows = vec![
+            row("~/src/wezterm", false),
+            prompt_row("$ ", "cargo build"),
+            row("Compiling...", false),
+        ];
+        let opts = CopyOptions {
+            strip_prompts: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_selection(&rows, &opts),
+            "~/src/wezterm\ncargo build\nCompiling..."
+        );
+        assert_eq!(
+            format_selection(&rows, &CopyOptions::default()),
+            "~/src/wezterm\n$ cargo build\nCompiling..."
+        );
+
+        let mut only_prompt = prompt_row("> ", "");
+        only_prompt.line.prune_trailing_blanks(SEQ_ZERO);
+        let rows = vec![only_prompt, row("output", false)];
+        assert_eq!(format_selection(&rows, &opts), "output");
+    }
+}
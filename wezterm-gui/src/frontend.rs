@@ -1,10 +1,13 @@
+use crate::overlay::{deliver_notification, Notification};
 use crate::scripting::guiwin::GuiWin;
 use crate::termwindow::TermWindowNotif;
 use crate::TermWindow;
 use ::window::*;
 use anyhow::{Context, Error};
 pub use config::FrontEndSelection;
+use config::NotificationSource;
 use mux::client::ClientId;
+use mux::pane::PaneId;
 use mux::window::WindowId as MuxWindowId;
 use mux::{Mux, MuxNotification};
 use promise::{Future, Promise};
@@ -13,7 +16,6 @@ use std::collections::{BTreeMap, HashSet};
 use std::rc::Rc;
 use std::sync::Arc;
 use wezterm_term::{Alert, ClipboardSelection};
-use wezterm_toast_notification::*;
 
 pub struct GuiFrontEnd {
     connection: Rc<Connection>,
@@ -21,6 +23,8 @@ pub struct GuiFrontEnd {
     spawned_mux_window: RefCell<HashSet<MuxWindowId>>,
     known_windows: RefCell<BTreeMap<Window, MuxWindowId>>,
     client_id: Arc<ClientId>,
+    shell_menu: RefCell<Option<Vec<ShellMenuGroup>>>,
+    _config_subscription: config::ConfigSubscription,
 }
 
 impl Drop for GuiFrontEnd {
@@ -42,7 +46,18 @@ impl GuiFrontEnd {
             spawned_mux_window: RefCell::new(HashSet::new()),
             known_windows: RefCell::new(BTreeMap::new()),
             client_id: client_id.clone(),
+            shell_menu: RefCell::new(None),
+            _config_subscription: config::subscribe_to_config_reload(|| {
+                promise::spawn::spawn_into_main_thread(async {
+                    if let Some(fe) = try_front_end() {
+                        fe.update_shell_menu();
+                    }
+                })
+                .detach();
+                true
+            }),
         });
+        front_end.update_shell_menu();
         let fe = Rc::downgrade(&front_end);
         mux.subscribe(move |n| {
             if let Some(fe) = fe.upgrade() {
@@ -56,16 +71,43 @@ impl GuiFrontEnd {
                             if !fe.is_switching_workspace() {
                                 fe.reconcile_workspace();
                             }
+                            fe.update_shell_menu();
                         })
                         .detach();
                     }
                     MuxNotification::TabAddedToWindow { .. } => {}
                     MuxNotification::PaneRemoved(_) => {}
                     MuxNotification::WindowInvalidated(_) => {}
+                    MuxNotification::PaneFocused(_) => {}
                     MuxNotification::PaneOutput(_) => {}
                     MuxNotification::PaneAdded(_) => {}
+                    MuxNotification::PaneCollaboratorsChanged(_) => {}
+                    MuxNotification::ClientDetachRequested(_) => {}
+                    MuxNotification::EscapeSequencePermission { pane_id, kind } => {
+                        // Handled by the TermWindow displaying the pane,
+                        // if there is one.  Otherwise, nothing would ever
+                        // conclude the prompt, so deny it now.
+                        let mux = Mux::get().expect("mux is calling us");
+                        let shown = match mux.resolve_pane_id(pane_id) {
+                            Some((_domain_id, window_id, _tab_id)) => fe
+                                .known_windows
+                                .borrow()
+                                .values()
+                                .any(|&id| id == window_id),
+                            None => false,
+                        };
+                        if !shown {
+                            mux::escape_permissions::deny_unhandled_prompt(pane_id, kind);
+                        }
+                    }
+                    MuxNotification::CaptureImage { .. }
+                    | MuxNotification::InspectDeadPane { .. }
+                    | MuxNotification::QueryClipboard { .. }
+                    | MuxNotification::PaneActivationRequested(_) => {
+                        // Handled by the TermWindow displaying the pane
+                    }
                     MuxNotification::Alert {
-                        pane_id: _,
+                        pane_id,
                         alert:
                             Alert::ToastNotification {
                                 title,
@@ -75,10 +117,13 @@ impl GuiFrontEnd {
                     } => {
                         let message = if title.is_none() { "" } else { &body };
                         let title = title.as_ref().unwrap_or(&body);
+                        let mut notif = Notification::new(NotificationSource::Toast, title);
+                        notif.body = message.to_string();
+                        notif.pane_id.replace(pane_id);
                         // FIXME: if notification.focus is true, we should do
                         // something here to arrange to focus pane_id when the
                         // notification is clicked
-                        persistent_toast_notification(title, message);
+                        fe.deliver_pane_notification(pane_id, notif);
                     }
                     MuxNotification::Alert {
                         pane_id: _,
@@ -86,6 +131,35 @@ impl GuiFrontEnd {
                     } => {
                         // Handled via TermWindowNotif; NOP it here.
                     }
+                    MuxNotification::Alert {
+                        pane_id,
+                        alert: Alert::CommandFinished { status },
+                    } => {
+                        // Only failures are recorded, as shells report the
+                        // status of every command that they run
+                        if status != 0 {
+                            let mut notif = Notification::new(
+                                NotificationSource::Command,
+                                format!("Command exited with status {}", status),
+                            );
+                            notif.body = pane_title(pane_id);
+                            notif.pane_id.replace(pane_id);
+                            fe.deliver_pane_notification(pane_id, notif);
+                        }
+                    }
+                    MuxNotification::ConnectionError { domain_id, message } => {
+                        let mux = Mux::get().expect("on main thread");
+                        if let Some(domain) = mux.get_domain(domain_id) {
+                            let name = domain.domain_name().to_string();
+                            let mut notif = Notification::new(
+                                NotificationSource::Connection,
+                                format!("Lost connection to {}", name),
+                            );
+                            notif.body = message;
+                            notif.domain_name.replace(name);
+                            deliver_notification(notif, false, false);
+                        }
+                    }
                     MuxNotification::Alert {
                         pane_id: _,
                         alert:
@@ -95,7 +169,9 @@ impl GuiFrontEnd {
                             | Alert::WindowTitleChanged(_)
                             | Alert::TabTitleChanged(_)
                             | Alert::IconTitleChanged(_)
-                            | Alert::SetUserVar { .. },
+                            | Alert::SetUserVar { .. }
+                            | Alert::BadgeChanged(_)
+                            | Alert::RequestAttention(_),
                     } => {}
                     MuxNotification::Empty => {
                         if mux::activity::Activity::count() == 0 {
@@ -126,7 +202,8 @@ impl GuiFrontEnd {
                             clipboard
                         );
                         if let Some(window) = fe.known_windows.borrow().keys().next() {
-                            window.set_clipboard(
+                            crate::termwindow::clipboard::set_clipboard_contents(
+                                window,
                                 match selection {
                                     ClipboardSelection::Clipboard => Clipboard::Clipboard,
                                     ClipboardSelection::PrimarySelection => {
@@ -152,6 +229,24 @@ impl GuiFrontEnd {
         Ok(front_end)
     }
 
+    /// Updates the taskbar jump list or Dock menu to reflect the
+    /// configuration and the recently used workspaces
+    pub fn update_shell_menu(&self) {
+        let mux = match Mux::get() {
+            Some(mux) => mux,
+            None => return,
+        };
+        let groups = crate::shellmenu::compute_shell_menu(
+            &config::configuration(),
+            &crate::shellmenu::recent_workspaces(&mux),
+        );
+        let mut shell_menu = self.shell_menu.borrow_mut();
+        if shell_menu.as_ref() != Some(&groups) {
+            self.connection.set_shell_menu(groups.clone());
+            shell_menu.replace(groups);
+        }
+    }
+
     pub fn run_forever(&self) -> anyhow::Result<()> {
         self.connection
             .run_message_loop()
@@ -183,6 +278,15 @@ impl GuiFrontEnd {
         let workspace = mux.active_workspace_for_client(&self.client_id);
         log::debug!("workspace is {}, fixup windows", workspace);
 
+        if config::configuration().workspace_per_window {
+            // Windows are never repurposed for another workspace;
+            // instead, every workspace gets windows of its own
+            self.known_windows
+                .borrow_mut()
+                .retain(|_, window_id| mux.get_window(*window_id).is_some());
+            return self.spawn_windows(mux.iter_windows(), promise);
+        }
+
         let mut mux_windows = mux.iter_windows_in_workspace(&workspace);
 
         // First, repurpose existing windows.
@@ -220,11 +324,16 @@ impl GuiFrontEnd {
         log::trace!("reconcile: windows -> {:?}", windows);
         *self.known_windows.borrow_mut() = windows;
 
+        self.spawn_windows(mux_windows.collect(), promise)
+    }
+
+    /// Creates a TermWindow for each of `mux_windows` that doesn't
+    /// already have one, and then completes the workspace switch
+    fn spawn_windows(&self, mux_windows: Vec<MuxWindowId>, mut promise: Promise<()>) -> Future<()> {
         let future = promise.get_future().unwrap();
 
-        // then spawn any new windows that are needed
         promise::spawn::spawn(async move {
-            while let Some(mux_window_id) = mux_windows.next() {
+            for mux_window_id in mux_windows {
                 if front_end().has_mux_window(mux_window_id)
                     || front_end()
                         .spawned_mux_window
@@ -268,7 +377,46 @@ impl GuiFrontEnd {
         let mux = Mux::get().expect("mux started and running on main thread");
         mux.set_active_workspace_for_client(&self.client_id, workspace);
         *self.switching_workspaces.borrow_mut() = false;
-        self.reconcile_workspace();
+        let reconciled = self.reconcile_workspace();
+
+        if config::configuration().workspace_per_window {
+            // The windows of the workspace already exist, or are being
+            // created; bring the first of them to the front
+            let workspace = workspace.to_string();
+            promise::spawn::spawn(async move {
+                reconciled.await.ok();
+                let fe = front_end();
+                let mux = Mux::get().expect("mux started and running on main thread");
+                let mux_windows = mux.iter_windows_in_workspace(&workspace);
+                let window = mux_windows.iter().find_map(|id| {
+                    fe.known_windows
+                        .borrow()
+                        .iter()
+                        .find(|(_, window_id)| *window_id == id)
+                        .map(|(window, _)| window.clone())
+                });
+                if let Some(window) = window {
+                    window.focus();
+                }
+            })
+            .detach();
+        }
+    }
+
+    /// When each window is bound to a workspace, the active workspace
+    /// follows the focused window
+    pub fn focused_mux_window(&self, mux_window_id: MuxWindowId) {
+        if !config::configuration().workspace_per_window || self.is_switching_workspace() {
+            return;
+        }
+        let mux = Mux::get().expect("mux started and running on main thread");
+        let workspace = match mux.get_window(mux_window_id) {
+            Some(window) => window.get_workspace().to_string(),
+            None => return,
+        };
+        if mux.active_workspace_for_client(&self.client_id) != workspace {
+            mux.set_active_workspace_for_client(&self.client_id, &workspace);
+        }
     }
 
     pub fn record_known_window(&self, window: Window, mux_window_id: MuxWindowId) {
@@ -291,6 +439,39 @@ impl GuiFrontEnd {
         *self.switching_workspaces.borrow()
     }
 
+    pub fn gui_windows(&self) -> Vec<GuiWin> {
+        self.known_windows
+            .borrow()
+            .iter()
+            .map(|(window, &mux_window_id)| GuiWin {
+                mux_window_id,
+                window: window.clone(),
+            })
+            .collect()
+    }
+
+    /// Delivers a notification from a pane via the window that
+    /// contains it, so that the notification_rules can take the
+    /// state of that window into account
+    fn deliver_pane_notification(&self, pane_id: PaneId, notif: Notification) {
+        let mux = Mux::get().expect("on main thread");
+        let gui_win = mux
+            .resolve_pane_id(pane_id)
+            .and_then(|(_domain_id, window_id, _tab_id)| self.gui_window_for_mux_window(window_id));
+        match gui_win {
+            Some(gui_win) => {
+                gui_win
+                    .window
+                    .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                        term_window.deliver_notification(notif);
+                    })));
+            }
+            None => {
+                deliver_notification(notif, false, false);
+            }
+        }
+    }
+
     pub fn gui_window_for_mux_window(&self, mux_window_id: MuxWindowId) -> Option<GuiWin> {
         let windows = self.known_windows.borrow();
         for (window, v) in windows.iter() {
@@ -305,6 +486,13 @@ impl GuiFrontEnd {
     }
 }
 
+fn pane_title(pane_id: PaneId) -> String {
+    Mux::get()
+        .and_then(|mux| mux.get_pane(pane_id))
+        .map(|pane| pane.get_title())
+        .unwrap_or_default()
+}
+
 thread_local! {
     static FRONT_END: RefCell<Option<Rc<GuiFrontEnd>>> = RefCell::new(None);
 }
@@ -21,6 +21,9 @@ uniform sampler2D atlas_nearest_sampler;
 uniform sampler2D atlas_linear_sampler;
 uniform bool subpixel_aa;
 uniform uint milliseconds;
+// Transforms the linear RGB output to implement color_vision_filter
+uniform mat3 color_vision;
+uniform bool has_color_vision;
 
 struct ColorEase {
   vec4 in_function;
@@ -148,6 +151,10 @@ void main() {
 
   color = apply_hsv(color, o_hsv);
 
+  if (has_color_vision) {
+    color.rgb = clamp(color_vision * color.rgb, 0.0, 1.0);
+  }
+
   // We MUST output SRGB and tell glium that we do that (outputs_srgb),
   // otherwise something in glium over-gamma-corrects depending on the gl setup.
   color = to_srgb(color);
@@ -287,10 +287,22 @@ impl InputMap {
                     },
                     MouseEventTrigger::Down {
                         streak: 1,
-                        button: MouseButton::Middle
+                        button: config.primary_selection_paste_button
                     },
                     PasteFrom(ClipboardPasteSource::PrimarySelection)
                 ],
+                [
+                    MouseEventTriggerMods {
+                        mods: Modifiers::NONE,
+                        mouse_reporting: false,
+                        alt_screen: MouseEventAltScreen::Any,
+                    },
+                    MouseEventTrigger::Down {
+                        streak: 1,
+                        button: MouseButton::Right
+                    },
+                    ShowContextMenu
+                ],
                 [
                     MouseEventTriggerMods {
                         mods: Modifiers::SUPER,
@@ -8,9 +8,9 @@ use anyhow::{anyhow, Context};
 use clap::builder::ValueParser;
 use clap::{Parser, ValueHint};
 use config::keyassignment::SpawnCommand;
-use config::{ConfigHandle, SshDomain, SshMultiplexing};
+use config::{ConfigHandle, SshDomain, SshMultiplexing, WssDomainClient};
 use mux::activity::Activity;
-use mux::domain::{Domain, LocalDomain};
+use mux::domain::{Domain, DomainState, LocalDomain};
 use mux::ssh::RemoteSshDomain;
 use mux::Mux;
 use portable_pty::cmdbuilder::CommandBuilder;
@@ -30,7 +30,9 @@ use wezterm_toast_notification::*;
 
 mod cache;
 mod colorease;
+mod colorvision;
 mod commands;
+mod copytext;
 mod customglyph;
 mod download;
 mod frontend;
@@ -40,10 +42,14 @@ mod markdown;
 mod overlay;
 mod quad;
 mod renderstate;
+mod richcopy;
 mod scripting;
 mod scrollbar;
 mod selection;
 mod shapecache;
+mod shellmenu;
+mod sound;
+mod startup;
 mod stats;
 mod tabbar;
 mod termwindow;
@@ -132,6 +138,7 @@ async fn async_run_ssh(opts: SshCommand) -> anyhow::Result<()> {
         username: opts.user_at_host_and_port.username.clone(),
         multiplexing: SshMultiplexing::None,
         ssh_option,
+        install_terminfo: true,
         ..Default::default()
     };
 
@@ -197,9 +204,14 @@ fn run_serial(config: config::ConfigHandle, opts: &SerialCommand) -> anyhow::Res
     if let Some(baud) = opts.baud {
         serial.set_baud_rate(serial::BaudRate::from_speed(baud));
     }
+    if let Some(flow_control) = opts.flow_control {
+        serial.set_flow_control(termwindow::serialport::flow_control_to_serial(flow_control));
+    }
+    if let Some(capture) = opts.capture.as_ref() {
+        serial.set_capture_file(capture);
+    }
 
-    let pty_system = Box::new(serial);
-    let domain: Arc<dyn Domain> = Arc::new(LocalDomain::with_pty_system("local", pty_system));
+    let domain = termwindow::serialport::create_serial_domain("local", serial);
     let mux = setup_mux(domain.clone(), &config, Some("local"), None)?;
 
     let gui = crate::frontend::try_new()?;
@@ -231,6 +243,14 @@ fn client_domains(config: &config::ConfigHandle) -> Vec<ClientDomainConfig> {
     for tls_client in &config.tls_clients {
         domains.push(ClientDomainConfig::Tls(tls_client.clone()));
     }
+
+    for wss_client in &config.wss_domains {
+        domains.push(ClientDomainConfig::Wss(wss_client.clone()));
+    }
+
+    for udp_client in &config.udp_domains {
+        domains.push(ClientDomainConfig::Udp(udp_client.clone()));
+    }
     domains
 }
 
@@ -270,15 +290,23 @@ async fn async_run_mux_client(opts: ConnectCommand) -> anyhow::Result<()> {
         log::warn!("{:#}", err);
     }
 
-    let domain = Mux::get()
-        .unwrap()
-        .get_domain_by_name(&opts.domain_name)
-        .ok_or_else(|| {
-            anyhow!(
-                "no multiplexer domain with name `{}` was found in the configuration",
-                opts.domain_name
-            )
-        })?;
+    let mux = Mux::get().unwrap();
+    if WssDomainClient::is_url(&opts.domain_name)
+        && mux.get_domain_by_name(&opts.domain_name).is_none()
+    {
+        // Allow connecting to an ad-hoc websocket domain by url
+        let domain: Arc<dyn Domain> = Arc::new(ClientDomain::new(ClientDomainConfig::Wss(
+            WssDomainClient::with_url(&opts.domain_name),
+        )));
+        mux.add_domain(&domain);
+    }
+
+    let domain = mux.get_domain_by_name(&opts.domain_name).ok_or_else(|| {
+        anyhow!(
+            "no multiplexer domain with name `{}` was found in the configuration",
+            opts.domain_name
+        )
+    })?;
 
     let opts = opts.clone();
     let cmd = if !opts.prog.is_empty() {
@@ -410,6 +438,36 @@ fn update_mux_domains(config: &ConfigHandle) -> anyhow::Result<()> {
         mux.add_domain(&domain);
     }
 
+    for k8s_dom in &config.kubernetes_domains {
+        if mux.get_domain_by_name(&k8s_dom.name).is_some() {
+            continue;
+        }
+
+        let domain: Arc<dyn Domain> = Arc::new(LocalDomain::new_kubernetes(k8s_dom.clone())?);
+        mux.add_domain(&domain);
+    }
+
+    for container_dom in &config.container_domains {
+        if mux.get_domain_by_name(&container_dom.name).is_some() {
+            continue;
+        }
+
+        let domain: Arc<dyn Domain> = Arc::new(LocalDomain::new_container(container_dom.clone())?);
+        mux.add_domain(&domain);
+    }
+
+    for tcp_dom in &config.tcp_domains {
+        if mux.get_domain_by_name(&tcp_dom.name).is_some() {
+            continue;
+        }
+
+        let domain: Arc<dyn Domain> = Arc::new(LocalDomain::with_pty_system(
+            &tcp_dom.name,
+            Box::new(mux::tcp::TcpTty::new(tcp_dom.clone())),
+        ));
+        mux.add_domain(&domain);
+    }
+
     if let Some(name) = &config.default_domain {
         if let Some(dom) = mux.get_domain_by_name(name) {
             mux.set_default_domain(&dom);
@@ -424,7 +482,9 @@ async fn connect_to_auto_connect_domains() -> anyhow::Result<()> {
     let domains = mux.iter_domains();
     for dom in domains {
         if let Some(dom) = dom.downcast_ref::<ClientDomain>() {
-            if dom.connect_automatically() {
+            // The default domain may already have been attached in
+            // order to spawn the initial tab
+            if dom.connect_automatically() && dom.state() == DomainState::Detached {
                 dom.attach(None).await?;
             }
         }
@@ -446,7 +506,21 @@ async fn async_run_terminal_gui(
     }
 
     if !opts.no_auto_connect {
-        connect_to_auto_connect_domains().await?;
+        // Connecting can take a while, and isn't needed to show
+        // the first window, so do it once that has been painted
+        startup::after_first_frame(|| {
+            promise::spawn::spawn(async {
+                if let Err(err) = connect_to_auto_connect_domains().await {
+                    log::error!("while connecting to domains: {:#}", err);
+                }
+            })
+            .detach();
+        });
+        promise::spawn::spawn(async {
+            smol::Timer::after(std::time::Duration::from_secs(5)).await;
+            startup::run_deferred();
+        })
+        .detach();
     }
 
     async fn trigger_gui_startup(
@@ -473,9 +547,12 @@ async fn async_run_terminal_gui(
         log::error!("{}", message);
         persistent_toast_notification("Error", &message);
     }
+    startup::mark("gui-startup event processed");
 
     let is_connecting = false;
-    spawn_tab_in_default_domain_if_mux_is_empty(cmd, is_connecting).await
+    spawn_tab_in_default_domain_if_mux_is_empty(cmd, is_connecting).await?;
+    startup::mark("initial tab spawned");
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -635,7 +712,7 @@ fn setup_mux(
     let mux = Rc::new(mux::Mux::new(Some(local_domain.clone())));
     Mux::set_mux(&mux);
     let client_id = Arc::new(mux::client::ClientId::new());
-    mux.register_client(client_id.clone());
+    mux.register_client(client_id.clone(), mux::client::ClientTransport::Local)?;
     mux.replace_identity(Some(client_id));
     mux.set_active_workspace(
         default_workspace_name.unwrap_or(
@@ -672,6 +749,9 @@ fn build_initial_mux(
 }
 
 fn run_terminal_gui(opts: StartCommand) -> anyhow::Result<()> {
+    if opts.profile_startup {
+        startup::enable_profiling();
+    }
     if let Some(cls) = opts.class.as_ref() {
         crate::set_window_class(cls);
     }
@@ -698,6 +778,7 @@ fn run_terminal_gui(opts: StartCommand) -> anyhow::Result<()> {
     };
 
     let mux = build_initial_mux(&config, None, opts.workspace.as_deref())?;
+    startup::mark("mux created");
 
     // First, let's see if we can ask an already running wezterm to do this.
     // We must do this before we start the gui frontend as the scheduler
@@ -713,6 +794,7 @@ fn run_terminal_gui(opts: StartCommand) -> anyhow::Result<()> {
     }
 
     let gui = crate::frontend::try_new()?;
+    startup::mark("gui frontend created");
     let activity = Activity::new();
 
     promise::spawn::spawn(async move {
@@ -756,6 +838,7 @@ fn terminate_with_error(err: anyhow::Error) -> ! {
 }
 
 fn main() {
+    startup::mark("process start");
     config::designate_this_as_the_main_thread();
     config::assign_error_callback(mux::connui::show_configuration_error_message);
     notify_on_panic();
@@ -1133,6 +1216,7 @@ fn run() -> anyhow::Result<()> {
         opts.skip_config,
     )?;
     let config = config::configuration();
+    startup::mark("configuration loaded");
 
     let sub = match opts.cmd.as_ref().cloned() {
         Some(sub) => sub,
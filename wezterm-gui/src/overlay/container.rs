@@ -0,0 +1,145 @@
+//! The container selector lists the running docker or podman containers
+//! and then opens a tab in a container domain for the chosen container.
+use crate::overlay::selector::{choose, show_error};
+use crate::termwindow::TermWindowNotif;
+use anyhow::Context;
+use config::keyassignment::{KeyAssignment, SpawnTabDomain};
+use config::ContainerDomain;
+use mux::domain::{Domain, LocalDomain};
+use mux::pane::PaneId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use std::sync::Arc;
+use termwiz::terminal::Terminal;
+
+struct RunningContainer {
+    name: String,
+    image: String,
+    status: String,
+}
+
+/// Parses the output of `docker ps --format '{{.Names}}\t{{.Image}}\t{{.Status}}'`
+fn parse_containers(output: &str) -> Vec<RunningContainer> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let name = fields.next()?.trim();
+            if name.is_empty() {
+                return None;
+            }
+            Some(RunningContainer {
+                name: name.to_string(),
+                image: fields.next().unwrap_or("").trim().to_string(),
+                status: fields.next().unwrap_or("").trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+fn running_containers() -> anyhow::Result<Vec<RunningContainer>> {
+    let engine = config::configuration().container_engine_path.clone();
+    let output = std::process::Command::new(&engine)
+        .args(["ps", "--format", "{{.Names}}\t{{.Image}}\t{{.Status}}"])
+        .output()
+        .with_context(|| format!("running {}", engine))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} ps failed: {}",
+            engine,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(parse_containers(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn select_container(term: &mut TermWizTerminal) -> anyhow::Result<Option<String>> {
+    let containers = running_containers()?;
+    if containers.is_empty() {
+        anyhow::bail!("There are no running containers");
+    }
+    let name_width = containers.iter().map(|c| c.name.len()).max().unwrap_or(0);
+    let image_width = containers.iter().map(|c| c.image.len()).max().unwrap_or(0);
+    let labels: Vec<String> = containers
+        .iter()
+        .map(|c| {
+            format!(
+                "{:name_width$}  {:image_width$}  {}",
+                c.name,
+                c.image,
+                c.status,
+                name_width = name_width,
+                image_width = image_width
+            )
+        })
+        .collect();
+
+    Ok(choose(term, "Container", &labels, None)?.and_then(|label| {
+        labels
+            .iter()
+            .position(|l| *l == label)
+            .map(|idx| containers[idx].name.clone())
+    }))
+}
+
+pub fn container_selector(
+    pane_id: PaneId,
+    mut term: TermWizTerminal,
+    window: ::window::Window,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    let container = match select_container(&mut term) {
+        Ok(Some(container)) => container,
+        Ok(None) => return Ok(()),
+        Err(err) => {
+            show_error(&mut term, &err)?;
+            return Ok(());
+        }
+    };
+
+    promise::spawn::spawn_into_main_thread(async move {
+        let mux = Mux::get().unwrap();
+        let name = format!("container:{}", container);
+        if mux.get_domain_by_name(&name).is_none() {
+            let dom = ContainerDomain {
+                name: name.clone(),
+                container,
+                engine: None,
+                user: None,
+                default_prog: None,
+            };
+            match LocalDomain::new_container(dom) {
+                Ok(domain) => {
+                    let domain: Arc<dyn Domain> = Arc::new(domain);
+                    mux.add_domain(&domain);
+                }
+                Err(err) => {
+                    log::error!("creating container domain {}: {:#}", name, err);
+                    return;
+                }
+            }
+        }
+        window.notify(TermWindowNotif::PerformAssignment {
+            pane_id,
+            assignment: KeyAssignment::SpawnTab(SpawnTabDomain::DomainName(name)),
+        });
+    })
+    .detach();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn containers() {
+        let containers =
+            parse_containers("web\tnginx:1.23\tUp 2 hours\n\ndb\tpostgres\tUp 5 days\n");
+        assert_eq!(containers.len(), 2);
+        assert_eq!(containers[0].name, "web");
+        assert_eq!(containers[0].image, "nginx:1.23");
+        assert_eq!(containers[1].status, "Up 5 days");
+    }
+}
@@ -1,4 +1,6 @@
+use crate::scripting::guiwin::GuiWin;
 use crate::selection::{SelectionCoordinate, SelectionRange, SelectionX};
+use crate::termwindow::redaction::redact;
 use crate::termwindow::{TermWindow, TermWindowNotif};
 use config::keyassignment::{
     ClipboardCopyDestination, CopyModeAssignment, KeyAssignment, KeyTable, KeyTableEntry,
@@ -11,6 +13,7 @@ use mux::pane::{
 };
 use mux::renderable::*;
 use mux::tab::TabId;
+use mux_lua::MuxPane;
 use rangeset::RangeSet;
 use std::cell::{RefCell, RefMut};
 use std::collections::HashMap;
@@ -18,7 +21,7 @@ use std::ops::Range;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use termwiz::cell::{Cell, CellAttributes};
+use termwiz::cell::{Cell, CellAttributes, Intensity};
 use termwiz::color::AnsiColor;
 use termwiz::surface::{CursorVisibility, SequenceNo, SEQ_ZERO};
 use unicode_segmentation::*;
@@ -65,6 +68,12 @@ struct CopyRenderable {
 
     /// The text that the user entered
     pattern: Pattern,
+    /// Whether the search respects case.  For string patterns this
+    /// mirrors the variant of `pattern`; it is tracked separately so
+    /// that it can also apply to regex patterns
+    case_sensitive: bool,
+    /// Whether matches must begin and end at word boundaries
+    whole_word: bool,
     /// The most recently queried set of matches
     results: Vec<SearchResult>,
     by_line: HashMap<StableRowIndex, Vec<MatchResult>>,
@@ -125,6 +134,16 @@ impl CopyOverlay {
             .clone()
             .ok_or_else(|| anyhow::anyhow!("failed to clone window handle"))?;
         let dims = pane.get_dimensions();
+        let pattern = if params.pattern.is_empty() {
+            SAVED_PATTERN
+                .lock()
+                .unwrap()
+                .get(&tab_id)
+                .map(|p| p.clone())
+                .unwrap_or(params.pattern)
+        } else {
+            params.pattern
+        };
         let mut render = CopyRenderable {
             cursor,
             window,
@@ -139,16 +158,9 @@ impl CopyOverlay {
             last_result_seqno: SEQ_ZERO,
             last_bar_pos: None,
             tab_id,
-            pattern: if params.pattern.is_empty() {
-                SAVED_PATTERN
-                    .lock()
-                    .unwrap()
-                    .get(&tab_id)
-                    .map(|p| p.clone())
-                    .unwrap_or(params.pattern)
-            } else {
-                params.pattern
-            },
+            case_sensitive: !matches!(pattern, Pattern::CaseInSensitiveString(_)),
+            whole_word: false,
+            pattern,
             editing_search: params.editing_search,
             result_pos: None,
             selection_mode: SelectionMode::Cell,
@@ -180,6 +192,7 @@ impl CopyOverlay {
         let mut render = self.render.borrow_mut();
         render.editing_search = params.editing_search;
         if render.pattern != params.pattern {
+            render.case_sensitive = !matches!(params.pattern, Pattern::CaseInSensitiveString(_));
             render.pattern = params.pattern;
             render.schedule_update_search();
         }
@@ -306,7 +319,7 @@ impl CopyRenderable {
         if !self.pattern.is_empty() {
             let pane: Rc<dyn Pane> = self.delegate.clone();
             let window = self.window.clone();
-            let pattern = self.pattern.clone();
+            let pattern = self.search_pattern();
             let dims = pane.get_dimensions();
 
             let end = dims.scrollback_top + dims.scrollback_rows as StableRowIndex;
@@ -352,7 +365,7 @@ impl CopyRenderable {
         range: Range<StableRowIndex>,
     ) {
         self.window.invalidate();
-        if pattern != self.pattern {
+        if pattern != self.search_pattern() {
             return;
         }
         let is_first = self.results.is_empty();
@@ -407,6 +420,136 @@ impl CopyRenderable {
         .detach();
     }
 
+    /// Returns the pattern that is searched for, which is the text
+    /// that the user entered adjusted for the case sensitivity and
+    /// whole word options
+    fn search_pattern(&self) -> Pattern {
+        let is_regex = matches!(self.pattern, Pattern::Regex(_));
+        if !self.whole_word && (!is_regex || self.case_sensitive) {
+            return self.pattern.clone();
+        }
+
+        let text = if is_regex {
+            self.pattern.to_string()
+        } else {
+            regex::escape(&self.pattern)
+        };
+        let mut regex = String::new();
+        if !self.case_sensitive {
+            regex.push_str("(?i)");
+        }
+        if self.whole_word {
+            regex.push_str(&format!(r"\b(?:{})\b", text));
+        } else {
+            regex.push_str(&text);
+        }
+        Pattern::Regex(regex)
+    }
+
+    /// Renders the search bar into `line`: the pattern, the match
+    /// count, and indicators for the case sensitivity, regex and
+    /// whole word options, which are highlighted when enabled
+    fn render_search_bar(&self, line: &mut Line, cols: usize) {
+        let rev = CellAttributes::default().set_reverse(true).clone();
+        line.fill_range(0..cols, &Cell::new(' ', rev.clone()), SEQ_ZERO);
+
+        let remain = match &self.searching {
+            Some(Searching { remain, .. }) => format!(", searching {remain} lines"),
+            None => String::new(),
+        };
+        line.overlay_text_with_attribute(
+            0,
+            &format!(
+                "Search: {} ({} of {} matches{remain})",
+                *self.pattern,
+                self.result_pos.map(|x| x + 1).unwrap_or(0),
+                self.results.len(),
+            ),
+            rev,
+            SEQ_ZERO,
+        );
+
+        let options = vec![
+            ("Aa", self.case_sensitive),
+            (".*", matches!(self.pattern, Pattern::Regex(_))),
+            ("\\b", self.whole_word),
+        ];
+        let width: usize = options.iter().map(|(label, _)| label.len() + 2).sum();
+        let mut x = cols.saturating_sub(width);
+        for (label, enabled) in options {
+            let mut attr = CellAttributes::default();
+            if enabled {
+                attr.set_intensity(Intensity::Bold);
+            } else {
+                attr.set_reverse(true).set_intensity(Intensity::Half);
+            }
+            line.overlay_text_with_attribute(x, &format!(" {label} "), attr, SEQ_ZERO);
+            x += label.len() + 2;
+        }
+    }
+
+    /// Returns the text of each match, ordered from the top of the
+    /// scrollback to the bottom
+    fn match_texts(&self) -> Vec<String> {
+        self.results
+            .iter()
+            .rev()
+            .map(|res| {
+                let (_top, lines) = self.delegate.get_lines(res.start_y..res.end_y + 1);
+                let mut text = String::new();
+                for (idx, line) in lines.iter().enumerate() {
+                    let y = res.start_y + idx as StableRowIndex;
+                    let start = if y == res.start_y { res.start_x } else { 0 };
+                    let end = if y == res.end_y {
+                        res.end_x
+                    } else {
+                        line.len()
+                    };
+                    text.push_str(
+                        &redact(self.delegate.pane_id(), line).columns_as_str(start..end),
+                    );
+                }
+                text
+            })
+            .collect()
+    }
+
+    fn copy_all_matches(&self, dest: ClipboardCopyDestination) {
+        let text = self.match_texts().join("\n");
+        if text.is_empty() {
+            return;
+        }
+        self.window
+            .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                term_window.copy_to_clipboard(dest, text);
+            })));
+    }
+
+    /// Emits the `name` event, passing the window, the pane and
+    /// the text of each of the matches to its handlers
+    fn emit_matches(&self, name: String) {
+        let matches = self.match_texts();
+        let pane = MuxPane(self.delegate.pane_id());
+        self.window
+            .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                let window = GuiWin::new(term_window);
+                promise::spawn::spawn(config::with_lua_config_on_main_thread(
+                    move |lua| async move {
+                        if let Some(lua) = lua {
+                            let args = lua.pack_multi((window, pane, matches))?;
+                            if let Err(err) =
+                                config::lua::emit_event(&lua, (name.clone(), args)).await
+                            {
+                                log::error!("while processing {} event: {:#}", name, err);
+                            }
+                        }
+                        anyhow::Result::<()>::Ok(())
+                    },
+                ))
+                .detach();
+            })));
+    }
+
     fn clear_selection(&mut self) {
         let pane_id = self.delegate.pane_id();
         self.window
@@ -418,6 +561,7 @@ impl CopyRenderable {
     }
 
     fn activate_match_number(&mut self, n: usize) {
+        self.record_jump();
         self.result_pos.replace(n);
         let result = self.results[n].clone();
         self.cursor.y = result.end_y;
@@ -558,7 +702,20 @@ impl CopyRenderable {
             })));
     }
 
+    /// Records the current scroll position in the jumplist of the pane
+    fn record_jump(&self) {
+        let pane_id = self.delegate.pane_id();
+        self.window
+            .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                term_window.record_jump(pane_id);
+            })));
+    }
+
     fn close(&self) {
+        if self.viewport.is_some() {
+            // Allow returning to where the search left the viewport
+            self.record_jump();
+        }
         self.set_viewport(None);
         TermWindow::schedule_cancel_overlay_for_pane(self.window.clone(), self.delegate.pane_id());
     }
@@ -653,10 +810,37 @@ impl CopyRenderable {
             Pattern::CaseInSensitiveString(s) => Pattern::Regex(s.clone()),
             Pattern::Regex(s) => Pattern::CaseSensitiveString(s.clone()),
         };
+        self.case_sensitive = !matches!(pattern, Pattern::CaseInSensitiveString(_));
         self.pattern = pattern;
         self.schedule_update_search();
     }
 
+    fn toggle_case_sensitivity(&mut self) {
+        self.case_sensitive = !self.case_sensitive;
+        let pattern = match &self.pattern {
+            Pattern::Regex(s) => Pattern::Regex(s.clone()),
+            _ if self.case_sensitive => Pattern::CaseSensitiveString(self.pattern.to_string()),
+            _ => Pattern::CaseInSensitiveString(self.pattern.to_string()),
+        };
+        self.pattern = pattern;
+        self.schedule_update_search();
+    }
+
+    fn toggle_regex(&mut self) {
+        let pattern = match &self.pattern {
+            Pattern::Regex(s) if self.case_sensitive => Pattern::CaseSensitiveString(s.clone()),
+            Pattern::Regex(s) => Pattern::CaseInSensitiveString(s.clone()),
+            _ => Pattern::Regex(self.pattern.to_string()),
+        };
+        self.pattern = pattern;
+        self.schedule_update_search();
+    }
+
+    fn toggle_whole_word(&mut self) {
+        self.whole_word = !self.whole_word;
+        self.schedule_update_search();
+    }
+
     fn move_to_viewport_middle(&mut self) {
         let dims = self.dimensions();
         self.cursor.y = dims.top + (dims.dims.viewport_rows as isize) / 2;
@@ -1131,6 +1315,11 @@ impl Pane for CopyOverlay {
                     PriorMatchPage => render.prior_match_page(),
                     NextMatchPage => render.next_match_page(),
                     CycleMatchType => render.cycle_match_type(),
+                    ToggleCaseSensitivity => render.toggle_case_sensitivity(),
+                    ToggleRegex => render.toggle_regex(),
+                    ToggleWholeWord => render.toggle_whole_word(),
+                    CopyAllMatches(dest) => render.copy_all_matches(*dest),
+                    EmitMatches(name) => render.emit_matches(name.clone()),
                     ClearPattern => render.clear_pattern(),
                     EditPattern => render.edit_pattern(),
                     AcceptPattern => render.accept_pattern(),
@@ -1276,33 +1465,7 @@ impl Pane for CopyOverlay {
                         && (self.renderer.editing_search || !self.renderer.pattern.is_empty())
                     {
                         // Replace with search UI
-                        let rev = CellAttributes::default().set_reverse(true).clone();
-                        line.fill_range(0..self.dims.cols, &Cell::new(' ', rev.clone()), SEQ_ZERO);
-                        let mode = &match self.renderer.pattern {
-                            Pattern::CaseSensitiveString(_) => "case-sensitive",
-                            Pattern::CaseInSensitiveString(_) => "ignore-case",
-                            Pattern::Regex(_) => "regex",
-                        };
-
-                        let remain = match &self.renderer.searching {
-                            Some(Searching { remain, .. }) => {
-                                format!(" searching {remain} lines")
-                            }
-                            None => String::new(),
-                        };
-
-                        line.overlay_text_with_attribute(
-                            0,
-                            &format!(
-                                "Search: {} ({}/{} matches. {}{remain})",
-                                *self.renderer.pattern,
-                                self.renderer.result_pos.map(|x| x + 1).unwrap_or(0),
-                                self.renderer.results.len(),
-                                mode
-                            ),
-                            rev,
-                            SEQ_ZERO,
-                        );
+                        self.renderer.render_search_bar(&mut line, self.dims.cols);
                         self.renderer.last_bar_pos = Some(self.search_row);
                         line.clear_appdata();
                     } else if let Some(matches) = self.renderer.by_line.get(&stable_idx) {
@@ -1377,25 +1540,7 @@ impl Pane for CopyOverlay {
             if stable_idx == search_row && (renderer.editing_search || !renderer.pattern.is_empty())
             {
                 // Replace with search UI
-                let rev = CellAttributes::default().set_reverse(true).clone();
-                line.fill_range(0..dims.cols, &Cell::new(' ', rev.clone()), SEQ_ZERO);
-                let mode = &match renderer.pattern {
-                    Pattern::CaseSensitiveString(_) => "case-sensitive",
-                    Pattern::CaseInSensitiveString(_) => "ignore-case",
-                    Pattern::Regex(_) => "regex",
-                };
-                line.overlay_text_with_attribute(
-                    0,
-                    &format!(
-                        "Search: {} ({}/{} matches. {})",
-                        *renderer.pattern,
-                        renderer.result_pos.map(|x| x + 1).unwrap_or(0),
-                        renderer.results.len(),
-                        mode
-                    ),
-                    rev,
-                    SEQ_ZERO,
-                );
+                renderer.render_search_bar(line, dims.cols);
                 renderer.last_bar_pos = Some(search_row);
             } else if let Some(matches) = renderer.by_line.get(&stable_idx) {
                 for m in matches {
@@ -1505,6 +1650,21 @@ pub fn search_key_table() -> KeyTable {
             Modifiers::CTRL,
             KeyAssignment::CopyMode(CopyModeAssignment::ClearPattern),
         ),
+        (
+            WKeyCode::Char('c'),
+            Modifiers::ALT,
+            KeyAssignment::CopyMode(CopyModeAssignment::ToggleCaseSensitivity),
+        ),
+        (
+            WKeyCode::Char('r'),
+            Modifiers::ALT,
+            KeyAssignment::CopyMode(CopyModeAssignment::ToggleRegex),
+        ),
+        (
+            WKeyCode::Char('w'),
+            Modifiers::ALT,
+            KeyAssignment::CopyMode(CopyModeAssignment::ToggleWholeWord),
+        ),
     ] {
         table.insert((key, mods), KeyTableEntry { action });
     }
@@ -0,0 +1,71 @@
+//! The `ShowCopyMenu` overlay, which offers a choice of adjustments
+//! to make to the selected text before copying it to the clipboard.
+use crate::overlay::selector::choose;
+use crate::termwindow::TermWindowNotif;
+use config::keyassignment::{CopyLineBreaks, CopyOptions, KeyAssignment};
+use mux::pane::PaneId;
+use mux::termwiztermtab::TermWizTerminal;
+use termwiz::terminal::Terminal;
+
+fn presets() -> Vec<(&'static str, CopyOptions)> {
+    vec![
+        ("Copy", CopyOptions::default()),
+        (
+            "Copy without trailing whitespace",
+            CopyOptions {
+                strip_trailing_whitespace: true,
+                ..Default::default()
+            },
+        ),
+        (
+            "Copy lines as displayed",
+            CopyOptions {
+                line_breaks: CopyLineBreaks::AsDisplayed,
+                ..Default::default()
+            },
+        ),
+        (
+            "Copy with lines joined into paragraphs",
+            CopyOptions {
+                line_breaks: CopyLineBreaks::JoinParagraphs,
+                ..Default::default()
+            },
+        ),
+        (
+            "Copy table without borders",
+            CopyOptions {
+                collapse_table_borders: true,
+                strip_trailing_whitespace: true,
+                ..Default::default()
+            },
+        ),
+        (
+            "Copy without prompts",
+            CopyOptions {
+                strip_prompts: true,
+                ..Default::default()
+            },
+        ),
+    ]
+}
+
+pub fn copy_menu(
+    pane_id: PaneId,
+    mut term: TermWizTerminal,
+    window: ::window::Window,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    let presets = presets();
+    let labels: Vec<String> = presets.iter().map(|(label, _)| label.to_string()).collect();
+
+    if let Some(label) = choose(&mut term, "Copy", &labels, None)? {
+        if let Some((_, options)) = presets.into_iter().find(|(l, _)| *l == label) {
+            window.notify(TermWindowNotif::PerformAssignment {
+                pane_id,
+                assignment: KeyAssignment::CopyWithOptions(options),
+            });
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,228 @@
+//! The `dead_pane_inspector` overlay, which is shown over a pane
+//! whose process exited unsuccessfully so that its final output
+//! can be read before deciding whether to restart it, copy its
+//! output or close it.
+use crate::termwindow::TermWindowNotif;
+use crate::TermWindow;
+use config::keyassignment::ClipboardCopyDestination;
+use mux::pane::{Pane, PaneId};
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use std::rc::Rc;
+use termwiz::cell::{unicode_column_width, AttributeChange, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+
+/// Returns up to the last `count` lines of output from the pane,
+/// ignoring the blank lines below the final output
+pub fn pane_output_lines(pane: &Rc<dyn Pane>, count: usize) -> Vec<String> {
+    let dims = pane.get_dimensions();
+    let end = dims.physical_top + dims.viewport_rows as isize;
+    let (_first, lines) = pane.get_lines(dims.scrollback_top..end);
+    let mut lines: Vec<String> = lines
+        .iter()
+        .map(|line| line.as_str().trim_end().to_string())
+        .collect();
+    while lines.last().map(|line| line.is_empty()).unwrap_or(false) {
+        lines.pop();
+    }
+    let start = lines.len().saturating_sub(count);
+    lines.split_off(start)
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Button {
+    Restart,
+    Copy,
+    Close,
+}
+
+const BUTTONS: &[(Button, &str)] = &[
+    (Button::Restart, " [R]estart "),
+    (Button::Copy, " [C]opy output "),
+    (Button::Close, " [X] Close "),
+];
+const BUTTON_SPACING: usize = 4;
+
+fn button_at(x: usize) -> Option<Button> {
+    let mut left = 0;
+    for (button, label) in BUTTONS {
+        let width = unicode_column_width(label, None);
+        if x >= left && x < left + width {
+            return Some(*button);
+        }
+        left += width + BUTTON_SPACING;
+    }
+    None
+}
+
+fn run_inspector(
+    status: &str,
+    lines: &[String],
+    term: &mut TermWizTerminal,
+    on_copy: &dyn Fn(),
+) -> anyhow::Result<Option<Button>> {
+    term.set_raw_mode()?;
+    let size = term.get_screen_size()?;
+
+    // Header, blank line, output, blank line, buttons
+    let max_lines = size.rows.saturating_sub(4);
+    let lines = &lines[lines.len().saturating_sub(max_lines)..];
+    let button_row = lines.len() + 3;
+    let mut active = None;
+    let mut copied = false;
+
+    let render =
+        |term: &mut TermWizTerminal, active: Option<Button>, copied: bool| -> termwiz::Result<()> {
+            let mut changes = vec![
+                Change::ClearScreen(ColorAttribute::Default),
+                Change::CursorVisibility(CursorVisibility::Hidden),
+                AttributeChange::Intensity(Intensity::Bold).into(),
+                format!("⚠️  Process exited: {}", status).into(),
+                AttributeChange::Intensity(Intensity::Normal).into(),
+            ];
+
+            for (y, line) in lines.iter().enumerate() {
+                changes.push(Change::CursorPosition {
+                    x: Position::Absolute(0),
+                    y: Position::Absolute(y + 2),
+                });
+                changes.push(Change::Text(line.to_string()));
+            }
+
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(button_row),
+            });
+            for (idx, (button, label)) in BUTTONS.iter().enumerate() {
+                if idx > 0 {
+                    changes.push(" ".repeat(BUTTON_SPACING).into());
+                }
+                if active == Some(*button) {
+                    changes.push(AttributeChange::Reverse(true).into());
+                }
+                changes.push((*label).into());
+                if active == Some(*button) {
+                    changes.push(AttributeChange::Reverse(false).into());
+                }
+            }
+            changes.push(
+                if copied {
+                    "    Copied!  Esc to dismiss"
+                } else {
+                    "    Esc to dismiss"
+                }
+                .into(),
+            );
+
+            term.render(&changes)?;
+            term.flush()
+        };
+
+    render(term, active, copied)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        let chosen = match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('r' | 'R'),
+                ..
+            }) => Some(Button::Restart),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('c' | 'C'),
+                ..
+            }) => Some(Button::Copy),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('x' | 'X'),
+                ..
+            }) => Some(Button::Close),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => return Ok(None),
+            InputEvent::Mouse(MouseEvent {
+                x,
+                y,
+                mouse_buttons,
+                ..
+            }) => {
+                active = if y as usize == button_row {
+                    button_at(x as usize)
+                } else {
+                    None
+                };
+                if mouse_buttons == MouseButtons::LEFT {
+                    active
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        match chosen {
+            Some(Button::Copy) => {
+                on_copy();
+                copied = true;
+            }
+            Some(button) => return Ok(Some(button)),
+            None => {}
+        }
+
+        render(term, active, copied)?;
+    }
+
+    Ok(None)
+}
+
+pub fn inspect_dead_pane(
+    pane_id: PaneId,
+    mut term: TermWizTerminal,
+    status: String,
+    lines: Vec<String>,
+    window: ::window::Window,
+) -> anyhow::Result<()> {
+    let copy_window = window.clone();
+    let on_copy = move || {
+        copy_window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+            let mux = Mux::get().unwrap();
+            if let Some(pane) = mux.get_pane(pane_id) {
+                let text = pane_output_lines(&pane, usize::MAX).join("\n");
+                term_window.copy_to_clipboard(
+                    ClipboardCopyDestination::ClipboardAndPrimarySelection,
+                    text,
+                );
+            }
+        })));
+    };
+
+    match run_inspector(&status, &lines, &mut term, &on_copy)? {
+        Some(Button::Restart) => {
+            promise::spawn::spawn_into_main_thread(async move {
+                let mux = Mux::get().unwrap();
+                if let Some(pane) = mux.get_pane(pane_id) {
+                    if let Err(err) = pane.restart() {
+                        log::error!("Failed to restart pane {}: {:#}", pane_id, err);
+                    }
+                }
+            })
+            .detach();
+        }
+        Some(Button::Close) => {
+            promise::spawn::spawn_into_main_thread(async move {
+                let mux = Mux::get().unwrap();
+                if let Some((_domain_id, _window_id, tab_id)) = mux.resolve_pane_id(pane_id) {
+                    if let Some(tab) = mux.get_tab(tab_id) {
+                        tab.kill_pane(pane_id);
+                    }
+                }
+            })
+            .detach();
+        }
+        Some(Button::Copy) | None => {}
+    }
+    TermWindow::schedule_cancel_overlay_for_pane(window, pane_id);
+
+    Ok(())
+}
@@ -0,0 +1,455 @@
+//! Previews a file that was clicked via a `file` hyperlink, as
+//! configured by `file_preview_extensions`
+use config::FilePreview;
+use mux::pane::PaneId;
+use mux::termwiztermtab::TermWizTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use termwiz::cell::{unicode_column_width, AttributeChange, Intensity};
+use termwiz::color::{AnsiColor, ColorAttribute};
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
+use termwiz::surface::change::{Image, ImageData};
+use termwiz::surface::{Change, CursorVisibility, Position, TextureCoordinate};
+use termwiz::terminal::{ScreenSize, Terminal};
+
+/// Files larger than this are truncated when shown as text
+const MAX_TEXT_BYTES: u64 = 1024 * 1024;
+const TAB_WIDTH: usize = 4;
+
+const KEYWORDS: &[&str] = &[
+    "as",
+    "async",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "def",
+    "default",
+    "do",
+    "elif",
+    "else",
+    "enum",
+    "except",
+    "export",
+    "extern",
+    "false",
+    "finally",
+    "fn",
+    "for",
+    "from",
+    "func",
+    "function",
+    "if",
+    "impl",
+    "import",
+    "in",
+    "interface",
+    "let",
+    "local",
+    "loop",
+    "match",
+    "mod",
+    "mut",
+    "new",
+    "nil",
+    "none",
+    "None",
+    "null",
+    "package",
+    "pub",
+    "return",
+    "self",
+    "static",
+    "struct",
+    "switch",
+    "then",
+    "this",
+    "throw",
+    "trait",
+    "true",
+    "True",
+    "False",
+    "try",
+    "type",
+    "typedef",
+    "use",
+    "var",
+    "while",
+    "yield",
+];
+
+/// The flavor of comments recognized by the highlighter
+fn line_comment(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "py" | "sh" | "bash" | "zsh" | "fish" | "rb" | "pl" | "toml" | "yaml" | "yml" | "conf"
+        | "ini" | "nix" | "r" | "mk" => "#",
+        "lua" | "sql" | "hs" => "--",
+        "el" | "lisp" | "clj" | "scm" | "asm" | "s" => ";",
+        "vim" => "\"",
+        "tex" | "erl" => "%",
+        _ => "//",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Plain,
+    Comment,
+    String,
+    Number,
+    Keyword,
+}
+
+impl Token {
+    fn attributes(self) -> Vec<Change> {
+        let (color, intensity) = match self {
+            Self::Plain => (ColorAttribute::Default, Intensity::Normal),
+            Self::Comment => (AnsiColor::Grey.into(), Intensity::Normal),
+            Self::String => (AnsiColor::Green.into(), Intensity::Normal),
+            Self::Number => (AnsiColor::Purple.into(), Intensity::Normal),
+            Self::Keyword => (AnsiColor::Navy.into(), Intensity::Bold),
+        };
+        vec![
+            AttributeChange::Foreground(color).into(),
+            AttributeChange::Intensity(intensity).into(),
+        ]
+    }
+}
+
+/// Splits a line into runs of highlighted text.  This is a simple
+/// lexical highlighter that knows about line comments, quoted strings,
+/// numbers and a set of keywords common to many languages.
+fn highlight_line(line: &str, comment: &str) -> Vec<(Token, String)> {
+    let mut runs: Vec<(Token, String)> = vec![];
+    let mut push = |token: Token, text: &str| match runs.last_mut() {
+        Some((last, s)) if *last == token => s.push_str(text),
+        _ => runs.push((token, text.to_string())),
+    };
+
+    let mut idx = 0;
+    while idx < line.len() {
+        let rest = &line[idx..];
+        let c = rest.chars().next().unwrap();
+
+        if rest.starts_with(comment) {
+            push(Token::Comment, rest);
+            break;
+        }
+
+        if c == '"' || c == '\'' || c == '`' {
+            let mut end = rest.len();
+            let mut escaped = false;
+            for (i, ch) in rest.char_indices().skip(1) {
+                if escaped {
+                    escaped = false;
+                } else if ch == '\\' {
+                    escaped = true;
+                } else if ch == c {
+                    end = i + ch.len_utf8();
+                    break;
+                }
+            }
+            push(Token::String, &rest[..end]);
+            idx += end;
+            continue;
+        }
+
+        if c.is_alphanumeric() || c == '_' {
+            let is_number = c.is_ascii_digit();
+            let end = rest
+                .char_indices()
+                .find(|(_, ch)| !(ch.is_alphanumeric() || *ch == '_' || (is_number && *ch == '.')))
+                .map(|(i, _)| i)
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            let token = if is_number {
+                Token::Number
+            } else if KEYWORDS.contains(&word) {
+                Token::Keyword
+            } else {
+                Token::Plain
+            };
+            push(token, word);
+            idx += end;
+            continue;
+        }
+
+        push(Token::Plain, &rest[..c.len_utf8()]);
+        idx += c.len_utf8();
+    }
+
+    runs
+}
+
+/// Truncates the runs so that they fit in `width` columns
+fn clip_runs(runs: Vec<(Token, String)>, width: usize) -> Vec<(Token, String)> {
+    let mut result = vec![];
+    let mut used = 0;
+    for (token, text) in runs {
+        let mut clipped = String::new();
+        for c in text.chars() {
+            let mut buf = [0u8; 4];
+            let w = unicode_column_width(c.encode_utf8(&mut buf), None);
+            if used + w > width {
+                result.push((token, clipped));
+                return result;
+            }
+            used += w;
+            clipped.push(c);
+        }
+        result.push((token, clipped));
+    }
+    result
+}
+
+fn header(path: &Path, cols: usize, hint: &str) -> Vec<Change> {
+    let title = format!(" {}  ({}) ", path.display(), hint);
+    let title: String = clip_runs(vec![(Token::Plain, title)], cols)
+        .into_iter()
+        .map(|(_, s)| s)
+        .collect();
+    let pad = cols.saturating_sub(unicode_column_width(&title, None));
+    vec![
+        Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(0),
+        },
+        AttributeChange::Reverse(true).into(),
+        Change::Text(format!("{}{}", title, " ".repeat(pad))),
+        AttributeChange::Reverse(false).into(),
+    ]
+}
+
+enum Action {
+    Close,
+    OpenWithSystem,
+}
+
+fn run_text_preview(path: &Path, term: &mut TermWizTerminal) -> anyhow::Result<Action> {
+    use std::io::Read;
+
+    let mut data = vec![];
+    std::fs::File::open(path)?
+        .take(MAX_TEXT_BYTES)
+        .read_to_end(&mut data)?;
+    let text = String::from_utf8_lossy(&data);
+    let comment = line_comment(path);
+    let lines: Vec<Vec<(Token, String)>> = text
+        .lines()
+        .map(|line| highlight_line(&line.replace('\t', &" ".repeat(TAB_WIDTH)), comment))
+        .collect();
+
+    let mut top_row = 0;
+
+    let render = |term: &mut TermWizTerminal, top_row: usize| -> anyhow::Result<()> {
+        let size = term.get_screen_size()?;
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorVisibility(CursorVisibility::Hidden),
+        ];
+        changes.append(&mut header(
+            path,
+            size.cols,
+            "q: close, o: open with system handler",
+        ));
+
+        for (y, line) in lines
+            .iter()
+            .skip(top_row)
+            .take(size.rows.saturating_sub(1))
+            .enumerate()
+        {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(y + 1),
+            });
+            for (token, text) in clip_runs(line.clone(), size.cols) {
+                changes.append(&mut token.attributes());
+                changes.push(Change::Text(text));
+            }
+            changes.append(&mut Token::Plain.attributes());
+        }
+
+        term.render(&changes)?;
+        term.flush()?;
+        Ok(())
+    };
+
+    render(term, top_row)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        let page = term.get_screen_size()?.rows.saturating_sub(1).max(1);
+        let max_top = lines.len().saturating_sub(page);
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('q') | KeyCode::Escape,
+                ..
+            }) => return Ok(Action::Close),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('o'),
+                ..
+            }) => return Ok(Action::OpenWithSystem),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow | KeyCode::Char('k'),
+                ..
+            }) => top_row = top_row.saturating_sub(1),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow | KeyCode::Char('j'),
+                ..
+            }) => top_row = (top_row + 1).min(max_top),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageUp,
+                ..
+            }) => top_row = top_row.saturating_sub(page),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageDown | KeyCode::Char(' '),
+                ..
+            }) => top_row = (top_row + page).min(max_top),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Home | KeyCode::Char('g'),
+                ..
+            }) => top_row = 0,
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::End | KeyCode::Char('G'),
+                ..
+            }) => top_row = max_top,
+            InputEvent::Mouse(MouseEvent { mouse_buttons, .. })
+                if mouse_buttons.contains(MouseButtons::VERT_WHEEL) =>
+            {
+                if mouse_buttons.contains(MouseButtons::WHEEL_POSITIVE) {
+                    top_row = top_row.saturating_sub(1);
+                } else {
+                    top_row = (top_row + 1).min(max_top);
+                }
+            }
+            InputEvent::Resized { .. } => {}
+            _ => continue,
+        }
+        render(term, top_row)?;
+    }
+
+    Ok(Action::Close)
+}
+
+/// Computes the size, in cells, of an image scaled to fit in the
+/// available area while preserving its aspect ratio
+fn fit_image(size: &ScreenSize, avail_rows: usize, width: u32, height: u32) -> (usize, usize) {
+    // Assume cells are twice as tall as they are wide if the
+    // terminal didn't tell us their size
+    let (cell_w, cell_h) = if size.xpixel == 0 || size.ypixel == 0 {
+        (1.0, 2.0)
+    } else {
+        (size.xpixel as f32, size.ypixel as f32)
+    };
+    let avail_w = size.cols as f32 * cell_w;
+    let avail_h = avail_rows as f32 * cell_h;
+    let scale = (avail_w / width as f32)
+        .min(avail_h / height as f32)
+        .min(1.0);
+    let cols = ((width as f32 * scale) / cell_w).round().max(1.) as usize;
+    let rows = ((height as f32 * scale) / cell_h).round().max(1.) as usize;
+    (cols.min(size.cols), rows.min(avail_rows.max(1)))
+}
+
+fn run_image_preview(path: &Path, term: &mut TermWizTerminal) -> anyhow::Result<Action> {
+    let data = std::fs::read(path)?;
+    let (width, height) = image::io::Reader::new(std::io::Cursor::new(&data))
+        .with_guessed_format()?
+        .into_dimensions()?;
+    let image = Arc::new(ImageData::with_raw_data(data));
+
+    let render = |term: &mut TermWizTerminal| -> anyhow::Result<()> {
+        let size = term.get_screen_size()?;
+        let avail_rows = size.rows.saturating_sub(1);
+        let (cols, rows) = fit_image(&size, avail_rows, width, height);
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorVisibility(CursorVisibility::Hidden),
+        ];
+        changes.append(&mut header(
+            path,
+            size.cols,
+            &format!(
+                "{}x{}, q: close, o: open with system handler",
+                width, height
+            ),
+        ));
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute((size.cols - cols) / 2),
+            y: Position::Absolute(1 + (avail_rows.saturating_sub(rows)) / 2),
+        });
+        changes.push(Change::Image(Image {
+            width: cols,
+            height: rows,
+            top_left: TextureCoordinate::new_f32(0., 0.),
+            bottom_right: TextureCoordinate::new_f32(1., 1.),
+            image: Arc::clone(&image),
+        }));
+
+        term.render(&changes)?;
+        term.flush()?;
+        Ok(())
+    };
+
+    render(term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('q') | KeyCode::Escape,
+                ..
+            }) => return Ok(Action::Close),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('o'),
+                ..
+            }) => return Ok(Action::OpenWithSystem),
+            InputEvent::Resized { .. } => render(term)?,
+            _ => {}
+        }
+    }
+
+    Ok(Action::Close)
+}
+
+pub fn file_preview(
+    _pane_id: PaneId,
+    mut term: TermWizTerminal,
+    path: PathBuf,
+    preview: FilePreview,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+
+    let result = match preview {
+        FilePreview::Text => run_text_preview(&path, &mut term),
+        FilePreview::Image => run_image_preview(&path, &mut term),
+    };
+
+    match result {
+        Ok(Action::Close) => {}
+        Ok(Action::OpenWithSystem) => {
+            if let Err(err) = open::that(&path) {
+                log::error!("Error opening {}: {:#}", path.display(), err);
+            }
+        }
+        Err(err) => {
+            log::error!("Unable to preview {}: {:#}", path.display(), err);
+            // Fall back to the system handler so that the click
+            // still does something useful
+            if let Err(err) = open::that(&path) {
+                log::error!("Error opening {}: {:#}", path.display(), err);
+            }
+        }
+    }
+
+    Ok(())
+}
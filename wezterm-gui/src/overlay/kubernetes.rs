@@ -0,0 +1,183 @@
+//! The kubernetes pod selector walks through the contexts, namespaces,
+//! pods and containers reported by kubectl, and then opens a tab in a
+//! kubernetes domain for the chosen container.
+use crate::overlay::selector::{choose, show_error};
+use crate::termwindow::TermWindowNotif;
+use anyhow::Context;
+use config::keyassignment::{KeyAssignment, SpawnTabDomain};
+use config::KubernetesDomain;
+use mux::domain::{Domain, LocalDomain};
+use mux::pane::PaneId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use std::sync::Arc;
+use termwiz::terminal::Terminal;
+
+/// Runs kubectl with the specified arguments, returning its output
+fn kubectl(args: &[&str]) -> anyhow::Result<String> {
+    let kubectl = config::configuration().kubectl_path.clone();
+    let output = std::process::Command::new(&kubectl)
+        .args(args)
+        .output()
+        .with_context(|| format!("running {}", kubectl))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} {} failed: {}",
+            kubectl,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Parses the output of `kubectl get -o name`, which has lines
+/// like `pod/web-0`, into a list of names
+fn parse_names(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.split_once('/') {
+            Some((_kind, name)) => name.to_string(),
+            None => line.to_string(),
+        })
+        .collect()
+}
+
+fn select_container(term: &mut TermWizTerminal) -> anyhow::Result<Option<KubernetesDomain>> {
+    // Context names may legitimately contain slashes, so they
+    // are not passed through parse_names
+    let contexts: Vec<String> = kubectl(&["config", "get-contexts", "-o", "name"])?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+    let current = kubectl(&["config", "current-context"]).unwrap_or_default();
+    let context = match choose(term, "Context", &contexts, Some(current.trim()))? {
+        Some(context) => context,
+        None => return Ok(None),
+    };
+
+    let namespaces = parse_names(&kubectl(&[
+        "--context",
+        &context,
+        "get",
+        "namespaces",
+        "-o",
+        "name",
+    ])?);
+    let namespace = match choose(term, "Namespace", &namespaces, Some("default"))? {
+        Some(namespace) => namespace,
+        None => return Ok(None),
+    };
+
+    let pods = parse_names(&kubectl(&[
+        "--context",
+        &context,
+        "--namespace",
+        &namespace,
+        "get",
+        "pods",
+        "--field-selector=status.phase=Running",
+        "-o",
+        "name",
+    ])?);
+    let pod = match choose(term, "Pod", &pods, None)? {
+        Some(pod) => pod,
+        None => return Ok(None),
+    };
+
+    let containers: Vec<String> = kubectl(&[
+        "--context",
+        &context,
+        "--namespace",
+        &namespace,
+        "get",
+        "pod",
+        &pod,
+        "-o",
+        "jsonpath={.spec.containers[*].name}",
+    ])?
+    .split_whitespace()
+    .map(str::to_string)
+    .collect();
+    let container = if containers.len() > 1 {
+        match choose(term, "Container", &containers, None)? {
+            Some(container) => Some(container),
+            None => return Ok(None),
+        }
+    } else {
+        containers.into_iter().next()
+    };
+
+    let name = match &container {
+        Some(container) => format!("k8s:{}/{}/{}/{}", context, namespace, pod, container),
+        None => format!("k8s:{}/{}/{}", context, namespace, pod),
+    };
+    Ok(Some(KubernetesDomain {
+        name,
+        context: Some(context),
+        namespace: Some(namespace),
+        pod,
+        container,
+        default_prog: None,
+        reattach: true,
+        reattach_delay_ms: 1000,
+    }))
+}
+
+pub fn kubernetes_pod_selector(
+    pane_id: PaneId,
+    mut term: TermWizTerminal,
+    window: ::window::Window,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    let k8s = match select_container(&mut term) {
+        Ok(Some(k8s)) => k8s,
+        Ok(None) => return Ok(()),
+        Err(err) => {
+            show_error(&mut term, &err)?;
+            return Ok(());
+        }
+    };
+
+    promise::spawn::spawn_into_main_thread(async move {
+        let mux = Mux::get().unwrap();
+        let name = k8s.name.clone();
+        if mux.get_domain_by_name(&name).is_none() {
+            match LocalDomain::new_kubernetes(k8s) {
+                Ok(domain) => {
+                    let domain: Arc<dyn Domain> = Arc::new(domain);
+                    mux.add_domain(&domain);
+                }
+                Err(err) => {
+                    log::error!("creating kubernetes domain {}: {:#}", name, err);
+                    return;
+                }
+            }
+        }
+        window.notify(TermWindowNotif::PerformAssignment {
+            pane_id,
+            assignment: KeyAssignment::SpawnTab(SpawnTabDomain::DomainName(name)),
+        });
+    })
+    .detach();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(
+            parse_names("pod/web-0\npod/web-1\n\n"),
+            vec!["web-0".to_string(), "web-1".to_string()]
+        );
+        assert_eq!(parse_names("default\n"), vec!["default".to_string()]);
+    }
+}
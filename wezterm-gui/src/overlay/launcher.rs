@@ -262,6 +262,17 @@ impl LauncherState {
                     });
                 }
             }
+            for definition in &config.workspaces {
+                if !args.workspaces.contains(&definition.name) {
+                    self.entries.push(Entry {
+                        label: format!("Start workspace: `{}`", definition.name),
+                        action: KeyAssignment::SwitchToWorkspace {
+                            name: Some(definition.name.clone()),
+                            spawn: None,
+                        },
+                    });
+                }
+            }
             self.entries.push(Entry {
                 label: format!(
                     "Create new Workspace (current is `{}`)",
@@ -8,18 +8,40 @@ use std::sync::Arc;
 use wezterm_term::{TerminalConfiguration, TerminalSize};
 
 pub mod confirm_close_pane;
+pub mod container;
 pub mod copy;
+pub mod copymenu;
+pub mod deadpane;
 pub mod debug;
+pub mod filepreview;
+pub mod kubernetes;
 pub mod launcher;
+pub mod muxclients;
+pub mod notificationcenter;
+pub mod pager;
 pub mod quickselect;
+pub mod recentpanes;
+mod selector;
 
 pub use confirm_close_pane::{
     confirm_close_pane, confirm_close_tab, confirm_close_window, confirm_quit_program,
 };
+pub use container::container_selector;
 pub use copy::{CopyModeParams, CopyOverlay};
+pub use copymenu::copy_menu;
+pub use deadpane::{inspect_dead_pane, pane_output_lines};
 pub use debug::show_debug_overlay;
+pub use filepreview::file_preview;
+pub use kubernetes::kubernetes_pod_selector;
 pub use launcher::{launcher, LauncherArgs, LauncherFlags};
+pub use muxclients::mux_clients;
+pub use notificationcenter::{
+    deliver_notification, do_not_disturb, notification_action, notification_center,
+    record_notification, toggle_do_not_disturb, Notification,
+};
+pub use pager::{last_command_output, pager};
 pub use quickselect::QuickSelectOverlay;
+pub use recentpanes::{recent_pane_entries, recent_panes};
 
 pub fn start_overlay<T, F>(
     term_window: &TermWindow,
@@ -0,0 +1,416 @@
+//! The `ShowMuxClients` overlay, which lists the clients that are
+//! connected to this instance and to the mux servers that it is
+//! attached to, and allows detaching or restricting them.
+use chrono::{DateTime, Utc};
+use codec::{DetachClient, GetClientList, SetClientPermission};
+use config::MuxClientPermission;
+use mux::client::ClientInfo;
+use mux::domain::{DomainId, DomainState};
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use std::future::Future;
+use tabout::{tabulate_output_as_string, Alignment, Column};
+use termwiz::cell::{AttributeChange, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent};
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+use termwiz_funcs::truncate_right;
+use wezterm_client::domain::ClientDomain;
+
+#[derive(Clone)]
+struct MuxClientEntry {
+    /// The client domain through which the server is reached, or
+    /// None for the clients of this instance
+    domain_id: Option<DomainId>,
+    server: String,
+    info: ClientInfo,
+    /// True if this is the connection used by this instance
+    is_self: bool,
+}
+
+enum Action {
+    Detach,
+    SetPermission(Option<MuxClientPermission>),
+}
+
+// spawn_into_main_thread wants the overall future to be Send, but the
+// mux and the client domains can only be used from the main thread,
+// and their futures are not Send.  Spawn the actual work separately
+// and funnel its result back through a channel.
+fn on_main_thread<F, Fut, T>(func: F) -> anyhow::Result<T>
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = T> + 'static,
+    T: Send + 'static,
+{
+    smol::block_on(promise::spawn::spawn_into_main_thread(async move {
+        let (tx, rx) = smol::channel::bounded(1);
+        promise::spawn::spawn(async move {
+            let _ = tx.send(func().await).await;
+        })
+        .detach();
+        rx.recv().await
+    }))
+    .map_err(|e| anyhow::anyhow!("{:#}", e))
+}
+
+/// Collects the clients of this instance and of each attached mux
+/// server, along with a description of any servers that could not
+/// be queried
+async fn fetch_entries() -> (Vec<MuxClientEntry>, Option<String>) {
+    let mux = Mux::get().unwrap();
+    let mut entries: Vec<MuxClientEntry> = mux
+        .iter_clients()
+        .into_iter()
+        .map(|info| MuxClientEntry {
+            domain_id: None,
+            server: "local".to_string(),
+            info,
+            is_self: false,
+        })
+        .collect();
+    let mut errors = vec![];
+
+    for domain in mux.iter_domains() {
+        if domain.state() != DomainState::Attached {
+            continue;
+        }
+        let inner = match ClientDomain::get_client_inner_for_domain(domain.domain_id()) {
+            Ok(inner) => inner,
+            Err(_) => continue,
+        };
+        match inner.client.list_clients(GetClientList).await {
+            Ok(response) => {
+                let mut clients = response.clients;
+                clients.sort_by(|a, b| a.connected_at.cmp(&b.connected_at));
+                entries.extend(clients.into_iter().map(|info| MuxClientEntry {
+                    domain_id: Some(domain.domain_id()),
+                    server: domain.domain_name().to_string(),
+                    is_self: *info.client_id == *inner.client.client_id(),
+                    info,
+                }));
+            }
+            Err(err) => errors.push(format!("{}: {:#}", domain.domain_name(), err)),
+        }
+    }
+
+    let errors = if errors.is_empty() {
+        None
+    } else {
+        Some(errors.join("; "))
+    };
+    (entries, errors)
+}
+
+async fn perform_action(entry: MuxClientEntry, action: Action) -> anyhow::Result<()> {
+    let client_id = (*entry.info.client_id).clone();
+    match entry.domain_id {
+        None => {
+            let mux = Mux::get().unwrap();
+            match action {
+                Action::Detach => mux.detach_client(&client_id),
+                Action::SetPermission(permission) => {
+                    mux.set_client_permission(&client_id, permission)
+                }
+            }
+        }
+        Some(domain_id) => {
+            let inner = ClientDomain::get_client_inner_for_domain(domain_id)?;
+            match action {
+                Action::Detach => {
+                    inner
+                        .client
+                        .detach_client(DetachClient { client_id })
+                        .await?;
+                }
+                Action::SetPermission(permission) => {
+                    inner
+                        .client
+                        .set_client_permission(SetClientPermission {
+                            client_id,
+                            permission,
+                        })
+                        .await?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn duration_string(d: chrono::Duration) -> String {
+    let secs = d.num_seconds().max(0);
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m", secs / 60)
+    } else if secs < 86400 {
+        format!("{}h{}m", secs / 3600, (secs % 3600) / 60)
+    } else {
+        format!("{}d{}h", secs / 86400, (secs % 86400) / 3600)
+    }
+}
+
+fn restrictions(permission: Option<&MuxClientPermission>) -> String {
+    let permission = match permission {
+        Some(permission) => permission,
+        None => return String::new(),
+    };
+    if permission.deny {
+        return "denied".to_string();
+    }
+    let mut result = vec![];
+    if permission.read_only {
+        result.push("read-only".to_string());
+    }
+    if !permission.workspaces.is_empty() {
+        result.push(format!("workspaces: {}", permission.workspaces.join(",")));
+    }
+    result.join(", ")
+}
+
+/// Formats the header and one line per entry
+fn format_entries(entries: &[MuxClientEntry]) -> Vec<String> {
+    let cols: Vec<Column> = [
+        ("SERVER", Alignment::Left),
+        ("CLIENT", Alignment::Left),
+        ("PID", Alignment::Right),
+        ("CONNECTED", Alignment::Left),
+        ("IDLE", Alignment::Left),
+        ("WORKSPACE", Alignment::Left),
+        ("RESTRICTIONS", Alignment::Left),
+    ]
+    .into_iter()
+    .map(|(name, alignment)| Column {
+        name: name.to_string(),
+        alignment,
+    })
+    .collect();
+
+    let now: DateTime<Utc> = Utc::now();
+    let rows: Vec<Vec<String>> = entries
+        .iter()
+        .map(|entry| {
+            let info = &entry.info;
+            vec![
+                entry.server.clone(),
+                format!(
+                    "{}@{}{}",
+                    info.client_id.username,
+                    info.client_id.hostname,
+                    if entry.is_self { " (this client)" } else { "" }
+                ),
+                info.client_id.pid.to_string(),
+                format!(
+                    "{} ({} ago)",
+                    info.connected_at
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M"),
+                    duration_string(now - info.connected_at)
+                ),
+                duration_string(now - info.last_input),
+                info.active_workspace.clone().unwrap_or_default(),
+                restrictions(info.permission.as_ref()),
+            ]
+        })
+        .collect();
+
+    tabulate_output_as_string(&cols, &rows)
+        .map(|text| text.lines().map(|line| line.to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// Returns the permission that results from toggling read-only
+/// access for the client
+fn toggle_read_only(info: &ClientInfo) -> MuxClientPermission {
+    let mut permission = info.permission.clone().unwrap_or_default();
+    permission.read_only = !permission.read_only;
+    permission
+}
+
+/// Returns the permission that limits the client to its active
+/// workspace, or that lifts the workspace limit if it has one
+fn toggle_workspace_limit(info: &ClientInfo) -> anyhow::Result<MuxClientPermission> {
+    let mut permission = info.permission.clone().unwrap_or_default();
+    if permission.workspaces.is_empty() {
+        let workspace = info
+            .active_workspace
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("the client has no active workspace"))?;
+        permission.workspaces = vec![workspace];
+    } else {
+        permission.workspaces.clear();
+    }
+    Ok(permission)
+}
+
+pub fn mux_clients(mut term: TermWizTerminal) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+
+    let (mut entries, mut status) = on_main_thread(fetch_entries)?;
+    let mut selected = 0;
+
+    loop {
+        selected = selected.min(entries.len().saturating_sub(1));
+        let size = term.get_screen_size()?;
+        let lines = format_entries(&entries);
+        let list_height = size.rows.saturating_sub(5).max(1);
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorVisibility(CursorVisibility::Hidden),
+            AttributeChange::Intensity(Intensity::Bold).into(),
+            truncate_right(
+                "Mux clients: Up/Down to choose, d to detach, r to toggle read-only, \
+                 w to limit to/unlimit from its workspace, Esc to close",
+                size.cols,
+            )
+            .into(),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(2),
+            },
+        ];
+        if let Some(header) = lines.first() {
+            changes.push(truncate_right(header, size.cols).into());
+        }
+        changes.push(AttributeChange::Intensity(Intensity::Normal).into());
+
+        if entries.is_empty() {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(3),
+            });
+            changes.push("No clients are connected".into());
+        }
+
+        let first = (selected + 1).saturating_sub(list_height);
+        for (row, (idx, line)) in lines
+            .iter()
+            .skip(1)
+            .enumerate()
+            .skip(first)
+            .take(list_height)
+            .enumerate()
+        {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(row + 3),
+            });
+            if idx == selected {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+            changes.push(truncate_right(line, size.cols).into());
+            if idx == selected {
+                changes.push(AttributeChange::Reverse(false).into());
+            }
+        }
+
+        if let Some(status) = &status {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(size.rows.saturating_sub(1)),
+            });
+            changes.push(truncate_right(status, size.cols).into());
+        }
+
+        term.render(&changes)?;
+        term.flush()?;
+
+        let action = match term.poll_input(None) {
+            Ok(Some(InputEvent::Key(KeyEvent { key, .. }))) => match key {
+                KeyCode::UpArrow | KeyCode::Char('k') => {
+                    selected = selected.saturating_sub(1);
+                    continue;
+                }
+                KeyCode::DownArrow | KeyCode::Char('j') => {
+                    selected += 1;
+                    continue;
+                }
+                KeyCode::Escape | KeyCode::Char('q') => return Ok(()),
+                KeyCode::Char('d') => Some(Action::Detach),
+                KeyCode::Char('r') => entries
+                    .get(selected)
+                    .map(|entry| Action::SetPermission(Some(toggle_read_only(&entry.info)))),
+                KeyCode::Char('w') => match entries.get(selected) {
+                    Some(entry) => match toggle_workspace_limit(&entry.info) {
+                        Ok(permission) => Some(Action::SetPermission(Some(permission))),
+                        Err(err) => {
+                            status = Some(format!("{:#}", err));
+                            continue;
+                        }
+                    },
+                    None => None,
+                },
+                _ => continue,
+            },
+            Ok(Some(_)) => continue,
+            Ok(None) | Err(_) => return Ok(()),
+        };
+
+        let (entry, action) = match (entries.get(selected), action) {
+            (Some(entry), Some(action)) => (entry.clone(), action),
+            _ => continue,
+        };
+        if entry.is_self {
+            status = Some("That is the connection used by this window".to_string());
+            continue;
+        }
+
+        let result = on_main_thread(move || perform_action(entry, action))?;
+        let (new_entries, errors) = on_main_thread(fetch_entries)?;
+        entries = new_entries;
+        status = match result {
+            Ok(()) => errors,
+            Err(err) => Some(format!("{:#}", err)),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mux::client::{ClientId, ClientTransport};
+    use std::sync::Arc;
+
+    fn info(workspace: Option<&str>) -> ClientInfo {
+        let mut info = ClientInfo::new(Arc::new(ClientId::new()), ClientTransport::Local);
+        info.active_workspace = workspace.map(|w| w.to_string());
+        info
+    }
+
+    #[test]
+    fn permission_toggles() {
+        let mut client = info(Some("work"));
+        assert_eq!(restrictions(client.permission.as_ref()), "");
+
+        client.permission = Some(toggle_read_only(&client));
+        assert_eq!(restrictions(client.permission.as_ref()), "read-only");
+
+        client.permission = Some(toggle_workspace_limit(&client).unwrap());
+        assert_eq!(
+            restrictions(client.permission.as_ref()),
+            "read-only, workspaces: work"
+        );
+
+        client.permission = Some(toggle_read_only(&client));
+        client.permission = Some(toggle_workspace_limit(&client).unwrap());
+        assert_eq!(
+            client.permission,
+            Some(MuxClientPermission::default()),
+            "toggling twice gives an unrestricted override"
+        );
+
+        assert!(toggle_workspace_limit(&info(None)).is_err());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(duration_string(chrono::Duration::seconds(5)), "5s");
+        assert_eq!(duration_string(chrono::Duration::seconds(125)), "2m");
+        assert_eq!(duration_string(chrono::Duration::seconds(3720)), "1h2m");
+        assert_eq!(duration_string(chrono::Duration::seconds(90000)), "1d1h");
+        assert_eq!(duration_string(chrono::Duration::seconds(-3)), "0s");
+    }
+}
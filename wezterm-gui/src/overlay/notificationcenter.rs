@@ -0,0 +1,478 @@
+//! The notification center keeps a history of the toast notifications,
+//! bells, failed commands and connection errors seen by this process,
+//! and provides an overlay to browse, filter and act upon them.
+//!
+//! Notifications are subject to the `notification_rules` and to the
+//! do-not-disturb state before they are delivered.
+use crate::termwindow::TermWindowNotif;
+use chrono::{DateTime, Local, Timelike};
+use config::keyassignment::KeyAssignment;
+use config::{NotificationAction, NotificationContext, NotificationSource, TimeOfDay};
+use mux::pane::PaneId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use termwiz::cell::{unicode_column_width, AttributeChange};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+use termwiz_funcs::truncate_right;
+use wezterm_toast_notification::persistent_toast_notification;
+use window::WindowOps;
+
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub source: NotificationSource,
+    pub time: DateTime<Local>,
+    pub title: String,
+    pub body: String,
+    /// The pane that generated the notification, which is
+    /// activated when the entry is chosen
+    pub pane_id: Option<PaneId>,
+    /// The domain that lost its connection, which is re-attached
+    /// when the entry is chosen
+    pub domain_name: Option<String>,
+}
+
+impl Notification {
+    pub fn new(source: NotificationSource, title: impl Into<String>) -> Self {
+        Self {
+            source,
+            time: Local::now(),
+            title: title.into(),
+            body: String::new(),
+            pane_id: None,
+            domain_name: None,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    /// Most recent first
+    static ref HISTORY: Mutex<VecDeque<Notification>> = Mutex::new(VecDeque::new());
+}
+
+fn push_notification(history: &mut VecDeque<Notification>, notif: Notification, size: usize) {
+    history.push_front(notif);
+    history.truncate(size);
+}
+
+/// Adds a notification to the history, which retains the most recent
+/// `notification_center_history_size` entries
+pub fn record_notification(notif: Notification) {
+    let size = config::configuration().notification_center_history_size;
+    push_notification(&mut HISTORY.lock().unwrap(), notif, size);
+}
+
+static DO_NOT_DISTURB: AtomicBool = AtomicBool::new(false);
+
+pub fn do_not_disturb() -> bool {
+    DO_NOT_DISTURB.load(Ordering::Relaxed)
+}
+
+/// Toggles do-not-disturb, returning the new state
+pub fn toggle_do_not_disturb() -> bool {
+    !DO_NOT_DISTURB.fetch_xor(true, Ordering::Relaxed)
+}
+
+/// Decides how the notification should be delivered, according to
+/// `notification_rules` and the do-not-disturb state
+pub fn notification_action(
+    notif: &Notification,
+    window_focused: bool,
+    window_fullscreen: bool,
+) -> NotificationAction {
+    let config = config::configuration();
+    // Looking up the foreground process can be relatively expensive,
+    // so only do it if a rule needs it
+    let foreground_process = if config
+        .notification_rules
+        .iter()
+        .any(|rule| rule.foreground_process.is_some())
+    {
+        notif
+            .pane_id
+            .and_then(|pane_id| Mux::get()?.get_pane(pane_id))
+            .and_then(|pane| pane.get_foreground_process_name())
+    } else {
+        None
+    };
+    let now = Local::now();
+    let ctx = NotificationContext {
+        source: notif.source,
+        window_focused,
+        window_fullscreen,
+        time_of_day: TimeOfDay::new(now.hour() as u16, now.minute() as u16),
+        foreground_process: foreground_process.as_deref(),
+    };
+    config.notification_action(&ctx, do_not_disturb())
+}
+
+/// Records the notification in the history and, for toasts, shows
+/// the system notification, subject to `notification_action`.
+pub fn deliver_notification(
+    notif: Notification,
+    window_focused: bool,
+    window_fullscreen: bool,
+) -> NotificationAction {
+    let action = notification_action(&notif, window_focused, window_fullscreen);
+    if action != NotificationAction::Suppress {
+        if action == NotificationAction::Allow && notif.source == NotificationSource::Toast {
+            persistent_toast_notification(&notif.title, &notif.body);
+        }
+        record_notification(notif);
+    }
+    action
+}
+
+fn filtered_notifications(
+    history: &VecDeque<Notification>,
+    filter: Option<NotificationSource>,
+) -> Vec<Notification> {
+    history
+        .iter()
+        .filter(|notif| filter.map(|f| f == notif.source).unwrap_or(true))
+        .cloned()
+        .collect()
+}
+
+/// Returns the filter that follows `filter` when cycling through them
+fn next_filter(filter: Option<NotificationSource>) -> Option<NotificationSource> {
+    let all = NotificationSource::ALL;
+    match filter {
+        None => Some(all[0]),
+        Some(source) => all
+            .iter()
+            .position(|s| *s == source)
+            .and_then(|idx| all.get(idx + 1))
+            .copied(),
+    }
+}
+
+fn filter_label(filter: Option<NotificationSource>) -> &'static str {
+    filter.map(|f| f.label()).unwrap_or("All")
+}
+
+/// Makes the pane the active pane of its tab and window
+fn focus_pane(pane_id: PaneId) {
+    let mux = Mux::get().unwrap();
+    let pane = match mux.get_pane(pane_id) {
+        Some(pane) => pane,
+        None => return,
+    };
+    let (_domain_id, window_id, tab_id) = match mux.resolve_pane_id(pane_id) {
+        Some(ids) => ids,
+        None => return,
+    };
+    if let Some(mut window) = mux.get_window_mut(window_id) {
+        if let Some(idx) = window.idx_by_id(tab_id) {
+            window.save_and_then_set_active(idx);
+        }
+    }
+    if let Some(tab) = mux.get_tab(tab_id) {
+        tab.set_active_pane(&pane);
+    }
+    if let Some(gui_win) = crate::frontend::front_end().gui_window_for_mux_window(window_id) {
+        gui_win.window.show();
+    }
+}
+
+struct NotificationCenter {
+    /// The pane that the overlay was started from
+    pane_id: PaneId,
+    window: ::window::Window,
+    filter: Option<NotificationSource>,
+    entries: Vec<Notification>,
+    active_idx: usize,
+    top_row: usize,
+    max_items: usize,
+}
+
+impl NotificationCenter {
+    fn update_entries(&mut self) {
+        self.entries = filtered_notifications(&HISTORY.lock().unwrap(), self.filter);
+        self.active_idx = 0;
+        self.top_row = 0;
+    }
+
+    /// Returns the filter whose label is displayed at column `x`
+    /// of the header row
+    fn filter_at(x: usize) -> Option<Option<NotificationSource>> {
+        let mut left = 0;
+        let filters = std::iter::once(None).chain(NotificationSource::ALL.iter().map(|s| Some(*s)));
+        for filter in filters {
+            let width = unicode_column_width(filter_label(filter), None) + 2;
+            if x >= left && x < left + width {
+                return Some(filter);
+            }
+            left += width + 1;
+        }
+        None
+    }
+
+    fn render(&mut self, term: &mut TermWizTerminal) -> termwiz::Result<()> {
+        let size = term.get_screen_size()?;
+        let max_width = size.cols.saturating_sub(6);
+        self.max_items = size.rows.saturating_sub(3);
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorVisibility(CursorVisibility::Hidden),
+        ];
+
+        let filters = std::iter::once(None).chain(NotificationSource::ALL.iter().map(|s| Some(*s)));
+        for filter in filters {
+            let selected = filter == self.filter;
+            if selected {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+            changes.push(format!(" {} ", filter_label(filter)).into());
+            if selected {
+                changes.push(AttributeChange::Reverse(false).into());
+            }
+            changes.push(" ".into());
+        }
+
+        let hint = if self.entries.is_empty() {
+            if config::configuration().notification_center_history_size == 0 {
+                "The notification center is disabled; set notification_center_history_size to enable it"
+            } else {
+                "There are no notifications"
+            }
+        } else {
+            "Enter: activate  Tab: filter  Delete: clear  Esc: close"
+        };
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(1),
+        });
+        changes.push(AttributeChange::Italic(true).into());
+        changes.push(truncate_right(hint, size.cols).into());
+        changes.push(AttributeChange::Italic(false).into());
+
+        for (row, (idx, notif)) in self
+            .entries
+            .iter()
+            .enumerate()
+            .skip(self.top_row)
+            .take(self.max_items)
+            .enumerate()
+        {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(row + 2),
+            });
+            if idx == self.active_idx {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+            let mut text = format!(
+                "{} {:<10} {}",
+                notif.time.format("%H:%M:%S"),
+                notif.source.label(),
+                notif.title
+            );
+            if !notif.body.is_empty() {
+                text.push_str(": ");
+                text.push_str(&notif.body);
+            }
+            let text = text.replace(|c: char| c.is_control(), " ");
+            changes.push(truncate_right(&text, max_width).into());
+            if idx == self.active_idx {
+                changes.push(AttributeChange::Reverse(false).into());
+            }
+        }
+
+        term.render(&changes)?;
+        term.flush()
+    }
+
+    /// Performs the action associated with the entry.
+    /// Returns true if the overlay should be closed.
+    fn activate(&self, idx: usize) -> bool {
+        let notif = match self.entries.get(idx) {
+            Some(notif) => notif.clone(),
+            None => return false,
+        };
+        if let Some(domain_name) = notif.domain_name {
+            self.window.notify(TermWindowNotif::PerformAssignment {
+                pane_id: self.pane_id,
+                assignment: KeyAssignment::AttachDomain(domain_name),
+            });
+        } else if let Some(pane_id) = notif.pane_id {
+            promise::spawn::spawn_into_main_thread(async move {
+                focus_pane(pane_id);
+            })
+            .detach();
+        }
+        true
+    }
+
+    fn move_up(&mut self) {
+        self.active_idx = self.active_idx.saturating_sub(1);
+        if self.active_idx < self.top_row {
+            self.top_row = self.active_idx;
+        }
+    }
+
+    fn move_down(&mut self) {
+        self.active_idx = (self.active_idx + 1).min(self.entries.len().saturating_sub(1));
+        if self.active_idx >= self.top_row + self.max_items {
+            self.top_row = self.active_idx + 1 - self.max_items;
+        }
+    }
+
+    fn run_loop(&mut self, term: &mut TermWizTerminal) -> anyhow::Result<()> {
+        while let Ok(Some(event)) = term.poll_input(None) {
+            match event {
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Escape,
+                    ..
+                }) => break,
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::UpArrow | KeyCode::Char('k'),
+                    ..
+                }) => self.move_up(),
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::DownArrow | KeyCode::Char('j'),
+                    ..
+                }) => self.move_down(),
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Tab, ..
+                }) => {
+                    self.filter = next_filter(self.filter);
+                    self.update_entries();
+                }
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Delete,
+                    ..
+                }) => {
+                    HISTORY.lock().unwrap().clear();
+                    self.update_entries();
+                }
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Enter,
+                    ..
+                }) => {
+                    if self.activate(self.active_idx) {
+                        break;
+                    }
+                }
+                InputEvent::Mouse(MouseEvent { mouse_buttons, .. })
+                    if mouse_buttons.contains(MouseButtons::VERT_WHEEL) =>
+                {
+                    if mouse_buttons.contains(MouseButtons::WHEEL_POSITIVE) {
+                        self.move_up();
+                    } else {
+                        self.move_down();
+                    }
+                }
+                InputEvent::Mouse(MouseEvent {
+                    x,
+                    y,
+                    mouse_buttons,
+                    ..
+                }) => {
+                    let clicked = mouse_buttons == MouseButtons::LEFT;
+                    if y == 0 {
+                        if clicked {
+                            if let Some(filter) = Self::filter_at(x as usize) {
+                                self.filter = filter;
+                                self.update_entries();
+                            }
+                        }
+                    } else if y > 1 && self.top_row + (y as usize - 2) < self.entries.len() {
+                        self.active_idx = self.top_row + y as usize - 2;
+                        if clicked && self.activate(self.active_idx) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            self.render(term)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn notification_center(
+    pane_id: PaneId,
+    mut term: TermWizTerminal,
+    window: ::window::Window,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    let mut center = NotificationCenter {
+        pane_id,
+        window,
+        filter: None,
+        entries: vec![],
+        active_idx: 0,
+        top_row: 0,
+        max_items: 0,
+    };
+    center.update_entries();
+    center.render(&mut term)?;
+    center.run_loop(&mut term)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn history_is_trimmed() {
+        let mut history = VecDeque::new();
+        for i in 0..5 {
+            push_notification(
+                &mut history,
+                Notification::new(NotificationSource::Bell, format!("{}", i)),
+                3,
+            );
+        }
+        let titles: Vec<&str> = history.iter().map(|n| n.title.as_str()).collect();
+        assert_eq!(titles, vec!["4", "3", "2"]);
+
+        push_notification(
+            &mut history,
+            Notification::new(NotificationSource::Bell, "disabled"),
+            0,
+        );
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn filtering() {
+        let mut history = VecDeque::new();
+        push_notification(
+            &mut history,
+            Notification::new(NotificationSource::Bell, "bell"),
+            10,
+        );
+        push_notification(
+            &mut history,
+            Notification::new(NotificationSource::Toast, "toast"),
+            10,
+        );
+        assert_eq!(filtered_notifications(&history, None).len(), 2);
+        let toasts = filtered_notifications(&history, Some(NotificationSource::Toast));
+        assert_eq!(toasts.len(), 1);
+        assert_eq!(toasts[0].title, "toast");
+    }
+
+    #[test]
+    fn filter_cycle() {
+        let mut filter = None;
+        let mut seen = vec![];
+        loop {
+            filter = next_filter(filter);
+            seen.push(filter_label(filter));
+            if filter.is_none() {
+                break;
+            }
+        }
+        assert_eq!(seen, vec!["Toast", "Bell", "Command", "Connection", "All"]);
+    }
+}
@@ -0,0 +1,403 @@
+//! A pager for the output of the most recent command in a pane, as
+//! found via the semantic zones reported by shell integration.
+//! The output is copied when the pager is opened, so the pane itself
+//! is left untouched; it keeps running behind the pager.
+use mux::pane::{Pane, PaneId};
+use mux::termwiztermtab::TermWizTerminal;
+use regex::Regex;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::rc::Rc;
+use termwiz::cell::{unicode_column_width, AttributeChange, CellAttributes};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, CursorVisibility, Line, Position, SEQ_ZERO};
+use termwiz::terminal::Terminal;
+use wezterm_term::SemanticType;
+
+/// Returns the lines of output of the most recent command in the
+/// pane that produced any output, or None if the pane has no
+/// semantic zones
+pub fn last_command_output(pane: &Rc<dyn Pane>) -> Option<Vec<Line>> {
+    let zones = pane.get_semantic_zones().ok()?;
+    for zone in zones
+        .iter()
+        .rev()
+        .filter(|zone| zone.semantic_type == SemanticType::Output)
+    {
+        let mut lines = vec![];
+        for line in pane.get_logical_lines(zone.start_y..zone.end_y + 1) {
+            let mut logical = line.logical;
+            let start = if line.first_row < zone.start_y {
+                0
+            } else {
+                zone.start_x
+            };
+            let last_row = line.first_row + line.physical_lines.len() as isize - 1;
+            if last_row >= zone.end_y {
+                // The zone ends part way through this line; end_x is
+                // relative to the physical line that holds it
+                let end_offset: usize = line.physical_lines
+                    [..(zone.end_y - line.first_row).max(0) as usize]
+                    .iter()
+                    .map(|l| l.len())
+                    .sum();
+                logical = logical.columns_as_line(0..end_offset + zone.end_x + 1);
+            }
+            if start > 0 {
+                logical = logical.columns_as_line(start..usize::MAX);
+            }
+            lines.push(logical);
+        }
+        while lines.last().map(|l| l.is_whitespace()).unwrap_or(false) {
+            lines.pop();
+        }
+        while lines.first().map(|l| l.is_whitespace()).unwrap_or(false) {
+            lines.remove(0);
+        }
+        if !lines.is_empty() {
+            return Some(lines);
+        }
+    }
+    None
+}
+
+enum Mode {
+    Normal,
+    Search(String),
+    Save(String),
+}
+
+struct Pager {
+    lines: Vec<Line>,
+    texts: Vec<String>,
+    wrap: bool,
+    /// The first displayed row, counted in display rows
+    top: usize,
+    /// How far the view is scrolled to the right when not wrapping
+    left: usize,
+    search: Option<Regex>,
+    mode: Mode,
+    status: Option<String>,
+}
+
+impl Pager {
+    fn new(lines: Vec<Line>) -> Self {
+        let texts = lines
+            .iter()
+            .map(|line| line.as_str().trim_end().to_string())
+            .collect();
+        Self {
+            lines,
+            texts,
+            wrap: true,
+            top: 0,
+            left: 0,
+            search: None,
+            mode: Mode::Normal,
+            status: None,
+        }
+    }
+
+    /// Returns the line with the matches of the search reversed
+    fn highlighted(&self, idx: usize) -> Line {
+        let mut line = self.lines[idx].clone();
+        let regex = match &self.search {
+            Some(regex) => regex,
+            None => return line,
+        };
+        let matches: Vec<Range<usize>> = regex
+            .find_iter(&self.texts[idx])
+            .map(|m| m.range())
+            .filter(|r| !r.is_empty())
+            .collect();
+        if matches.is_empty() {
+            return line;
+        }
+        let mut offset = 0;
+        let mut cols = vec![];
+        for cell in line.visible_cells() {
+            if matches.iter().any(|r| r.contains(&offset)) {
+                cols.push(cell.cell_index()..cell.cell_index() + cell.width());
+            }
+            offset += cell.str().len();
+        }
+        let cells = line.cells_mut_for_attr_changes_only();
+        let len = cells.len();
+        for range in cols {
+            for cell in cells[range.start.min(len)..range.end.min(len)].iter_mut() {
+                cell.attrs_mut().set_reverse(true);
+            }
+        }
+        line
+    }
+
+    /// Returns the rows to display, as the index of the line that
+    /// each came from and the content of the row
+    fn display_rows(&self, cols: usize) -> Vec<(usize, Line)> {
+        let mut rows = vec![];
+        for idx in 0..self.lines.len() {
+            let line = self.highlighted(idx);
+            if self.wrap {
+                if line.len() <= cols {
+                    rows.push((idx, line));
+                } else {
+                    for row in line.wrap(cols.max(1), SEQ_ZERO) {
+                        rows.push((idx, row));
+                    }
+                }
+            } else {
+                rows.push((idx, line.columns_as_line(self.left..self.left + cols)));
+            }
+        }
+        rows
+    }
+
+    /// Scrolls to the next line after (or before) the current top
+    /// line that matches the search
+    fn find(&mut self, cols: usize, forwards: bool) {
+        let regex = match &self.search {
+            Some(regex) => regex,
+            None => return,
+        };
+        let rows = self.display_rows(cols);
+        let current = rows.get(self.top).map(|(idx, _)| *idx).unwrap_or(0);
+        let found = if forwards {
+            (current + 1..self.texts.len()).find(|&idx| regex.is_match(&self.texts[idx]))
+        } else {
+            (0..current)
+                .rev()
+                .find(|&idx| regex.is_match(&self.texts[idx]))
+        };
+        match found {
+            Some(found) => {
+                self.top = rows.iter().position(|(idx, _)| *idx == found).unwrap_or(0);
+                self.status = None;
+            }
+            None => self.status = Some("No more matches".to_string()),
+        }
+    }
+
+    fn save(&mut self, path: &str) {
+        let path = match path.strip_prefix("~/") {
+            Some(rest) => config::HOME_DIR.join(rest),
+            None => PathBuf::from(path),
+        };
+        let mut text = self.texts.join("\n");
+        text.push('\n');
+        self.status = Some(match std::fs::write(&path, text) {
+            Ok(()) => format!("Saved to {}", path.display()),
+            Err(err) => format!("Unable to save to {}: {:#}", path.display(), err),
+        });
+    }
+
+    fn render(&self, term: &mut TermWizTerminal) -> anyhow::Result<()> {
+        let size = term.get_screen_size()?;
+        let page = size.rows.saturating_sub(2);
+        let rows = self.display_rows(size.cols);
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorVisibility(CursorVisibility::Hidden),
+        ];
+
+        let title = format!(
+            " Output of the last command: {} line{} ",
+            self.lines.len(),
+            if self.lines.len() == 1 { "" } else { "s" }
+        );
+        changes.push(AttributeChange::Reverse(true).into());
+        changes.push(Change::Text(format!(
+            "{}{}",
+            title,
+            " ".repeat(size.cols.saturating_sub(unicode_column_width(&title, None)))
+        )));
+        changes.push(AttributeChange::Reverse(false).into());
+
+        for (y, (_, row)) in rows.iter().skip(self.top).take(page).enumerate() {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(y + 1),
+            });
+            changes.append(&mut row.changes(&CellAttributes::default()));
+            changes.push(Change::AllAttributes(CellAttributes::default()));
+        }
+
+        let status = match &self.mode {
+            Mode::Search(text) => format!("/{}", text),
+            Mode::Save(path) => format!("Save to: {}", path),
+            Mode::Normal => match &self.status {
+                Some(status) => status.clone(),
+                None => format!(
+                    "q: close  /: search  n/N: next/previous  w: wrap {}  s: save",
+                    if self.wrap { "off" } else { "on" }
+                ),
+            },
+        };
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(size.rows.saturating_sub(1)),
+        });
+        changes.push(AttributeChange::Reverse(true).into());
+        changes.push(Change::Text(format!(
+            "{}{}",
+            status,
+            " ".repeat(
+                size.cols
+                    .saturating_sub(unicode_column_width(&status, None))
+            )
+        )));
+        changes.push(AttributeChange::Reverse(false).into());
+
+        term.render(&changes)?;
+        term.flush()?;
+        Ok(())
+    }
+
+    /// Handles a key while a prompt is shown at the bottom
+    fn prompt_key(&mut self, key: KeyCode, cols: usize) {
+        let text = match &mut self.mode {
+            Mode::Search(text) | Mode::Save(text) => text,
+            Mode::Normal => return,
+        };
+        match key {
+            KeyCode::Char(c) => text.push(c),
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Escape => self.mode = Mode::Normal,
+            KeyCode::Enter => match std::mem::replace(&mut self.mode, Mode::Normal) {
+                Mode::Search(text) if !text.is_empty() => {
+                    // Smart case: only case sensitive if the search
+                    // contains an upper case character
+                    let pattern = if text.chars().any(char::is_uppercase) {
+                        regex::escape(&text)
+                    } else {
+                        format!("(?i){}", regex::escape(&text))
+                    };
+                    self.search = Regex::new(&pattern).ok();
+                    let regex = self.search.as_ref().unwrap();
+                    let rows = self.display_rows(cols);
+                    let current = rows.get(self.top).map(|(idx, _)| *idx).unwrap_or(0);
+                    if regex.is_match(&self.texts[current]) {
+                        self.status = None;
+                    } else {
+                        self.find(cols, true);
+                    }
+                }
+                Mode::Search(_) => self.search = None,
+                Mode::Save(path) if !path.is_empty() => self.save(&path),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+pub fn pager(
+    _pane_id: PaneId,
+    mut term: TermWizTerminal,
+    lines: Vec<Line>,
+    default_save_path: String,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+    let mut pager = Pager::new(lines);
+    pager.render(&mut term)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        let size = term.get_screen_size()?;
+        let page = size.rows.saturating_sub(2).max(1);
+        let max_top = pager.display_rows(size.cols).len().saturating_sub(page);
+
+        if !matches!(pager.mode, Mode::Normal) {
+            if let InputEvent::Key(KeyEvent { key, .. }) = event {
+                pager.prompt_key(key, size.cols);
+            }
+            pager.render(&mut term)?;
+            continue;
+        }
+
+        pager.status = None;
+        match event {
+            InputEvent::Key(KeyEvent { key, modifiers }) => match (key, modifiers) {
+                (KeyCode::Char('q'), _) | (KeyCode::Escape, _) => break,
+                (KeyCode::UpArrow, _) | (KeyCode::Char('k'), _) => {
+                    pager.top = pager.top.saturating_sub(1)
+                }
+                (KeyCode::DownArrow, _) | (KeyCode::Char('j'), _) | (KeyCode::Enter, _) => {
+                    pager.top = (pager.top + 1).min(max_top)
+                }
+                (KeyCode::PageUp, _) | (KeyCode::Char('b'), _) => {
+                    pager.top = pager.top.saturating_sub(page)
+                }
+                (KeyCode::PageDown, _) | (KeyCode::Char(' '), _) => {
+                    pager.top = (pager.top + page).min(max_top)
+                }
+                (KeyCode::Home, _) | (KeyCode::Char('g'), _) => pager.top = 0,
+                (KeyCode::End, _) | (KeyCode::Char('G'), _) => pager.top = max_top,
+                (KeyCode::LeftArrow, _) | (KeyCode::Char('h'), _) if !pager.wrap => {
+                    pager.left = pager.left.saturating_sub(size.cols / 2)
+                }
+                (KeyCode::RightArrow, _) | (KeyCode::Char('l'), _) if !pager.wrap => {
+                    pager.left += size.cols / 2
+                }
+                (KeyCode::Char('w'), _) => {
+                    pager.wrap = !pager.wrap;
+                    pager.top = 0;
+                    pager.left = 0;
+                }
+                (KeyCode::Char('/'), _) => pager.mode = Mode::Search(String::new()),
+                (KeyCode::Char('n'), Modifiers::NONE) => pager.find(size.cols, true),
+                (KeyCode::Char('N'), _) => pager.find(size.cols, false),
+                (KeyCode::Char('s'), _) => pager.mode = Mode::Save(default_save_path.clone()),
+                _ => continue,
+            },
+            InputEvent::Mouse(MouseEvent { mouse_buttons, .. })
+                if mouse_buttons.contains(MouseButtons::VERT_WHEEL) =>
+            {
+                if mouse_buttons.contains(MouseButtons::WHEEL_POSITIVE) {
+                    pager.top = pager.top.saturating_sub(1);
+                } else {
+                    pager.top = (pager.top + 1).min(max_top);
+                }
+            }
+            InputEvent::Resized { .. } => {}
+            _ => continue,
+        }
+        pager.render(&mut term)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pager(text: &[&str]) -> Pager {
+        Pager::new(
+            text.iter()
+                .map(|s| Line::from_text(s, &CellAttributes::default(), SEQ_ZERO, None))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn wraps_and_searches() {
+        let mut pager = pager(&["one", "a long line that wraps", "three", "Two"]);
+        assert_eq!(pager.display_rows(10).len(), 6);
+        pager.wrap = false;
+        assert_eq!(pager.display_rows(10).len(), 4);
+        pager.wrap = true;
+
+        pager.mode = Mode::Search("t".to_string());
+        pager.prompt_key(KeyCode::Char('w'), 10);
+        pager.prompt_key(KeyCode::Enter, 10);
+        // Smart case matches "Two"; "wraps" in the long line doesn't
+        // match "tw"
+        assert_eq!(pager.display_rows(10)[pager.top].0, 3);
+
+        pager.find(10, false);
+        assert_eq!(pager.status.as_deref(), Some("No more matches"));
+    }
+}
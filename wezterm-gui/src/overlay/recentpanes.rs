@@ -0,0 +1,200 @@
+//! The `ShowRecentPanes` overlay, which lists the panes with the most
+//! recently focused first, along with a preview of the output of the
+//! selected pane, and activates the chosen pane.
+use crate::overlay::pane_output_lines;
+use mux::pane::PaneId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use termwiz::cell::{AttributeChange, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+use termwiz_funcs::truncate_right;
+
+/// The largest number of lines of output kept for the preview
+const PREVIEW_LINES: usize = 100;
+
+pub struct RecentPane {
+    pub pane_id: PaneId,
+    pub label: String,
+    pub preview: Vec<String>,
+}
+
+/// Collects the panes that can be switched to, most recently focused
+/// first.  Only the panes of the active workspace are included, unless
+/// each window is bound to its own workspace.
+/// This must be called on the main thread.
+pub fn recent_pane_entries(workspace_per_window: bool) -> Vec<RecentPane> {
+    let mux = Mux::get().expect("called on main thread");
+    let workspace = mux.active_workspace();
+
+    let mut pane_ids = mux.recent_panes();
+    let mut others: Vec<PaneId> = mux
+        .iter_panes()
+        .iter()
+        .map(|pane| pane.pane_id())
+        .filter(|id| !pane_ids.contains(id))
+        .collect();
+    others.sort();
+    pane_ids.extend(others);
+
+    pane_ids
+        .into_iter()
+        .filter_map(|pane_id| {
+            let pane = mux.get_pane(pane_id)?;
+            let (_domain_id, window_id, tab_id) = mux.resolve_pane_id(pane_id)?;
+            let window = mux.get_window(window_id)?;
+            if !workspace_per_window && window.get_workspace() != workspace {
+                return None;
+            }
+            let tab_idx = window.idx_by_id(tab_id)?;
+            let tab = window.get_by_idx(tab_idx)?;
+            let tab_title = tab.get_title();
+            let title = if tab_title.is_empty() {
+                pane.get_title()
+            } else {
+                format!("{}: {}", tab_title, pane.get_title())
+            };
+            Some(RecentPane {
+                pane_id,
+                label: format!(
+                    "{}  window {} tab {}  {}",
+                    window.get_workspace(),
+                    window_id,
+                    tab_idx + 1,
+                    title
+                ),
+                preview: pane_output_lines(&pane, PREVIEW_LINES),
+            })
+        })
+        .collect()
+}
+
+fn run_selector(
+    entries: &[RecentPane],
+    term: &mut TermWizTerminal,
+) -> anyhow::Result<Option<PaneId>> {
+    term.set_raw_mode()?;
+    let size = term.get_screen_size()?;
+
+    // Header, blank line, list, separator, preview
+    let list_height = entries
+        .len()
+        .min(10)
+        .min(size.rows.saturating_sub(4) / 2)
+        .max(1);
+    let preview_top = list_height + 3;
+    let preview_height = size.rows.saturating_sub(preview_top);
+
+    // The first entry is the active pane, so start with the one
+    // before it, as Alt-Tab does
+    let mut selected = if entries.len() > 1 { 1 } else { 0 };
+
+    let render = |term: &mut TermWizTerminal, selected: usize| -> termwiz::Result<()> {
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorVisibility(CursorVisibility::Hidden),
+            AttributeChange::Intensity(Intensity::Bold).into(),
+            truncate_right(
+                "Recent panes: Tab/Down and Shift-Tab/Up to choose, \
+                 Enter to activate, Esc to cancel",
+                size.cols,
+            )
+            .into(),
+            AttributeChange::Intensity(Intensity::Normal).into(),
+        ];
+
+        let first = (selected + 1).saturating_sub(list_height);
+        for (row, (idx, entry)) in entries
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(list_height)
+            .enumerate()
+        {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(row + 2),
+            });
+            if idx == selected {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+            changes.push(truncate_right(&entry.label, size.cols).into());
+            if idx == selected {
+                changes.push(AttributeChange::Reverse(false).into());
+            }
+        }
+
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(preview_top - 1),
+        });
+        changes.push("─".repeat(size.cols).into());
+
+        if let Some(entry) = entries.get(selected) {
+            let lines = &entry.preview[entry.preview.len().saturating_sub(preview_height)..];
+            for (row, line) in lines.iter().enumerate() {
+                changes.push(Change::CursorPosition {
+                    x: Position::Absolute(0),
+                    y: Position::Absolute(preview_top + row),
+                });
+                changes.push(truncate_right(line, size.cols).into());
+            }
+        }
+
+        term.render(&changes)?;
+        term.flush()
+    };
+
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    render(term, selected)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Tab,
+                modifiers: Modifiers::SHIFT,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow | KeyCode::Char('k'),
+                ..
+            }) => {
+                selected = (selected + entries.len() - 1) % entries.len();
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Tab | KeyCode::DownArrow | KeyCode::Char('j'),
+                ..
+            }) => {
+                selected = (selected + 1) % entries.len();
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }) => return Ok(Some(entries[selected].pane_id)),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape | KeyCode::Char('q'),
+                ..
+            }) => return Ok(None),
+            _ => continue,
+        }
+        render(term, selected)?;
+    }
+
+    Ok(None)
+}
+
+pub fn recent_panes(mut term: TermWizTerminal, entries: Vec<RecentPane>) -> anyhow::Result<()> {
+    if let Some(pane_id) = run_selector(&entries, &mut term)? {
+        promise::spawn::spawn_into_main_thread(async move {
+            let mux = Mux::get().unwrap();
+            if let Err(err) = mux.activate_pane(pane_id) {
+                log::error!("Failed to activate pane {}: {:#}", pane_id, err);
+            }
+        })
+        .detach();
+    }
+    Ok(())
+}
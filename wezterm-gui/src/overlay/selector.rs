@@ -0,0 +1,153 @@
+//! A simple list selector for overlays that need to ask the user
+//! to choose from a list that is produced by an external command
+use mux::termwiztermtab::TermWizTerminal;
+use termwiz::cell::{AttributeChange, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+
+/// Shows `items` and lets the user pick one of them, by typing to
+/// filter the list and using the arrow keys to select.
+/// Returns None if the user cancelled.
+pub fn choose(
+    term: &mut TermWizTerminal,
+    title: &str,
+    items: &[String],
+    initial: Option<&str>,
+) -> anyhow::Result<Option<String>> {
+    let mut filter = String::new();
+    let mut selected = initial
+        .and_then(|initial| items.iter().position(|item| item == initial))
+        .unwrap_or(0);
+    let mut top_row = 0;
+
+    loop {
+        let filter_lower = filter.to_lowercase();
+        let matches: Vec<&String> = items
+            .iter()
+            .filter(|item| item.to_lowercase().contains(&filter_lower))
+            .collect();
+        selected = selected.min(matches.len().saturating_sub(1));
+
+        let size = term.get_screen_size()?;
+        let max_rows = size.rows.saturating_sub(2).max(1);
+        if selected < top_row {
+            top_row = selected;
+        } else if selected >= top_row + max_rows {
+            top_row = selected + 1 - max_rows;
+        }
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorVisibility(CursorVisibility::Hidden),
+            AttributeChange::Intensity(Intensity::Bold).into(),
+            Change::Text(format!(
+                "{}: {}_   (Enter=select Esc=cancel)",
+                title, filter
+            )),
+            AttributeChange::Intensity(Intensity::Normal).into(),
+        ];
+        if matches.is_empty() {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(2),
+            });
+            changes.push(Change::Text("No matches".to_string()));
+        }
+        for (row, (idx, item)) in matches
+            .iter()
+            .enumerate()
+            .skip(top_row)
+            .take(max_rows)
+            .enumerate()
+        {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(row + 2),
+            });
+            if idx == selected {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+            changes.push(Change::Text(format!(" {} ", item)));
+            if idx == selected {
+                changes.push(AttributeChange::Reverse(false).into());
+            }
+        }
+        term.render(&changes)?;
+        term.flush()?;
+
+        match term.poll_input(None)? {
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }))
+            | Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('g'),
+                modifiers: Modifiers::CTRL,
+            })) => return Ok(None),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            })) => {
+                if let Some(item) = matches.get(selected) {
+                    return Ok(Some(item.to_string()));
+                }
+            }
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            })) => {
+                selected = selected.saturating_sub(1);
+            }
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            })) => {
+                selected += 1;
+            }
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Backspace,
+                ..
+            })) => {
+                filter.pop();
+                selected = 0;
+            }
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE | Modifiers::SHIFT,
+            })) => {
+                filter.push(c);
+                selected = 0;
+            }
+            None => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// Shows an error and waits for a key press before returning
+pub fn show_error(term: &mut TermWizTerminal, err: &anyhow::Error) -> anyhow::Result<()> {
+    let size = term.get_screen_size()?;
+    let message = textwrap::fill(&format!("{:#}", err), size.cols.saturating_sub(1).max(1));
+    let mut changes = vec![
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorVisibility(CursorVisibility::Hidden),
+    ];
+    for (row, line) in message.lines().enumerate() {
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(row),
+        });
+        changes.push(Change::Text(line.to_string()));
+    }
+    changes.push(Change::Text("\r\n\r\nPress any key to close".to_string()));
+    term.render(&changes)?;
+    term.flush()?;
+    while let Some(event) = term.poll_input(None)? {
+        if let InputEvent::Key(_) = event {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,299 @@
+//! Converts the selected lines into HTML and RTF, resolving their
+//! colors through the palette of the pane, so that pasting them into
+//! documents and slides keeps their colors and styles.
+use config::ConfigHandle;
+use termwiz::cell::{CellAttributes, Intensity, Underline};
+use termwiz::surface::Line;
+use wezterm_term::color::ColorPalette;
+use window::ClipboardContent;
+
+type Rgb = (u8, u8, u8);
+
+fn rgb(color: wezterm_term::color::SrgbaTuple) -> Rgb {
+    let (r, g, b, _) = color.to_srgb_u8();
+    (r, g, b)
+}
+
+fn hex((r, g, b): Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Style {
+    fg: Rgb,
+    bg: Rgb,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+}
+
+impl Style {
+    fn new(attrs: &CellAttributes, palette: &ColorPalette) -> Self {
+        let mut fg = rgb(palette.resolve_fg(attrs.foreground()));
+        let mut bg = rgb(palette.resolve_bg(attrs.background()));
+        if attrs.reverse() {
+            std::mem::swap(&mut fg, &mut bg);
+        }
+        if attrs.invisible() {
+            fg = bg;
+        }
+        Self {
+            fg,
+            bg,
+            bold: attrs.intensity() == Intensity::Bold,
+            italic: attrs.italic(),
+            underline: attrs.underline() != Underline::None,
+            strikethrough: attrs.strikethrough(),
+        }
+    }
+}
+
+/// Splits each line into runs of text that share the same style
+fn styled_runs(lines: &[Line], palette: &ColorPalette) -> Vec<Vec<(Style, String)>> {
+    lines
+        .iter()
+        .map(|line| {
+            let mut runs: Vec<(Style, String)> = vec![];
+            for cell in line.visible_cells() {
+                let style = Style::new(cell.attrs(), palette);
+                match runs.last_mut() {
+                    Some((last, text)) if *last == style => text.push_str(cell.str()),
+                    _ => runs.push((style, cell.str().to_string())),
+                }
+            }
+            runs
+        })
+        .collect()
+}
+
+fn escape_html(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Produces a `<pre>` element holding the lines
+pub fn lines_to_html(lines: &[Line], palette: &ColorPalette, font_family: &str) -> String {
+    let default_fg = rgb(palette.foreground);
+    let default_bg = rgb(palette.background);
+    let mut html = format!(
+        "<pre style=\"font-family: '{}', monospace; color: {}; background-color: {};\">",
+        escape_html(&font_family.replace('\'', "")),
+        hex(default_fg),
+        hex(default_bg)
+    );
+
+    for (idx, runs) in styled_runs(lines, palette).into_iter().enumerate() {
+        if idx > 0 {
+            html.push('\n');
+        }
+        for (style, text) in runs {
+            let mut css = vec![];
+            if style.fg != default_fg {
+                css.push(format!("color: {}", hex(style.fg)));
+            }
+            if style.bg != default_bg {
+                css.push(format!("background-color: {}", hex(style.bg)));
+            }
+            if style.bold {
+                css.push("font-weight: bold".to_string());
+            }
+            if style.italic {
+                css.push("font-style: italic".to_string());
+            }
+            match (style.underline, style.strikethrough) {
+                (true, true) => css.push("text-decoration: underline line-through".to_string()),
+                (true, false) => css.push("text-decoration: underline".to_string()),
+                (false, true) => css.push("text-decoration: line-through".to_string()),
+                (false, false) => {}
+            }
+            if css.is_empty() {
+                html.push_str(&escape_html(&text));
+            } else {
+                html.push_str(&format!(
+                    "<span style=\"{}\">{}</span>",
+                    css.join("; "),
+                    escape_html(&text)
+                ));
+            }
+        }
+    }
+
+    html.push_str("</pre>");
+    html
+}
+
+fn escape_rtf(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '{' | '}' => {
+                result.push('\\');
+                result.push(c);
+            }
+            '\t' => result.push_str("\\tab "),
+            ' '..='~' => result.push(c),
+            c => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    result.push_str(&format!("\\u{}?", *unit as i16));
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Produces an RTF document holding the lines
+pub fn lines_to_rtf(
+    lines: &[Line],
+    palette: &ColorPalette,
+    font_family: &str,
+    font_size: f64,
+) -> String {
+    let runs = styled_runs(lines, palette);
+
+    // Index 0 of the color table is the "auto" color, so the
+    // colors that are referenced start at 1
+    let mut colors = vec![rgb(palette.foreground), rgb(palette.background)];
+    let mut color_index = |color: Rgb| match colors.iter().position(|&c| c == color) {
+        Some(idx) => idx + 1,
+        None => {
+            colors.push(color);
+            colors.len()
+        }
+    };
+
+    let mut body = String::new();
+    for (idx, line) in runs.iter().enumerate() {
+        if idx > 0 {
+            body.push_str("\\line\n");
+        }
+        for (style, text) in line {
+            let fg = color_index(style.fg);
+            let bg = color_index(style.bg);
+            body.push_str(&format!("{{\\cf{} \\chcbpat{} \\cb{}", fg, bg, bg));
+            if style.bold {
+                body.push_str("\\b");
+            }
+            if style.italic {
+                body.push_str("\\i");
+            }
+            if style.underline {
+                body.push_str("\\ul");
+            }
+            if style.strikethrough {
+                body.push_str("\\strike");
+            }
+            body.push(' ');
+            body.push_str(&escape_rtf(text));
+            body.push('}');
+        }
+    }
+
+    let color_table: String = colors
+        .iter()
+        .map(|(r, g, b)| format!("\\red{}\\green{}\\blue{};", r, g, b))
+        .collect();
+
+    format!(
+        "{{\\rtf1\\ansi\\deff0{{\\fonttbl{{\\f0\\fmodern {};}}}}{{\\colortbl;{}}}\n\
+         \\f0\\fs{}\n{}}}",
+        escape_rtf(font_family),
+        color_table,
+        (font_size * 2.).round() as usize,
+        body
+    )
+}
+
+/// Returns the selection as plain text along with HTML and RTF
+/// versions of it that use the font from the configuration
+pub fn clipboard_content(
+    text: String,
+    lines: &[Line],
+    palette: &ColorPalette,
+    config: &ConfigHandle,
+) -> ClipboardContent {
+    let font_family = config
+        .font
+        .font
+        .first()
+        .map(|attr| attr.family.as_str())
+        .unwrap_or("monospace");
+    ClipboardContent {
+        text,
+        html: Some(lines_to_html(lines, palette, font_family)),
+        rtf: Some(lines_to_rtf(lines, palette, font_family, config.font_size)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::cell::AttributeChange;
+    use termwiz::color::{AnsiColor, ColorAttribute};
+    use termwiz::surface::{Change, Surface};
+
+    fn lines(changes: Vec<Change>) -> Vec<Line> {
+        let mut surface = Surface::new(20, 2);
+        surface.add_changes(changes);
+        let mut lines = surface
+            .screen_lines()
+            .into_iter()
+            .map(|l| l.into_owned())
+            .collect::<Vec<_>>();
+        for line in &mut lines {
+            line.prune_trailing_blanks(0);
+        }
+        lines
+    }
+
+    fn sample() -> Vec<Line> {
+        lines(vec![
+            Change::Text("a<b ".into()),
+            AttributeChange::Foreground(ColorAttribute::PaletteIndex(AnsiColor::Red as u8)).into(),
+            AttributeChange::Intensity(Intensity::Bold).into(),
+            Change::Text("red".into()),
+            Change::AllAttributes(CellAttributes::default()),
+            Change::Text("\r\n{é}".into()),
+        ])
+    }
+
+    #[test]
+    fn html() {
+        let palette = ColorPalette::default();
+        let red = hex(rgb(palette.colors.0[1]));
+        assert_eq!(
+            lines_to_html(&sample(), &palette, "Fira Code"),
+            format!(
+                "<pre style=\"font-family: 'Fira Code', monospace; color: {}; \
+                 background-color: {};\">a&lt;b <span style=\"color: {}; \
+                 font-weight: bold\">red</span>\n{{é}}</pre>",
+                hex(rgb(palette.foreground)),
+                hex(rgb(palette.background)),
+                red
+            )
+        );
+    }
+
+    #[test]
+    fn rtf() {
+        let palette = ColorPalette::default();
+        let rtf = lines_to_rtf(&sample(), &palette, "Fira Code", 12.0);
+        assert!(rtf.starts_with("{\\rtf1\\ansi\\deff0{\\fonttbl{\\f0\\fmodern Fira Code;}}"));
+        assert!(rtf.contains("\\fs24\n"));
+        assert!(rtf.contains("{\\cf1 \\chcbpat2 \\cb2 a<b }"));
+        assert!(rtf.contains("{\\cf3 \\chcbpat2 \\cb2\\b red}\\line\n"));
+        assert!(rtf.contains("{\\cf1 \\chcbpat2 \\cb2 \\{\\u233?\\}}"));
+        assert!(rtf.ends_with("}}"));
+    }
+}
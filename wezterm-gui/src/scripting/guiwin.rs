@@ -93,6 +93,10 @@ impl UserData for GuiWin {
             this.window.notify(TermWindowNotif::SetLeftStatus(status));
             Ok(())
         });
+        methods.add_method("set_subtitle", |_, this, subtitle: String| {
+            this.window.set_subtitle(&subtitle);
+            Ok(())
+        });
         methods.add_async_method("get_dimensions", |_, this, _: ()| async move {
             let (tx, rx) = smol::channel::bounded(1);
             this.window.notify(TermWindowNotif::GetDimensions(tx));
@@ -137,6 +141,17 @@ impl UserData for GuiWin {
                 Ok(text)
             },
         );
+        methods.add_async_method("capture_image", |lua, this, _: ()| async move {
+            let (tx, rx) = smol::channel::bounded(1);
+            this.window.notify(TermWindowNotif::CaptureImage(tx));
+            let png = rx
+                .recv()
+                .await
+                .map_err(|e| anyhow::anyhow!("{:#}", e))
+                .and_then(|result| result)
+                .map_err(luaerr)?;
+            lua.create_string(&png)
+        });
         methods.add_async_method("current_event", |lua, this, _: ()| async move {
             let (tx, rx) = smol::channel::bounded(1);
             this.window
@@ -146,6 +161,15 @@ impl UserData for GuiWin {
             let result = rx.recv().await.map_err(mlua::Error::external)?;
             luahelper::dynamic_to_lua_value(lua, result)
         });
+        methods.add_method("request_animation_frame", |_, this, _: ()| {
+            let window = this.window.clone();
+            this.window
+                .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                    term_window.animation_frame_requested = true;
+                    window.invalidate();
+                })));
+            Ok(())
+        });
         methods.add_method(
             "perform_action",
             |_, this, (assignment, pane): (KeyAssignment, MuxPane)| {
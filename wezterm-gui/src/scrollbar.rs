@@ -46,6 +46,20 @@ impl ScrollHit {
         }
     }
 
+    /// Compute the offset from the top of the scroll bar that
+    /// corresponds to the stable row index `row`
+    pub fn row_offset(pane: &dyn Pane, row: StableRowIndex, max_thumb_height: usize) -> usize {
+        let render_dims = pane.get_dimensions();
+        let total = (render_dims.physical_top + render_dims.viewport_rows as StableRowIndex)
+            .saturating_sub(render_dims.scrollback_top)
+            .max(1);
+        let row = row
+            .saturating_sub(render_dims.scrollback_top)
+            .max(0)
+            .min(total - 1);
+        ((row as f32 / total as f32) * max_thumb_height as f32) as usize
+    }
+
     /// Given a new thumb top coordinate (produced by dragging the thumb),
     /// compute the equivalent viewport offset.
     pub fn thumb_top_to_scroll_top(
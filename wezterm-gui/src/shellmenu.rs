@@ -0,0 +1,134 @@
+//! Computes the content of the menu that the operating system shell
+//! presents for wezterm; the taskbar jump list on Windows and the
+//! Dock menu on macOS
+use ::window::{ShellMenuGroup, ShellMenuItem};
+use config::keyassignment::{SpawnCommand, SpawnTabDomain};
+use config::ConfigHandle;
+use mux::Mux;
+
+/// Returns the command line arguments for wezterm-gui that will spawn
+/// `cmd`, or None if that isn't expressible on the command line
+fn launch_menu_args(cmd: &SpawnCommand) -> Option<Vec<String>> {
+    let mut args = match &cmd.domain {
+        SpawnTabDomain::DefaultDomain | SpawnTabDomain::CurrentPaneDomain => {
+            let mut args = vec!["start".to_string()];
+            if let Some(cwd) = &cmd.cwd {
+                args.push("--cwd".to_string());
+                args.push(cwd.to_string_lossy().into_owned());
+            }
+            args
+        }
+        SpawnTabDomain::DomainName(name) => vec!["connect".to_string(), name.to_string()],
+        SpawnTabDomain::DomainId(_) => return None,
+    };
+    if let Some(prog) = &cmd.args {
+        args.push("--".to_string());
+        args.extend(prog.iter().cloned());
+    }
+    Some(args)
+}
+
+fn launch_menu_label(cmd: &SpawnCommand) -> String {
+    match (&cmd.label, &cmd.args) {
+        (Some(label), _) => label.to_string(),
+        (None, Some(args)) => args.join(" "),
+        (None, None) => match &cmd.domain {
+            SpawnTabDomain::DomainName(name) => name.to_string(),
+            _ => "Default".to_string(),
+        },
+    }
+}
+
+/// Computes the menu from the configuration and the names of the
+/// workspaces, which are ordered from most to least recently used
+pub fn compute_shell_menu(config: &ConfigHandle, workspaces: &[String]) -> Vec<ShellMenuGroup> {
+    let mut groups = vec![];
+
+    if config.shell_menu_include_launch_menu {
+        let items: Vec<ShellMenuItem> = config
+            .launch_menu
+            .iter()
+            .filter_map(|cmd| {
+                let args = launch_menu_args(cmd)?;
+                Some(ShellMenuItem {
+                    label: launch_menu_label(cmd),
+                    description: cmd.args.as_ref().map(|args| args.join(" ")),
+                    args,
+                })
+            })
+            .collect();
+        if !items.is_empty() {
+            groups.push(ShellMenuGroup { title: None, items });
+        }
+    }
+
+    let items: Vec<ShellMenuItem> = workspaces
+        .iter()
+        .take(config.shell_menu_recent_workspaces)
+        .map(|name| ShellMenuItem {
+            label: name.to_string(),
+            description: Some(format!("Open the {} workspace", name)),
+            args: vec![
+                "start".to_string(),
+                "--workspace".to_string(),
+                name.to_string(),
+            ],
+        })
+        .collect();
+    if !items.is_empty() {
+        groups.push(ShellMenuGroup {
+            title: Some("Recent Workspaces".to_string()),
+            items,
+        });
+    }
+
+    groups
+}
+
+/// Returns the names of the workspaces, most recently used first
+pub fn recent_workspaces(mux: &Mux) -> Vec<String> {
+    let mut workspaces = mux.iter_workspaces();
+    // iter_workspaces is sorted by name, so workspaces without any
+    // recorded activity retain that order
+    workspaces.sort_by(|a, b| {
+        mux.workspace_last_activity(b)
+            .cmp(&mux.workspace_last_activity(a))
+    });
+    workspaces
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn launch_menu() {
+        let cmd = SpawnCommand {
+            label: Some("Top".to_string()),
+            args: Some(vec!["top".to_string()]),
+            cwd: Some("/tmp".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            launch_menu_args(&cmd).unwrap(),
+            vec!["start", "--cwd", "/tmp", "--", "top"]
+        );
+        assert_eq!(launch_menu_label(&cmd), "Top");
+
+        let cmd = SpawnCommand {
+            domain: SpawnTabDomain::DomainName("WSL:Ubuntu".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            launch_menu_args(&cmd).unwrap(),
+            vec!["connect", "WSL:Ubuntu"]
+        );
+        assert_eq!(launch_menu_label(&cmd), "WSL:Ubuntu");
+
+        let cmd = SpawnCommand {
+            domain: SpawnTabDomain::DomainId(1),
+            ..Default::default()
+        };
+        assert_eq!(launch_menu_args(&cmd), None);
+    }
+}
@@ -0,0 +1,95 @@
+//! Plays sound files for the `audible_bell`.
+//! Rather than linking in an audio stack, the file is handed to the
+//! player that ships with the system.
+use anyhow::Context;
+use ratelim::RateLimiter;
+use std::sync::Mutex;
+
+lazy_static::lazy_static! {
+    static ref BELL_LIMITER: Mutex<RateLimiter> = Mutex::new(RateLimiter::new(|config| {
+        config.audible_bell_max_per_second.max(1)
+    }));
+}
+
+/// Returns true if the bell may make a sound now, or false if
+/// it has already made `audible_bell_max_per_second` sounds
+/// in the last second
+pub fn bell_admitted() -> bool {
+    BELL_LIMITER
+        .lock()
+        .unwrap()
+        .non_blocking_admittance_check(1)
+}
+
+/// Plays the sound file at `path` with the specified volume,
+/// in the range 0.0-1.0, in the background
+pub fn play_sound_file(path: String, volume: f32) {
+    std::thread::spawn(move || {
+        if let Err(err) = play(&path, volume) {
+            log::error!("Failed to play {}: {:#}", path, err);
+        }
+    });
+}
+
+#[cfg(target_os = "macos")]
+fn play(path: &str, volume: f32) -> anyhow::Result<()> {
+    run_player("afplay", &["-v", &format!("{:.2}", volume), path])
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn play(path: &str, volume: f32) -> anyhow::Result<()> {
+    // paplay expresses volume in the range 0-65536 (100%)
+    let pa_volume = format!("--volume={}", (volume * 65536.) as u32);
+    let pw_volume = format!("--volume={:.2}", volume);
+    let players: Vec<(&str, Vec<&str>)> = vec![
+        ("paplay", vec![&pa_volume, path]),
+        ("pw-play", vec![&pw_volume, path]),
+        // aplay has no volume control
+        ("aplay", vec!["-q", path]),
+    ];
+
+    let mut errors = vec![];
+    for (player, args) in players {
+        match run_player(player, &args) {
+            Ok(()) => return Ok(()),
+            Err(err) => errors.push(format!("{:#}", err)),
+        }
+    }
+    anyhow::bail!("no sound player succeeded: {}", errors.join(", "));
+}
+
+#[cfg(windows)]
+fn play(path: &str, _volume: f32) -> anyhow::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::um::playsoundapi::{PlaySoundW, SND_FILENAME, SND_NODEFAULT, SND_SYNC};
+
+    let wide: Vec<u16> = std::ffi::OsStr::new(path)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    // We're running on our own thread, so we can play synchronously.
+    // PlaySound doesn't support adjusting the volume.
+    let ok = unsafe {
+        PlaySoundW(
+            wide.as_ptr(),
+            std::ptr::null_mut(),
+            SND_FILENAME | SND_NODEFAULT | SND_SYNC,
+        )
+    };
+    anyhow::ensure!(ok != 0, "PlaySoundW failed");
+    Ok(())
+}
+
+#[cfg(unix)]
+fn run_player(player: &str, args: &[&str]) -> anyhow::Result<()> {
+    use std::process::{Command, Stdio};
+    let status = Command::new(player)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .with_context(|| format!("running {}", player))?;
+    anyhow::ensure!(status.success(), "{} exited with {}", player, status);
+    Ok(())
+}
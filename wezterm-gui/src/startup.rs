@@ -0,0 +1,110 @@
+//! Keeps track of how long each phase of starting the GUI takes, and
+//! defers work that isn't needed to show the first window until that
+//! window has painted its first frame.
+//!
+//! The timeline is printed when `--profile-startup` is passed.
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+lazy_static::lazy_static! {
+    static ref START: Instant = Instant::now();
+    static ref TIMELINE: Mutex<Vec<(Duration, String)>> = Mutex::new(vec![]);
+}
+
+static PROFILE: AtomicBool = AtomicBool::new(false);
+static FIRST_FRAME: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static DEFERRED: RefCell<Vec<Box<dyn FnOnce()>>> = RefCell::new(vec![]);
+}
+
+/// Records that a phase of startup has completed
+pub fn mark(label: &str) {
+    let elapsed = START.elapsed();
+    log::trace!("startup: {} at {:?}", label, elapsed);
+    if !FIRST_FRAME.load(Ordering::Relaxed) {
+        TIMELINE.lock().unwrap().push((elapsed, label.to_string()));
+    }
+}
+
+pub fn enable_profiling() {
+    PROFILE.store(true, Ordering::Relaxed);
+}
+
+/// Runs `func` on the main thread once the first frame has been
+/// painted, or immediately if it already has been
+pub fn after_first_frame<F: FnOnce() + 'static>(func: F) {
+    if FIRST_FRAME.load(Ordering::Relaxed) {
+        func();
+    } else {
+        DEFERRED.with(|deferred| deferred.borrow_mut().push(Box::new(func)));
+    }
+}
+
+/// Runs the work that was deferred until after the first frame.
+/// This is also called after a timeout, so that the work isn't
+/// held up indefinitely if the window is never painted.
+pub fn run_deferred() {
+    let deferred = DEFERRED.with(|deferred| std::mem::take(&mut *deferred.borrow_mut()));
+    for func in deferred {
+        func();
+    }
+}
+
+fn format_timeline(timeline: &[(Duration, String)]) -> String {
+    let mut result = "startup timeline:\n".to_string();
+    let mut prior = Duration::ZERO;
+    for (elapsed, label) in timeline {
+        result.push_str(&format!(
+            "{:>9.1}ms {:>+9.1}ms  {}\n",
+            elapsed.as_secs_f64() * 1000.,
+            (*elapsed - prior).as_secs_f64() * 1000.,
+            label
+        ));
+        prior = *elapsed;
+    }
+    result
+}
+
+/// Called each time that a frame has been painted
+pub fn frame_painted() {
+    if FIRST_FRAME.load(Ordering::Relaxed) {
+        return;
+    }
+    mark("first frame painted");
+    FIRST_FRAME.store(true, Ordering::Relaxed);
+
+    let timeline = std::mem::take(&mut *TIMELINE.lock().unwrap());
+    if PROFILE.load(Ordering::Relaxed) {
+        eprint!("{}", format_timeline(&timeline));
+    } else if let Some((elapsed, _)) = timeline.last() {
+        log::debug!("first frame painted after {:?}", elapsed);
+    }
+    run_deferred();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timeline() {
+        let timeline = vec![
+            (Duration::from_millis(0), "process start".to_string()),
+            (
+                Duration::from_micros(12_500),
+                "configuration loaded".to_string(),
+            ),
+            (Duration::from_millis(40), "first frame painted".to_string()),
+        ];
+        assert_eq!(
+            format_timeline(&timeline),
+            "startup timeline:\n\
+             \x20     0.0ms      +0.0ms  process start\n\
+             \x20    12.5ms     +12.5ms  configuration loaded\n\
+             \x20    40.0ms     +27.5ms  first frame painted\n"
+        );
+    }
+}
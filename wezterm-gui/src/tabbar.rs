@@ -2,13 +2,16 @@ use crate::termwindow::{PaneInformation, TabInformation, UIItem, UIItemType};
 use config::{ConfigHandle, TabBarColors};
 use finl_unicode::grapheme_clusters::Graphemes;
 use mlua::FromLua;
-use termwiz::cell::{unicode_column_width, Cell, CellAttributes};
+use mux::Mux;
+use termwiz::cell::{
+    unicode_column_width, AttributeChange, Cell, CellAttributes, Intensity, Underline,
+};
 use termwiz::color::ColorSpec;
 use termwiz::escape::csi::Sgr;
 use termwiz::escape::parser::Parser;
 use termwiz::escape::{Action, ControlCode, CSI};
 use termwiz::surface::SEQ_ZERO;
-use termwiz_funcs::{format_as_escapes, FormatItem};
+use termwiz_funcs::{format_as_escapes, FormatColor, FormatItem};
 use wezterm_term::Line;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -100,6 +103,173 @@ fn call_format_tab_title(
     }
 }
 
+/// Returns the item that corresponds to an attribute placeholder
+/// in `tab_title_format`, such as `{bold}` or `{fg:#ff0000}`
+fn title_format_attribute(name: &str) -> Option<FormatItem> {
+    let color = |spec: &str| {
+        if spec == "default" {
+            FormatColor::Default
+        } else {
+            FormatColor::Color(spec.to_string())
+        }
+    };
+    if let Some(spec) = name.strip_prefix("fg:") {
+        return Some(FormatItem::Foreground(color(spec)));
+    }
+    if let Some(spec) = name.strip_prefix("bg:") {
+        return Some(FormatItem::Background(color(spec)));
+    }
+    let change = match name {
+        "bold" => AttributeChange::Intensity(Intensity::Bold),
+        "half" => AttributeChange::Intensity(Intensity::Half),
+        "normal" => AttributeChange::Intensity(Intensity::Normal),
+        "italic" => AttributeChange::Italic(true),
+        "underline" => AttributeChange::Underline(Underline::Single),
+        "reset" => return Some(FormatItem::ResetAttributes),
+        _ => return None,
+    };
+    Some(FormatItem::Attribute(change))
+}
+
+/// Expands a `tab_title_format` template.  `lookup` resolves the
+/// value of a variable placeholder, returning None if the variable
+/// is not known, in which case the placeholder is kept as-is.
+/// `{{` and `}}` produce literal braces.
+fn expand_title_format(template: &str, lookup: impl Fn(&str) -> Option<String>) -> Vec<FormatItem> {
+    let mut items = vec![];
+    let mut text = String::new();
+    let mut remain = template;
+
+    while let Some(start) = remain.find(|c| c == '{' || c == '}') {
+        text.push_str(&remain[..start]);
+        let rest = &remain[start..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            text.push_str(&rest[..1]);
+            remain = &rest[2..];
+            continue;
+        }
+        let end = match rest.find('}') {
+            Some(end) if rest.starts_with('{') => end,
+            _ => {
+                // A stray brace
+                text.push_str(&rest[..1]);
+                remain = &rest[1..];
+                continue;
+            }
+        };
+        let name = &rest[1..end];
+        if let Some(item) = title_format_attribute(name) {
+            if !text.is_empty() {
+                items.push(FormatItem::Text(std::mem::take(&mut text)));
+            }
+            items.push(item);
+        } else {
+            match lookup(name) {
+                Some(value) => text.push_str(&value),
+                None => text.push_str(&rest[..=end]),
+            }
+        }
+        remain = &rest[end + 1..];
+    }
+    text.push_str(remain);
+    if !text.is_empty() {
+        items.push(FormatItem::Text(text));
+    }
+    items
+}
+
+/// Expands a `window_title_format` template.  Window titles are
+/// plain text, so the placeholders that style text are removed.
+pub(crate) fn expand_window_title_format(
+    template: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> String {
+    expand_title_format(template, lookup)
+        .into_iter()
+        .filter_map(|item| match item {
+            FormatItem::Text(text) => Some(text),
+            _ => None,
+        })
+        .collect()
+}
+
+fn basename(path: &str) -> String {
+    path.rsplit(|c| c == '/' || c == '\\')
+        .find(|component| !component.is_empty())
+        .unwrap_or(path)
+        .to_string()
+}
+
+/// Produces the title of the tab from the `tab_title_format` template
+fn format_tab_title_template(
+    template: &str,
+    tab: &TabInformation,
+    config: &ConfigHandle,
+) -> TitleText {
+    let pane_info = tab.active_pane.as_ref();
+    let pane = pane_info.and_then(|info| Mux::get()?.get_pane(info.pane_id));
+    let cwd = || {
+        pane.as_ref()
+            .and_then(|pane| pane.get_current_working_dir())
+            .map(|url| {
+                percent_encoding::percent_decode_str(url.path())
+                    .decode_utf8_lossy()
+                    .to_string()
+            })
+            .unwrap_or_default()
+    };
+
+    let mut items = expand_title_format(template, |name| {
+        Some(match name {
+            "index" => (tab.tab_index
+                + if config.tab_and_split_indices_are_zero_based {
+                    0
+                } else {
+                    1
+                })
+            .to_string(),
+            "title" => pane_info.map(|info| info.title.clone()).unwrap_or_default(),
+            "badge" => pane_info
+                .and_then(|info| info.badge.clone())
+                .unwrap_or_default(),
+            "process" => pane
+                .as_ref()
+                .and_then(|pane| pane.get_foreground_process_name())
+                .map(|name| basename(&name))
+                .unwrap_or_default(),
+            "cwd" => {
+                let cwd = cwd();
+                let home = config::HOME_DIR.to_string_lossy();
+                match cwd.strip_prefix(&*home) {
+                    Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+                        format!("~{}", rest)
+                    }
+                    _ => cwd,
+                }
+            }
+            "cwd:short" => basename(&cwd()),
+            "domain" => pane
+                .as_ref()
+                .and_then(|pane| {
+                    Mux::get()?
+                        .get_domain(pane.domain_id())
+                        .map(|domain| domain.domain_name().to_string())
+                })
+                .unwrap_or_default(),
+            _ => return None,
+        })
+    });
+
+    let esc = format_as_escapes(items.clone()).unwrap_or_default();
+    let mut len = parse_status_text(&esc, CellAttributes::default()).len();
+    // Keep the same soft minimum width as the default titles
+    if !config.use_fancy_tab_bar && len < 5 {
+        items.push(FormatItem::Text(" ".repeat(5 - len)));
+        len = 5;
+    }
+    TitleText { items, len }
+}
+
 fn compute_tab_title(
     tab: &TabInformation,
     tab_info: &[TabInformation],
@@ -112,9 +282,16 @@ fn compute_tab_title(
 
     match title {
         Some(title) => title,
+        None if !config.tab_title_format.is_empty() => {
+            format_tab_title_template(&config.tab_title_format, tab, config)
+        }
         None => {
             let title = if let Some(pane) = &tab.active_pane {
-                let mut title = pane.title.clone();
+                let pane_title = match &pane.badge {
+                    Some(badge) => format!("{} [{}]", pane.title, badge),
+                    None => pane.title.clone(),
+                };
+                let mut title = pane_title.clone();
                 let classic_spacing = if config.use_fancy_tab_bar { "" } else { " " };
                 if config.show_tab_index_in_tab_bar {
                     title = format!(
@@ -126,7 +303,7 @@ fn compute_tab_title(
                             } else {
                                 1
                             },
-                        pane.title,
+                        pane_title,
                         classic_spacing,
                     );
                 }
@@ -499,3 +676,48 @@ pub fn parse_status_text(text: &str, default_cell: CellAttributes) -> Line {
     flush_print(&mut print_buffer, &mut cells, &pen);
     Line::from_cells(cells, SEQ_ZERO)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "index" => Some("1".to_string()),
+            "cwd:short" => Some("src".to_string()),
+            "process" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn title_format() {
+        assert_eq!(
+            expand_title_format("{index}: {cwd:short}{process}", lookup),
+            vec![FormatItem::Text("1: src".to_string())]
+        );
+        assert_eq!(
+            expand_title_format("{bold}{index}{reset} {{x}} {nope} {", lookup),
+            vec![
+                FormatItem::Attribute(AttributeChange::Intensity(Intensity::Bold)),
+                FormatItem::Text("1".to_string()),
+                FormatItem::ResetAttributes,
+                FormatItem::Text(" {x} {nope} {".to_string()),
+            ]
+        );
+        assert_eq!(
+            expand_title_format("{fg:#ff0000}{index}", lookup),
+            vec![
+                FormatItem::Foreground(FormatColor::Color("#ff0000".to_string())),
+                FormatItem::Text("1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn basenames() {
+        assert_eq!(basename("/home/user/src/"), "src");
+        assert_eq!(basename("C:\\Users\\user"), "user");
+        assert_eq!(basename("/"), "/");
+    }
+}
@@ -0,0 +1,210 @@
+//! Exposes the visible text of the active pane to assistive technology
+//! such as screen readers, when `enable_screen_reader_support` is set
+use crate::TermWindow;
+use ::window::{AccessibleText, Window, WindowOps};
+use mux::pane::PaneId;
+use termwiz::surface::Line;
+use wezterm_term::StableRowIndex;
+
+/// Limits how many lines of new output are announced in one go,
+/// so that a burst of output doesn't flood the screen reader
+const MAX_ANNOUNCED_LINES: usize = 10;
+
+/// Remembers what was last sent to the window, so that we only
+/// update the accessibility tree when something changed
+#[derive(Default)]
+pub struct AccessibleState {
+    pane_id: Option<PaneId>,
+    top: StableRowIndex,
+    lines: Vec<String>,
+    cursor: usize,
+}
+
+/// Returns the text of a line with trailing whitespace removed,
+/// along with the character offset of `cursor_x`, if specified.
+/// Trailing whitespace before the cursor is preserved so that the
+/// cursor position remains meaningful.
+fn line_text(line: &Line, cursor_x: Option<usize>) -> (String, Option<usize>) {
+    let mut text = String::new();
+    let mut num_chars = 0;
+    let mut cursor = None;
+    let mut next_col = 0;
+
+    for cell in line.visible_cells() {
+        if let Some(x) = cursor_x {
+            if cursor.is_none() && cell.cell_index() >= x {
+                cursor.replace(num_chars);
+            }
+        }
+        text.push_str(cell.str());
+        num_chars += cell.str().chars().count();
+        next_col = cell.cell_index() + cell.width();
+    }
+
+    let mut text = text.trim_end().to_string();
+    if let Some(x) = cursor_x {
+        let cursor = *cursor.get_or_insert_with(|| num_chars + x.saturating_sub(next_col));
+        let len = text.chars().count();
+        if cursor > len {
+            text.extend(std::iter::repeat(' ').take(cursor - len));
+        }
+    }
+
+    (text, cursor)
+}
+
+/// Computes the text of the viewport, one entry per line, along with
+/// the character offset of the cursor within the text that results
+/// from joining those lines with newlines
+fn viewport_text(
+    lines: &[Line],
+    cursor_row: Option<usize>,
+    cursor_x: usize,
+) -> (Vec<String>, usize) {
+    let mut texts = vec![];
+    let mut offset = 0;
+    let mut cursor = None;
+
+    for (idx, line) in lines.iter().enumerate() {
+        let is_cursor_row = cursor_row == Some(idx);
+        let (text, line_cursor) =
+            line_text(line, if is_cursor_row { Some(cursor_x) } else { None });
+        if let Some(line_cursor) = line_cursor {
+            cursor.replace(offset + line_cursor);
+        }
+        offset += text.chars().count() + 1;
+        texts.push(text);
+    }
+
+    (texts, cursor.unwrap_or(0))
+}
+
+/// Returns the lines that should be announced as new output,
+/// given the previous and current content of the viewport
+fn new_output(
+    prior_top: StableRowIndex,
+    prior: &[String],
+    top: StableRowIndex,
+    current: &[String],
+    cursor_row: Option<usize>,
+) -> Option<String> {
+    let mut changed = vec![];
+    for (idx, text) in current.iter().enumerate() {
+        if Some(idx) == cursor_row || text.is_empty() {
+            // Changes on the cursor line are typically the result of
+            // typing, which the screen reader echoes on its own
+            continue;
+        }
+        let row = top + idx as StableRowIndex;
+        let prior_text = if row >= prior_top {
+            prior.get((row - prior_top) as usize)
+        } else {
+            None
+        };
+        if prior_text != Some(text) {
+            changed.push(text.as_str());
+        }
+    }
+
+    if changed.is_empty() {
+        return None;
+    }
+    let skip = changed.len().saturating_sub(MAX_ANNOUNCED_LINES);
+    Some(changed[skip..].join("\n"))
+}
+
+impl TermWindow {
+    /// Sends the visible text of the active pane to the window so
+    /// that it can be exposed to screen readers
+    pub(crate) fn update_accessible_text(&mut self, window: &Window) {
+        if !self.config.enable_screen_reader_support {
+            return;
+        }
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+        let pane_id = pane.pane_id();
+        let dims = pane.get_dimensions();
+        let top = self.get_viewport(pane_id).unwrap_or(dims.physical_top);
+        let (top, lines) = pane.get_lines(top..top + dims.viewport_rows as StableRowIndex);
+
+        let cursor = pane.get_cursor_position();
+        let cursor_row = if cursor.y >= top && cursor.y < top + lines.len() as StableRowIndex {
+            Some((cursor.y - top) as usize)
+        } else {
+            None
+        };
+        let (texts, cursor_offset) = viewport_text(&lines, cursor_row, cursor.x);
+
+        let state = &mut self.accessible_state;
+        if state.pane_id == Some(pane_id) && state.lines == texts && state.cursor == cursor_offset {
+            return;
+        }
+
+        // Only announce output when the content changed underneath
+        // the same pane; switching panes or focus re-reads the whole
+        // content anyway
+        let announcement = if state.pane_id == Some(pane_id) {
+            new_output(state.top, &state.lines, top, &texts, cursor_row)
+        } else {
+            None
+        };
+
+        window.set_accessible_text(AccessibleText {
+            text: texts.join("\n"),
+            cursor: cursor_offset,
+            announcement,
+        });
+
+        *state = AccessibleState {
+            pane_id: Some(pane_id),
+            top,
+            lines: texts,
+            cursor: cursor_offset,
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::surface::SEQ_ZERO;
+
+    fn lines(text: &[&str]) -> Vec<Line> {
+        text.iter()
+            .map(|t| Line::from_text(t, &Default::default(), SEQ_ZERO, None))
+            .collect()
+    }
+
+    #[test]
+    fn viewport() {
+        let lines = lines(&["hello   ", "$ ", ""]);
+        let (texts, cursor) = viewport_text(&lines, Some(1), 2);
+        assert_eq!(texts, vec!["hello", "$ ", ""]);
+        // "hello\n$ " puts the cursor after the prompt
+        assert_eq!(cursor, 8);
+
+        let (texts, cursor) = viewport_text(&lines, Some(2), 3);
+        assert_eq!(texts, vec!["hello", "$", "   "]);
+        assert_eq!(cursor, 11);
+    }
+
+    #[test]
+    fn announcements() {
+        let prior = vec!["$ ls".to_string(), "$ ".to_string()];
+        let current = vec!["$ ls".to_string(), "foo bar".to_string(), "$ ".to_string()];
+        assert_eq!(
+            new_output(0, &prior, 0, &current, Some(2)),
+            Some("foo bar".to_string())
+        );
+
+        // Scrolling the same content doesn't announce anything
+        assert_eq!(new_output(0, &current, 1, &current[1..], Some(1)), None);
+
+        let many: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+        let announced = new_output(0, &[], 0, &many, None).unwrap();
+        assert_eq!(announced.lines().count(), MAX_ANNOUNCED_LINES);
+        assert!(announced.ends_with("19"));
+    }
+}
@@ -0,0 +1,104 @@
+//! Requires the user to authenticate before performing the key
+//! assignments that match `authenticate_actions`
+use crate::termwindow::TermWindowNotif;
+use crate::TermWindow;
+use ::window::{Connection, ConnectionOps, WindowOps};
+use config::keyassignment::KeyAssignment;
+use mux::pane::Pane;
+use mux::Mux;
+use std::rc::Rc;
+
+impl TermWindow {
+    /// Returns the reason to present to the user if `assignment`
+    /// requires them to authenticate before it can be performed
+    /// in `pane`
+    fn authentication_reason(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        assignment: &KeyAssignment,
+    ) -> Option<String> {
+        if self.config.authenticate_actions.is_empty() {
+            return None;
+        }
+        if self.authenticated_assignment.take().as_ref() == Some(assignment) {
+            return None;
+        }
+
+        let name = assignment.name();
+        let domain_name = Mux::get()
+            .and_then(|mux| mux.get_domain(pane.domain_id()))
+            .map(|domain| domain.domain_name().to_string());
+        self.config
+            .authenticate_actions
+            .iter()
+            .find(|rule| rule.matches(&name, domain_name.as_deref()))
+            .map(|rule| {
+                rule.reason
+                    .clone()
+                    .unwrap_or_else(|| format!("perform {}", name))
+            })
+    }
+
+    /// If `assignment` requires authentication, asks the user to
+    /// authenticate and performs it once they have done so, returning
+    /// true.  Returns false if the assignment can be performed now.
+    pub(crate) fn authenticate_before_assignment(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        assignment: &KeyAssignment,
+    ) -> bool {
+        let reason = match self.authentication_reason(pane, assignment) {
+            Some(reason) => reason,
+            None => return false,
+        };
+
+        let future = match Connection::get().and_then(|conn| conn.authenticate_user(&reason)) {
+            Some(future) => future,
+            None => {
+                log::warn!(
+                    "authenticate_actions: authentication is not available \
+                     on this system, performing {} without it",
+                    assignment.name()
+                );
+                return false;
+            }
+        };
+
+        let window = match self.window.clone() {
+            Some(window) => window,
+            None => return true,
+        };
+        let pane_id = pane.pane_id();
+        let assignment = assignment.clone();
+        promise::spawn::spawn(async move {
+            match future.await {
+                Ok(true) => {
+                    window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                        let pane = match term_window.get_active_pane_or_overlay() {
+                            Some(pane) if pane.pane_id() == pane_id => pane,
+                            _ => match Mux::get().and_then(|mux| mux.get_pane(pane_id)) {
+                                Some(pane) => pane,
+                                None => return,
+                            },
+                        };
+                        term_window
+                            .authenticated_assignment
+                            .replace(assignment.clone());
+                        if let Err(err) = term_window.perform_key_assignment(&pane, &assignment) {
+                            log::error!("Error performing {:?}: {:#}", assignment, err);
+                        }
+                    })));
+                }
+                Ok(false) => {
+                    log::info!("Authentication for {} was declined", assignment.name());
+                }
+                Err(err) => {
+                    log::error!("Authentication for {} failed: {:#}", assignment.name(), err);
+                }
+            }
+        })
+        .detach();
+
+        true
+    }
+}
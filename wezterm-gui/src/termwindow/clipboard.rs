@@ -1,13 +1,69 @@
-use crate::termwindow::TermWindowNotif;
+use crate::scripting::guiwin::GuiWin;
+use crate::termwindow::{pastereview, TermWindowNotif};
 use crate::TermWindow;
 use config::keyassignment::{ClipboardCopyDestination, ClipboardPasteSource};
-use mux::pane::Pane;
+use config::WslDomain;
+use mlua::FromLua;
+use mux::pane::{Pane, PaneId};
 use mux::Mux;
+use mux_lua::MuxPane;
 use std::rc::Rc;
-use window::{Clipboard, WindowOps};
+use std::sync::Mutex;
+use window::{Clipboard, ClipboardContent, Window, WindowOps};
+
+lazy_static::lazy_static! {
+    /// Holds the most recent selection when the primary selection
+    /// is being emulated
+    static ref EMULATED_PRIMARY_SELECTION: Mutex<String> = Mutex::new(String::new());
+}
+
+/// Returns true if the primary selection should be held internally
+/// rather than being routed to the system clipboard.  Only Windows
+/// and macOS lack a native primary selection.
+fn emulating_primary_selection(clipboard: Clipboard) -> bool {
+    clipboard == Clipboard::PrimarySelection
+        && cfg!(any(windows, target_os = "macos"))
+        && config::configuration().emulate_primary_selection
+}
+
+/// Assigns text to the specified clipboard, taking care of
+/// primary selection emulation
+pub fn set_clipboard_contents(window: &Window, clipboard: Clipboard, text: String) {
+    set_clipboard_content(window, clipboard, text.into());
+}
+
+/// Assigns text and its formatted versions to the specified clipboard,
+/// taking care of primary selection emulation, which only holds the text
+pub fn set_clipboard_content(window: &Window, clipboard: Clipboard, content: ClipboardContent) {
+    if emulating_primary_selection(clipboard) {
+        *EMULATED_PRIMARY_SELECTION.lock().unwrap() = content.text;
+    } else {
+        window.set_clipboard_content(clipboard, content);
+    }
+}
+
+/// Retrieves the contents of the specified clipboard, taking care
+/// of primary selection emulation
+pub fn get_clipboard_contents(window: &Window, clipboard: Clipboard) -> promise::Future<String> {
+    if emulating_primary_selection(clipboard) {
+        promise::Future::ok(EMULATED_PRIMARY_SELECTION.lock().unwrap().clone())
+    } else {
+        window.get_clipboard(clipboard)
+    }
+}
 
 impl TermWindow {
     pub fn copy_to_clipboard(&self, clipboard: ClipboardCopyDestination, text: String) {
+        self.copy_content_to_clipboard(clipboard, text.into());
+    }
+
+    /// Copies text along with formatted versions of it
+    pub fn copy_content_to_clipboard(
+        &self,
+        clipboard: ClipboardCopyDestination,
+        content: ClipboardContent,
+    ) {
+        crate::termwindow::clipboardhistory::record_copy(&content.text);
         let clipboard = match clipboard {
             ClipboardCopyDestination::Clipboard => [Some(Clipboard::Clipboard), None],
             ClipboardCopyDestination::PrimarySelection => [Some(Clipboard::PrimarySelection), None],
@@ -18,7 +74,7 @@ impl TermWindow {
         };
         for &c in &clipboard {
             if let Some(c) = c {
-                self.window.as_ref().unwrap().set_clipboard(c, text.clone());
+                set_clipboard_content(self.window.as_ref().unwrap(), c, content.clone());
             }
         }
     }
@@ -35,26 +91,109 @@ impl TermWindow {
             ClipboardPasteSource::Clipboard => Clipboard::Clipboard,
             ClipboardPasteSource::PrimarySelection => Clipboard::PrimarySelection,
         };
+        if emulating_primary_selection(clipboard) {
+            let text = EMULATED_PRIMARY_SELECTION.lock().unwrap().clone();
+            if !text.is_empty() {
+                self.paste_text(pane_id, text);
+            }
+            self.maybe_scroll_to_bottom_for_input(&pane);
+            return;
+        }
         let future = window.get_clipboard(clipboard);
         promise::spawn::spawn(async move {
             if let Ok(clip) = future.await {
                 window.notify(TermWindowNotif::Apply(Box::new(move |myself| {
-                    if let Some(pane) = myself
-                        .pane_state(pane_id)
-                        .overlay
-                        .as_ref()
-                        .map(|overlay| overlay.pane.clone())
-                        .or_else(|| {
-                            let mux = Mux::get().unwrap();
-                            mux.get_pane(pane_id)
-                        })
-                    {
-                        pane.trickle_paste(clip).ok();
-                    }
+                    myself.paste_text(pane_id, clip);
                 })));
             }
         })
         .detach();
         self.maybe_scroll_to_bottom_for_input(&pane);
     }
+
+    /// Returns the WSL domain that hosts the pane, if it has path
+    /// translation enabled
+    pub(crate) fn wsl_domain_for_pane(&self, pane_id: PaneId) -> Option<WslDomain> {
+        let mux = Mux::get()?;
+        let (domain_id, _, _) = mux.resolve_pane_id(pane_id)?;
+        let domain = mux.get_domain(domain_id)?;
+        self.config
+            .wsl_domain(domain.domain_name())
+            .filter(|wsl| wsl.translate_paths)
+            .cloned()
+    }
+
+    /// Pastes text into the pane, giving the `filter-paste` event a
+    /// chance to transform it first, and prompting the user to review
+    /// it if `paste_review_prompt` is enabled and it looks unsafe.
+    /// Text pasted into overlays is passed through unchanged.
+    pub fn paste_text(&mut self, pane_id: PaneId, text: String) {
+        if let Some(overlay) = self.pane_state(pane_id).overlay.as_ref() {
+            overlay.pane.trickle_paste(text).ok();
+            return;
+        }
+
+        // A lone windows path pasted into a WSL pane is translated
+        // to the path by which the distribution can reach it
+        let text = match self.wsl_domain_for_pane(pane_id) {
+            Some(wsl) if !text.contains('\n') => {
+                wsl.windows_path_to_wsl(text.trim()).unwrap_or(text)
+            }
+            _ => text,
+        };
+
+        let window = self.window.as_ref().unwrap().clone();
+        let gui_win = GuiWin::new(self);
+        let mux_pane = MuxPane(pane_id);
+
+        async fn filter_paste(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: MuxPane,
+            text: String,
+        ) -> anyhow::Result<Option<String>> {
+            let lua = match lua {
+                Some(lua) => lua,
+                None => return Ok(Some(text)),
+            };
+            let args = lua.pack_multi((window, pane, text.clone()))?;
+            let v =
+                config::lua::emit_async_callback(&*lua, ("filter-paste".to_string(), args)).await?;
+            match v {
+                mlua::Value::Nil => Ok(Some(text)),
+                mlua::Value::Boolean(false) => Ok(None),
+                v => Ok(Some(String::from_lua(v, &*lua)?)),
+            }
+        }
+
+        promise::spawn::spawn(async move {
+            let text = match config::with_lua_config_on_main_thread(move |lua| {
+                filter_paste(lua, gui_win, mux_pane, text)
+            })
+            .await
+            {
+                Ok(Some(text)) => text,
+                Ok(None) => return,
+                Err(err) => {
+                    log::error!("while processing filter-paste event: {:#}", err);
+                    return;
+                }
+            };
+            window.notify(TermWindowNotif::Apply(Box::new(move |myself| {
+                let mux = Mux::get().unwrap();
+                let pane = match mux.get_pane(pane_id) {
+                    Some(pane) => pane,
+                    None => return,
+                };
+                if myself.config.paste_review_prompt && pastereview::needs_review(&text) {
+                    let modal = pastereview::PasteReview::new(myself, pane_id, text);
+                    myself.modal.borrow_mut().replace(Rc::new(modal));
+                    myself.invalidate_modal();
+                } else {
+                    pane.trickle_paste(text).ok();
+                }
+            })));
+        })
+        .detach();
+    }
 }
@@ -0,0 +1,727 @@
+use crate::termwindow::box_model::*;
+use crate::termwindow::modal::Modal;
+use crate::termwindow::render::{
+    BOTTOM_LEFT_ROUNDED_CORNER, BOTTOM_RIGHT_ROUNDED_CORNER, TOP_LEFT_ROUNDED_CORNER,
+    TOP_RIGHT_ROUNDED_CORNER,
+};
+use crate::termwindow::DimensionContext;
+use crate::utilsprites::RenderMetrics;
+use crate::TermWindow;
+use anyhow::{anyhow, Context};
+use config::keyassignment::KeyAssignment;
+use config::Dimension;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::cell::{Ref, RefCell};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wezterm_term::{KeyCode, KeyModifiers, MouseEvent};
+use window::color::LinearRgba;
+
+const KEYCHAIN_SERVICE: &str = "wezterm";
+const KEYCHAIN_ACCOUNT: &str = "clipboard-history";
+
+/// The longest label that we'll show for an entry in the picker
+const MAX_LABEL_LEN: usize = 120;
+
+/// How long the writer waits for further changes before it saves
+/// the history, so that a burst of copies results in a single write
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct HistoryEntry {
+    text: String,
+    pinned: bool,
+}
+
+/// The clipboard history is shared by all windows.
+/// It is loaded lazily, when it is first needed, so that we
+/// don't touch the keychain unless the history is enabled.
+/// Accessing the keychain may prompt the user, so loading and
+/// saving happen on the writer thread rather than the gui thread,
+/// and the lock is never held while doing either.
+#[derive(Default)]
+struct History {
+    /// Set by the writer thread once the saved entries were loaded
+    loaded: bool,
+    /// Most recent first
+    entries: Vec<HistoryEntry>,
+    /// None if the keychain is not available, in which case
+    /// the history is kept in memory only
+    key: Option<Arc<LessSafeKey>>,
+    /// The texts of the entries that were removed before the saved
+    /// entries were loaded, so that loading doesn't bring them back
+    removed: Vec<String>,
+}
+
+lazy_static::lazy_static! {
+    static ref HISTORY: Mutex<History> = Mutex::new(History::default());
+    static ref WRITER: Mutex<Sender<()>> = Mutex::new(spawn_writer());
+}
+
+fn history_file_name() -> PathBuf {
+    config::RUNTIME_DIR.join("clipboard-history.enc")
+}
+
+/// Retrieves the key used to encrypt the history from the OS keychain,
+/// generating and storing a new one if there isn't one yet
+fn history_key() -> anyhow::Result<LessSafeKey> {
+    let entry = keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT);
+    let key_bytes = match entry.get_password() {
+        Ok(encoded) => base64::decode(encoded).context("decoding clipboard history key")?,
+        Err(keyring::Error::NoEntry) => {
+            let mut key_bytes = vec![0u8; CHACHA20_POLY1305.key_len()];
+            SystemRandom::new()
+                .fill(&mut key_bytes)
+                .map_err(|_| anyhow!("failed to generate clipboard history key"))?;
+            entry
+                .set_password(&base64::encode(&key_bytes))
+                .context("storing clipboard history key in the keychain")?;
+            key_bytes
+        }
+        Err(err) => return Err(err).context("reading clipboard history key from the keychain"),
+    };
+    let key = UnboundKey::new(&CHACHA20_POLY1305, &key_bytes)
+        .map_err(|_| anyhow!("clipboard history key has the wrong length"))?;
+    Ok(LessSafeKey::new(key))
+}
+
+/// The file holds a random nonce followed by the sealed json
+/// encoded entries
+fn load_entries(key: &LessSafeKey) -> anyhow::Result<Vec<HistoryEntry>> {
+    let mut data = match std::fs::read(history_file_name()) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err).context("reading clipboard history"),
+    };
+    if data.len() < NONCE_LEN {
+        anyhow::bail!("clipboard history file is truncated");
+    }
+    let mut sealed = data.split_off(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(&data)
+        .map_err(|_| anyhow!("invalid clipboard history nonce"))?;
+    let json = key
+        .open_in_place(nonce, Aad::empty(), &mut sealed)
+        .map_err(|_| anyhow!("failed to decrypt clipboard history"))?;
+    Ok(serde_json::from_slice(json)?)
+}
+
+fn save_entries(key: &LessSafeKey, entries: &[HistoryEntry]) -> anyhow::Result<()> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| anyhow!("failed to generate clipboard history nonce"))?;
+    let mut sealed = serde_json::to_vec(entries)?;
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::empty(),
+        &mut sealed,
+    )
+    .map_err(|_| anyhow!("failed to encrypt clipboard history"))?;
+
+    let mut data = nonce.to_vec();
+    data.append(&mut sealed);
+    let file_name = history_file_name();
+    std::fs::write(&file_name, data).with_context(|| format!("writing {}", file_name.display()))?;
+    Ok(())
+}
+
+/// Starts the thread that loads the saved history and then writes
+/// it back to disk whenever it is asked to
+fn spawn_writer() -> Sender<()> {
+    let (tx, rx) = channel();
+    std::thread::Builder::new()
+        .name("clipboard-history".to_string())
+        .spawn(move || {
+            load_history();
+            while rx.recv().is_ok() {
+                while rx.recv_timeout(SAVE_DELAY).is_ok() {}
+                save_history();
+            }
+        })
+        .expect("failed to spawn clipboard history thread");
+    tx
+}
+
+/// Makes sure that the saved history is being loaded
+fn start_loading() {
+    lazy_static::initialize(&WRITER);
+}
+
+/// Asks the writer thread to save the history soon
+fn request_save() {
+    WRITER.lock().unwrap().send(()).ok();
+}
+
+fn load_history() {
+    let (key, saved) = match history_key() {
+        Ok(key) => {
+            let saved = load_entries(&key).unwrap_or_else(|err| {
+                log::error!("{:#}", err);
+                vec![]
+            });
+            (Some(Arc::new(key)), saved)
+        }
+        Err(err) => {
+            log::warn!("{:#}; the clipboard history will not be saved to disk", err);
+            (None, vec![])
+        }
+    };
+    let size = config::configuration().clipboard_history_size;
+    let mut history = HISTORY.lock().unwrap();
+    history.merge_saved(saved, size);
+    history.key = key;
+    history.loaded = true;
+}
+
+fn save_history() {
+    let (key, entries) = {
+        let history = HISTORY.lock().unwrap();
+        match (&history.key, history.loaded) {
+            (Some(key), true) => (Arc::clone(key), history.entries.clone()),
+            _ => return,
+        }
+    };
+    if let Err(err) = save_entries(&key, &entries) {
+        log::error!("{:#}", err);
+    }
+}
+
+impl History {
+    /// Adds the entries that were loaded from disk after the ones
+    /// that were copied while they were being loaded
+    fn merge_saved(&mut self, saved: Vec<HistoryEntry>, size: usize) {
+        for entry in saved {
+            if self.removed.contains(&entry.text) {
+                continue;
+            }
+            match self.entries.iter_mut().find(|e| e.text == entry.text) {
+                Some(existing) => existing.pinned |= entry.pinned,
+                None => self.entries.push(entry),
+            }
+        }
+        self.removed.clear();
+        self.trim(size);
+    }
+
+    /// Retains the pinned entries, and up to `size` unpinned entries
+    fn trim(&mut self, size: usize) {
+        let mut unpinned = 0;
+        self.entries.retain(|entry| {
+            if entry.pinned {
+                true
+            } else {
+                unpinned += 1;
+                unpinned <= size
+            }
+        });
+    }
+}
+
+/// Called when text is copied from wezterm; adds it to the
+/// history, if the history is enabled
+pub fn record_copy(text: &str) {
+    let size = config::configuration().clipboard_history_size;
+    if size == 0 || text.is_empty() {
+        return;
+    }
+    start_loading();
+    let mut history = HISTORY.lock().unwrap();
+    let pinned = match history.entries.iter().position(|entry| entry.text == text) {
+        Some(idx) => history.entries.remove(idx).pinned,
+        None => false,
+    };
+    history.entries.insert(
+        0,
+        HistoryEntry {
+            text: text.to_string(),
+            pinned,
+        },
+    );
+    history.trim(size);
+    drop(history);
+    request_save();
+}
+
+fn history_entries() -> Vec<HistoryEntry> {
+    if config::configuration().clipboard_history_size == 0 {
+        return vec![];
+    }
+    start_loading();
+    HISTORY.lock().unwrap().entries.clone()
+}
+
+fn toggle_pinned(text: &str) {
+    let mut history = HISTORY.lock().unwrap();
+    if let Some(entry) = history.entries.iter_mut().find(|entry| entry.text == text) {
+        entry.pinned = !entry.pinned;
+    }
+    history.trim(config::configuration().clipboard_history_size);
+    drop(history);
+    request_save();
+}
+
+fn remove_entry(text: &str) {
+    let mut history = HISTORY.lock().unwrap();
+    history.entries.retain(|entry| entry.text != text);
+    if !history.loaded {
+        history.removed.push(text.to_string());
+    }
+    drop(history);
+    request_save();
+}
+
+/// Produces a single line summary of the text of an entry
+fn entry_label(entry: &HistoryEntry) -> String {
+    let marker = if entry.pinned { "📌" } else { "  " };
+    let mut label = String::new();
+    for (idx, c) in entry.text.trim_end().chars().enumerate() {
+        if idx >= MAX_LABEL_LEN {
+            label.push('…');
+            break;
+        }
+        match c {
+            '\n' => label.push('⏎'),
+            '\r' => {}
+            '\t' => label.push(' '),
+            c if c.is_control() => {}
+            c => label.push(c),
+        }
+    }
+    format!("{marker} {label}")
+}
+
+pub struct ClipboardHistoryPicker {
+    element: RefCell<Option<Vec<ComputedElement>>>,
+    entries: RefCell<Vec<HistoryEntry>>,
+    filter: RefCell<String>,
+    /// indices into entries that match the filter, in display order
+    matches: RefCell<Vec<usize>>,
+    selected_row: RefCell<usize>,
+    top_row: RefCell<usize>,
+    max_rows_on_screen: RefCell<usize>,
+}
+
+impl ClipboardHistoryPicker {
+    pub fn new(_term_window: &mut TermWindow) -> Self {
+        let picker = Self {
+            element: RefCell::new(None),
+            entries: RefCell::new(vec![]),
+            filter: RefCell::new(String::new()),
+            matches: RefCell::new(vec![]),
+            selected_row: RefCell::new(0),
+            top_row: RefCell::new(0),
+            max_rows_on_screen: RefCell::new(0),
+        };
+        picker.refresh();
+        picker
+    }
+
+    /// Re-reads the history and recomputes the matches, keeping the
+    /// selection within bounds
+    fn refresh(&self) {
+        *self.entries.borrow_mut() = history_entries();
+        self.update_matches();
+
+        let limit = self.matches.borrow().len().saturating_sub(1);
+        let mut row = self.selected_row.borrow_mut();
+        *row = (*row).min(limit);
+        let mut top_row = self.top_row.borrow_mut();
+        *top_row = (*top_row).min(*row);
+    }
+
+    fn update_matches(&self) {
+        let entries = self.entries.borrow();
+        let filter = self.filter.borrow();
+        let mut matches: Vec<(usize, i64)> = if filter.is_empty() {
+            entries
+                .iter()
+                .enumerate()
+                .map(|(idx, _)| (idx, 0))
+                .collect()
+        } else {
+            let matcher = SkimMatcherV2::default();
+            entries
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, entry)| {
+                    matcher
+                        .fuzzy_match(&entry.text, &filter)
+                        .map(|score| (idx, score))
+                })
+                .collect()
+        };
+        // Best match first, then pinned entries, then most recent
+        matches.sort_by(|(a_idx, a_score), (b_idx, b_score)| {
+            b_score
+                .cmp(a_score)
+                .then(entries[*b_idx].pinned.cmp(&entries[*a_idx].pinned))
+                .then(a_idx.cmp(b_idx))
+        });
+        *self.matches.borrow_mut() = matches.into_iter().map(|(idx, _)| idx).collect();
+    }
+
+    fn updated_input(&self) {
+        self.update_matches();
+        *self.selected_row.borrow_mut() = 0;
+        *self.top_row.borrow_mut() = 0;
+    }
+
+    fn selected_text(&self) -> Option<String> {
+        let idx = *self.matches.borrow().get(*self.selected_row.borrow())?;
+        self.entries
+            .borrow()
+            .get(idx)
+            .map(|entry| entry.text.clone())
+    }
+
+    fn move_up(&self) {
+        let mut row = self.selected_row.borrow_mut();
+        *row = row.saturating_sub(1);
+
+        let mut top_row = self.top_row.borrow_mut();
+        if *row < *top_row {
+            *top_row = *row;
+        }
+    }
+
+    fn move_down(&self) {
+        let max_rows_on_screen = (*self.max_rows_on_screen.borrow()).max(1);
+        let limit = self.matches.borrow().len().saturating_sub(1);
+        let mut row = self.selected_row.borrow_mut();
+        *row = row.saturating_add(1).min(limit);
+        let mut top_row = self.top_row.borrow_mut();
+        if *row > *top_row + max_rows_on_screen - 1 {
+            *top_row = row.saturating_sub(max_rows_on_screen - 1);
+        }
+    }
+
+    fn compute(
+        &self,
+        term_window: &mut TermWindow,
+        max_rows_on_screen: usize,
+    ) -> anyhow::Result<Vec<ComputedElement>> {
+        let font = term_window
+            .fonts
+            .char_select_font()
+            .expect("to resolve char selection font");
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+
+        let top_bar_height = if term_window.show_tab_bar && !term_window.config.tab_bar_at_bottom {
+            term_window.tab_bar_pixel_height().unwrap()
+        } else {
+            0.
+        };
+        let (padding_left, padding_top) = term_window.padding_left_top();
+        let border = term_window.get_os_border();
+        let top_pixel_y = top_bar_height + padding_top + border.top.get() as f32;
+
+        let selected_row = *self.selected_row.borrow();
+        let top_row = *self.top_row.borrow();
+        let entries = self.entries.borrow();
+        let matches = self.matches.borrow();
+
+        let header = if term_window.config.clipboard_history_size == 0 && entries.is_empty() {
+            "The clipboard history is disabled; set clipboard_history_size to enable it".to_string()
+        } else {
+            format!(
+                "Clipboard history: {}_   (Enter=paste Ctrl-P=pin Ctrl-D=delete Esc=close)",
+                self.filter.borrow()
+            )
+        };
+
+        let mut elements = vec![Element::new(&font, ElementContent::Text(header))
+            .colors(ElementColors {
+                border: BorderColor::default(),
+                bg: LinearRgba::TRANSPARENT.into(),
+                text: term_window.config.pane_select_fg_color.to_linear().into(),
+            })
+            .display(DisplayType::Block)];
+
+        for (display_idx, &entry_idx) in matches
+            .iter()
+            .enumerate()
+            .skip(top_row)
+            .take(max_rows_on_screen)
+        {
+            let (bg, text) = if display_idx == selected_row {
+                (
+                    term_window.config.pane_select_fg_color.to_linear().into(),
+                    term_window.config.pane_select_bg_color.to_linear().into(),
+                )
+            } else {
+                (
+                    LinearRgba::TRANSPARENT.into(),
+                    term_window.config.pane_select_fg_color.to_linear().into(),
+                )
+            };
+            elements.push(
+                Element::new(
+                    &font,
+                    ElementContent::Text(entry_label(&entries[entry_idx])),
+                )
+                .colors(ElementColors {
+                    border: BorderColor::default(),
+                    bg,
+                    text,
+                })
+                .padding(BoxDimension {
+                    left: Dimension::Cells(0.25),
+                    right: Dimension::Cells(0.25),
+                    top: Dimension::Cells(0.),
+                    bottom: Dimension::Cells(0.),
+                })
+                .display(DisplayType::Block),
+            );
+        }
+
+        let element = Element::new(&font, ElementContent::Children(elements))
+            .colors(ElementColors {
+                border: BorderColor::new(
+                    term_window.config.pane_select_bg_color.to_linear().into(),
+                ),
+                bg: term_window.config.pane_select_bg_color.to_linear().into(),
+                text: term_window.config.pane_select_fg_color.to_linear().into(),
+            })
+            .margin(BoxDimension {
+                left: Dimension::Cells(1.25),
+                right: Dimension::Cells(1.25),
+                top: Dimension::Cells(1.25),
+                bottom: Dimension::Cells(1.25),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.25),
+                right: Dimension::Cells(0.25),
+                top: Dimension::Cells(0.25),
+                bottom: Dimension::Cells(0.25),
+            })
+            .border(BoxDimension::new(Dimension::Pixels(1.)))
+            .border_corners(Some(Corners {
+                top_left: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: TOP_LEFT_ROUNDED_CORNER,
+                },
+                top_right: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: TOP_RIGHT_ROUNDED_CORNER,
+                },
+                bottom_left: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: BOTTOM_LEFT_ROUNDED_CORNER,
+                },
+                bottom_right: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: BOTTOM_RIGHT_ROUNDED_CORNER,
+                },
+            }));
+
+        let dimensions = term_window.dimensions;
+        let size = term_window.terminal_size;
+
+        let computed = term_window.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_height as f32,
+                    pixel_cell: metrics.cell_size.height as f32,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_width as f32,
+                    pixel_cell: metrics.cell_size.width as f32,
+                },
+                bounds: euclid::rect(
+                    padding_left,
+                    top_pixel_y,
+                    size.cols as f32 * term_window.render_metrics.cell_size.width as f32,
+                    size.rows as f32 * term_window.render_metrics.cell_size.height as f32,
+                ),
+                metrics: &metrics,
+                gl_state: term_window.render_state.as_ref().unwrap(),
+                zindex: 100,
+            },
+            &element,
+        )?;
+
+        Ok(vec![computed])
+    }
+}
+
+impl Modal for ClipboardHistoryPicker {
+    fn perform_assignment(
+        &self,
+        _assignment: &KeyAssignment,
+        _term_window: &mut TermWindow,
+    ) -> bool {
+        false
+    }
+
+    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn key_down(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<()> {
+        match (key, mods) {
+            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+                term_window.cancel_modal();
+                return Ok(());
+            }
+            (KeyCode::UpArrow, KeyModifiers::NONE) => {
+                self.move_up();
+            }
+            (KeyCode::DownArrow, KeyModifiers::NONE) => {
+                self.move_down();
+            }
+            (KeyCode::Char('p'), KeyModifiers::CTRL) => {
+                if let Some(text) = self.selected_text() {
+                    toggle_pinned(&text);
+                    self.refresh();
+                }
+            }
+            (KeyCode::Char('d'), KeyModifiers::CTRL) => {
+                if let Some(text) = self.selected_text() {
+                    remove_entry(&text);
+                    self.refresh();
+                }
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                self.filter.borrow_mut().push(c);
+                self.updated_input();
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                self.filter.borrow_mut().pop();
+                self.updated_input();
+            }
+            (KeyCode::Char('u'), KeyModifiers::CTRL) => {
+                self.filter.borrow_mut().clear();
+                self.updated_input();
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                let text = self.selected_text();
+                term_window.cancel_modal();
+                if let (Some(text), Some(pane)) = (text, term_window.get_active_pane_no_overlay()) {
+                    term_window.paste_text(pane.pane_id(), text);
+                    term_window.maybe_scroll_to_bottom_for_input(&pane);
+                }
+                return Ok(());
+            }
+            _ => return Ok(()),
+        }
+        term_window.invalidate_modal();
+        Ok(())
+    }
+
+    fn computed_element(
+        &self,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<Ref<[ComputedElement]>> {
+        let font = term_window
+            .fonts
+            .char_select_font()
+            .expect("to resolve char selection font");
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+
+        let max_rows_on_screen = ((term_window.dimensions.pixel_height * 8 / 10)
+            / metrics.cell_size.height as usize)
+            .saturating_sub(2)
+            .max(1);
+        *self.max_rows_on_screen.borrow_mut() = max_rows_on_screen;
+
+        if self.element.borrow().is_none() {
+            let element = self.compute(term_window, max_rows_on_screen)?;
+            self.element.borrow_mut().replace(element);
+        }
+        Ok(Ref::map(self.element.borrow(), |v| {
+            v.as_ref().unwrap().as_slice()
+        }))
+    }
+
+    fn reconfigure(&self, _term_window: &mut TermWindow) {
+        self.element.borrow_mut().take();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(text: &str, pinned: bool) -> HistoryEntry {
+        HistoryEntry {
+            text: text.to_string(),
+            pinned,
+        }
+    }
+
+    #[test]
+    fn trim_keeps_pinned() {
+        let mut history = History {
+            entries: vec![
+                entry("a", false),
+                entry("b", true),
+                entry("c", false),
+                entry("d", false),
+            ],
+            ..History::default()
+        };
+        history.trim(2);
+        let texts: Vec<&str> = history.entries.iter().map(|e| e.text.as_str()).collect();
+        assert_eq!(texts, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn merge_keeps_recent_copies_first() {
+        let mut history = History {
+            entries: vec![entry("new", false), entry("b", false)],
+            ..History::default()
+        };
+        history.merge_saved(
+            vec![entry("a", false), entry("b", true), entry("c", false)],
+            3,
+        );
+        let texts: Vec<(&str, bool)> = history
+            .entries
+            .iter()
+            .map(|e| (e.text.as_str(), e.pinned))
+            .collect();
+        assert_eq!(
+            texts,
+            vec![("new", false), ("b", true), ("a", false), ("c", false)]
+        );
+    }
+
+    #[test]
+    fn merge_skips_entries_removed_while_loading() {
+        let mut history = History {
+            entries: vec![entry("new", false)],
+            removed: vec!["b".to_string()],
+            ..History::default()
+        };
+        history.merge_saved(vec![entry("a", false), entry("b", true)], 3);
+        let texts: Vec<&str> = history.entries.iter().map(|e| e.text.as_str()).collect();
+        assert_eq!(texts, vec!["new", "a"]);
+        assert!(history.removed.is_empty());
+    }
+
+    #[test]
+    fn label_is_single_line() {
+        assert_eq!(
+            entry_label(&entry("ls -l\r\necho\t1\n", false)),
+            "   ls -l⏎echo 1"
+        );
+        assert_eq!(entry_label(&entry("x", true)), "📌 x");
+    }
+}
@@ -0,0 +1,429 @@
+//! Handles requests from applications to read the clipboard via OSC 52
+use crate::scripting::guiwin::GuiWin;
+use crate::termwindow::box_model::ComputedElement;
+use crate::termwindow::clipboard::get_clipboard_contents;
+use crate::termwindow::escapepermission::compute_prompt;
+use crate::termwindow::modal::Modal;
+use crate::termwindow::TermWindowNotif;
+use crate::TermWindow;
+use config::keyassignment::KeyAssignment;
+use config::EscapeSequencePermission;
+use mux::domain::LocalDomain;
+use mux::escape_permissions::{explicit_permission, remembered_decision, resolve_prompt};
+use mux::pane::{Pane, PaneId};
+use mux::Mux;
+use mux_lua::MuxPane;
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+use termwiz::escape::osc::Selection;
+use termwiz::escape::OperatingSystemCommand;
+use wezterm_term::{ClipboardSelection, EscapeSequenceKind, KeyCode, KeyModifiers, MouseEvent};
+use window::{Clipboard, WindowOps};
+
+enum Permission {
+    Allow(&'static str),
+    Deny(String),
+    Prompt,
+}
+
+/// Where the application in a pane is running
+enum Host {
+    Local,
+    Remote(String),
+    /// The pane is in a local domain but hasn't reported the local
+    /// host name via OSC 7.  It may be running something like ssh,
+    /// so it is treated as a remote host.
+    Unknown,
+}
+
+/// Determines the host that the pane is connected to.  Panes in
+/// remote domains are identified by the domain name.  In a local
+/// domain we can't tell whether the application is connected to
+/// another host, and the host reported by OSC 7 is under the control
+/// of the application, so the pane is only considered to be local
+/// when it reports the local host name.
+fn pane_host(pane: &Rc<dyn Pane>) -> Host {
+    let mux = Mux::get().expect("called on main thread");
+    match mux.get_domain(pane.domain_id()) {
+        Some(domain) if domain.downcast_ref::<LocalDomain>().is_some() => {}
+        Some(domain) => return Host::Remote(domain.domain_name().to_string()),
+        None => return Host::Unknown,
+    }
+
+    let url = match pane.get_current_working_dir() {
+        Some(url) => url,
+        None => return Host::Unknown,
+    };
+    let host = match url.host_str() {
+        Some(host) if !host.is_empty() && host != "localhost" => host,
+        _ => return Host::Unknown,
+    };
+    match hostname::get() {
+        Ok(local_host) if local_host.to_str() == Some(host) => Host::Local,
+        _ => Host::Remote(host.to_string()),
+    }
+}
+
+fn permission(pane: &Rc<dyn Pane>) -> Permission {
+    let config = config::configuration();
+    let mux = Mux::get().expect("called on main thread");
+    let domain_name = mux
+        .get_domain(pane.domain_id())
+        .map(|domain| domain.domain_name().to_string())
+        .unwrap_or_default();
+    let explicit = explicit_permission(
+        pane.pane_id(),
+        &domain_name,
+        EscapeSequenceKind::ClipboardRead,
+    );
+    let configured = explicit.unwrap_or_else(|| {
+        config.escape_sequence_permission(&domain_name, EscapeSequenceKind::ClipboardRead)
+    });
+
+    if configured == EscapeSequencePermission::Deny {
+        return Permission::Deny(match explicit {
+            Some(_) => format!(
+                "escape_sequence_policy denies clipboard_read for pane {} in {domain_name}",
+                pane.pane_id()
+            ),
+            None => "osc52_clipboard_read is Deny".to_string(),
+        });
+    }
+
+    // An explicit policy for the pane or its domain takes the place of the
+    // list of allowed hosts
+    if explicit.is_none() {
+        match pane_host(pane) {
+            Host::Local => {}
+            Host::Remote(host) => {
+                if !config
+                    .osc52_clipboard_read_allowed_hosts
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(&host))
+                {
+                    return Permission::Deny(format!(
+                        "{host} is not listed in osc52_clipboard_read_allowed_hosts"
+                    ));
+                }
+            }
+            Host::Unknown => {
+                return Permission::Deny(
+                    "the pane has not reported the local host name via OSC 7, \
+                     so it is treated as a remote host"
+                        .to_string(),
+                );
+            }
+        }
+    }
+
+    match remembered_decision(pane.pane_id(), EscapeSequenceKind::ClipboardRead) {
+        Some(true) => return Permission::Allow("always allowed for this pane"),
+        Some(false) => return Permission::Deny("always denied for this pane".to_string()),
+        None => {}
+    }
+
+    match (configured, explicit) {
+        (EscapeSequencePermission::Allow, Some(_)) => {
+            Permission::Allow("allowed by escape_sequence_policy")
+        }
+        (EscapeSequencePermission::Allow, None) => {
+            Permission::Allow("osc52_clipboard_read is Allow")
+        }
+        _ => Permission::Prompt,
+    }
+}
+
+fn selection_name(selection: ClipboardSelection) -> &'static str {
+    match selection {
+        ClipboardSelection::Clipboard => "Clipboard",
+        ClipboardSelection::PrimarySelection => "PrimarySelection",
+    }
+}
+
+/// Sends the OSC 52 response to the application in the pane.
+/// Denied requests are answered with empty data, so that the
+/// application doesn't sit waiting for a response.
+fn send_response(pane: &Rc<dyn Pane>, selection: ClipboardSelection, text: String) {
+    let selection = match selection {
+        ClipboardSelection::Clipboard => Selection::CLIPBOARD,
+        ClipboardSelection::PrimarySelection => Selection::PRIMARY,
+    };
+    let response = OperatingSystemCommand::SetSelection(selection, text).to_string();
+    if let Err(err) = pane.writer().write_all(response.as_bytes()) {
+        log::error!("failed to send OSC 52 response: {:#}", err);
+    }
+}
+
+impl TermWindow {
+    /// Called when an application in the pane has requested the
+    /// contents of the clipboard via OSC 52
+    pub fn query_clipboard(&mut self, pane_id: PaneId, selection: ClipboardSelection) {
+        let mux = Mux::get().expect("called on main thread");
+        let pane = match mux.get_pane(pane_id) {
+            Some(pane) => pane,
+            None => return,
+        };
+
+        match permission(&pane) {
+            Permission::Allow(reason) => self.read_clipboard_for_pane(pane_id, selection, reason),
+            Permission::Deny(reason) => {
+                self.audit_clipboard_read(&pane, selection, false, &reason, 0);
+                send_response(&pane, selection, String::new());
+            }
+            Permission::Prompt => {
+                if self.get_modal().is_some() {
+                    self.audit_clipboard_read(
+                        &pane,
+                        selection,
+                        false,
+                        "another prompt is already active",
+                        0,
+                    );
+                    send_response(&pane, selection, String::new());
+                } else {
+                    let modal = ClipboardReadPrompt::new(self, pane_id, selection);
+                    self.modal.borrow_mut().replace(Rc::new(modal));
+                    self.invalidate_modal();
+                }
+            }
+        }
+    }
+
+    fn read_clipboard_for_pane(
+        &mut self,
+        pane_id: PaneId,
+        selection: ClipboardSelection,
+        reason: &'static str,
+    ) {
+        let clipboard = match selection {
+            ClipboardSelection::Clipboard => Clipboard::Clipboard,
+            ClipboardSelection::PrimarySelection => Clipboard::PrimarySelection,
+        };
+        let window = self.window.as_ref().unwrap().clone();
+        let future = get_clipboard_contents(&window, clipboard);
+        promise::spawn::spawn(async move {
+            let text = match future.await {
+                Ok(text) => text,
+                Err(err) => {
+                    log::error!("failed to read clipboard for OSC 52: {:#}", err);
+                    String::new()
+                }
+            };
+            window.notify(TermWindowNotif::Apply(Box::new(move |myself| {
+                let mux = Mux::get().expect("called on main thread");
+                let pane = match mux.get_pane(pane_id) {
+                    Some(pane) => pane,
+                    None => return,
+                };
+                let max_bytes = myself.config.osc52_clipboard_read_max_bytes;
+                if text.len() > max_bytes {
+                    myself.audit_clipboard_read(
+                        &pane,
+                        selection,
+                        false,
+                        &format!(
+                            "{} bytes exceeds osc52_clipboard_read_max_bytes",
+                            text.len()
+                        ),
+                        text.len(),
+                    );
+                    send_response(&pane, selection, String::new());
+                } else {
+                    myself.audit_clipboard_read(&pane, selection, true, reason, text.len());
+                    send_response(&pane, selection, text);
+                }
+            })));
+        })
+        .detach();
+    }
+
+    /// Logs the outcome of the request and emits the
+    /// `osc52-clipboard-read` event
+    fn audit_clipboard_read(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        selection: ClipboardSelection,
+        allowed: bool,
+        reason: &str,
+        bytes: usize,
+    ) {
+        log::info!(
+            "OSC 52 read of {} by pane {}: {} ({}, {} bytes)",
+            selection_name(selection),
+            pane.pane_id(),
+            if allowed { "allowed" } else { "denied" },
+            reason,
+            bytes
+        );
+
+        let window = GuiWin::new(self);
+        let pane = MuxPane(pane.pane_id());
+        let reason = reason.to_string();
+
+        async fn emit(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: MuxPane,
+            selection: ClipboardSelection,
+            allowed: bool,
+            reason: String,
+            bytes: usize,
+        ) -> anyhow::Result<()> {
+            if let Some(lua) = lua {
+                let info = lua.create_table()?;
+                info.set("selection", selection_name(selection))?;
+                info.set("allowed", allowed)?;
+                info.set("reason", reason)?;
+                info.set("bytes", bytes)?;
+                let args = lua.pack_multi((window, pane, info))?;
+                config::lua::emit_event(&lua, ("osc52-clipboard-read".to_string(), args))
+                    .await
+                    .map_err(|e| {
+                        log::error!("while processing osc52-clipboard-read event: {:#}", e);
+                        e
+                    })?;
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            emit(lua, window, pane, selection, allowed, reason, bytes)
+        }))
+        .detach();
+    }
+}
+
+pub struct ClipboardReadPrompt {
+    element: RefCell<Option<Vec<ComputedElement>>>,
+    pane_id: PaneId,
+    selection: ClipboardSelection,
+    /// Set once the user has answered, so that we don't
+    /// respond a second time when the modal is cancelled
+    answered: RefCell<bool>,
+}
+
+impl ClipboardReadPrompt {
+    pub fn new(
+        _term_window: &mut TermWindow,
+        pane_id: PaneId,
+        selection: ClipboardSelection,
+    ) -> Self {
+        Self {
+            element: RefCell::new(None),
+            pane_id,
+            selection,
+            answered: RefCell::new(false),
+        }
+    }
+
+    fn answer(&self, allow: bool, remember: bool, term_window: &mut TermWindow) {
+        *self.answered.borrow_mut() = true;
+        term_window.cancel_modal();
+
+        resolve_prompt(
+            self.pane_id,
+            EscapeSequenceKind::ClipboardRead,
+            allow,
+            remember,
+        );
+
+        if allow {
+            let reason = if remember {
+                "always allowed for this pane"
+            } else {
+                "allowed by the user"
+            };
+            term_window.read_clipboard_for_pane(self.pane_id, self.selection, reason);
+        } else {
+            let mux = Mux::get().expect("called on main thread");
+            let pane = match mux.get_pane(self.pane_id) {
+                Some(pane) => pane,
+                None => return,
+            };
+            let reason = if remember {
+                "always denied for this pane"
+            } else {
+                "denied by the user"
+            };
+            term_window.audit_clipboard_read(&pane, self.selection, false, reason, 0);
+            send_response(&pane, self.selection, String::new());
+        }
+    }
+
+    fn compute(&self, term_window: &mut TermWindow) -> anyhow::Result<Vec<ComputedElement>> {
+        let mux = Mux::get().expect("called on main thread");
+        let title = mux
+            .get_pane(self.pane_id)
+            .map(|pane| pane.get_title())
+            .unwrap_or_default();
+
+        let lines = vec![
+            format!(
+                "Pane {} ({}) wants to read the {}.",
+                self.pane_id,
+                title,
+                match self.selection {
+                    ClipboardSelection::Clipboard => "clipboard",
+                    ClipboardSelection::PrimarySelection => "primary selection",
+                }
+            ),
+            "y=allow once  a=always allow for this pane  \
+             n=deny once  d=always deny for this pane"
+                .to_string(),
+        ];
+
+        compute_prompt(term_window, lines)
+    }
+}
+
+impl Modal for ClipboardReadPrompt {
+    fn perform_assignment(
+        &self,
+        _assignment: &KeyAssignment,
+        _term_window: &mut TermWindow,
+    ) -> bool {
+        false
+    }
+
+    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn key_down(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<()> {
+        if *self.answered.borrow() {
+            return Ok(());
+        }
+        match (key, mods) {
+            (KeyCode::Char('y'), KeyModifiers::NONE) => self.answer(true, false, term_window),
+            (KeyCode::Char('a'), KeyModifiers::NONE) => self.answer(true, true, term_window),
+            (KeyCode::Char('n'), KeyModifiers::NONE)
+            | (KeyCode::Escape, KeyModifiers::NONE)
+            | (KeyCode::Char('g'), KeyModifiers::CTRL) => self.answer(false, false, term_window),
+            (KeyCode::Char('d'), KeyModifiers::NONE) => self.answer(false, true, term_window),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn computed_element(
+        &self,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<Ref<[ComputedElement]>> {
+        if self.element.borrow().is_none() {
+            let element = self.compute(term_window)?;
+            self.element.borrow_mut().replace(element);
+        }
+        Ok(Ref::map(self.element.borrow(), |v| {
+            v.as_ref().unwrap().as_slice()
+        }))
+    }
+
+    fn reconfigure(&self, _term_window: &mut TermWindow) {
+        self.element.borrow_mut().take();
+    }
+}
@@ -0,0 +1,204 @@
+//! Shows the other clients that are attached to a pane that is shared
+//! through a mux server: the cells that they are pointing at, and who
+//! holds the input turn for the pane
+use crate::quad::TripleLayerQuadAllocator;
+use crate::termwindow::box_model::*;
+use crate::termwindow::render::{
+    BOTTOM_LEFT_ROUNDED_CORNER, BOTTOM_RIGHT_ROUNDED_CORNER, TOP_LEFT_ROUNDED_CORNER,
+    TOP_RIGHT_ROUNDED_CORNER,
+};
+use crate::termwindow::DimensionContext;
+use crate::utilsprites::RenderMetrics;
+use crate::TermWindow;
+use config::Dimension;
+use mux::client::ClientId;
+use mux::collab::InputTurn;
+use mux::pane::Pane;
+use mux::tab::PositionedPane;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use wezterm_term::color::ColorPalette;
+use wezterm_term::StableRowIndex;
+use window::color::LinearRgba;
+
+/// Picks one of the red, green, yellow, blue, magenta or cyan colors
+/// of the palette for the client, so that each client keeps the same
+/// color for as long as it is connected
+fn collaborator_color(palette: &ColorPalette, client_id: &ClientId) -> LinearRgba {
+    let mut hasher = DefaultHasher::new();
+    client_id.hash(&mut hasher);
+    let idx = 1 + (hasher.finish() % 6) as usize;
+    palette.colors.0[idx].to_linear()
+}
+
+impl TermWindow {
+    /// Tells the pane that we are pointing at the cell `x`,`y`, and
+    /// the pane that we pointed at before, if different, that we
+    /// are no longer pointing at it
+    pub(crate) fn update_collaborator_cursor(
+        &mut self,
+        pointing: Option<(&Rc<dyn Pane>, usize, StableRowIndex)>,
+    ) {
+        let pane_id = pointing.map(|(pane, _, _)| pane.pane_id());
+        if let Some(prior) = self.collaborator_cursor_pane.take() {
+            if Some(prior) != pane_id {
+                let mux = mux::Mux::get().unwrap();
+                if let Some(pane) = mux.get_pane(prior) {
+                    pane.set_collaborator_cursor(None);
+                }
+            }
+        }
+        if let Some((pane, x, y)) = pointing {
+            pane.set_collaborator_cursor(Some((x, y)));
+            self.collaborator_cursor_pane = pane_id;
+        }
+    }
+
+    /// Draws a bar at the left edge of each cell that another client
+    /// is pointing at, in the color of that client.  The cell of the
+    /// client that holds the input turn is also outlined.
+    pub(crate) fn paint_collaborator_cursors(
+        &self,
+        pos: &PositionedPane,
+        layers: &mut TripleLayerQuadAllocator,
+    ) -> anyhow::Result<()> {
+        let collaborators = match pos.pane.get_collaborators() {
+            Some(collaborators) => collaborators,
+            None => return Ok(()),
+        };
+        let dims = pos.pane.get_dimensions();
+        let top = self
+            .get_viewport(pos.pane.pane_id())
+            .unwrap_or(dims.physical_top);
+        let palette = pos.pane.palette();
+
+        let (pane_left, pane_top) = self.pane_origin(pos)?;
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+        let thickness = (cell_width / 8.).max(2.).round();
+
+        for collaborator in &collaborators.clients {
+            let (x, y) = match collaborator.cursor {
+                Some(cursor) => cursor,
+                None => continue,
+            };
+            let row = y - top;
+            if row < 0 || row >= pos.height as StableRowIndex || x >= pos.width {
+                continue;
+            }
+            let color = collaborator_color(&palette, &collaborator.client_id);
+            let left = pane_left + x as f32 * cell_width;
+            let cell_top = pane_top + row as f32 * cell_height;
+
+            self.filled_rectangle(
+                layers,
+                2,
+                euclid::rect(left, cell_top, thickness, cell_height),
+                color,
+            )?;
+
+            if collaborators.input_turn == InputTurn::Client(collaborator.client_id.clone()) {
+                let outline = (thickness / 2.).max(1.);
+                for rect in [
+                    euclid::rect(left, cell_top, cell_width, outline),
+                    euclid::rect(left, cell_top + cell_height - outline, cell_width, outline),
+                    euclid::rect(left + cell_width - outline, cell_top, outline, cell_height),
+                ] {
+                    self.filled_rectangle(layers, 2, rect, color)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Shows who holds the input turn in the top right corner of
+    /// each shared pane whose turn has been taken
+    pub(crate) fn paint_input_turn_indicators(&mut self) -> anyhow::Result<()> {
+        if self.get_modal().is_some() {
+            return Ok(());
+        }
+
+        for pos in self.get_panes_to_render() {
+            let text = match pos.pane.get_collaborators().map(|c| c.input_turn) {
+                Some(InputTurn::You) => "You have the input turn".to_string(),
+                Some(InputTurn::Client(owner)) => {
+                    format!("{}@{} has the input turn", owner.username, owner.hostname)
+                }
+                Some(InputTurn::Anyone) | None => continue,
+            };
+            let computed = self.compute_input_turn_indicator(&pos, text)?;
+            let gl_state = self.render_state.as_ref().unwrap();
+            self.render_element(&computed, gl_state, None)?;
+        }
+        Ok(())
+    }
+
+    fn compute_input_turn_indicator(
+        &mut self,
+        pos: &PositionedPane,
+        text: String,
+    ) -> anyhow::Result<ComputedElement> {
+        let font = self.fonts.title_font()?;
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+        let fg = self.config.pane_select_fg_color.to_linear();
+        let bg = self.config.pane_select_bg_color.to_linear();
+
+        let (pane_left, pane_top) = self.pane_origin(pos)?;
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+        let pane_width = pos.width as f32 * cell_width;
+
+        let dimensions = self.dimensions;
+        let pixel_width = dimensions.pixel_width as f32;
+        let pixel_height = dimensions.pixel_height as f32;
+        let context = LayoutContext {
+            height: DimensionContext {
+                dpi: dimensions.dpi as f32,
+                pixel_max: pixel_height,
+                pixel_cell: metrics.cell_size.height as f32,
+            },
+            width: DimensionContext {
+                dpi: dimensions.dpi as f32,
+                pixel_max: pixel_width,
+                pixel_cell: metrics.cell_size.width as f32,
+            },
+            bounds: euclid::rect(0., 0., pixel_width, pixel_height),
+            metrics: &metrics,
+            gl_state: self.render_state.as_ref().unwrap(),
+            zindex: 100,
+        };
+
+        let corner = |poly: &'static [Poly]| SizedPoly {
+            width: Dimension::Cells(0.25),
+            height: Dimension::Cells(0.25),
+            poly,
+        };
+        let label = Element::new(&font, ElementContent::Text(text))
+            .colors(ElementColors {
+                border: BorderColor::new(fg.into()),
+                bg: bg.mul_alpha(0.85).into(),
+                text: fg.into(),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.5),
+                right: Dimension::Cells(0.5),
+                top: Dimension::Cells(0.25),
+                bottom: Dimension::Cells(0.25),
+            })
+            .border(BoxDimension::new(Dimension::Pixels(1.)))
+            .border_corners(Some(Corners {
+                top_left: corner(TOP_LEFT_ROUNDED_CORNER),
+                top_right: corner(TOP_RIGHT_ROUNDED_CORNER),
+                bottom_left: corner(BOTTOM_LEFT_ROUNDED_CORNER),
+                bottom_right: corner(BOTTOM_RIGHT_ROUNDED_CORNER),
+            }));
+        let mut label = self.compute_element(&context, &label)?;
+
+        // Place the label in the top right corner of the pane
+        let x = (pane_left + pane_width - label.bounds.width() - cell_width).max(pane_left);
+        let y = pane_top + cell_height / 2.;
+        label.translate(euclid::vec2(x, y));
+        Ok(label)
+    }
+}
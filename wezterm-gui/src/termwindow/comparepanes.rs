@@ -0,0 +1,316 @@
+//! Implements `ToggleComparePanes`, which helps to compare the output
+//! of two commands running in adjacent panes.
+//!
+//! While two panes are being compared their scrolling is locked
+//! together: scrolling one of them scrolls the other to the same
+//! distance from the bottom of its scrollback.  The lines of their
+//! scrollbacks are diffed, and the lines that are not common to
+//! both panes are highlighted.
+use crate::quad::TripleLayerQuadAllocator;
+use crate::TermWindow;
+use config::keyassignment::ComparePanes;
+use mux::pane::{Pane, PaneId};
+use mux::renderable::RenderableDimensions;
+use mux::tab::PositionedPane;
+use mux::Mux;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+use termwiz::surface::SequenceNo;
+use wezterm_term::StableRowIndex;
+
+/// Above this many cells in the table used to compute the longest
+/// common subsequence, lines are compared by position instead
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+struct DiffCache {
+    seqnos: [SequenceNo; 2],
+    /// The stable rows in each pane that differ from the other pane
+    differing: [HashSet<StableRowIndex>; 2],
+}
+
+pub struct CompareState {
+    panes: [PaneId; 2],
+    highlight: bool,
+    /// Set while the viewport of one pane is being made to follow
+    /// the other, so that it doesn't echo back
+    syncing: bool,
+    diff: RefCell<Option<DiffCache>>,
+}
+
+impl CompareState {
+    fn index_of(&self, pane_id: PaneId) -> Option<usize> {
+        self.panes.iter().position(|&p| p == pane_id)
+    }
+
+    fn partner(&self, pane_id: PaneId) -> Option<PaneId> {
+        self.index_of(pane_id).map(|idx| self.panes[1 - idx])
+    }
+}
+
+/// Returns the trimmed text of each row of the pane, and the stable
+/// row index of the first of them
+fn row_texts(pane: &dyn Pane) -> (StableRowIndex, Vec<String>) {
+    let dims = pane.get_dimensions();
+    let (first_row, lines) = pane
+        .get_lines(dims.scrollback_top..dims.physical_top + dims.viewport_rows as StableRowIndex);
+    let texts = lines
+        .iter()
+        .map(|line| line.as_str().trim_end().to_string())
+        .collect();
+    (first_row, texts)
+}
+
+/// Returns, for each line of `a` and `b`, whether it is not part of
+/// the longest common subsequence of their lines
+fn differing_lines(a: &[String], b: &[String]) -> (Vec<bool>, Vec<bool>) {
+    let mut a_differs = vec![true; a.len()];
+    let mut b_differs = vec![true; b.len()];
+
+    let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    for differs in [&mut a_differs, &mut b_differs] {
+        let len = differs.len();
+        differs[..prefix].iter_mut().for_each(|d| *d = false);
+        differs[len - suffix..].iter_mut().for_each(|d| *d = false);
+    }
+
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+    let (n, m) = (a_mid.len(), b_mid.len());
+    if n == 0 || m == 0 {
+        return (a_differs, b_differs);
+    }
+
+    if (n + 1) * (m + 1) > MAX_DIFF_CELLS {
+        // Too large to diff; compare the lines by position
+        for (i, (x, y)) in a_mid.iter().zip(b_mid.iter()).enumerate() {
+            if x == y {
+                a_differs[prefix + i] = false;
+                b_differs[prefix + i] = false;
+            }
+        }
+        return (a_differs, b_differs);
+    }
+
+    // lcs[i * (m + 1) + j] is the length of the longest common
+    // subsequence of a_mid[i..] and b_mid[j..]
+    let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * (m + 1) + j] = if a_mid[i] == b_mid[j] {
+                lcs[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a_mid[i] == b_mid[j] {
+            a_differs[prefix + i] = false;
+            b_differs[prefix + j] = false;
+            i += 1;
+            j += 1;
+        } else if lcs[(i + 1) * (m + 1) + j] >= lcs[i * (m + 1) + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    (a_differs, b_differs)
+}
+
+impl TermWindow {
+    pub(crate) fn toggle_compare_panes(&mut self, pane: &Rc<dyn Pane>, args: &ComparePanes) {
+        if let Some(compare) = self.compare.take() {
+            log::info!(
+                "stopped comparing panes {} and {}",
+                compare.panes[0],
+                compare.panes[1]
+            );
+            if compare.index_of(pane.pane_id()).is_some() {
+                self.window.as_ref().unwrap().invalidate();
+                return;
+            }
+        }
+
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let other = match tab.get_pane_direction(args.direction).and_then(|idx| {
+            tab.iter_panes()
+                .into_iter()
+                .find(|p| p.index == idx)
+                .map(|p| p.pane)
+        }) {
+            Some(other) => other,
+            None => {
+                log::warn!(
+                    "ToggleComparePanes: there is no pane {:?} of pane {}",
+                    args.direction,
+                    pane.pane_id()
+                );
+                return;
+            }
+        };
+
+        log::info!("comparing panes {} and {}", pane.pane_id(), other.pane_id());
+        self.compare.replace(CompareState {
+            panes: [pane.pane_id(), other.pane_id()],
+            highlight: args.highlight_differences,
+            syncing: false,
+            diff: RefCell::new(None),
+        });
+
+        // Align the other pane with this one
+        let viewport = self.get_viewport(pane.pane_id());
+        self.sync_compared_viewport(pane.pane_id(), viewport, pane.get_dimensions());
+        self.window.as_ref().unwrap().invalidate();
+    }
+
+    /// Called when the viewport of a pane has changed; if the pane is
+    /// being compared, scrolls the other pane to match it
+    pub(crate) fn sync_compared_viewport(
+        &mut self,
+        pane_id: PaneId,
+        position: Option<StableRowIndex>,
+        dims: RenderableDimensions,
+    ) {
+        let partner = match self.compare.as_ref() {
+            Some(compare) if !compare.syncing => match compare.partner(pane_id) {
+                Some(partner) => partner,
+                None => return,
+            },
+            _ => return,
+        };
+        let partner = match Mux::get().and_then(|mux| mux.get_pane(partner)) {
+            Some(partner) => partner,
+            None => {
+                // One of the panes has been closed
+                self.compare.take();
+                return;
+            }
+        };
+
+        let offset = position.map(|pos| dims.physical_top - pos).unwrap_or(0);
+        let partner_dims = partner.get_dimensions();
+        let partner_position = if offset > 0 {
+            Some(partner_dims.physical_top - offset)
+        } else {
+            None
+        };
+
+        if let Some(compare) = self.compare.as_mut() {
+            compare.syncing = true;
+        }
+        self.set_viewport(partner.pane_id(), partner_position, partner_dims);
+        if let Some(compare) = self.compare.as_mut() {
+            compare.syncing = false;
+        }
+    }
+
+    /// Returns the rows of the pane that differ from the pane that it
+    /// is being compared with, if any
+    fn compared_differing_rows(&self, pane_id: PaneId) -> Option<HashSet<StableRowIndex>> {
+        let compare = self.compare.as_ref().filter(|c| c.highlight)?;
+        let idx = compare.index_of(pane_id)?;
+        let mux = Mux::get()?;
+        let panes = [
+            mux.get_pane(compare.panes[0])?,
+            mux.get_pane(compare.panes[1])?,
+        ];
+        let seqnos = [panes[0].get_current_seqno(), panes[1].get_current_seqno()];
+
+        let mut diff = compare.diff.borrow_mut();
+        if diff.as_ref().map(|d| d.seqnos) != Some(seqnos) {
+            let (a_first, a) = row_texts(&*panes[0]);
+            let (b_first, b) = row_texts(&*panes[1]);
+            let (a_differs, b_differs) = differing_lines(&a, &b);
+            let rows = |first: StableRowIndex, differs: Vec<bool>| {
+                differs
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, differs)| *differs)
+                    .map(|(idx, _)| first + idx as StableRowIndex)
+                    .collect()
+            };
+            diff.replace(DiffCache {
+                seqnos,
+                differing: [rows(a_first, a_differs), rows(b_first, b_differs)],
+            });
+        }
+        diff.as_ref().map(|d| d.differing[idx].clone())
+    }
+
+    /// Highlights the visible rows of the pane that differ from the
+    /// pane that it is being compared with
+    pub(crate) fn paint_compare_differences(
+        &self,
+        pos: &PositionedPane,
+        layers: &mut TripleLayerQuadAllocator,
+    ) -> anyhow::Result<()> {
+        let differing = match self.compared_differing_rows(pos.pane.pane_id()) {
+            Some(differing) if !differing.is_empty() => differing,
+            _ => return Ok(()),
+        };
+        let dims = pos.pane.get_dimensions();
+        let top = self
+            .get_viewport(pos.pane.pane_id())
+            .unwrap_or(dims.physical_top);
+
+        let (pane_left, pane_top) = self.pane_origin(pos)?;
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+        let color = self.config.pane_select_fg_color.to_linear().mul_alpha(0.15);
+        for row in 0..pos.height {
+            if differing.contains(&(top + row as StableRowIndex)) {
+                self.filled_rectangle(
+                    layers,
+                    0,
+                    euclid::rect(
+                        pane_left,
+                        pane_top + row as f32 * cell_height,
+                        pos.width as f32 * cell_width,
+                        cell_height,
+                    ),
+                    color,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split(' ').map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn diff() {
+        let (a, b) = differing_lines(&lines("a b c d e"), &lines("a x c e f"));
+        assert_eq!(a, vec![false, true, false, true, false]);
+        assert_eq!(b, vec![false, true, false, false, true]);
+
+        let (a, b) = differing_lines(&lines("a b"), &lines("a b"));
+        assert_eq!(a, vec![false, false]);
+        assert_eq!(b, vec![false, false]);
+
+        let (a, b) = differing_lines(&lines("a b c"), &[]);
+        assert_eq!(a, vec![true, true, true]);
+        assert!(b.is_empty());
+    }
+}
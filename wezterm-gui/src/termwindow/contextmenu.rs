@@ -0,0 +1,516 @@
+//! A menu of actions that pops up at the mouse pointer, such as the
+//! context menu of hyperlinks, panes and the tab bar.  The menu is
+//! shown using the system menus where they are available and enabled
+//! by `context_menu_style`; otherwise it is drawn as a modal in which
+//! items are chosen by clicking them, or with the arrow keys and Enter;
+//! clicking elsewhere or pressing Escape dismisses the menu.
+use crate::scripting::guiwin::GuiWin;
+use crate::termwindow::box_model::*;
+use crate::termwindow::modal::Modal;
+use crate::termwindow::render::{
+    BOTTOM_LEFT_ROUNDED_CORNER, BOTTOM_RIGHT_ROUNDED_CORNER, TOP_LEFT_ROUNDED_CORNER,
+    TOP_RIGHT_ROUNDED_CORNER,
+};
+use crate::termwindow::{DimensionContext, UIItem, UIItemType};
+use crate::utilsprites::RenderMetrics;
+use crate::TermWindow;
+use config::keyassignment::{
+    ClipboardCopyDestination, ClipboardPasteSource, ContextMenuEntry, KeyAssignment, SpawnCommand,
+    SpawnTabDomain,
+};
+use config::{ContextMenuStyle, Dimension};
+use mlua::FromLua;
+use mux::pane::Pane;
+use mux::Mux;
+use mux_lua::MuxPane;
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+use termwiz::cell::unicode_column_width;
+use wezterm_term::{KeyCode, KeyModifiers, MouseEvent};
+use window::color::LinearRgba;
+use window::{
+    MouseEvent as WindowMouseEvent, MouseEventKind as WMEK, MousePress, RectF, WindowOps,
+};
+
+pub struct ContextMenuItem {
+    pub label: String,
+    pub action: Box<dyn Fn(&mut TermWindow)>,
+}
+
+impl ContextMenuItem {
+    pub fn new(label: impl Into<String>, action: impl Fn(&mut TermWindow) + 'static) -> Self {
+        Self {
+            label: label.into(),
+            action: Box::new(action),
+        }
+    }
+}
+
+/// The part of the window for which a context menu is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextMenuKind {
+    Pane,
+    Tab,
+    TabBarBackground,
+}
+
+impl ContextMenuKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Pane => "Pane",
+            Self::Tab => "Tab",
+            Self::TabBarBackground => "TabBarBackground",
+        }
+    }
+
+    /// The entries that are shown unless the `context-menu` event
+    /// returns others
+    fn default_entries(self) -> Vec<ContextMenuEntry> {
+        fn entry(label: &str, action: KeyAssignment) -> ContextMenuEntry {
+            ContextMenuEntry {
+                label: label.to_string(),
+                action,
+            }
+        }
+        let split = || SpawnCommand {
+            domain: SpawnTabDomain::CurrentPaneDomain,
+            ..Default::default()
+        };
+        let new_tab = || {
+            entry(
+                "New Tab",
+                KeyAssignment::SpawnTab(SpawnTabDomain::CurrentPaneDomain),
+            )
+        };
+
+        match self {
+            Self::Pane => vec![
+                entry(
+                    "Copy",
+                    KeyAssignment::CopyTo(ClipboardCopyDestination::Clipboard),
+                ),
+                entry(
+                    "Paste",
+                    KeyAssignment::PasteFrom(ClipboardPasteSource::Clipboard),
+                ),
+                entry(
+                    "Split Horizontally",
+                    KeyAssignment::SplitHorizontal(split()),
+                ),
+                entry("Split Vertically", KeyAssignment::SplitVertical(split())),
+                entry("Toggle Zoom", KeyAssignment::TogglePaneZoomState),
+                entry(
+                    "Close Pane",
+                    KeyAssignment::CloseCurrentPane { confirm: true },
+                ),
+            ],
+            Self::Tab => vec![
+                new_tab(),
+                entry("Move Tab Left", KeyAssignment::MoveTabRelative(-1)),
+                entry("Move Tab Right", KeyAssignment::MoveTabRelative(1)),
+                entry(
+                    "Close Tab",
+                    KeyAssignment::CloseCurrentTab { confirm: true },
+                ),
+            ],
+            Self::TabBarBackground => vec![
+                new_tab(),
+                entry("Show Tab Navigator", KeyAssignment::ShowTabNavigator),
+                entry("Show Launcher", KeyAssignment::ShowLauncher),
+            ],
+        }
+    }
+}
+
+/// Gives the `context-menu` event the chance to replace the entries
+/// of the menu.  Returns None if the event isn't handled.
+fn call_context_menu_event(
+    window: GuiWin,
+    pane: MuxPane,
+    kind: ContextMenuKind,
+    defaults: Vec<ContextMenuEntry>,
+) -> Option<Vec<ContextMenuEntry>> {
+    match config::run_immediate_with_lua_config(|lua| {
+        if let Some(lua) = lua {
+            let v = config::lua::emit_sync_callback(
+                &*lua,
+                (
+                    "context-menu".to_string(),
+                    (window, pane, kind.name().to_string(), defaults),
+                ),
+            )?;
+            match &v {
+                mlua::Value::Nil => Ok(None),
+                _ => Ok(Some(<Vec<ContextMenuEntry>>::from_lua(v, &*lua)?)),
+            }
+        } else {
+            Ok(None)
+        }
+    }) {
+        Ok(entries) => entries,
+        Err(err) => {
+            log::warn!("context-menu: {}", err);
+            None
+        }
+    }
+}
+
+pub struct ContextMenu {
+    element: RefCell<Option<Vec<ComputedElement>>>,
+    /// Where the menu was opened, in window pixel coordinates
+    position: (f32, f32),
+    items: Vec<ContextMenuItem>,
+    selected: RefCell<Option<usize>>,
+}
+
+/// Returns the origin of a menu of the given size that is opened at
+/// `position`, moved so that the menu fits within `bounds`
+fn menu_origin(position: (f32, f32), size: (f32, f32), bounds: RectF) -> (f32, f32) {
+    let x = if position.0 + size.0 > bounds.max_x() {
+        position.0 - size.0
+    } else {
+        position.0
+    };
+    let y = if position.1 + size.1 > bounds.max_y() {
+        position.1 - size.1
+    } else {
+        position.1
+    };
+    (x.max(bounds.min_x()), y.max(bounds.min_y()))
+}
+
+impl ContextMenu {
+    pub fn new(position: (f32, f32), items: Vec<ContextMenuItem>) -> Self {
+        Self {
+            element: RefCell::new(None),
+            position,
+            items,
+            selected: RefCell::new(None),
+        }
+    }
+
+    fn activate(&self, idx: usize, term_window: &mut TermWindow) {
+        term_window.cancel_modal();
+        if let Some(item) = self.items.get(idx) {
+            (item.action)(term_window);
+        }
+    }
+
+    fn compute(&self, term_window: &mut TermWindow) -> anyhow::Result<Vec<ComputedElement>> {
+        let font = term_window
+            .fonts
+            .char_select_font()
+            .expect("to resolve char selection font");
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+
+        let fg = term_window.config.pane_select_fg_color.to_linear();
+        let bg = term_window.config.pane_select_bg_color.to_linear();
+        let selected = *self.selected.borrow();
+
+        let label_width = self
+            .items
+            .iter()
+            .map(|item| unicode_column_width(&item.label, None))
+            .max()
+            .unwrap_or(0);
+
+        let elements = self
+            .items
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| {
+                let (item_fg, item_bg) = if selected == Some(idx) {
+                    (bg, fg)
+                } else {
+                    (fg, LinearRgba::TRANSPARENT)
+                };
+                Element::new(&font, ElementContent::Text(item.label.clone()))
+                    .colors(ElementColors {
+                        border: BorderColor::default(),
+                        bg: item_bg.into(),
+                        text: item_fg.into(),
+                    })
+                    .hover_colors(Some(ElementColors {
+                        border: BorderColor::default(),
+                        bg: fg.into(),
+                        text: bg.into(),
+                    }))
+                    .padding(BoxDimension {
+                        left: Dimension::Cells(0.5),
+                        right: Dimension::Cells(0.5),
+                        top: Dimension::Cells(0.1),
+                        bottom: Dimension::Cells(0.1),
+                    })
+                    .min_width(Some(Dimension::Cells(label_width as f32 + 1.)))
+                    .item_type(UIItemType::ContextMenuItem(idx))
+                    .display(DisplayType::Block)
+            })
+            .collect();
+
+        let element = Element::new(&font, ElementContent::Children(elements))
+            .colors(ElementColors {
+                border: BorderColor::new(fg.into()),
+                bg: bg.into(),
+                text: fg.into(),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.25),
+                right: Dimension::Cells(0.25),
+                top: Dimension::Cells(0.25),
+                bottom: Dimension::Cells(0.25),
+            })
+            .border(BoxDimension::new(Dimension::Pixels(1.)))
+            .border_corners(Some(Corners {
+                top_left: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: TOP_LEFT_ROUNDED_CORNER,
+                },
+                top_right: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: TOP_RIGHT_ROUNDED_CORNER,
+                },
+                bottom_left: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: BOTTOM_LEFT_ROUNDED_CORNER,
+                },
+                bottom_right: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: BOTTOM_RIGHT_ROUNDED_CORNER,
+                },
+            }));
+
+        let dimensions = term_window.dimensions;
+        let cell_width = metrics.cell_size.width as f32;
+        let cell_height = metrics.cell_size.height as f32;
+        // An estimate of the size of the menu, so that it can be moved
+        // to fit within the window before it is laid out
+        let size = (
+            (label_width as f32 + 2.5) * cell_width,
+            self.items.len() as f32 * cell_height * 1.2 + cell_height,
+        );
+        let window_bounds = euclid::rect(
+            0.,
+            0.,
+            dimensions.pixel_width as f32,
+            dimensions.pixel_height as f32,
+        );
+        let (x, y) = menu_origin(self.position, size, window_bounds);
+
+        let computed = term_window.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_height as f32,
+                    pixel_cell: cell_height,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_width as f32,
+                    pixel_cell: cell_width,
+                },
+                bounds: euclid::rect(x, y, size.0, size.1),
+                metrics: &metrics,
+                gl_state: term_window.render_state.as_ref().unwrap(),
+                zindex: 100,
+            },
+            &element,
+        )?;
+
+        Ok(vec![computed])
+    }
+}
+
+impl Modal for ContextMenu {
+    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn key_down(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<()> {
+        let num_items = self.items.len();
+        if num_items == 0 {
+            term_window.cancel_modal();
+            return Ok(());
+        }
+        match (key, mods) {
+            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+                term_window.cancel_modal();
+                return Ok(());
+            }
+            (KeyCode::UpArrow, KeyModifiers::NONE) | (KeyCode::Char('k'), KeyModifiers::NONE) => {
+                let mut selected = self.selected.borrow_mut();
+                *selected = Some(match *selected {
+                    Some(idx) if idx > 0 => idx - 1,
+                    _ => num_items - 1,
+                });
+            }
+            (KeyCode::DownArrow, KeyModifiers::NONE) | (KeyCode::Char('j'), KeyModifiers::NONE) => {
+                let mut selected = self.selected.borrow_mut();
+                *selected = Some(match *selected {
+                    Some(idx) if idx + 1 < num_items => idx + 1,
+                    _ => 0,
+                });
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                let selected = *self.selected.borrow();
+                if let Some(idx) = selected {
+                    self.activate(idx, term_window);
+                }
+                return Ok(());
+            }
+            _ => return Ok(()),
+        }
+        term_window.invalidate_modal();
+        Ok(())
+    }
+
+    fn computed_element(
+        &self,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<Ref<[ComputedElement]>> {
+        if self.element.borrow().is_none() {
+            let element = self.compute(term_window)?;
+            self.element.borrow_mut().replace(element);
+        }
+        Ok(Ref::map(self.element.borrow(), |v| {
+            v.as_ref().unwrap().as_slice()
+        }))
+    }
+
+    fn reconfigure(&self, _term_window: &mut TermWindow) {
+        self.element.borrow_mut().take();
+    }
+}
+
+impl TermWindow {
+    /// Shows the context menu of `kind`, whose actions are performed
+    /// on behalf of `pane`
+    pub fn show_pane_context_menu(&mut self, pane: &Rc<dyn Pane>, kind: ContextMenuKind) {
+        let defaults = kind.default_entries();
+        let window = GuiWin::new(self);
+        let entries =
+            call_context_menu_event(window, MuxPane(pane.pane_id()), kind, defaults.clone())
+                .unwrap_or(defaults);
+
+        let pane_id = pane.pane_id();
+        let items = entries
+            .into_iter()
+            .map(|entry| {
+                let action = entry.action;
+                ContextMenuItem::new(entry.label, move |tw| {
+                    if let Some(pane) = Mux::get().unwrap().get_pane(pane_id) {
+                        if let Err(err) = tw.perform_assignment_unless_locked(&pane, &action) {
+                            log::error!("context menu: {:#}", err);
+                        }
+                    }
+                })
+            })
+            .collect();
+        self.show_context_menu(items);
+    }
+
+    /// Shows a context menu with the given items at the mouse pointer
+    pub fn show_context_menu(&mut self, items: Vec<ContextMenuItem>) {
+        if items.is_empty() {
+            return;
+        }
+        if self.config.context_menu_style == ContextMenuStyle::Native {
+            let labels = items.iter().map(|item| item.label.clone()).collect();
+            if let Some(window) = self.window.as_ref() {
+                if window.show_context_menu(labels) {
+                    self.native_context_menu.replace(items);
+                    return;
+                }
+            }
+        }
+        let position = match self.current_mouse_event.as_ref() {
+            Some(event) => (event.coords.x as f32, event.coords.y as f32),
+            None => (0., 0.),
+        };
+        let menu = ContextMenu::new(position, items);
+        self.modal.borrow_mut().replace(Rc::new(menu));
+        self.invalidate_modal();
+    }
+
+    /// Performs the chosen item of the native context menu
+    pub(crate) fn native_context_menu_item_selected(&mut self, idx: usize) {
+        if let Some(items) = self.native_context_menu.take() {
+            if let Some(item) = items.get(idx) {
+                (item.action)(self);
+            }
+        }
+    }
+
+    /// Routes mouse events to an open context menu.  Returns true if
+    /// the event was consumed by the menu, in which case it must not
+    /// be passed on to the terminal.
+    pub(crate) fn context_menu_mouse_event(
+        &mut self,
+        ui_item: Option<&UIItem>,
+        event: &WindowMouseEvent,
+    ) -> bool {
+        let modal = match self.get_modal() {
+            Some(modal) => modal,
+            None => return false,
+        };
+        let menu = match modal.downcast_ref::<ContextMenu>() {
+            Some(menu) => menu,
+            None => return false,
+        };
+        let item = match ui_item.map(|item| &item.item_type) {
+            Some(UIItemType::ContextMenuItem(idx)) => Some(*idx),
+            _ => None,
+        };
+        match (&event.kind, item) {
+            (WMEK::Release(MousePress::Left), Some(idx)) => menu.activate(idx, self),
+            (WMEK::Press(_), None) => self.cancel_modal(),
+            _ => {}
+        }
+        !matches!(event.kind, WMEK::Move) || item.is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn origin() {
+        let bounds = euclid::rect(0., 0., 800., 600.);
+        assert_eq!(menu_origin((10., 20.), (200., 100.), bounds), (10., 20.));
+        assert_eq!(menu_origin((700., 20.), (200., 100.), bounds), (500., 20.));
+        assert_eq!(
+            menu_origin((700., 550.), (200., 100.), bounds),
+            (500., 450.)
+        );
+        assert_eq!(menu_origin((100., 50.), (200., 100.), bounds), (100., 50.));
+        assert_eq!(menu_origin((150., 550.), (200., 700.), bounds), (150., 0.));
+    }
+
+    #[test]
+    fn default_entries_round_trip() {
+        use wezterm_dynamic::{FromDynamic, ToDynamic};
+        // The defaults are passed to the `context-menu` event, which
+        // may hand them back
+        for kind in [
+            ContextMenuKind::Pane,
+            ContextMenuKind::Tab,
+            ContextMenuKind::TabBarBackground,
+        ] {
+            let entries = kind.default_entries();
+            assert!(!entries.is_empty());
+            let value = entries.to_dynamic();
+            let decoded =
+                <Vec<ContextMenuEntry>>::from_dynamic(&value, Default::default()).unwrap();
+            assert_eq!(decoded, entries);
+        }
+    }
+}
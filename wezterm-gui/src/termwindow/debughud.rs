@@ -0,0 +1,159 @@
+//! Renders the `enable_debug_hud` overlay, which shows the latency
+//! and throughput measurements from `mux::panestats` in the top
+//! right corner of each pane, along with those from `mux::domainstats`
+//! for panes in remote domains
+use crate::termwindow::box_model::*;
+use crate::termwindow::DimensionContext;
+use crate::utilsprites::RenderMetrics;
+use crate::TermWindow;
+use config::Dimension;
+use mux::domainstats::{domain_metrics, has_metrics, DomainMetrics};
+use mux::panestats::{pane_metrics, LatencySummary, PaneMetrics};
+use mux::tab::PositionedPane;
+use std::time::{Duration, Instant};
+use window::color::LinearRgba;
+
+/// How often the HUD is refreshed when nothing else causes a repaint
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+fn format_latency(label: &str, summary: &LatencySummary) -> String {
+    if summary.samples == 0 {
+        return format!("{}: -", label);
+    }
+    format!(
+        "{}: p50 {:.1?} p95 {:.1?} p99 {:.1?} max {:.1?} (n={})",
+        label, summary.p50, summary.p95, summary.p99, summary.max, summary.samples
+    )
+}
+
+fn format_throughput(metrics: &PaneMetrics) -> String {
+    format!(
+        "pty: {:.1} KiB/s, {:.1} KiB total",
+        metrics.pty_bytes_per_second as f64 / 1024.,
+        metrics.pty_bytes_total as f64 / 1024.
+    )
+}
+
+fn format_network(metrics: &DomainMetrics) -> String {
+    format!(
+        "net: up {:.1} KiB/s, down {:.1} KiB/s",
+        metrics.sent_bytes_per_second as f64 / 1024.,
+        metrics.received_bytes_per_second as f64 / 1024.
+    )
+}
+
+impl TermWindow {
+    pub(crate) fn paint_debug_hud(&mut self) -> anyhow::Result<()> {
+        if !self.config.enable_debug_hud {
+            return Ok(());
+        }
+
+        for pos in self.get_panes_to_render() {
+            let computed = self.compute_debug_hud(&pos)?;
+            let gl_state = self.render_state.as_ref().unwrap();
+            self.render_element(&computed, gl_state, None)?;
+        }
+
+        self.update_next_frame_time(Some(Instant::now() + REFRESH_INTERVAL));
+        Ok(())
+    }
+
+    fn compute_debug_hud(&mut self, pos: &PositionedPane) -> anyhow::Result<ComputedElement> {
+        let metrics = pane_metrics(pos.pane.pane_id());
+        let mut lines = vec![
+            format!("pane {}", metrics.pane_id),
+            format_latency("key->render", &metrics.input_latency),
+            format_latency("frame", &metrics.frame_time),
+            format_throughput(&metrics),
+        ];
+
+        // Show the network measurements alongside the rendering ones,
+        // so that it is clear which of them is responsible for slowness
+        let domain_id = pos.pane.domain_id();
+        if has_metrics(domain_id) {
+            let domain = domain_metrics(domain_id);
+            lines.push(format_latency("rtt", &domain.rtt));
+            lines.push(format_network(&domain));
+        }
+
+        let font = self.fonts.title_font()?;
+        let render_metrics = RenderMetrics::with_font_metrics(&font.metrics());
+        let text = self.config.pane_select_fg_color.to_linear();
+
+        let children = lines
+            .into_iter()
+            .map(|line| {
+                Element::new(&font, ElementContent::Text(line))
+                    .colors(ElementColors {
+                        border: BorderColor::default(),
+                        bg: LinearRgba::TRANSPARENT.into(),
+                        text: text.into(),
+                    })
+                    .display(DisplayType::Block)
+            })
+            .collect();
+
+        let element = Element::new(&font, ElementContent::Children(children))
+            .colors(ElementColors {
+                border: BorderColor::new(text.into()),
+                bg: self
+                    .config
+                    .pane_select_bg_color
+                    .to_linear()
+                    .mul_alpha(0.85)
+                    .into(),
+                text: text.into(),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.5),
+                right: Dimension::Cells(0.5),
+                top: Dimension::Cells(0.25),
+                bottom: Dimension::Cells(0.25),
+            })
+            .border(BoxDimension::new(Dimension::Pixels(1.)));
+
+        let dimensions = self.dimensions;
+        let pixel_width = dimensions.pixel_width as f32;
+        let pixel_height = dimensions.pixel_height as f32;
+
+        let mut computed = self.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: pixel_height,
+                    pixel_cell: render_metrics.cell_size.height as f32,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: pixel_width,
+                    pixel_cell: render_metrics.cell_size.width as f32,
+                },
+                bounds: euclid::rect(0., 0., pixel_width, pixel_height),
+                metrics: &render_metrics,
+                gl_state: self.render_state.as_ref().unwrap(),
+                zindex: 100,
+            },
+            &element,
+        )?;
+
+        // Place the HUD in the top right corner of the pane
+        let (padding_left, padding_top) = self.padding_left_top();
+        let border = self.get_os_border();
+        let top_bar_height = if self.show_tab_bar && !self.config.tab_bar_at_bottom {
+            self.tab_bar_pixel_height()?
+        } else {
+            0.
+        };
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+
+        let pane_right =
+            padding_left + border.left.get() as f32 + (pos.left + pos.width) as f32 * cell_width;
+        let pane_top =
+            top_bar_height + padding_top + border.top.get() as f32 + pos.top as f32 * cell_height;
+        let x = (pane_right - computed.bounds.width()).max(0.);
+
+        computed.translate(euclid::vec2(x, pane_top));
+        Ok(computed)
+    }
+}
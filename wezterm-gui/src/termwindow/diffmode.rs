@@ -0,0 +1,442 @@
+//! Implements the diff rendering mode that is toggled for a pane by
+//! `TogglePaneDiffMode`.  Unified diffs in the output of the pane are
+//! recognized and rendered in a similar way to `delta`: added and
+//! removed lines get a tinted background, the words that changed
+//! between a removed line and the added line that replaced it are
+//! highlighted, and hunks can be collapsed via `ToggleDiffHunk`.
+//!
+//! This is a transform applied to the lines as they are rendered;
+//! the content of the pane and the colors chosen by the application
+//! for the text are left alone.  Because the pane has a fixed number
+//! of rows, collapsing a hunk blanks out its lines and summarizes
+//! them on the hunk header, rather than removing them.
+use crate::TermWindow;
+use mux::pane::Pane;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::rc::Rc;
+use termwiz::cell::{CellAttributes, Intensity};
+use termwiz::color::{ColorAttribute, RgbColor};
+use termwiz::surface::Line;
+use wezterm_term::StableRowIndex;
+
+/// How far above the viewport to look for the header of the hunk
+/// that holds the first visible line
+const LOOKBACK_ROWS: StableRowIndex = 1000;
+
+/// Above this many tokens in a pair of lines, the lines are
+/// highlighted as a whole rather than word by word
+const MAX_WORD_DIFF_TOKENS: usize = 500;
+
+lazy_static::lazy_static! {
+    static ref HUNK_HEADER: regex::Regex =
+        regex::Regex::new(r"^@@ -\d+(?:,(\d+))? \+\d+(?:,(\d+))? @@").unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RowKind {
+    HunkHeader,
+    Context,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DiffRow {
+    kind: RowKind,
+    /// The index of the header of the hunk that holds this row
+    header: usize,
+}
+
+/// Returns the old and new line counts from a hunk header
+fn parse_hunk_header(text: &str) -> Option<(usize, usize)> {
+    let captures = HUNK_HEADER.captures(text)?;
+    let count = |idx| {
+        captures
+            .get(idx)
+            .map(|m| m.as_str().parse().unwrap_or(1))
+            .unwrap_or(1)
+    };
+    Some((count(1), count(2)))
+}
+
+/// Classifies each row as part of a hunk of a unified diff, or None
+/// if it is not part of a hunk.  The line counts in the hunk header
+/// determine where the hunk ends.
+fn classify(texts: &[String]) -> Vec<Option<DiffRow>> {
+    let mut rows = vec![None; texts.len()];
+    let mut idx = 0;
+    while idx < texts.len() {
+        let (mut old, mut new) = match parse_hunk_header(&texts[idx]) {
+            Some(counts) => counts,
+            None => {
+                idx += 1;
+                continue;
+            }
+        };
+        let header = idx;
+        rows[idx] = Some(DiffRow {
+            kind: RowKind::HunkHeader,
+            header,
+        });
+        idx += 1;
+        while idx < texts.len() && (old > 0 || new > 0) {
+            let text = &texts[idx];
+            let kind = match text.chars().next() {
+                // Context lines that are empty may have had their
+                // leading space trimmed
+                Some(' ') | None if old > 0 && new > 0 => {
+                    old -= 1;
+                    new -= 1;
+                    RowKind::Context
+                }
+                Some('-') if old > 0 => {
+                    old -= 1;
+                    RowKind::Removed
+                }
+                Some('+') if new > 0 => {
+                    new -= 1;
+                    RowKind::Added
+                }
+                // "\ No newline at end of file"
+                Some('\\') => RowKind::Context,
+                _ => break,
+            };
+            rows[idx] = Some(DiffRow { kind, header });
+            idx += 1;
+        }
+    }
+    rows
+}
+
+/// Splits text into words and individual non-word characters,
+/// returning the byte range of each
+fn tokenize(text: &str) -> Vec<Range<usize>> {
+    let mut tokens: Vec<Range<usize>> = vec![];
+    let mut in_word = false;
+    for (idx, c) in text.char_indices() {
+        let is_word = c.is_alphanumeric() || c == '_';
+        match tokens.last_mut() {
+            Some(last) if is_word && in_word => last.end = idx + c.len_utf8(),
+            _ => tokens.push(idx..idx + c.len_utf8()),
+        }
+        in_word = is_word;
+    }
+    tokens
+}
+
+/// Returns the byte ranges of `old` and `new` that differ between
+/// them, by computing the longest common subsequence of their words
+fn changed_words(old: &str, new: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let a = tokenize(old);
+    let b = tokenize(new);
+    if a.len() > MAX_WORD_DIFF_TOKENS || b.len() > MAX_WORD_DIFF_TOKENS {
+        return (vec![0..old.len()], vec![0..new.len()]);
+    }
+    let (n, m) = (a.len(), b.len());
+    let same = |i: usize, j: usize| old[a[i].clone()] == new[b[j].clone()];
+
+    let mut lcs = vec![0u16; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * (m + 1) + j] = if same(i, j) {
+                lcs[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let mut a_changed = vec![true; n];
+    let mut b_changed = vec![true; m];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if same(i, j) {
+            a_changed[i] = false;
+            b_changed[j] = false;
+            i += 1;
+            j += 1;
+        } else if lcs[(i + 1) * (m + 1) + j] >= lcs[i * (m + 1) + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    let ranges = |tokens: Vec<Range<usize>>, changed: Vec<bool>| {
+        let mut ranges: Vec<Range<usize>> = vec![];
+        for (token, changed) in tokens.into_iter().zip(changed) {
+            if !changed {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(last) if last.end == token.start => last.end = token.end,
+                _ => ranges.push(token),
+            }
+        }
+        ranges
+    };
+    (ranges(a, a_changed), ranges(b, b_changed))
+}
+
+fn background(red: u8, green: u8, blue: u8) -> ColorAttribute {
+    ColorAttribute::TrueColorWithDefaultFallback(RgbColor::new_8bpc(red, green, blue).into())
+}
+
+/// Returns a copy of the line, padded to `cols`, with the background
+/// of the whole line set to `line_bg` and that of the cells that hold
+/// the byte ranges of its text set to `word_bg`
+fn highlight(
+    line: &Line,
+    cols: usize,
+    line_bg: &ColorAttribute,
+    words: &[Range<usize>],
+    word_bg: &ColorAttribute,
+) -> Line {
+    let seqno = line.current_seqno();
+    let mut text_len = 0;
+    let mut word_cells = vec![];
+    for cell in line.visible_cells() {
+        if words.iter().any(|range| range.contains(&text_len)) {
+            word_cells.push(cell.cell_index()..cell.cell_index() + cell.width());
+        }
+        text_len += cell.str().len();
+    }
+
+    let mut line = line.clone();
+    if line.len() < cols {
+        line.resize(cols, seqno);
+    }
+    let cells = line.cells_mut_for_attr_changes_only();
+    for cell in cells.iter_mut() {
+        cell.attrs_mut().set_background(line_bg.clone());
+    }
+    for range in word_cells {
+        for cell in cells[range].iter_mut() {
+            cell.attrs_mut().set_background(word_bg.clone());
+        }
+    }
+    line
+}
+
+/// Returns the lines in `range` that are rendered differently when
+/// the pane is in diff mode, keyed by their stable row index
+fn diff_mode_lines(
+    pane: &dyn Pane,
+    range: Range<StableRowIndex>,
+    collapsed: &HashSet<StableRowIndex>,
+) -> HashMap<StableRowIndex, Line> {
+    let dims = pane.get_dimensions();
+    let start = (range.start - LOOKBACK_ROWS).max(dims.scrollback_top);
+    let (first_row, lines) = pane.get_lines(start..range.end);
+    let texts: Vec<String> = lines
+        .iter()
+        .map(|line| line.as_str().trim_end().to_string())
+        .collect();
+    let rows = classify(&texts);
+
+    let removed_bg = background(0x3f, 0x00, 0x01);
+    let removed_word_bg = background(0x90, 0x10, 0x11);
+    let added_bg = background(0x00, 0x28, 0x00);
+    let added_word_bg = background(0x00, 0x60, 0x00);
+    let stable_row = |idx: usize| first_row + idx as StableRowIndex;
+
+    // Pair each run of removed lines with the run of added lines
+    // that follows it, to find the words that changed
+    let mut words: HashMap<usize, Vec<Range<usize>>> = HashMap::new();
+    let mut idx = 0;
+    while idx < rows.len() {
+        if rows[idx].map(|r| r.kind) != Some(RowKind::Removed) {
+            idx += 1;
+            continue;
+        }
+        let removed_start = idx;
+        while idx < rows.len() && rows[idx].map(|r| r.kind) == Some(RowKind::Removed) {
+            idx += 1;
+        }
+        let added_start = idx;
+        while idx < rows.len() && rows[idx].map(|r| r.kind) == Some(RowKind::Added) {
+            idx += 1;
+        }
+        let pairs = (added_start - removed_start).min(idx - added_start);
+        for n in 0..pairs {
+            let (old, new) = (removed_start + n, added_start + n);
+            // Skip the leading - and + when comparing
+            let (old_words, new_words) = changed_words(&texts[old][1..], &texts[new][1..]);
+            let shift = |ranges: Vec<Range<usize>>| {
+                ranges
+                    .into_iter()
+                    .map(|r| r.start + 1..r.end + 1)
+                    .collect::<Vec<_>>()
+            };
+            words.insert(old, shift(old_words));
+            words.insert(new, shift(new_words));
+        }
+    }
+
+    let mut result = HashMap::new();
+    for (idx, (line, row)) in lines.iter().zip(rows.iter()).enumerate() {
+        let row_idx = stable_row(idx);
+        if row_idx < range.start {
+            continue;
+        }
+        let row = match row {
+            Some(row) => row,
+            None => continue,
+        };
+        let is_collapsed = collapsed.contains(&stable_row(row.header));
+        let no_words = vec![];
+        let words = words.get(&idx).unwrap_or(&no_words);
+        let rendered = match row.kind {
+            RowKind::HunkHeader => {
+                let mut line = line.clone();
+                let seqno = line.current_seqno();
+                if is_collapsed {
+                    let hidden = rows[idx + 1..]
+                        .iter()
+                        .take_while(|r| r.map(|r| r.header) == Some(row.header))
+                        .count();
+                    let mut attr = CellAttributes::default();
+                    attr.set_intensity(Intensity::Half);
+                    line.overlay_text_with_attribute(
+                        texts[idx].chars().count() + 1,
+                        &format!(
+                            "\u{22ef} {} line{} collapsed",
+                            hidden,
+                            if hidden == 1 { "" } else { "s" }
+                        ),
+                        attr,
+                        seqno,
+                    );
+                }
+                for cell in line.cells_mut_for_attr_changes_only() {
+                    cell.attrs_mut().set_intensity(Intensity::Bold);
+                }
+                line
+            }
+            _ if is_collapsed => Line::with_width(dims.cols, line.current_seqno()),
+            RowKind::Removed => highlight(line, dims.cols, &removed_bg, words, &removed_word_bg),
+            RowKind::Added => highlight(line, dims.cols, &added_bg, words, &added_word_bg),
+            RowKind::Context => continue,
+        };
+        result.insert(row_idx, rendered);
+    }
+    result
+}
+
+impl TermWindow {
+    /// Returns the lines of the pane that are rendered differently
+    /// because it is in diff mode, keyed by their stable row index
+    pub(crate) fn diff_mode_lines(
+        &self,
+        pane: &Rc<dyn Pane>,
+        range: Range<StableRowIndex>,
+    ) -> HashMap<StableRowIndex, Line> {
+        let state = self.pane_state(pane.pane_id());
+        if !state.diff_mode {
+            return HashMap::new();
+        }
+        let collapsed = state.collapsed_hunks.clone();
+        drop(state);
+        diff_mode_lines(&**pane, range, &collapsed)
+    }
+
+    pub(crate) fn toggle_pane_diff_mode(&mut self, pane: &Rc<dyn Pane>) {
+        let mut state = self.pane_state(pane.pane_id());
+        state.diff_mode = !state.diff_mode;
+        state.collapsed_hunks.clear();
+        drop(state);
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    /// Collapses or expands the hunk under the mouse cursor, or if the
+    /// mouse isn't over the pane, the hunk that holds the cursor
+    pub(crate) fn toggle_diff_hunk(&mut self, pane: &Rc<dyn Pane>) {
+        if !self.pane_state(pane.pane_id()).diff_mode {
+            return;
+        }
+        let row = match self.pane_state(pane.pane_id()).mouse_terminal_coords {
+            Some((_, row)) => row,
+            None => pane.get_cursor_position().y,
+        };
+
+        let dims = pane.get_dimensions();
+        let start = (row - LOOKBACK_ROWS).max(dims.scrollback_top);
+        let (first_row, lines) = pane.get_lines(start..row + 1);
+        let texts: Vec<String> = lines
+            .iter()
+            .map(|line| line.as_str().trim_end().to_string())
+            .collect();
+        let header = match classify(&texts).last().copied().flatten() {
+            Some(diff_row) => first_row + diff_row.header as StableRowIndex,
+            None => return,
+        };
+
+        let mut state = self.pane_state(pane.pane_id());
+        if !state.collapsed_hunks.remove(&header) {
+            state.collapsed_hunks.insert(header);
+        }
+        drop(state);
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn classifies_hunks() {
+        let texts: Vec<String> = [
+            "diff --git a/f b/f",
+            "--- a/f",
+            "+++ b/f",
+            "@@ -1,3 +1,3 @@ fn main",
+            " one",
+            "-two",
+            "+2",
+            "",
+            "$ echo done",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let kinds: Vec<Option<RowKind>> = classify(&texts)
+            .into_iter()
+            .map(|r| r.map(|r| r.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                None,
+                None,
+                None,
+                Some(RowKind::HunkHeader),
+                Some(RowKind::Context),
+                Some(RowKind::Removed),
+                Some(RowKind::Added),
+                Some(RowKind::Context),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn word_diff() {
+        let old = "let x = foo(1);";
+        let new = "let y = foo(10);";
+        let (a, b) = changed_words(old, new);
+        assert_eq!(
+            a.iter().map(|r| &old[r.clone()]).collect::<Vec<_>>(),
+            vec!["x", "1"]
+        );
+        assert_eq!(
+            b.iter().map(|r| &new[r.clone()]).collect::<Vec<_>>(),
+            vec!["y", "10"]
+        );
+    }
+}
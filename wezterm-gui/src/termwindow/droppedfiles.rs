@@ -0,0 +1,185 @@
+//! Handles files that are dropped onto a pane.  The `dropped-files`
+//! event gets the first chance to act on them; unless it returns
+//! `false`, their paths are pasted into the pane, after uploading the
+//! files to the remote host when `dropped_files_action` asks for it.
+use crate::scripting::guiwin::GuiWin;
+use crate::TermWindow;
+use anyhow::Context;
+use config::{DroppedFileQuoting, DroppedFilesAction};
+use mux::pane::Pane;
+use mux::ssh::RemoteSshDomain;
+use mux::Mux;
+use mux_lua::MuxPane;
+use smol::io::AsyncWriteExt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use wezterm_ssh::Session;
+
+/// Returns the ssh session of the domain of the pane, if it is
+/// in an ssh domain
+fn ssh_session_for_pane(pane: &Rc<dyn Pane>) -> Option<Session> {
+    let mux = Mux::get()?;
+    let domain = mux.get_domain(pane.domain_id())?;
+    domain.downcast_ref::<RemoteSshDomain>()?.session()
+}
+
+/// Returns the working directory that the pane reported via OSC 7
+fn reported_cwd(pane: &Rc<dyn Pane>) -> Option<String> {
+    let url = pane.get_current_working_dir()?;
+    percent_encoding::percent_decode_str(url.path())
+        .decode_utf8()
+        .ok()
+        .map(|path| path.into_owned())
+}
+
+/// Returns the path in `dir` on the remote host to which `local` is uploaded
+fn remote_upload_path(dir: &str, local: &Path) -> anyhow::Result<String> {
+    let name = local
+        .file_name()
+        .with_context(|| format!("{} has no file name", local.display()))?
+        .to_string_lossy();
+    let dir = dir.trim_end_matches('/');
+    Ok(format!("{}/{}", dir, name))
+}
+
+async fn upload_files(
+    session: &Session,
+    paths: &[PathBuf],
+    dir: Option<String>,
+) -> anyhow::Result<Vec<String>> {
+    let sftp = session.sftp();
+    let dir = match dir {
+        Some(dir) => dir,
+        None => sftp
+            .canonicalize(".")
+            .await
+            .context("resolving the remote home directory")?
+            .into_string(),
+    };
+
+    let mut remote_paths = vec![];
+    for path in paths {
+        let remote_path = remote_upload_path(&dir, path)?;
+        let data = smol::fs::read(path)
+            .await
+            .with_context(|| format!("reading {}", path.display()))?;
+        let mut file = sftp
+            .create(remote_path.as_str())
+            .await
+            .with_context(|| format!("creating {}", remote_path))?;
+        file.write_all(&data)
+            .await
+            .with_context(|| format!("writing {}", remote_path))?;
+        file.close().await?;
+        log::info!("uploaded {} to {}", path.display(), remote_path);
+        remote_paths.push(remote_path);
+    }
+    Ok(remote_paths)
+}
+
+async fn emit_dropped_files(
+    lua: Option<Rc<mlua::Lua>>,
+    window: GuiWin,
+    pane: MuxPane,
+    paths: Vec<String>,
+) -> anyhow::Result<bool> {
+    match lua {
+        Some(lua) => {
+            let args = lua.pack_multi((window, pane, paths))?;
+            Ok(config::lua::emit_event(&lua, ("dropped-files".to_string(), args)).await?)
+        }
+        None => Ok(true),
+    }
+}
+
+impl TermWindow {
+    pub fn dropped_files(&mut self, pane: Rc<dyn Pane>, paths: Vec<PathBuf>) {
+        if self.input_is_locked(pane.pane_id()) {
+            log::warn!(
+                "input to pane {} is locked; ignoring dropped files",
+                pane.pane_id()
+            );
+            return;
+        }
+
+        // Files dropped into a WSL pane are translated to the
+        // paths by which the distribution can reach them, and
+        // are quoted for its unix shell
+        let wsl = self.wsl_domain_for_pane(pane.pane_id());
+        let local_text = paths
+            .iter()
+            .map(|path| {
+                let path = path.to_string_lossy();
+                match wsl.as_ref().and_then(|wsl| wsl.windows_path_to_wsl(&path)) {
+                    Some(path) => DroppedFileQuoting::Posix.escape(&path),
+                    None => self.config.quote_dropped_files.escape(&path),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let session = match self.config.dropped_files_action {
+            DroppedFilesAction::UploadToRemote => ssh_session_for_pane(&pane),
+            DroppedFilesAction::PastePaths => None,
+        };
+        let upload_dir = self
+            .config
+            .dropped_files_upload_dir
+            .clone()
+            .or_else(|| reported_cwd(&pane));
+
+        let window = GuiWin::new(self);
+        let mux_pane = MuxPane(pane.pane_id());
+        let names = paths
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+
+        promise::spawn::spawn(async move {
+            match config::with_lua_config_on_main_thread(move |lua| {
+                emit_dropped_files(lua, window, mux_pane, names)
+            })
+            .await
+            {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(err) => log::error!("while processing dropped-files event: {:#}", err),
+            }
+
+            let text = match session {
+                Some(session) => match upload_files(&session, &paths, upload_dir).await {
+                    Ok(remote_paths) => remote_paths
+                        .iter()
+                        .map(|path| DroppedFileQuoting::Posix.escape(path))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    Err(err) => {
+                        log::error!("uploading dropped files: {:#}", err);
+                        return;
+                    }
+                },
+                None => local_text,
+            };
+            pane.trickle_paste(text).ok();
+        })
+        .detach();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn upload_path() {
+        assert_eq!(
+            remote_upload_path("/home/wez/", Path::new("/tmp/a b.txt")).unwrap(),
+            "/home/wez/a b.txt"
+        );
+        assert_eq!(
+            remote_upload_path("/srv", Path::new("report.pdf")).unwrap(),
+            "/srv/report.pdf"
+        );
+        assert!(remote_upload_path("/srv", Path::new("/")).is_err());
+    }
+}
@@ -0,0 +1,307 @@
+//! Asks the user whether potentially dangerous escape sequences
+//! should be honored, as directed by the `escape_sequence_policy`
+use crate::scripting::guiwin::GuiWin;
+use crate::termwindow::box_model::*;
+use crate::termwindow::modal::Modal;
+use crate::termwindow::render::{
+    BOTTOM_LEFT_ROUNDED_CORNER, BOTTOM_RIGHT_ROUNDED_CORNER, TOP_LEFT_ROUNDED_CORNER,
+    TOP_RIGHT_ROUNDED_CORNER,
+};
+use crate::termwindow::{DimensionContext, TermWindowNotif};
+use crate::utilsprites::RenderMetrics;
+use crate::TermWindow;
+use config::keyassignment::KeyAssignment;
+use config::Dimension;
+use mux::escape_permissions::resolve_prompt;
+use mux::pane::PaneId;
+use mux::Mux;
+use mux_lua::MuxPane;
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+use wezterm_term::{EscapeSequenceKind, KeyCode, KeyModifiers, MouseEvent};
+use window::color::LinearRgba;
+
+fn describe(kind: EscapeSequenceKind) -> &'static str {
+    match kind {
+        EscapeSequenceKind::ClipboardRead => "read the clipboard",
+        EscapeSequenceKind::ClipboardWrite => "set the clipboard",
+        EscapeSequenceKind::SetTitle => "change the title",
+        EscapeSequenceKind::FileTransfer => "transfer a file to your downloads",
+        EscapeSequenceKind::Apc => "use an application program command (APC)",
+    }
+}
+
+impl TermWindow {
+    /// Called when a sequence whose policy is Prompt was emitted in
+    /// the pane.  The `escape-sequence-permission` event is given the
+    /// first chance to decide; if it doesn't, the user is asked.
+    pub fn escape_sequence_permission(&mut self, pane_id: PaneId, kind: EscapeSequenceKind) {
+        let window = self.window.as_ref().unwrap().clone();
+        let gui_win = GuiWin::new(self);
+        let mux_pane = MuxPane(pane_id);
+
+        async fn ask_lua(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: MuxPane,
+            kind: EscapeSequenceKind,
+        ) -> anyhow::Result<Option<bool>> {
+            let lua = match lua {
+                Some(lua) => lua,
+                None => return Ok(None),
+            };
+            let args = lua.pack_multi((window, pane, kind.name()))?;
+            let v = config::lua::emit_async_callback(
+                &*lua,
+                ("escape-sequence-permission".to_string(), args),
+            )
+            .await?;
+            match v {
+                mlua::Value::Boolean(allow) => Ok(Some(allow)),
+                _ => Ok(None),
+            }
+        }
+
+        promise::spawn::spawn(async move {
+            let decision = match config::with_lua_config_on_main_thread(move |lua| {
+                ask_lua(lua, gui_win, mux_pane, kind)
+            })
+            .await
+            {
+                Ok(decision) => decision,
+                Err(err) => {
+                    log::error!(
+                        "while processing escape-sequence-permission event: {:#}",
+                        err
+                    );
+                    None
+                }
+            };
+            window.notify(TermWindowNotif::Apply(Box::new(move |myself| {
+                if let Some(allow) = decision {
+                    resolve_prompt(pane_id, kind, allow, true);
+                    return;
+                }
+                let mux = Mux::get().expect("called on main thread");
+                if mux.get_pane(pane_id).is_none() {
+                    return;
+                }
+                if myself.get_modal().is_some() {
+                    // Don't stack prompts; we'll ask again the next
+                    // time that the application emits this sequence
+                    resolve_prompt(pane_id, kind, false, false);
+                    return;
+                }
+                let modal = EscapeSequencePrompt::new(myself, pane_id, kind);
+                myself.modal.borrow_mut().replace(Rc::new(modal));
+                myself.invalidate_modal();
+            })));
+        })
+        .detach();
+    }
+}
+
+pub struct EscapeSequencePrompt {
+    element: RefCell<Option<Vec<ComputedElement>>>,
+    pane_id: PaneId,
+    kind: EscapeSequenceKind,
+}
+
+impl EscapeSequencePrompt {
+    pub fn new(_term_window: &mut TermWindow, pane_id: PaneId, kind: EscapeSequenceKind) -> Self {
+        Self {
+            element: RefCell::new(None),
+            pane_id,
+            kind,
+        }
+    }
+
+    fn answer(&self, allow: bool, remember: bool, term_window: &mut TermWindow) {
+        log::info!(
+            "{} sequences in pane {}: {}{}",
+            self.kind.name(),
+            self.pane_id,
+            if allow { "allowed" } else { "denied" },
+            if remember { " for this pane" } else { "" }
+        );
+        resolve_prompt(self.pane_id, self.kind, allow, remember);
+        term_window.cancel_modal();
+    }
+
+    fn compute(&self, term_window: &mut TermWindow) -> anyhow::Result<Vec<ComputedElement>> {
+        let mux = Mux::get().expect("called on main thread");
+        let title = mux
+            .get_pane(self.pane_id)
+            .map(|pane| pane.get_title())
+            .unwrap_or_default();
+
+        let lines = vec![
+            format!(
+                "Pane {} ({}) wants to {}.",
+                self.pane_id,
+                title,
+                describe(self.kind)
+            ),
+            "a=always allow for this pane  d=always deny for this pane  \
+             Esc=ignore this time"
+                .to_string(),
+        ];
+
+        compute_prompt(term_window, lines)
+    }
+}
+
+impl Modal for EscapeSequencePrompt {
+    fn perform_assignment(
+        &self,
+        _assignment: &KeyAssignment,
+        _term_window: &mut TermWindow,
+    ) -> bool {
+        false
+    }
+
+    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn key_down(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<()> {
+        match (key, mods) {
+            (KeyCode::Char('a'), KeyModifiers::NONE) => self.answer(true, true, term_window),
+            (KeyCode::Char('d'), KeyModifiers::NONE) => self.answer(false, true, term_window),
+            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+                self.answer(false, false, term_window)
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn computed_element(
+        &self,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<Ref<[ComputedElement]>> {
+        if self.element.borrow().is_none() {
+            let element = self.compute(term_window)?;
+            self.element.borrow_mut().replace(element);
+        }
+        Ok(Ref::map(self.element.borrow(), |v| {
+            v.as_ref().unwrap().as_slice()
+        }))
+    }
+
+    fn reconfigure(&self, _term_window: &mut TermWindow) {
+        self.element.borrow_mut().take();
+    }
+}
+
+/// Computes the box used to present a permission prompt
+/// consisting of the specified lines of text
+pub(super) fn compute_prompt(
+    term_window: &mut TermWindow,
+    lines: Vec<String>,
+) -> anyhow::Result<Vec<ComputedElement>> {
+    let font = term_window
+        .fonts
+        .char_select_font()
+        .expect("to resolve char selection font");
+    let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+
+    let top_bar_height = if term_window.show_tab_bar && !term_window.config.tab_bar_at_bottom {
+        term_window.tab_bar_pixel_height().unwrap()
+    } else {
+        0.
+    };
+    let (padding_left, padding_top) = term_window.padding_left_top();
+    let border = term_window.get_os_border();
+    let top_pixel_y = top_bar_height + padding_top + border.top.get() as f32;
+
+    let elements = lines
+        .into_iter()
+        .map(|line| {
+            Element::new(&font, ElementContent::Text(line))
+                .colors(ElementColors {
+                    border: BorderColor::default(),
+                    bg: LinearRgba::TRANSPARENT.into(),
+                    text: term_window.config.pane_select_fg_color.to_linear().into(),
+                })
+                .display(DisplayType::Block)
+        })
+        .collect();
+
+    let element = Element::new(&font, ElementContent::Children(elements))
+        .colors(ElementColors {
+            border: BorderColor::new(term_window.config.pane_select_bg_color.to_linear().into()),
+            bg: term_window.config.pane_select_bg_color.to_linear().into(),
+            text: term_window.config.pane_select_fg_color.to_linear().into(),
+        })
+        .margin(BoxDimension {
+            left: Dimension::Cells(1.25),
+            right: Dimension::Cells(1.25),
+            top: Dimension::Cells(1.25),
+            bottom: Dimension::Cells(1.25),
+        })
+        .padding(BoxDimension {
+            left: Dimension::Cells(0.25),
+            right: Dimension::Cells(0.25),
+            top: Dimension::Cells(0.25),
+            bottom: Dimension::Cells(0.25),
+        })
+        .border(BoxDimension::new(Dimension::Pixels(1.)))
+        .border_corners(Some(Corners {
+            top_left: SizedPoly {
+                width: Dimension::Cells(0.25),
+                height: Dimension::Cells(0.25),
+                poly: TOP_LEFT_ROUNDED_CORNER,
+            },
+            top_right: SizedPoly {
+                width: Dimension::Cells(0.25),
+                height: Dimension::Cells(0.25),
+                poly: TOP_RIGHT_ROUNDED_CORNER,
+            },
+            bottom_left: SizedPoly {
+                width: Dimension::Cells(0.25),
+                height: Dimension::Cells(0.25),
+                poly: BOTTOM_LEFT_ROUNDED_CORNER,
+            },
+            bottom_right: SizedPoly {
+                width: Dimension::Cells(0.25),
+                height: Dimension::Cells(0.25),
+                poly: BOTTOM_RIGHT_ROUNDED_CORNER,
+            },
+        }));
+
+    let dimensions = term_window.dimensions;
+    let size = term_window.terminal_size;
+
+    let computed = term_window.compute_element(
+        &LayoutContext {
+            height: DimensionContext {
+                dpi: dimensions.dpi as f32,
+                pixel_max: dimensions.pixel_height as f32,
+                pixel_cell: metrics.cell_size.height as f32,
+            },
+            width: DimensionContext {
+                dpi: dimensions.dpi as f32,
+                pixel_max: dimensions.pixel_width as f32,
+                pixel_cell: metrics.cell_size.width as f32,
+            },
+            bounds: euclid::rect(
+                padding_left,
+                top_pixel_y,
+                size.cols as f32 * term_window.render_metrics.cell_size.width as f32,
+                size.rows as f32 * term_window.render_metrics.cell_size.height as f32,
+            ),
+            metrics: &metrics,
+            gl_state: term_window.render_state.as_ref().unwrap(),
+            zindex: 100,
+        },
+        &element,
+    )?;
+
+    Ok(vec![computed])
+}
@@ -0,0 +1,528 @@
+//! Hints mode labels the things in the window that can be clicked,
+//! such as hyperlinks, paths in the output of panes, tab bar buttons
+//! and pane dividers, so that they can be activated by typing their
+//! label, in the same way as the link hints of browser extensions
+//! such as Vimium.
+use crate::tabbar::TabBarItem;
+use crate::termwindow::box_model::*;
+use crate::termwindow::modal::Modal;
+use crate::termwindow::render::{
+    BOTTOM_LEFT_ROUNDED_CORNER, BOTTOM_RIGHT_ROUNDED_CORNER, TOP_LEFT_ROUNDED_CORNER,
+    TOP_RIGHT_ROUNDED_CORNER,
+};
+use crate::termwindow::{DimensionContext, UIItemType};
+use crate::utilsprites::RenderMetrics;
+use crate::TermWindow;
+use config::keyassignment::{KeyAssignment, SpawnTabDomain};
+use config::Dimension;
+use mux::pane::{Pane, PaneId};
+use mux::tab::{PositionedSplit, SplitDirection};
+use mux::Mux;
+use std::cell::{Ref, RefCell};
+use std::path::PathBuf;
+use std::rc::Rc;
+use termwiz::hyperlink::Rule;
+use url::Url;
+use wezterm_term::{KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex};
+
+/// Matches things that look like paths in the output of a pane:
+/// absolute paths, paths relative to the home directory and relative
+/// paths that contain at least one directory separator
+const PATH_REGEX: &str = r#"(?:~|\.{1,2})?/[^\s'"`:;,()\[\]{}<>|]*[^\s'"`:;,.()\[\]{}<>|]|\b[\w.@+-]+/[^\s'"`:;,()\[\]{}<>|]*[^\s'"`:;,.()\[\]{}<>|]"#;
+
+lazy_static::lazy_static! {
+    static ref PATH_RULE: Rule = Rule::new(PATH_REGEX, "$0").expect("path regex to compile");
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum HintTarget {
+    /// An explicit or implicit hyperlink in the output of a pane
+    Link {
+        pane_id: PaneId,
+        uri: String,
+    },
+    /// A path that was detected in the output of a pane
+    Path {
+        pane_id: PaneId,
+        path: String,
+    },
+    Tab(usize),
+    CloseTab(usize),
+    NewTab,
+    Split(PositionedSplit),
+}
+
+#[derive(Debug)]
+struct Hint {
+    label: String,
+    target: HintTarget,
+    /// The pixel coordinates of the top left of the label
+    x: f32,
+    y: f32,
+}
+
+pub struct HintsSelector {
+    element: RefCell<Option<Vec<ComputedElement>>>,
+    hints: RefCell<Option<Vec<Hint>>>,
+    selection: RefCell<String>,
+    alphabet: String,
+    /// Once a pane divider has been selected, the arrow keys move it
+    resizing: RefCell<Option<PositionedSplit>>,
+}
+
+/// Returns the column of the cell whose text begins at the byte
+/// `offset` of `line.as_str()`
+fn column_for_byte_offset(line: &Line, offset: usize) -> Option<usize> {
+    let mut pos = 0;
+    for cell in line.visible_cells() {
+        if pos == offset {
+            return Some(cell.cell_index());
+        }
+        pos += cell.str().len();
+        if pos > offset {
+            return None;
+        }
+    }
+    None
+}
+
+/// Returns the hyperlinks and paths in the visible lines of a pane,
+/// as the column and visible row at which they start
+fn pane_targets(pane: &Rc<dyn Pane>, top: StableRowIndex) -> Vec<(usize, usize, HintTarget)> {
+    let pane_id = pane.pane_id();
+    let dims = pane.get_dimensions();
+    let (_first_row, lines) = pane.get_lines(top..top + dims.viewport_rows as StableRowIndex);
+
+    let mut targets = vec![];
+    // Tracked across lines so that a link that wraps onto the next
+    // line is only labelled once
+    let mut prior_uri: Option<String> = None;
+    for (row, line) in lines.iter().enumerate() {
+        for cell in line.visible_cells() {
+            match cell.attrs().hyperlink() {
+                Some(link) => {
+                    if prior_uri.as_deref() != Some(link.uri()) {
+                        targets.push((
+                            cell.cell_index(),
+                            row,
+                            HintTarget::Link {
+                                pane_id,
+                                uri: link.uri().to_string(),
+                            },
+                        ));
+                        prior_uri = Some(link.uri().to_string());
+                    }
+                }
+                None => prior_uri = None,
+            }
+        }
+
+        let text = line.as_str();
+        for m in Rule::match_hyperlinks(&text, std::slice::from_ref(&*PATH_RULE)) {
+            let col = match column_for_byte_offset(line, m.range.start) {
+                Some(col) => col,
+                None => continue,
+            };
+            let is_link = line
+                .get_cell(col)
+                .map_or(false, |cell| cell.attrs().hyperlink().is_some());
+            if !is_link {
+                targets.push((
+                    col,
+                    row,
+                    HintTarget::Path {
+                        pane_id,
+                        path: m.link.uri().to_string(),
+                    },
+                ));
+            }
+        }
+    }
+    targets.sort_by_key(|(col, row, _)| (*row, *col));
+    targets
+}
+
+/// Produces a file url for a path that was found in the output of
+/// `pane`, resolving relative paths against its working directory
+fn path_to_url(pane: &Rc<dyn Pane>, path: &str) -> String {
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => config::HOME_DIR.join(rest),
+        None => PathBuf::from(path),
+    };
+    let path = if path.is_relative() {
+        match pane
+            .get_current_working_dir()
+            .and_then(|url| url.to_file_path().ok())
+        {
+            Some(cwd) => cwd.join(path),
+            None => path,
+        }
+    } else {
+        path
+    };
+    match Url::from_file_path(&path) {
+        Ok(url) => url.to_string(),
+        Err(()) => path.display().to_string(),
+    }
+}
+
+impl HintsSelector {
+    pub fn new(term_window: &mut TermWindow) -> Self {
+        Self {
+            element: RefCell::new(None),
+            hints: RefCell::new(None),
+            selection: RefCell::new(String::new()),
+            alphabet: term_window.config.quick_select_alphabet.clone(),
+            resizing: RefCell::new(None),
+        }
+    }
+
+    /// Returns the pixel coordinates of the middle of a pane divider
+    fn split_middle(term_window: &TermWindow, split: &PositionedSplit) -> (f32, f32) {
+        let top_bar_height = if term_window.show_tab_bar && !term_window.config.tab_bar_at_bottom {
+            term_window.tab_bar_pixel_height().unwrap_or(0.)
+        } else {
+            0.
+        };
+        let (padding_left, padding_top) = term_window.padding_left_top();
+        let border = term_window.get_os_border();
+        let cell_width = term_window.render_metrics.cell_size.width as f32;
+        let cell_height = term_window.render_metrics.cell_size.height as f32;
+        let (col, row) = match split.direction {
+            SplitDirection::Horizontal => {
+                (split.left as f32, split.top as f32 + split.size as f32 / 2.)
+            }
+            SplitDirection::Vertical => {
+                (split.left as f32 + split.size as f32 / 2., split.top as f32)
+            }
+        };
+        (
+            padding_left + border.left.get() as f32 + col * cell_width,
+            top_bar_height + padding_top + border.top.get() as f32 + row * cell_height,
+        )
+    }
+
+    fn compute_hints(&self, term_window: &mut TermWindow) -> anyhow::Result<Vec<Hint>> {
+        let cell_width = term_window.render_metrics.cell_size.width as f32;
+        let cell_height = term_window.render_metrics.cell_size.height as f32;
+
+        let mut targets = vec![];
+        for pos in term_window.get_panes_to_render() {
+            let (left, top) = term_window.pane_origin(&pos)?;
+            let viewport_top = term_window
+                .get_viewport(pos.pane.pane_id())
+                .unwrap_or_else(|| pos.pane.get_dimensions().physical_top);
+            for (col, row, target) in pane_targets(&pos.pane, viewport_top) {
+                targets.push((
+                    target,
+                    left + col as f32 * cell_width,
+                    top + row as f32 * cell_height,
+                ));
+            }
+        }
+
+        for item in &term_window.ui_items {
+            let target = match &item.item_type {
+                UIItemType::TabBar(TabBarItem::Tab { tab_idx, .. }) => HintTarget::Tab(*tab_idx),
+                UIItemType::TabBar(TabBarItem::NewTabButton) => HintTarget::NewTab,
+                UIItemType::CloseTab(tab_idx) => HintTarget::CloseTab(*tab_idx),
+                UIItemType::Split(split) => HintTarget::Split(*split),
+                _ => continue,
+            };
+            let (x, y) = match &target {
+                HintTarget::Split(split) => Self::split_middle(term_window, split),
+                _ => (item.x as f32, item.y as f32),
+            };
+            targets.push((target, x, y));
+        }
+
+        let labels =
+            crate::overlay::quickselect::compute_labels_for_alphabet(&self.alphabet, targets.len());
+        Ok(targets
+            .into_iter()
+            .zip(labels.into_iter())
+            .map(|((target, x, y), label)| Hint {
+                label,
+                target,
+                x,
+                y,
+            })
+            .collect())
+    }
+
+    fn compute_label(
+        term_window: &mut TermWindow,
+        text: String,
+        x: f32,
+        y: f32,
+    ) -> anyhow::Result<ComputedElement> {
+        let font = term_window.fonts.default_font()?;
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+        let fg = term_window.config.pane_select_fg_color.to_linear();
+        let bg = term_window.config.pane_select_bg_color.to_linear();
+        let corner = |poly: &'static [Poly]| SizedPoly {
+            width: Dimension::Cells(0.25),
+            height: Dimension::Cells(0.25),
+            poly,
+        };
+        let element = Element::new(&font, ElementContent::Text(text))
+            .colors(ElementColors {
+                border: BorderColor::new(bg.into()),
+                bg: bg.into(),
+                text: fg.into(),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.125),
+                right: Dimension::Cells(0.125),
+                top: Dimension::Cells(0.),
+                bottom: Dimension::Cells(0.),
+            })
+            .border(BoxDimension::new(Dimension::Pixels(1.)))
+            .border_corners(Some(Corners {
+                top_left: corner(TOP_LEFT_ROUNDED_CORNER),
+                top_right: corner(TOP_RIGHT_ROUNDED_CORNER),
+                bottom_left: corner(BOTTOM_LEFT_ROUNDED_CORNER),
+                bottom_right: corner(BOTTOM_RIGHT_ROUNDED_CORNER),
+            }));
+
+        let dimensions = term_window.dimensions;
+        let pixel_width = dimensions.pixel_width as f32;
+        let pixel_height = dimensions.pixel_height as f32;
+        term_window.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: pixel_height,
+                    pixel_cell: metrics.cell_size.height as f32,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: pixel_width,
+                    pixel_cell: metrics.cell_size.width as f32,
+                },
+                bounds: euclid::rect(x, y, (pixel_width - x).max(0.), (pixel_height - y).max(0.)),
+                metrics: &metrics,
+                gl_state: term_window.render_state.as_ref().unwrap(),
+                zindex: 100,
+            },
+            &element,
+        )
+    }
+
+    fn compute(&self, term_window: &mut TermWindow) -> anyhow::Result<Vec<ComputedElement>> {
+        if let Some(split) = *self.resizing.borrow() {
+            let (x, y) = Self::split_middle(term_window, &split);
+            let text = match split.direction {
+                SplitDirection::Horizontal => "\u{2190} \u{2192}",
+                SplitDirection::Vertical => "\u{2191} \u{2193}",
+            };
+            return Ok(vec![Self::compute_label(
+                term_window,
+                text.to_string(),
+                x,
+                y,
+            )?]);
+        }
+
+        if self.hints.borrow().is_none() {
+            let hints = self.compute_hints(term_window)?;
+            self.hints.borrow_mut().replace(hints);
+        }
+
+        // Only show the labels that can still be selected
+        let selection = self.selection.borrow().clone();
+        let labels: Vec<(String, f32, f32)> = self
+            .hints
+            .borrow()
+            .iter()
+            .flatten()
+            .filter(|hint| hint.label.starts_with(&selection))
+            .map(|hint| (hint.label.clone(), hint.x, hint.y))
+            .collect();
+
+        let mut elements = vec![];
+        for (label, x, y) in labels {
+            elements.push(Self::compute_label(term_window, label, x, y)?);
+        }
+        Ok(elements)
+    }
+
+    fn perform_selection(
+        &self,
+        target: HintTarget,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<()> {
+        let mux = Mux::get().unwrap();
+        match target {
+            HintTarget::Link { pane_id, uri } => {
+                if let Some(pane) = mux.get_pane(pane_id) {
+                    term_window.open_link(&pane, uri);
+                }
+            }
+            HintTarget::Path { pane_id, path } => {
+                if let Some(pane) = mux.get_pane(pane_id) {
+                    let url = path_to_url(&pane, &path);
+                    term_window.open_link(&pane, url);
+                }
+            }
+            HintTarget::Tab(tab_idx) => {
+                term_window.activate_tab(tab_idx as isize)?;
+            }
+            HintTarget::CloseTab(tab_idx) => {
+                term_window.close_specific_tab(tab_idx, true);
+            }
+            HintTarget::NewTab => {
+                term_window.spawn_tab(&SpawnTabDomain::CurrentPaneDomain);
+            }
+            HintTarget::Split(split) => {
+                // Stay in the modal so that the divider can be moved
+                // using the arrow keys
+                self.resizing.borrow_mut().replace(split);
+                term_window.invalidate_modal();
+                return Ok(());
+            }
+        }
+        term_window.cancel_modal();
+        Ok(())
+    }
+
+    fn resize_key_down(
+        &self,
+        split: PositionedSplit,
+        key: KeyCode,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<()> {
+        let delta = match (key, split.direction) {
+            (KeyCode::LeftArrow, SplitDirection::Horizontal)
+            | (KeyCode::UpArrow, SplitDirection::Vertical) => -1,
+            (KeyCode::RightArrow, SplitDirection::Horizontal)
+            | (KeyCode::DownArrow, SplitDirection::Vertical) => 1,
+            (KeyCode::Escape, _) | (KeyCode::Enter, _) => {
+                term_window.cancel_modal();
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+
+        let mux = Mux::get().unwrap();
+        if let Some(tab) = mux.get_active_tab_for_window(term_window.mux_window_id) {
+            tab.resize_split_by(split.index, delta);
+            if let Some(split) = tab.iter_splits().into_iter().nth(split.index) {
+                self.resizing.borrow_mut().replace(split);
+            }
+        }
+        term_window.invalidate_modal();
+        Ok(())
+    }
+}
+
+impl Modal for HintsSelector {
+    fn perform_assignment(
+        &self,
+        _assignment: &KeyAssignment,
+        _term_window: &mut TermWindow,
+    ) -> bool {
+        false
+    }
+
+    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn key_down(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<()> {
+        let resizing = *self.resizing.borrow();
+        if let Some(split) = resizing {
+            return self.resize_key_down(split, key, term_window);
+        }
+
+        match (key, mods) {
+            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+                term_window.cancel_modal();
+                return Ok(());
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                // Type to add to the selection
+                let selection = {
+                    let mut selection = self.selection.borrow_mut();
+                    selection.push(c);
+                    selection.clone()
+                };
+
+                // and if we have a complete match, activate it
+                let target = self
+                    .hints
+                    .borrow()
+                    .iter()
+                    .flatten()
+                    .find(|hint| hint.label == selection)
+                    .map(|hint| hint.target.clone());
+                if let Some(target) = target {
+                    return self.perform_selection(target, term_window);
+                }
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                // Backspace to edit the selection
+                self.selection.borrow_mut().pop();
+            }
+            (KeyCode::Char('u'), KeyModifiers::CTRL) => {
+                // CTRL-u to clear the selection
+                self.selection.borrow_mut().clear();
+            }
+            _ => return Ok(()),
+        }
+        term_window.invalidate_modal();
+        Ok(())
+    }
+
+    fn computed_element(
+        &self,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<Ref<[ComputedElement]>> {
+        if self.element.borrow().is_none() {
+            let element = self.compute(term_window)?;
+            self.element.borrow_mut().replace(element);
+        }
+        Ok(Ref::map(self.element.borrow(), |v| {
+            v.as_ref().unwrap().as_slice()
+        }))
+    }
+
+    fn reconfigure(&self, _term_window: &mut TermWindow) {
+        self.element.borrow_mut().take();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn paths(text: &str) -> Vec<String> {
+        Rule::match_hyperlinks(text, std::slice::from_ref(&*PATH_RULE))
+            .into_iter()
+            .map(|m| m.link.uri().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn detect_paths() {
+        assert_eq!(
+            paths("error in ~/src/main.rs: oops"),
+            vec!["~/src/main.rs".to_string()]
+        );
+        assert_eq!(
+            paths("see /etc/hosts, or ./target/debug."),
+            vec!["./target/debug".to_string(), "/etc/hosts".to_string()]
+        );
+        assert_eq!(
+            paths("modified:   wezterm-gui/src/main.rs"),
+            vec!["wezterm-gui/src/main.rs".to_string()]
+        );
+        assert!(paths("a / b").is_empty());
+        assert!(paths("plain words only").is_empty());
+    }
+}
@@ -0,0 +1,372 @@
+//! Shows the target of the hyperlink under the mouse pointer and
+//! dispatches clicked hyperlinks to the `hyperlink_handlers` or
+//! the internal file preview, and builds the context menu of
+//! hyperlinks
+use crate::customglyph::Poly;
+use crate::overlay::{file_preview, start_overlay_pane};
+use crate::scripting::guiwin::GuiWin;
+use crate::termwindow::box_model::*;
+use crate::termwindow::contextmenu::ContextMenuItem;
+use crate::termwindow::render::{
+    BOTTOM_LEFT_ROUNDED_CORNER, BOTTOM_RIGHT_ROUNDED_CORNER, TOP_LEFT_ROUNDED_CORNER,
+    TOP_RIGHT_ROUNDED_CORNER,
+};
+use crate::termwindow::spawn::SpawnWhere;
+use crate::termwindow::{DimensionContext, TermWindowNotif};
+use crate::utilsprites::RenderMetrics;
+use crate::TermWindow;
+use config::keyassignment::{ClipboardCopyDestination, SpawnCommand};
+use config::{Dimension, FilePreview, HyperlinkHandler, HyperlinkHoverDisplay};
+use mux::pane::{Pane, PaneId};
+use mux::tab::{SplitDirection, SplitRequest, SplitSize};
+use mux::Mux;
+use mux_lua::MuxPane;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Returns the path portion of a uri; for `file://host/path` this
+/// is the local path.
+fn uri_path(uri: &str) -> String {
+    match url::Url::parse(uri) {
+        Ok(url) if url.scheme() == "file" => match url.to_file_path() {
+            Ok(path) => path.to_string_lossy().to_string(),
+            Err(_) => url.path().to_string(),
+        },
+        Ok(url) => url.path().to_string(),
+        Err(_) => uri.to_string(),
+    }
+}
+
+/// Expands the `{uri}` and `{path}` placeholders in `args`.
+/// If neither is present, the uri is appended.
+fn expand_args(args: &[String], uri: &str) -> Vec<String> {
+    let path = uri_path(uri);
+    let mut substituted = false;
+    let mut result: Vec<String> = args
+        .iter()
+        .map(|arg| {
+            if arg.contains("{uri}") || arg.contains("{path}") {
+                substituted = true;
+            }
+            arg.replace("{uri}", uri).replace("{path}", &path)
+        })
+        .collect();
+    if !substituted {
+        result.push(uri.to_string());
+    }
+    result
+}
+
+fn open_with_system(uri: String) {
+    std::thread::spawn(move || {
+        log::info!("clicking {}", uri);
+        if let Err(err) = open::that(&uri) {
+            log::error!("Error opening {}: {:#}", uri, err);
+        }
+    });
+}
+
+fn open_with_app(target: String, app: String) {
+    std::thread::spawn(move || {
+        log::info!("opening {} with {}", target, app);
+        if let Err(err) = open::with(&target, &app) {
+            log::error!("Error opening {} with {}: {:#}", target, app, err);
+        }
+    });
+}
+
+/// Returns the local path of a `file://` uri
+fn file_uri_path(uri: &str) -> Option<String> {
+    match url::Url::parse(uri) {
+        Ok(url) if url.scheme() == "file" => Some(uri_path(uri)),
+        _ => None,
+    }
+}
+
+/// Opens `uri` using the handler configured for its scheme, or
+/// the system default handler if there is none.
+pub async fn dispatch_hyperlink(
+    lua: Option<Rc<mlua::Lua>>,
+    window: GuiWin,
+    pane: MuxPane,
+    uri: String,
+) -> anyhow::Result<()> {
+    let config = config::configuration();
+    let handler = config.hyperlink_handler(&uri).cloned();
+    match handler {
+        None => {
+            let preview = match url::Url::parse(&uri) {
+                Ok(url) if url.scheme() == "file" => url
+                    .to_file_path()
+                    .ok()
+                    .and_then(|path| config.file_preview(&path).map(|p| (path, p))),
+                _ => None,
+            };
+            match preview {
+                Some((path, preview)) => {
+                    let pane_id = pane.0;
+                    window
+                        .window
+                        .notify(TermWindowNotif::Apply(Box::new(move |tw| {
+                            tw.show_file_preview(pane_id, path, preview);
+                        })));
+                }
+                None => open_with_system(uri),
+            }
+        }
+        Some(handler) => run_hyperlink_handler(lua, window, pane, uri, handler).await?,
+    }
+    Ok(())
+}
+
+/// Runs `handler` for `uri`
+pub async fn run_hyperlink_handler(
+    lua: Option<Rc<mlua::Lua>>,
+    window: GuiWin,
+    pane: MuxPane,
+    uri: String,
+    handler: HyperlinkHandler,
+) -> anyhow::Result<()> {
+    match handler {
+        HyperlinkHandler::OpenWithSystem => open_with_system(uri),
+        HyperlinkHandler::Command(args) if !args.is_empty() => {
+            let args = expand_args(&args, &uri);
+            std::thread::spawn(move || {
+                log::info!("running {:?} for {}", args, uri);
+                let mut cmd = std::process::Command::new(&args[0]);
+                cmd.args(&args[1..]);
+                if let Err(err) = cmd.spawn() {
+                    log::error!("Error running {:?} for {}: {:#}", args, uri, err);
+                }
+            });
+        }
+        HyperlinkHandler::Command(_) => {
+            log::error!("hyperlink_handlers: Command for {} has no arguments", uri);
+        }
+        HyperlinkHandler::SplitPane(args) => {
+            let spawn = SpawnCommand {
+                args: Some(expand_args(&args, &uri)),
+                ..Default::default()
+            };
+            window
+                .window
+                .notify(TermWindowNotif::Apply(Box::new(move |tw| {
+                    tw.spawn_command(
+                        &spawn,
+                        SpawnWhere::SplitPane(SplitRequest {
+                            direction: SplitDirection::Horizontal,
+                            target_is_second: true,
+                            size: SplitSize::Percent(50),
+                            top_level: false,
+                        }),
+                    );
+                })));
+        }
+        HyperlinkHandler::NewTab(args) => {
+            let spawn = SpawnCommand {
+                args: Some(expand_args(&args, &uri)),
+                ..Default::default()
+            };
+            window
+                .window
+                .notify(TermWindowNotif::Apply(Box::new(move |tw| {
+                    tw.spawn_command(&spawn, SpawnWhere::NewTab);
+                })));
+        }
+        HyperlinkHandler::EmitEvent(name) => {
+            if let Some(lua) = lua {
+                let args = lua.pack_multi((window, pane, uri))?;
+                config::lua::emit_event(&lua, (name.clone(), args))
+                    .await
+                    .map_err(|e| {
+                        log::error!("while processing {} event: {:#}", name, e);
+                        e
+                    })?;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl TermWindow {
+    /// Shows the context menu for the hyperlink under the mouse pointer
+    pub(crate) fn show_hyperlink_context_menu(&mut self, pane: &Rc<dyn Pane>) {
+        let uri = match self.current_highlight.as_ref() {
+            Some(link) => link.uri().to_string(),
+            None => return,
+        };
+        let pane_id = pane.pane_id();
+        let path = file_uri_path(&uri);
+        let target = path.clone().unwrap_or_else(|| uri.clone());
+
+        let mut items = vec![];
+        {
+            let uri = uri.clone();
+            items.push(ContextMenuItem::new("Open", move |tw| {
+                if let Some(pane) = Mux::get().unwrap().get_pane(pane_id) {
+                    tw.open_link(&pane, uri.clone());
+                }
+            }));
+        }
+        for app in &self.config.hyperlink_open_with {
+            let target = target.clone();
+            let app = app.clone();
+            items.push(ContextMenuItem::new(
+                format!("Open With {}", app),
+                move |_tw| open_with_app(target.clone(), app.clone()),
+            ));
+        }
+        {
+            let uri = uri.clone();
+            items.push(ContextMenuItem::new("Copy Link", move |tw| {
+                tw.copy_to_clipboard(ClipboardCopyDestination::Clipboard, uri.clone());
+            }));
+        }
+        if let Some(path) = path {
+            items.push(ContextMenuItem::new("Copy Path", move |tw| {
+                tw.copy_to_clipboard(ClipboardCopyDestination::Clipboard, path.clone());
+            }));
+        }
+        for entry in &self.config.hyperlink_context_menu {
+            if !entry.applies_to(&uri) {
+                continue;
+            }
+            let uri = uri.clone();
+            let handler = entry.handler.clone();
+            items.push(ContextMenuItem::new(entry.label.clone(), move |tw| {
+                let window = GuiWin::new(tw);
+                let pane = MuxPane(pane_id);
+                let uri = uri.clone();
+                let handler = handler.clone();
+                promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+                    run_hyperlink_handler(lua, window, pane, uri, handler)
+                }))
+                .detach();
+            }));
+        }
+
+        self.show_context_menu(items);
+    }
+
+    /// Shows the internal preview of a clicked file in an overlay
+    /// on top of the pane
+    fn show_file_preview(&mut self, pane_id: PaneId, path: PathBuf, preview: FilePreview) {
+        let pane = match Mux::get().unwrap().get_pane(pane_id) {
+            Some(pane) => pane,
+            None => return,
+        };
+        let (overlay, future) = start_overlay_pane(self, &pane, move |pane_id, term| {
+            file_preview(pane_id, term, path, preview)
+        });
+        self.assign_overlay_for_pane(pane_id, overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    /// Renders the target of the hovered hyperlink according to
+    /// the `hyperlink_hover_display` option
+    pub(crate) fn paint_hyperlink_hover(&mut self) -> anyhow::Result<()> {
+        let display = self.config.hyperlink_hover_display;
+        if display == HyperlinkHoverDisplay::None || self.get_modal().is_some() {
+            return Ok(());
+        }
+        let uri = match self.current_highlight.as_ref() {
+            Some(link) => link.uri().to_string(),
+            None => return Ok(()),
+        };
+
+        let computed = self.compute_hyperlink_hover(uri, display)?;
+        let gl_state = self.render_state.as_ref().unwrap();
+        self.render_element(&computed, gl_state, None)?;
+        Ok(())
+    }
+
+    fn compute_hyperlink_hover(
+        &mut self,
+        uri: String,
+        display: HyperlinkHoverDisplay,
+    ) -> anyhow::Result<ComputedElement> {
+        let font = self.fonts.title_font()?;
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+        let corner = |poly: &'static [Poly]| SizedPoly {
+            width: Dimension::Cells(0.25),
+            height: Dimension::Cells(0.25),
+            poly,
+        };
+
+        let element = Element::new(&font, ElementContent::Text(uri))
+            .colors(ElementColors {
+                border: BorderColor::new(self.config.pane_select_bg_color.to_linear().into()),
+                bg: self.config.pane_select_bg_color.to_linear().into(),
+                text: self.config.pane_select_fg_color.to_linear().into(),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.5),
+                right: Dimension::Cells(0.5),
+                top: Dimension::Cells(0.25),
+                bottom: Dimension::Cells(0.25),
+            })
+            .border(BoxDimension::new(Dimension::Pixels(1.)))
+            .border_corners(Some(Corners {
+                top_left: corner(TOP_LEFT_ROUNDED_CORNER),
+                top_right: corner(TOP_RIGHT_ROUNDED_CORNER),
+                bottom_left: corner(BOTTOM_LEFT_ROUNDED_CORNER),
+                bottom_right: corner(BOTTOM_RIGHT_ROUNDED_CORNER),
+            }));
+
+        let dimensions = self.dimensions;
+        let pixel_width = dimensions.pixel_width as f32;
+        let pixel_height = dimensions.pixel_height as f32;
+
+        let mut computed = self.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: pixel_height,
+                    pixel_cell: metrics.cell_size.height as f32,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: pixel_width,
+                    pixel_cell: metrics.cell_size.width as f32,
+                },
+                bounds: euclid::rect(0., 0., pixel_width, pixel_height),
+                metrics: &metrics,
+                gl_state: self.render_state.as_ref().unwrap(),
+                zindex: 100,
+            },
+            &element,
+        )?;
+
+        let width = computed.bounds.width();
+        let height = computed.bounds.height();
+        let border = self.get_os_border();
+
+        let (x, y) = match (display, self.current_mouse_event.as_ref()) {
+            (HyperlinkHoverDisplay::Tooltip, Some(event)) => {
+                // Place the tooltip below and to the right of the
+                // pointer, flipping above it if it wouldn't fit
+                let x = event.coords.x as f32;
+                let mut y = event.coords.y as f32 + self.render_metrics.cell_size.height as f32;
+                if y + height > pixel_height {
+                    y = event.coords.y as f32 - height;
+                }
+                (x.min(pixel_width - width).max(0.), y.max(0.))
+            }
+            _ => {
+                let bottom_bar_height = if self.show_tab_bar && self.config.tab_bar_at_bottom {
+                    self.tab_bar_pixel_height()?
+                } else {
+                    0.
+                };
+                (
+                    border.left.get() as f32,
+                    (pixel_height - border.bottom.get() as f32 - bottom_bar_height - height)
+                        .max(0.),
+                )
+            }
+        };
+
+        computed.translate(euclid::vec2(x, y));
+        Ok(computed)
+    }
+}
@@ -0,0 +1,266 @@
+//! Implements `LockInput`, which stops input from being delivered to
+//! a pane, or to the whole window, until `input_lock_passphrase` is
+//! typed into a prompt.  This is useful when sharing a screen, to
+//! avoid accidentally typing into a pane that is being shown.
+//!
+//! Keys are intercepted before key bindings are considered, so none
+//! of the key assignments can be used on a locked pane, and mouse
+//! events are not reported to it.  Neither are dropped files, nor the
+//! actions of context menus, the Touch Bar or title bar buttons, which
+//! don't pass through key handling.
+//!
+//! The Dock menu is not affected: its items start a new process that
+//! asks this one to spawn a new window, so they never reach a pane.
+use crate::termwindow::box_model::*;
+use crate::termwindow::escapepermission::compute_prompt;
+use crate::termwindow::modal::Modal;
+use crate::TermWindow;
+use config::keyassignment::{InputLockScope, KeyAssignment};
+use mux::pane::{Pane, PaneId};
+use std::cell::{Cell, Ref, RefCell};
+use std::rc::Rc;
+use wezterm_term::{KeyCode, KeyModifiers, MouseEvent};
+
+/// Compares the passphrases without revealing the position of the
+/// first difference through the time taken
+fn passphrase_matches(typed: &str, passphrase: &str) -> bool {
+    typed.len() == passphrase.len()
+        && typed
+            .bytes()
+            .zip(passphrase.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+impl TermWindow {
+    pub(crate) fn lock_input(&mut self, pane: &Rc<dyn Pane>, scope: InputLockScope) {
+        if self
+            .config
+            .input_lock_passphrase
+            .as_deref()
+            .map(str::is_empty)
+            .unwrap_or(true)
+        {
+            log::error!("LockInput: input_lock_passphrase is not set, so input cannot be locked");
+            return;
+        }
+        match scope {
+            InputLockScope::Pane => {
+                log::info!("input to pane {} is locked", pane.pane_id());
+                self.pane_state(pane.pane_id()).input_locked = true;
+            }
+            InputLockScope::Window => {
+                log::info!("input to the window is locked");
+                self.input_locked = true;
+                self.show_unlock_prompt(None);
+            }
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    /// Returns true if input to the pane is currently locked
+    pub(crate) fn input_is_locked(&self, pane_id: PaneId) -> bool {
+        self.input_locked || self.pane_state(pane_id).input_locked
+    }
+
+    /// Performs an assignment that was chosen with the mouse or the
+    /// Touch Bar rather than a key, unless input to the pane is locked,
+    /// in which case the unlock prompt is shown instead
+    pub(crate) fn perform_assignment_unless_locked(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        assignment: &KeyAssignment,
+    ) -> anyhow::Result<()> {
+        if self.input_is_locked(pane.pane_id()) {
+            log::warn!(
+                "input to pane {} is locked; not performing {:?}",
+                pane.pane_id(),
+                assignment
+            );
+            self.show_locked_prompt(pane);
+            return Ok(());
+        }
+        self.perform_key_assignment(pane, assignment)?;
+        Ok(())
+    }
+
+    /// Shows the unlock prompt for the lock that applies to the pane
+    pub(crate) fn show_locked_prompt(&mut self, pane: &Rc<dyn Pane>) -> Rc<dyn Modal> {
+        let pane_id = if self.input_locked {
+            None
+        } else {
+            Some(pane.pane_id())
+        };
+        self.show_unlock_prompt(pane_id)
+    }
+
+    /// Called in place of the usual key handling when input to the
+    /// pane is locked; the key is given to the unlock prompt
+    pub(crate) fn locked_key_down(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        key: KeyCode,
+        mods: KeyModifiers,
+    ) {
+        let prompt = self.show_locked_prompt(pane);
+        prompt.key_down(key, mods, self).ok();
+    }
+
+    /// Shows the prompt for the passphrase, unless it is already shown
+    fn show_unlock_prompt(&mut self, pane_id: Option<PaneId>) -> Rc<dyn Modal> {
+        if let Some(modal) = self.get_modal() {
+            if let Some(prompt) = modal.downcast_ref::<InputUnlockPrompt>() {
+                if prompt.pane_id == pane_id {
+                    return modal;
+                }
+            }
+        }
+        let modal: Rc<dyn Modal> = Rc::new(InputUnlockPrompt::new(pane_id));
+        self.modal.borrow_mut().replace(Rc::clone(&modal));
+        self.invalidate_modal();
+        modal
+    }
+
+    fn unlock_input(&mut self, pane_id: Option<PaneId>) {
+        match pane_id {
+            Some(pane_id) => {
+                log::info!("input to pane {} is unlocked", pane_id);
+                self.pane_state(pane_id).input_locked = false;
+            }
+            None => {
+                log::info!("input to the window is unlocked");
+                self.input_locked = false;
+            }
+        }
+        self.cancel_modal();
+    }
+}
+
+pub struct InputUnlockPrompt {
+    element: RefCell<Option<Vec<ComputedElement>>>,
+    /// The locked pane, or None if the whole window is locked
+    pane_id: Option<PaneId>,
+    typed: RefCell<String>,
+    failed: Cell<bool>,
+}
+
+impl InputUnlockPrompt {
+    fn new(pane_id: Option<PaneId>) -> Self {
+        Self {
+            element: RefCell::new(None),
+            pane_id,
+            typed: RefCell::new(String::new()),
+            failed: Cell::new(false),
+        }
+    }
+
+    fn submit(&self, term_window: &mut TermWindow) {
+        let typed = std::mem::take(&mut *self.typed.borrow_mut());
+        let passphrase = term_window.config.input_lock_passphrase.clone();
+        match passphrase {
+            Some(passphrase) if passphrase_matches(&typed, &passphrase) => {
+                term_window.unlock_input(self.pane_id);
+            }
+            _ => {
+                self.failed.set(true);
+                term_window.invalidate_modal();
+            }
+        }
+    }
+
+    fn compute(&self, term_window: &mut TermWindow) -> anyhow::Result<Vec<ComputedElement>> {
+        let what = match self.pane_id {
+            Some(pane_id) => format!("Input to pane {} is locked.", pane_id),
+            None => "Input to this window is locked.".to_string(),
+        };
+        let mut lines = vec![
+            what,
+            format!(
+                "Type the passphrase and press Enter to unlock: {}",
+                "*".repeat(self.typed.borrow().chars().count())
+            ),
+        ];
+        if self.failed.get() {
+            lines.push("That passphrase is not correct.".to_string());
+        }
+        compute_prompt(term_window, lines)
+    }
+}
+
+impl Modal for InputUnlockPrompt {
+    fn perform_assignment(
+        &self,
+        _assignment: &KeyAssignment,
+        _term_window: &mut TermWindow,
+    ) -> bool {
+        // Nothing may be performed while input is locked
+        true
+    }
+
+    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn key_down(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<()> {
+        match (key, mods) {
+            (KeyCode::Enter, KeyModifiers::NONE) => self.submit(term_window),
+            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+                self.typed.borrow_mut().clear();
+                if self.pane_id.is_some() {
+                    // The pane remains locked, but the rest of the
+                    // window can be used again
+                    term_window.cancel_modal();
+                } else {
+                    term_window.invalidate_modal();
+                }
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                self.typed.borrow_mut().pop();
+                term_window.invalidate_modal();
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                self.typed.borrow_mut().push(c);
+                term_window.invalidate_modal();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn computed_element(
+        &self,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<Ref<[ComputedElement]>> {
+        if self.element.borrow().is_none() {
+            let element = self.compute(term_window)?;
+            self.element.borrow_mut().replace(element);
+        }
+        Ok(Ref::map(self.element.borrow(), |v| {
+            v.as_ref().unwrap().as_slice()
+        }))
+    }
+
+    fn reconfigure(&self, _term_window: &mut TermWindow) {
+        self.element.borrow_mut().take();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compares_passphrases() {
+        assert!(passphrase_matches("open sesame", "open sesame"));
+        assert!(!passphrase_matches("open sesam", "open sesame"));
+        assert!(!passphrase_matches("open Sesame", "open sesame"));
+        assert!(!passphrase_matches("", "open sesame"));
+    }
+}
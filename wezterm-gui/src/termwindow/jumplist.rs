@@ -0,0 +1,135 @@
+//! A per-pane list of the scroll positions that were jumped away
+//! from, which can be navigated backwards and forwards in the same
+//! way as the jumplist in vim
+use crate::TermWindow;
+use mux::pane::{Pane, PaneId};
+use mux::Mux;
+use std::rc::Rc;
+use wezterm_term::StableRowIndex;
+
+/// The maximum number of positions retained per pane
+const MAX_JUMPS: usize = 100;
+
+#[derive(Default, Debug)]
+pub struct JumpList {
+    positions: Vec<StableRowIndex>,
+    /// The index of the position that we most recently navigated
+    /// to, or `positions.len()` if we are not navigating the list
+    current: usize,
+}
+
+impl JumpList {
+    /// Records `row` as the most recent jump point, discarding
+    /// any earlier record of the same position
+    pub fn push(&mut self, row: StableRowIndex) {
+        self.positions.retain(|&r| r != row);
+        self.positions.push(row);
+        if self.positions.len() > MAX_JUMPS {
+            self.positions.remove(0);
+        }
+        self.current = self.positions.len();
+    }
+
+    /// Moves to the position before the current one.  `row` is the
+    /// position of the viewport, which is recorded when starting to
+    /// navigate so that `forward` can return to it
+    pub fn back(&mut self, row: StableRowIndex) -> Option<StableRowIndex> {
+        if self.current >= self.positions.len() {
+            if self.positions.last() != Some(&row) {
+                self.push(row);
+            }
+            self.current = self.positions.len() - 1;
+        }
+        if self.current == 0 {
+            return None;
+        }
+        self.current -= 1;
+        Some(self.positions[self.current])
+    }
+
+    /// Moves to the position after the current one
+    pub fn forward(&mut self) -> Option<StableRowIndex> {
+        if self.current + 1 >= self.positions.len() {
+            return None;
+        }
+        self.current += 1;
+        Some(self.positions[self.current])
+    }
+}
+
+impl TermWindow {
+    fn viewport_top(&self, pane: &Rc<dyn Pane>) -> StableRowIndex {
+        self.get_viewport(pane.pane_id())
+            .unwrap_or_else(|| pane.get_dimensions().physical_top)
+    }
+
+    /// Records the current scroll position of the pane in its jumplist
+    pub fn record_jump(&mut self, pane_id: PaneId) {
+        let mux = Mux::get().unwrap();
+        if let Some(pane) = mux.get_pane(pane_id) {
+            let row = self.viewport_top(&pane);
+            self.pane_state(pane_id).jump_list.push(row);
+        }
+    }
+
+    /// Scrolls to the previous (`forward == false`) or next position
+    /// in the jumplist of the pane
+    pub fn navigate_jump_list(&mut self, pane: &Rc<dyn Pane>, forward: bool) {
+        let row = self.viewport_top(pane);
+        let target = {
+            let mut state = self.pane_state(pane.pane_id());
+            if forward {
+                state.jump_list.forward()
+            } else {
+                state.jump_list.back(row)
+            }
+        };
+        if let Some(target) = target {
+            self.set_viewport(pane.pane_id(), Some(target), pane.get_dimensions());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn navigate() {
+        let mut list = JumpList::default();
+        assert_eq!(list.back(50), None);
+        assert_eq!(list.forward(), None);
+
+        list.push(10);
+        list.push(20);
+        list.push(30);
+
+        // Going back records where we started from, so that
+        // we can return to it
+        assert_eq!(list.back(40), Some(30));
+        assert_eq!(list.back(30), Some(20));
+        assert_eq!(list.back(20), Some(10));
+        assert_eq!(list.back(10), None);
+        assert_eq!(list.forward(), Some(20));
+        assert_eq!(list.forward(), Some(30));
+        assert_eq!(list.forward(), Some(40));
+        assert_eq!(list.forward(), None);
+
+        // A new jump ends navigation and moves a revisited
+        // position to the end
+        list.back(40);
+        list.push(10);
+        assert_eq!(list.back(25), Some(10));
+        assert_eq!(list.back(10), Some(40));
+    }
+
+    #[test]
+    fn bounded() {
+        let mut list = JumpList::default();
+        for row in 0..(MAX_JUMPS as StableRowIndex + 10) {
+            list.push(row);
+        }
+        assert_eq!(list.positions.len(), MAX_JUMPS);
+        assert_eq!(list.positions[0], 10);
+    }
+}
@@ -379,6 +379,7 @@ impl super::TermWindow {
                             && self.pane_state(pane.pane_id()).overlay.is_none()
                         {
                             self.maybe_scroll_to_bottom_for_input(&pane);
+                            mux::panestats::record_input(pane.pane_id());
                         }
                         context.set_cursor(None);
                         if !keycode.is_modifier() {
@@ -423,6 +424,10 @@ impl super::TermWindow {
             Some(pane) => pane,
             None => return,
         };
+        if self.input_is_locked(pane.pane_id()) {
+            // Leave it to key_event_impl to pass to the unlock prompt
+            return;
+        }
 
         // First, try to match raw physical key
         let phys_key = match &key.key {
@@ -564,6 +569,27 @@ impl super::TermWindow {
 
         let modifiers = window_mods_to_termwiz_mods(window_key.modifiers);
 
+        // While input is locked, keys go to the unlock prompt
+        // rather than being considered for key bindings
+        if self.input_is_locked(pane.pane_id()) {
+            if window_key.key_is_down {
+                match self.win_key_code_to_termwiz_key_code(&window_key.key) {
+                    Key::Code(key) => self.locked_key_down(&pane, key, modifiers),
+                    Key::Composed(s) => {
+                        for c in s.chars() {
+                            self.locked_key_down(
+                                &pane,
+                                ::termwiz::input::KeyCode::Char(c),
+                                ::termwiz::input::Modifiers::NONE,
+                            );
+                        }
+                    }
+                    Key::None => {}
+                }
+            }
+            return;
+        }
+
         if self.process_key(
             &pane,
             context,
@@ -615,6 +641,18 @@ impl super::TermWindow {
                     return;
                 }
 
+                // While the new output indicator is shown, End jumps
+                // to the bottom rather than being sent to the pane
+                if window_key.key_is_down
+                    && key == ::termwiz::input::KeyCode::End
+                    && modifiers.is_empty()
+                    && self.new_output_since_scrolled(&pane).is_some()
+                {
+                    self.scroll_to_bottom(&pane);
+                    context.invalidate();
+                    return;
+                }
+
                 let res = if let Some(encoded) = self.encode_win32_input(&pane, &window_key) {
                     if self.config.debug_key_events {
                         log::info!("Encoded input as {:?}", encoded);
@@ -634,6 +672,7 @@ impl super::TermWindow {
                         && self.pane_state(pane.pane_id()).overlay.is_none()
                     {
                         self.maybe_scroll_to_bottom_for_input(&pane);
+                        mux::panestats::record_input(pane.pane_id());
                     }
                     context.set_cursor(None);
                     if !key.is_modifier() {
@@ -7,18 +7,22 @@ use crate::frontend::{front_end, try_front_end};
 use crate::glium::texture::SrgbTexture2d;
 use crate::inputmap::InputMap;
 use crate::overlay::{
-    confirm_close_pane, confirm_close_tab, confirm_close_window, confirm_quit_program, launcher,
-    start_overlay, start_overlay_pane, CopyModeParams, CopyOverlay, LauncherArgs, LauncherFlags,
-    QuickSelectOverlay,
+    confirm_close_pane, confirm_close_tab, confirm_close_window, confirm_quit_program,
+    container_selector, copy_menu, deliver_notification, do_not_disturb, inspect_dead_pane,
+    kubernetes_pod_selector, last_command_output, launcher, mux_clients, notification_action,
+    notification_center, pager, pane_output_lines, recent_pane_entries, recent_panes,
+    record_notification, start_overlay, start_overlay_pane, toggle_do_not_disturb, CopyModeParams,
+    CopyOverlay, LauncherArgs, LauncherFlags, Notification, QuickSelectOverlay,
 };
 use crate::scripting::guiwin::GuiWin;
 use crate::scrollbar::*;
 use crate::selection::Selection;
 use crate::shapecache::*;
-use crate::tabbar::{TabBarItem, TabBarState};
+use crate::tabbar::{expand_window_title_format, TabBarItem, TabBarState};
 use crate::termwindow::background::{
     load_background_image, reload_background_image, LoadedBackgroundLayer,
 };
+use crate::termwindow::contextmenu::ContextMenuKind;
 use crate::termwindow::keyevent::{KeyTableArgs, KeyTableState};
 use crate::termwindow::modal::Modal;
 use crate::termwindow::render::{
@@ -33,8 +37,8 @@ use config::keyassignment::{
     QuickSelectArguments, RotationDirection, SpawnCommand, SplitSize,
 };
 use config::{
-    configuration, AudibleBell, ConfigHandle, Dimension, DimensionContext, TermConfig,
-    WindowCloseConfirmation,
+    configuration, AudibleBell, ConfigHandle, Dimension, DimensionContext, NotificationAction,
+    NotificationSource, TermConfig, WindowCloseConfirmation,
 };
 use mlua::{FromLua, UserData, UserDataFields};
 use mux::pane::{CloseReason, Pane, PaneId, Pattern as MuxPattern, PerformAssignmentResult};
@@ -49,7 +53,7 @@ use mux_lua::MuxPane;
 use smol::channel::Sender;
 use smol::Timer;
 use std::cell::{RefCell, RefMut};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::Add;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -62,21 +66,47 @@ use wezterm_font::FontConfiguration;
 use wezterm_gui_subcommands::GuiPosition;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::input::LastMouseClick;
-use wezterm_term::{Alert, StableRowIndex, TerminalConfiguration, TerminalSize};
+use wezterm_term::{Alert, AttentionRequest, StableRowIndex, TerminalConfiguration, TerminalSize};
 
+mod accessibility;
+mod authenticate;
 pub mod background;
 pub mod box_model;
 pub mod charselect;
 pub mod clipboard;
+pub mod clipboardhistory;
+mod clipboardread;
+mod collab;
+mod comparepanes;
+pub mod contextmenu;
+mod debughud;
+mod diffmode;
+mod droppedfiles;
+mod escapepermission;
+mod hints;
+mod hyperlink;
+mod inputlock;
+mod jumplist;
 mod keyevent;
 pub mod modal;
 mod mouseevent;
+mod namedmarks;
+mod newoutput;
 pub mod paneselect;
+mod password;
+pub mod pastereview;
 mod prevcursor;
+pub mod redaction;
 mod render;
 pub mod resize;
+mod screenshot;
 mod selection;
+pub mod serialport;
+mod snapshot;
 pub mod spawn;
+mod tabpreview;
+mod touchbar;
+pub mod workspacemanager;
 use prevcursor::PrevCursorPos;
 use spawn::SpawnWhere;
 
@@ -123,6 +153,7 @@ pub enum TermWindowNotif {
         tx: Sender<String>,
     },
     GetEffectiveConfig(Sender<ConfigHandle>),
+    CaptureImage(Sender<anyhow::Result<Vec<u8>>>),
     FinishWindowEvent {
         name: String,
         again: bool,
@@ -144,10 +175,14 @@ pub enum TermWindowNotif {
 pub enum UIItemType {
     TabBar(TabBarItem),
     CloseTab(usize),
+    /// An index into integrated_title_buttons
+    TitleButton(usize),
     AboveScrollThumb,
     ScrollThumb,
     BelowScrollThumb,
     Split(PositionedSplit),
+    /// An index into the items of the open context menu
+    ContextMenuItem(usize),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -193,6 +228,23 @@ pub struct PaneState {
 
     bell_start: Option<Instant>,
     pub mouse_terminal_coords: Option<(ClickPosition, StableRowIndex)>,
+    /// If is_some(), the stable row index just past the bottom of
+    /// the screen at the time that the viewport was scrolled away
+    /// from the bottom; rows from here on are new output
+    seen_end: Option<StableRowIndex>,
+    /// The scroll positions that were jumped away from
+    jump_list: jumplist::JumpList,
+    /// The rows marked via SetNamedMark, keyed by name
+    named_marks: BTreeMap<String, StableRowIndex>,
+    /// The volume of the audible bell, as a percentage of
+    /// audible_bell_volume, as set by SetPaneBellVolume
+    bell_volume: Option<u8>,
+    /// Whether input to the pane is locked by LockInput
+    input_locked: bool,
+    /// Whether unified diffs in the pane are rendered by diffmode
+    diff_mode: bool,
+    /// The stable rows of the headers of the hunks that are collapsed
+    collapsed_hunks: HashSet<StableRowIndex>,
 }
 
 /// Data used when synchronously formatting pane and window titles
@@ -263,6 +315,7 @@ pub struct PaneInformation {
     pub pixel_height: usize,
     pub title: String,
     pub user_vars: HashMap<String, String>,
+    pub badge: Option<String>,
 }
 
 impl UserData for PaneInformation {
@@ -280,6 +333,7 @@ impl UserData for PaneInformation {
         fields.add_field_method_get("pixel_height", |_, this| Ok(this.pixel_width));
         fields.add_field_method_get("title", |_, this| Ok(this.title.clone()));
         fields.add_field_method_get("user_vars", |_, this| Ok(this.user_vars.clone()));
+        fields.add_field_method_get("badge", |_, this| Ok(this.badge.clone()));
         fields.add_field_method_get("foreground_process_name", |_, this| {
             let mut name = None;
             if let Some(mux) = Mux::get() {
@@ -292,6 +346,32 @@ impl UserData for PaneInformation {
                 None => Ok("".to_string()),
             }
         });
+        fields.add_field_method_get("domain_name", |_, this| {
+            let mut name = None;
+            if let Some(mux) = Mux::get() {
+                if let Some(pane) = mux.get_pane(this.pane_id) {
+                    name = mux
+                        .get_domain(pane.domain_id())
+                        .map(|domain| domain.domain_name().to_string());
+                }
+            }
+            match name {
+                Some(name) => Ok(name),
+                None => Ok("".to_string()),
+            }
+        });
+        fields.add_field_method_get("inferred_title", |_, this| {
+            let mut title = None;
+            if let Some(mux) = Mux::get() {
+                if let Some(pane) = mux.get_pane(this.pane_id) {
+                    title = pane.get_inferred_title();
+                }
+            }
+            match title {
+                Some(title) => Ok(title),
+                None => Ok("".to_string()),
+            }
+        });
         fields.add_field_method_get("current_working_dir", |_, this| {
             let mut name = None;
             if let Some(mux) = Mux::get() {
@@ -371,6 +451,10 @@ pub struct TermWindow {
     show_scroll_bar: bool,
     tab_bar: TabBarState,
     fancy_tab_bar: Option<box_model::ComputedElement>,
+    /// Thumbnails of the tabs, shown when hovering over them
+    tab_previews: HashMap<TabId, tabpreview::TabPreview>,
+    tab_preview_pending: bool,
+    capturing_tab_preview: bool,
     pub right_status: String,
     pub left_status: String,
     last_ui_item: Option<UIItem>,
@@ -379,8 +463,24 @@ pub struct TermWindow {
     is_click_to_focus_window: bool,
     last_mouse_coords: (usize, i64),
     window_drag_position: Option<MouseEvent>,
+    /// Set once the selection has been dragged out of the window,
+    /// until the mouse button is next pressed
+    selection_drag_started: bool,
+    /// The items of the native context menu that is being shown
+    native_context_menu: Option<Vec<contextmenu::ContextMenuItem>>,
     current_mouse_event: Option<MouseEvent>,
+    /// The shared pane that we last told where we are pointing
+    collaborator_cursor_pane: Option<PaneId>,
     prev_cursor: PrevCursorPos,
+    accessible_state: accessibility::AccessibleState,
+    touch_bar_items: Vec<::window::TouchBarItem>,
+    /// An assignment that the user has just authenticated for, which
+    /// can be performed once without asking them again
+    authenticated_assignment: Option<KeyAssignment>,
+    /// Whether input to the whole window is locked by LockInput
+    input_locked: bool,
+    /// The panes that are being compared by ToggleComparePanes
+    compare: Option<comparepanes::CompareState>,
     last_scroll_info: RenderableDimensions,
 
     tab_state: RefCell<HashMap<TabId, TabState>>,
@@ -411,6 +511,10 @@ pub struct TermWindow {
 
     line_quad_cache: RefCell<LfuCache<LineQuadCacheKey, LineQuadCacheValue>>,
 
+    /// Foreground colors adjusted for minimum contrast, keyed by
+    /// the bits of the foreground and background colors
+    contrast_cache: RefCell<HashMap<([u32; 4], [u32; 3]), ::window::color::LinearRgba>>,
+
     last_status_call: Instant,
     cursor_blink_state: RefCell<ColorEase>,
     blink_state: RefCell<ColorEase>,
@@ -426,6 +530,9 @@ pub struct TermWindow {
     event_states: HashMap<String, EventState>,
     pub current_event: Option<Value>,
     has_animation: RefCell<Option<Instant>>,
+    /// Set by window:request_animation_frame() to have the
+    /// animation-frame event emitted after the next paint
+    pub animation_frame_requested: bool,
     /// We use this to attempt to do something reasonable
     /// if we run out of texture space
     allow_images: bool,
@@ -526,6 +633,16 @@ impl TermWindow {
             pane.focus_changed(focused);
         }
 
+        if focused {
+            // Selecting a native tab focuses its window without any
+            // other input, so let the mux know which pane is active
+            // now rather than waiting for the next paint
+            if let (Some(mux), Some(pane)) = (Mux::get(), self.get_active_pane_no_overlay()) {
+                mux.record_focus_for_current_identity(pane.pane_id());
+            }
+            front_end().focused_mux_window(self.mux_window_id);
+        }
+
         self.update_title();
         self.emit_window_event("window-focus-changed", None);
     }
@@ -574,6 +691,7 @@ impl TermWindow {
         let config = configuration();
         let dpi = config.dpi.unwrap_or_else(|| ::window::default_dpi()) as usize;
         let fontconfig = Rc::new(FontConfiguration::new(Some(config.clone()), dpi)?);
+        crate::startup::mark("font configuration created");
 
         let mux = Mux::get().expect("to be main thread with mux running");
         let size = match mux.get_active_tab_for_window(mux_window_id) {
@@ -588,6 +706,7 @@ impl TermWindow {
 
         let render_metrics = RenderMetrics::new(&fontconfig)?;
         log::trace!("using render_metrics {:#?}", render_metrics);
+        crate::startup::mark("fonts loaded");
 
         // Initially we have only a single tab, so take that into account
         // for the tab bar state.
@@ -688,12 +807,23 @@ impl TermWindow {
             show_scroll_bar: config.enable_scroll_bar,
             tab_bar: TabBarState::default(),
             fancy_tab_bar: None,
+            tab_previews: HashMap::new(),
+            tab_preview_pending: false,
+            capturing_tab_preview: false,
             right_status: String::new(),
             left_status: String::new(),
             last_mouse_coords: (0, -1),
             window_drag_position: None,
+            selection_drag_started: false,
+            native_context_menu: None,
             current_mouse_event: None,
+            collaborator_cursor_pane: None,
             prev_cursor: PrevCursorPos::new(),
+            accessible_state: accessibility::AccessibleState::default(),
+            touch_bar_items: vec![],
+            authenticated_assignment: None,
+            input_locked: false,
+            compare: None,
             last_scroll_info: RenderableDimensions::default(),
             tab_state: RefCell::new(HashMap::new()),
             pane_state: RefCell::new(HashMap::new()),
@@ -728,6 +858,7 @@ impl TermWindow {
                 |config| config.line_to_ele_shape_cache_size,
                 &config,
             )),
+            contrast_cache: RefCell::new(HashMap::new()),
             last_status_call: Instant::now(),
             cursor_blink_state: RefCell::new(ColorEase::new(
                 config.cursor_blink_rate,
@@ -753,6 +884,7 @@ impl TermWindow {
             event_states: HashMap::new(),
             current_event: None,
             has_animation: RefCell::new(None),
+            animation_frame_requested: false,
             scheduled_animation: RefCell::new(None),
             allow_images: true,
             semantic_zones: HashMap::new(),
@@ -799,6 +931,7 @@ impl TermWindow {
         )
         .await?;
         tw.borrow_mut().window.replace(window.clone());
+        crate::startup::mark("window created");
 
         Self::apply_icon(&window)?;
 
@@ -909,23 +1042,32 @@ impl TermWindow {
                 Ok(true)
             }
             WindowEvent::DroppedFile(paths) => {
-                let pane = match self.get_active_pane_or_overlay() {
-                    Some(pane) => pane,
-                    None => return Ok(true),
-                };
-                let paths = paths
-                    .iter()
-                    .map(|path| {
-                        self.config
-                            .quote_dropped_files
-                            .escape(&path.to_string_lossy())
-                    })
-                    .collect::<Vec<_>>()
-                    .join(" ");
-                pane.trickle_paste(paths)?;
+                if paths.is_empty() {
+                    return Ok(true);
+                }
+                if let Some(pane) = self.get_active_pane_or_overlay() {
+                    if self.input_is_locked(pane.pane_id()) {
+                        self.show_locked_prompt(&pane);
+                    } else {
+                        self.dropped_files(pane, paths);
+                    }
+                }
                 Ok(true)
             }
             WindowEvent::DraggedFile(_) => Ok(true),
+            WindowEvent::TouchBarItemPressed(id) => {
+                self.touch_bar_item_pressed(&id);
+                Ok(true)
+            }
+            WindowEvent::ContextMenuItemSelected(idx) => {
+                self.native_context_menu_item_selected(idx);
+                Ok(true)
+            }
+            WindowEvent::NewNativeTabRequested => {
+                // The new window joins the native tab group of this one
+                self.spawn_command(&SpawnCommand::default(), SpawnWhere::NewWindow);
+                Ok(true)
+            }
         }
     }
 
@@ -951,6 +1093,8 @@ impl TermWindow {
         );
 
         self.paint_impl(&mut frame);
+        self.update_accessible_text(window);
+        self.update_touch_bar(window);
         window.finish_frame(frame).is_ok()
     }
 
@@ -999,6 +1143,11 @@ impl TermWindow {
                     .map_err(chan_err)
                     .context("send GetDimensions response")?;
             }
+            TermWindowNotif::CaptureImage(tx) => {
+                tx.try_send(self.capture_image(None))
+                    .map_err(chan_err)
+                    .context("send CaptureImage response")?;
+            }
             TermWindowNotif::GetEffectiveConfig(tx) => {
                 tx.try_send(self.config.clone())
                     .map_err(chan_err)
@@ -1037,11 +1186,30 @@ impl TermWindow {
                         | Alert::CurrentWorkingDirectoryChanged
                         | Alert::WindowTitleChanged(_)
                         | Alert::TabTitleChanged(_)
-                        | Alert::IconTitleChanged(_),
+                        | Alert::IconTitleChanged(_)
+                        | Alert::BadgeChanged(_),
                     ..
                 } => {
                     self.update_title();
                 }
+                MuxNotification::Alert {
+                    alert: Alert::RequestAttention(request),
+                    pane_id,
+                } => {
+                    let mux = Mux::get().expect("on main thread");
+                    match mux.resolve_pane_id(pane_id) {
+                        Some((_domain_id, window_id, _tab_id))
+                            if window_id == self.mux_window_id =>
+                        {
+                            window.request_attention(match request {
+                                AttentionRequest::UntilFocused => UserAttentionType::Critical,
+                                AttentionRequest::Once => UserAttentionType::Informational,
+                                AttentionRequest::Cancel => UserAttentionType::Cancel,
+                            });
+                        }
+                        _ => {}
+                    }
+                }
                 MuxNotification::Alert {
                     alert: Alert::PaletteChanged,
                     pane_id,
@@ -1052,22 +1220,41 @@ impl TermWindow {
                     alert: Alert::Bell,
                     pane_id,
                 } => {
-                    match self.config.audible_bell {
-                        AudibleBell::SystemBeep => {
-                            Connection::get().expect("on main thread").beep();
-                        }
-                        AudibleBell::Disabled => {}
+                    let mux = Mux::get().expect("on main thread");
+                    let pane = mux.get_pane(pane_id);
+                    let mut notif = Notification::new(NotificationSource::Bell, "Bell");
+                    notif.body = pane.as_ref().map(|p| p.get_title()).unwrap_or_default();
+                    notif.pane_id.replace(pane_id);
+                    let action = notification_action(
+                        &notif,
+                        self.focused.is_some(),
+                        self.window_state.contains(WindowState::FULL_SCREEN),
+                    );
+
+                    // Only the window that contains the pane records the bell
+                    let in_this_window = matches!(
+                        mux.resolve_pane_id(pane_id),
+                        Some((_domain_id, window_id, _tab_id)) if window_id == self.mux_window_id
+                    );
+                    if in_this_window && action != NotificationAction::Suppress {
+                        record_notification(notif);
                     }
 
                     log::trace!("Ding! (this is the bell) in pane {}", pane_id);
                     self.emit_window_event("bell", Some(pane_id));
 
-                    let mut per_pane = self.pane_state(pane_id);
-                    per_pane.bell_start.replace(Instant::now());
-                    window.invalidate();
+                    if action == NotificationAction::Allow {
+                        if in_this_window {
+                            self.ring_audible_bell(pane_id);
+                        }
+
+                        let mut per_pane = self.pane_state(pane_id);
+                        per_pane.bell_start.replace(Instant::now());
+                        window.invalidate();
+                    }
                 }
                 MuxNotification::Alert {
-                    alert: Alert::ToastNotification { .. },
+                    alert: Alert::ToastNotification { .. } | Alert::CommandFinished { .. },
                     ..
                 } => {}
                 MuxNotification::TabAddedToWindow {
@@ -1111,10 +1298,34 @@ impl TermWindow {
                 MuxNotification::SaveToDownloads { .. } => {
                     // Handled by frontend
                 }
+                MuxNotification::QueryClipboard { pane_id, selection } => {
+                    self.query_clipboard(pane_id, selection);
+                }
+                MuxNotification::EscapeSequencePermission { pane_id, kind } => {
+                    self.escape_sequence_permission(pane_id, kind);
+                }
+                MuxNotification::InspectDeadPane { pane_id, status } => {
+                    self.show_dead_pane_inspector(pane_id, status);
+                }
+                MuxNotification::PaneActivationRequested(_) => {
+                    // The mux has already made it the active pane and tab
+                    window.focus();
+                    window.invalidate();
+                }
+                MuxNotification::CaptureImage { pane_id, reply } => {
+                    reply
+                        .try_send(self.capture_image(Some(pane_id)))
+                        .map_err(chan_err)
+                        .context("send CaptureImage response")?;
+                }
                 MuxNotification::PaneAdded(_)
                 | MuxNotification::PaneRemoved(_)
                 | MuxNotification::WindowWorkspaceChanged(_)
                 | MuxNotification::ActiveWorkspaceChanged(_)
+                | MuxNotification::PaneFocused(_)
+                | MuxNotification::ConnectionError { .. }
+                | MuxNotification::PaneCollaboratorsChanged(_)
+                | MuxNotification::ClientDetachRequested(_)
                 | MuxNotification::Empty
                 | MuxNotification::WindowCreated(_) => {}
             },
@@ -1227,8 +1438,28 @@ impl TermWindow {
 
     fn mux_pane_output_event(&mut self, pane_id: PaneId) {
         metrics::histogram!("mux.pane_output_event.rate", 1.);
+        mux::panestats::record_output(pane_id);
         if self.is_pane_visible(pane_id) {
             if let Some(ref win) = self.window {
+                if self.config.enable_synchronized_output {
+                    let sync_active = Mux::get()
+                        .and_then(|mux| mux.get_pane(pane_id))
+                        .map_or(false, |pane| pane.is_synchronized_output_active());
+                    if sync_active {
+                        // The application is part way through drawing a
+                        // frame; defer painting until it has finished,
+                        // but not for longer than the configured deadline
+                        let window = win.clone();
+                        let timeout =
+                            Duration::from_millis(self.config.synchronized_output_timeout_ms);
+                        promise::spawn::spawn(async move {
+                            Timer::after(timeout).await;
+                            window.invalidate();
+                        })
+                        .detach();
+                        return;
+                    }
+                }
                 win.invalidate();
             }
         }
@@ -1255,6 +1486,8 @@ impl TermWindow {
                     | Alert::TabTitleChanged(_)
                     | Alert::IconTitleChanged(_)
                     | Alert::SetUserVar { .. }
+                    | Alert::BadgeChanged(_)
+                    | Alert::RequestAttention(_)
                     | Alert::Bell,
             }
             | MuxNotification::PaneOutput(pane_id) => {
@@ -1282,6 +1515,17 @@ impl TermWindow {
                 let mux = Mux::get().expect("mux is calling us");
                 return mux.get_window(mux_window_id).is_some();
             }
+            MuxNotification::CaptureImage { pane_id, .. }
+            | MuxNotification::InspectDeadPane { pane_id, .. }
+            | MuxNotification::QueryClipboard { pane_id, .. }
+            | MuxNotification::EscapeSequencePermission { pane_id, .. }
+            | MuxNotification::PaneActivationRequested(pane_id) => {
+                let mux = Mux::get().expect("mux is calling us");
+                match mux.resolve_pane_id(pane_id) {
+                    Some((_domain_id, window_id, _tab_id)) if window_id == mux_window_id => {}
+                    _ => return true,
+                }
+            }
             MuxNotification::TabAddedToWindow { window_id, .. }
             | MuxNotification::WindowRemoved(window_id)
             | MuxNotification::WindowInvalidated(window_id) => {
@@ -1290,14 +1534,21 @@ impl TermWindow {
                 }
             }
             MuxNotification::Alert {
-                alert: Alert::ToastNotification { .. } | Alert::PaletteChanged { .. },
+                alert:
+                    Alert::ToastNotification { .. }
+                    | Alert::PaletteChanged { .. }
+                    | Alert::CommandFinished { .. },
                 ..
             }
             | MuxNotification::AssignClipboard { .. }
+            | MuxNotification::ConnectionError { .. }
+            | MuxNotification::PaneCollaboratorsChanged(_)
+            | MuxNotification::ClientDetachRequested(_)
             | MuxNotification::SaveToDownloads { .. }
             | MuxNotification::PaneRemoved(_)
             | MuxNotification::WindowCreated(_)
             | MuxNotification::ActiveWorkspaceChanged(_)
+            | MuxNotification::PaneFocused(_)
             | MuxNotification::Empty
             | MuxNotification::WindowWorkspaceChanged(_) => return true,
         }
@@ -1541,6 +1792,8 @@ impl TermWindow {
             None,
         );
 
+        self.contrast_cache.borrow_mut().clear();
+
         self.show_scroll_bar = config.enable_scroll_bar;
         self.shape_generation += 1;
         {
@@ -1691,6 +1944,16 @@ impl TermWindow {
         self.update_title_impl();
     }
 
+    /// Delivers a notification from one of the panes in this window,
+    /// taking the focus and fullscreen state of the window into account
+    pub fn deliver_notification(&self, notif: Notification) {
+        deliver_notification(
+            notif,
+            self.focused.is_some(),
+            self.window_state.contains(WindowState::FULL_SCREEN),
+        );
+    }
+
     fn update_title_impl(&mut self) {
         let mux = Mux::get().unwrap();
         let window = match mux.get_window(self.mux_window_id) {
@@ -1729,6 +1992,12 @@ impl TermWindow {
 
         let tab_bar_height = self.tab_bar_pixel_height().unwrap_or(0.);
 
+        let right_status = if do_not_disturb() {
+            format!("🔕 {}", self.right_status)
+        } else {
+            self.right_status.clone()
+        };
+
         let hovering_in_tab_bar = match &self.current_mouse_event {
             Some(event) => {
                 let mouse_y = event.coords.y as f32;
@@ -1749,7 +2018,7 @@ impl TermWindow {
             self.config.resolved_palette.tab_bar.as_ref(),
             &self.config,
             &self.left_status,
-            &self.right_status,
+            &right_status,
         );
         if new_tab_bar != self.tab_bar {
             self.tab_bar = new_tab_bar;
@@ -1764,6 +2033,19 @@ impl TermWindow {
         if num_tabs == 0 {
             return;
         }
+        let workspace = window.get_workspace().to_string();
+        let active_tab_idx = window.get_active_idx();
+        let unseen_tabs = window
+            .iter()
+            .enumerate()
+            .filter(|(idx, tab)| {
+                *idx != active_tab_idx
+                    && tab
+                        .iter_panes()
+                        .iter()
+                        .any(|pos| pos.pane.has_unseen_output())
+            })
+            .count();
         drop(window);
 
         let title = match config::run_immediate_with_lua_config(|lua| {
@@ -1801,6 +2083,40 @@ impl TermWindow {
 
         let title = match title {
             Some(title) => title,
+            None if !self.config.window_title_format.is_empty() => {
+                expand_window_title_format(&self.config.window_title_format, |name| {
+                    Some(match name {
+                        "title" => active_pane
+                            .as_ref()
+                            .map(|pos| pos.title.clone())
+                            .unwrap_or_default(),
+                        "zoomed" => {
+                            if active_pane.as_ref().map_or(false, |pos| pos.is_zoomed) {
+                                "[Z] ".to_string()
+                            } else {
+                                String::new()
+                            }
+                        }
+                        "index" => (active_tab_idx
+                            + if self.config.tab_and_split_indices_are_zero_based {
+                                0
+                            } else {
+                                1
+                            })
+                        .to_string(),
+                        "num_tabs" => num_tabs.to_string(),
+                        "unseen_tabs" => unseen_tabs.to_string(),
+                        "workspace" => workspace.clone(),
+                        "domain" => active_pane
+                            .as_ref()
+                            .and_then(|pos| mux.get_pane(pos.pane_id))
+                            .and_then(|pane| mux.get_domain(pane.domain_id()))
+                            .map(|domain| domain.domain_name().to_string())
+                            .unwrap_or_default(),
+                        _ => return None,
+                    })
+                })
+            }
             None => {
                 if let (Some(pos), Some(tab)) = (active_pane, active_tab) {
                     if num_tabs == 1 {
@@ -1954,6 +2270,57 @@ impl TermWindow {
         }
     }
 
+    /// Activates the most recently focused pane in the active tab,
+    /// other than the active pane
+    fn activate_last_pane(&mut self) -> anyhow::Result<()> {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return Ok(()),
+        };
+        if self.tab_state(tab.tab_id()).overlay.is_some() {
+            return Ok(());
+        }
+
+        let active = tab.get_active_pane().map(|pane| pane.pane_id());
+        let panes = tab.iter_panes_ignoring_zoom();
+        let last = mux
+            .recent_panes()
+            .into_iter()
+            .filter(|&pane_id| Some(pane_id) != active)
+            .find_map(|pane_id| panes.iter().find(|p| p.pane.pane_id() == pane_id));
+        if let Some(pos) = last {
+            if tab.set_zoomed(false) && !self.config.unzoom_on_switch_pane {
+                // Leave the zoomed pane alone
+                tab.set_zoomed(true);
+                return Ok(());
+            }
+            tab.set_active_idx(pos.index);
+        }
+        Ok(())
+    }
+
+    /// Activates the most recently focused window, other than this one
+    fn activate_last_window(&mut self) -> anyhow::Result<()> {
+        let mux = Mux::get().unwrap();
+        let workspace = mux.active_workspace();
+        let last = mux.recent_windows().into_iter().find(|&window_id| {
+            window_id != self.mux_window_id
+                && (self.config.workspace_per_window
+                    || mux
+                        .get_window(window_id)
+                        .map(|window| window.get_workspace() == workspace)
+                        .unwrap_or(false))
+        });
+        let pane = last
+            .and_then(|window_id| mux.get_active_tab_for_window(window_id))
+            .and_then(|tab| tab.get_active_pane());
+        if let Some(pane) = pane {
+            mux.activate_pane(pane.pane_id())?;
+        }
+        Ok(())
+    }
+
     fn move_tab(&mut self, tab_idx: usize) -> anyhow::Result<()> {
         let mux = Mux::get().unwrap();
         let mut window = mux
@@ -2111,6 +2478,7 @@ impl TermWindow {
             zones.get(idx).cloned()
         };
         if let Some(zone) = zone {
+            self.record_jump(pane.pane_id());
             self.set_viewport(pane.pane_id(), Some(zone), dims);
         }
 
@@ -2195,6 +2563,10 @@ impl TermWindow {
     ) -> anyhow::Result<PerformAssignmentResult> {
         use KeyAssignment::*;
 
+        if self.authenticate_before_assignment(pane, assignment) {
+            return Ok(PerformAssignmentResult::Handled);
+        }
+
         if let Some(modal) = self.get_modal() {
             if modal.perform_assignment(assignment, self) {
                 return Ok(PerformAssignmentResult::Handled);
@@ -2295,6 +2667,29 @@ impl TermWindow {
                 let text = self.selection_text(pane);
                 self.copy_to_clipboard(*dest, text);
             }
+            CopyRichTextTo(dest) => {
+                let text = self.selection_text(pane);
+                let lines = self.selection_lines(pane);
+                let content =
+                    crate::richcopy::clipboard_content(text, &lines, &pane.palette(), &self.config);
+                self.copy_content_to_clipboard(*dest, content);
+            }
+            CopyWithOptions(options) => {
+                let text = crate::copytext::format_selection(&self.selection_rows(pane), options);
+                self.copy_to_clipboard(options.destination, text);
+            }
+            ShowCopyMenu => {
+                let mux = Mux::get().unwrap();
+                if let Some(tab) = mux.get_active_tab_for_window(self.mux_window_id) {
+                    let pane_id = pane.pane_id();
+                    let window = self.window.clone().unwrap();
+                    let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+                        copy_menu(pane_id, term, window)
+                    });
+                    self.assign_overlay(tab.tab_id(), overlay);
+                    promise::spawn::spawn(future).detach();
+                }
+            }
             Paste => {
                 self.paste_from_clipboard(pane, ClipboardPasteSource::Clipboard);
             }
@@ -2311,6 +2706,8 @@ impl TermWindow {
                 self.activate_tab_relative(*n, false)?;
             }
             ActivateLastTab => self.activate_last_tab()?,
+            ActivateLastPane => self.activate_last_pane()?,
+            ActivateLastWindow => self.activate_last_window()?,
             DecreaseFontSize => {
                 if let Some(w) = window.as_ref() {
                     self.decrease_font_size(w)
@@ -2410,9 +2807,21 @@ impl TermWindow {
             StartWindowDrag => {
                 self.window_drag_position = self.current_mouse_event.clone();
             }
+            StartSelectionDrag(format) => {
+                if !self.selection_drag_started {
+                    self.selection_drag_started = self.start_selection_drag(pane, *format);
+                }
+            }
             OpenLinkAtMouseCursor => {
                 self.do_open_link_at_mouse_cursor(pane);
             }
+            ShowContextMenu => {
+                if self.current_highlight.is_some() {
+                    self.show_hyperlink_context_menu(pane);
+                } else {
+                    self.show_pane_context_menu(pane, ContextMenuKind::Pane);
+                }
+            }
             EmitEvent(name) => {
                 self.emit_window_event(name, None);
             }
@@ -2610,7 +3019,28 @@ impl TermWindow {
                 let switcher = crate::frontend::WorkspaceSwitcher::new(&name);
                 mux.set_active_workspace(&name);
 
-                if mux.iter_windows_in_workspace(&name).is_empty() {
+                let definition = if spawn.is_none() {
+                    self.config.workspace_definition(&name).cloned()
+                } else {
+                    None
+                };
+
+                if let (Some(definition), true) =
+                    (definition, mux.iter_windows_in_workspace(&name).is_empty())
+                {
+                    let size = self.terminal_size;
+                    promise::spawn::spawn(async move {
+                        let mux = Mux::get().unwrap();
+                        if let Err(err) =
+                            mux.spawn_workspace_from_definition(&definition, size).await
+                        {
+                            log::error!("Failed to spawn workspace {}: {:#}", definition.name, err);
+                        }
+                        switcher.do_switch();
+                        drop(activity);
+                    })
+                    .detach();
+                } else if mux.iter_windows_in_workspace(&name).is_empty() {
                     let spawn = spawn.as_ref().map(|s| s.clone()).unwrap_or_default();
                     let size = self.terminal_size;
                     let term_config = Arc::new(TermConfig::with_config(self.config.clone()));
@@ -2670,6 +3100,30 @@ impl TermWindow {
                 })
                 .detach();
             }
+            MoveTabToDomain(domain) => {
+                let mux = Mux::get().unwrap();
+                let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+                    Some(tab) => tab,
+                    None => return Ok(PerformAssignmentResult::Handled),
+                };
+                let tab_id = tab.tab_id();
+                let domain = domain.clone();
+
+                promise::spawn::spawn(async move {
+                    let mux = Mux::get().unwrap();
+                    if let Err(err) = mux.move_tab_to_domain(tab_id, domain).await {
+                        log::error!("MoveTabToDomain: {:#}", err);
+                    }
+                })
+                .detach();
+            }
+            TogglePaneInputTurn => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    if let Err(err) = pane.toggle_input_turn() {
+                        log::error!("TogglePaneInputTurn: {:#}", err);
+                    }
+                }
+            }
             CopyMode(_) => {
                 // NOP here; handled by the overlay directly
             }
@@ -2723,62 +3177,275 @@ impl TermWindow {
                 let modal = crate::termwindow::charselect::CharSelector::new(self, args);
                 self.modal.borrow_mut().replace(Rc::new(modal));
             }
+            ShowWorkspaceManager => {
+                let modal = crate::termwindow::workspacemanager::WorkspaceManager::new(self);
+                self.modal.borrow_mut().replace(Rc::new(modal));
+            }
+            ShowClipboardHistory => {
+                let modal = crate::termwindow::clipboardhistory::ClipboardHistoryPicker::new(self);
+                self.modal.borrow_mut().replace(Rc::new(modal));
+            }
             ResetTerminal => {
                 pane.perform_actions(vec![termwiz::escape::Action::Esc(
                     termwiz::escape::Esc::Code(termwiz::escape::EscCode::FullReset),
                 )]);
             }
+            RestartPane => {
+                if let Err(err) = pane.restart() {
+                    log::error!("RestartPane: {:#}", err);
+                }
+            }
+            ToggleDoNotDisturb => {
+                toggle_do_not_disturb();
+                for gui_win in crate::frontend::front_end().gui_windows() {
+                    gui_win
+                        .window
+                        .notify(TermWindowNotif::Apply(Box::new(|term_window| {
+                            term_window.update_title();
+                        })));
+                }
+            }
+            SetMark => self.record_jump(pane.pane_id()),
+            JumpBack => self.navigate_jump_list(pane, false),
+            JumpForward => self.navigate_jump_list(pane, true),
+            SetNamedMark(name) => self.set_named_mark(pane, name),
+            GotoNamedMark(name) => self.goto_named_mark(pane, name),
+            ShowNamedMarks => {
+                let modal = namedmarks::NamedMarkPicker::new(self, pane);
+                self.modal.borrow_mut().replace(Rc::new(modal));
+            }
+            ActivateHintsMode => {
+                let modal = hints::HintsSelector::new(self);
+                self.modal.borrow_mut().replace(Rc::new(modal));
+            }
+            SetPaneBidiMode(mode) => {
+                pane.set_bidi_mode(mode.enabled, mode.direction);
+            }
+            SetPaneBellVolume(percent) => {
+                self.pane_state(pane.pane_id())
+                    .bell_volume
+                    .replace((*percent).min(100));
+            }
+            ShowSerialPortSelector => {
+                let modal = serialport::SerialPortSelector::new(pane);
+                self.modal.borrow_mut().replace(Rc::new(modal));
+            }
+            SetSerialBaudRate(_) | SetSerialFlowControl(_) | ToggleSerialDtr | ToggleSerialRts => {
+                serialport::perform_serial_assignment(pane, assignment);
+            }
+            TogglePipeToPane(direction) => {
+                let mux = Mux::get().unwrap();
+                let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+                    Some(tab) => tab,
+                    None => return Ok(PerformAssignmentResult::Handled),
+                };
+                let target = tab.get_pane_direction(*direction).and_then(|idx| {
+                    tab.iter_panes()
+                        .into_iter()
+                        .find(|p| p.index == idx)
+                        .map(|p| p.pane.pane_id())
+                });
+                if let Some(target) = target {
+                    let source = pane.pane_id();
+                    let existing = mux::panepipe::pipes_from(source)
+                        .into_iter()
+                        .find(|(_, pipe_target)| *pipe_target == target);
+                    match existing {
+                        Some((id, _)) => {
+                            mux::panepipe::remove_pipe(source, id);
+                        }
+                        None => {
+                            if let Err(err) = mux::panepipe::pipe_to(source, target, true) {
+                                log::error!("{:#}", err);
+                            }
+                        }
+                    }
+                }
+            }
+            TogglePaneLogging(args) => {
+                let pane_id = pane.pane_id();
+                if let Some(path) = mux::panelog::log_path(pane_id) {
+                    mux::panelog::stop_logging(pane_id);
+                    log::info!("pane {}: stopped logging to {}", pane_id, path.display());
+                } else {
+                    let path = mux::panelog::resolve_log_path(pane_id, args.path.as_deref());
+                    let options = mux::panelog::LogOptions {
+                        with_escapes: args.with_escapes,
+                        max_size: args.max_size,
+                        max_files: args.max_files,
+                    };
+                    match mux::panelog::start_logging(pane_id, &path, options) {
+                        Ok(()) => log::info!("pane {}: logging to {}", pane_id, path.display()),
+                        Err(err) => log::error!(
+                            "pane {}: unable to log to {}: {:#}",
+                            pane_id,
+                            path.display(),
+                            err
+                        ),
+                    }
+                }
+            }
+            FillPassword => self.fill_password(pane),
+            LockInput(scope) => self.lock_input(pane, *scope),
+            ToggleComparePanes(args) => self.toggle_compare_panes(pane, args),
+            TogglePaneDiffMode => self.toggle_pane_diff_mode(pane),
+            ToggleDiffHunk => self.toggle_diff_hunk(pane),
+            PageLastCommandOutput => self.page_last_command_output(pane),
+            SnapshotPane(direction) => self.snapshot_pane(pane, *direction),
+            ToggleRedactionReveal => {
+                let revealed = redaction::toggle_reveal(pane.pane_id());
+                log::info!(
+                    "pane {}: redacted text is {}",
+                    pane.pane_id(),
+                    if revealed { "revealed" } else { "masked" }
+                );
+                if let Some(window) = self.window.as_ref() {
+                    window.invalidate();
+                }
+            }
+            ShowContainerSelector => {
+                let mux = Mux::get().unwrap();
+                if let Some(tab) = mux.get_active_tab_for_window(self.mux_window_id) {
+                    let pane_id = pane.pane_id();
+                    let window = self.window.clone().unwrap();
+                    let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+                        container_selector(pane_id, term, window)
+                    });
+                    self.assign_overlay(tab.tab_id(), overlay);
+                    promise::spawn::spawn(future).detach();
+                }
+            }
+            ShowKubernetesPodSelector => {
+                let mux = Mux::get().unwrap();
+                if let Some(tab) = mux.get_active_tab_for_window(self.mux_window_id) {
+                    let pane_id = pane.pane_id();
+                    let window = self.window.clone().unwrap();
+                    let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+                        kubernetes_pod_selector(pane_id, term, window)
+                    });
+                    self.assign_overlay(tab.tab_id(), overlay);
+                    promise::spawn::spawn(future).detach();
+                }
+            }
+            ShowRecentPanes => {
+                let mux = Mux::get().unwrap();
+                if let Some(tab) = mux.get_active_tab_for_window(self.mux_window_id) {
+                    let entries = recent_pane_entries(self.config.workspace_per_window);
+                    let (overlay, future) =
+                        start_overlay(self, &tab, move |_tab_id, term| recent_panes(term, entries));
+                    self.assign_overlay(tab.tab_id(), overlay);
+                    promise::spawn::spawn(future).detach();
+                }
+            }
+            ShowMuxClients => {
+                let mux = Mux::get().unwrap();
+                if let Some(tab) = mux.get_active_tab_for_window(self.mux_window_id) {
+                    let (overlay, future) =
+                        start_overlay(self, &tab, move |_tab_id, term| mux_clients(term));
+                    self.assign_overlay(tab.tab_id(), overlay);
+                    promise::spawn::spawn(future).detach();
+                }
+            }
+            ShowNotificationCenter => {
+                let mux = Mux::get().unwrap();
+                if let Some(tab) = mux.get_active_tab_for_window(self.mux_window_id) {
+                    let pane_id = pane.pane_id();
+                    let window = self.window.clone().unwrap();
+                    let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+                        notification_center(pane_id, term, window)
+                    });
+                    self.assign_overlay(tab.tab_id(), overlay);
+                    promise::spawn::spawn(future).detach();
+                }
+            }
         };
         Ok(PerformAssignmentResult::Handled)
     }
 
+    /// Makes the sound selected by `audible_bell` for a bell that rang
+    /// in the specified pane, unless the bell is muted for that pane
+    /// or its domain, or has rung too often recently
+    fn ring_audible_bell(&self, pane_id: PaneId) {
+        let pane_volume = self.pane_state(pane_id).bell_volume.unwrap_or(100);
+        let volume = self.config.audible_bell_volume.clamp(0., 1.) * pane_volume as f32 / 100.;
+        if volume <= 0. {
+            return;
+        }
+
+        let mux = Mux::get().expect("on main thread");
+        let domain_is_muted = mux
+            .resolve_pane_id(pane_id)
+            .and_then(|(domain_id, _, _)| mux.get_domain(domain_id))
+            .map(|domain| {
+                self.config
+                    .audible_bell_muted_domains
+                    .iter()
+                    .any(|name| name == domain.domain_name())
+            })
+            .unwrap_or(false);
+        if domain_is_muted || !crate::sound::bell_admitted() {
+            return;
+        }
+
+        match &self.config.audible_bell {
+            AudibleBell::SystemBeep => {
+                Connection::get().expect("on main thread").beep();
+            }
+            AudibleBell::SoundFile(path) => {
+                crate::sound::play_sound_file(path.clone(), volume);
+            }
+            AudibleBell::Disabled => {}
+        }
+    }
+
     fn do_open_link_at_mouse_cursor(&self, pane: &Rc<dyn Pane>) {
         // They clicked on a link, so let's open it!
+        if let Some(link) = self.current_highlight.as_ref().cloned() {
+            self.open_link(pane, link.uri().to_string());
+        }
+    }
+
+    /// Opens `link` on behalf of `pane`.
+    pub fn open_link(&self, pane: &Rc<dyn Pane>, link: String) {
         // We need to ensure that we spawn the `open` call outside of the context
         // of our window loop; on Windows it can cause a panic due to
         // triggering our WndProc recursively.
         // We get that assurance for free as part of the async dispatch that we
         // perform below; here we allow the user to define an `open-uri` event
         // handler that can bypass the normal `open::that` functionality.
-        if let Some(link) = self.current_highlight.as_ref().cloned() {
-            let window = GuiWin::new(self);
-            let pane = MuxPane(pane.pane_id());
-
-            async fn open_uri(
-                lua: Option<Rc<mlua::Lua>>,
-                window: GuiWin,
-                pane: MuxPane,
-                link: String,
-            ) -> anyhow::Result<()> {
-                let default_click = match lua {
-                    Some(lua) => {
-                        let args = lua.pack_multi((window, pane, link.clone()))?;
-                        config::lua::emit_event(&lua, ("open-uri".to_string(), args))
-                            .await
-                            .map_err(|e| {
-                                log::error!("while processing open-uri event: {:#}", e);
-                                e
-                            })?
-                    }
-                    None => true,
-                };
-                if default_click {
-                    std::thread::spawn(move || {
-                        log::info!("clicking {}", link);
-                        if let Err(err) = open::that(&link) {
-                            log::error!("Error opening {}: {:#}", link, err);
-                        }
-                    });
+        let window = GuiWin::new(self);
+        let pane = MuxPane(pane.pane_id());
+
+        async fn open_uri(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: MuxPane,
+            link: String,
+        ) -> anyhow::Result<()> {
+            let default_click = match &lua {
+                Some(lua) => {
+                    let args = lua.pack_multi((window.clone(), pane, link.clone()))?;
+                    config::lua::emit_event(lua, ("open-uri".to_string(), args))
+                        .await
+                        .map_err(|e| {
+                            log::error!("while processing open-uri event: {:#}", e);
+                            e
+                        })?
                 }
-                Ok(())
+                None => true,
+            };
+            if default_click {
+                hyperlink::dispatch_hyperlink(lua, window, pane, link).await?;
             }
-
-            promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
-                open_uri(lua, window, pane, link.uri().to_string())
-            }))
-            .detach();
+            Ok(())
         }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            open_uri(lua, window, pane, link)
+        }))
+        .detach();
     }
+
     fn close_current_pane(&mut self, confirm: bool) {
         let mux_window_id = self.mux_window_id;
         let mux = Mux::get().unwrap();
@@ -2804,6 +3471,44 @@ impl TermWindow {
         }
     }
 
+    fn show_dead_pane_inspector(&mut self, pane_id: PaneId, status: String) {
+        let mux = Mux::get().unwrap();
+        let pane = match mux.get_pane(pane_id) {
+            Some(pane) => pane,
+            None => return,
+        };
+        let lines = pane_output_lines(&pane, self.config.dead_pane_inspector_lines);
+        let window = self.window.clone().unwrap();
+        let (overlay, future) = start_overlay_pane(self, &pane, move |pane_id, term| {
+            inspect_dead_pane(pane_id, term, status, lines, window)
+        });
+        self.assign_overlay_for_pane(pane_id, overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn page_last_command_output(&mut self, pane: &Rc<dyn Pane>) {
+        let lines = match last_command_output(pane) {
+            Some(lines) => lines,
+            None => {
+                log::warn!(
+                    "PageLastCommandOutput: pane {} has no command output; \
+                     shell integration is required to find it",
+                    pane.pane_id()
+                );
+                return;
+            }
+        };
+        let save_path = format!(
+            "~/wezterm-output-{}.txt",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        );
+        let (overlay, future) = start_overlay_pane(self, pane, move |pane_id, term| {
+            pager(pane_id, term, lines, save_path)
+        });
+        self.assign_overlay_for_pane(pane.pane_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
     fn close_specific_tab(&mut self, tab_idx: usize, confirm: bool) {
         let mux = Mux::get().unwrap();
         let mux_window_id = self.mux_window_id;
@@ -2919,7 +3624,17 @@ impl TermWindow {
         };
 
         let mut state = self.pane_state(pane_id);
-        if pos != state.viewport {
+        let changed = pos != state.viewport;
+        if changed {
+            match (state.viewport, pos) {
+                (None, Some(_)) => {
+                    state.seen_end = Some(dims.physical_top + dims.viewport_rows as StableRowIndex);
+                }
+                (_, None) => {
+                    state.seen_end = None;
+                }
+                _ => {}
+            }
             state.viewport = pos;
 
             // This is a bit gross.  If we add other overlays that need this information,
@@ -2932,6 +3647,10 @@ impl TermWindow {
                 }
             }
         }
+        drop(state);
+        if changed {
+            self.sync_compared_viewport(pane_id, pos, dims);
+        }
         self.window.as_ref().unwrap().invalidate();
     }
 
@@ -2947,7 +3666,9 @@ impl TermWindow {
     }
 
     fn scroll_to_bottom(&mut self, pane: &Rc<dyn Pane>) {
-        self.pane_state(pane.pane_id()).viewport = None;
+        let mut state = self.pane_state(pane.pane_id());
+        state.viewport = None;
+        state.seen_end = None;
     }
 
     fn get_active_pane_no_overlay(&self) -> Option<Rc<dyn Pane>> {
@@ -3020,6 +3741,7 @@ impl TermWindow {
             pixel_height: pos.pixel_height,
             title: pos.pane.get_title(),
             user_vars: pos.pane.copy_user_vars(),
+            badge: pos.pane.get_badge(),
         }
     }
 
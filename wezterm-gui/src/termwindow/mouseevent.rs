@@ -1,4 +1,5 @@
 use crate::tabbar::TabBarItem;
+use crate::termwindow::contextmenu::ContextMenuKind;
 use crate::termwindow::keyevent::window_mods_to_termwiz_mods;
 use crate::termwindow::{MouseCapture, PositionedSplit, ScrollHit, UIItem, UIItemType, TMB};
 use ::window::{
@@ -6,7 +7,7 @@ use ::window::{
     WindowState,
 };
 use config::keyassignment::{MouseEventTrigger, SpawnTabDomain};
-use config::MouseEventAltScreen;
+use config::{IntegratedTitleButton, MouseEventAltScreen};
 use mux::pane::{Pane, WithPaneLines};
 use mux::tab::SplitDirection;
 use mux::Mux;
@@ -38,10 +39,12 @@ impl super::TermWindow {
                 self.update_title_post_status();
             }
             UIItemType::CloseTab(_)
+            | UIItemType::TitleButton(_)
             | UIItemType::AboveScrollThumb
             | UIItemType::BelowScrollThumb
             | UIItemType::ScrollThumb
-            | UIItemType::Split(_) => {}
+            | UIItemType::Split(_)
+            | UIItemType::ContextMenuItem(_) => {}
         }
     }
 
@@ -49,10 +52,12 @@ impl super::TermWindow {
         match item.item_type {
             UIItemType::TabBar(_) => {}
             UIItemType::CloseTab(_)
+            | UIItemType::TitleButton(_)
             | UIItemType::AboveScrollThumb
             | UIItemType::BelowScrollThumb
             | UIItemType::ScrollThumb
-            | UIItemType::Split(_) => {}
+            | UIItemType::Split(_)
+            | UIItemType::ContextMenuItem(_) => {}
         }
     }
 
@@ -135,6 +140,10 @@ impl super::TermWindow {
 
             WMEK::Press(ref press) => {
                 capture_mouse = true;
+                // The system takes over the mouse while the selection
+                // is dragged out of the window, so we may not see the
+                // release that ended that drag
+                self.selection_drag_started = false;
 
                 // Perform click counting
                 let button = mouse_press_to_tmb(press);
@@ -210,6 +219,10 @@ impl super::TermWindow {
             None
         };
 
+        if self.context_menu_mouse_event(ui_item.as_ref(), &event) {
+            return;
+        }
+
         if let Some(item) = ui_item {
             if capture_mouse {
                 self.current_mouse_capture = Some(MouseCapture::UI);
@@ -236,6 +249,7 @@ impl super::TermWindow {
 
     pub fn mouse_leave_impl(&mut self, context: &dyn WindowOps) {
         self.current_mouse_event = None;
+        self.update_collaborator_cursor(None);
         self.update_title();
         context.set_cursor(Some(MouseCursor::Arrow));
         context.invalidate();
@@ -372,7 +386,51 @@ impl super::TermWindow {
             UIItemType::CloseTab(idx) => {
                 self.mouse_event_close_tab(idx, event, context);
             }
+            UIItemType::TitleButton(idx) => {
+                self.mouse_event_title_button(idx, event, context);
+            }
+            // Handled by context_menu_mouse_event
+            UIItemType::ContextMenuItem(_) => {}
+        }
+    }
+
+    pub fn mouse_event_title_button(
+        &mut self,
+        idx: usize,
+        event: MouseEvent,
+        context: &dyn WindowOps,
+    ) {
+        match event.kind {
+            WMEK::Press(MousePress::Left) => {
+                match self.config.integrated_title_buttons.get(idx).cloned() {
+                    Some(IntegratedTitleButton::Hide) => context.hide(),
+                    Some(IntegratedTitleButton::Maximize) => {
+                        if self.window_state.contains(WindowState::MAXIMIZED) {
+                            context.restore();
+                        } else {
+                            context.maximize();
+                        }
+                    }
+                    Some(IntegratedTitleButton::Close) => {
+                        if let Some(window) = self.window.clone() {
+                            self.close_requested(&window);
+                        }
+                    }
+                    Some(IntegratedTitleButton::Custom(button)) => {
+                        if let Some(pane) = self.get_active_pane_or_overlay() {
+                            if let Err(err) =
+                                self.perform_assignment_unless_locked(&pane, &button.action)
+                            {
+                                log::error!("Error performing {:?}: {:#}", button.action, err);
+                            }
+                        }
+                    }
+                    None => {}
+                }
+            }
+            _ => {}
         }
+        context.set_cursor(Some(MouseCursor::Arrow));
     }
 
     pub fn mouse_event_close_tab(
@@ -426,13 +484,21 @@ impl super::TermWindow {
                 | TabBarItem::RightStatus => {}
             },
             WMEK::Press(MousePress::Right) => match item {
-                TabBarItem::Tab { .. } => {
-                    self.show_tab_navigator();
+                TabBarItem::Tab { tab_idx, .. } => {
+                    // The actions of the menu apply to the active tab
+                    self.activate_tab(tab_idx as isize).ok();
+                    if let Some(pane) = self.get_active_pane_or_overlay() {
+                        self.show_pane_context_menu(&pane, ContextMenuKind::Tab);
+                    }
                 }
                 TabBarItem::NewTabButton { .. } => {
                     self.show_launcher();
                 }
-                TabBarItem::None | TabBarItem::LeftStatus | TabBarItem::RightStatus => {}
+                TabBarItem::None | TabBarItem::LeftStatus | TabBarItem::RightStatus => {
+                    if let Some(pane) = self.get_active_pane_or_overlay() {
+                        self.show_pane_context_menu(&pane, ContextMenuKind::TabBarBackground);
+                    }
+                }
             },
             WMEK::Move => match item {
                 TabBarItem::None => {
@@ -554,6 +620,7 @@ impl super::TermWindow {
             Some(MouseCapture::TerminalPane(_))
         );
 
+        let mut hovered_pane = None;
         for pos in self.get_panes_to_render() {
             if !is_already_captured
                 && row >= pos.top as i64
@@ -593,6 +660,7 @@ impl super::TermWindow {
                 }
                 column = column.saturating_sub(pos.left);
                 row = row.saturating_sub(pos.top as i64);
+                hovered_pane = Some(Rc::clone(&pos.pane));
                 break;
             } else if is_already_captured && pane.pane_id() == pos.pane.pane_id() {
                 column = column.saturating_sub(pos.left);
@@ -606,6 +674,7 @@ impl super::TermWindow {
                     y_pixel_offset -= self.render_metrics.cell_size.height
                         * (pos.top as isize - position.row as isize);
                 }
+                hovered_pane = Some(Rc::clone(&pos.pane));
 
                 break;
             }
@@ -615,6 +684,18 @@ impl super::TermWindow {
             self.current_mouse_capture = Some(MouseCapture::TerminalPane(pane.pane_id()));
         }
 
+        match &hovered_pane {
+            Some(hovered) => {
+                let dims = hovered.get_dimensions();
+                let y = self
+                    .get_viewport(hovered.pane_id())
+                    .unwrap_or(dims.physical_top)
+                    + row as StableRowIndex;
+                self.update_collaborator_cursor(Some((hovered, column, y)));
+            }
+            None => self.update_collaborator_cursor(None),
+        }
+
         let is_focused = if let Some(focused) = self.focused.as_ref() {
             !self.config.swallow_mouse_click_on_window_focus
                 || (focused.elapsed() > Duration::from_millis(200))
@@ -641,7 +722,9 @@ impl super::TermWindow {
             return;
         }
 
-        let allow_action = if self.is_click_to_focus_window || !is_focused {
+        let allow_action = if self.input_is_locked(pane.pane_id()) {
+            false
+        } else if self.is_click_to_focus_window || !is_focused {
             matches!(&event.kind, WMEK::VertWheel(_) | WMEK::HorzWheel(_))
         } else {
             true
@@ -0,0 +1,459 @@
+//! Named marks anchor a name to a line in the scrollback of a pane.
+//! They are recorded as stable row indices, so they continue to refer
+//! to the same line as new output arrives, until that line is evicted
+//! from the scrollback.
+use crate::termwindow::box_model::*;
+use crate::termwindow::modal::Modal;
+use crate::termwindow::render::{
+    BOTTOM_LEFT_ROUNDED_CORNER, BOTTOM_RIGHT_ROUNDED_CORNER, TOP_LEFT_ROUNDED_CORNER,
+    TOP_RIGHT_ROUNDED_CORNER,
+};
+use crate::termwindow::DimensionContext;
+use crate::utilsprites::RenderMetrics;
+use crate::TermWindow;
+use config::keyassignment::KeyAssignment;
+use config::Dimension;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use mux::pane::{Pane, PaneId};
+use mux::Mux;
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+use wezterm_term::{KeyCode, KeyModifiers, MouseEvent, StableRowIndex};
+use window::color::LinearRgba;
+
+/// The longest preview of the marked line that we'll show in the picker
+const MAX_PREVIEW_LEN: usize = 80;
+
+impl TermWindow {
+    /// Returns the named marks of the pane that still refer to lines
+    /// in its scrollback, ordered by name
+    pub fn named_marks(&self, pane: &Rc<dyn Pane>) -> Vec<(String, StableRowIndex)> {
+        let dims = pane.get_dimensions();
+        let mut state = self.pane_state(pane.pane_id());
+        state
+            .named_marks
+            .retain(|_name, row| *row >= dims.scrollback_top);
+        state
+            .named_marks
+            .iter()
+            .map(|(name, row)| (name.clone(), *row))
+            .collect()
+    }
+
+    /// Marks the line at the top of the viewport of the pane with `name`,
+    /// replacing any prior mark with the same name
+    pub fn set_named_mark(&mut self, pane: &Rc<dyn Pane>, name: &str) {
+        let row = self
+            .get_viewport(pane.pane_id())
+            .unwrap_or_else(|| pane.get_dimensions().physical_top);
+        self.pane_state(pane.pane_id())
+            .named_marks
+            .insert(name.to_string(), row);
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    /// Scrolls the pane so that the line marked with `name` is at the
+    /// top of the viewport
+    pub fn goto_named_mark(&mut self, pane: &Rc<dyn Pane>, name: &str) {
+        let row = self.named_marks(pane).into_iter().find_map(|(mark, row)| {
+            if mark == name {
+                Some(row)
+            } else {
+                None
+            }
+        });
+        match row {
+            Some(row) => {
+                self.record_jump(pane.pane_id());
+                self.set_viewport(pane.pane_id(), Some(row), pane.get_dimensions());
+            }
+            None => log::warn!("GotoNamedMark: there is no mark named {}", name),
+        }
+    }
+
+    fn remove_named_mark(&mut self, pane_id: PaneId, name: &str) {
+        self.pane_state(pane_id).named_marks.remove(name);
+    }
+}
+
+/// Returns the text of the marked line, trimmed to fit in the picker
+fn line_preview(pane: &Rc<dyn Pane>, row: StableRowIndex) -> String {
+    let (_first, lines) = pane.get_lines(row..row + 1);
+    let mut preview = String::new();
+    if let Some(line) = lines.get(0) {
+        for (idx, c) in line.as_str().trim().chars().enumerate() {
+            if idx >= MAX_PREVIEW_LEN {
+                preview.push('…');
+                break;
+            }
+            if !c.is_control() {
+                preview.push(c);
+            }
+        }
+    }
+    preview
+}
+
+struct MarkEntry {
+    name: String,
+    preview: String,
+}
+
+pub struct NamedMarkPicker {
+    pane_id: PaneId,
+    element: RefCell<Option<Vec<ComputedElement>>>,
+    entries: RefCell<Vec<MarkEntry>>,
+    filter: RefCell<String>,
+    /// indices into entries that match the filter, in display order
+    matches: RefCell<Vec<usize>>,
+    selected_row: RefCell<usize>,
+    top_row: RefCell<usize>,
+    max_rows_on_screen: RefCell<usize>,
+}
+
+impl NamedMarkPicker {
+    pub fn new(term_window: &mut TermWindow, pane: &Rc<dyn Pane>) -> Self {
+        let picker = Self {
+            pane_id: pane.pane_id(),
+            element: RefCell::new(None),
+            entries: RefCell::new(vec![]),
+            filter: RefCell::new(String::new()),
+            matches: RefCell::new(vec![]),
+            selected_row: RefCell::new(0),
+            top_row: RefCell::new(0),
+            max_rows_on_screen: RefCell::new(0),
+        };
+        picker.refresh(term_window);
+        picker
+    }
+
+    /// Re-reads the marks and recomputes the matches, keeping the
+    /// selection within bounds
+    fn refresh(&self, term_window: &TermWindow) {
+        let mux = Mux::get().unwrap();
+        *self.entries.borrow_mut() = match mux.get_pane(self.pane_id) {
+            Some(pane) => term_window
+                .named_marks(&pane)
+                .into_iter()
+                .map(|(name, row)| MarkEntry {
+                    preview: line_preview(&pane, row),
+                    name,
+                })
+                .collect(),
+            None => vec![],
+        };
+        self.update_matches();
+
+        let limit = self.matches.borrow().len().saturating_sub(1);
+        let mut row = self.selected_row.borrow_mut();
+        *row = (*row).min(limit);
+        let mut top_row = self.top_row.borrow_mut();
+        *top_row = (*top_row).min(*row);
+    }
+
+    fn update_matches(&self) {
+        let entries = self.entries.borrow();
+        let filter = self.filter.borrow();
+        let matches: Vec<usize> = if filter.is_empty() {
+            (0..entries.len()).collect()
+        } else {
+            let matcher = SkimMatcherV2::default();
+            let mut scored: Vec<(usize, i64)> = entries
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, entry)| {
+                    matcher
+                        .fuzzy_match(&entry.name, &filter)
+                        .map(|score| (idx, score))
+                })
+                .collect();
+            scored.sort_by(|(a_idx, a_score), (b_idx, b_score)| {
+                b_score.cmp(a_score).then(a_idx.cmp(b_idx))
+            });
+            scored.into_iter().map(|(idx, _)| idx).collect()
+        };
+        *self.matches.borrow_mut() = matches;
+    }
+
+    fn updated_input(&self) {
+        self.update_matches();
+        *self.selected_row.borrow_mut() = 0;
+        *self.top_row.borrow_mut() = 0;
+    }
+
+    fn selected_name(&self) -> Option<String> {
+        let idx = *self.matches.borrow().get(*self.selected_row.borrow())?;
+        self.entries
+            .borrow()
+            .get(idx)
+            .map(|entry| entry.name.clone())
+    }
+
+    fn move_up(&self) {
+        let mut row = self.selected_row.borrow_mut();
+        *row = row.saturating_sub(1);
+
+        let mut top_row = self.top_row.borrow_mut();
+        if *row < *top_row {
+            *top_row = *row;
+        }
+    }
+
+    fn move_down(&self) {
+        let max_rows_on_screen = (*self.max_rows_on_screen.borrow()).max(1);
+        let limit = self.matches.borrow().len().saturating_sub(1);
+        let mut row = self.selected_row.borrow_mut();
+        *row = row.saturating_add(1).min(limit);
+        let mut top_row = self.top_row.borrow_mut();
+        if *row > *top_row + max_rows_on_screen - 1 {
+            *top_row = row.saturating_sub(max_rows_on_screen - 1);
+        }
+    }
+
+    fn compute(
+        &self,
+        term_window: &mut TermWindow,
+        max_rows_on_screen: usize,
+    ) -> anyhow::Result<Vec<ComputedElement>> {
+        let font = term_window
+            .fonts
+            .char_select_font()
+            .expect("to resolve char selection font");
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+
+        let top_bar_height = if term_window.show_tab_bar && !term_window.config.tab_bar_at_bottom {
+            term_window.tab_bar_pixel_height().unwrap()
+        } else {
+            0.
+        };
+        let (padding_left, padding_top) = term_window.padding_left_top();
+        let border = term_window.get_os_border();
+        let top_pixel_y = top_bar_height + padding_top + border.top.get() as f32;
+
+        let selected_row = *self.selected_row.borrow();
+        let top_row = *self.top_row.borrow();
+        let entries = self.entries.borrow();
+        let matches = self.matches.borrow();
+
+        let fg = term_window.config.pane_select_fg_color.to_linear();
+        let bg = term_window.config.pane_select_bg_color.to_linear();
+
+        let header = if entries.is_empty() {
+            "There are no marks in this pane; use SetNamedMark to add one".to_string()
+        } else {
+            format!(
+                "Marks: {}_   (Enter=go to mark Ctrl-D=delete Esc=close)",
+                self.filter.borrow()
+            )
+        };
+
+        let mut elements = vec![Element::new(&font, ElementContent::Text(header))
+            .colors(ElementColors {
+                border: BorderColor::default(),
+                bg: LinearRgba::TRANSPARENT.into(),
+                text: fg.into(),
+            })
+            .display(DisplayType::Block)];
+
+        let name_width = entries
+            .iter()
+            .map(|entry| entry.name.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        for (display_idx, &entry_idx) in matches
+            .iter()
+            .enumerate()
+            .skip(top_row)
+            .take(max_rows_on_screen)
+        {
+            let (row_bg, text) = if display_idx == selected_row {
+                (fg.into(), bg.into())
+            } else {
+                (LinearRgba::TRANSPARENT.into(), fg.into())
+            };
+            let entry = &entries[entry_idx];
+            let label = format!(
+                "{:name_width$}  {}",
+                entry.name,
+                entry.preview,
+                name_width = name_width
+            );
+            elements.push(
+                Element::new(&font, ElementContent::Text(label))
+                    .colors(ElementColors {
+                        border: BorderColor::default(),
+                        bg: row_bg,
+                        text,
+                    })
+                    .padding(BoxDimension {
+                        left: Dimension::Cells(0.25),
+                        right: Dimension::Cells(0.25),
+                        top: Dimension::Cells(0.),
+                        bottom: Dimension::Cells(0.),
+                    })
+                    .display(DisplayType::Block),
+            );
+        }
+
+        let corner = |poly: &'static [Poly]| SizedPoly {
+            width: Dimension::Cells(0.25),
+            height: Dimension::Cells(0.25),
+            poly,
+        };
+        let element = Element::new(&font, ElementContent::Children(elements))
+            .colors(ElementColors {
+                border: BorderColor::new(bg.into()),
+                bg: bg.into(),
+                text: fg.into(),
+            })
+            .margin(BoxDimension {
+                left: Dimension::Cells(1.25),
+                right: Dimension::Cells(1.25),
+                top: Dimension::Cells(1.25),
+                bottom: Dimension::Cells(1.25),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.25),
+                right: Dimension::Cells(0.25),
+                top: Dimension::Cells(0.25),
+                bottom: Dimension::Cells(0.25),
+            })
+            .border(BoxDimension::new(Dimension::Pixels(1.)))
+            .border_corners(Some(Corners {
+                top_left: corner(TOP_LEFT_ROUNDED_CORNER),
+                top_right: corner(TOP_RIGHT_ROUNDED_CORNER),
+                bottom_left: corner(BOTTOM_LEFT_ROUNDED_CORNER),
+                bottom_right: corner(BOTTOM_RIGHT_ROUNDED_CORNER),
+            }));
+
+        let dimensions = term_window.dimensions;
+        let size = term_window.terminal_size;
+
+        let computed = term_window.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_height as f32,
+                    pixel_cell: metrics.cell_size.height as f32,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_width as f32,
+                    pixel_cell: metrics.cell_size.width as f32,
+                },
+                bounds: euclid::rect(
+                    padding_left,
+                    top_pixel_y,
+                    size.cols as f32 * term_window.render_metrics.cell_size.width as f32,
+                    size.rows as f32 * term_window.render_metrics.cell_size.height as f32,
+                ),
+                metrics: &metrics,
+                gl_state: term_window.render_state.as_ref().unwrap(),
+                zindex: 100,
+            },
+            &element,
+        )?;
+
+        Ok(vec![computed])
+    }
+}
+
+impl Modal for NamedMarkPicker {
+    fn perform_assignment(
+        &self,
+        _assignment: &KeyAssignment,
+        _term_window: &mut TermWindow,
+    ) -> bool {
+        false
+    }
+
+    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn key_down(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<()> {
+        match (key, mods) {
+            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+                term_window.cancel_modal();
+                return Ok(());
+            }
+            (KeyCode::UpArrow, KeyModifiers::NONE) => {
+                self.move_up();
+            }
+            (KeyCode::DownArrow, KeyModifiers::NONE) => {
+                self.move_down();
+            }
+            (KeyCode::Char('d'), KeyModifiers::CTRL) => {
+                if let Some(name) = self.selected_name() {
+                    term_window.remove_named_mark(self.pane_id, &name);
+                    self.refresh(term_window);
+                    if let Some(window) = term_window.window.as_ref() {
+                        window.invalidate();
+                    }
+                }
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                self.filter.borrow_mut().push(c);
+                self.updated_input();
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                self.filter.borrow_mut().pop();
+                self.updated_input();
+            }
+            (KeyCode::Char('u'), KeyModifiers::CTRL) => {
+                self.filter.borrow_mut().clear();
+                self.updated_input();
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                let name = self.selected_name();
+                term_window.cancel_modal();
+                let mux = Mux::get().unwrap();
+                if let (Some(name), Some(pane)) = (name, mux.get_pane(self.pane_id)) {
+                    term_window.goto_named_mark(&pane, &name);
+                }
+                return Ok(());
+            }
+            _ => return Ok(()),
+        }
+        term_window.invalidate_modal();
+        Ok(())
+    }
+
+    fn computed_element(
+        &self,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<Ref<[ComputedElement]>> {
+        let font = term_window
+            .fonts
+            .char_select_font()
+            .expect("to resolve char selection font");
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+
+        let max_rows_on_screen = ((term_window.dimensions.pixel_height * 8 / 10)
+            / metrics.cell_size.height as usize)
+            .saturating_sub(2)
+            .max(1);
+        *self.max_rows_on_screen.borrow_mut() = max_rows_on_screen;
+
+        if self.element.borrow().is_none() {
+            let element = self.compute(term_window, max_rows_on_screen)?;
+            self.element.borrow_mut().replace(element);
+        }
+        Ok(Ref::map(self.element.borrow(), |v| {
+            v.as_ref().unwrap().as_slice()
+        }))
+    }
+
+    fn reconfigure(&self, _term_window: &mut TermWindow) {
+        self.element.borrow_mut().take();
+    }
+}
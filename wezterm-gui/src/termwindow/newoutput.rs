@@ -0,0 +1,181 @@
+//! Renders the `show_new_output_indicator` divider and count, which
+//! show how much output has arrived in a pane while it is scrolled
+//! back into its scrollback
+use crate::quad::TripleLayerQuadAllocator;
+use crate::termwindow::box_model::*;
+use crate::termwindow::render::{
+    BOTTOM_LEFT_ROUNDED_CORNER, BOTTOM_RIGHT_ROUNDED_CORNER, TOP_LEFT_ROUNDED_CORNER,
+    TOP_RIGHT_ROUNDED_CORNER,
+};
+use crate::termwindow::DimensionContext;
+use crate::utilsprites::RenderMetrics;
+use crate::TermWindow;
+use config::Dimension;
+use mux::pane::Pane;
+use mux::tab::PositionedPane;
+use std::rc::Rc;
+
+impl TermWindow {
+    /// Returns the number of lines of output that have arrived in the
+    /// pane since it was scrolled away from the bottom, along with the
+    /// stable row index of the first of them
+    pub fn new_output_since_scrolled(&self, pane: &Rc<dyn Pane>) -> Option<(usize, isize)> {
+        if !self.config.show_new_output_indicator {
+            return None;
+        }
+        let seen_end = self.pane_state(pane.pane_id()).seen_end?;
+        let dims = pane.get_dimensions();
+        let end = dims.physical_top + dims.viewport_rows as isize;
+        if end > seen_end {
+            Some(((end - seen_end) as usize, seen_end))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the pixel coordinates of the top left of the pane
+    pub(crate) fn pane_origin(&self, pos: &PositionedPane) -> anyhow::Result<(f32, f32)> {
+        let (padding_left, padding_top) = self.padding_left_top();
+        let border = self.get_os_border();
+        let top_bar_height = if self.show_tab_bar && !self.config.tab_bar_at_bottom {
+            self.tab_bar_pixel_height()?
+        } else {
+            0.
+        };
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+        Ok((
+            padding_left + border.left.get() as f32 + pos.left as f32 * cell_width,
+            top_bar_height + padding_top + border.top.get() as f32 + pos.top as f32 * cell_height,
+        ))
+    }
+
+    /// Draws a divider along the top edge of the first line of output
+    /// that arrived since the pane was scrolled back, if that line is
+    /// visible
+    pub(crate) fn paint_new_output_divider(
+        &self,
+        pos: &PositionedPane,
+        layers: &mut TripleLayerQuadAllocator,
+    ) -> anyhow::Result<()> {
+        let (_count, first_row) = match self.new_output_since_scrolled(&pos.pane) {
+            Some(new) => new,
+            None => return Ok(()),
+        };
+        let top = match self.get_viewport(pos.pane.pane_id()) {
+            Some(top) => top,
+            None => return Ok(()),
+        };
+        let visible_row = first_row - top;
+        if visible_row <= 0 || visible_row >= pos.height as isize {
+            return Ok(());
+        }
+
+        let (pane_left, pane_top) = self.pane_origin(pos)?;
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+        let thickness = (cell_height / 16.).max(1.).round();
+        let color = self.config.pane_select_fg_color.to_linear().mul_alpha(0.5);
+        self.filled_rectangle(
+            layers,
+            2,
+            euclid::rect(
+                pane_left,
+                pane_top + visible_row as f32 * cell_height - thickness / 2.,
+                pos.width as f32 * cell_width,
+                thickness,
+            ),
+            color,
+        )?;
+        Ok(())
+    }
+
+    pub(crate) fn paint_new_output_indicators(&mut self) -> anyhow::Result<()> {
+        if !self.config.show_new_output_indicator || self.get_modal().is_some() {
+            return Ok(());
+        }
+
+        for pos in self.get_panes_to_render() {
+            if let Some((count, _first_row)) = self.new_output_since_scrolled(&pos.pane) {
+                let computed = self.compute_new_output_indicator(&pos, count)?;
+                let gl_state = self.render_state.as_ref().unwrap();
+                self.render_element(&computed, gl_state, None)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn compute_new_output_indicator(
+        &mut self,
+        pos: &PositionedPane,
+        count: usize,
+    ) -> anyhow::Result<ComputedElement> {
+        let font = self.fonts.title_font()?;
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+        let fg = self.config.pane_select_fg_color.to_linear();
+        let bg = self.config.pane_select_bg_color.to_linear();
+
+        let (pane_left, pane_top) = self.pane_origin(pos)?;
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+        let pane_width = pos.width as f32 * cell_width;
+        let pane_height = pos.height as f32 * cell_height;
+
+        let dimensions = self.dimensions;
+        let pixel_width = dimensions.pixel_width as f32;
+        let pixel_height = dimensions.pixel_height as f32;
+        let context = LayoutContext {
+            height: DimensionContext {
+                dpi: dimensions.dpi as f32,
+                pixel_max: pixel_height,
+                pixel_cell: metrics.cell_size.height as f32,
+            },
+            width: DimensionContext {
+                dpi: dimensions.dpi as f32,
+                pixel_max: pixel_width,
+                pixel_cell: metrics.cell_size.width as f32,
+            },
+            bounds: euclid::rect(0., 0., pixel_width, pixel_height),
+            metrics: &metrics,
+            gl_state: self.render_state.as_ref().unwrap(),
+            zindex: 100,
+        };
+
+        let corner = |poly: &'static [Poly]| SizedPoly {
+            width: Dimension::Cells(0.25),
+            height: Dimension::Cells(0.25),
+            poly,
+        };
+        let text = format!(
+            "{} new line{} \u{2014} press End to jump",
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+        let label = Element::new(&font, ElementContent::Text(text))
+            .colors(ElementColors {
+                border: BorderColor::new(fg.into()),
+                bg: bg.mul_alpha(0.85).into(),
+                text: fg.into(),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.5),
+                right: Dimension::Cells(0.5),
+                top: Dimension::Cells(0.25),
+                bottom: Dimension::Cells(0.25),
+            })
+            .border(BoxDimension::new(Dimension::Pixels(1.)))
+            .border_corners(Some(Corners {
+                top_left: corner(TOP_LEFT_ROUNDED_CORNER),
+                top_right: corner(TOP_RIGHT_ROUNDED_CORNER),
+                bottom_left: corner(BOTTOM_LEFT_ROUNDED_CORNER),
+                bottom_right: corner(BOTTOM_RIGHT_ROUNDED_CORNER),
+            }));
+        let mut label = self.compute_element(&context, &label)?;
+
+        // Place the label in the bottom right corner of the pane
+        let x = (pane_left + pane_width - label.bounds.width() - cell_width).max(pane_left);
+        let y = (pane_top + pane_height - label.bounds.height() - cell_height / 2.).max(pane_top);
+        label.translate(euclid::vec2(x, y));
+        Ok(label)
+    }
+}
@@ -0,0 +1,301 @@
+//! Implements `FillPassword`, which types a password obtained from
+//! a password manager into a pane that is showing a password prompt.
+//!
+//! The password is written straight to the pane, so it never passes
+//! through the clipboard, the clipboard history or paste handling,
+//! and it is never logged.  It is only typed while the pane is showing
+//! a prompt that matches `password_prompts` and the pty is known not
+//! to be echoing its input, so that it doesn't end up in the scrollback.
+//!
+//! The text of the prompt is produced by whatever is running in the
+//! pane, so it is only used to decide whether a source applies; it is
+//! never substituted into the backend, as that would allow a remote
+//! host to choose which credential is fetched.
+use crate::TermWindow;
+use anyhow::{anyhow, bail, Context};
+use config::{PasswordBackend, PasswordSource};
+use mux::pane::{Pane, PaneId};
+use mux::Mux;
+use regex::Regex;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use wezterm_dynamic::Value;
+
+/// Returns the text of the line that holds the cursor, up to the cursor
+fn prompt_text(pane: &dyn Pane) -> String {
+    let cursor = pane.get_cursor_position();
+    let (_, lines) = pane.get_lines(cursor.y..cursor.y + 1);
+    match lines.first() {
+        Some(line) => line.columns_as_str(0..cursor.x),
+        None => String::new(),
+    }
+}
+
+fn is_password_prompt(prompts: &[String], text: &str) -> bool {
+    prompts.iter().any(|prompt| match Regex::new(prompt) {
+        Ok(regex) => regex.is_match(text),
+        Err(err) => {
+            log::error!("password_prompts: invalid regex {}: {:#}", prompt, err);
+            false
+        }
+    })
+}
+
+/// Returns Some(true) if the pty is known to be echoing its input,
+/// or None if we can't tell
+fn is_echoing(pane: &dyn Pane) -> Option<bool> {
+    match pane.get_metadata() {
+        Value::Object(obj) => match obj.get(&Value::String("password_input".to_string())) {
+            Some(Value::Bool(password_input)) => Some(!password_input),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the backend of the first source that applies to the
+/// prompt, and whether to submit the password
+fn select_source(
+    sources: &[PasswordSource],
+    text: &str,
+    domain: Option<&str>,
+) -> Option<(PasswordBackend, bool)> {
+    for source in sources {
+        if !source.domains.is_empty()
+            && !domain
+                .map(|domain| source.domains.iter().any(|d| d == domain))
+                .unwrap_or(false)
+        {
+            continue;
+        }
+        match &source.prompt {
+            Some(prompt) => {
+                let regex = match Regex::new(prompt) {
+                    Ok(regex) => regex,
+                    Err(err) => {
+                        log::error!("password_sources: invalid regex {}: {:#}", prompt, err);
+                        continue;
+                    }
+                };
+                if regex.is_match(text) {
+                    return Some((source.backend.clone(), source.submit));
+                }
+            }
+            None => return Some((source.backend.clone(), source.submit)),
+        }
+    }
+    None
+}
+
+/// Runs a password manager command and returns the first line of its output
+fn run_command(args: &[String]) -> anyhow::Result<String> {
+    let (program, args) = args
+        .split_first()
+        .ok_or_else(|| anyhow!("the command is empty"))?;
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("running {}", program))?;
+    let mut stdout = output.stdout;
+    let result = if output.status.success() {
+        let line = stdout.split(|&b| b == b'\n').next().unwrap_or(&[]);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        String::from_utf8(line.to_vec()).context("the password is not valid UTF-8")
+    } else {
+        Err(anyhow!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    };
+    stdout.iter_mut().for_each(|b| *b = 0);
+    result
+}
+
+fn fetch_password(backend: &PasswordBackend) -> anyhow::Result<String> {
+    match backend {
+        PasswordBackend::Keychain { service, account } => {
+            match keyring::Entry::new(service, account).get_password() {
+                Ok(password) => Ok(password),
+                Err(keyring::Error::NoEntry) => bail!(
+                    "there is no keychain entry for service={} account={}",
+                    service,
+                    account
+                ),
+                Err(err) => bail!(
+                    "keychain service={} account={}: {:#}",
+                    service,
+                    account,
+                    err
+                ),
+            }
+        }
+        PasswordBackend::Pass { name } => {
+            run_command(&["pass".to_string(), "show".to_string(), name.to_string()])
+        }
+        PasswordBackend::OnePassword { reference } => {
+            run_command(&["op".to_string(), "read".to_string(), reference.to_string()])
+        }
+        PasswordBackend::Command { args } => run_command(args),
+    }
+}
+
+/// Types the password into the pane, provided that it is still
+/// showing the prompt
+fn type_password(pane_id: PaneId, mut password: String, submit: bool) -> anyhow::Result<()> {
+    let result = (|| {
+        let pane = Mux::get()
+            .and_then(|mux| mux.get_pane(pane_id))
+            .ok_or_else(|| anyhow!("pane {} has gone away", pane_id))?;
+        let config = config::configuration();
+        if !is_password_prompt(&config.password_prompts, &prompt_text(&*pane)) {
+            bail!("pane {} is no longer showing a password prompt", pane_id);
+        }
+        // Echo may also have been turned back on while we were
+        // fetching the password
+        if is_echoing(&*pane) != Some(false) {
+            bail!(
+                "pane {} is no longer known to have echo turned off",
+                pane_id
+            );
+        }
+        let mut writer = pane.writer();
+        writer.write_all(password.as_bytes())?;
+        if submit {
+            writer.write_all(b"\r")?;
+        }
+        Ok(())
+    })();
+    // Don't leave the password lying around in memory
+    unsafe { password.as_bytes_mut() }
+        .iter_mut()
+        .for_each(|b| *b = 0);
+    result
+}
+
+impl TermWindow {
+    pub(crate) fn fill_password(&mut self, pane: &Rc<dyn Pane>) {
+        let text = prompt_text(&**pane);
+        if !is_password_prompt(&self.config.password_prompts, &text) {
+            log::warn!(
+                "FillPassword: pane {} is not showing a password prompt",
+                pane.pane_id()
+            );
+            return;
+        }
+        match is_echoing(&**pane) {
+            Some(false) => {}
+            Some(true) => {
+                log::warn!(
+                    "FillPassword: pane {} is echoing its input, so the password \
+                     would be visible; not typing it",
+                    pane.pane_id()
+                );
+                return;
+            }
+            None => {
+                log::warn!(
+                    "FillPassword: can't tell whether pane {} is echoing its input, \
+                     so the password might be visible; not typing it",
+                    pane.pane_id()
+                );
+                return;
+            }
+        }
+
+        let domain_name = Mux::get()
+            .and_then(|mux| mux.get_domain(pane.domain_id()))
+            .map(|domain| domain.domain_name().to_string());
+        let (backend, submit) =
+            match select_source(&self.config.password_sources, &text, domain_name.as_deref()) {
+                Some(source) => source,
+                None => {
+                    log::warn!(
+                        "FillPassword: none of the password_sources apply to the prompt in pane {}",
+                        pane.pane_id()
+                    );
+                    return;
+                }
+            };
+
+        let pane_id = pane.pane_id();
+        promise::spawn::spawn(async move {
+            // The password manager may prompt the user or take a
+            // while to respond, so don't block the gui
+            let result = match smol::unblock(move || fetch_password(&backend)).await {
+                Ok(password) => type_password(pane_id, password, submit),
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                log::error!("FillPassword: {:#}", err);
+            }
+        })
+        .detach();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn prompts() {
+        let prompts = config::default_password_prompts();
+        assert!(is_password_prompt(&prompts, "[sudo] password for wez: "));
+        assert!(is_password_prompt(&prompts, "Password:"));
+        assert!(is_password_prompt(
+            &prompts,
+            "Enter passphrase for key '/home/wez/.ssh/id_ed25519': "
+        ));
+        assert!(!is_password_prompt(&prompts, "$ echo password: done"));
+        assert!(!is_password_prompt(&prompts, "Password changed"));
+    }
+
+    #[test]
+    fn sources() {
+        let sources = vec![
+            PasswordSource {
+                prompt: None,
+                domains: vec!["SSH:prod".to_string()],
+                backend: PasswordBackend::Pass {
+                    name: "prod".to_string(),
+                },
+                submit: true,
+            },
+            PasswordSource {
+                prompt: Some(r"\[sudo\] password for (?P<user>\S+):".to_string()),
+                domains: vec![],
+                backend: PasswordBackend::Keychain {
+                    service: "sudo".to_string(),
+                    account: "$user".to_string(),
+                },
+                submit: false,
+            },
+        ];
+
+        // The prompt selects the source, but its text is not
+        // substituted into the backend
+        assert_eq!(
+            select_source(&sources, "[sudo] password for wez: ", Some("local")),
+            Some((
+                PasswordBackend::Keychain {
+                    service: "sudo".to_string(),
+                    account: "$user".to_string(),
+                },
+                false
+            ))
+        );
+        assert_eq!(
+            select_source(&sources, "Password: ", Some("SSH:prod")),
+            Some((
+                PasswordBackend::Pass {
+                    name: "prod".to_string()
+                },
+                true
+            ))
+        );
+        assert_eq!(select_source(&sources, "Password: ", Some("local")), None);
+    }
+}
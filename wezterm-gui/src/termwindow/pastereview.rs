@@ -0,0 +1,401 @@
+use crate::termwindow::box_model::*;
+use crate::termwindow::modal::Modal;
+use crate::termwindow::render::{
+    BOTTOM_LEFT_ROUNDED_CORNER, BOTTOM_RIGHT_ROUNDED_CORNER, TOP_LEFT_ROUNDED_CORNER,
+    TOP_RIGHT_ROUNDED_CORNER,
+};
+use crate::termwindow::DimensionContext;
+use crate::utilsprites::RenderMetrics;
+use crate::TermWindow;
+use config::keyassignment::KeyAssignment;
+use config::Dimension;
+use mux::pane::PaneId;
+use std::cell::{Ref, RefCell};
+use wezterm_term::{KeyCode, KeyModifiers, MouseEvent};
+use window::color::LinearRgba;
+
+/// Returns true if the text is multi-line or contains control
+/// characters, either of which may cause the receiving program
+/// to act on the text before the user has had a chance to review it
+pub fn needs_review(text: &str) -> bool {
+    text.chars()
+        .any(|c| c == '\n' || c == '\r' || (c.is_control() && c != '\t'))
+}
+
+/// Removes any trailing newlines from the text
+fn strip_trailing_newline(text: &str) -> String {
+    text.trim_end_matches(|c| c == '\n' || c == '\r')
+        .to_string()
+}
+
+/// Converts CRLF and lone CR line endings to LF
+fn convert_crlf(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Removes the leading whitespace that is common to all of
+/// the non-blank lines of the text
+fn dedent(text: &str) -> String {
+    let indent = text
+        .split('\n')
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches(|c| c == ' ' || c == '\t').len())
+        .min()
+        .unwrap_or(0);
+
+    text.split('\n')
+        .map(|line| {
+            if line.trim().is_empty() {
+                line.trim_start_matches(|c| c == ' ' || c == '\t')
+            } else {
+                &line[indent..]
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Makes control characters visible by replacing them with the
+/// corresponding symbol from the Control Pictures unicode block
+fn visible_controls(line: &str) -> String {
+    line.chars()
+        .map(|c| match c {
+            '\t' => "    ".to_string(),
+            '\u{7f}' => '\u{2421}'.to_string(),
+            c if (c as u32) < 0x20 => char::from_u32(0x2400 + c as u32).unwrap().to_string(),
+            c if c.is_control() => format!("<U+{:X}>", c as u32),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+pub struct PasteReview {
+    pane_id: PaneId,
+    element: RefCell<Option<Vec<ComputedElement>>>,
+    text: RefCell<String>,
+    editing: RefCell<bool>,
+}
+
+impl PasteReview {
+    pub fn new(_term_window: &mut TermWindow, pane_id: PaneId, text: String) -> Self {
+        Self {
+            pane_id,
+            element: RefCell::new(None),
+            text: RefCell::new(text),
+            editing: RefCell::new(false),
+        }
+    }
+
+    fn transform(&self, func: fn(&str) -> String) {
+        let mut text = self.text.borrow_mut();
+        *text = func(&text);
+    }
+
+    fn compute(
+        &self,
+        term_window: &mut TermWindow,
+        max_rows_on_screen: usize,
+    ) -> anyhow::Result<Vec<ComputedElement>> {
+        let font = term_window
+            .fonts
+            .char_select_font()
+            .expect("to resolve char selection font");
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+
+        let top_bar_height = if term_window.show_tab_bar && !term_window.config.tab_bar_at_bottom {
+            term_window.tab_bar_pixel_height().unwrap()
+        } else {
+            0.
+        };
+        let (padding_left, padding_top) = term_window.padding_left_top();
+        let border = term_window.get_os_border();
+        let top_pixel_y = top_bar_height + padding_top + border.top.get() as f32;
+
+        let text = self.text.borrow();
+        let editing = *self.editing.borrow();
+        let lines: Vec<&str> = text.split('\n').collect();
+
+        let header = if editing {
+            "Editing paste: type to append, Backspace to delete, Esc to finish editing".to_string()
+        } else {
+            format!(
+                "Review paste of {} line{}: Enter=paste e=edit s=strip trailing newline \
+                 c=convert CRLF d=dedent Esc=cancel",
+                lines.len(),
+                if lines.len() == 1 { "" } else { "s" }
+            )
+        };
+
+        let mut elements = vec![Element::new(&font, ElementContent::Text(header))
+            .colors(ElementColors {
+                border: BorderColor::default(),
+                bg: LinearRgba::TRANSPARENT.into(),
+                text: term_window.config.pane_select_fg_color.to_linear().into(),
+            })
+            .display(DisplayType::Block)];
+
+        // Show the tail of the text while editing, so that the
+        // edit point is visible
+        let skip = if editing {
+            lines.len().saturating_sub(max_rows_on_screen)
+        } else {
+            0
+        };
+        let num_shown = lines.len().saturating_sub(skip).min(max_rows_on_screen);
+        for (idx, line) in lines.iter().enumerate().skip(skip).take(num_shown) {
+            let mut line = visible_controls(line);
+            if idx + 1 < lines.len() {
+                line.push('⏎');
+            } else if editing {
+                line.push('_');
+            }
+            elements.push(
+                Element::new(&font, ElementContent::Text(line))
+                    .colors(ElementColors {
+                        border: BorderColor::default(),
+                        bg: LinearRgba::TRANSPARENT.into(),
+                        text: term_window.config.pane_select_fg_color.to_linear().into(),
+                    })
+                    .padding(BoxDimension {
+                        left: Dimension::Cells(0.25),
+                        right: Dimension::Cells(0.25),
+                        top: Dimension::Cells(0.),
+                        bottom: Dimension::Cells(0.),
+                    })
+                    .display(DisplayType::Block),
+            );
+        }
+        if skip + num_shown < lines.len() {
+            elements.push(
+                Element::new(
+                    &font,
+                    ElementContent::Text(format!(
+                        "... {} more lines",
+                        lines.len() - skip - num_shown
+                    )),
+                )
+                .colors(ElementColors {
+                    border: BorderColor::default(),
+                    bg: LinearRgba::TRANSPARENT.into(),
+                    text: term_window.config.pane_select_fg_color.to_linear().into(),
+                })
+                .display(DisplayType::Block),
+            );
+        }
+
+        let element = Element::new(&font, ElementContent::Children(elements))
+            .colors(ElementColors {
+                border: BorderColor::new(
+                    term_window.config.pane_select_bg_color.to_linear().into(),
+                ),
+                bg: term_window.config.pane_select_bg_color.to_linear().into(),
+                text: term_window.config.pane_select_fg_color.to_linear().into(),
+            })
+            .margin(BoxDimension {
+                left: Dimension::Cells(1.25),
+                right: Dimension::Cells(1.25),
+                top: Dimension::Cells(1.25),
+                bottom: Dimension::Cells(1.25),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.25),
+                right: Dimension::Cells(0.25),
+                top: Dimension::Cells(0.25),
+                bottom: Dimension::Cells(0.25),
+            })
+            .border(BoxDimension::new(Dimension::Pixels(1.)))
+            .border_corners(Some(Corners {
+                top_left: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: TOP_LEFT_ROUNDED_CORNER,
+                },
+                top_right: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: TOP_RIGHT_ROUNDED_CORNER,
+                },
+                bottom_left: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: BOTTOM_LEFT_ROUNDED_CORNER,
+                },
+                bottom_right: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: BOTTOM_RIGHT_ROUNDED_CORNER,
+                },
+            }));
+
+        let dimensions = term_window.dimensions;
+        let size = term_window.terminal_size;
+
+        let computed = term_window.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_height as f32,
+                    pixel_cell: metrics.cell_size.height as f32,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_width as f32,
+                    pixel_cell: metrics.cell_size.width as f32,
+                },
+                bounds: euclid::rect(
+                    padding_left,
+                    top_pixel_y,
+                    size.cols as f32 * term_window.render_metrics.cell_size.width as f32,
+                    size.rows as f32 * term_window.render_metrics.cell_size.height as f32,
+                ),
+                metrics: &metrics,
+                gl_state: term_window.render_state.as_ref().unwrap(),
+                zindex: 100,
+            },
+            &element,
+        )?;
+
+        Ok(vec![computed])
+    }
+
+    fn key_down_editing(&self, key: KeyCode, mods: KeyModifiers) -> bool {
+        let mut text = self.text.borrow_mut();
+        match (key, mods) {
+            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+                *self.editing.borrow_mut() = false;
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                text.push(c);
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                text.push('\n');
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                text.pop();
+            }
+            (KeyCode::Char('u'), KeyModifiers::CTRL) => {
+                // Delete the last line
+                let len = text
+                    .trim_end_matches('\n')
+                    .rfind('\n')
+                    .map_or(0, |idx| idx + 1);
+                text.truncate(len);
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl Modal for PasteReview {
+    fn perform_assignment(
+        &self,
+        _assignment: &KeyAssignment,
+        _term_window: &mut TermWindow,
+    ) -> bool {
+        false
+    }
+
+    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn key_down(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<()> {
+        if *self.editing.borrow() {
+            if self.key_down_editing(key, mods) {
+                term_window.invalidate_modal();
+            }
+            return Ok(());
+        }
+
+        match (key, mods) {
+            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+                term_window.cancel_modal();
+                return Ok(());
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                term_window.cancel_modal();
+                let text = self.text.borrow().clone();
+                let mux = mux::Mux::get().expect("paste review to run on main thread");
+                if let Some(pane) = mux.get_pane(self.pane_id) {
+                    pane.trickle_paste(text)?;
+                    term_window.maybe_scroll_to_bottom_for_input(&pane);
+                }
+                return Ok(());
+            }
+            (KeyCode::Char('e'), KeyModifiers::NONE) => {
+                *self.editing.borrow_mut() = true;
+            }
+            (KeyCode::Char('s'), KeyModifiers::NONE) => {
+                self.transform(strip_trailing_newline);
+            }
+            (KeyCode::Char('c'), KeyModifiers::NONE) => {
+                self.transform(convert_crlf);
+            }
+            (KeyCode::Char('d'), KeyModifiers::NONE) => {
+                self.transform(dedent);
+            }
+            _ => return Ok(()),
+        }
+        term_window.invalidate_modal();
+        Ok(())
+    }
+
+    fn computed_element(
+        &self,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<Ref<[ComputedElement]>> {
+        let font = term_window
+            .fonts
+            .char_select_font()
+            .expect("to resolve char selection font");
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+
+        let max_rows_on_screen = ((term_window.dimensions.pixel_height * 8 / 10)
+            / metrics.cell_size.height as usize)
+            .saturating_sub(3)
+            .max(1);
+
+        if self.element.borrow().is_none() {
+            let element = self.compute(term_window, max_rows_on_screen)?;
+            self.element.borrow_mut().replace(element);
+        }
+        Ok(Ref::map(self.element.borrow(), |v| {
+            v.as_ref().unwrap().as_slice()
+        }))
+    }
+
+    fn reconfigure(&self, _term_window: &mut TermWindow) {
+        self.element.borrow_mut().take();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn review() {
+        assert!(!needs_review("ls -l\tfoo"));
+        assert!(needs_review("ls -l\n"));
+        assert!(needs_review("ls\r"));
+        assert!(needs_review("\u{1b}[201~rm -rf"));
+    }
+
+    #[test]
+    fn transforms() {
+        assert_eq!(strip_trailing_newline("echo\r\n\n"), "echo");
+        assert_eq!(convert_crlf("a\r\nb\rc\n"), "a\nb\nc\n");
+        assert_eq!(
+            dedent("    if x:\n        y()\n\n    z()\n"),
+            "if x:\n    y()\n\nz()\n"
+        );
+        assert_eq!(dedent("a\n  b"), "a\n  b");
+        assert_eq!(visible_controls("\u{1b}[0m\t."), "\u{241b}[0m    .");
+    }
+}
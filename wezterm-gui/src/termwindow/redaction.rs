@@ -0,0 +1,149 @@
+//! Implements `redaction_rules`, which mask sensitive text such as
+//! access tokens in the display of a pane and in text copied from it.
+//!
+//! Redaction is a transform that is applied to lines as they are
+//! rendered or copied; the content of the pane is untouched, so the
+//! text can be revealed again via `ToggleRedactionReveal`.  Rules are
+//! matched against each physical line, so text that wraps onto the
+//! next line is only masked if the part on each line matches.
+use config::RedactionRule;
+use mux::pane::PaneId;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::Mutex;
+use termwiz::cell::Cell;
+use termwiz::surface::Line;
+
+struct CompiledRules {
+    generation: usize,
+    rules: Vec<(Regex, char)>,
+}
+
+lazy_static::lazy_static! {
+    static ref RULES: Mutex<Option<CompiledRules>> = Mutex::new(None);
+    /// The panes whose redacted text is currently revealed
+    static ref REVEALED: Mutex<HashSet<PaneId>> = Mutex::new(HashSet::new());
+}
+
+fn compile_rules(rules: &[RedactionRule]) -> Vec<(Regex, char)> {
+    rules
+        .iter()
+        .filter_map(|rule| match Regex::new(&rule.regex) {
+            Ok(regex) => Some((regex, rule.mask)),
+            Err(err) => {
+                log::error!("redaction_rules: invalid regex {}: {:#}", rule.regex, err);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Toggles whether the redacted text in the pane is revealed.
+/// Returns true if it is now revealed.
+pub fn toggle_reveal(pane_id: PaneId) -> bool {
+    let mut revealed = REVEALED.lock().unwrap();
+    if revealed.remove(&pane_id) {
+        false
+    } else {
+        revealed.insert(pane_id);
+        true
+    }
+}
+
+pub fn is_revealed(pane_id: PaneId) -> bool {
+    REVEALED.lock().unwrap().contains(&pane_id)
+}
+
+/// Returns the line as it should be shown or copied from the pane:
+/// with the text that matches `redaction_rules` masked, unless the
+/// pane has been revealed
+pub fn redact(pane_id: PaneId, line: &Line) -> Cow<Line> {
+    let config = config::configuration();
+    if config.redaction_rules.is_empty() || is_revealed(pane_id) {
+        return Cow::Borrowed(line);
+    }
+
+    let mut rules = RULES.lock().unwrap();
+    let generation = config.generation();
+    if rules.as_ref().map(|r| r.generation) != Some(generation) {
+        rules.replace(CompiledRules {
+            generation,
+            rules: compile_rules(&config.redaction_rules),
+        });
+    }
+    match redact_line(&rules.as_ref().unwrap().rules, line) {
+        Some(line) => Cow::Owned(line),
+        None => Cow::Borrowed(line),
+    }
+}
+
+/// Returns a copy of the line with the cells that hold matches of
+/// the rules replaced by the mask, or None if nothing matched
+fn redact_line(rules: &[(Regex, char)], line: &Line) -> Option<Line> {
+    let mut text = String::new();
+    // The byte offset into `text` of each visible cell
+    let mut cells = vec![];
+    for cell in line.visible_cells() {
+        cells.push((
+            text.len(),
+            cell.cell_index(),
+            cell.width(),
+            cell.attrs().clone(),
+        ));
+        text.push_str(cell.str());
+    }
+
+    let mut matches: Vec<(Range<usize>, char)> = vec![];
+    for (regex, mask) in rules {
+        for m in regex.find_iter(&text) {
+            if !m.range().is_empty() {
+                matches.push((m.range(), *mask));
+            }
+        }
+    }
+    if matches.is_empty() {
+        return None;
+    }
+
+    let seqno = line.current_seqno();
+    let mut redacted = line.clone();
+    for (offset, idx, width, attrs) in cells {
+        if let Some((_, mask)) = matches.iter().find(|(range, _)| range.contains(&offset)) {
+            // Mask each column of wide characters so that the
+            // layout of the line is unchanged
+            for col in idx..idx + width {
+                redacted.set_cell(col, Cell::new(*mask, attrs.clone()), seqno);
+            }
+        }
+    }
+    Some(redacted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::cell::CellAttributes;
+    use termwiz::surface::SEQ_ZERO;
+
+    #[test]
+    fn masks_matches() {
+        let rules = vec![(Regex::new(r"AKIA[0-9A-Z]{4}").unwrap(), '*')];
+        let line = Line::from_text(
+            "key=AKIAABCD ok",
+            &CellAttributes::default(),
+            SEQ_ZERO,
+            None,
+        );
+        let redacted = redact_line(&rules, &line).unwrap();
+        assert_eq!(redacted.as_str(), "key=******** ok");
+        assert!(redact_line(&rules, &redacted).is_none());
+
+        let wide = Line::from_text("AKIA1234 日本", &CellAttributes::default(), SEQ_ZERO, None);
+        let rules = vec![(Regex::new("日本").unwrap(), '#')];
+        let redacted = redact_line(&rules, &wide).unwrap();
+        assert_eq!(redacted.as_str(), "AKIA1234 ####");
+        assert_eq!(redacted.len(), wide.len());
+    }
+}
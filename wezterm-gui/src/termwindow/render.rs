@@ -21,11 +21,14 @@ use ::window::glium::uniforms::{
     MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction,
 };
 use ::window::glium::{BlendingFunction, LinearBlendingFactor, Surface};
-use ::window::{glium, DeadKeyStatus, PointF, RectF, SizeF, ULength, WindowOps};
+use ::window::{
+    glium, DeadKeyStatus, PointF, RectF, SizeF, ULength, WindowDecorations, WindowOps, WindowState,
+};
 use anyhow::anyhow;
 use config::{
-    ConfigHandle, Dimension, DimensionContext, FreeTypeLoadTarget, HsbTransform, TabBarColors,
-    TextStyle, VisualBellTarget,
+    ConfigHandle, Dimension, DimensionContext, FreeTypeLoadTarget, HsbTransform,
+    IntegratedTitleButton, IntegratedTitleButtonAlignment, TabBarColors, TextStyle,
+    VisualBellTarget,
 };
 use euclid::num::Zero;
 use mux::pane::{Pane, PaneId, WithPaneLines};
@@ -33,6 +36,7 @@ use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::{PositionedPane, PositionedSplit, SplitDirection};
 use ordered_float::NotNan;
 use smol::Timer;
+use std::collections::HashMap;
 use std::ops::Range;
 use std::rc::Rc;
 use std::sync::Arc;
@@ -51,6 +55,9 @@ use wezterm_term::{CellAttributes, Line, StableRowIndex};
 use window::bitmaps::Texture2d;
 use window::color::LinearRgba;
 
+/// Bounds the number of color pairs remembered by `ensure_min_contrast`
+const MAX_CONTRAST_CACHE_ENTRIES: usize = 1024;
+
 pub const TOP_LEFT_ROUNDED_CORNER: &[Poly] = &[Poly {
     path: &[
         PolyCommand::MoveTo(BlockCoord::One, BlockCoord::One),
@@ -343,6 +350,60 @@ impl super::TermWindow {
 
         frame.clear_color(0., 0., 0., 0.);
 
+        self.paint_passes();
+        log::debug!("paint_impl before call_draw elapsed={:?}", start.elapsed());
+
+        self.call_draw(frame).ok();
+        self.last_frame_duration = start.elapsed();
+        crate::startup::frame_painted();
+        for pos in self.get_panes_to_render() {
+            mux::panestats::record_render(pos.pane.pane_id(), self.last_frame_duration);
+        }
+        log::debug!(
+            "paint_impl elapsed={:?}, fps={}",
+            self.last_frame_duration,
+            self.fps
+        );
+        metrics::histogram!("gui.paint.opengl", self.last_frame_duration);
+        metrics::histogram!("gui.paint.opengl.rate", 1.);
+        self.update_title_post_status();
+
+        if self.animation_frame_requested {
+            self.animation_frame_requested = false;
+            self.emit_window_event("animation-frame", None);
+        }
+
+        // If self.has_animation is some, then the last render detected
+        // image attachments with multiple frames, so we also need to
+        // invalidate the viewport when the next frame is due
+        if self.focused.is_some() {
+            if let Some(next_due) = *self.has_animation.borrow() {
+                let prior = self.scheduled_animation.borrow_mut().take();
+                match prior {
+                    Some(prior) if prior <= next_due => {
+                        // Already due before that time
+                    }
+                    _ => {
+                        self.scheduled_animation.borrow_mut().replace(next_due);
+                        let window = self.window.clone().take().unwrap();
+                        promise::spawn::spawn(async move {
+                            Timer::at(next_due).await;
+                            let win = window.clone();
+                            window.notify(TermWindowNotif::Apply(Box::new(move |tw| {
+                                tw.scheduled_animation.borrow_mut().take();
+                                win.invalidate();
+                            })));
+                        })
+                        .detach();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Populates the vertex buffers for the current state of the window,
+    /// retrying when the texture atlas or quad allocation needs to grow
+    pub fn paint_passes(&mut self) {
         'pass: for pass in 0.. {
             match self.paint_opengl_pass() {
                 Ok(_) => match self.render_state.as_mut().unwrap().allocated_more_quads() {
@@ -406,45 +467,6 @@ impl super::TermWindow {
                 }
             }
         }
-        log::debug!("paint_impl before call_draw elapsed={:?}", start.elapsed());
-
-        self.call_draw(frame).ok();
-        self.last_frame_duration = start.elapsed();
-        log::debug!(
-            "paint_impl elapsed={:?}, fps={}",
-            self.last_frame_duration,
-            self.fps
-        );
-        metrics::histogram!("gui.paint.opengl", self.last_frame_duration);
-        metrics::histogram!("gui.paint.opengl.rate", 1.);
-        self.update_title_post_status();
-
-        // If self.has_animation is some, then the last render detected
-        // image attachments with multiple frames, so we also need to
-        // invalidate the viewport when the next frame is due
-        if self.focused.is_some() {
-            if let Some(next_due) = *self.has_animation.borrow() {
-                let prior = self.scheduled_animation.borrow_mut().take();
-                match prior {
-                    Some(prior) if prior <= next_due => {
-                        // Already due before that time
-                    }
-                    _ => {
-                        self.scheduled_animation.borrow_mut().replace(next_due);
-                        let window = self.window.clone().take().unwrap();
-                        promise::spawn::spawn(async move {
-                            Timer::at(next_due).await;
-                            let win = window.clone();
-                            window.notify(TermWindowNotif::Apply(Box::new(move |tw| {
-                                tw.scheduled_animation.borrow_mut().take();
-                                win.invalidate();
-                            })));
-                        })
-                        .detach();
-                    }
-                }
-            }
-        }
     }
 
     pub fn update_next_frame_time(&self, next_due: Option<Instant>) {
@@ -572,6 +594,52 @@ impl super::TermWindow {
         self.fancy_tab_bar.take();
     }
 
+    /// Returns the elements for the integrated_title_buttons
+    fn build_integrated_title_buttons(&self, font: &Rc<LoadedFont>) -> Vec<Element> {
+        let frame = &self.config.window_frame;
+        let glyphs = &self.config.integrated_title_button_glyphs;
+        let colors = ElementColors {
+            border: BorderColor::default(),
+            bg: rgbcolor_to_window_color(frame.button_bg).into(),
+            text: rgbcolor_to_window_color(frame.button_fg).into(),
+        };
+        let hover_colors = ElementColors {
+            border: BorderColor::default(),
+            bg: rgbcolor_to_window_color(frame.button_hover_bg).into(),
+            text: rgbcolor_to_window_color(frame.button_hover_fg).into(),
+        };
+
+        self.config
+            .integrated_title_buttons
+            .iter()
+            .enumerate()
+            .map(|(idx, button)| {
+                let glyph = match button {
+                    IntegratedTitleButton::Hide => &glyphs.hide,
+                    IntegratedTitleButton::Maximize
+                        if self.window_state.contains(WindowState::MAXIMIZED) =>
+                    {
+                        &glyphs.restore
+                    }
+                    IntegratedTitleButton::Maximize => &glyphs.maximize,
+                    IntegratedTitleButton::Close => &glyphs.close,
+                    IntegratedTitleButton::Custom(button) => &button.glyph,
+                };
+                Element::new(font, ElementContent::Text(glyph.to_string()))
+                    .item_type(UIItemType::TitleButton(idx))
+                    .line_height(Some(1.75))
+                    .padding(BoxDimension {
+                        left: Dimension::Cells(0.5),
+                        right: Dimension::Cells(0.5),
+                        top: Dimension::Cells(0.),
+                        bottom: Dimension::Cells(0.),
+                    })
+                    .colors(colors.clone())
+                    .hover_colors(Some(hover_colors.clone()))
+            })
+            .collect()
+    }
+
     pub fn build_fancy_tab_bar(&self, palette: &ColorPalette) -> anyhow::Result<ComputedElement> {
         let tab_bar_height = self.tab_bar_pixel_height()?;
         let font = self.fonts.title_font()?;
@@ -892,6 +960,21 @@ impl super::TermWindow {
 
         let mut children = vec![];
 
+        if self
+            .config
+            .window_decorations
+            .contains(WindowDecorations::INTEGRATED_BUTTONS)
+        {
+            let buttons = self.build_integrated_title_buttons(&font);
+            match self.config.integrated_title_button_alignment {
+                IntegratedTitleButtonAlignment::Left => children.push(
+                    Element::new(&font, ElementContent::Children(buttons))
+                        .colors(bar_colors.clone()),
+                ),
+                IntegratedTitleButtonAlignment::Right => right_eles.extend(buttons),
+            }
+        }
+
         if !left_status.is_empty() {
             children.push(
                 Element::new(&font, ElementContent::Children(left_status))
@@ -1545,12 +1628,14 @@ impl super::TermWindow {
 
             let min_height = self.min_scroll_bar_height();
 
+            let max_thumb_height = self
+                .dimensions
+                .pixel_height
+                .saturating_sub(thumb_y_offset + border.bottom.get() + bottom_bar_height as usize);
             let info = ScrollHit::thumb(
                 &*pos.pane,
                 current_viewport,
-                self.dimensions.pixel_height.saturating_sub(
-                    thumb_y_offset + border.bottom.get() + bottom_bar_height as usize,
-                ),
+                max_thumb_height,
                 min_height as usize,
             );
             let abs_thumb_top = thumb_y_offset + info.top;
@@ -1600,6 +1685,27 @@ impl super::TermWindow {
                 ),
                 color,
             )?;
+
+            if self.config.show_named_marks_in_scroll_bar {
+                let mark_color = self.config.pane_select_fg_color.to_linear();
+                let mark_height = (self.render_metrics.cell_size.height as f32 / 8.)
+                    .max(1.)
+                    .round();
+                for (_name, row) in self.named_marks(&pos.pane) {
+                    let offset = ScrollHit::row_offset(&*pos.pane, row, max_thumb_height);
+                    self.filled_rectangle(
+                        layers,
+                        2,
+                        euclid::rect(
+                            thumb_x as f32,
+                            (thumb_y_offset + offset) as f32,
+                            padding,
+                            mark_height,
+                        ),
+                        mark_color,
+                    )?;
+                }
+            }
         }
 
         let (selrange, rectangular) = {
@@ -1648,11 +1754,13 @@ impl super::TermWindow {
                 window_is_transparent: bool,
                 layers: &'a mut TripleLayerQuadAllocator<'b>,
                 error: Option<anyhow::Error>,
+                diff_lines: HashMap<StableRowIndex, Line>,
             }
 
             let left_pixel_x = padding_left
                 + border.left.get() as f32
                 + (pos.left as f32 * self.render_metrics.cell_size.width as f32);
+            let diff_lines = self.diff_mode_lines(&pos.pane, stable_range.clone());
 
             let mut render = LineRender {
                 term_window: self,
@@ -1678,6 +1786,7 @@ impl super::TermWindow {
                 window_is_transparent,
                 layers,
                 error: None,
+                diff_lines,
             };
 
             impl<'a, 'b> LineRender<'a, 'b> {
@@ -1688,6 +1797,12 @@ impl super::TermWindow {
                     line: &&mut Line,
                 ) -> anyhow::Result<()> {
                     let stable_row = stable_top + line_idx as StableRowIndex;
+                    let line = match self.diff_lines.get(&stable_row) {
+                        Some(line) => line,
+                        None => &**line,
+                    };
+                    let line = crate::termwindow::redaction::redact(self.pane_id, line);
+                    let line = &*line;
                     let selrange = self
                         .selrange
                         .map_or(0..0, |sel| sel.cols_for_row(stable_row, self.rectangular));
@@ -1870,6 +1985,10 @@ impl super::TermWindow {
             }
         }
 
+        self.paint_new_output_divider(pos, layers)?;
+        self.paint_collaborator_cursors(pos, layers)?;
+        self.paint_compare_differences(pos, layers)?;
+
         /*
         if let Some(zone) = zone {
             // TODO: render a thingy to jump to prior prompt
@@ -1881,7 +2000,7 @@ impl super::TermWindow {
         Ok(())
     }
 
-    fn call_draw(&mut self, frame: &mut glium::Frame) -> anyhow::Result<()> {
+    pub fn call_draw<S: Surface>(&mut self, frame: &mut S) -> anyhow::Result<()> {
         let gl_state = self.render_state.as_ref().unwrap();
         let tex = gl_state.glyph_cache.borrow().atlas.texture();
         let projection = euclid::Transform3D::<f32, f32, f32>::ortho(
@@ -1960,6 +2079,12 @@ impl super::TermWindow {
         let blink: ColorEaseUniform = (*self.blink_state.borrow()).into();
         let rapid_blink: ColorEaseUniform = (*self.rapid_blink_state.borrow()).into();
 
+        let color_vision = crate::colorvision::color_vision_matrix(self.config.color_vision_filter);
+        let has_color_vision = color_vision.is_some();
+        let color_vision = crate::colorvision::to_column_major(
+            &color_vision.unwrap_or(crate::colorvision::IDENTITY),
+        );
+
         for layer in gl_state.layers.borrow().iter() {
             for idx in 0..3 {
                 let vb = &layer.vb.borrow()[idx];
@@ -1979,6 +2104,8 @@ impl super::TermWindow {
                     uniforms.add_struct("cursor_blink", &cursor_blink);
                     uniforms.add_struct("blink", &blink);
                     uniforms.add_struct("rapid_blink", &rapid_blink);
+                    uniforms.add("color_vision", &color_vision);
+                    uniforms.add("has_color_vision", &has_color_vision);
 
                     frame.draw(
                         vertices.slice(0..vertex_count).unwrap(),
@@ -2202,7 +2329,13 @@ impl super::TermWindow {
 
         self.paint_window_borders(&mut layers)?;
         drop(layers);
+        self.paint_new_output_indicators()?;
+        self.paint_input_turn_indicators()?;
         self.paint_modal()?;
+        self.paint_hyperlink_hover()?;
+        self.paint_tab_preview()?;
+        self.paint_debug_hud()?;
+        self.schedule_tab_preview_capture();
 
         Ok(())
     }
@@ -2280,6 +2413,8 @@ impl super::TermWindow {
                         bg_default = false;
                     }
 
+                    fg = self.ensure_min_contrast(params.config, fg, bg);
+
                     // Check for blink, and if this is the "not-visible"
                     // part of blinking then set fg = bg.  This is a cheap
                     // means of getting it done without impacting other
@@ -2463,8 +2598,7 @@ impl super::TermWindow {
 
         let mut composition_width = 0;
 
-        let (_bidi_enabled, bidi_direction) = params.line.bidi_info();
-        let direction = bidi_direction.direction();
+        let direction = params.line.resolved_direction();
 
         // Do we need to shape immediately, or can we use the pre-shaped data?
         if let Some(composing) = composing {
@@ -3335,6 +3469,7 @@ impl super::TermWindow {
             // Normally, render the cell as configured (or if the window is unfocused)
             _ => (params.fg_color, params.bg_color, params.cursor_border_color),
         };
+        let fg_color = self.ensure_min_contrast(params.config, fg_color, bg_color);
 
         let blinking = params.cursor.is_some()
             && params.is_active_pane
@@ -3361,7 +3496,8 @@ impl super::TermWindow {
                 cursor_shape,
                 CursorShape::BlinkingBlock | CursorShape::SteadyBlock,
             ) {
-                fg_color_alt = params.fg_color;
+                fg_color_alt =
+                    self.ensure_min_contrast(params.config, params.fg_color, params.bg_color);
                 fg_color_mix = intensity;
             }
 
@@ -3394,6 +3530,46 @@ impl super::TermWindow {
         }
     }
 
+    /// Adjusts the foreground color so that it meets the minimum
+    /// contrast ratio against the background that is required by
+    /// `minimum_contrast_ratio` and `high_contrast_mode`.
+    /// The adjusted colors are cached, as the same handful of
+    /// color pairs tend to be resolved over and over.
+    pub(crate) fn ensure_min_contrast(
+        &self,
+        config: &ConfigHandle,
+        fg: LinearRgba,
+        bg: LinearRgba,
+    ) -> LinearRgba {
+        let mut min_ratio = config.minimum_contrast_ratio;
+        if config.high_contrast_mode {
+            min_ratio = min_ratio.max(config.high_contrast_min_ratio);
+        }
+        if min_ratio <= 1.0 {
+            return fg;
+        }
+
+        let key = (
+            [
+                fg.0.to_bits(),
+                fg.1.to_bits(),
+                fg.2.to_bits(),
+                fg.3.to_bits(),
+            ],
+            [bg.0.to_bits(), bg.1.to_bits(), bg.2.to_bits()],
+        );
+        let mut cache = self.contrast_cache.borrow_mut();
+        if let Some(adjusted) = cache.get(&key) {
+            return *adjusted;
+        }
+        if cache.len() >= MAX_CONTRAST_CACHE_ENTRIES {
+            cache.clear();
+        }
+        let adjusted = fg.ensure_contrast_ratio(bg, min_ratio);
+        cache.insert(key, adjusted);
+        adjusted
+    }
+
     fn glyph_infos_to_glyphs(
         &self,
         style: &TextStyle,
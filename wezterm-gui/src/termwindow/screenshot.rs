@@ -0,0 +1,97 @@
+use crate::TermWindow;
+use ::window::glium::texture::{RawImage2d, SrgbTexture2d};
+use ::window::glium::Surface;
+use anyhow::{anyhow, Context};
+use mux::pane::PaneId;
+use std::io::Cursor;
+
+impl TermWindow {
+    /// Renders the current contents of the window into an offscreen
+    /// texture and returns it as a PNG encoded image.
+    /// If pane_id is specified, the image is cropped to that pane,
+    /// which must be visible in the active tab of this window.
+    pub fn capture_image(&mut self, pane_id: Option<PaneId>) -> anyhow::Result<Vec<u8>> {
+        let image = self.capture_rgba(pane_id)?;
+
+        let mut png = vec![];
+        image
+            .write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)
+            .context("encoding png")?;
+
+        Ok(png)
+    }
+
+    /// Renders the current contents of the window into an offscreen
+    /// texture and returns its pixels, cropped as for capture_image
+    pub(crate) fn capture_rgba(
+        &mut self,
+        pane_id: Option<PaneId>,
+    ) -> anyhow::Result<image::RgbaImage> {
+        let context = match self.render_state.as_ref() {
+            Some(rs) => rs.context.clone(),
+            None => anyhow::bail!("window has no render state"),
+        };
+
+        let crop = match pane_id {
+            Some(pane_id) => Some(self.pane_pixel_rect(pane_id)?),
+            None => None,
+        };
+
+        let width = self.dimensions.pixel_width as u32;
+        let height = self.dimensions.pixel_height as u32;
+        let texture =
+            SrgbTexture2d::empty(&context, width, height).context("allocating capture texture")?;
+        {
+            let mut surface = texture.as_surface();
+            surface.clear_color(0., 0., 0., 0.);
+            self.paint_passes();
+            self.call_draw(&mut surface)?;
+        }
+
+        let raw: RawImage2d<u8> = texture.read();
+        let mut image = image::RgbaImage::from_raw(raw.width, raw.height, raw.data.into_owned())
+            .ok_or_else(|| anyhow!("captured image has an unexpected size"))?;
+        // OpenGL stores the rows from the bottom up
+        image::imageops::flip_vertical_in_place(&mut image);
+
+        if let Some((x, y, w, h)) = crop {
+            image = image::imageops::crop_imm(&image, x, y, w, h).to_image();
+        }
+
+        // The offscreen render consumed this frame's vertex buffers,
+        // so arrange to repaint the window itself
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+
+        Ok(image)
+    }
+
+    /// Returns the x, y, width and height of the cells of the pane,
+    /// in pixels relative to the top left of the window
+    fn pane_pixel_rect(&mut self, pane_id: PaneId) -> anyhow::Result<(u32, u32, u32, u32)> {
+        let pos = self
+            .get_panes_to_render()
+            .into_iter()
+            .find(|pos| pos.pane.pane_id() == pane_id)
+            .ok_or_else(|| anyhow!("pane {} is not visible in its window", pane_id))?;
+
+        let (padding_left, padding_top) = self.padding_left_top();
+        let top_bar_height = if self.show_tab_bar && !self.config.tab_bar_at_bottom {
+            self.tab_bar_pixel_height()?
+        } else {
+            0.
+        };
+        let border = self.get_os_border();
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+
+        let x = padding_left + border.left.get() as f32 + pos.left as f32 * cell_width;
+        let y =
+            top_bar_height + padding_top + border.top.get() as f32 + pos.top as f32 * cell_height;
+        let width = pos.width as f32 * cell_width;
+        let height = pos.height as f32 * cell_height;
+
+        Ok((x as u32, y as u32, width as u32, height as u32))
+    }
+}
@@ -1,8 +1,12 @@
+use super::redaction::redact;
+use crate::copytext::SelectedRow;
 use crate::selection::{Selection, SelectionCoordinate, SelectionMode, SelectionRange, SelectionX};
-use ::window::WindowOps;
+use ::window::{DragContent, WindowOps};
+use config::keyassignment::SelectionDragFormat;
 use mux::pane::{Pane, PaneId};
 use std::cell::RefMut;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use termwiz::surface::Line;
 use wezterm_term::StableRowIndex;
 
@@ -37,7 +41,7 @@ impl super::TermWindow {
                         let last_phys_idx = phys.len().saturating_sub(1);
                         let cols = sel.cols_for_row(this_row, rectangular);
                         let last_col_idx = cols.end.saturating_sub(1).min(last_phys_idx);
-                        let mut col_span = phys.columns_as_line(cols);
+                        let mut col_span = redact(pane.pane_id(), phys).columns_as_line(cols);
                         let seqno = col_span.current_seqno();
                         // Only trim trailing whitespace if we are the last line
                         // in a wrapped sequence
@@ -87,7 +91,7 @@ impl super::TermWindow {
                         let last_phys_idx = phys.len().saturating_sub(1);
                         let cols = sel.cols_for_row(this_row, rectangular);
                         let last_col_idx = cols.end.saturating_sub(1).min(last_phys_idx);
-                        let col_span = phys.columns_as_str(cols);
+                        let col_span = redact(pane.pane_id(), phys).columns_as_str(cols);
                         // Only trim trailing whitespace if we are the last line
                         // in a wrapped sequence
                         if idx == last_idx {
@@ -109,6 +113,75 @@ impl super::TermWindow {
         s
     }
 
+    /// Drags the selected text out of the window.  Returns true if
+    /// the drag was started.
+    pub fn start_selection_drag(
+        &mut self,
+        pane: &Rc<dyn Pane>,
+        format: SelectionDragFormat,
+    ) -> bool {
+        let text = self.selection_text(pane);
+        if text.is_empty() {
+            return false;
+        }
+        let window = match self.window.as_ref() {
+            Some(window) => window,
+            None => return false,
+        };
+        let content = match format {
+            SelectionDragFormat::Text => DragContent::Text(text),
+            SelectionDragFormat::File => match write_selection_file(&text) {
+                Ok(path) => DragContent::File(path),
+                Err(err) => {
+                    log::error!("writing the selection for dragging: {:#}", err);
+                    return false;
+                }
+            },
+        };
+        if !window.start_drag(content) {
+            log::warn!("Dragging the selection out of the window is not supported on this system");
+        }
+        true
+    }
+
+    /// Returns the selected rows, noting which of them were wrapped,
+    /// so that the text can be adjusted by `copytext::format_selection`
+    pub fn selection_rows(&self, pane: &Rc<dyn Pane>) -> Vec<SelectedRow> {
+        let mut result = vec![];
+        let rectangular = self.selection(pane.pane_id()).rectangular;
+        if let Some(sel) = self
+            .selection(pane.pane_id())
+            .range
+            .as_ref()
+            .map(|r| r.normalize())
+        {
+            let first_row = sel.rows().start;
+            let last_row = sel.rows().end;
+
+            for line in pane.get_logical_lines(sel.rows()) {
+                for (idx, phys) in line.physical_lines.iter().enumerate() {
+                    let this_row = line.first_row + idx as StableRowIndex;
+                    if this_row >= first_row && this_row < last_row {
+                        let last_phys_idx = phys.len().saturating_sub(1);
+                        let cols = sel.cols_for_row(this_row, rectangular);
+                        let last_col_idx = cols.end.saturating_sub(1).min(last_phys_idx);
+                        let wrapped = last_col_idx == last_phys_idx
+                            && phys
+                                .get_cell(last_col_idx)
+                                .map(|c| c.attrs().wrapped())
+                                .unwrap_or(false);
+                        result.push(SelectedRow {
+                            line: redact(pane.pane_id(), phys).columns_as_line(cols),
+                            wrapped,
+                        });
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
     pub fn clear_selection(&mut self, pane: &Rc<dyn Pane>) {
         let mut selection = self.selection(pane.pane_id());
         selection.clear();
@@ -281,3 +354,16 @@ impl super::TermWindow {
         self.window.as_ref().unwrap().invalidate();
     }
 }
+
+/// Writes the text into a new file in the temporary directory, from
+/// which the application that it is dropped onto can copy it
+fn write_selection_file(text: &str) -> anyhow::Result<std::path::PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "wezterm-selection-{}-{}.txt",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&path, text)?;
+    Ok(path)
+}
@@ -0,0 +1,415 @@
+//! Runtime control of serial port connections.
+//! `wezterm serial` and the serial port selector create a `LocalDomain`
+//! whose pty system is a `SerialTty`; the control handle of that tty is
+//! recorded here against the domain id so that key assignments can
+//! adjust the port settings of the active pane.
+use crate::termwindow::box_model::*;
+use crate::termwindow::modal::Modal;
+use crate::termwindow::render::{
+    BOTTOM_LEFT_ROUNDED_CORNER, BOTTOM_RIGHT_ROUNDED_CORNER, TOP_LEFT_ROUNDED_CORNER,
+    TOP_RIGHT_ROUNDED_CORNER,
+};
+use crate::termwindow::DimensionContext;
+use crate::utilsprites::RenderMetrics;
+use crate::TermWindow;
+use config::keyassignment::{KeyAssignment, SerialFlowControl, SpawnTabDomain};
+use config::Dimension;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use mux::domain::{Domain, DomainId, LocalDomain};
+use mux::pane::{Pane, PaneId};
+use mux::Mux;
+use portable_pty::serial::{available_ports, SerialPortControl, SerialTty};
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use wezterm_term::{KeyCode, KeyModifiers, MouseEvent};
+use window::color::LinearRgba;
+
+lazy_static::lazy_static! {
+    static ref CONTROLS: Mutex<HashMap<DomainId, SerialPortControl>> = Mutex::new(HashMap::new());
+}
+
+/// Creates a local domain that connects to the serial port represented
+/// by `serial`, and records its control handle
+pub fn create_serial_domain(name: &str, serial: SerialTty) -> Arc<dyn Domain> {
+    let control = serial.control();
+    let domain: Arc<dyn Domain> = Arc::new(LocalDomain::with_pty_system(name, Box::new(serial)));
+    CONTROLS.lock().unwrap().insert(domain.domain_id(), control);
+    domain
+}
+
+pub fn flow_control_to_serial(flow_control: SerialFlowControl) -> serial::FlowControl {
+    match flow_control {
+        SerialFlowControl::None => serial::FlowControl::FlowNone,
+        SerialFlowControl::Software => serial::FlowControl::FlowSoftware,
+        SerialFlowControl::Hardware => serial::FlowControl::FlowHardware,
+    }
+}
+
+/// Applies one of the serial port key assignments to the port that
+/// is connected to `pane`
+pub fn perform_serial_assignment(pane: &Rc<dyn Pane>, assignment: &KeyAssignment) {
+    let control = match CONTROLS.lock().unwrap().get(&pane.domain_id()) {
+        Some(control) => control.clone(),
+        None => {
+            log::error!(
+                "{}: pane {} is not connected to a serial port",
+                assignment.name(),
+                pane.pane_id()
+            );
+            return;
+        }
+    };
+
+    let result = match assignment {
+        KeyAssignment::SetSerialBaudRate(baud) => {
+            control.set_baud_rate(serial::BaudRate::from_speed(*baud))
+        }
+        KeyAssignment::SetSerialFlowControl(flow_control) => {
+            control.set_flow_control(flow_control_to_serial(*flow_control))
+        }
+        KeyAssignment::ToggleSerialDtr => control.toggle_dtr().map(|_| ()),
+        KeyAssignment::ToggleSerialRts => control.toggle_rts().map(|_| ()),
+        _ => return,
+    };
+
+    match result {
+        Ok(()) => log::info!("serial port is now {}", control.describe()),
+        Err(err) => log::error!("{}: {:#}", assignment.name(), err),
+    }
+}
+
+/// Opens a new tab connected to `port` in the window of `term_window`.
+/// The domain for the port is created on first use and re-used after that.
+fn open_serial_port(term_window: &mut TermWindow, pane_id: PaneId, port: &str) {
+    let mux = Mux::get().unwrap();
+    let name = format!("serial:{}", port);
+    if mux.get_domain_by_name(&name).is_none() {
+        let domain = create_serial_domain(&name, SerialTty::new(port));
+        mux.add_domain(&domain);
+    }
+    if let Some(pane) = mux.get_pane(pane_id) {
+        if let Err(err) = term_window.perform_key_assignment(
+            &pane,
+            &KeyAssignment::SpawnTab(SpawnTabDomain::DomainName(name)),
+        ) {
+            log::error!("opening serial port {}: {:#}", port, err);
+        }
+    }
+}
+
+pub struct SerialPortSelector {
+    pane_id: PaneId,
+    element: RefCell<Option<Vec<ComputedElement>>>,
+    entries: RefCell<Vec<String>>,
+    filter: RefCell<String>,
+    /// indices into entries that match the filter, in display order
+    matches: RefCell<Vec<usize>>,
+    selected_row: RefCell<usize>,
+    top_row: RefCell<usize>,
+    max_rows_on_screen: RefCell<usize>,
+}
+
+impl SerialPortSelector {
+    pub fn new(pane: &Rc<dyn Pane>) -> Self {
+        let selector = Self {
+            pane_id: pane.pane_id(),
+            element: RefCell::new(None),
+            entries: RefCell::new(vec![]),
+            filter: RefCell::new(String::new()),
+            matches: RefCell::new(vec![]),
+            selected_row: RefCell::new(0),
+            top_row: RefCell::new(0),
+            max_rows_on_screen: RefCell::new(0),
+        };
+        selector.rescan();
+        selector
+    }
+
+    fn rescan(&self) {
+        *self.entries.borrow_mut() = available_ports();
+        self.updated_input();
+    }
+
+    fn update_matches(&self) {
+        let entries = self.entries.borrow();
+        let filter = self.filter.borrow();
+        let matches: Vec<usize> = if filter.is_empty() {
+            (0..entries.len()).collect()
+        } else {
+            let matcher = SkimMatcherV2::default();
+            let mut scored: Vec<(usize, i64)> = entries
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, entry)| {
+                    matcher
+                        .fuzzy_match(entry, &filter)
+                        .map(|score| (idx, score))
+                })
+                .collect();
+            scored.sort_by(|(a_idx, a_score), (b_idx, b_score)| {
+                b_score.cmp(a_score).then(a_idx.cmp(b_idx))
+            });
+            scored.into_iter().map(|(idx, _)| idx).collect()
+        };
+        *self.matches.borrow_mut() = matches;
+    }
+
+    fn updated_input(&self) {
+        self.update_matches();
+        *self.selected_row.borrow_mut() = 0;
+        *self.top_row.borrow_mut() = 0;
+    }
+
+    fn selected_port(&self) -> Option<String> {
+        let idx = *self.matches.borrow().get(*self.selected_row.borrow())?;
+        self.entries.borrow().get(idx).cloned()
+    }
+
+    fn move_up(&self) {
+        let mut row = self.selected_row.borrow_mut();
+        *row = row.saturating_sub(1);
+
+        let mut top_row = self.top_row.borrow_mut();
+        if *row < *top_row {
+            *top_row = *row;
+        }
+    }
+
+    fn move_down(&self) {
+        let max_rows_on_screen = (*self.max_rows_on_screen.borrow()).max(1);
+        let limit = self.matches.borrow().len().saturating_sub(1);
+        let mut row = self.selected_row.borrow_mut();
+        *row = row.saturating_add(1).min(limit);
+        let mut top_row = self.top_row.borrow_mut();
+        if *row > *top_row + max_rows_on_screen - 1 {
+            *top_row = row.saturating_sub(max_rows_on_screen - 1);
+        }
+    }
+
+    fn compute(
+        &self,
+        term_window: &mut TermWindow,
+        max_rows_on_screen: usize,
+    ) -> anyhow::Result<Vec<ComputedElement>> {
+        let font = term_window
+            .fonts
+            .char_select_font()
+            .expect("to resolve char selection font");
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+
+        let top_bar_height = if term_window.show_tab_bar && !term_window.config.tab_bar_at_bottom {
+            term_window.tab_bar_pixel_height().unwrap()
+        } else {
+            0.
+        };
+        let (padding_left, padding_top) = term_window.padding_left_top();
+        let border = term_window.get_os_border();
+        let top_pixel_y = top_bar_height + padding_top + border.top.get() as f32;
+
+        let selected_row = *self.selected_row.borrow();
+        let top_row = *self.top_row.borrow();
+        let entries = self.entries.borrow();
+        let matches = self.matches.borrow();
+
+        let fg = term_window.config.pane_select_fg_color.to_linear();
+        let bg = term_window.config.pane_select_bg_color.to_linear();
+
+        let header = if entries.is_empty() {
+            "No serial ports were found (Ctrl-R=rescan Esc=close)".to_string()
+        } else {
+            format!(
+                "Serial ports: {}_   (Enter=open Ctrl-R=rescan Esc=close)",
+                self.filter.borrow()
+            )
+        };
+
+        let mut elements = vec![Element::new(&font, ElementContent::Text(header))
+            .colors(ElementColors {
+                border: BorderColor::default(),
+                bg: LinearRgba::TRANSPARENT.into(),
+                text: fg.into(),
+            })
+            .display(DisplayType::Block)];
+
+        for (display_idx, &entry_idx) in matches
+            .iter()
+            .enumerate()
+            .skip(top_row)
+            .take(max_rows_on_screen)
+        {
+            let (row_bg, text) = if display_idx == selected_row {
+                (fg.into(), bg.into())
+            } else {
+                (LinearRgba::TRANSPARENT.into(), fg.into())
+            };
+            elements.push(
+                Element::new(&font, ElementContent::Text(entries[entry_idx].clone()))
+                    .colors(ElementColors {
+                        border: BorderColor::default(),
+                        bg: row_bg,
+                        text,
+                    })
+                    .padding(BoxDimension {
+                        left: Dimension::Cells(0.25),
+                        right: Dimension::Cells(0.25),
+                        top: Dimension::Cells(0.),
+                        bottom: Dimension::Cells(0.),
+                    })
+                    .display(DisplayType::Block),
+            );
+        }
+
+        let corner = |poly: &'static [Poly]| SizedPoly {
+            width: Dimension::Cells(0.25),
+            height: Dimension::Cells(0.25),
+            poly,
+        };
+        let element = Element::new(&font, ElementContent::Children(elements))
+            .colors(ElementColors {
+                border: BorderColor::new(bg.into()),
+                bg: bg.into(),
+                text: fg.into(),
+            })
+            .margin(BoxDimension {
+                left: Dimension::Cells(1.25),
+                right: Dimension::Cells(1.25),
+                top: Dimension::Cells(1.25),
+                bottom: Dimension::Cells(1.25),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.25),
+                right: Dimension::Cells(0.25),
+                top: Dimension::Cells(0.25),
+                bottom: Dimension::Cells(0.25),
+            })
+            .border(BoxDimension::new(Dimension::Pixels(1.)))
+            .border_corners(Some(Corners {
+                top_left: corner(TOP_LEFT_ROUNDED_CORNER),
+                top_right: corner(TOP_RIGHT_ROUNDED_CORNER),
+                bottom_left: corner(BOTTOM_LEFT_ROUNDED_CORNER),
+                bottom_right: corner(BOTTOM_RIGHT_ROUNDED_CORNER),
+            }));
+
+        let dimensions = term_window.dimensions;
+        let size = term_window.terminal_size;
+
+        let computed = term_window.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_height as f32,
+                    pixel_cell: metrics.cell_size.height as f32,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_width as f32,
+                    pixel_cell: metrics.cell_size.width as f32,
+                },
+                bounds: euclid::rect(
+                    padding_left,
+                    top_pixel_y,
+                    size.cols as f32 * term_window.render_metrics.cell_size.width as f32,
+                    size.rows as f32 * term_window.render_metrics.cell_size.height as f32,
+                ),
+                metrics: &metrics,
+                gl_state: term_window.render_state.as_ref().unwrap(),
+                zindex: 100,
+            },
+            &element,
+        )?;
+
+        Ok(vec![computed])
+    }
+}
+
+impl Modal for SerialPortSelector {
+    fn perform_assignment(
+        &self,
+        _assignment: &KeyAssignment,
+        _term_window: &mut TermWindow,
+    ) -> bool {
+        false
+    }
+
+    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn key_down(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<()> {
+        match (key, mods) {
+            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+                term_window.cancel_modal();
+                return Ok(());
+            }
+            (KeyCode::UpArrow, KeyModifiers::NONE) => {
+                self.move_up();
+            }
+            (KeyCode::DownArrow, KeyModifiers::NONE) => {
+                self.move_down();
+            }
+            (KeyCode::Char('r'), KeyModifiers::CTRL) => {
+                self.rescan();
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                self.filter.borrow_mut().push(c);
+                self.updated_input();
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                self.filter.borrow_mut().pop();
+                self.updated_input();
+            }
+            (KeyCode::Char('u'), KeyModifiers::CTRL) => {
+                self.filter.borrow_mut().clear();
+                self.updated_input();
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                let port = self.selected_port();
+                term_window.cancel_modal();
+                if let Some(port) = port {
+                    open_serial_port(term_window, self.pane_id, &port);
+                }
+                return Ok(());
+            }
+            _ => return Ok(()),
+        }
+        term_window.invalidate_modal();
+        Ok(())
+    }
+
+    fn computed_element(
+        &self,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<Ref<[ComputedElement]>> {
+        let font = term_window
+            .fonts
+            .char_select_font()
+            .expect("to resolve char selection font");
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+
+        let max_rows_on_screen = ((term_window.dimensions.pixel_height * 8 / 10)
+            / metrics.cell_size.height as usize)
+            .saturating_sub(2)
+            .max(1);
+        *self.max_rows_on_screen.borrow_mut() = max_rows_on_screen;
+
+        if self.element.borrow().is_none() {
+            let element = self.compute(term_window, max_rows_on_screen)?;
+            self.element.borrow_mut().replace(element);
+        }
+        Ok(Ref::map(self.element.borrow(), |v| {
+            v.as_ref().unwrap().as_slice()
+        }))
+    }
+
+    fn reconfigure(&self, _term_window: &mut TermWindow) {
+        self.element.borrow_mut().take();
+    }
+}
@@ -0,0 +1,138 @@
+//! Implements `SnapshotPane`, which copies the current content of a
+//! pane into a new read-only pane beside it.  This keeps eg: an error
+//! message visible while work continues in the original pane.
+//!
+//! The snapshot is an applet pane that renders the copied lines,
+//! including their colors, and then ignores all input until it is
+//! closed.
+use crate::TermWindow;
+use config::keyassignment::PaneDirection;
+use mux::pane::Pane;
+use mux::tab::{SplitDirection, SplitRequest, SplitSize};
+use mux::termwiztermtab::allocate;
+use mux::Mux;
+use std::rc::Rc;
+use std::sync::Arc;
+use termwiz::cell::CellAttributes;
+use termwiz::surface::{Change, CursorVisibility, Line};
+use termwiz::terminal::Terminal;
+use wezterm_term::{StableRowIndex, TerminalConfiguration, TerminalSize};
+
+/// Returns the changes that reproduce the lines, from the top of the
+/// snapshot pane
+fn snapshot_changes(title: String, lines: &[Line]) -> Vec<Change> {
+    let mut changes = vec![
+        Change::Title(title),
+        Change::CursorVisibility(CursorVisibility::Hidden),
+    ];
+    for (idx, line) in lines.iter().enumerate() {
+        changes.append(&mut line.changes(&CellAttributes::default()));
+        changes.push(Change::AllAttributes(CellAttributes::default()));
+        // Lines that were wrapped are continued by the terminal, so
+        // that they are re-wrapped if the snapshot is resized
+        if idx + 1 < lines.len() && !line.last_cell_was_wrapped() {
+            changes.push(Change::Text("\r\n".to_string()));
+        }
+    }
+    changes
+}
+
+impl TermWindow {
+    pub(crate) fn snapshot_pane(&mut self, pane: &Rc<dyn Pane>, direction: PaneDirection) {
+        let (direction, target_is_second) = match direction {
+            PaneDirection::Left => (SplitDirection::Horizontal, false),
+            PaneDirection::Right => (SplitDirection::Horizontal, true),
+            PaneDirection::Up => (SplitDirection::Vertical, false),
+            PaneDirection::Down => (SplitDirection::Vertical, true),
+            PaneDirection::Next | PaneDirection::Prev => {
+                log::error!("Invalid direction {:?} for SnapshotPane", direction);
+                return;
+            }
+        };
+
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let pane_index = match tab
+            .iter_panes()
+            .into_iter()
+            .find(|p| p.pane.pane_id() == pane.pane_id())
+        {
+            Some(p) => p.index,
+            None => return,
+        };
+
+        let dims = pane.get_dimensions();
+        let (_, mut lines) = pane.get_lines(
+            dims.scrollback_top..dims.physical_top + dims.viewport_rows as StableRowIndex,
+        );
+        while lines.last().map(|l| l.is_whitespace()).unwrap_or(false) {
+            lines.pop();
+        }
+        let title = format!("Snapshot of {}", pane.get_title());
+
+        let size = TerminalSize {
+            cols: dims.cols,
+            rows: dims.viewport_rows,
+            pixel_width: self.render_metrics.cell_size.width as usize * dims.cols,
+            pixel_height: self.render_metrics.cell_size.height as usize * dims.viewport_rows,
+            dpi: dims.dpi,
+        };
+        let term_config: Arc<dyn TerminalConfiguration + Send + Sync> =
+            Arc::new(config::TermConfig::with_config(self.config.clone()));
+        let (mut term, snapshot) = allocate(size, term_config);
+
+        let request = SplitRequest {
+            direction,
+            target_is_second,
+            top_level: false,
+            size: SplitSize::Percent(50),
+        };
+        if let Err(err) = tab.split_and_insert(pane_index, request, Rc::clone(&snapshot)) {
+            log::error!("SnapshotPane: {:#}", err);
+            mux.remove_pane(snapshot.pane_id());
+            return;
+        }
+        // Keep working in the original pane
+        tab.set_active_pane(pane);
+
+        let changes = snapshot_changes(title, &lines);
+        promise::spawn::spawn_into_new_thread(move || -> anyhow::Result<()> {
+            term.render(&changes)?;
+            term.flush()?;
+            // The snapshot is read-only; wait for it to be closed
+            while term.poll_input(None).is_ok() {}
+            Ok(())
+        })
+        .detach();
+
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::surface::SEQ_ZERO;
+
+    #[test]
+    fn separates_lines() {
+        let attrs = CellAttributes::default();
+        let mut wrapped = Line::from_text("wrap", &attrs, SEQ_ZERO, None);
+        wrapped.set_last_cell_was_wrapped(true, SEQ_ZERO);
+        let lines = vec![
+            Line::from_text("one", &attrs, SEQ_ZERO, None),
+            wrapped,
+            Line::from_text("ped", &attrs, SEQ_ZERO, None),
+        ];
+        let newlines = snapshot_changes("title".to_string(), &lines)
+            .into_iter()
+            .filter(|c| matches!(c, Change::Text(t) if t == "\r\n"))
+            .count();
+        assert_eq!(newlines, 1);
+    }
+}
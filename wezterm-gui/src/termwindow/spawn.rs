@@ -1,12 +1,14 @@
 use crate::termwindow::MuxWindowId;
 use anyhow::{anyhow, bail, Context};
 use config::keyassignment::{SpawnCommand, SpawnTabDomain};
-use config::TermConfig;
+use config::{ResourceLimits, RestartOnExit, TermConfig};
 use mux::activity::Activity;
 use mux::domain::SplitSource;
+use mux::pane::Pane;
 use mux::tab::SplitRequest;
 use mux::Mux;
 use portable_pty::CommandBuilder;
+use std::rc::Rc;
 use std::sync::Arc;
 use wezterm_term::TerminalSize;
 
@@ -113,6 +115,7 @@ impl super::TermWindow {
                         .await
                         .context("split_pane")?;
                     pane.set_config(term_config);
+                    apply_spawn_options(&pane, &spawn.resource_limits, spawn.restart_on_exit);
                 } else {
                     bail!("there is no active tab while splitting pane!?");
                 }
@@ -133,6 +136,7 @@ impl super::TermWindow {
                     )
                     .await
                     .context("spawn_tab_or_window")?;
+                apply_spawn_options(&pane, &spawn.resource_limits, spawn.restart_on_exit);
 
                 // If it was created in this window, it copies our handlers.
                 // Otherwise, we'll pick them up when we later respond to
@@ -158,3 +162,29 @@ impl super::TermWindow {
         );
     }
 }
+
+/// Applies the options of a SpawnCommand that only local panes support
+fn apply_spawn_options(
+    pane: &Rc<dyn Pane>,
+    limits: &Option<ResourceLimits>,
+    restart_on_exit: Option<RestartOnExit>,
+) {
+    if let Some(limits) = limits {
+        if let Err(err) = pane.set_resource_limits(limits) {
+            log::error!(
+                "Unable to apply resource limits to pane {}: {:#}",
+                pane.pane_id(),
+                err
+            );
+        }
+    }
+    if restart_on_exit.is_some() {
+        if let Err(err) = pane.set_restart_on_exit(restart_on_exit) {
+            log::error!(
+                "Unable to set restart_on_exit for pane {}: {:#}",
+                pane.pane_id(),
+                err
+            );
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Shows a thumbnail of the active pane of a tab when the mouse is
+//! over that tab in the fancy tab bar.
+//! Only the active tab can be rendered, so its thumbnail is captured
+//! offscreen from time to time and cached; the thumbnail of an inactive
+//! tab shows how it looked when it was last active.
+use crate::quad::{TripleLayerQuadAllocator, TripleLayerQuadAllocatorTrait};
+use crate::tabbar::TabBarItem;
+use crate::termwindow::{BorrowedLayers, DimensionContext, TermWindowNotif, UIItemType};
+use crate::TermWindow;
+use ::window::color::LinearRgba;
+use mux::tab::TabId;
+use mux::Mux;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use termwiz::image::{ImageData, ImageDataType};
+
+/// How often the thumbnail of the active tab is refreshed
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+pub struct TabPreview {
+    image: Arc<ImageData>,
+    captured: Instant,
+}
+
+impl TermWindow {
+    fn tab_previews_enabled(&self) -> bool {
+        self.config.show_tab_previews && self.config.use_fancy_tab_bar && self.show_tab_bar
+    }
+
+    /// Arranges to capture a new thumbnail of the active tab if the
+    /// cached one is missing or stale.  The capture is deferred until
+    /// after the current paint, as it renders the window again.
+    pub(crate) fn schedule_tab_preview_capture(&mut self) {
+        if !self.tab_previews_enabled() || self.tab_preview_pending || self.capturing_tab_preview {
+            return;
+        }
+        let mux = Mux::get().expect("to be called on gui thread");
+        let tab_id = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab.tab_id(),
+            None => return,
+        };
+        if let Some(preview) = self.tab_previews.get(&tab_id) {
+            if preview.captured.elapsed() < REFRESH_INTERVAL {
+                return;
+            }
+        }
+        let window = match self.window.as_ref() {
+            Some(window) => window,
+            None => return,
+        };
+
+        self.tab_preview_pending = true;
+        window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+            term_window.tab_preview_pending = false;
+            if let Err(err) = term_window.capture_tab_preview(tab_id) {
+                log::debug!("Failed to capture preview of tab {}: {:#}", tab_id, err);
+            }
+        })));
+    }
+
+    fn capture_tab_preview(&mut self, tab_id: TabId) -> anyhow::Result<()> {
+        let mux = Mux::get().expect("to be called on gui thread");
+        self.tab_previews
+            .retain(|&id, _| id == tab_id || mux.get_tab(id).is_some());
+
+        match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) if tab.tab_id() == tab_id => {}
+            _ => return Ok(()),
+        }
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(pane) => pane,
+            None => return Ok(()),
+        };
+
+        self.capturing_tab_preview = true;
+        let image = self.capture_rgba(Some(pane.pane_id()));
+        self.capturing_tab_preview = false;
+        let image = image?;
+        if image.width() == 0 || image.height() == 0 {
+            return Ok(());
+        }
+
+        let width = DimensionContext {
+            dpi: self.dimensions.dpi as f32,
+            pixel_max: self.dimensions.pixel_width as f32,
+            pixel_cell: self.render_metrics.cell_size.width as f32,
+        };
+        let width = (self.config.tab_preview_width.evaluate_as_pixels(width) as u32)
+            .clamp(1, image.width());
+        let height = ((image.height() as u64 * width as u64) / image.width() as u64).max(1) as u32;
+        let thumbnail = image::imageops::thumbnail(&image, width, height);
+
+        let data = ImageDataType::new_single_frame(width, height, thumbnail.into_raw());
+        self.tab_previews.insert(
+            tab_id,
+            TabPreview {
+                image: Arc::new(ImageData::with_data(data)),
+                captured: Instant::now(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Draws the thumbnail of the inactive tab under the mouse pointer
+    pub(crate) fn paint_tab_preview(&mut self) -> anyhow::Result<()> {
+        if !self.tab_previews_enabled() || self.capturing_tab_preview || self.get_modal().is_some()
+        {
+            return Ok(());
+        }
+        let (item, tab_idx) = match &self.last_ui_item {
+            Some(item) => match item.item_type {
+                UIItemType::TabBar(TabBarItem::Tab {
+                    tab_idx,
+                    active: false,
+                }) => (item.clone(), tab_idx),
+                _ => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+
+        let mux = Mux::get().expect("to be called on gui thread");
+        let tab_id = match mux
+            .get_window(self.mux_window_id)
+            .and_then(|window| window.get_by_idx(tab_idx).map(|tab| tab.tab_id()))
+        {
+            Some(tab_id) => tab_id,
+            None => return Ok(()),
+        };
+        let image = match self.tab_previews.get(&tab_id) {
+            Some(preview) => Arc::clone(&preview.image),
+            None => return Ok(()),
+        };
+
+        let gl_state = self.render_state.as_ref().unwrap();
+        let (sprite, _next_due) = gl_state
+            .glyph_cache
+            .borrow_mut()
+            .cached_image(&image, None)?;
+
+        let pixel_width = self.dimensions.pixel_width as f32;
+        let pixel_height = self.dimensions.pixel_height as f32;
+        let width = sprite.coords.width() as f32;
+        let height = sprite.coords.height() as f32;
+
+        // Place it below the tab, or above it when the tab bar is at
+        // the bottom, keeping it inside the window
+        let x = (item.x as f32).min(pixel_width - width).max(0.);
+        let y = if self.config.tab_bar_at_bottom {
+            (item.y as f32 - height).max(0.)
+        } else {
+            (item.y + item.height) as f32
+        };
+
+        let layer = gl_state.layer_for_zindex(100)?;
+        let vbs = layer.vb.borrow();
+        let mut vb_mut0 = vbs[0].current_vb_mut();
+        let mut vb_mut1 = vbs[1].current_vb_mut();
+        let mut vb_mut2 = vbs[2].current_vb_mut();
+        let mut layers = TripleLayerQuadAllocator::Gpu(BorrowedLayers([
+            vbs[0].map(&mut vb_mut0),
+            vbs[1].map(&mut vb_mut1),
+            vbs[2].map(&mut vb_mut2),
+        ]));
+
+        self.filled_rectangle(
+            &mut layers,
+            0,
+            euclid::rect(x - 1., y - 1., width + 2., height + 2.),
+            self.config.pane_select_bg_color.to_linear().into(),
+        )?;
+
+        let mut quad = layers.allocate(1)?;
+        quad.set_position(
+            x - pixel_width / 2.,
+            y - pixel_height / 2.,
+            x + width - pixel_width / 2.,
+            y + height - pixel_height / 2.,
+        );
+        quad.set_texture(sprite.texture_coords());
+        quad.set_fg_color(LinearRgba::with_components(1., 1., 1., 1.));
+        quad.set_hsv(None);
+        quad.set_has_color(true);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+//! Populates the macOS Touch Bar according to `touch_bar_layout`
+use crate::TermWindow;
+use ::window::{TouchBarItem, Window, WindowOps};
+use config::keyassignment::KeyAssignment;
+use config::TouchBarElement;
+use mux::Mux;
+
+/// Tab titles are truncated to this many characters so that
+/// more of them fit on the Touch Bar
+const MAX_TAB_LABEL_LEN: usize = 16;
+
+const KEY_TABLE_ID: &str = "key_table";
+const TAB_ID_PREFIX: &str = "tab:";
+const BUTTON_ID_PREFIX: &str = "button:";
+
+fn tab_label(idx: usize, title: &str) -> String {
+    let mut label = format!("{}: {}", idx + 1, title);
+    if let Some((byte_idx, _)) = label.char_indices().nth(MAX_TAB_LABEL_LEN) {
+        label.truncate(byte_idx);
+        label.push('…');
+    }
+    label
+}
+
+impl TermWindow {
+    fn compute_touch_bar_items(&mut self) -> Vec<TouchBarItem> {
+        let mut items = vec![];
+        let layout = self.config.touch_bar_layout.clone();
+        for (element_idx, element) in layout.iter().enumerate() {
+            match element {
+                TouchBarElement::KeyTable => {
+                    if let Some(name) = self.current_key_table_name() {
+                        items.push(TouchBarItem {
+                            id: KEY_TABLE_ID.to_string(),
+                            label: name,
+                            selected: true,
+                        });
+                    }
+                }
+                TouchBarElement::Tabs => {
+                    let mux = Mux::get().expect("called on the mux thread");
+                    let window = match mux.get_window(self.mux_window_id) {
+                        Some(window) => window,
+                        None => continue,
+                    };
+                    let active_idx = window.get_active_idx();
+                    for (idx, tab) in window.iter().enumerate() {
+                        let mut title = tab.get_title();
+                        if title.is_empty() {
+                            title = tab
+                                .get_active_pane()
+                                .map(|pane| pane.get_title())
+                                .unwrap_or_default();
+                        }
+                        items.push(TouchBarItem {
+                            id: format!("{}{}", TAB_ID_PREFIX, idx),
+                            label: tab_label(idx, &title),
+                            selected: idx == active_idx,
+                        });
+                    }
+                }
+                TouchBarElement::Button(button) => {
+                    items.push(TouchBarItem {
+                        id: format!("{}{}", BUTTON_ID_PREFIX, element_idx),
+                        label: button.label.clone(),
+                        selected: false,
+                    });
+                }
+            }
+        }
+        items
+    }
+
+    /// Updates the Touch Bar to reflect the state of the window
+    pub(crate) fn update_touch_bar(&mut self, window: &Window) {
+        if !cfg!(target_os = "macos") {
+            return;
+        }
+        let items = self.compute_touch_bar_items();
+        if items != self.touch_bar_items {
+            window.set_touch_bar(items.clone());
+            self.touch_bar_items = items;
+        }
+    }
+
+    /// Performs the action associated with the Touch Bar item
+    pub(crate) fn touch_bar_item_pressed(&mut self, id: &str) {
+        let assignment = if id == KEY_TABLE_ID {
+            Some(KeyAssignment::PopKeyTable)
+        } else if let Some(idx) = id.strip_prefix(TAB_ID_PREFIX) {
+            idx.parse().ok().map(KeyAssignment::ActivateTab)
+        } else if let Some(idx) = id.strip_prefix(BUTTON_ID_PREFIX) {
+            idx.parse::<usize>()
+                .ok()
+                .and_then(|idx| match self.config.touch_bar_layout.get(idx) {
+                    Some(TouchBarElement::Button(button)) => Some(button.action.clone()),
+                    _ => None,
+                })
+        } else {
+            None
+        };
+
+        if let (Some(assignment), Some(pane)) = (assignment, self.get_active_pane_or_overlay()) {
+            if let Err(err) = self.perform_assignment_unless_locked(&pane, &assignment) {
+                log::error!("Error performing {:?}: {:#}", assignment, err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tab_labels() {
+        assert_eq!(tab_label(0, "zsh"), "1: zsh");
+        assert_eq!(
+            tab_label(1, "a very long title indeed"),
+            "2: a very long t…"
+        );
+    }
+}
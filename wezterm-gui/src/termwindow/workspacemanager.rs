@@ -0,0 +1,557 @@
+use crate::termwindow::box_model::*;
+use crate::termwindow::modal::Modal;
+use crate::termwindow::render::{
+    BOTTOM_LEFT_ROUNDED_CORNER, BOTTOM_RIGHT_ROUNDED_CORNER, TOP_LEFT_ROUNDED_CORNER,
+    TOP_RIGHT_ROUNDED_CORNER,
+};
+use crate::termwindow::DimensionContext;
+use crate::utilsprites::RenderMetrics;
+use crate::TermWindow;
+use chrono::{DateTime, Utc};
+use config::keyassignment::KeyAssignment;
+use config::Dimension;
+use mux::Mux;
+use std::cell::{Ref, RefCell};
+use wezterm_term::{KeyCode, KeyModifiers, MouseEvent};
+use window::color::LinearRgba;
+
+struct WorkspaceRow {
+    name: String,
+    num_windows: usize,
+    num_tabs: usize,
+    last_activity: Option<DateTime<Utc>>,
+}
+
+impl WorkspaceRow {
+    fn label(&self, active: bool) -> String {
+        let marker = if active { "*" } else { " " };
+        let activity = match self.last_activity {
+            Some(when) => format_age(when),
+            None => "never".to_string(),
+        };
+        format!(
+            "{marker} {}  ({} window{}, {} tab{}, active {activity})",
+            self.name,
+            self.num_windows,
+            if self.num_windows == 1 { "" } else { "s" },
+            self.num_tabs,
+            if self.num_tabs == 1 { "" } else { "s" },
+        )
+    }
+}
+
+/// Formats the time elapsed since `when` in a compact, human
+/// friendly way, eg: "just now", "5m ago", "3h ago", "2d ago"
+fn format_age(when: DateTime<Utc>) -> String {
+    let secs = (Utc::now() - when).num_seconds().max(0);
+    if secs < 60 {
+        "just now".to_string()
+    } else if secs < 60 * 60 {
+        format!("{}m ago", secs / 60)
+    } else if secs < 24 * 60 * 60 {
+        format!("{}h ago", secs / (60 * 60))
+    } else {
+        format!("{}d ago", secs / (24 * 60 * 60))
+    }
+}
+
+fn collect_workspaces() -> Vec<WorkspaceRow> {
+    let mux = Mux::get().expect("workspace manager to run on main thread");
+    let mut rows: Vec<WorkspaceRow> = mux
+        .iter_workspaces()
+        .into_iter()
+        .map(|name| {
+            let windows = mux.iter_windows_in_workspace(&name);
+            let num_tabs = windows
+                .iter()
+                .filter_map(|&window_id| mux.get_window(window_id).map(|w| w.len()))
+                .sum();
+            WorkspaceRow {
+                num_windows: windows.len(),
+                num_tabs,
+                last_activity: mux.workspace_last_activity(&name),
+                name,
+            }
+        })
+        .collect();
+    // Most recently used first; iter_workspaces is already sorted by
+    // name, so workspaces without any recorded activity retain that order
+    rows.sort_by(|a, b| b.last_activity.cmp(&a.last_activity));
+    rows
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Mode {
+    Normal,
+    Rename(String),
+    Create(String),
+    ConfirmDelete,
+}
+
+pub struct WorkspaceManager {
+    element: RefCell<Option<Vec<ComputedElement>>>,
+    workspaces: RefCell<Vec<WorkspaceRow>>,
+    active_workspace: RefCell<String>,
+    mode: RefCell<Mode>,
+    message: RefCell<Option<String>>,
+    selected_row: RefCell<usize>,
+    top_row: RefCell<usize>,
+    max_rows_on_screen: RefCell<usize>,
+}
+
+impl WorkspaceManager {
+    pub fn new(_term_window: &mut TermWindow) -> Self {
+        let manager = Self {
+            element: RefCell::new(None),
+            workspaces: RefCell::new(vec![]),
+            active_workspace: RefCell::new(String::new()),
+            mode: RefCell::new(Mode::Normal),
+            message: RefCell::new(None),
+            selected_row: RefCell::new(0),
+            top_row: RefCell::new(0),
+            max_rows_on_screen: RefCell::new(0),
+        };
+        manager.refresh();
+        manager
+    }
+
+    /// Re-reads the workspace list from the mux, keeping the
+    /// selection within bounds
+    fn refresh(&self) {
+        let mux = Mux::get().expect("workspace manager to run on main thread");
+        *self.active_workspace.borrow_mut() = mux.active_workspace();
+        *self.workspaces.borrow_mut() = collect_workspaces();
+
+        let limit = self.workspaces.borrow().len().saturating_sub(1);
+        let mut row = self.selected_row.borrow_mut();
+        *row = (*row).min(limit);
+        let mut top_row = self.top_row.borrow_mut();
+        *top_row = (*top_row).min(*row);
+    }
+
+    fn selected_name(&self) -> Option<String> {
+        self.workspaces
+            .borrow()
+            .get(*self.selected_row.borrow())
+            .map(|row| row.name.clone())
+    }
+
+    fn move_up(&self) {
+        let mut row = self.selected_row.borrow_mut();
+        *row = row.saturating_sub(1);
+
+        let mut top_row = self.top_row.borrow_mut();
+        if *row < *top_row {
+            *top_row = *row;
+        }
+    }
+
+    fn move_down(&self) {
+        let max_rows_on_screen = (*self.max_rows_on_screen.borrow()).max(1);
+        let limit = self.workspaces.borrow().len().saturating_sub(1);
+        let mut row = self.selected_row.borrow_mut();
+        *row = row.saturating_add(1).min(limit);
+        let mut top_row = self.top_row.borrow_mut();
+        if *row > *top_row + max_rows_on_screen - 1 {
+            *top_row = row.saturating_sub(max_rows_on_screen - 1);
+        }
+    }
+
+    fn switch_to(&self, name: String, term_window: &mut TermWindow) {
+        if let Some(pane) = term_window.get_active_pane_or_overlay() {
+            if let Err(err) = term_window.perform_key_assignment(
+                &pane,
+                &KeyAssignment::SwitchToWorkspace {
+                    name: Some(name),
+                    spawn: None,
+                },
+            ) {
+                log::error!("Failed to switch workspace: {:#}", err);
+            }
+        }
+    }
+
+    fn rename_selected(&self, new_name: &str) {
+        let new_name = new_name.trim();
+        let old_name = match self.selected_name() {
+            Some(name) => name,
+            None => return,
+        };
+        if new_name.is_empty() || new_name == old_name {
+            return;
+        }
+        if self.workspaces.borrow().iter().any(|w| w.name == new_name) {
+            self.message
+                .borrow_mut()
+                .replace(format!("A workspace named {new_name} already exists"));
+            return;
+        }
+        let mux = Mux::get().expect("workspace manager to run on main thread");
+        mux.rename_workspace(&old_name, new_name);
+    }
+
+    fn delete_selected(&self, term_window: &mut TermWindow) {
+        let name = match self.selected_name() {
+            Some(name) => name,
+            None => return,
+        };
+
+        if name == *self.active_workspace.borrow() {
+            // Move this gui window somewhere else before tearing down
+            // the windows in the workspace, otherwise we'd close ourselves
+            let other = self
+                .workspaces
+                .borrow()
+                .iter()
+                .find(|w| w.name != name)
+                .map(|w| w.name.clone());
+            match other {
+                Some(other) => self.switch_to(other, term_window),
+                None => {
+                    self.message
+                        .borrow_mut()
+                        .replace("Cannot delete the only workspace".to_string());
+                    return;
+                }
+            }
+        }
+
+        let mux = Mux::get().expect("workspace manager to run on main thread");
+        mux.kill_workspace(&name);
+    }
+
+    fn compute(
+        &self,
+        term_window: &mut TermWindow,
+        max_rows_on_screen: usize,
+    ) -> anyhow::Result<Vec<ComputedElement>> {
+        let font = term_window
+            .fonts
+            .char_select_font()
+            .expect("to resolve char selection font");
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+
+        let top_bar_height = if term_window.show_tab_bar && !term_window.config.tab_bar_at_bottom {
+            term_window.tab_bar_pixel_height().unwrap()
+        } else {
+            0.
+        };
+        let (padding_left, padding_top) = term_window.padding_left_top();
+        let border = term_window.get_os_border();
+        let top_pixel_y = top_bar_height + padding_top + border.top.get() as f32;
+
+        let selected_row = *self.selected_row.borrow();
+        let top_row = *self.top_row.borrow();
+        let workspaces = self.workspaces.borrow();
+        let selected_name = workspaces
+            .get(selected_row)
+            .map(|w| w.name.as_str())
+            .unwrap_or("");
+
+        let header = match &*self.mode.borrow() {
+            Mode::Normal => match &*self.message.borrow() {
+                Some(message) => message.to_string(),
+                None => "Workspaces: Enter=switch r=rename d=delete n=new Esc=close".to_string(),
+            },
+            Mode::Rename(text) => format!("Rename {selected_name} to: {text}_"),
+            Mode::Create(text) => format!("New workspace name: {text}_"),
+            Mode::ConfirmDelete => {
+                format!("Delete workspace {selected_name} and close all of its windows? (y/n)")
+            }
+        };
+
+        let mut elements = vec![Element::new(&font, ElementContent::Text(header))
+            .colors(ElementColors {
+                border: BorderColor::default(),
+                bg: LinearRgba::TRANSPARENT.into(),
+                text: term_window.config.pane_select_fg_color.to_linear().into(),
+            })
+            .display(DisplayType::Block)];
+
+        let active_workspace = self.active_workspace.borrow();
+        for (display_idx, workspace) in workspaces
+            .iter()
+            .enumerate()
+            .skip(top_row)
+            .take(max_rows_on_screen)
+        {
+            let (bg, text) = if display_idx == selected_row {
+                (
+                    term_window.config.pane_select_fg_color.to_linear().into(),
+                    term_window.config.pane_select_bg_color.to_linear().into(),
+                )
+            } else {
+                (
+                    LinearRgba::TRANSPARENT.into(),
+                    term_window.config.pane_select_fg_color.to_linear().into(),
+                )
+            };
+            elements.push(
+                Element::new(
+                    &font,
+                    ElementContent::Text(workspace.label(workspace.name == *active_workspace)),
+                )
+                .colors(ElementColors {
+                    border: BorderColor::default(),
+                    bg,
+                    text,
+                })
+                .padding(BoxDimension {
+                    left: Dimension::Cells(0.25),
+                    right: Dimension::Cells(0.25),
+                    top: Dimension::Cells(0.),
+                    bottom: Dimension::Cells(0.),
+                })
+                .display(DisplayType::Block),
+            );
+        }
+
+        let element = Element::new(&font, ElementContent::Children(elements))
+            .colors(ElementColors {
+                border: BorderColor::new(
+                    term_window.config.pane_select_bg_color.to_linear().into(),
+                ),
+                bg: term_window.config.pane_select_bg_color.to_linear().into(),
+                text: term_window.config.pane_select_fg_color.to_linear().into(),
+            })
+            .margin(BoxDimension {
+                left: Dimension::Cells(1.25),
+                right: Dimension::Cells(1.25),
+                top: Dimension::Cells(1.25),
+                bottom: Dimension::Cells(1.25),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.25),
+                right: Dimension::Cells(0.25),
+                top: Dimension::Cells(0.25),
+                bottom: Dimension::Cells(0.25),
+            })
+            .border(BoxDimension::new(Dimension::Pixels(1.)))
+            .border_corners(Some(Corners {
+                top_left: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: TOP_LEFT_ROUNDED_CORNER,
+                },
+                top_right: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: TOP_RIGHT_ROUNDED_CORNER,
+                },
+                bottom_left: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: BOTTOM_LEFT_ROUNDED_CORNER,
+                },
+                bottom_right: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: BOTTOM_RIGHT_ROUNDED_CORNER,
+                },
+            }));
+
+        let dimensions = term_window.dimensions;
+        let size = term_window.terminal_size;
+
+        let computed = term_window.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_height as f32,
+                    pixel_cell: metrics.cell_size.height as f32,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_width as f32,
+                    pixel_cell: metrics.cell_size.width as f32,
+                },
+                bounds: euclid::rect(
+                    padding_left,
+                    top_pixel_y,
+                    size.cols as f32 * term_window.render_metrics.cell_size.width as f32,
+                    size.rows as f32 * term_window.render_metrics.cell_size.height as f32,
+                ),
+                metrics: &metrics,
+                gl_state: term_window.render_state.as_ref().unwrap(),
+                zindex: 100,
+            },
+            &element,
+        )?;
+
+        Ok(vec![computed])
+    }
+
+    fn key_down_normal(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<()> {
+        self.message.borrow_mut().take();
+        match (key, mods) {
+            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+                term_window.cancel_modal();
+                return Ok(());
+            }
+            (KeyCode::UpArrow, KeyModifiers::NONE) | (KeyCode::Char('k'), KeyModifiers::NONE) => {
+                self.move_up();
+            }
+            (KeyCode::DownArrow, KeyModifiers::NONE) | (KeyCode::Char('j'), KeyModifiers::NONE) => {
+                self.move_down();
+            }
+            (KeyCode::Char('r'), KeyModifiers::NONE) => {
+                if let Some(name) = self.selected_name() {
+                    *self.mode.borrow_mut() = Mode::Rename(name);
+                }
+            }
+            (KeyCode::Char('d'), KeyModifiers::NONE) => {
+                if self.selected_name().is_some() {
+                    *self.mode.borrow_mut() = Mode::ConfirmDelete;
+                }
+            }
+            (KeyCode::Char('n'), KeyModifiers::NONE) => {
+                *self.mode.borrow_mut() = Mode::Create(String::new());
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                if let Some(name) = self.selected_name() {
+                    term_window.cancel_modal();
+                    self.switch_to(name, term_window);
+                }
+                return Ok(());
+            }
+            _ => return Ok(()),
+        }
+        term_window.invalidate_modal();
+        Ok(())
+    }
+
+    fn key_down_text(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<()> {
+        let mut mode = self.mode.borrow_mut();
+        let text = match &mut *mode {
+            Mode::Rename(text) | Mode::Create(text) => text,
+            _ => return Ok(()),
+        };
+        match (key, mods) {
+            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+                *mode = Mode::Normal;
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                text.push(c);
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                text.pop();
+            }
+            (KeyCode::Char('u'), KeyModifiers::CTRL) => {
+                text.clear();
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                let mode = std::mem::replace(&mut *mode, Mode::Normal);
+                match mode {
+                    Mode::Rename(new_name) => {
+                        self.rename_selected(&new_name);
+                        self.refresh();
+                    }
+                    Mode::Create(name) => {
+                        let name = name.trim();
+                        if !name.is_empty() {
+                            term_window.cancel_modal();
+                            self.switch_to(name.to_string(), term_window);
+                            return Ok(());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => return Ok(()),
+        }
+        term_window.invalidate_modal();
+        Ok(())
+    }
+
+    fn key_down_confirm_delete(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<()> {
+        match (key, mods) {
+            (KeyCode::Char('y'), KeyModifiers::NONE)
+            | (KeyCode::Char('Y'), KeyModifiers::SHIFT) => {
+                *self.mode.borrow_mut() = Mode::Normal;
+                self.delete_selected(term_window);
+                self.refresh();
+            }
+            (KeyCode::Char('n'), KeyModifiers::NONE)
+            | (KeyCode::Char('N'), KeyModifiers::SHIFT)
+            | (KeyCode::Escape, KeyModifiers::NONE)
+            | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+                *self.mode.borrow_mut() = Mode::Normal;
+            }
+            _ => return Ok(()),
+        }
+        term_window.invalidate_modal();
+        Ok(())
+    }
+}
+
+impl Modal for WorkspaceManager {
+    fn perform_assignment(
+        &self,
+        _assignment: &KeyAssignment,
+        _term_window: &mut TermWindow,
+    ) -> bool {
+        false
+    }
+
+    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn key_down(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<()> {
+        let mode = self.mode.borrow().clone();
+        match mode {
+            Mode::Normal => self.key_down_normal(key, mods, term_window),
+            Mode::Rename(_) | Mode::Create(_) => self.key_down_text(key, mods, term_window),
+            Mode::ConfirmDelete => self.key_down_confirm_delete(key, mods, term_window),
+        }
+    }
+
+    fn computed_element(
+        &self,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<Ref<[ComputedElement]>> {
+        let font = term_window
+            .fonts
+            .char_select_font()
+            .expect("to resolve char selection font");
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+
+        let max_rows_on_screen = ((term_window.dimensions.pixel_height * 8 / 10)
+            / metrics.cell_size.height as usize)
+            .saturating_sub(2)
+            .max(1);
+        *self.max_rows_on_screen.borrow_mut() = max_rows_on_screen;
+
+        if self.element.borrow().is_none() {
+            let element = self.compute(term_window, max_rows_on_screen)?;
+            self.element.borrow_mut().replace(element);
+        }
+        Ok(Ref::map(self.element.borrow(), |v| {
+            v.as_ref().unwrap().as_slice()
+        }))
+    }
+
+    fn reconfigure(&self, _term_window: &mut TermWindow) {
+        self.element.borrow_mut().take();
+    }
+}
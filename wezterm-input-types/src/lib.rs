@@ -1276,6 +1276,9 @@ bitflags! {
         const TITLE = 1;
         const RESIZE = 2;
         const NONE = 0;
+        /// Draw the window management buttons in the tab bar,
+        /// rather than relying on the system titlebar
+        const INTEGRATED_BUTTONS = 4;
     }
 }
 
@@ -1291,6 +1294,8 @@ impl TryFrom<String> for WindowDecorations {
                 flags = Self::NONE;
             } else if ele == "RESIZE" {
                 flags |= Self::RESIZE;
+            } else if ele == "INTEGRATED_BUTTONS" {
+                flags |= Self::INTEGRATED_BUTTONS;
             } else {
                 return Err(format!("invalid WindowDecoration name {} in {}", ele, s));
             }
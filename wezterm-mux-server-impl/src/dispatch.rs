@@ -1,12 +1,14 @@
-use crate::sessionhandler::{PduSender, SessionHandler};
+use crate::sessionhandler::{ClientTransport, PduSender, SessionHandler};
 use crate::UnixStream;
 use anyhow::Context;
 use async_ossl::AsyncSslStream;
-use codec::{DecodedPdu, Pdu};
+use codec::{DecodedPdu, MuxEvent, Pdu};
 use futures::FutureExt;
 use mux::{Mux, MuxNotification};
 use smol::prelude::*;
 use smol::Async;
+use std::time::Duration;
+use wezterm_term::Alert;
 
 #[cfg(unix)]
 pub trait AsRawDesc: std::os::unix::io::AsRawFd {}
@@ -21,9 +23,14 @@ enum Item {
     Notif(MuxNotification),
     WritePdu(DecodedPdu),
     Readable,
+    CheckIdle,
 }
 
-pub async fn process<T>(stream: T) -> anyhow::Result<()>
+/// How often the session checks whether its client has been idle for
+/// longer than `mux_client_idle_timeout_secs`
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+pub async fn process<T>(stream: T, transport: ClientTransport) -> anyhow::Result<()>
 where
     T: 'static,
     T: std::io::Read,
@@ -32,10 +39,13 @@ where
     T: std::fmt::Debug,
 {
     let stream = smol::Async::new(stream)?;
-    process_async(stream).await
+    process_async(stream, transport).await
 }
 
-pub async fn process_async<T>(mut stream: Async<T>) -> anyhow::Result<()>
+pub async fn process_async<T>(
+    mut stream: Async<T>,
+    transport: ClientTransport,
+) -> anyhow::Result<()>
 where
     T: 'static,
     T: std::io::Read,
@@ -54,7 +64,7 @@ where
                 .map_err(|e| anyhow::anyhow!("{:?}", e))
         }
     });
-    let mut handler = SessionHandler::new(pdu_sender);
+    let mut handler = SessionHandler::new(pdu_sender, transport);
 
     {
         let mux = Mux::get().expect("to be running on gui thread");
@@ -62,6 +72,19 @@ where
         mux.subscribe(move |n| tx.try_send(Item::Notif(n)).is_ok());
     }
 
+    {
+        let tx = item_tx.clone();
+        promise::spawn::spawn(async move {
+            loop {
+                smol::Timer::after(IDLE_CHECK_INTERVAL).await;
+                if tx.try_send(Item::CheckIdle).is_err() {
+                    break;
+                }
+            }
+        })
+        .detach();
+    }
+
     loop {
         let rx_msg = item_rx.recv();
         let wait_for_read = stream.readable().map(|_| Ok(Item::Readable));
@@ -83,7 +106,15 @@ where
                 handler.process_one(decoded);
             }
             Ok(Item::WritePdu(decoded)) => {
-                match decoded.pdu.encode_async(&mut stream, decoded.serial).await {
+                match decoded
+                    .pdu
+                    .encode_async_with_compression(
+                        &mut stream,
+                        decoded.serial,
+                        handler.compression(),
+                    )
+                    .await
+                {
                     Ok(()) => {}
                     Err(err) => {
                         if let Some(err) = err.root_cause().downcast_ref::<std::io::Error>() {
@@ -106,17 +137,77 @@ where
                     }
                 }
             }
+            Ok(Item::CheckIdle) => {
+                if let Some(client_id) = handler.client_id() {
+                    let mux = Mux::get().expect("to be running on gui thread");
+                    // This results in a ClientDetachRequested notification
+                    // which ends the session
+                    mux.detach_client_if_idle(client_id);
+                }
+            }
             Ok(Item::Notif(MuxNotification::PaneOutput(pane_id))) => {
-                handler.schedule_pane_push(pane_id);
+                if !handler.may_view_pane(pane_id) {
+                    continue;
+                }
+                handler.send_event(MuxEvent::PaneOutput { pane_id });
+                if !handler.is_event_stream() {
+                    handler.schedule_pane_push(pane_id);
+                }
+            }
+            Ok(Item::Notif(MuxNotification::PaneFocused(pane_id))) => {
+                if !handler.may_view_pane(pane_id) {
+                    continue;
+                }
+                handler.send_event(MuxEvent::PaneFocused { pane_id });
+            }
+            Ok(Item::Notif(MuxNotification::PaneCollaboratorsChanged(pane_id))) => {
+                if handler.is_event_stream()
+                    || !handler.is_tracking_pane(pane_id)
+                    || !handler.may_view_pane(pane_id)
+                {
+                    continue;
+                }
+                Pdu::PaneCollaboratorsChanged(codec::PaneCollaboratorsChanged {
+                    pane_id,
+                    collaborators: handler.pane_collaborators(pane_id),
+                })
+                .encode_async_with_compression(&mut stream, 0, handler.compression())
+                .await?;
+                stream.flush().await.context("flushing PDU to client")?;
+            }
+            Ok(Item::Notif(MuxNotification::ClientDetachRequested(client_id))) => {
+                if handler.client_id() == Some(&client_id) {
+                    log::info!("detaching client {:?} as requested", client_id);
+                    return Ok(());
+                }
             }
             Ok(Item::Notif(MuxNotification::PaneAdded(_pane_id))) => {}
+            Ok(Item::Notif(MuxNotification::PaneActivationRequested(_pane_id))) => {}
             Ok(Item::Notif(MuxNotification::PaneRemoved(pane_id))) => {
                 Pdu::PaneRemoved(codec::PaneRemoved { pane_id })
-                    .encode_async(&mut stream, 0)
+                    .encode_async_with_compression(&mut stream, 0, handler.compression())
                     .await?;
                 stream.flush().await.context("flushing PDU to client")?;
             }
             Ok(Item::Notif(MuxNotification::Alert { pane_id, alert })) => {
+                if !handler.may_view_pane(pane_id) {
+                    continue;
+                }
+                if let Alert::WindowTitleChanged(_)
+                | Alert::TabTitleChanged(_)
+                | Alert::IconTitleChanged(_) = &alert
+                {
+                    let title = {
+                        let mux = Mux::get().expect("to be running on gui thread");
+                        mux.get_pane(pane_id).map(|pane| pane.get_title())
+                    };
+                    if let Some(title) = title {
+                        handler.send_event(MuxEvent::PaneTitleChanged { pane_id, title });
+                    }
+                }
+                if handler.is_event_stream() {
+                    continue;
+                }
                 {
                     let per_pane = handler.per_pane(pane_id);
                     let mut per_pane = per_pane.lock().unwrap();
@@ -125,20 +216,46 @@ where
                 handler.schedule_pane_push(pane_id);
             }
             Ok(Item::Notif(MuxNotification::SaveToDownloads { .. })) => {}
+            Ok(Item::Notif(MuxNotification::CaptureImage { .. })) => {}
+            Ok(Item::Notif(MuxNotification::InspectDeadPane { .. })) => {}
+            Ok(Item::Notif(MuxNotification::ConnectionError { .. })) => {}
+            Ok(Item::Notif(MuxNotification::EscapeSequencePermission { .. })) => {}
             Ok(Item::Notif(MuxNotification::AssignClipboard {
                 pane_id,
                 selection,
                 clipboard,
             })) => {
+                if !handler.may_view_pane(pane_id) {
+                    continue;
+                }
                 Pdu::SetClipboard(codec::SetClipboard {
                     pane_id,
                     clipboard,
                     selection,
                 })
-                .encode_async(&mut stream, 0)
+                .encode_async_with_compression(&mut stream, 0, handler.compression())
                 .await?;
                 stream.flush().await.context("flushing PDU to client")?;
             }
+            Ok(Item::Notif(MuxNotification::QueryClipboard { pane_id, selection })) => {
+                if !handler.may_view_pane(pane_id) {
+                    continue;
+                }
+                // Only the client that has the pane focused is asked,
+                // rather than every client that is viewing it
+                let focused = {
+                    let mux = Mux::get().expect("to be running on gui thread");
+                    mux.client_focusing_pane(pane_id)
+                };
+                match (focused, handler.client_id()) {
+                    (Some(focused), Some(client_id)) if focused == *client_id => {}
+                    _ => continue,
+                }
+                Pdu::QueryClipboard(codec::QueryClipboard { pane_id, selection })
+                    .encode_async_with_compression(&mut stream, 0, handler.compression())
+                    .await?;
+                stream.flush().await.context("flushing PDU to client")?;
+            }
             Ok(Item::Notif(MuxNotification::TabAddedToWindow { .. })) => {}
             Ok(Item::Notif(MuxNotification::WindowRemoved(_window_id))) => {}
             Ok(Item::Notif(MuxNotification::WindowCreated(_window_id))) => {}
@@ -150,16 +267,29 @@ where
                         .map(|w| w.get_workspace().to_string())
                 };
                 if let Some(workspace) = workspace {
+                    handler.send_event(MuxEvent::WindowWorkspaceChanged {
+                        window_id,
+                        workspace: workspace.clone(),
+                    });
                     Pdu::WindowWorkspaceChanged(codec::WindowWorkspaceChanged {
                         window_id,
                         workspace,
                     })
-                    .encode_async(&mut stream, 0)
+                    .encode_async_with_compression(&mut stream, 0, handler.compression())
                     .await?;
                     stream.flush().await.context("flushing PDU to client")?;
                 }
             }
-            Ok(Item::Notif(MuxNotification::ActiveWorkspaceChanged(_))) => {}
+            Ok(Item::Notif(MuxNotification::ActiveWorkspaceChanged(client_id))) => {
+                let workspace = {
+                    let mux = Mux::get().expect("to be running on gui thread");
+                    mux.active_workspace_for_client(&client_id)
+                };
+                handler.send_event(MuxEvent::ActiveWorkspaceChanged {
+                    client_id: (*client_id).clone(),
+                    workspace,
+                });
+            }
             Ok(Item::Notif(MuxNotification::Empty)) => {}
             Err(err) => {
                 log::error!("process_async Err {}", err);
@@ -1,3 +1,4 @@
+use crate::sessionhandler::ClientTransport;
 use crate::UnixListener;
 use anyhow::{anyhow, Context as _};
 use config::{create_user_owned_dirs, UnixDomain};
@@ -22,10 +23,12 @@ impl LocalListener {
             match stream {
                 Ok(stream) => {
                     spawn_into_main_thread(async move {
-                        crate::dispatch::process(stream).await.map_err(|e| {
-                            log::error!("{:#}", e);
-                            e
-                        })
+                        crate::dispatch::process(stream, ClientTransport::Local)
+                            .await
+                            .map_err(|e| {
+                                log::error!("{:#}", e);
+                                e
+                            })
                     })
                     .detach();
                 }
@@ -1,6 +1,17 @@
 use anyhow::{anyhow, Context as _};
 use rcgen::{BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType, IsCa};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// How long a generated client certificate remains valid.
+/// Clients are expected to renew their certificate before it expires.
+const CLIENT_CERT_VALIDITY: Duration = Duration::from_secs(30 * 86400);
+
+/// Allow for a little clock skew between the client and server
+const CLOCK_SKEW_ALLOWANCE: Duration = Duration::from_secs(86400);
 
 /// A helper for managing keys for the TLS server component.
 /// Each time the server is started, a new CA is generated
@@ -13,9 +24,12 @@ use std::path::PathBuf;
 /// both the public CA certificate information and that key to the client.
 /// The client will use both of those things to connect to the TLS
 /// server.
+/// The CA can be rotated while the server is running, which also
+/// invalidates all previously issued client certificates.
 pub struct Pki {
-    ca_cert: Certificate,
+    ca_cert: Mutex<Certificate>,
     pki_dir: PathBuf,
+    generation: AtomicUsize,
 }
 
 impl Pki {
@@ -24,6 +38,16 @@ impl Pki {
         std::fs::create_dir_all(&pki_dir)?;
         log::error!("runtime dir is {}", pki_dir.display());
 
+        let ca_cert = Self::generate_ca_and_server_cert(&pki_dir)?;
+
+        Ok(Self {
+            pki_dir,
+            ca_cert: Mutex::new(ca_cert),
+            generation: AtomicUsize::new(0),
+        })
+    }
+
+    fn generate_ca_and_server_cert(pki_dir: &PathBuf) -> anyhow::Result<Certificate> {
         let alt_names = vec![
             hostname::get()?
                 .into_string()
@@ -56,7 +80,25 @@ impl Pki {
         std::fs::write(&server_pem_path, signed_cert.as_bytes())
             .context(format!("saving {}", server_pem_path.display()))?;
 
-        Ok(Self { pki_dir, ca_cert })
+        Ok(ca_cert)
+    }
+
+    /// Generate a new CA and server certificate, replacing the current ones.
+    /// Client certificates issued prior to this call will no longer be
+    /// accepted by TLS listeners once they pick up the new generation.
+    pub fn rotate(&self) -> anyhow::Result<()> {
+        let ca_cert = Self::generate_ca_and_server_cert(&self.pki_dir)?;
+        *self.ca_cert.lock().unwrap() = ca_cert;
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        log::info!("rotated TLS credentials; generation is now {}", generation);
+        Ok(())
+    }
+
+    /// Returns a counter that is incremented each time the credentials
+    /// are rotated, allowing consumers to detect that they need to
+    /// reload the CA and server certificate files.
+    pub fn generation(&self) -> usize {
+        self.generation.load(Ordering::SeqCst)
     }
 
     pub fn generate_client_cert(&self) -> anyhow::Result<String> {
@@ -67,8 +109,13 @@ impl Pki {
         dn.push(DnType::CommonName, unix_name);
         params.distinguished_name = dn;
 
+        let now = OffsetDateTime::now_utc();
+        params.not_before = now - CLOCK_SKEW_ALLOWANCE;
+        params.not_after = now + CLIENT_CERT_VALIDITY;
+
         let client_cert = Certificate::from_params(params)?;
-        let mut signed_cert = client_cert.serialize_pem_with_signer(&self.ca_cert)?;
+        let mut signed_cert =
+            client_cert.serialize_pem_with_signer(&self.ca_cert.lock().unwrap())?;
         let key_bits = client_cert.get_key_pair().serialize_pem();
         signed_cert.push_str(&key_bits);
 
@@ -77,6 +124,8 @@ impl Pki {
 
     pub fn ca_pem_string(&self) -> anyhow::Result<String> {
         self.ca_cert
+            .lock()
+            .unwrap()
             .serialize_pem()
             .context("Serializing ca cert pem")
     }
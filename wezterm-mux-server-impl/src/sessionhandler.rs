@@ -1,13 +1,18 @@
 use crate::PKI;
 use anyhow::{anyhow, Context};
 use codec::*;
+use config::MuxClientPermission;
 use mux::client::ClientId;
+pub use mux::client::ClientTransport;
+use mux::collab::ClientCursor;
 use mux::domain::SplitSource;
 use mux::pane::{Pane, PaneId};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::TabId;
-use mux::Mux;
+use mux::window::WindowId;
+use mux::{Mux, MuxNotification};
 use promise::spawn::spawn_into_main_thread;
+use rangeset::RangeSet;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
@@ -46,6 +51,9 @@ pub(crate) struct PerPane {
     seqno: SequenceNo,
     config_generation: usize,
     pub(crate) notifications: Vec<Alert>,
+    /// The viewport rows whose current content we have already
+    /// sent to the client; used to avoid re-sending unchanged lines
+    sent_viewport_rows: RangeSet<StableRowIndex>,
 }
 
 impl PerPane {
@@ -63,6 +71,9 @@ impl PerPane {
         let dims = pane.get_dimensions();
         if dims != self.dimensions {
             changed = true;
+            // A resize may have rewrapped the lines, so we can no
+            // longer assume that the client has the same content
+            self.sent_viewport_rows = RangeSet::new();
         }
 
         let cursor_position = pane.get_cursor_position();
@@ -96,17 +107,24 @@ impl PerPane {
         let viewport_range =
             dims.physical_top..dims.physical_top + dims.viewport_rows as StableRowIndex;
 
-        let (first_line, lines) = pane.get_lines(viewport_range);
-        let mut bonus_lines = lines
-            .into_iter()
-            .enumerate()
-            .map(|(idx, mut line)| {
-                let stable_row = first_line + idx as StableRowIndex;
+        // Only send the viewport lines that changed since our last push,
+        // or that the client hasn't seen from us yet; the client already
+        // holds the content of the rest of the viewport.
+        let (first_line, lines) = pane.get_lines(viewport_range.clone());
+        let mut bonus_lines = vec![];
+        for (idx, mut line) in lines.into_iter().enumerate() {
+            let stable_row = first_line + idx as StableRowIndex;
+            if all_dirty_lines.contains(stable_row) || !self.sent_viewport_rows.contains(stable_row)
+            {
                 all_dirty_lines.remove(stable_row);
                 line.compress_for_scrollback();
-                (stable_row, line)
-            })
-            .collect::<Vec<_>>();
+                bonus_lines.push((stable_row, line));
+            }
+        }
+        // Everything in the viewport has now been sent, and rows that
+        // have scrolled out of it are no longer tracked
+        self.sent_viewport_rows = RangeSet::new();
+        self.sent_viewport_rows.add_range(viewport_range);
 
         // Always send the cursor's row, as that tends to the busiest and we don't
         // have a sequencing concept for our idea of the remote state.
@@ -193,8 +211,11 @@ fn maybe_push_pane_changes(
 
 pub struct SessionHandler {
     to_write_tx: PduSender,
+    transport: ClientTransport,
     per_pane: HashMap<TabId, Arc<Mutex<PerPane>>>,
     client_id: Option<Arc<ClientId>>,
+    compression: Compression,
+    subscribed_events: Vec<MuxEventKind>,
 }
 
 impl Drop for SessionHandler {
@@ -207,14 +228,41 @@ impl Drop for SessionHandler {
 }
 
 impl SessionHandler {
-    pub fn new(to_write_tx: PduSender) -> Self {
+    pub fn new(to_write_tx: PduSender, transport: ClientTransport) -> Self {
         Self {
             to_write_tx,
+            transport,
             per_pane: HashMap::new(),
             client_id: None,
+            compression: Compression::default(),
+            subscribed_events: vec![],
         }
     }
 
+    /// Returns true if the client has subscribed to events, in which
+    /// case it is an event stream rather than a renderer of panes
+    pub fn is_event_stream(&self) -> bool {
+        !self.subscribed_events.is_empty()
+    }
+
+    /// Sends `event` to the client if it subscribed to that kind of event
+    pub fn send_event(&self, event: MuxEvent) {
+        if self.subscribed_events.contains(&event.kind()) {
+            self.to_write_tx
+                .send(DecodedPdu {
+                    pdu: Pdu::MuxEvent(event),
+                    serial: 0,
+                })
+                .ok();
+        }
+    }
+
+    /// Returns the compression that the client asked us to use
+    /// when sending PDUs to it
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
     pub(crate) fn per_pane(&mut self, pane_id: PaneId) -> Arc<Mutex<PerPane>> {
         Arc::clone(
             self.per_pane
@@ -223,6 +271,168 @@ impl SessionHandler {
         )
     }
 
+    /// Returns what this client is allowed to do, if it is restricted
+    fn permission(&self) -> Option<MuxClientPermission> {
+        let mux = Mux::get().unwrap();
+        mux.client_permission(self.client_id.as_deref(), &self.transport)
+    }
+
+    /// Returns the identity that the client reported, if any
+    pub fn client_id(&self) -> Option<&Arc<ClientId>> {
+        self.client_id.as_ref()
+    }
+
+    /// Returns true if the client is allowed to see the content of `pane_id`
+    pub fn may_view_pane(&self, pane_id: PaneId) -> bool {
+        match self.permission() {
+            Some(permission) if permission.deny => false,
+            Some(permission) if !permission.workspaces.is_empty() => {
+                let mux = Mux::get().unwrap();
+                workspace_of_pane(&mux, pane_id)
+                    .map_or(false, |workspace| permission.allows_workspace(&workspace))
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns true if the client has asked for the content of `pane_id`
+    pub fn is_tracking_pane(&self, pane_id: PaneId) -> bool {
+        self.per_pane.contains_key(&pane_id)
+    }
+
+    /// Returns the other clients attached to `pane_id` and who holds
+    /// its input turn, as seen by this client
+    pub fn pane_collaborators(&self, pane_id: PaneId) -> mux::collab::PaneCollaborators {
+        let mux = Mux::get().unwrap();
+        mux.pane_collaborators(pane_id, self.client_id.as_deref())
+    }
+
+    /// Returns an error if `pdu` sends input to a pane whose input
+    /// turn is held by another client
+    fn check_input_turn(&self, pdu: &Pdu) -> anyhow::Result<()> {
+        match pdu {
+            Pdu::WriteToPane(WriteToPane { pane_id, .. })
+            | Pdu::SendPaste(SendPaste { pane_id, .. })
+            | Pdu::SendPasteChunk(SendPasteChunk { pane_id, .. })
+            | Pdu::SendKeyDown(SendKeyDown { pane_id, .. })
+            | Pdu::SendMouseEvent(SendMouseEvent { pane_id, .. }) => {
+                let mux = Mux::get().unwrap();
+                mux.check_pane_input_turn(*pane_id, self.client_id.as_deref())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an error if the client is not allowed to make the request `pdu`
+    fn check_permission(&self, pdu: &Pdu) -> anyhow::Result<()> {
+        let permission = match self.permission() {
+            Some(permission) => permission,
+            None => return Ok(()),
+        };
+        if permission.deny {
+            anyhow::bail!("this client is not permitted to use this server");
+        }
+        if permission.read_only && requires_write_access(pdu) {
+            anyhow::bail!("this client has read-only access");
+        }
+        if permission.workspaces.is_empty() {
+            return Ok(());
+        }
+
+        let mux = Mux::get().unwrap();
+        let workspaces = match pdu {
+            Pdu::WriteToPane(WriteToPane { pane_id, .. })
+            | Pdu::KillPane(KillPane { pane_id })
+            | Pdu::SendPaste(SendPaste { pane_id, .. })
+            | Pdu::InjectOutput(InjectOutput { pane_id, .. })
+            | Pdu::SendPasteChunk(SendPasteChunk { pane_id, .. })
+            | Pdu::SearchScrollbackRequest(SearchScrollbackRequest { pane_id, .. })
+            | Pdu::CaptureImage(CaptureImage { pane_id })
+            | Pdu::SetPaneZoomed(SetPaneZoomed { pane_id, .. })
+            | Pdu::ActivatePaneDirection(ActivatePaneDirection { pane_id, .. })
+            | Pdu::ResizePane(ResizePane { pane_id, .. })
+            | Pdu::ActivatePane(ActivatePane { pane_id })
+            | Pdu::Resize(Resize { pane_id, .. })
+            | Pdu::SendKeyDown(SendKeyDown { pane_id, .. })
+            | Pdu::SendMouseEvent(SendMouseEvent { pane_id, .. })
+            | Pdu::GetPaneRenderChanges(GetPaneRenderChanges { pane_id, .. })
+            | Pdu::GetLines(GetLines { pane_id, .. })
+            | Pdu::GetImageCell(GetImageCell { pane_id, .. })
+            | Pdu::SetFocusedPane(SetFocusedPane { pane_id })
+            | Pdu::TogglePaneInputTurn(TogglePaneInputTurn { pane_id })
+            | Pdu::SetClientCursor(SetClientCursor {
+                cursor: Some(ClientCursor { pane_id, .. }),
+            })
+            | Pdu::GetPaneMetrics(GetPaneMetrics {
+                pane_id: Some(pane_id),
+            }) => vec![workspace_of_pane(&mux, *pane_id)],
+            Pdu::SplitPane(SplitPane {
+                pane_id,
+                move_pane_id,
+                ..
+            }) => {
+                let mut workspaces = vec![workspace_of_pane(&mux, *pane_id)];
+                if let Some(move_pane_id) = move_pane_id {
+                    workspaces.push(workspace_of_pane(&mux, *move_pane_id));
+                }
+                workspaces
+            }
+            Pdu::MovePaneToNewTab(MovePaneToNewTab {
+                pane_id,
+                window_id,
+                workspace_for_new_window,
+            }) => vec![
+                workspace_of_pane(&mux, *pane_id),
+                match window_id {
+                    Some(window_id) => workspace_of_window(&mux, *window_id),
+                    None => workspace_for_new_window
+                        .clone()
+                        .or_else(|| workspace_of_pane(&mux, *pane_id)),
+                },
+            ],
+            Pdu::MoveTabToDomain(MoveTabToDomain { tab_id, .. }) => vec![mux
+                .window_containing_tab(*tab_id)
+                .and_then(|window_id| workspace_of_window(&mux, window_id))],
+            Pdu::SetWindowWorkspace(SetWindowWorkspace {
+                window_id,
+                workspace,
+            }) => vec![
+                workspace_of_window(&mux, *window_id),
+                Some(workspace.clone()),
+            ],
+            Pdu::SpawnV2(SpawnV2 {
+                window_id: Some(window_id),
+                ..
+            })
+            | Pdu::SpawnBatch(SpawnBatch {
+                window_id: Some(window_id),
+                ..
+            }) => vec![workspace_of_window(&mux, *window_id)],
+            Pdu::SpawnV2(SpawnV2 {
+                window_id: None,
+                workspace,
+                ..
+            })
+            | Pdu::SpawnBatch(SpawnBatch {
+                window_id: None,
+                workspace,
+                ..
+            })
+            | Pdu::SpawnWorkspace(SpawnWorkspace {
+                name: workspace, ..
+            }) => vec![Some(workspace.clone())],
+            _ => vec![],
+        };
+
+        for workspace in workspaces {
+            match workspace {
+                Some(workspace) if permission.allows_workspace(&workspace) => {}
+                _ => anyhow::bail!("this client cannot access that workspace"),
+            }
+        }
+        Ok(())
+    }
+
     pub fn schedule_pane_push(&mut self, pane_id: PaneId) {
         let sender = self.to_write_tx.clone();
         let per_pane = self.per_pane(pane_id);
@@ -265,6 +475,14 @@ impl SessionHandler {
             send_response(f());
         }
 
+        if let Err(err) = check_transport(&self.transport, &decoded.pdu)
+            .and_then(|_| self.check_permission(&decoded.pdu))
+            .and_then(|_| self.check_input_turn(&decoded.pdu))
+        {
+            send_response(Err(err));
+            return;
+        }
+
         match decoded.pdu {
             Pdu::Ping(Ping {}) => send_response(Ok(Pdu::Pong(Pong {}))),
             Pdu::SetWindowWorkspace(SetWindowWorkspace {
@@ -287,13 +505,31 @@ impl SessionHandler {
                 .detach();
             }
             Pdu::SetClientId(SetClientId { client_id }) => {
+                // The id is used to attribute input and to look up the
+                // permission of the client, so it can't be changed
+                // once it has been set
+                if self.client_id.is_some() {
+                    send_response(Err(anyhow!("the client id has already been set")));
+                    return;
+                }
                 let client_id = Arc::new(client_id);
-                self.client_id.replace(client_id.clone());
-                spawn_into_main_thread(async move {
-                    let mux = Mux::get().unwrap();
-                    mux.register_client(client_id);
-                })
-                .detach();
+                let mux = Mux::get().unwrap();
+                match mux.register_client(client_id.clone(), self.transport.clone()) {
+                    Ok(()) => {
+                        self.client_id.replace(client_id);
+                        send_response(Ok(Pdu::UnitResponse(UnitResponse {})))
+                    }
+                    Err(err) => send_response(Err(err)),
+                }
+            }
+            Pdu::SetCompression(SetCompression { compression }) => {
+                log::trace!("client requested compression {:?}", compression);
+                self.compression = compression;
+                send_response(Ok(Pdu::UnitResponse(UnitResponse {})))
+            }
+            Pdu::SubscribeToEvents(SubscribeToEvents { kinds }) => {
+                log::trace!("client subscribed to events {:?}", kinds);
+                self.subscribed_events = kinds;
                 send_response(Ok(Pdu::UnitResponse(UnitResponse {})))
             }
             Pdu::SetFocusedPane(SetFocusedPane { pane_id }) => {
@@ -306,6 +542,67 @@ impl SessionHandler {
                 .detach();
                 send_response(Ok(Pdu::UnitResponse(UnitResponse {})))
             }
+            Pdu::SetClientCursor(SetClientCursor { cursor }) => {
+                let client_id = self.client_id.clone();
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let client_id = client_id
+                                .ok_or_else(|| anyhow!("client has not identified itself"))?;
+                            let mux = Mux::get().unwrap();
+                            mux.set_client_cursor(&client_id, cursor);
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+            Pdu::TogglePaneInputTurn(TogglePaneInputTurn { pane_id }) => {
+                let client_id = self.client_id.clone();
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let client_id = client_id
+                                .ok_or_else(|| anyhow!("client has not identified itself"))?;
+                            let mux = Mux::get().unwrap();
+                            mux.toggle_pane_input_turn(pane_id, client_id)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+            Pdu::DetachClient(DetachClient { client_id }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.detach_client(&client_id)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+            Pdu::SetClientPermission(SetClientPermission {
+                client_id,
+                permission,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.set_client_permission(&client_id, permission)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
             Pdu::GetClientList(GetClientList) => {
                 spawn_into_main_thread(async move {
                     catch(
@@ -321,7 +618,52 @@ impl SessionHandler {
                 })
                 .detach();
             }
+            Pdu::GetPaneMetrics(GetPaneMetrics { pane_id }) => {
+                let permission = self.permission();
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane_ids = match pane_id {
+                                Some(pane_id) => {
+                                    mux.get_pane(pane_id)
+                                        .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                                    vec![pane_id]
+                                }
+                                None => {
+                                    let mut pane_ids: Vec<PaneId> = mux
+                                        .iter_panes()
+                                        .iter()
+                                        .map(|p| p.pane_id())
+                                        .filter(|&pane_id| {
+                                            permission.as_ref().map_or(true, |p| {
+                                                p.workspaces.is_empty()
+                                                    || workspace_of_pane(&mux, pane_id)
+                                                        .map_or(false, |workspace| {
+                                                            p.allows_workspace(&workspace)
+                                                        })
+                                            })
+                                        })
+                                        .collect();
+                                    pane_ids.sort();
+                                    pane_ids
+                                }
+                            };
+                            let panes = pane_ids
+                                .into_iter()
+                                .map(mux::panestats::pane_metrics)
+                                .collect();
+                            Ok(Pdu::GetPaneMetricsResponse(GetPaneMetricsResponse {
+                                panes,
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
             Pdu::ListPanes(ListPanes {}) => {
+                let permission = self.permission();
                 spawn_into_main_thread(async move {
                     catch(
                         move || {
@@ -329,6 +671,12 @@ impl SessionHandler {
                             let mut tabs = vec![];
                             for window_id in mux.iter_windows().into_iter() {
                                 let window = mux.get_window(window_id).unwrap();
+                                if !permission
+                                    .as_ref()
+                                    .map_or(true, |p| p.allows_workspace(window.get_workspace()))
+                                {
+                                    continue;
+                                }
                                 for tab in window.iter() {
                                     tabs.push(tab.codec_pane_tree());
                                 }
@@ -342,6 +690,56 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::ListPaneEntries(ListPaneEntries { filter }) => {
+                let permission = self.permission();
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let query = match filter {
+                                Some(filter) => Some(
+                                    filter
+                                        .parse::<mux::query::PaneQuery>()
+                                        .context("parsing filter")?,
+                                ),
+                                None => None,
+                            };
+                            let mux = Mux::get().unwrap();
+                            let process_name = |pane_id| {
+                                mux.get_pane(pane_id)
+                                    .and_then(|pane| pane.get_foreground_process_name())
+                            };
+                            let mut panes = vec![];
+                            for window_id in mux.iter_windows().into_iter() {
+                                let window = mux.get_window(window_id).unwrap();
+                                if !permission
+                                    .as_ref()
+                                    .map_or(true, |p| p.allows_workspace(window.get_workspace()))
+                                {
+                                    continue;
+                                }
+                                for tab in window.iter() {
+                                    panes.extend(
+                                        tab.codec_pane_tree()
+                                            .into_pane_entries()
+                                            .into_iter()
+                                            .filter(|entry| {
+                                                query.as_ref().map_or(true, |q| {
+                                                    q.matches_with(entry, &process_name)
+                                                })
+                                            }),
+                                    );
+                                }
+                            }
+                            Ok(Pdu::ListPaneEntriesResponse(ListPaneEntriesResponse {
+                                panes,
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
             Pdu::WriteToPane(WriteToPane { pane_id, data }) => {
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
@@ -401,6 +799,58 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::InjectOutput(InjectOutput { pane_id, data }) => {
+                let sender = self.to_write_tx.clone();
+                let per_pane = self.per_pane(pane_id);
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            let mut parser = termwiz::escape::parser::Parser::new();
+                            let mut actions = vec![];
+                            parser.parse(data.as_bytes(), |action| actions.push(action));
+                            pane.perform_actions(actions);
+                            maybe_push_pane_changes(&pane, sender, per_pane)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::SendPasteChunk(SendPasteChunk {
+                pane_id,
+                data,
+                bracketed,
+                is_first,
+                is_last,
+            }) => {
+                let sender = self.to_write_tx.clone();
+                let per_pane = self.per_pane(pane_id);
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            let bracketed =
+                                pane.send_paste_chunk(&data, bracketed, is_first, is_last)?;
+                            maybe_push_pane_changes(&pane, sender, per_pane)?;
+                            Ok(Pdu::SendPasteChunkResponse(SendPasteChunkResponse {
+                                bracketed,
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
             Pdu::SearchScrollbackRequest(SearchScrollbackRequest {
                 pane_id,
                 pattern,
@@ -435,6 +885,30 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::CaptureImage(CaptureImage { pane_id }) => {
+                async fn do_capture(pane_id: PaneId) -> anyhow::Result<Pdu> {
+                    let mux = Mux::get().unwrap();
+                    mux.get_pane(pane_id)
+                        .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+
+                    let (reply, rx) = smol::channel::bounded(1);
+                    mux.notify(MuxNotification::CaptureImage { pane_id, reply });
+                    let data = rx.recv().await.map_err(|_| {
+                        anyhow!("pane {} is not being displayed by a gui window", pane_id)
+                    })??;
+                    Ok(Pdu::CaptureImageResponse(CaptureImageResponse { data }))
+                }
+
+                spawn_into_main_thread(async move {
+                    promise::spawn::spawn(async move {
+                        let result = do_capture(pane_id).await;
+                        send_response(result);
+                    })
+                    .detach();
+                })
+                .detach();
+            }
+
             Pdu::SetPaneZoomed(SetPaneZoomed {
                 containing_tab_id,
                 pane_id,
@@ -480,6 +954,38 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::ResizePane(ResizePane {
+                pane_id,
+                cols,
+                rows,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.resize_pane(pane_id, cols, rows)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::ActivatePane(ActivatePane { pane_id }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            mux.activate_pane(pane_id)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
             Pdu::Resize(Resize {
                 containing_tab_id,
                 pane_id,
@@ -567,6 +1073,22 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::SpawnWorkspace(spawn) => {
+                let client_id = self.client_id.clone();
+                spawn_into_main_thread(async move {
+                    schedule_spawn_workspace(spawn, send_response, client_id);
+                })
+                .detach();
+            }
+
+            Pdu::SpawnBatch(batch) => {
+                let client_id = self.client_id.clone();
+                spawn_into_main_thread(async move {
+                    schedule_spawn_batch(batch, send_response, client_id);
+                })
+                .detach();
+            }
+
             Pdu::SplitPane(split) => {
                 let client_id = self.client_id.clone();
                 spawn_into_main_thread(async move {
@@ -583,6 +1105,17 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::MoveTabToDomain(request) => {
+                let client_id = self.client_id.clone();
+                spawn_into_main_thread(async move {
+                    promise::spawn::spawn(async move {
+                        send_response(move_tab_to_domain(request, client_id).await)
+                    })
+                    .detach();
+                })
+                .detach();
+            }
+
             Pdu::GetPaneRenderChanges(GetPaneRenderChanges { pane_id, .. }) => {
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
@@ -706,13 +1239,27 @@ impl SessionHandler {
                 );
             }
 
+            Pdu::RotateTlsCreds(_) => {
+                catch(
+                    move || {
+                        PKI.rotate()?;
+                        Ok(Pdu::UnitResponse(UnitResponse {}))
+                    },
+                    send_response,
+                );
+            }
+
             Pdu::Invalid { .. } => send_response(Err(anyhow!("invalid PDU {:?}", decoded.pdu))),
             Pdu::Pong { .. }
             | Pdu::ListPanesResponse { .. }
             | Pdu::SetClipboard { .. }
+            | Pdu::QueryClipboard { .. }
             | Pdu::NotifyAlert { .. }
             | Pdu::SetPalette { .. }
             | Pdu::SpawnResponse { .. }
+            | Pdu::SpawnBatchResponse { .. }
+            | Pdu::MoveTabToDomainResponse { .. }
+            | Pdu::PaneCollaboratorsChanged { .. }
             | Pdu::GetPaneRenderChangesResponse { .. }
             | Pdu::UnitResponse { .. }
             | Pdu::LivenessResponse { .. }
@@ -725,6 +1272,11 @@ impl SessionHandler {
             | Pdu::PaneRemoved { .. }
             | Pdu::GetImageCellResponse { .. }
             | Pdu::MovePaneToNewTabResponse { .. }
+            | Pdu::ListPaneEntriesResponse { .. }
+            | Pdu::MuxEvent { .. }
+            | Pdu::SendPasteChunkResponse { .. }
+            | Pdu::CaptureImageResponse { .. }
+            | Pdu::GetPaneMetricsResponse { .. }
             | Pdu::ErrorResponse { .. } => {
                 send_response(Err(anyhow!("expected a request, got {:?}", decoded.pdu)))
             }
@@ -732,6 +1284,70 @@ impl SessionHandler {
     }
 }
 
+/// Returns an error if `pdu` may only be sent by a client that is
+/// connected via the local unix domain socket.  Permissions can only
+/// be granted per listener, so these requests would otherwise let a
+/// restricted client lift its own restrictions or lock out others.
+fn check_transport(transport: &ClientTransport, pdu: &Pdu) -> anyhow::Result<()> {
+    if *transport == ClientTransport::Local {
+        return Ok(());
+    }
+    let what = match pdu {
+        // Client certificates are issued to the owner of the server,
+        // typically via bootstrap_via_ssh
+        Pdu::GetTlsCreds(_) => "TLS credentials can only be obtained",
+        Pdu::RotateTlsCreds(_) => "TLS credentials can only be rotated",
+        Pdu::DetachClient(_) => "clients can only be detached",
+        Pdu::SetClientPermission(_) => "client permissions can only be changed",
+        _ => return Ok(()),
+    };
+    anyhow::bail!(
+        "{} by a client connected via the local unix domain socket",
+        what
+    );
+}
+
+/// Returns true if `pdu` changes the state of the mux or of its panes
+fn requires_write_access(pdu: &Pdu) -> bool {
+    matches!(
+        pdu,
+        Pdu::SetWindowWorkspace(_)
+            | Pdu::WriteToPane(_)
+            | Pdu::KillPane(_)
+            | Pdu::SendPaste(_)
+            | Pdu::InjectOutput(_)
+            | Pdu::SendPasteChunk(_)
+            | Pdu::SetPaneZoomed(_)
+            | Pdu::ActivatePaneDirection(_)
+            | Pdu::ResizePane(_)
+            | Pdu::ActivatePane(_)
+            | Pdu::Resize(_)
+            | Pdu::SendKeyDown(_)
+            | Pdu::SendMouseEvent(_)
+            | Pdu::SpawnV2(_)
+            | Pdu::SpawnWorkspace(_)
+            | Pdu::SpawnBatch(_)
+            | Pdu::SplitPane(_)
+            | Pdu::MovePaneToNewTab(_)
+            | Pdu::MoveTabToDomain(_)
+            | Pdu::TogglePaneInputTurn(_)
+            | Pdu::SetFocusedPane(_)
+            | Pdu::DetachClient(_)
+            | Pdu::SetClientPermission(_)
+            | Pdu::RotateTlsCreds(_)
+    )
+}
+
+fn workspace_of_window(mux: &Mux, window_id: WindowId) -> Option<String> {
+    mux.get_window(window_id)
+        .map(|window| window.get_workspace().to_string())
+}
+
+fn workspace_of_pane(mux: &Mux, pane_id: PaneId) -> Option<String> {
+    let (_domain_id, window_id, _tab_id) = mux.resolve_pane_id(pane_id)?;
+    workspace_of_window(mux, window_id)
+}
+
 // Dancing around a little bit here; we can't directly spawn_into_main_thread the domain_spawn
 // function below because the compiler thinks that all of its locals then need to be Send.
 // We need to shimmy through this helper to break that aspect of the compiler flow
@@ -808,6 +1424,146 @@ async fn domain_spawn_v2(spawn: SpawnV2, client_id: Option<Arc<ClientId>>) -> an
     }))
 }
 
+fn schedule_spawn_workspace<SND>(
+    spawn: SpawnWorkspace,
+    send_response: SND,
+    client_id: Option<Arc<ClientId>>,
+) where
+    SND: Fn(anyhow::Result<Pdu>) + 'static,
+{
+    promise::spawn::spawn(async move { send_response(spawn_workspace(spawn, client_id).await) })
+        .detach();
+}
+
+async fn spawn_workspace(
+    spawn: SpawnWorkspace,
+    client_id: Option<Arc<ClientId>>,
+) -> anyhow::Result<Pdu> {
+    let mux = Mux::get().unwrap();
+    let _identity = mux.with_identity(client_id);
+
+    if !mux.iter_windows_in_workspace(&spawn.name).is_empty() {
+        anyhow::bail!("workspace {} already exists", spawn.name);
+    }
+
+    let config = config::configuration();
+    let definition = config
+        .workspace_definition(&spawn.name)
+        .ok_or_else(|| anyhow!("no workspace named {} is defined", spawn.name))?;
+
+    let (tab, pane, window_id) = mux
+        .spawn_workspace_from_definition(definition, spawn.size)
+        .await?;
+
+    Ok::<Pdu, anyhow::Error>(Pdu::SpawnResponse(SpawnResponse {
+        pane_id: pane.pane_id(),
+        tab_id: tab.tab_id(),
+        window_id,
+        size: tab.get_size(),
+    }))
+}
+
+fn schedule_spawn_batch<SND>(
+    batch: SpawnBatch,
+    send_response: SND,
+    client_id: Option<Arc<ClientId>>,
+) where
+    SND: Fn(anyhow::Result<Pdu>) + 'static,
+{
+    promise::spawn::spawn(async move { send_response(spawn_batch(batch, client_id).await) })
+        .detach();
+}
+
+async fn spawn_batch(batch: SpawnBatch, client_id: Option<Arc<ClientId>>) -> anyhow::Result<Pdu> {
+    let mux = Mux::get().unwrap();
+    let _identity = mux.with_identity(client_id);
+
+    if batch.tabs.is_empty() {
+        anyhow::bail!("the batch has no tabs");
+    }
+    if let Some(window_id) = batch.window_id {
+        if mux.get_window(window_id).is_none() {
+            anyhow::bail!("window_id {} not found", window_id);
+        }
+    }
+
+    let mut window_id = batch.window_id;
+    let mut tabs = vec![];
+    let result = spawn_batch_tabs(&mux, batch, &mut window_id, &mut tabs).await;
+
+    match (result, window_id) {
+        (Ok(()), Some(window_id)) => Ok(Pdu::SpawnBatchResponse(SpawnBatchResponse {
+            window_id,
+            tabs,
+        })),
+        (Ok(()), None) => Err(anyhow!("the batch has no window")),
+        (Err(err), _) => {
+            // Don't leave a partially constructed layout behind
+            for tab in &tabs {
+                mux.remove_tab(tab.tab_id);
+            }
+            Err(err)
+        }
+    }
+}
+
+async fn spawn_batch_tabs(
+    mux: &Rc<Mux>,
+    batch: SpawnBatch,
+    window_id: &mut Option<WindowId>,
+    tabs: &mut Vec<SpawnBatchTabResponse>,
+) -> anyhow::Result<()> {
+    for (tab_idx, tab_spec) in batch.tabs.into_iter().enumerate() {
+        let (tab, pane, new_window_id) = mux
+            .spawn_tab_or_window(
+                *window_id,
+                tab_spec.domain,
+                tab_spec.command,
+                tab_spec.command_dir,
+                batch.size,
+                None,
+                batch.workspace.clone(),
+            )
+            .await
+            .with_context(|| format!("spawning tab {}", tab_idx))?;
+        window_id.replace(new_window_id);
+        tabs.push(SpawnBatchTabResponse {
+            tab_id: tab.tab_id(),
+            pane_ids: vec![pane.pane_id()],
+        });
+
+        for (split_idx, split) in tab_spec.splits.into_iter().enumerate() {
+            let pane_ids = &mut tabs.last_mut().unwrap().pane_ids;
+            let target = match split.target {
+                Some(idx) => *pane_ids.get(idx).ok_or_else(|| {
+                    anyhow!(
+                        "split {} of tab {} targets pane {}, but the tab only has {} panes",
+                        split_idx,
+                        tab_idx,
+                        idx,
+                        pane_ids.len()
+                    )
+                })?,
+                None => *pane_ids.last().unwrap(),
+            };
+            let (pane, _size) = mux
+                .split_pane(
+                    target,
+                    split.split_request,
+                    SplitSource::Spawn {
+                        command: split.command,
+                        command_dir: split.command_dir,
+                    },
+                    split.domain,
+                )
+                .await
+                .with_context(|| format!("spawning split {} of tab {}", split_idx, tab_idx))?;
+            pane_ids.push(pane.pane_id());
+        }
+    }
+    Ok(())
+}
+
 fn schedule_move_pane<SND>(
     request: MovePaneToNewTab,
     send_response: SND,
@@ -839,3 +1595,45 @@ async fn move_pane(
         window_id,
     }))
 }
+
+async fn move_tab_to_domain(
+    request: MoveTabToDomain,
+    client_id: Option<Arc<ClientId>>,
+) -> anyhow::Result<Pdu> {
+    let mux = Mux::get().unwrap();
+    let _identity = mux.with_identity(client_id);
+
+    let (tab, window_id) = mux
+        .move_tab_to_domain(request.tab_id, request.domain)
+        .await?;
+
+    Ok::<Pdu, anyhow::Error>(Pdu::MoveTabToDomainResponse(MoveTabToDomainResponse {
+        tab_id: tab.tab_id(),
+        window_id,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn remote_clients_cannot_manage_other_clients() {
+        let remote = ClientTransport::Remote {
+            listener: "0.0.0.0:8080".to_string(),
+        };
+        let detach = Pdu::DetachClient(DetachClient {
+            client_id: ClientId::new(),
+        });
+        let set_permission = Pdu::SetClientPermission(SetClientPermission {
+            client_id: ClientId::new(),
+            permission: None,
+        });
+
+        for pdu in [&detach, &set_permission] {
+            assert!(check_transport(&remote, pdu).is_err());
+            assert!(check_transport(&ClientTransport::Local, pdu).is_ok());
+        }
+        assert!(check_transport(&remote, &Pdu::Ping(Ping {})).is_ok());
+    }
+}
@@ -1,8 +1,12 @@
 #![cfg(unix)]
 use anyhow::Context;
 use libc::pid_t;
-use std::io::Write;
-use std::os::unix::io::AsRawFd;
+use std::io::{Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+/// The environment variable used to pass the write end of the
+/// readiness pipe through to the re-executed daemon process
+const READY_FD_ENV: &str = "WEZTERM_MUX_SERVER_READY_FD";
 
 enum Fork {
     Child(pid_t),
@@ -79,14 +83,37 @@ pub fn daemonize(config: &config::ConfigHandle) -> anyhow::Result<()> {
     let stderr = config.daemon_options.open_stderr()?;
     let devnull = std::fs::File::open("/dev/null").context("opening /dev/null for read")?;
 
+    // The original process waits on this pipe until the daemon reports
+    // that it is ready to accept connections, so that whoever launched
+    // us can connect as soon as we exit.
+    let mut pipe_fds: [RawFd; 2] = [-1, -1];
+    if unsafe { libc::pipe(pipe_fds.as_mut_ptr()) } != 0 {
+        let err: anyhow::Error = std::io::Error::last_os_error().into();
+        return Err(err.context("pipe"));
+    }
+    let mut ready_read = unsafe { std::fs::File::from_raw_fd(pipe_fds[0]) };
+    let ready_write = unsafe { std::fs::File::from_raw_fd(pipe_fds[1]) };
+
     match fork()? {
         Fork::Parent(pid) => {
+            drop(ready_write);
             let mut status = 0;
             unsafe { libc::waitpid(pid, &mut status, 0) };
-            std::process::exit(0);
+            let mut buf = [0u8; 1];
+            match ready_read.read(&mut buf) {
+                Ok(1) => std::process::exit(0),
+                _ => {
+                    eprintln!(
+                        "wezterm-mux-server failed to start; see {} for details",
+                        config.daemon_options.stderr().display()
+                    );
+                    std::process::exit(1);
+                }
+            }
         }
         Fork::Child(_) => {}
     }
+    drop(ready_read);
 
     setsid()?;
     match fork()? {
@@ -107,5 +134,24 @@ pub fn daemonize(config: &config::ConfigHandle) -> anyhow::Result<()> {
     unsafe { libc::dup2(stdout.as_raw_fd(), libc::STDOUT_FILENO) };
     unsafe { libc::dup2(stderr.as_raw_fd(), libc::STDERR_FILENO) };
 
+    // Don't close the descriptor on exec; the re-executed daemon
+    // will pick it up via the environment
+    let ready_fd = ready_write.as_raw_fd();
+    unsafe { libc::fcntl(ready_fd, libc::F_SETFD, 0) };
+    std::env::set_var(READY_FD_ENV, ready_fd.to_string());
+    std::mem::forget(ready_write);
+
     Ok(())
 }
+
+/// If we were started via `daemonize`, take ownership of the write end
+/// of the readiness pipe.  Writing to the returned file signals the
+/// original process that we are ready; dropping it without writing
+/// indicates that we failed to start.
+pub fn take_ready_pipe() -> Option<std::fs::File> {
+    let fd: RawFd = std::env::var(READY_FD_ENV).ok()?.parse().ok()?;
+    std::env::remove_var(READY_FD_ENV);
+    // Prevent the programs that we spawn from inheriting it
+    unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    Some(unsafe { std::fs::File::from_raw_fd(fd) })
+}
@@ -0,0 +1,103 @@
+//! When running as a service, the mux server records the layout of its
+//! workspaces when asked to stop, so that the next instance of the server
+//! can recreate them when it is started again.  The processes that were
+//! running in the panes cannot be carried over, but each tab is respawned
+//! with the default program in the same working directory and workspace.
+use anyhow::Context;
+use mux::Mux;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct HandoffTab {
+    cwd: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct HandoffWindow {
+    workspace: String,
+    tabs: Vec<HandoffTab>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Handoff {
+    active_workspace: String,
+    windows: Vec<HandoffWindow>,
+}
+
+fn handoff_path() -> PathBuf {
+    config::RUNTIME_DIR.join("mux-handoff.json")
+}
+
+/// Record the current set of workspaces, windows and tabs
+#[cfg_attr(not(unix), allow(dead_code))]
+pub fn save() -> anyhow::Result<()> {
+    let mux = Mux::get().ok_or_else(|| anyhow::anyhow!("no mux"))?;
+    let mut windows = vec![];
+    for window_id in mux.iter_windows() {
+        let window = match mux.get_window(window_id) {
+            Some(window) => window,
+            None => continue,
+        };
+        let tabs = window
+            .iter()
+            .map(|tab| HandoffTab {
+                cwd: tab
+                    .get_active_pane()
+                    .and_then(|pane| pane.get_current_working_dir())
+                    .and_then(|url| url.to_file_path().ok())
+                    .map(|path| path.to_string_lossy().to_string()),
+            })
+            .collect();
+        windows.push(HandoffWindow {
+            workspace: window.get_workspace().to_string(),
+            tabs,
+        });
+    }
+
+    let handoff = Handoff {
+        active_workspace: mux.active_workspace(),
+        windows,
+    };
+    let path = handoff_path();
+    std::fs::write(&path, serde_json::to_string(&handoff)?)
+        .with_context(|| format!("writing {}", path.display()))?;
+    log::info!("saved workspace handoff to {}", path.display());
+    Ok(())
+}
+
+/// Recreate the workspaces recorded by a previous call to `save`.
+/// Returns false if there was nothing to restore.
+/// The handoff file is removed so that it is applied at most once.
+pub async fn restore() -> anyhow::Result<bool> {
+    let path = handoff_path();
+    let data = match std::fs::read(&path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+    };
+    std::fs::remove_file(&path).ok();
+    let handoff: Handoff =
+        serde_json::from_slice(&data).with_context(|| format!("parsing {}", path.display()))?;
+
+    let mux = Mux::get().unwrap();
+    let domain = mux.default_domain();
+    let config = config::configuration();
+    let mut restored = false;
+    for window in handoff.windows {
+        if window.tabs.is_empty() {
+            continue;
+        }
+        let window_id = mux.new_empty_window(Some(window.workspace));
+        domain.attach(Some(*window_id)).await?;
+        for tab in window.tabs {
+            domain
+                .spawn(config.initial_size(0), None, tab.cwd, *window_id)
+                .await?;
+            restored = true;
+        }
+    }
+    mux.set_active_workspace(&handoff.active_workspace);
+    log::info!("restored workspace handoff from {}", path.display());
+    Ok(restored)
+}
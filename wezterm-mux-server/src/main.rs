@@ -2,7 +2,7 @@ use clap::*;
 use config::configuration;
 use mux::activity::Activity;
 use mux::domain::{Domain, LocalDomain};
-use mux::Mux;
+use mux::{Mux, MuxNotification};
 use portable_pty::cmdbuilder::CommandBuilder;
 use std::ffi::OsString;
 use std::process::Command;
@@ -12,6 +12,8 @@ use std::thread;
 use wezterm_gui_subcommands::*;
 
 mod daemonize;
+mod handoff;
+mod systemd;
 
 #[derive(Debug, Parser)]
 #[command(
@@ -46,6 +48,13 @@ struct Opt {
     #[arg(long = "daemonize")]
     daemonize: bool,
 
+    /// Run as a service managed by systemd (or a compatible service
+    /// manager): accept connections on the listening sockets passed
+    /// via socket activation, report readiness via sd_notify, and
+    /// save and restore the workspace layout across restarts.
+    #[arg(long = "systemd-socket-activation", conflicts_with = "daemonize")]
+    systemd_socket_activation: bool,
+
     /// Specify the current working directory for the initially
     /// spawned program
     #[arg(long = "cwd", value_parser, value_hint=ValueHint::DirPath)]
@@ -80,6 +89,21 @@ fn run() -> anyhow::Result<()> {
     )?;
 
     let config = config::configuration();
+
+    #[cfg(unix)]
+    let activated_sockets = if opts.systemd_socket_activation {
+        systemd::listen_fds()?
+    } else {
+        systemd::ActivatedSockets::default()
+    };
+    #[cfg(not(unix))]
+    if opts.systemd_socket_activation {
+        anyhow::bail!("--systemd-socket-activation is not supported on this system");
+    }
+
+    #[cfg(unix)]
+    let ready_pipe = daemonize::take_ready_pipe();
+
     #[cfg(unix)]
     {
         if opts.daemonize {
@@ -179,19 +203,47 @@ fn run() -> anyhow::Result<()> {
     let mux = Rc::new(mux::Mux::new(Some(domain.clone())));
     Mux::set_mux(&mux);
 
+    // There is no window in which to ask the user about escape
+    // sequences that the policy says to prompt for, so deny them
+    mux.subscribe(|n| {
+        if let MuxNotification::EscapeSequencePermission { pane_id, kind } = n {
+            mux::escape_permissions::deny_unhandled_prompt(pane_id, kind);
+        }
+        true
+    });
+
     let executor = promise::spawn::SimpleExecutor::new();
 
-    spawn_listener().map_err(|e| {
+    spawn_listener(
+        #[cfg(unix)]
+        activated_sockets,
+    )
+    .map_err(|e| {
         log::error!("problem spawning listeners: {:?}", e);
         e
     })?;
 
+    let service_mode = opts.systemd_socket_activation;
+    #[cfg(unix)]
+    if service_mode {
+        spawn_signal_handler()?;
+    }
+
     let activity = Activity::new();
 
     promise::spawn::spawn(async move {
-        if let Err(err) = async_run(cmd).await {
+        if let Err(err) = async_run(cmd, service_mode).await {
             terminate_with_error(err);
         }
+
+        #[cfg(unix)]
+        {
+            if let Some(mut ready_pipe) = ready_pipe {
+                use std::io::Write;
+                ready_pipe.write_all(b"1").ok();
+            }
+            systemd::notify("READY=1");
+        }
         drop(activity);
     })
     .detach();
@@ -209,7 +261,28 @@ async fn trigger_mux_startup(lua: Option<Rc<mlua::Lua>>) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn async_run(cmd: Option<CommandBuilder>) -> anyhow::Result<()> {
+/// Arrange to save the workspace layout and exit cleanly when the
+/// service manager asks us to stop
+#[cfg(unix)]
+fn spawn_signal_handler() -> anyhow::Result<()> {
+    let signals = signal_hook::iterator::Signals::new(&[libc::SIGTERM, libc::SIGINT])?;
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            log::info!("received signal {}; shutting down", signal);
+            promise::spawn::spawn_into_main_thread(async move {
+                systemd::notify("STOPPING=1");
+                if let Err(err) = handoff::save() {
+                    log::error!("failed to save workspace handoff: {:#}", err);
+                }
+                std::process::exit(0);
+            })
+            .detach();
+        }
+    });
+    Ok(())
+}
+
+async fn async_run(cmd: Option<CommandBuilder>, service_mode: bool) -> anyhow::Result<()> {
     let mux = Mux::get().unwrap();
 
     let domain = mux.default_domain();
@@ -222,6 +295,14 @@ async fn async_run(cmd: Option<CommandBuilder>) -> anyhow::Result<()> {
         }
     }
 
+    if service_mode && cmd.is_none() && mux.iter_panes().is_empty() {
+        match handoff::restore().await {
+            Ok(true) => return Ok(()),
+            Ok(false) => {}
+            Err(err) => log::error!("failed to restore workspace handoff: {:#}", err),
+        }
+    }
+
     let have_panes_in_domain = mux
         .iter_panes()
         .iter()
@@ -246,19 +327,61 @@ fn terminate_with_error(err: anyhow::Error) -> ! {
 }
 
 mod ossl;
+mod udp;
+mod wss;
 
-pub fn spawn_listener() -> anyhow::Result<()> {
+pub fn spawn_listener(
+    #[cfg(unix)] activated_sockets: systemd::ActivatedSockets,
+) -> anyhow::Result<()> {
     let config = configuration();
-    for unix_dom in &config.unix_domains {
-        std::env::set_var("WEZTERM_UNIX_SOCKET", unix_dom.socket_path());
-        let mut listener = wezterm_mux_server_impl::local::LocalListener::with_domain(unix_dom)?;
-        thread::spawn(move || {
-            listener.run();
-        });
+
+    #[cfg(unix)]
+    let (activated_unix, mut activated_tcp) =
+        (activated_sockets.unix, activated_sockets.tcp.into_iter());
+    #[cfg(not(unix))]
+    let (activated_unix, mut activated_tcp) = (vec![], std::iter::empty());
+
+    if activated_unix.is_empty() {
+        for unix_dom in &config.unix_domains {
+            std::env::set_var("WEZTERM_UNIX_SOCKET", unix_dom.socket_path());
+            let mut listener =
+                wezterm_mux_server_impl::local::LocalListener::with_domain(unix_dom)?;
+            thread::spawn(move || {
+                listener.run();
+            });
+        }
+    } else {
+        // The service manager owns the socket paths; we simply
+        // accept connections on the sockets that it passed to us
+        if let Some(unix_dom) = config.unix_domains.first() {
+            std::env::set_var("WEZTERM_UNIX_SOCKET", unix_dom.socket_path());
+        }
+        for listener in activated_unix {
+            let mut listener = wezterm_mux_server_impl::local::LocalListener::new(listener);
+            thread::spawn(move || {
+                listener.run();
+            });
+        }
     }
 
+    // Activated tcp sockets are paired with the tls_servers entries
+    // in the order that they appear in the configuration
     for tls_server in &config.tls_servers {
-        ossl::spawn_tls_listener(tls_server)?;
+        match activated_tcp.next() {
+            Some(listener) => ossl::spawn_tls_listener_on(listener, tls_server)?,
+            None => ossl::spawn_tls_listener(tls_server)?,
+        }
+    }
+    if activated_tcp.next().is_some() {
+        log::error!("ignoring activated tcp sockets that have no corresponding tls_servers entry");
+    }
+
+    for wss_server in &config.wss_servers {
+        wss::spawn_wss_listener(wss_server)?;
+    }
+
+    for udp_server in &config.udp_servers {
+        udp::spawn_udp_listener(udp_server)?;
     }
 
     Ok(())
@@ -7,64 +7,42 @@ use promise::spawn::spawn_into_main_thread;
 use std::net::TcpListener;
 use std::path::Path;
 use std::sync::Arc;
+use wezterm_mux_server_impl::sessionhandler::ClientTransport;
 use wezterm_mux_server_impl::PKI;
 
 struct OpenSSLNetListener {
     acceptor: Arc<SslAcceptor>,
     listener: TcpListener,
+    tls_server: TlsDomainServer,
+    pki_generation: usize,
 }
 
 impl OpenSSLNetListener {
-    pub fn new(listener: TcpListener, acceptor: SslAcceptor) -> Self {
-        Self {
+    pub fn new(listener: TcpListener, tls_server: &TlsDomainServer) -> anyhow::Result<Self> {
+        Ok(Self {
             listener,
-            acceptor: Arc::new(acceptor),
-        }
+            acceptor: Arc::new(build_acceptor(tls_server)?),
+            tls_server: tls_server.clone(),
+            pki_generation: PKI.generation(),
+        })
     }
 
-    /// Authenticates the peer.
-    /// The requirements are:
-    /// * The peer must have a certificate
-    /// * The peer certificate must be trusted
-    /// * The peer certificate must include a CN string that is
-    ///   either an exact match for the unix username of the
-    ///   user running this mux server instance, or must match
-    ///   a special encoded prefix set up by a proprietary PKI
-    ///   infrastructure in an environment used by the author.
-    fn verify_peer_cert<T>(stream: &SslStream<T>) -> anyhow::Result<()> {
-        let cert = stream
-            .ssl()
-            .peer_certificate()
-            .ok_or_else(|| anyhow!("no peer cert"))?;
-        let subject = cert.subject_name();
-        let cn = subject
-            .entries_by_nid(openssl::nid::Nid::COMMONNAME)
-            .next()
-            .ok_or_else(|| anyhow!("cert has no CN"))?;
-        let cn_str = cn.data().as_utf8()?.to_string();
-
-        let wanted_unix_name = std::env::var("USER")?;
-
-        if wanted_unix_name == cn_str {
-            log::trace!(
-                "Peer certificate CN `{}` == $USER `{}`",
-                cn_str,
-                wanted_unix_name
-            );
-            Ok(())
-        } else {
-            // Some environments that are used by the author of this
-            // program encode the CN in the form `user:unixname/DATA`
-            let maybe_encoded = format!("user:{}/", wanted_unix_name);
-            if cn_str.starts_with(&maybe_encoded) {
-                log::trace!(
-                    "Peer certificate CN `{}` matches $USER `{}`",
-                    cn_str,
-                    wanted_unix_name
-                );
-                Ok(())
-            } else {
-                anyhow::bail!("CN `{}` did not match $USER `{}`", cn_str, wanted_unix_name);
+    /// If the PKI credentials were rotated since we built our acceptor,
+    /// rebuild it so that we present the new server certificate and
+    /// only trust client certificates issued by the new CA.
+    fn refresh_acceptor_if_rotated(&mut self) {
+        let generation = PKI.generation();
+        if generation == self.pki_generation {
+            return;
+        }
+        match build_acceptor(&self.tls_server) {
+            Ok(acceptor) => {
+                log::info!("TLS credentials were rotated; reloaded acceptor");
+                self.acceptor = Arc::new(acceptor);
+                self.pki_generation = generation;
+            }
+            Err(err) => {
+                log::error!("failed to reload acceptor after rotation: {:#}", err);
             }
         }
     }
@@ -74,19 +52,24 @@ impl OpenSSLNetListener {
             match stream {
                 Ok(stream) => {
                     stream.set_nodelay(true).ok();
+                    self.refresh_acceptor_if_rotated();
                     let acceptor = self.acceptor.clone();
 
                     match acceptor.accept(stream) {
                         Ok(stream) => {
-                            if let Err(err) = Self::verify_peer_cert(&stream) {
+                            if let Err(err) = verify_peer_cert(&stream) {
                                 log::error!("problem with peer cert: {}", err);
                                 break;
                             }
+                            let transport = ClientTransport::Remote {
+                                listener: self.tls_server.bind_address.clone(),
+                            };
                             spawn_into_main_thread(async move {
                                 log::error!("Making new AsyncSslStream");
-                                wezterm_mux_server_impl::dispatch::process(AsyncSslStream::new(
-                                    stream,
-                                ))
+                                wezterm_mux_server_impl::dispatch::process(
+                                    AsyncSslStream::new(stream),
+                                    transport,
+                                )
                                 .await
                                 .map_err(|e| {
                                     log::error!("process: {:?}", e);
@@ -109,9 +92,56 @@ impl OpenSSLNetListener {
     }
 }
 
-pub fn spawn_tls_listener(tls_server: &TlsDomainServer) -> Result<(), Error> {
-    openssl::init();
+/// Authenticates the peer.
+/// The requirements are:
+/// * The peer must have a certificate
+/// * The peer certificate must be trusted
+/// * The peer certificate must include a CN string that is
+///   either an exact match for the unix username of the
+///   user running this mux server instance, or must match
+///   a special encoded prefix set up by a proprietary PKI
+///   infrastructure in an environment used by the author.
+///
+/// Returns the CN, which `mux_client_permissions` rules can match.
+pub fn verify_peer_cert<T>(stream: &SslStream<T>) -> anyhow::Result<()> {
+    let cert = stream
+        .ssl()
+        .peer_certificate()
+        .ok_or_else(|| anyhow!("no peer cert"))?;
+    let subject = cert.subject_name();
+    let cn = subject
+        .entries_by_nid(openssl::nid::Nid::COMMONNAME)
+        .next()
+        .ok_or_else(|| anyhow!("cert has no CN"))?;
+    let cn_str = cn.data().as_utf8()?.to_string();
+
+    let wanted_unix_name = std::env::var("USER")?;
+
+    if wanted_unix_name == cn_str {
+        log::trace!(
+            "Peer certificate CN `{}` == $USER `{}`",
+            cn_str,
+            wanted_unix_name
+        );
+        Ok(())
+    } else {
+        // Some environments that are used by the author of this
+        // program encode the CN in the form `user:unixname/DATA`
+        let maybe_encoded = format!("user:{}/", wanted_unix_name);
+        if cn_str.starts_with(&maybe_encoded) {
+            log::trace!(
+                "Peer certificate CN `{}` matches $USER `{}`",
+                cn_str,
+                wanted_unix_name
+            );
+            Ok(())
+        } else {
+            anyhow::bail!("CN `{}` did not match $USER `{}`", cn_str, wanted_unix_name);
+        }
+    }
+}
 
+pub fn build_acceptor(tls_server: &TlsDomainServer) -> Result<SslAcceptor, Error> {
     let mut acceptor = SslAcceptor::mozilla_modern(SslMethod::tls())?;
 
     let cert_file = tls_server
@@ -168,19 +198,33 @@ pub fn spawn_tls_listener(tls_server: &TlsDomainServer) -> Result<(), Error> {
 
     acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
 
-    let acceptor = acceptor.build();
+    Ok(acceptor.build())
+}
+
+pub fn spawn_tls_listener(tls_server: &TlsDomainServer) -> Result<(), Error> {
+    openssl::init();
 
     log::error!("listening with TLS on {:?}", tls_server.bind_address);
 
-    let mut net_listener = OpenSSLNetListener::new(
+    spawn_tls_listener_on(
         TcpListener::bind(&tls_server.bind_address).with_context(|| {
             format!(
                 "error binding to mux_server_bind_address {}",
                 tls_server.bind_address,
             )
         })?,
-        acceptor,
-    );
+        tls_server,
+    )
+}
+
+/// Accept TLS connections on an already bound listener, such as
+/// one passed to us via socket activation
+pub fn spawn_tls_listener_on(
+    listener: TcpListener,
+    tls_server: &TlsDomainServer,
+) -> Result<(), Error> {
+    openssl::init();
+    let mut net_listener = OpenSSLNetListener::new(listener, tls_server)?;
     std::thread::spawn(move || {
         net_listener.run();
     });
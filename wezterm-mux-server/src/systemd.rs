@@ -0,0 +1,130 @@
+//! Support for running as a systemd (or compatible) user service:
+//! receiving pre-bound listening sockets via socket activation and
+//! reporting readiness and shutdown via sd_notify.
+#![cfg(unix)]
+use anyhow::Context;
+use std::net::TcpListener;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::os::unix::net::UnixListener;
+
+/// The first file descriptor passed by the service manager
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// The listening sockets that were passed to us by the service manager
+#[derive(Default)]
+pub struct ActivatedSockets {
+    pub unix: Vec<UnixListener>,
+    pub tcp: Vec<TcpListener>,
+}
+
+/// Implements the receiving side of the sd_listen_fds(3) protocol.
+/// The environment variables are removed so that they are not
+/// inherited by the programs that we spawn.
+pub fn listen_fds() -> anyhow::Result<ActivatedSockets> {
+    let pid = std::env::var("LISTEN_PID").ok();
+    let num_fds = std::env::var("LISTEN_FDS").ok();
+    for name in &["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(name);
+    }
+
+    let pid: libc::pid_t = pid
+        .context("LISTEN_PID is not set; was wezterm-mux-server started via socket activation?")?
+        .parse()
+        .context("parsing LISTEN_PID")?;
+    if pid != unsafe { libc::getpid() } {
+        anyhow::bail!("LISTEN_PID {} does not refer to this process", pid);
+    }
+    let num_fds: RawFd = num_fds
+        .context("LISTEN_FDS is not set")?
+        .parse()
+        .context("parsing LISTEN_FDS")?;
+
+    let mut sockets = ActivatedSockets::default();
+    for fd in SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + num_fds {
+        unsafe {
+            libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+        match socket_family(fd)? {
+            libc::AF_UNIX => sockets.unix.push(unsafe { UnixListener::from_raw_fd(fd) }),
+            libc::AF_INET | libc::AF_INET6 => {
+                sockets.tcp.push(unsafe { TcpListener::from_raw_fd(fd) })
+            }
+            family => anyhow::bail!("fd {} has unsupported socket family {}", fd, family),
+        }
+    }
+    log::info!(
+        "socket activation provided {} unix and {} tcp sockets",
+        sockets.unix.len(),
+        sockets.tcp.len()
+    );
+    Ok(sockets)
+}
+
+fn socket_family(fd: RawFd) -> anyhow::Result<libc::c_int> {
+    let mut addr: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockname(
+            fd,
+            &mut addr as *mut _ as *mut libc::sockaddr,
+            &mut len as *mut _,
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("getsockname on socket activated fd {}", fd));
+    }
+    Ok(addr.ss_family as libc::c_int)
+}
+
+/// Implements sd_notify(3); sends `state` to the service manager
+/// if we were started with NOTIFY_SOCKET in the environment.
+/// Does nothing if that isn't the case.
+pub fn notify(state: &str) {
+    if let Err(err) = try_notify(state) {
+        log::error!("sd_notify {}: {:#}", state, err);
+    }
+}
+
+fn try_notify(state: &str) -> anyhow::Result<()> {
+    let path = match std::env::var_os("NOTIFY_SOCKET") {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let path = std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str()).to_vec();
+
+    let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as _;
+    if path.is_empty() || path.len() >= addr.sun_path.len() {
+        anyhow::bail!("invalid NOTIFY_SOCKET");
+    }
+    for (dest, src) in addr.sun_path.iter_mut().zip(path.iter()) {
+        *dest = *src as libc::c_char;
+    }
+    // A leading '@' denotes a socket in the abstract namespace
+    if path[0] == b'@' {
+        addr.sun_path[0] = 0;
+    }
+    let addr_len = (std::mem::size_of::<libc::sa_family_t>() + path.len()) as libc::socklen_t;
+
+    let sock = unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_DGRAM, 0) };
+    if sock < 0 {
+        return Err(std::io::Error::last_os_error()).context("creating notify socket");
+    }
+    let res = unsafe {
+        libc::sendto(
+            sock,
+            state.as_ptr() as *const _,
+            state.len(),
+            0,
+            &addr as *const _ as *const libc::sockaddr,
+            addr_len,
+        )
+    };
+    let err = std::io::Error::last_os_error();
+    unsafe { libc::close(sock) };
+    if res < 0 {
+        return Err(err).context("sending to NOTIFY_SOCKET");
+    }
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use anyhow::{Context, Error};
+use async_ossl::{UdpKey, UdpListener};
+use config::UdpDomainServer;
+use promise::spawn::spawn_into_main_thread;
+use wezterm_mux_server_impl::sessionhandler::ClientTransport;
+
+pub fn spawn_udp_listener(udp_server: &UdpDomainServer) -> Result<(), Error> {
+    openssl::init();
+    let key = UdpKey::load(&udp_server.key_file)?;
+    let listener = UdpListener::bind(&udp_server.bind_address, key, udp_server.idle_timeout)
+        .with_context(|| {
+            format!(
+                "error binding to udp_servers bind_address {}",
+                udp_server.bind_address,
+            )
+        })?;
+
+    log::error!(
+        "listening for udp sessions on {:?}",
+        udp_server.bind_address
+    );
+
+    let bind_address = udp_server.bind_address.clone();
+    std::thread::spawn(move || {
+        let result = listener.run(|stream| {
+            let transport = ClientTransport::Remote {
+                listener: bind_address.clone(),
+            };
+            spawn_into_main_thread(async move {
+                wezterm_mux_server_impl::dispatch::process(stream, transport)
+                    .await
+                    .map_err(|e| {
+                        log::error!("process: {:?}", e);
+                        e
+                    })
+            })
+            .detach();
+        });
+        if let Err(err) = result {
+            log::error!("udp listener on {} failed: {:#}", bind_address, err);
+        }
+    });
+    Ok(())
+}
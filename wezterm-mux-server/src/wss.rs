@@ -0,0 +1,217 @@
+use crate::ossl::{build_acceptor, verify_peer_cert};
+use anyhow::{Context, Error};
+use async_ossl::{AsyncWebSocketStream, WebSocketTransport};
+use config::{read_wss_auth_token, WssDomainServer};
+use openssl::ssl::SslAcceptor;
+use promise::spawn::spawn_into_main_thread;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::{header, StatusCode};
+use wezterm_mux_server_impl::sessionhandler::ClientTransport;
+use wezterm_mux_server_impl::PKI;
+
+struct WebSocketListener {
+    listener: TcpListener,
+    wss_server: WssDomainServer,
+    acceptor: Option<SslAcceptor>,
+    auth_token: Option<String>,
+    pki_generation: usize,
+}
+
+impl WebSocketListener {
+    fn new(listener: TcpListener, wss_server: &WssDomainServer) -> anyhow::Result<Self> {
+        let acceptor = if wss_server.use_tls {
+            Some(build_acceptor(&wss_server.tls_server())?)
+        } else {
+            None
+        };
+        let auth_token = match &wss_server.auth_token_file {
+            Some(path) => Some(read_wss_auth_token(path)?),
+            None => None,
+        };
+        Ok(Self {
+            listener,
+            wss_server: wss_server.clone(),
+            acceptor,
+            auth_token,
+            pki_generation: PKI.generation(),
+        })
+    }
+
+    /// If the PKI credentials were rotated since we built our acceptor,
+    /// rebuild it so that we present the new server certificate.
+    fn refresh_acceptor_if_rotated(&mut self) {
+        let generation = PKI.generation();
+        if self.acceptor.is_none() || generation == self.pki_generation {
+            return;
+        }
+        match build_acceptor(&self.wss_server.tls_server()) {
+            Ok(acceptor) => {
+                self.acceptor.replace(acceptor);
+                self.pki_generation = generation;
+            }
+            Err(err) => {
+                log::error!("failed to reload acceptor after rotation: {:#}", err);
+            }
+        }
+    }
+
+    fn run(&mut self) {
+        for stream in self.listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    stream.set_nodelay(true).ok();
+                    self.refresh_acceptor_if_rotated();
+                    let handshake = Handshake {
+                        acceptor: self.acceptor.clone(),
+                        path: self.wss_server.path.clone(),
+                        auth_token: self.auth_token.clone(),
+                    };
+                    let listener = self.wss_server.bind_address.clone();
+                    // The handshakes wait on the peer, so run them away
+                    // from the accept loop so that a slow or idle client
+                    // cannot hold up the others
+                    let spawned = std::thread::Builder::new()
+                        .name(format!("wss handshake {}", listener))
+                        .spawn(move || match handshake.accept(stream) {
+                            Ok(stream) => {
+                                let transport = ClientTransport::Remote { listener };
+                                spawn_into_main_thread(async move {
+                                    wezterm_mux_server_impl::dispatch::process(stream, transport)
+                                        .await
+                                        .map_err(|e| {
+                                            log::error!("process: {:?}", e);
+                                            e
+                                        })
+                                })
+                                .detach();
+                            }
+                            Err(err) => {
+                                log::error!("failed websocket accept: {:#}", err);
+                            }
+                        });
+                    if let Err(err) = spawned {
+                        log::error!("failed to spawn websocket handshake thread: {:#}", err);
+                    }
+                }
+                Err(err) => {
+                    log::error!("accept failed: {}", err);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What is needed to complete the TLS and websocket handshakes
+/// for a newly accepted connection
+struct Handshake {
+    acceptor: Option<SslAcceptor>,
+    path: String,
+    auth_token: Option<String>,
+}
+
+impl Handshake {
+    fn accept(self, stream: TcpStream) -> anyhow::Result<AsyncWebSocketStream> {
+        // Don't let a client that stalls part way through tie up
+        // this thread indefinitely
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let transport = match &self.acceptor {
+            Some(acceptor) => {
+                let stream = acceptor.accept(stream).context("TLS accept")?;
+                verify_peer_cert(&stream).context("problem with peer cert")?;
+                WebSocketTransport::Tls(stream)
+            }
+            None => WebSocketTransport::Plain(stream),
+        };
+
+        let path = self.path;
+        let auth_token = self.auth_token;
+        let check_request = move |req: &Request, response: Response| {
+            let error = |status: StatusCode| {
+                let mut response =
+                    ErrorResponse::new(Some(status.canonical_reason().unwrap_or("").to_string()));
+                *response.status_mut() = status;
+                Err(response)
+            };
+            if req.uri().path() != path {
+                return error(StatusCode::NOT_FOUND);
+            }
+            if let Some(wanted) = &auth_token {
+                let presented = req
+                    .headers()
+                    .get(header::AUTHORIZATION)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.strip_prefix("Bearer "));
+                if !presented.map_or(false, |token| token_matches(token, wanted)) {
+                    log::error!("websocket client did not present a valid auth token");
+                    return error(StatusCode::UNAUTHORIZED);
+                }
+            }
+            Ok(response)
+        };
+
+        let ws = tungstenite::accept_hdr(transport, check_request)
+            .map_err(|err| anyhow::anyhow!("websocket handshake failed: {}", err))?;
+        AsyncWebSocketStream::new(ws)
+    }
+}
+
+/// Compares the tokens in constant time so that the comparison
+/// doesn't reveal how much of the token was guessed correctly
+fn token_matches(presented: &str, wanted: &str) -> bool {
+    presented.len() == wanted.len() && openssl::memcmp::eq(presented.as_bytes(), wanted.as_bytes())
+}
+
+pub fn spawn_wss_listener(wss_server: &WssDomainServer) -> Result<(), Error> {
+    if wss_server.use_tls {
+        openssl::init();
+    } else {
+        // Without TLS there is no client certificate, so we require
+        // a token and only allow listening where a local reverse proxy
+        // can reach us
+        if wss_server.auth_token_file.is_none() {
+            anyhow::bail!(
+                "wss_servers entry for {} has use_tls = false, \
+                 which requires auth_token_file to be set",
+                wss_server.bind_address
+            );
+        }
+        for addr in wss_server
+            .bind_address
+            .to_socket_addrs()
+            .with_context(|| format!("resolving {}", wss_server.bind_address))?
+        {
+            if !addr.ip().is_loopback() {
+                anyhow::bail!(
+                    "wss_servers entry for {} has use_tls = false, \
+                     which is only permitted for loopback addresses",
+                    wss_server.bind_address
+                );
+            }
+        }
+    }
+
+    log::error!(
+        "listening with websockets on {:?} path {}",
+        wss_server.bind_address,
+        wss_server.path
+    );
+
+    let mut listener = WebSocketListener::new(
+        TcpListener::bind(&wss_server.bind_address).with_context(|| {
+            format!(
+                "error binding to wss_servers bind_address {}",
+                wss_server.bind_address,
+            )
+        })?,
+        wss_server,
+    )?;
+    std::thread::spawn(move || {
+        listener.run();
+    });
+    Ok(())
+}
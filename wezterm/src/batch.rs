@@ -0,0 +1,267 @@
+//! Implements the file format used by `wezterm cli spawn --batch`,
+//! which describes several tabs and splits that are all created by
+//! a single request to the mux server.
+use anyhow::{anyhow, Context};
+use config::keyassignment::SpawnTabDomain;
+use mux::tab::{SplitDirection, SplitRequest, SplitSize};
+use portable_pty::cmdbuilder::CommandBuilder;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Batch {
+    /// The default working directory for all of the panes
+    pub cwd: Option<PathBuf>,
+    /// The default domain for the tabs
+    pub domain: Option<String>,
+    #[serde(default)]
+    pub set_environment_variables: HashMap<String, String>,
+    pub tabs: Vec<BatchTab>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BatchTab {
+    #[serde(flatten)]
+    pub command: BatchCommand,
+    #[serde(default)]
+    pub splits: Vec<BatchSplit>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchDirection {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Default for BatchDirection {
+    fn default() -> Self {
+        Self::Bottom
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BatchSplit {
+    /// The pane to split, counting the panes of the tab in the order
+    /// that they are created.  The default is the previous pane.
+    pub pane: Option<usize>,
+    #[serde(default)]
+    pub direction: BatchDirection,
+    pub cells: Option<usize>,
+    pub percent: Option<u8>,
+    #[serde(default)]
+    pub top_level: bool,
+    #[serde(flatten)]
+    pub command: BatchCommand,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BatchCommand {
+    pub args: Option<Vec<String>>,
+    pub cwd: Option<PathBuf>,
+    pub domain: Option<String>,
+    #[serde(default)]
+    pub set_environment_variables: HashMap<String, String>,
+}
+
+impl Batch {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data = if path == Path::new("-") {
+            let mut data = String::new();
+            std::io::Read::read_to_string(&mut std::io::stdin(), &mut data)?;
+            data
+        } else {
+            std::fs::read_to_string(path).with_context(|| format!("reading {:?}", path))?
+        };
+        Self::parse(&data).with_context(|| format!("parsing {:?}", path))
+    }
+
+    pub fn parse(data: &str) -> anyhow::Result<Self> {
+        let batch: Self = serde_json::from_str(data)?;
+        if batch.tabs.is_empty() {
+            anyhow::bail!("the batch must have at least one tab");
+        }
+        for (tab_idx, tab) in batch.tabs.iter().enumerate() {
+            for (split_idx, split) in tab.splits.iter().enumerate() {
+                if split.cells.is_some() && split.percent.is_some() {
+                    anyhow::bail!(
+                        "split {} of tab {} specifies both cells and percent",
+                        split_idx,
+                        tab_idx
+                    );
+                }
+                if let Some(pane) = split.pane {
+                    // Pane 0 is the initial pane, and each split
+                    // before this one adds another
+                    if pane > split_idx {
+                        anyhow::bail!(
+                            "split {} of tab {} refers to pane {}, which has not been created yet",
+                            split_idx,
+                            tab_idx,
+                            pane
+                        );
+                    }
+                }
+            }
+        }
+        Ok(batch)
+    }
+
+    /// Converts the batch into the tabs of a SpawnBatch request.
+    /// Relative working directories are resolved against `cwd`.
+    pub fn to_tabs(&self, cwd: &Path) -> anyhow::Result<Vec<codec::SpawnBatchTab>> {
+        let cwd = match &self.cwd {
+            Some(dir) => cwd.join(dir),
+            None => cwd.to_path_buf(),
+        };
+        self.tabs
+            .iter()
+            .map(|tab| {
+                Ok(codec::SpawnBatchTab {
+                    domain: match tab.command.domain.as_ref().or(self.domain.as_ref()) {
+                        Some(name) => SpawnTabDomain::DomainName(name.clone()),
+                        None => SpawnTabDomain::DefaultDomain,
+                    },
+                    command: self.command_builder(&tab.command),
+                    command_dir: self.command_dir(&cwd, &tab.command)?,
+                    splits: tab
+                        .splits
+                        .iter()
+                        .map(|split| {
+                            Ok(codec::SpawnBatchSplit {
+                                target: split.pane,
+                                split_request: split_request(split),
+                                // Splits default to the domain of the pane
+                                // that they split
+                                domain: match &split.command.domain {
+                                    Some(name) => SpawnTabDomain::DomainName(name.clone()),
+                                    None => SpawnTabDomain::CurrentPaneDomain,
+                                },
+                                command: self.command_builder(&split.command),
+                                command_dir: self.command_dir(&cwd, &split.command)?,
+                            })
+                        })
+                        .collect::<anyhow::Result<Vec<_>>>()?,
+                })
+            })
+            .collect()
+    }
+
+    fn command_dir(&self, cwd: &Path, command: &BatchCommand) -> anyhow::Result<Option<String>> {
+        let dir = match &command.cwd {
+            Some(dir) => cwd.join(dir),
+            None if self.cwd.is_some() => cwd.to_path_buf(),
+            None => return Ok(None),
+        };
+        Ok(Some(
+            dir.to_str()
+                .ok_or_else(|| anyhow!("path {} is not representable as String", dir.display()))?
+                .to_string(),
+        ))
+    }
+
+    fn command_builder(&self, command: &BatchCommand) -> Option<CommandBuilder> {
+        if command.args.is_none()
+            && command.set_environment_variables.is_empty()
+            && self.set_environment_variables.is_empty()
+        {
+            return None;
+        }
+        let mut builder = match &command.args {
+            Some(args) => CommandBuilder::from_argv(args.iter().map(Into::into).collect()),
+            None => CommandBuilder::new_default_prog(),
+        };
+        for (k, v) in self
+            .set_environment_variables
+            .iter()
+            .chain(command.set_environment_variables.iter())
+        {
+            builder.env(k, v);
+        }
+        Some(builder)
+    }
+}
+
+fn split_request(split: &BatchSplit) -> SplitRequest {
+    SplitRequest {
+        direction: match split.direction {
+            BatchDirection::Left | BatchDirection::Right => SplitDirection::Horizontal,
+            BatchDirection::Top | BatchDirection::Bottom => SplitDirection::Vertical,
+        },
+        target_is_second: matches!(
+            split.direction,
+            BatchDirection::Right | BatchDirection::Bottom
+        ),
+        size: match (split.cells, split.percent) {
+            (Some(c), _) => SplitSize::Cells(c),
+            (_, Some(p)) => SplitSize::Percent(p),
+            (None, None) => SplitSize::Percent(50),
+        },
+        top_level: split.top_level,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_and_convert() {
+        let batch = Batch::parse(
+            r#"{
+                "cwd": "project",
+                "tabs": [
+                    {
+                        "args": ["nvim"],
+                        "splits": [
+                            {"direction": "right", "percent": 30, "cwd": "/tmp"},
+                            {"pane": 0, "args": ["cargo", "watch"]}
+                        ]
+                    },
+                    {"domain": "remote"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let tabs = batch.to_tabs(Path::new("/home/user")).unwrap();
+        assert_eq!(tabs.len(), 2);
+
+        let first = &tabs[0];
+        assert_eq!(first.command_dir.as_deref(), Some("/home/user/project"));
+        assert_eq!(first.splits[0].command_dir.as_deref(), Some("/tmp"));
+        assert_eq!(first.splits[0].target, None);
+        assert_eq!(
+            first.splits[0].split_request,
+            SplitRequest {
+                direction: SplitDirection::Horizontal,
+                target_is_second: true,
+                size: SplitSize::Percent(30),
+                top_level: false,
+            }
+        );
+        assert_eq!(first.splits[1].target, Some(0));
+        assert_eq!(
+            first.splits[1].split_request.direction,
+            SplitDirection::Vertical
+        );
+
+        assert_eq!(
+            tabs[1].domain,
+            SpawnTabDomain::DomainName("remote".to_string())
+        );
+        assert!(tabs[1].command.is_none());
+        assert_eq!(first.splits[0].domain, SpawnTabDomain::CurrentPaneDomain);
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        assert!(Batch::parse(r#"{"tabs": []}"#).is_err());
+        assert!(Batch::parse(r#"{"tabs": [{"splits": [{"pane": 1}]}]}"#).is_err());
+        assert!(Batch::parse(r#"{"tabs": [{"splits": [{"cells": 1, "percent": 2}]}]}"#).is_err());
+        assert!(Batch::parse(r#"{"tabs": [{}], "bogus": true}"#).is_err());
+    }
+}
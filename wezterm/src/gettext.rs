@@ -0,0 +1,137 @@
+//! Renders the lines of a pane as text for `wezterm cli get-text`
+use std::fmt::Write;
+use std::ops::Range;
+use termwiz::cell::{Blink, CellAttributes, Intensity, SemanticType, Underline};
+use termwiz::color::{ColorAttribute, ColorSpec};
+use termwiz::escape::csi::{Sgr, CSI};
+use termwiz::escape::osc::OperatingSystemCommand;
+use termwiz::surface::Line;
+
+fn color_spec(color: ColorAttribute) -> ColorSpec {
+    match color {
+        ColorAttribute::TrueColorWithPaletteFallback(color, _)
+        | ColorAttribute::TrueColorWithDefaultFallback(color) => ColorSpec::TrueColor(color),
+        ColorAttribute::PaletteIndex(idx) => ColorSpec::PaletteIndex(idx),
+        ColorAttribute::Default => ColorSpec::Default,
+    }
+}
+
+/// Returns a copy of attrs without the bits that don't affect
+/// how the text is rendered
+fn rendition(attrs: &CellAttributes) -> CellAttributes {
+    let mut attrs = attrs.clone();
+    attrs.set_wrapped(false);
+    attrs.set_semantic_type(SemanticType::Output);
+    attrs
+}
+
+/// Emits a reset followed by the SGR sequences required to
+/// produce attrs, so that each run of text is self contained
+fn push_sgr(out: &mut String, attrs: &CellAttributes) {
+    let mut sgr = vec![Sgr::Reset];
+    if attrs.intensity() != Intensity::Normal {
+        sgr.push(Sgr::Intensity(attrs.intensity()));
+    }
+    if attrs.underline() != Underline::None {
+        sgr.push(Sgr::Underline(attrs.underline()));
+    }
+    if attrs.underline_color() != ColorAttribute::Default {
+        sgr.push(Sgr::UnderlineColor(color_spec(attrs.underline_color())));
+    }
+    if attrs.blink() != Blink::None {
+        sgr.push(Sgr::Blink(attrs.blink()));
+    }
+    if attrs.italic() {
+        sgr.push(Sgr::Italic(true));
+    }
+    if attrs.reverse() {
+        sgr.push(Sgr::Inverse(true));
+    }
+    if attrs.invisible() {
+        sgr.push(Sgr::Invisible(true));
+    }
+    if attrs.strikethrough() {
+        sgr.push(Sgr::StrikeThrough(true));
+    }
+    if attrs.overline() {
+        sgr.push(Sgr::Overline(true));
+    }
+    if attrs.foreground() != ColorAttribute::Default {
+        sgr.push(Sgr::Foreground(color_spec(attrs.foreground())));
+    }
+    if attrs.background() != ColorAttribute::Default {
+        sgr.push(Sgr::Background(color_spec(attrs.background())));
+    }
+    for sgr in sgr {
+        write!(out, "{}", CSI::Sgr(sgr)).ok();
+    }
+}
+
+/// Returns the text of the cells of `line` whose column falls within
+/// `cols`.  If `escapes` is true, the text includes the SGR sequences
+/// that reproduce its colors and styling, and hyperlinks are included
+/// as OSC 8 sequences; otherwise trailing whitespace is removed.
+pub fn line_text(line: &Line, cols: Range<usize>, escapes: bool) -> String {
+    let mut out = String::new();
+    let mut current: Option<CellAttributes> = None;
+
+    for cell in line.visible_cells() {
+        let idx = cell.cell_index();
+        if idx < cols.start {
+            continue;
+        }
+        if idx >= cols.end {
+            break;
+        }
+
+        if escapes {
+            let attrs = rendition(cell.attrs());
+            if current.as_ref() != Some(&attrs) {
+                let prior_link = current.as_ref().and_then(|c| c.hyperlink());
+                if prior_link != attrs.hyperlink() {
+                    let link = attrs.hyperlink().map(|link| (**link).clone());
+                    write!(out, "{}", OperatingSystemCommand::SetHyperlink(link)).ok();
+                }
+                push_sgr(&mut out, &attrs);
+                current.replace(attrs);
+            }
+        }
+
+        out.push_str(cell.str());
+    }
+
+    if escapes {
+        if let Some(current) = current {
+            if current.hyperlink().is_some() {
+                write!(out, "{}", OperatingSystemCommand::SetHyperlink(None)).ok();
+            }
+            write!(out, "{}", CSI::Sgr(Sgr::Reset)).ok();
+        }
+    } else {
+        let len = out.trim_end().len();
+        out.truncate(len);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::surface::SEQ_ZERO;
+
+    #[test]
+    fn plain_and_escaped() {
+        let mut line = Line::from_text("hello", &CellAttributes::default(), SEQ_ZERO, None);
+        let mut bold = CellAttributes::default();
+        bold.set_intensity(Intensity::Bold);
+        line.append_line(Line::from_text(" world  ", &bold, SEQ_ZERO, None), SEQ_ZERO);
+
+        assert_eq!(line_text(&line, 0..usize::MAX, false), "hello world");
+        assert_eq!(line_text(&line, 2..7, false), "llo w");
+        assert_eq!(
+            line_text(&line, 3..8, true),
+            "\u{1b}[0mlo\u{1b}[0m\u{1b}[1m wo\u{1b}[0m"
+        );
+    }
+}
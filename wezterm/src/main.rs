@@ -14,6 +14,7 @@ use portable_pty::cmdbuilder::CommandBuilder;
 use serde::Serializer as _;
 use std::ffi::OsString;
 use std::io::{Read, Write};
+use std::path::PathBuf;
 use std::rc::Rc;
 use tabout::{tabulate_output, Alignment, Column};
 use umask::UmaskSaver;
@@ -22,6 +23,9 @@ use wezterm_gui_subcommands::*;
 use wezterm_term::TerminalSize;
 
 mod asciicast;
+mod batch;
+mod gettext;
+mod plugin;
 
 //    let message = "; ❤ 😍🤢\n\x1b[91;mw00t\n\x1b[37;104;m bleet\x1b[0;m.";
 
@@ -127,12 +131,21 @@ enum SubCommand {
     )]
     SetCwd(SetCwdCommand),
 
+    #[command(
+        name = "install-terminfo",
+        about = "Compile and install the wezterm terminfo entry for the current user"
+    )]
+    InstallTerminfo(InstallTerminfoCommand),
+
     #[command(name = "record", about = "Record a terminal session as an asciicast")]
     Record(asciicast::RecordCommand),
 
     #[command(name = "replay", about = "Replay an asciicast terminal session")]
     Replay(asciicast::PlayCommand),
 
+    #[command(name = "plugin", about = "Manage the plugins used by the config")]
+    Plugin(plugin::PluginCommand),
+
     /// Generate shell completion information
     #[command(name = "shell-completion")]
     ShellCompletion {
@@ -188,14 +201,132 @@ impl std::str::FromStr for CliOutputFormatKind {
 struct CliOutputFormat {
     /// Controls the output format.
     /// "table" and "json" are possible formats.
+    /// Commands that create a single item, such as spawn, print
+    /// just its id when using the "table" format.
     #[arg(long = "format", default_value = "table")]
     format: CliOutputFormatKind,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CliEventKind {
+    /// A pane produced output
+    PaneOutput,
+    /// A client focused a different pane
+    Focus,
+    /// The title of a pane changed
+    Title,
+    /// A window was moved to a different workspace, or a client
+    /// switched to a different workspace
+    Workspace,
+}
+
+impl From<CliEventKind> for codec::MuxEventKind {
+    fn from(kind: CliEventKind) -> codec::MuxEventKind {
+        match kind {
+            CliEventKind::PaneOutput => codec::MuxEventKind::PaneOutput,
+            CliEventKind::Focus => codec::MuxEventKind::Focus,
+            CliEventKind::Title => codec::MuxEventKind::Title,
+            CliEventKind::Workspace => codec::MuxEventKind::Workspace,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CliBracketedPaste {
+    /// Only if the application in the pane has enabled bracketed
+    /// paste mode
+    Auto,
+    /// Always, even if the application hasn't asked for it
+    Always,
+    /// Never
+    Never,
+}
+
+impl CliBracketedPaste {
+    fn bracketed(self) -> Option<bool> {
+        match self {
+            Self::Auto => None,
+            Self::Always => Some(true),
+            Self::Never => Some(false),
+        }
+    }
+}
+
+/// A rectangle expressed as `x0,y0,x1,y1` on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CliRegion {
+    x0: usize,
+    y0: isize,
+    x1: usize,
+    y1: isize,
+}
+
+impl std::str::FromStr for CliRegion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let fields: Vec<&str> = s.split(',').map(str::trim).collect();
+        if fields.len() != 4 {
+            anyhow::bail!("region must be specified as x0,y0,x1,y1, got {}", s);
+        }
+        let region = Self {
+            x0: fields[0].parse().context("x0")?,
+            y0: fields[1].parse().context("y0")?,
+            x1: fields[2].parse().context("x1")?,
+            y1: fields[3].parse().context("y1")?,
+        };
+        if region.x1 < region.x0 || region.y1 < region.y0 {
+            anyhow::bail!("region {} must have x0 <= x1 and y0 <= y1", s);
+        }
+        Ok(region)
+    }
+}
+
+#[derive(Debug, Parser, Clone)]
+enum TlsCredsAction {
+    /// Generate a new CA and server certificate for the mux server.
+    /// Certificates previously issued to clients will no longer be
+    /// accepted, so clients will need to bootstrap again via SSH.
+    #[command(name = "rotate")]
+    Rotate,
+}
+
+#[derive(Debug, Parser, Clone)]
+enum CliDebugAction {
+    /// Show the keypress-to-render latency, frame time and pty read
+    /// throughput measured for each pane.
+    /// Latency and frame times are only measured for panes that
+    /// are displayed by the gui that hosts the mux server.
+    #[command(name = "metrics", rename_all = "kebab")]
+    Metrics {
+        /// Only report the specified pane.
+        /// The default is to report all panes.
+        #[arg(long)]
+        pane_id: Option<PaneId>,
+
+        #[command(flatten)]
+        format: CliOutputFormat,
+    },
+}
+
 #[derive(Debug, Parser, Clone)]
 enum CliSubCommand {
     #[command(name = "list", about = "list windows, tabs and panes")]
-    List(CliOutputFormat),
+    List {
+        #[command(flatten)]
+        format: CliOutputFormat,
+
+        /// Only list panes that match the filter expression, which
+        /// is evaluated by the mux server.
+        /// For example: `workspace=dev && title~"vim"`.
+        /// The available fields are window_id, tab_id, pane_id,
+        /// workspace, title, cwd, is_active, is_zoomed and process.
+        /// `=` and `!=` compare strings, `~` and `!~` match
+        /// regular expressions, and terms can be combined using
+        /// `&&`, `||`, `!` and parentheses.
+        #[arg(long)]
+        filter: Option<String>,
+    },
 
     #[command(name = "list-clients", about = "list clients")]
     ListClients(CliOutputFormat),
@@ -204,7 +335,10 @@ enum CliSubCommand {
     Proxy,
 
     #[command(name = "tlscreds", about = "obtain tls credentials")]
-    TlsCreds,
+    TlsCreds {
+        #[command(subcommand)]
+        action: Option<TlsCredsAction>,
+    },
 
     #[command(
         name = "move-pane-to-new-tab",
@@ -234,6 +368,38 @@ enum CliSubCommand {
         /// with the provided name.  The default name is "default".
         #[arg(long)]
         workspace: Option<String>,
+
+        #[command(flatten)]
+        format: CliOutputFormat,
+    },
+
+    /// Re-create a tab in another domain, and close the original.
+    /// Use this to move a tab from the local domain into a multiplexer
+    /// domain so that it survives the GUI exiting, or to adopt it back.
+    /// The programs running in the tab are not moved: each of the new
+    /// panes runs the default program in the same directory as the
+    /// pane that it replaces.
+    #[command(name = "move-tab-to-domain", rename_all = "kebab")]
+    MoveTabToDomain {
+        /// Specify the tab that should be moved.
+        /// The default is to use the tab containing the current pane.
+        #[arg(long)]
+        tab_id: Option<mux::tab::TabId>,
+
+        /// Specify the current pane, whose tab is moved if --tab-id
+        /// is not specified.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[arg(long, conflicts_with = "tab_id")]
+        pane_id: Option<PaneId>,
+
+        /// The name of the domain into which the tab is moved.
+        /// The default is the default domain.
+        #[arg(long)]
+        domain_name: Option<String>,
+
+        #[command(flatten)]
+        format: CliOutputFormat,
     },
 
     #[command(
@@ -296,6 +462,9 @@ Outputs the pane-id for the newly created pane on success"
         #[arg(long, conflicts_with_all=&["cwd", "prog"])]
         move_pane_id: Option<PaneId>,
 
+        #[command(flatten)]
+        format: CliOutputFormat,
+
         /// Instead of executing your shell, run PROG.
         /// For example: `wezterm cli split-pane -- bash -l` will spawn bash
         /// as if it were a login shell.
@@ -343,6 +512,31 @@ Outputs the pane-id for the newly created pane on success"
         #[arg(long, requires = "new_window")]
         workspace: Option<String>,
 
+        /// Create a new workspace from the definition with the
+        /// given name in the `workspaces` configuration, creating
+        /// its tabs and panes.
+        /// Outputs the pane-id of the first pane in the workspace.
+        #[arg(
+            long,
+            conflicts_with_all=&["window_id", "new_window", "workspace", "cwd", "domain_name", "prog"]
+        )]
+        new_workspace_from: Option<String>,
+
+        /// Create the tabs and splits described by the JSON in FILE,
+        /// or read from stdin if FILE is `-`, in a single request.
+        /// If any of them fail to spawn, none of them are kept.
+        /// Outputs the pane-id of each of the new panes.
+        #[arg(
+            long,
+            value_name = "FILE",
+            value_hint=ValueHint::FilePath,
+            conflicts_with_all=&["cwd", "new_workspace_from", "prog"]
+        )]
+        batch: Option<PathBuf>,
+
+        #[command(flatten)]
+        format: CliOutputFormat,
+
         /// Instead of executing your shell, run PROG.
         /// For example: `wezterm cli spawn -- bash -l` will spawn bash
         /// as if it were a login shell.
@@ -361,14 +555,118 @@ Outputs the pane-id for the newly created pane on success"
         #[arg(long)]
         pane_id: Option<PaneId>,
 
+        /// Send the text to every pane that matches the filter
+        /// expression, rather than to a single pane.
+        /// See `wezterm cli list --help` for the syntax; the
+        /// `process` field matches the foreground process of the
+        /// pane, for example: `process~"n?vim$"`.
+        #[arg(long, conflicts_with = "pane_id")]
+        filter: Option<String>,
+
         /// Send the text directly, rather than as a bracketed paste.
-        #[arg(long)]
+        #[arg(long, conflicts_with = "bracketed_paste")]
         no_paste: bool,
 
+        /// Controls whether the text is enclosed in bracketed
+        /// paste sequences.
+        #[arg(long, value_enum, default_value = "auto")]
+        bracketed_paste: CliBracketedPaste,
+
         /// The text to send. If omitted, will read the text from stdin.
+        /// Text read from stdin is streamed to the pane in pieces,
+        /// waiting for each piece to be accepted before reading more.
         text: Option<String>,
     },
 
+    /// Print the text of a pane.
+    /// Lines are numbered relative to the top of the visible screen,
+    /// starting at 0; negative numbers refer to lines in the
+    /// scrollback.  The default is to print the visible screen.
+    #[command(name = "get-text", rename_all = "kebab")]
+    GetText {
+        /// Specify the pane to capture.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[arg(long)]
+        pane_id: Option<PaneId>,
+
+        /// The first line to capture.
+        #[arg(long, allow_hyphen_values = true)]
+        start_line: Option<isize>,
+
+        /// The last line to capture, inclusive.
+        /// The default is the bottom of the screen.
+        #[arg(long, allow_hyphen_values = true)]
+        end_line: Option<isize>,
+
+        /// Capture a rectangular region rather than whole lines,
+        /// specified as `x0,y0,x1,y1`, where x is a column and y is
+        /// a line number as for `--start-line`.  Both corners are
+        /// included in the region.
+        #[arg(long, allow_hyphen_values = true, conflicts_with_all=&["start_line", "end_line"])]
+        region: Option<CliRegion>,
+
+        /// Include the escape sequences that reproduce the colors,
+        /// styling and hyperlinks of the text.
+        #[arg(long)]
+        escapes: bool,
+    },
+
+    /// Render a pane as it is currently displayed by the gui and
+    /// save it as a PNG image.
+    #[command(name = "screenshot", rename_all = "kebab")]
+    Screenshot {
+        /// Specify the pane to capture.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[arg(long)]
+        pane_id: Option<PaneId>,
+
+        /// The file to which the image will be written.
+        /// If omitted, the image is written to stdout.
+        #[arg(long, value_parser, value_hint=ValueHint::FilePath)]
+        out: Option<OsString>,
+    },
+
+    /// Stream events from the mux server as newline delimited JSON,
+    /// one object per line, until the server goes away.
+    #[command(name = "subscribe", rename_all = "kebab")]
+    Subscribe {
+        /// Which events to report, separated by commas.
+        /// The default is to report all of them.
+        #[arg(long, value_enum, value_delimiter = ',')]
+        events: Vec<CliEventKind>,
+    },
+
+    /// Activate a pane, bringing its tab and window to the front.
+    #[command(name = "activate-pane", rename_all = "kebab")]
+    ActivatePane {
+        /// Specify the pane that should be activated.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[arg(long)]
+        pane_id: Option<PaneId>,
+    },
+
+    /// Resize a pane by moving the splits next to it.
+    /// The size is limited by the space needed by the other panes.
+    #[command(name = "resize-pane", rename_all = "kebab")]
+    ResizePane {
+        /// Specify the pane that should be resized.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[arg(long)]
+        pane_id: Option<PaneId>,
+
+        /// The number of columns that the pane should have
+        #[arg(long, required_unless_present = "rows")]
+        cols: Option<usize>,
+
+        /// The number of rows that the pane should have
+        #[arg(long)]
+        rows: Option<usize>,
+    },
+
     /// Activate an adjacent pane in the specified direction.
     #[command(name = "activate-pane-direction", rename_all = "kebab")]
     ActivatePaneDirection {
@@ -376,6 +674,13 @@ Outputs the pane-id for the newly created pane on success"
         #[arg(value_parser=PaneDirectionParser{})]
         direction: PaneDirection,
     },
+
+    /// Commands that help to diagnose problems with wezterm.
+    #[command(name = "debug")]
+    Debug {
+        #[command(subcommand)]
+        action: CliDebugAction,
+    },
 }
 
 #[derive(Clone, Copy)]
@@ -499,6 +804,57 @@ impl SetCwdCommand {
     }
 }
 
+#[derive(Debug, Parser, Clone)]
+struct InstallTerminfoCommand {
+    /// The terminfo directory to install into.
+    /// If omitted, `~/.terminfo` will be used.
+    #[arg(long, value_parser, value_hint=ValueHint::DirPath)]
+    output_dir: Option<PathBuf>,
+
+    /// Print the terminfo source rather than installing it.
+    /// This is useful for installing it on another host, for
+    /// example: `wezterm install-terminfo --print-source | ssh HOST tic -x /dev/stdin`
+    #[arg(long)]
+    print_source: bool,
+}
+
+impl InstallTerminfoCommand {
+    fn run(&self) -> anyhow::Result<()> {
+        if self.print_source {
+            print!("{}", mux::terminfo::WEZTERM_TERMINFO);
+            return Ok(());
+        }
+
+        let output_dir = self
+            .output_dir
+            .clone()
+            .unwrap_or_else(|| config::HOME_DIR.join(".terminfo"));
+        std::fs::create_dir_all(&output_dir)
+            .with_context(|| format!("creating {}", output_dir.display()))?;
+
+        let mut source = tempfile::NamedTempFile::new()?;
+        source.write_all(mux::terminfo::WEZTERM_TERMINFO.as_bytes())?;
+        source.flush()?;
+
+        let status = std::process::Command::new("tic")
+            .arg("-x")
+            .arg("-o")
+            .arg(&output_dir)
+            .arg(source.path())
+            .status()
+            .context("running tic; is it installed?")?;
+        if !status.success() {
+            anyhow::bail!("tic failed: {}", status);
+        }
+
+        println!(
+            "Installed the wezterm terminfo entry into {}",
+            output_dir.display()
+        );
+        Ok(())
+    }
+}
+
 fn resolve_relative_cwd(cwd: Option<OsString>) -> anyhow::Result<Option<String>> {
     match cwd {
         None => Ok(None),
@@ -557,9 +913,11 @@ fn run() -> anyhow::Result<()> {
         | SubCommand::Connect(_) => delegate_to_gui(saver),
         SubCommand::ImageCat(cmd) => cmd.run(),
         SubCommand::SetCwd(cmd) => cmd.run(),
+        SubCommand::InstallTerminfo(cmd) => cmd.run(),
         SubCommand::Cli(cli) => run_cli(config, cli),
         SubCommand::Record(cmd) => cmd.run(config),
         SubCommand::Replay(cmd) => cmd.run(),
+        SubCommand::Plugin(cmd) => cmd.run(),
         SubCommand::ShellCompletion { shell } => {
             use clap::CommandFactory;
             let mut cmd = Opt::command();
@@ -617,6 +975,103 @@ fn delegate_to_gui(saver: UmaskSaver) -> anyhow::Result<()> {
     }
 }
 
+/// How much text to read from stdin at a time when streaming it
+/// to a pane via `wezterm cli send-text`
+const SEND_TEXT_CHUNK_SIZE: usize = 32 * 1024;
+
+/// Reads valid UTF-8 text from a reader in chunks, taking care not
+/// to split a multi-byte sequence or a CRLF pair across chunks
+struct Utf8ChunkReader<R> {
+    reader: R,
+    pending: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> Utf8ChunkReader<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pending: vec![],
+            eof: false,
+        }
+    }
+
+    /// Returns the next chunk of text, or None at EOF
+    fn next_chunk(&mut self) -> anyhow::Result<Option<String>> {
+        loop {
+            if !self.eof {
+                let mut buf = vec![0u8; SEND_TEXT_CHUNK_SIZE];
+                let len = self.reader.read(&mut buf)?;
+                if len == 0 {
+                    self.eof = true;
+                } else {
+                    self.pending.extend_from_slice(&buf[..len]);
+                }
+            }
+
+            let mut len = match std::str::from_utf8(&self.pending) {
+                Ok(_) => self.pending.len(),
+                Err(err) if err.error_len().is_none() && !self.eof => err.valid_up_to(),
+                Err(err) => anyhow::bail!("input is not valid UTF-8: {}", err),
+            };
+            if !self.eof && self.pending[..len].ends_with(b"\r") {
+                // Hold back the CR in case the next chunk starts with LF
+                len -= 1;
+            }
+
+            if len == 0 {
+                if self.eof {
+                    return Ok(None);
+                }
+                continue;
+            }
+
+            let remainder = self.pending.split_off(len);
+            let text = std::mem::replace(&mut self.pending, remainder);
+            return Ok(Some(String::from_utf8(text)?));
+        }
+    }
+}
+
+/// The panes to which `wezterm cli send-text` sends text, along
+/// with whether the paste is bracketed for each of them, which is
+/// decided by the server when the first chunk is sent in auto mode
+struct SendTextTargets<'a> {
+    client: &'a Client,
+    panes: Vec<(PaneId, Option<bool>)>,
+    no_paste: bool,
+}
+
+impl<'a> SendTextTargets<'a> {
+    async fn send(&mut self, data: String, is_first: bool, is_last: bool) -> anyhow::Result<()> {
+        for (pane_id, bracketed) in self.panes.iter_mut() {
+            if self.no_paste {
+                if !data.is_empty() {
+                    self.client
+                        .write_to_pane(codec::WriteToPane {
+                            pane_id: *pane_id,
+                            data: data.as_bytes().to_vec(),
+                        })
+                        .await?;
+                }
+            } else {
+                let response = self
+                    .client
+                    .send_paste_chunk(codec::SendPasteChunk {
+                        pane_id: *pane_id,
+                        data: data.clone(),
+                        bracketed: *bracketed,
+                        is_first,
+                        is_last,
+                    })
+                    .await?;
+                bracketed.replace(response.bracketed);
+            }
+        }
+        Ok(())
+    }
+}
+
 async fn resolve_pane_id(client: &Client, pane_id: Option<PaneId>) -> anyhow::Result<PaneId> {
     let pane_id: PaneId = match pane_id {
         Some(p) => p,
@@ -733,6 +1188,53 @@ impl From<mux::tab::PaneEntry> for CliListResultItem {
     }
 }
 
+// This will be serialized to JSON via the 'debug metrics' command.
+// As such it is intended to be a stable output format,
+// Thus we need to be careful about the stability of the fields and types
+// herein as they are directly reflected in the output.
+#[derive(serde::Serialize)]
+struct CliLatencyItem {
+    samples: usize,
+    p50_ms: f64,
+    p95_ms: f64,
+    p99_ms: f64,
+    max_ms: f64,
+}
+
+impl From<mux::panestats::LatencySummary> for CliLatencyItem {
+    fn from(summary: mux::panestats::LatencySummary) -> CliLatencyItem {
+        let ms = |d: std::time::Duration| d.as_secs_f64() * 1000.;
+        CliLatencyItem {
+            samples: summary.samples,
+            p50_ms: ms(summary.p50),
+            p95_ms: ms(summary.p95),
+            p99_ms: ms(summary.p99),
+            max_ms: ms(summary.max),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct CliPaneMetricsItem {
+    pane_id: PaneId,
+    input_latency: CliLatencyItem,
+    frame_time: CliLatencyItem,
+    pty_bytes_per_second: u64,
+    pty_bytes_total: u64,
+}
+
+impl From<mux::panestats::PaneMetrics> for CliPaneMetricsItem {
+    fn from(metrics: mux::panestats::PaneMetrics) -> CliPaneMetricsItem {
+        CliPaneMetricsItem {
+            pane_id: metrics.pane_id,
+            input_latency: metrics.input_latency.into(),
+            frame_time: metrics.frame_time.into(),
+            pty_bytes_per_second: metrics.pty_bytes_per_second,
+            pty_bytes_total: metrics.pty_bytes_total,
+        }
+    }
+}
+
 // This will be serialized to JSON via the 'ListClients' command.
 // As such it is intended to be a stable output format,
 // Thus we need to be careful about the stability of the fields and types
@@ -785,6 +1287,145 @@ impl From<mux::client::ClientInfo> for CliListClientsResultItem {
     }
 }
 
+// This will be serialized to JSON by the commands that spawn panes.
+// As such it is intended to be a stable output format,
+// Thus we need to be careful about the stability of the fields and types
+// herein as they are directly reflected in the output.
+#[derive(serde::Serialize)]
+struct CliSpawnResultItem {
+    window_id: mux::window::WindowId,
+    tab_id: mux::tab::TabId,
+    pane_id: mux::pane::PaneId,
+}
+
+// This will be serialized to JSON via the 'MovePaneToNewTab' command.
+// As such it is intended to be a stable output format,
+// Thus we need to be careful about the stability of the fields and types
+// herein as they are directly reflected in the output.
+#[derive(serde::Serialize)]
+struct CliMovePaneResultItem {
+    window_id: mux::window::WindowId,
+    tab_id: mux::tab::TabId,
+}
+
+// This will be serialized to JSON via the 'Subscribe' command.
+// As such it is intended to be a stable output format,
+// Thus we need to be careful about the stability of the fields and types
+// herein as they are directly reflected in the output.
+#[derive(serde::Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+enum CliEventItem {
+    PaneOutput {
+        pane_id: mux::pane::PaneId,
+    },
+    Focus {
+        pane_id: mux::pane::PaneId,
+    },
+    Title {
+        pane_id: mux::pane::PaneId,
+        title: String,
+    },
+    WindowWorkspace {
+        window_id: mux::window::WindowId,
+        workspace: String,
+    },
+    ActiveWorkspace {
+        username: String,
+        hostname: String,
+        pid: u32,
+        workspace: String,
+    },
+}
+
+impl From<codec::MuxEvent> for CliEventItem {
+    fn from(event: codec::MuxEvent) -> CliEventItem {
+        match event {
+            codec::MuxEvent::PaneOutput { pane_id } => CliEventItem::PaneOutput { pane_id },
+            codec::MuxEvent::PaneFocused { pane_id } => CliEventItem::Focus { pane_id },
+            codec::MuxEvent::PaneTitleChanged { pane_id, title } => {
+                CliEventItem::Title { pane_id, title }
+            }
+            codec::MuxEvent::WindowWorkspaceChanged {
+                window_id,
+                workspace,
+            } => CliEventItem::WindowWorkspace {
+                window_id,
+                workspace,
+            },
+            codec::MuxEvent::ActiveWorkspaceChanged {
+                client_id,
+                workspace,
+            } => CliEventItem::ActiveWorkspace {
+                username: client_id.username,
+                hostname: client_id.hostname,
+                pid: client_id.pid,
+                workspace,
+            },
+        }
+    }
+}
+
+fn print_json<T: serde::Serialize>(value: &T) -> anyhow::Result<()> {
+    let out = std::io::stdout();
+    let mut out = out.lock();
+    serde_json::to_writer_pretty(&mut out, value)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+fn print_spawned(
+    format: CliOutputFormatKind,
+    spawned: &codec::SpawnResponse,
+) -> anyhow::Result<()> {
+    match format {
+        CliOutputFormatKind::Json => print_json(&CliSpawnResultItem {
+            window_id: spawned.window_id,
+            tab_id: spawned.tab_id,
+            pane_id: spawned.pane_id,
+        }),
+        CliOutputFormatKind::Table => {
+            println!("{}", spawned.pane_id);
+            Ok(())
+        }
+    }
+}
+
+/// Returns the window that contains the pane, which defaults to the
+/// current pane
+async fn window_id_for_pane(
+    client: &Client,
+    pane_id: Option<PaneId>,
+) -> anyhow::Result<Option<WindowId>> {
+    Ok(find_pane_entry(client, pane_id)
+        .await?
+        .map(|entry| entry.window_id))
+}
+
+async fn find_pane_entry(
+    client: &Client,
+    pane_id: Option<PaneId>,
+) -> anyhow::Result<Option<mux::tab::PaneEntry>> {
+    let pane_id = resolve_pane_id(client, pane_id).await?;
+
+    let panes = client.list_panes().await?;
+    for tabroot in panes.tabs {
+        let mut cursor = tabroot.into_tree().cursor();
+
+        loop {
+            if let Some(entry) = cursor.leaf_mut() {
+                if entry.pane_id == pane_id {
+                    return Ok(Some(entry.clone()));
+                }
+            }
+            match cursor.preorder_next() {
+                Ok(c) => cursor = c,
+                Err(_) => break,
+            }
+        }
+    }
+    Ok(None)
+}
+
 async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow::Result<()> {
     let mut ui = mux::connui::ConnectionUI::new_headless();
     let initial = true;
@@ -875,25 +1516,25 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                 }
             }
         }
-        CliSubCommand::List(CliOutputFormat { format }) => {
+        CliSubCommand::List {
+            format: CliOutputFormat { format },
+            filter,
+        } => {
             let out = std::io::stdout();
 
-            let mut output_items = vec![];
-            let panes = client.list_panes().await?;
-
-            for tabroot in panes.tabs {
-                let mut cursor = tabroot.into_tree().cursor();
-
-                loop {
-                    if let Some(entry) = cursor.leaf_mut() {
-                        output_items.push(CliListResultItem::from(entry.clone()));
-                    }
-                    match cursor.preorder_next() {
-                        Ok(c) => cursor = c,
-                        Err(_) => break,
-                    }
-                }
+            if let Some(filter) = &filter {
+                // Validate locally so that syntax errors are reported
+                // without the round trip
+                filter.parse::<mux::query::PaneQuery>()?;
             }
+
+            let output_items: Vec<CliListResultItem> = client
+                .list_pane_entries(codec::ListPaneEntries { filter })
+                .await?
+                .panes
+                .into_iter()
+                .map(CliListResultItem::from)
+                .collect();
             match format {
                 CliOutputFormatKind::Json => {
                     let mut writer = serde_json::Serializer::pretty(out.lock());
@@ -953,6 +1594,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             window_id,
             new_window,
             workspace,
+            format: CliOutputFormat { format },
         } => {
             let pane_id = resolve_pane_id(&client, pane_id).await?;
             let window_id = if new_window {
@@ -993,6 +1635,46 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                 .await?;
 
             log::debug!("{:?}", moved);
+            if let CliOutputFormatKind::Json = format {
+                print_json(&CliMovePaneResultItem {
+                    window_id: moved.window_id,
+                    tab_id: moved.tab_id,
+                })?;
+            }
+        }
+        CliSubCommand::MoveTabToDomain {
+            tab_id,
+            pane_id,
+            domain_name,
+            format: CliOutputFormat { format },
+        } => {
+            let tab_id = match tab_id {
+                Some(tab_id) => tab_id,
+                None => {
+                    find_pane_entry(&client, pane_id)
+                        .await?
+                        .ok_or_else(|| anyhow!("unable to resolve the current tab"))?
+                        .tab_id
+                }
+            };
+            let moved = client
+                .move_tab_to_domain(codec::MoveTabToDomain {
+                    tab_id,
+                    domain: match domain_name {
+                        Some(name) => SpawnTabDomain::DomainName(name),
+                        None => SpawnTabDomain::DefaultDomain,
+                    },
+                })
+                .await?;
+
+            log::debug!("{:?}", moved);
+            match format {
+                CliOutputFormatKind::Json => print_json(&CliMovePaneResultItem {
+                    window_id: moved.window_id,
+                    tab_id: moved.tab_id,
+                })?,
+                CliOutputFormatKind::Table => println!("{}", moved.tab_id),
+            }
         }
         CliSubCommand::SplitPane {
             pane_id,
@@ -1007,6 +1689,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             cells,
             percent,
             move_pane_id,
+            format: CliOutputFormat { format },
         } => {
             let pane_id = resolve_pane_id(&client, pane_id).await?;
 
@@ -1048,77 +1731,201 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                 .await?;
 
             log::debug!("{:?}", spawned);
-            println!("{}", spawned.pane_id);
+            print_spawned(format, &spawned)?;
         }
         CliSubCommand::SendText {
             pane_id,
+            filter,
             text,
             no_paste,
+            bracketed_paste,
         } => {
-            let pane_id = resolve_pane_id(&client, pane_id).await?;
+            let pane_ids = match filter {
+                Some(filter) => {
+                    filter.parse::<mux::query::PaneQuery>()?;
+                    let panes = client
+                        .list_pane_entries(codec::ListPaneEntries {
+                            filter: Some(filter.clone()),
+                        })
+                        .await?
+                        .panes;
+                    if panes.is_empty() {
+                        anyhow::bail!("no panes matched {}", filter);
+                    }
+                    panes.into_iter().map(|entry| entry.pane_id).collect()
+                }
+                None => vec![resolve_pane_id(&client, pane_id).await?],
+            };
+
+            let mut targets = SendTextTargets {
+                client: &client,
+                panes: pane_ids
+                    .into_iter()
+                    .map(|pane_id| (pane_id, bracketed_paste.bracketed()))
+                    .collect(),
+                no_paste,
+            };
 
-            let data = match text {
-                Some(text) => text,
+            match text {
+                Some(text) => targets.send(text, true, true).await?,
                 None => {
-                    let mut text = String::new();
-                    std::io::stdin()
-                        .read_to_string(&mut text)
-                        .context("reading stdin")?;
-                    text
+                    let mut stdin = Utf8ChunkReader::new(std::io::stdin());
+                    let mut is_first = true;
+                    while let Some(chunk) = stdin.next_chunk().context("reading stdin")? {
+                        targets.send(chunk, is_first, false).await?;
+                        is_first = false;
+                    }
+                    // Let the pane know that the paste is complete
+                    targets.send(String::new(), is_first, true).await?;
                 }
+            }
+        }
+        CliSubCommand::GetText {
+            pane_id,
+            start_line,
+            end_line,
+            region,
+            escapes,
+        } => {
+            let pane_id = resolve_pane_id(&client, pane_id).await?;
+            let entry = client
+                .list_pane_entries(codec::ListPaneEntries {
+                    filter: Some(format!("pane_id={}", pane_id)),
+                })
+                .await?
+                .panes
+                .pop()
+                .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+
+            let (first, last, cols) = match region {
+                Some(r) => (r.y0, r.y1, r.x0..r.x1 + 1),
+                None => (
+                    start_line.unwrap_or(0),
+                    end_line.unwrap_or(entry.size.rows as isize - 1),
+                    0..usize::MAX,
+                ),
             };
+            if last < first {
+                anyhow::bail!("the end line must not be before the start line");
+            }
 
-            if no_paste {
-                client
-                    .write_to_pane(codec::WriteToPane {
-                        pane_id,
-                        data: data.as_bytes().to_vec(),
-                    })
-                    .await?;
-            } else {
-                client
-                    .send_paste(codec::SendPaste { pane_id, data })
-                    .await?;
+            let top = entry.physical_top;
+            let lines = client
+                .get_lines(codec::GetLines {
+                    pane_id,
+                    lines: vec![top + first..top + last + 1],
+                })
+                .await?;
+            let (lines, _images) = lines.lines.extract_data();
+
+            let mut out = std::io::stdout().lock();
+            for (_, line) in lines {
+                writeln!(out, "{}", gettext::line_text(&line, cols.clone(), escapes))?;
+            }
+        }
+        CliSubCommand::Screenshot { pane_id, out } => {
+            let pane_id = resolve_pane_id(&client, pane_id).await?;
+            let image = client
+                .capture_image(codec::CaptureImage { pane_id })
+                .await?;
+            match out {
+                Some(out) => std::fs::write(&out, &image.data)
+                    .with_context(|| format!("writing {:?}", out))?,
+                None => std::io::stdout().write_all(&image.data)?,
             }
         }
         CliSubCommand::SpawnCommand {
-            cwd,
-            prog,
+            new_workspace_from: Some(name),
+            format: CliOutputFormat { format },
+            ..
+        } => {
+            let spawned = client
+                .spawn_workspace(codec::SpawnWorkspace {
+                    name,
+                    size: config.initial_size(0),
+                })
+                .await?;
+
+            log::debug!("{:?}", spawned);
+            print_spawned(format, &spawned)?;
+        }
+        CliSubCommand::SpawnCommand {
+            batch: Some(path),
             pane_id,
             domain_name,
             window_id,
             new_window,
             workspace,
+            format: CliOutputFormat { format },
+            ..
         } => {
+            let mut batch = batch::Batch::load(&path)?;
+            if batch.domain.is_none() {
+                batch.domain = domain_name;
+            }
+            let tabs = batch.to_tabs(&std::env::current_dir()?)?;
+
             let window_id = if new_window {
                 None
             } else {
                 match window_id {
                     Some(w) => Some(w),
-                    None => {
-                        let pane_id = resolve_pane_id(&client, pane_id).await?;
+                    None => window_id_for_pane(&client, pane_id).await?,
+                }
+            };
 
-                        let panes = client.list_panes().await?;
-                        let mut window_id = None;
-                        'outer: for tabroot in panes.tabs {
-                            let mut cursor = tabroot.into_tree().cursor();
+            let spawned = client
+                .spawn_batch(codec::SpawnBatch {
+                    window_id,
+                    workspace: workspace.unwrap_or_else(|| mux::DEFAULT_WORKSPACE.to_string()),
+                    size: config.initial_size(0),
+                    tabs,
+                })
+                .await?;
 
-                            loop {
-                                if let Some(entry) = cursor.leaf_mut() {
-                                    if entry.pane_id == pane_id {
-                                        window_id.replace(entry.window_id);
-                                        break 'outer;
-                                    }
-                                }
-                                match cursor.preorder_next() {
-                                    Ok(c) => cursor = c,
-                                    Err(_) => break,
-                                }
-                            }
+            log::debug!("{:?}", spawned);
+            match format {
+                CliOutputFormatKind::Json => {
+                    let mut items = vec![];
+                    for tab in &spawned.tabs {
+                        for pane_id in &tab.pane_ids {
+                            items.push(CliSpawnResultItem {
+                                window_id: spawned.window_id,
+                                tab_id: tab.tab_id,
+                                pane_id: *pane_id,
+                            });
+                        }
+                    }
+                    print_json(&items)?;
+                }
+                CliOutputFormatKind::Table => {
+                    for tab in &spawned.tabs {
+                        for pane_id in &tab.pane_ids {
+                            println!("{}", pane_id);
                         }
-                        window_id
                     }
                 }
+            }
+        }
+        CliSubCommand::SpawnCommand {
+            cwd,
+            prog,
+            pane_id,
+            domain_name,
+            window_id,
+            new_window,
+            workspace,
+            new_workspace_from: None,
+            batch: None,
+            format: CliOutputFormat { format },
+        } => {
+            let window_id = if new_window {
+                None
+            } else {
+                match window_id {
+                    Some(w) => Some(w),
+                    None => window_id_for_pane(&client, pane_id).await?,
+                }
             };
 
             let workspace = workspace.unwrap_or_else(|| mux::DEFAULT_WORKSPACE.to_string());
@@ -1144,7 +1951,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                 .await?;
 
             log::debug!("{:?}", spawned);
-            println!("{}", spawned.pane_id);
+            print_spawned(format, &spawned)?;
         }
         CliSubCommand::Proxy => {
             // The client object we created above will have spawned
@@ -1177,16 +1984,126 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             // Wait forever; the stdio threads will terminate on EOF
             smol::future::pending().await
         }
-        CliSubCommand::TlsCreds => {
+        CliSubCommand::TlsCreds { action: None } => {
             let creds = client.get_tls_creds().await?;
             codec::Pdu::GetTlsCredsResponse(creds).encode(std::io::stdout().lock(), 0)?;
         }
+        CliSubCommand::TlsCreds {
+            action: Some(TlsCredsAction::Rotate),
+        } => {
+            client.rotate_tls_creds().await?;
+            println!("TLS credentials rotated; clients will need to bootstrap again");
+        }
+        CliSubCommand::Subscribe { events } => {
+            let kinds = if events.is_empty() {
+                CliEventKind::value_variants()
+                    .iter()
+                    .map(|&kind| kind.into())
+                    .collect()
+            } else {
+                events.into_iter().map(Into::into).collect()
+            };
+            let events = client.subscribe_to_events(kinds).await?;
+
+            let out = std::io::stdout();
+            while let Ok(event) = events.recv().await {
+                let mut out = out.lock();
+                serde_json::to_writer(&mut out, &CliEventItem::from(event))?;
+                writeln!(out)?;
+                out.flush()?;
+            }
+        }
+        CliSubCommand::ActivatePane { pane_id } => {
+            let pane_id = resolve_pane_id(&client, pane_id).await?;
+            client
+                .activate_pane(codec::ActivatePane { pane_id })
+                .await?;
+        }
+        CliSubCommand::ResizePane {
+            pane_id,
+            cols,
+            rows,
+        } => {
+            let pane_id = resolve_pane_id(&client, pane_id).await?;
+            client
+                .resize_pane(codec::ResizePane {
+                    pane_id,
+                    cols,
+                    rows,
+                })
+                .await?;
+        }
         CliSubCommand::ActivatePaneDirection { direction } => {
             let pane_id = resolve_pane_id(&client, None).await?;
             client
                 .activate_pane_direction(codec::ActivatePaneDirection { pane_id, direction })
                 .await?;
         }
+        CliSubCommand::Debug {
+            action:
+                CliDebugAction::Metrics {
+                    pane_id,
+                    format: CliOutputFormat { format },
+                },
+        } => {
+            let metrics = client
+                .get_pane_metrics(codec::GetPaneMetrics { pane_id })
+                .await?;
+            match format {
+                CliOutputFormatKind::Json => {
+                    let out = std::io::stdout();
+                    let mut writer = serde_json::Serializer::pretty(out.lock());
+                    writer.collect_seq(metrics.panes.into_iter().map(CliPaneMetricsItem::from))?;
+                }
+                CliOutputFormatKind::Table => {
+                    let cols = [
+                        ("PANE", Alignment::Right),
+                        ("KEY->RENDER p50", Alignment::Right),
+                        ("p95", Alignment::Right),
+                        ("p99", Alignment::Right),
+                        ("FRAME p50", Alignment::Right),
+                        ("p95", Alignment::Right),
+                        ("p99", Alignment::Right),
+                        ("PTY KiB/s", Alignment::Right),
+                    ]
+                    .iter()
+                    .map(|&(name, alignment)| Column {
+                        name: name.to_string(),
+                        alignment,
+                    })
+                    .collect::<Vec<_>>();
+
+                    fn latency(
+                        summary: &mux::panestats::LatencySummary,
+                        d: std::time::Duration,
+                    ) -> String {
+                        if summary.samples == 0 {
+                            "-".to_string()
+                        } else {
+                            format!("{:.1?}", d)
+                        }
+                    }
+
+                    let data = metrics
+                        .panes
+                        .iter()
+                        .map(|m| {
+                            vec![
+                                m.pane_id.to_string(),
+                                latency(&m.input_latency, m.input_latency.p50),
+                                latency(&m.input_latency, m.input_latency.p95),
+                                latency(&m.input_latency, m.input_latency.p99),
+                                latency(&m.frame_time, m.frame_time.p50),
+                                latency(&m.frame_time, m.frame_time.p95),
+                                latency(&m.frame_time, m.frame_time.p99),
+                                format!("{:.1}", m.pty_bytes_per_second as f64 / 1024.),
+                            ]
+                        })
+                        .collect::<Vec<_>>();
+                    tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
+                }
+            }
+        }
     }
     Ok(())
 }
@@ -0,0 +1,82 @@
+use clap::{Parser, Subcommand};
+use tabout::{tabulate_output, Alignment, Column};
+
+#[derive(Debug, Parser, Clone)]
+pub struct PluginCommand {
+    #[command(subcommand)]
+    sub: PluginSubCommand,
+}
+
+#[derive(Debug, Subcommand, Clone)]
+enum PluginSubCommand {
+    #[command(name = "list", about = "List the installed plugins")]
+    List,
+
+    #[command(
+        name = "update",
+        about = "Update plugins to the newest version that satisfies \
+                 their version requirement and record it in plugins.lock"
+    )]
+    Update {
+        /// The url of the plugin to update.
+        /// If omitted, all plugins are updated.
+        url: Option<String>,
+    },
+
+    #[command(name = "remove", about = "Remove an installed plugin")]
+    Remove {
+        /// The url of the plugin to remove
+        url: String,
+
+        /// Keep the data directory of the plugin
+        #[arg(long)]
+        keep_data: bool,
+    },
+}
+
+fn print_plugins(plugins: Vec<plugin::PluginInfo>) -> anyhow::Result<()> {
+    let cols = vec![
+        Column {
+            name: "URL".to_string(),
+            alignment: Alignment::Left,
+        },
+        Column {
+            name: "VERSION".to_string(),
+            alignment: Alignment::Left,
+        },
+        Column {
+            name: "TAG".to_string(),
+            alignment: Alignment::Left,
+        },
+        Column {
+            name: "COMMIT".to_string(),
+            alignment: Alignment::Left,
+        },
+    ];
+    let data: Vec<Vec<String>> = plugins
+        .into_iter()
+        .map(|info| {
+            vec![
+                info.url,
+                info.version.unwrap_or_else(|| "*".to_string()),
+                info.tag.unwrap_or_default(),
+                info.commit.chars().take(12).collect(),
+            ]
+        })
+        .collect();
+    tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
+    Ok(())
+}
+
+impl PluginCommand {
+    pub fn run(&self) -> anyhow::Result<()> {
+        match &self.sub {
+            PluginSubCommand::List => print_plugins(plugin::list_plugins()?),
+            PluginSubCommand::Update { url: Some(url) } => {
+                print_plugins(vec![plugin::update_plugin(url)?])
+            }
+            PluginSubCommand::Update { url: None } => print_plugins(plugin::update_all()?),
+            PluginSubCommand::Remove { url, keep_data } => plugin::remove_plugin(url, *keep_data),
+        }
+    }
+}
@@ -38,6 +38,22 @@ fn main() {
     }
     .unwrap();
 
+    // The wayland-protocols crate doesn't yet provide these protocols,
+    // so we generate the bindings for them ourselves
+    #[cfg(feature = "wayland")]
+    {
+        use wayland_scanner::{generate_code, Side};
+        for name in &["fractional-scale-v1"] {
+            let xml = format!("protocols/{}.xml", name);
+            println!("cargo:rerun-if-changed={}", xml);
+            generate_code(
+                &xml,
+                dest.join(format!("{}_client_api.rs", name.replace('-', "_"))),
+                Side::Client,
+            );
+        }
+    }
+
     if target.contains("apple") {
         println!("cargo:rustc-link-lib=framework=Carbon");
     }
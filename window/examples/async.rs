@@ -84,6 +84,9 @@ impl MyWindow {
             | WindowEvent::FocusChanged(_)
             | WindowEvent::DraggedFile(_)
             | WindowEvent::DroppedFile(_)
+            | WindowEvent::NewNativeTabRequested
+            | WindowEvent::TouchBarItemPressed(_)
+            | WindowEvent::ContextMenuItemSelected(_)
             | WindowEvent::MouseLeave => {}
         }
     }
@@ -1,5 +1,8 @@
 use crate::screen::Screens;
-use crate::{Appearance, Connection, GeometryOrigin, RequestedWindowGeometry, ResolvedGeometry};
+use crate::{
+    Appearance, Connection, GeometryOrigin, RequestedWindowGeometry, ResolvedGeometry,
+    ShellMenuGroup,
+};
 use anyhow::Result as Fallible;
 use config::DimensionContext;
 use std::cell::RefCell;
@@ -51,6 +54,18 @@ pub trait ConnectionOps {
     /// Perform the system beep/notification sound
     fn beep(&self) {}
 
+    /// Ask the user to authenticate, using Touch ID or their password,
+    /// in order to proceed with the action described by `reason`.
+    /// The future resolves to true if they did so.
+    /// Returns None if this is not supported by the system.
+    fn authenticate_user(&self, _reason: &str) -> Option<promise::Future<bool>> {
+        None
+    }
+
+    /// Replace the menu that the operating system shell presents for
+    /// the application with `groups`
+    fn set_shell_menu(&self, _groups: Vec<ShellMenuGroup>) {}
+
     /// Returns information about the screens
     fn screens(&self) -> anyhow::Result<Screens> {
         anyhow::bail!("Unable to query screen information");
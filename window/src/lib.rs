@@ -46,6 +46,48 @@ impl Default for Clipboard {
     }
 }
 
+/// Text to be placed on a clipboard, along with formatted versions
+/// of it for the applications that understand them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClipboardContent {
+    pub text: String,
+    /// A fragment of HTML, offered as `text/html`
+    pub html: Option<String>,
+    /// An RTF document.  Only offered on macOS and Windows.
+    pub rtf: Option<String>,
+}
+
+impl From<String> for ClipboardContent {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            ..Default::default()
+        }
+    }
+}
+
+/// What is dragged out of a window by `WindowOps::start_drag`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DragContent {
+    Text(String),
+    /// A file, offered to the drop target as a file rather than as
+    /// text, so that it can be dropped onto a folder or an attachment
+    /// area
+    File(PathBuf),
+}
+
+/// The kinds of request that can be made to draw the
+/// attention of the user to a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserAttentionType {
+    /// Keep drawing attention to the window until it is focused
+    Critical,
+    /// Draw attention to the window briefly
+    Informational,
+    /// Cancel an outstanding request
+    Cancel,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Dimensions {
     pub pixel_width: usize,
@@ -136,6 +178,51 @@ pub enum DeadKeyStatus {
     Composing(String),
 }
 
+/// Describes the text content of a window to assistive technology,
+/// such as a screen reader
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessibleText {
+    /// The text that is visible in the window, with lines
+    /// separated by `\n`
+    pub text: String,
+    /// The position of the text cursor, as a character index
+    /// into `text`
+    pub cursor: usize,
+    /// Text that has changed since the prior update, and which
+    /// should be announced to the user
+    pub announcement: Option<String>,
+}
+
+/// A button on the macOS Touch Bar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TouchBarItem {
+    /// Identifies the item in `WindowEvent::TouchBarItemPressed`
+    pub id: String,
+    pub label: String,
+    /// Whether the item is highlighted, such as the active tab
+    pub selected: bool,
+}
+
+/// An entry in the menu that the operating system shell presents for
+/// the application; the taskbar jump list on Windows and the Dock menu
+/// on macOS.  Selecting it launches the application executable with
+/// `args`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShellMenuItem {
+    pub label: String,
+    /// Shown as a tooltip, where supported
+    pub description: Option<String>,
+    pub args: Vec<String>,
+}
+
+/// A group of related shell menu items.  Groups without a title are
+/// presented as the application's own tasks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShellMenuGroup {
+    pub title: Option<String>,
+    pub items: Vec<ShellMenuItem>,
+}
+
 #[derive(Debug)]
 pub enum WindowEvent {
     /// Called when the window close button is clicked.
@@ -184,6 +271,18 @@ pub enum WindowEvent {
 
     // Called when the files are dropped into the window
     DroppedFile(Vec<PathBuf>),
+
+    /// Called when the user asks for a new window to be added to the
+    /// native tab group of this window, such as by clicking the `+`
+    /// button of the macOS tab bar
+    NewNativeTabRequested,
+
+    /// Called when the Touch Bar item with the specified id is pressed
+    TouchBarItemPressed(String),
+
+    /// Called when the item with the specified index is chosen from
+    /// the menu shown by `WindowOps::show_context_menu`
+    ContextMenuItemSelected(usize),
 }
 
 pub struct WindowEventSender {
@@ -220,6 +319,12 @@ pub trait WindowOps {
     /// Show a hidden window
     fn show(&self);
 
+    /// Ask the windowing system to bring the window to the front
+    /// and give it the keyboard focus.  The system may decline to
+    /// do so, for example to prevent focus stealing.
+    /// This is currently only implemented on Wayland.
+    fn focus(&self) {}
+
     fn notify<T: Any + Send + Sync>(&self, t: T)
     where
         Self: Sized;
@@ -248,6 +353,21 @@ pub trait WindowOps {
     /// Change the titlebar text for the window
     fn set_title(&self, title: &str);
 
+    /// Change the secondary text that is shown below the title
+    /// in the titlebar of the window.
+    /// This is only implemented on macOS 11 and later.
+    fn set_subtitle(&self, _subtitle: &str) {}
+
+    /// Update the text that is exposed to assistive technology,
+    /// such as a screen reader, and announce any changes.
+    /// This is only implemented on macOS.
+    fn set_accessible_text(&self, _text: AccessibleText) {}
+
+    /// Replace the content of the Touch Bar that is shown while
+    /// the window is focused.
+    /// This is only implemented on macOS.
+    fn set_touch_bar(&self, _items: Vec<TouchBarItem>) {}
+
     /// Resize the inner or client area of the window
     fn set_inner_size(&self, width: usize, height: usize);
 
@@ -284,6 +404,35 @@ pub trait WindowOps {
     /// Set some text in the clipboard
     fn set_clipboard(&self, clipboard: Clipboard, text: String);
 
+    /// Set some text in the clipboard, along with formatted versions
+    /// of it.  Formats that the platform doesn't support are dropped.
+    fn set_clipboard_content(&self, clipboard: Clipboard, content: ClipboardContent) {
+        self.set_clipboard(clipboard, content.text);
+    }
+
+    /// Start dragging `content` out of the window, to be dropped onto
+    /// another application.  This must be called while the left mouse
+    /// button is held down.  Returns false if dragging out of the
+    /// window is not implemented on this system, which is the case on
+    /// X11 and Windows.
+    fn start_drag(&self, _content: DragContent) -> bool {
+        false
+    }
+
+    /// Show a native menu with the given item labels at the mouse
+    /// pointer.  Choosing an item sends `WindowEvent::ContextMenuItemSelected`
+    /// with its index.  Returns false if native menus are not implemented
+    /// on this system; this is only implemented on macOS.
+    fn show_context_menu(&self, _labels: Vec<String>) -> bool {
+        false
+    }
+
+    /// Ask the windowing system to draw the attention of the user
+    /// to the window, for example by bouncing its dock icon or
+    /// flashing its taskbar button.
+    /// This is not implemented on Wayland.
+    fn request_attention(&self, _kind: UserAttentionType) {}
+
     /// Set the icon for the window.
     /// Depending on the system this may be shown in its titlebar
     /// and/or in the task manager/task switcher
@@ -0,0 +1,58 @@
+// let () = msg_send! is a common pattern for objc
+#![allow(clippy::let_unit_value)]
+
+//! Authenticates the user with Touch ID, falling back to their
+//! password, via the LocalAuthentication framework
+
+use super::nsstring;
+use block::ConcreteBlock;
+use cocoa::base::{id, nil, BOOL, YES};
+use objc::runtime::Class;
+use objc::*;
+use promise::{Future, Promise};
+use std::sync::{Arc, Mutex};
+
+#[link(name = "LocalAuthentication", kind = "framework")]
+extern "C" {}
+
+/// LAPolicyDeviceOwnerAuthentication; biometrics, with a fallback
+/// to the password of the user
+const LA_POLICY_DEVICE_OWNER_AUTHENTICATION: isize = 2;
+
+pub fn authenticate_user(reason: &str) -> Option<Future<bool>> {
+    let class = Class::get("LAContext")?;
+
+    unsafe {
+        let context: id = msg_send![class, new];
+        let mut error: id = nil;
+        let can_evaluate: BOOL = msg_send![context,
+            canEvaluatePolicy: LA_POLICY_DEVICE_OWNER_AUTHENTICATION
+            error: &mut error];
+        if can_evaluate != YES {
+            let () = msg_send![context, release];
+            return None;
+        }
+
+        let mut promise = Promise::new();
+        let future = promise.get_future().unwrap();
+        // The reply block may be copied, so the promise needs to be
+        // shared rather than moved into it
+        let promise = Arc::new(Mutex::new(Some(promise)));
+        let reply = ConcreteBlock::new(move |success: BOOL, _error: id| {
+            if let Some(mut promise) = promise.lock().unwrap().take() {
+                promise.ok(success == YES);
+                // We kept the context alive until the evaluation completed
+                let () = msg_send![context, release];
+            }
+        })
+        .copy();
+
+        let reason = nsstring(reason);
+        let () = msg_send![context,
+            evaluatePolicy: LA_POLICY_DEVICE_OWNER_AUTHENTICATION
+            localizedReason: *reason
+            reply: &*reply];
+
+        Some(future)
+    }
+}
@@ -6,7 +6,7 @@ use super::window::WindowInner;
 use crate::connection::ConnectionOps;
 use crate::screen::{ScreenInfo, Screens};
 use crate::spawn::*;
-use crate::Appearance;
+use crate::{Appearance, ShellMenuGroup};
 use cocoa::appkit::{NSApp, NSApplication, NSApplicationActivationPolicyRegular, NSScreen};
 use cocoa::base::{id, nil};
 use cocoa::foundation::{NSArray, NSInteger, NSRect};
@@ -126,6 +126,14 @@ impl ConnectionOps for Connection {
         }
     }
 
+    fn authenticate_user(&self, reason: &str) -> Option<promise::Future<bool>> {
+        super::authenticate::authenticate_user(reason)
+    }
+
+    fn set_shell_menu(&self, groups: Vec<ShellMenuGroup>) {
+        super::dockmenu::set_dock_menu(groups);
+    }
+
     fn screens(&self) -> anyhow::Result<Screens> {
         let mut by_name = HashMap::new();
         let mut virtual_rect = euclid::rect(0, 0, 0, 0);
@@ -0,0 +1,140 @@
+// let () = msg_send! is a common pattern for objc
+#![allow(clippy::let_unit_value)]
+
+//! Provides the menu that the Dock shows for the application, by way
+//! of an application delegate that implements `applicationDockMenu:`
+
+use super::nsstring;
+use crate::{ShellMenuGroup, ShellMenuItem};
+use cocoa::appkit::{NSApp, NSMenu, NSMenuItem};
+use cocoa::base::{id, nil, NO};
+use cocoa::foundation::{NSAutoreleasePool, NSInteger};
+use objc::declare::ClassDecl;
+use objc::runtime::{Class, Object, Sel};
+use objc::*;
+use std::cell::{Cell, RefCell};
+
+const DELEGATE_CLS_NAME: &str = "WezTermAppDelegate";
+
+thread_local! {
+    static GROUPS: RefCell<Vec<ShellMenuGroup>> = RefCell::new(vec![]);
+    static DELEGATE: Cell<id> = Cell::new(nil);
+}
+
+/// Launches a new instance of the application with the arguments
+/// from the item; the new instance hands off to the running one
+/// when it is able to do so
+fn launch(item: &ShellMenuItem) -> anyhow::Result<()> {
+    let exe = std::env::current_exe()?;
+    let mut child = std::process::Command::new(exe).args(&item.args).spawn()?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+fn item_by_tag(tag: NSInteger) -> Option<ShellMenuItem> {
+    if tag < 0 {
+        return None;
+    }
+    GROUPS.with(|groups| {
+        groups
+            .borrow()
+            .iter()
+            .flat_map(|group| group.items.iter())
+            .nth(tag as usize)
+            .cloned()
+    })
+}
+
+extern "C" fn perform_shell_menu_item(_this: &mut Object, _sel: Sel, sender: id) {
+    let tag: NSInteger = unsafe { msg_send![sender, tag] };
+    if let Some(item) = item_by_tag(tag) {
+        if let Err(err) = launch(&item) {
+            log::error!("Failed to launch {:?}: {:#}", item.args, err);
+        }
+    }
+}
+
+extern "C" fn application_dock_menu(this: &mut Object, _sel: Sel, _app: id) -> id {
+    GROUPS.with(|groups| unsafe {
+        let menu = NSMenu::new(nil).autorelease();
+        let () = msg_send![menu, setAutoenablesItems: NO];
+        let mut tag: NSInteger = 0;
+
+        for group in groups.borrow().iter() {
+            if group.items.is_empty() {
+                continue;
+            }
+            let num_items: NSInteger = msg_send![menu, numberOfItems];
+            if num_items > 0 {
+                menu.addItem_(NSMenuItem::separatorItem(nil));
+            }
+            if let Some(title) = &group.title {
+                let header = NSMenuItem::alloc(nil)
+                    .initWithTitle_action_keyEquivalent_(
+                        *nsstring(title),
+                        sel!(weztermPerformShellMenuItem:),
+                        *nsstring(""),
+                    )
+                    .autorelease();
+                let () = msg_send![header, setTag: -1 as NSInteger];
+                let () = msg_send![header, setEnabled: NO];
+                menu.addItem_(header);
+            }
+            for item in &group.items {
+                let menu_item = NSMenuItem::alloc(nil)
+                    .initWithTitle_action_keyEquivalent_(
+                        *nsstring(&item.label),
+                        sel!(weztermPerformShellMenuItem:),
+                        *nsstring(""),
+                    )
+                    .autorelease();
+                let () = msg_send![menu_item, setTarget: this as *mut Object];
+                let () = msg_send![menu_item, setTag: tag];
+                if let Some(description) = &item.description {
+                    let () = msg_send![menu_item, setToolTip: *nsstring(description)];
+                }
+                menu.addItem_(menu_item);
+                tag += 1;
+            }
+        }
+
+        menu
+    })
+}
+
+fn get_class() -> &'static Class {
+    Class::get(DELEGATE_CLS_NAME).unwrap_or_else(|| {
+        let mut cls = ClassDecl::new(DELEGATE_CLS_NAME, class!(NSObject))
+            .expect("Unable to register application delegate class");
+
+        unsafe {
+            cls.add_method(
+                sel!(applicationDockMenu:),
+                application_dock_menu as extern "C" fn(&mut Object, Sel, id) -> id,
+            );
+            cls.add_method(
+                sel!(weztermPerformShellMenuItem:),
+                perform_shell_menu_item as extern "C" fn(&mut Object, Sel, id),
+            );
+        }
+
+        cls.register()
+    })
+}
+
+/// Replaces the content of the Dock menu, installing the application
+/// delegate that provides it on first use
+pub fn set_dock_menu(groups: Vec<ShellMenuGroup>) {
+    GROUPS.with(|g| *g.borrow_mut() = groups);
+    DELEGATE.with(|delegate| {
+        if delegate.get() == nil {
+            unsafe {
+                let instance: id = msg_send![get_class(), new];
+                let () = msg_send![NSApp(), setDelegate: instance];
+                // The application doesn't retain its delegate, so we
+                // keep our reference for the lifetime of the process
+                delegate.set(instance);
+            }
+        }
+    });
+}
@@ -8,6 +8,8 @@ pub mod bitmap;
 pub mod connection;
 pub mod window;
 
+mod authenticate;
+mod dockmenu;
 mod keycodes;
 
 pub use self::window::*;
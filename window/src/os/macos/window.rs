@@ -6,19 +6,20 @@ use super::{nsstring, nsstring_to_str};
 use crate::connection::ConnectionOps;
 use crate::parameters::{Border, Parameters, TitleBar};
 use crate::{
-    Clipboard, Connection, DeadKeyStatus, Dimensions, Handled, KeyCode, KeyEvent, Modifiers,
-    MouseButtons, MouseCursor, MouseEvent, MouseEventKind, MousePress, Point, RawKeyEvent, Rect,
-    RequestedWindowGeometry, ResolvedGeometry, ScreenPoint, Size, ULength, WindowDecorations,
-    WindowEvent, WindowEventSender, WindowOps, WindowState,
+    AccessibleText, Clipboard, ClipboardContent, Connection, DeadKeyStatus, Dimensions,
+    DragContent, Handled, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseCursor, MouseEvent,
+    MouseEventKind, MousePress, Point, RawKeyEvent, Rect, RequestedWindowGeometry,
+    ResolvedGeometry, ScreenPoint, Size, TouchBarItem, ULength, UserAttentionType,
+    WindowDecorations, WindowEvent, WindowEventSender, WindowOps, WindowState,
 };
 use anyhow::{anyhow, bail, ensure};
 use async_trait::async_trait;
 use clipboard_macos::Clipboard as ClipboardContext;
 use cocoa::appkit::{
-    self, CGFloat, NSApplication, NSApplicationActivateIgnoringOtherApps,
+    self, CGFloat, NSApp, NSApplication, NSApplicationActivateIgnoringOtherApps,
     NSApplicationPresentationOptions, NSBackingStoreBuffered, NSEvent, NSEventModifierFlags,
-    NSOpenGLContext, NSOpenGLPixelFormat, NSPasteboard, NSRunningApplication, NSScreen, NSView,
-    NSViewHeightSizable, NSViewWidthSizable, NSWindow, NSWindowStyleMask,
+    NSMenu, NSMenuItem, NSOpenGLContext, NSOpenGLPixelFormat, NSPasteboard, NSRunningApplication,
+    NSScreen, NSView, NSViewHeightSizable, NSViewWidthSizable, NSWindow, NSWindowStyleMask,
 };
 use cocoa::base::*;
 use cocoa::foundation::{
@@ -44,12 +45,19 @@ use std::ffi::c_void;
 use std::path::PathBuf;
 use std::rc::Rc;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicIsize, Ordering};
 use std::time::Instant;
 use wezterm_font::FontConfiguration;
 use wezterm_input_types::is_ascii_control;
 
 #[allow(non_upper_case_globals)]
 const NSViewLayerContentsPlacementTopLeft: NSInteger = 11;
+/// NSDragOperationCopy
+const NS_DRAG_OPERATION_COPY: NSUInteger = 1;
+
+/// The Touch Bar items are identified by this prefix followed
+/// by their index
+const TOUCH_BAR_ITEM_PREFIX: &str = "org.wezfurlong.wezterm.touchbar.";
 
 fn round_away_from_zerof(value: f64) -> f64 {
     if value > 0. {
@@ -116,6 +124,58 @@ impl NSRange {
     }
 }
 
+/// The text that is exposed to the accessibility APIs.
+/// NSAccessibility expresses positions and ranges in terms of
+/// UTF-16 code units, so we hold the text in that form.
+#[derive(Default, PartialEq)]
+struct AccessibleContent {
+    text: Vec<u16>,
+    cursor: usize,
+}
+
+impl AccessibleContent {
+    fn new(accessible: &AccessibleText) -> Self {
+        let cursor = accessible
+            .text
+            .chars()
+            .take(accessible.cursor)
+            .map(char::len_utf16)
+            .sum();
+        Self {
+            text: accessible.text.encode_utf16().collect(),
+            cursor,
+        }
+    }
+
+    fn clamp_range(&self, location: u64, length: u64) -> std::ops::Range<usize> {
+        let start = (location as usize).min(self.text.len());
+        let end = start.saturating_add(length as usize).min(self.text.len());
+        start..end
+    }
+
+    fn string_for_range(&self, location: u64, length: u64) -> String {
+        String::from_utf16_lossy(&self.text[self.clamp_range(location, length)])
+    }
+
+    fn line_for_index(&self, index: usize) -> usize {
+        self.text[..index.min(self.text.len())]
+            .iter()
+            .filter(|&&c| c == b'\n' as u16)
+            .count()
+    }
+
+    fn range_for_line(&self, line: usize) -> NSRange {
+        let mut start = 0;
+        for (idx, segment) in self.text.split(|&c| c == b'\n' as u16).enumerate() {
+            if idx == line {
+                return NSRange::new(start as u64, segment.len() as u64);
+            }
+            start += segment.len() + 1;
+        }
+        NSRange::new(NSNotFound as _, 0)
+    }
+}
+
 #[derive(Clone)]
 pub enum BackendImpl {
     Cgl(Rc<cglbits::GlState>),
@@ -457,6 +517,8 @@ impl Window {
                 ime_last_event: None,
                 live_resizing: false,
                 ime_text: String::new(),
+                accessible_text: AccessibleContent::default(),
+                touch_bar_items: vec![],
             }));
 
             let window: id = msg_send![get_window_class(), alloc];
@@ -470,8 +532,7 @@ impl Window {
 
             apply_decorations_to_window(&window, config.window_decorations);
 
-            // Prevent Cocoa native tabs from being used
-            let _: () = msg_send![*window, setTabbingMode:2 /* NSWindowTabbingModeDisallowed */];
+            apply_tabbing_mode(*window, &config);
             let _: () = msg_send![*window, setRestorable: NO];
 
             window.setReleasedWhenClosed_(NO);
@@ -656,6 +717,54 @@ impl WindowOps for Window {
         });
     }
 
+    fn set_subtitle(&self, subtitle: &str) {
+        let subtitle = subtitle.to_owned();
+        Connection::with_window_inner(self.id, move |inner| {
+            inner.set_subtitle(&subtitle);
+            Ok(())
+        });
+    }
+
+    fn set_accessible_text(&self, text: AccessibleText) {
+        Connection::with_window_inner(self.id, move |inner| {
+            inner.set_accessible_text(&text);
+            Ok(())
+        });
+    }
+
+    fn set_touch_bar(&self, items: Vec<TouchBarItem>) {
+        Connection::with_window_inner(self.id, move |inner| {
+            inner.set_touch_bar(items);
+            Ok(())
+        });
+    }
+
+    fn request_attention(&self, kind: UserAttentionType) {
+        // NSRequestUserAttentionType values
+        const NS_CRITICAL_REQUEST: NSInteger = 0;
+        const NS_INFORMATIONAL_REQUEST: NSInteger = 10;
+        static LAST_REQUEST: AtomicIsize = AtomicIsize::new(0);
+
+        unsafe {
+            let app = NSApp();
+            let request_type = match kind {
+                UserAttentionType::Critical => NS_CRITICAL_REQUEST,
+                UserAttentionType::Informational => NS_INFORMATIONAL_REQUEST,
+                UserAttentionType::Cancel => {
+                    let request = LAST_REQUEST.swap(0, Ordering::Relaxed);
+                    if request != 0 {
+                        let () = msg_send![app, cancelUserAttentionRequest: request];
+                    }
+                    return;
+                }
+            };
+            // The request is ignored, returning 0, if the
+            // application is already active
+            let request: NSInteger = msg_send![app, requestUserAttention: request_type];
+            LAST_REQUEST.store(request, Ordering::Relaxed);
+        }
+    }
+
     fn set_inner_size(&self, width: usize, height: usize) {
         Connection::with_window_inner(
             self.id,
@@ -691,6 +800,46 @@ impl WindowOps for Window {
             .ok();
     }
 
+    fn set_clipboard_content(&self, clipboard: Clipboard, content: ClipboardContent) {
+        if content.html.is_none() && content.rtf.is_none() {
+            return self.set_clipboard(clipboard, content.text);
+        }
+        let flavors = [
+            ("public.utf8-plain-text", Some(&content.text)),
+            ("public.html", content.html.as_ref()),
+            ("public.rtf", content.rtf.as_ref()),
+        ];
+        unsafe {
+            let pb: id = msg_send![class!(NSPasteboard), generalPasteboard];
+            let _: NSInteger = msg_send![pb, clearContents];
+            for (pboard_type, data) in flavors {
+                if let Some(data) = data {
+                    let data: id = msg_send![class!(NSData),
+                        dataWithBytes: data.as_ptr() as *const std::ffi::c_void
+                        length: data.len() as NSUInteger];
+                    let pboard_type = nsstring(pboard_type);
+                    let _: BOOL = msg_send![pb, setData: data forType: *pboard_type];
+                }
+            }
+        }
+    }
+
+    fn start_drag(&self, content: DragContent) -> bool {
+        Connection::with_window_inner(self.id, move |inner| {
+            inner.start_drag(content);
+            Ok(())
+        });
+        true
+    }
+
+    fn show_context_menu(&self, labels: Vec<String>) -> bool {
+        Connection::with_window_inner(self.id, move |inner| {
+            inner.show_context_menu(labels);
+            Ok(())
+        });
+        true
+    }
+
     fn toggle_fullscreen(&self) {
         Connection::with_window_inner(self.id, move |inner| {
             inner.toggle_fullscreen();
@@ -948,6 +1097,66 @@ impl WindowInner {
 }
 
 impl WindowInner {
+    fn start_drag(&mut self, content: DragContent) {
+        unsafe {
+            // The drag session is anchored to the mouse event that
+            // caused it, which is the mouseDragged: event that is
+            // still being processed
+            let event: id = msg_send![NSApp(), currentEvent];
+            if event.is_null() {
+                log::warn!("start_drag: there is no current mouse event");
+                return;
+            }
+
+            let writer: StrongPtr = match &content {
+                DragContent::Text(text) => nsstring(text),
+                DragContent::File(path) => {
+                    let path = nsstring(&path.to_string_lossy());
+                    let url: id = msg_send![class!(NSURL), fileURLWithPath: *path];
+                    StrongPtr::retain(url)
+                }
+            };
+            let item: id = msg_send![class!(NSDraggingItem), alloc];
+            let item = StrongPtr::new(msg_send![item, initWithPasteboardWriter: *writer]);
+
+            let point = NSView::convertPoint_fromView_(*self.view, event.locationInWindow(), nil);
+            let frame = NSRect::new(point, NSSize::new(1., 1.));
+            let () = msg_send![*item, setDraggingFrame: frame contents: nil];
+
+            let items = NSArray::arrayWithObject(nil, *item);
+            let _session: id = msg_send![*self.view,
+                beginDraggingSessionWithItems: items
+                event: event
+                source: *self.view];
+        }
+    }
+
+    fn show_context_menu(&mut self, labels: Vec<String>) {
+        unsafe {
+            let menu = NSMenu::new(nil).autorelease();
+            let () = msg_send![menu, setAutoenablesItems: NO];
+            for (idx, label) in labels.iter().enumerate() {
+                let item = NSMenuItem::alloc(nil)
+                    .initWithTitle_action_keyEquivalent_(
+                        *nsstring(label),
+                        sel!(weztermContextMenuItemSelected:),
+                        *nsstring(""),
+                    )
+                    .autorelease();
+                let () = msg_send![item, setTarget: *self.view];
+                let () = msg_send![item, setTag: idx as NSInteger];
+                menu.addItem_(item);
+            }
+            // Popping up the menu runs a nested event loop until it is
+            // dismissed, so defer it until we are no longer holding
+            // the window borrowed
+            let () = msg_send![*self.view,
+                performSelector: sel!(weztermShowContextMenu:)
+                withObject: menu
+                afterDelay: 0.0f64];
+        }
+    }
+
     fn show(&mut self) {
         unsafe {
             let current_app = NSRunningApplication::currentApplication(nil);
@@ -1008,6 +1217,76 @@ impl WindowInner {
         }
     }
 
+    fn set_subtitle(&mut self, subtitle: &str) {
+        let subtitle = nsstring(subtitle);
+        unsafe {
+            // NSWindow.subtitle was introduced in macOS 11
+            let has_subtitle: BOOL =
+                msg_send![*self.window, respondsToSelector: sel!(setSubtitle:)];
+            if has_subtitle == YES {
+                let () = msg_send![*self.window, setSubtitle: *subtitle];
+            }
+        }
+    }
+
+    fn set_touch_bar(&mut self, items: Vec<TouchBarItem>) {
+        match WindowView::get_this(unsafe { &**self.view }) {
+            Some(window_view) => {
+                let mut inner = window_view.inner.borrow_mut();
+                if inner.touch_bar_items == items {
+                    return;
+                }
+                inner.touch_bar_items = items;
+            }
+            None => return,
+        }
+        unsafe {
+            // Discarding the current touch bar causes AppKit to ask
+            // for a new one via makeTouchBar
+            let () = msg_send![*self.view, setTouchBar: nil];
+        }
+    }
+
+    fn set_accessible_text(&mut self, accessible: &AccessibleText) {
+        let content = AccessibleContent::new(accessible);
+        let (text_changed, cursor_changed) = match WindowView::get_this(unsafe { &**self.view }) {
+            Some(window_view) => {
+                let mut inner = window_view.inner.borrow_mut();
+                let text_changed = inner.accessible_text.text != content.text;
+                let cursor_changed = inner.accessible_text.cursor != content.cursor;
+                inner.accessible_text = content;
+                (text_changed, cursor_changed)
+            }
+            None => return,
+        };
+
+        unsafe {
+            if text_changed {
+                NSAccessibilityPostNotification(
+                    *self.view,
+                    NSAccessibilityValueChangedNotification,
+                );
+            }
+            if cursor_changed {
+                NSAccessibilityPostNotification(
+                    *self.view,
+                    NSAccessibilitySelectedTextChangedNotification,
+                );
+            }
+            if let Some(announcement) = &accessible.announcement {
+                let announcement = nsstring(announcement);
+                let user_info: id = msg_send![class!(NSDictionary),
+                    dictionaryWithObject: *announcement
+                    forKey: NSAccessibilityAnnouncementKey];
+                NSAccessibilityPostNotificationWithUserInfo(
+                    *self.view,
+                    NSAccessibilityAnnouncementRequestedNotification,
+                    user_info,
+                );
+            }
+        }
+    }
+
     fn set_inner_size(&mut self, width: usize, height: usize) -> Dimensions {
         unsafe {
             let frame = NSView::frame(*self.view as *mut _);
@@ -1110,6 +1389,9 @@ impl WindowInner {
         }
         self.update_window_shadow();
         self.apply_decorations();
+        unsafe {
+            apply_tabbing_mode(*self.window, config);
+        }
     }
 }
 
@@ -1144,6 +1426,7 @@ fn apply_decorations_to_window(window: &StrongPtr, decorations: WindowDecoration
 }
 
 fn decoration_to_mask(decorations: WindowDecorations) -> NSWindowStyleMask {
+    let decorations = decorations - WindowDecorations::INTEGRATED_BUTTONS;
     if decorations == WindowDecorations::TITLE | WindowDecorations::RESIZE {
         NSWindowStyleMask::NSTitledWindowMask
             | NSWindowStyleMask::NSClosableWindowMask
@@ -1217,6 +1500,12 @@ struct Inner {
     live_resizing: bool,
 
     ime_text: String,
+
+    /// The text content exposed to screen readers
+    accessible_text: AccessibleContent,
+
+    /// The buttons shown on the Touch Bar
+    touch_bar_items: Vec<TouchBarItem>,
 }
 
 #[repr(C)]
@@ -1271,6 +1560,16 @@ extern "C" {
     fn LMGetKbdType() -> u8;
 }
 
+extern "C" {
+    static NSAccessibilityValueChangedNotification: id;
+    static NSAccessibilitySelectedTextChangedNotification: id;
+    static NSAccessibilityAnnouncementRequestedNotification: id;
+    static NSAccessibilityAnnouncementKey: id;
+
+    fn NSAccessibilityPostNotification(element: id, notification: id);
+    fn NSAccessibilityPostNotificationWithUserInfo(element: id, notification: id, user_info: id);
+}
+
 #[derive(Debug)]
 enum TranslateStatus {
     Composing(String),
@@ -1521,6 +1820,18 @@ fn key_modifiers(flags: NSEventModifierFlags) -> Modifiers {
     mods
 }
 
+/// Native window tabs are disallowed unless `native_macos_tabs` is
+/// enabled, in which case new windows prefer to join the tab group of
+/// the key window, the same way that they do in Terminal.app
+unsafe fn apply_tabbing_mode(window: id, config: &ConfigHandle) {
+    if config.native_macos_tabs {
+        let _: () = msg_send![window, setTabbingMode:1 /* NSWindowTabbingModePreferred */];
+        let _: () = msg_send![window, setTabbingIdentifier: *nsstring("org.wezfurlong.wezterm")];
+    } else {
+        let _: () = msg_send![window, setTabbingMode:2 /* NSWindowTabbingModeDisallowed */];
+    }
+}
+
 /// We register our own subclass of NSWindow so that we can override
 /// canBecomeKeyWindow so that our simple fullscreen style can keep
 /// focus once the titlebar has been removed; the default behavior of
@@ -1790,6 +2101,105 @@ impl WindowView {
         YES
     }
 
+    extern "C" fn is_accessibility_element(_this: &mut Object, _sel: Sel) -> BOOL {
+        YES
+    }
+
+    extern "C" fn accessibility_role(_this: &mut Object, _sel: Sel) -> id {
+        // NSAccessibilityTextAreaRole
+        nsstring("AXTextArea").autorelease()
+    }
+
+    extern "C" fn accessibility_value(this: &mut Object, _sel: Sel) -> id {
+        let text = match Self::get_this(this) {
+            Some(myself) => String::from_utf16_lossy(&myself.inner.borrow().accessible_text.text),
+            None => String::new(),
+        };
+        nsstring(&text).autorelease()
+    }
+
+    extern "C" fn accessibility_number_of_characters(this: &mut Object, _sel: Sel) -> NSInteger {
+        Self::get_this(this)
+            .map(|myself| myself.inner.borrow().accessible_text.text.len() as NSInteger)
+            .unwrap_or(0)
+    }
+
+    extern "C" fn accessibility_selected_text_range(this: &mut Object, _sel: Sel) -> NSRange {
+        match Self::get_this(this) {
+            Some(myself) => NSRange::new(myself.inner.borrow().accessible_text.cursor as u64, 0),
+            None => NSRange::new(0, 0),
+        }
+    }
+
+    extern "C" fn accessibility_visible_character_range(this: &mut Object, _sel: Sel) -> NSRange {
+        match Self::get_this(this) {
+            Some(myself) => {
+                NSRange::new(0, myself.inner.borrow().accessible_text.text.len() as u64)
+            }
+            None => NSRange::new(0, 0),
+        }
+    }
+
+    extern "C" fn accessibility_insertion_point_line_number(
+        this: &mut Object,
+        _sel: Sel,
+    ) -> NSInteger {
+        match Self::get_this(this) {
+            Some(myself) => {
+                let inner = myself.inner.borrow();
+                let content = &inner.accessible_text;
+                content.line_for_index(content.cursor) as NSInteger
+            }
+            None => 0,
+        }
+    }
+
+    extern "C" fn accessibility_string_for_range(
+        this: &mut Object,
+        _sel: Sel,
+        range: NSRange,
+    ) -> id {
+        let text = match Self::get_this(this) {
+            Some(myself) => myself
+                .inner
+                .borrow()
+                .accessible_text
+                .string_for_range(range.0.location, range.0.length),
+            None => String::new(),
+        };
+        nsstring(&text).autorelease()
+    }
+
+    extern "C" fn accessibility_line_for_index(
+        this: &mut Object,
+        _sel: Sel,
+        index: NSInteger,
+    ) -> NSInteger {
+        match Self::get_this(this) {
+            Some(myself) => myself
+                .inner
+                .borrow()
+                .accessible_text
+                .line_for_index(index.max(0) as usize) as NSInteger,
+            None => 0,
+        }
+    }
+
+    extern "C" fn accessibility_range_for_line(
+        this: &mut Object,
+        _sel: Sel,
+        line: NSInteger,
+    ) -> NSRange {
+        match Self::get_this(this) {
+            Some(myself) if line >= 0 => myself
+                .inner
+                .borrow()
+                .accessible_text
+                .range_for_line(line as usize),
+            _ => NSRange::new(NSNotFound as _, 0),
+        }
+    }
+
     extern "C" fn view_did_change_effective_appearance(this: &mut Object, _sel: Sel) {
         if let Some(this) = Self::get_this(this) {
             let appearance = Connection::get().unwrap().get_appearance();
@@ -1879,6 +2289,115 @@ impl WindowView {
         NO
     }
 
+    extern "C" fn make_touch_bar(this: &mut Object, _sel: Sel) -> id {
+        let num_items = match Self::get_this(this) {
+            Some(view) => view.inner.borrow().touch_bar_items.len(),
+            None => return nil,
+        };
+        let class = match Class::get("NSTouchBar") {
+            Some(class) if num_items > 0 => class,
+            _ => return nil,
+        };
+
+        unsafe {
+            let touch_bar: id = msg_send![class, new];
+            let () = msg_send![touch_bar, setDelegate: this as *mut Object];
+            let identifiers: Vec<StrongPtr> = (0..num_items)
+                .map(|idx| nsstring(&format!("{}{}", TOUCH_BAR_ITEM_PREFIX, idx)))
+                .collect();
+            let identifiers: Vec<id> = identifiers.iter().map(|ident| **ident).collect();
+            let () = msg_send![touch_bar,
+                setDefaultItemIdentifiers: NSArray::arrayWithObjects(nil, &identifiers)];
+            msg_send![touch_bar, autorelease]
+        }
+    }
+
+    extern "C" fn touch_bar_make_item(
+        this: &mut Object,
+        _sel: Sel,
+        _touch_bar: id,
+        identifier: id,
+    ) -> id {
+        let idx: usize = match unsafe { nsstring_to_str(identifier) }
+            .strip_prefix(TOUCH_BAR_ITEM_PREFIX)
+            .and_then(|idx| idx.parse().ok())
+        {
+            Some(idx) => idx,
+            None => return nil,
+        };
+        let item = match Self::get_this(this)
+            .and_then(|view| view.inner.borrow().touch_bar_items.get(idx).cloned())
+        {
+            Some(item) => item,
+            None => return nil,
+        };
+        let class = match Class::get("NSCustomTouchBarItem") {
+            Some(class) => class,
+            None => return nil,
+        };
+
+        unsafe {
+            let touch_bar_item: id = msg_send![class, alloc];
+            let touch_bar_item: id = msg_send![touch_bar_item, initWithIdentifier: identifier];
+            let button: id = msg_send![class!(NSButton),
+                buttonWithTitle: *nsstring(&item.label)
+                target: this as *mut Object
+                action: sel!(weztermTouchBarItemPressed:)];
+            let () = msg_send![button, setTag: idx as NSInteger];
+            if item.selected {
+                let color: id = msg_send![class!(NSColor), systemBlueColor];
+                let () = msg_send![button, setBezelColor: color];
+            }
+            let () = msg_send![touch_bar_item, setView: button];
+            msg_send![touch_bar_item, autorelease]
+        }
+    }
+
+    extern "C" fn touch_bar_item_pressed(this: &mut Object, _sel: Sel, sender: id) {
+        let idx: NSInteger = unsafe { msg_send![sender, tag] };
+        if let Some(this) = Self::get_this(this) {
+            let id = match this.inner.borrow().touch_bar_items.get(idx as usize) {
+                Some(item) => item.id.clone(),
+                None => return,
+            };
+            this.inner
+                .borrow_mut()
+                .events
+                .dispatch(WindowEvent::TouchBarItemPressed(id));
+        }
+    }
+
+    extern "C" fn show_context_menu(_this: &mut Object, _sel: Sel, menu: id) {
+        unsafe {
+            let location: NSPoint = msg_send![class!(NSEvent), mouseLocation];
+            let _: BOOL = msg_send![menu,
+                popUpMenuPositioningItem: nil
+                atLocation: location
+                inView: nil];
+        }
+    }
+
+    extern "C" fn context_menu_item_selected(this: &mut Object, _sel: Sel, sender: id) {
+        let idx: NSInteger = unsafe { msg_send![sender, tag] };
+        if let Some(this) = Self::get_this(this) {
+            this.inner
+                .borrow_mut()
+                .events
+                .dispatch(WindowEvent::ContextMenuItemSelected(idx as usize));
+        }
+    }
+
+    // Sent by the `+` button in the native tab bar.  Implementing it
+    // is what causes that button to be shown.
+    extern "C" fn new_window_for_tab(this: &mut Object, _sel: Sel, _id: id) {
+        if let Some(this) = Self::get_this(this) {
+            this.inner
+                .borrow_mut()
+                .events
+                .dispatch(WindowEvent::NewNativeTabRequested);
+        }
+    }
+
     extern "C" fn window_will_close(this: &mut Object, _sel: Sel, _id: id) {
         if let Some(this) = Self::get_this(this) {
             // Advise the window of its impending death
@@ -2567,6 +3086,17 @@ impl WindowView {
         YES
     }
 
+    /// NSDraggingSource: text and files that are dragged out of the
+    /// window are only ever copied
+    extern "C" fn dragging_source_operation_mask(
+        _this: &mut Object,
+        _: Sel,
+        _session: id,
+        _context: NSInteger,
+    ) -> NSUInteger {
+        NS_DRAG_OPERATION_COPY
+    }
+
     extern "C" fn perform_drag_operation(this: &mut Object, _: Sel, sender: id) -> BOOL {
         if let Some(this) = Self::get_this(this) {
             let mut inner = this.inner.borrow_mut();
@@ -2626,6 +3156,9 @@ impl WindowView {
         cls.add_protocol(
             Protocol::get("NSTextInputClient").expect("failed to get NSTextInputClient protocol"),
         );
+        if let Some(protocol) = Protocol::get("NSDraggingSource") {
+            cls.add_protocol(protocol);
+        }
 
         unsafe {
             cls.add_method(
@@ -2663,6 +3196,32 @@ impl WindowView {
                 Self::allow_automatic_tabbing as extern "C" fn(&Object, Sel) -> BOOL,
             );
 
+            cls.add_method(
+                sel!(newWindowForTab:),
+                Self::new_window_for_tab as extern "C" fn(&mut Object, Sel, id),
+            );
+
+            cls.add_method(
+                sel!(makeTouchBar),
+                Self::make_touch_bar as extern "C" fn(&mut Object, Sel) -> id,
+            );
+            cls.add_method(
+                sel!(touchBar:makeItemForIdentifier:),
+                Self::touch_bar_make_item as extern "C" fn(&mut Object, Sel, id, id) -> id,
+            );
+            cls.add_method(
+                sel!(weztermTouchBarItemPressed:),
+                Self::touch_bar_item_pressed as extern "C" fn(&mut Object, Sel, id),
+            );
+            cls.add_method(
+                sel!(weztermShowContextMenu:),
+                Self::show_context_menu as extern "C" fn(&mut Object, Sel, id),
+            );
+            cls.add_method(
+                sel!(weztermContextMenuItemSelected:),
+                Self::context_menu_item_selected as extern "C" fn(&mut Object, Sel, id),
+            );
+
             cls.add_method(
                 sel!(windowWillStartLiveResize:),
                 Self::will_start_live_resize as extern "C" fn(&mut Object, Sel, id),
@@ -2828,6 +3387,61 @@ impl WindowView {
                 sel!(performDragOperation:),
                 Self::perform_drag_operation as extern "C" fn(&mut Object, Sel, id) -> BOOL,
             );
+            cls.add_method(
+                sel!(draggingSession:sourceOperationMaskForDraggingContext:),
+                Self::dragging_source_operation_mask
+                    as extern "C" fn(&mut Object, Sel, id, NSInteger) -> NSUInteger,
+            );
+
+            // NSAccessibility support, so that screen readers can
+            // see the text content of the window
+            cls.add_method(
+                sel!(isAccessibilityElement),
+                Self::is_accessibility_element as extern "C" fn(&mut Object, Sel) -> BOOL,
+            );
+            cls.add_method(
+                sel!(accessibilityRole),
+                Self::accessibility_role as extern "C" fn(&mut Object, Sel) -> id,
+            );
+            cls.add_method(
+                sel!(accessibilityValue),
+                Self::accessibility_value as extern "C" fn(&mut Object, Sel) -> id,
+            );
+            cls.add_method(
+                sel!(accessibilityNumberOfCharacters),
+                Self::accessibility_number_of_characters
+                    as extern "C" fn(&mut Object, Sel) -> NSInteger,
+            );
+            cls.add_method(
+                sel!(accessibilitySelectedTextRange),
+                Self::accessibility_selected_text_range
+                    as extern "C" fn(&mut Object, Sel) -> NSRange,
+            );
+            cls.add_method(
+                sel!(accessibilityVisibleCharacterRange),
+                Self::accessibility_visible_character_range
+                    as extern "C" fn(&mut Object, Sel) -> NSRange,
+            );
+            cls.add_method(
+                sel!(accessibilityInsertionPointLineNumber),
+                Self::accessibility_insertion_point_line_number
+                    as extern "C" fn(&mut Object, Sel) -> NSInteger,
+            );
+            cls.add_method(
+                sel!(accessibilityStringForRange:),
+                Self::accessibility_string_for_range
+                    as extern "C" fn(&mut Object, Sel, NSRange) -> id,
+            );
+            cls.add_method(
+                sel!(accessibilityLineForIndex:),
+                Self::accessibility_line_for_index
+                    as extern "C" fn(&mut Object, Sel, NSInteger) -> NSInteger,
+            );
+            cls.add_method(
+                sel!(accessibilityRangeForLine:),
+                Self::accessibility_range_for_line
+                    as extern "C" fn(&mut Object, Sel, NSInteger) -> NSRange,
+            );
         }
 
         cls.register()
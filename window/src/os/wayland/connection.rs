@@ -4,6 +4,7 @@ use super::window::*;
 use crate::connection::ConnectionOps;
 use crate::os::wayland::inputhandler::InputHandler;
 use crate::os::wayland::output::OutputHandler;
+use crate::os::wayland::protocols::fractional_scale_v1::client::wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1;
 use crate::os::x11::keyboard::Keyboard;
 use crate::screen::{ScreenInfo, Screens};
 use crate::spawn::*;
@@ -18,21 +19,35 @@ use std::os::unix::fs::FileExt;
 use std::os::unix::io::FromRawFd;
 use std::rc::Rc;
 use std::sync::atomic::AtomicUsize;
-use toolkit::environment::Environment;
+use toolkit::environment::{Environment, SimpleGlobal};
 use toolkit::reexports::client::Display;
 use toolkit::seat::SeatListener;
 use toolkit::shm::AutoMemPool;
 use wayland_client::protocol::wl_keyboard::{Event as WlKeyboardEvent, KeymapFormat, WlKeyboard};
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::protocol::wl_surface::WlSurface;
 use wayland_client::{EventQueue, Main};
+use wayland_protocols::staging::xdg_activation::v1::client::xdg_activation_token_v1::Event as ActivationTokenEvent;
+use wayland_protocols::staging::xdg_activation::v1::client::xdg_activation_v1::XdgActivationV1;
+use wayland_protocols::unstable::text_input::v3::client::zwp_text_input_v3::{
+    ContentHint, ContentPurpose,
+};
+use wayland_protocols::viewporter::client::wp_viewporter::WpViewporter;
 
 toolkit::default_environment!(MyEnvironment, desktop,
 fields=[
     output_handler: OutputHandler,
     input_handler: InputHandler,
+    fractional_scale_manager: SimpleGlobal<WpFractionalScaleManagerV1>,
+    viewporter: SimpleGlobal<WpViewporter>,
+    activation: SimpleGlobal<XdgActivationV1>,
 ],
 singles=[
     wayland_protocols::wlr::unstable::output_management::v1::client::zwlr_output_manager_v1::ZwlrOutputManagerV1 => output_handler,
     wayland_protocols::unstable::text_input::v3::client::zwp_text_input_manager_v3::ZwpTextInputManagerV3 => input_handler,
+    WpFractionalScaleManagerV1 => fractional_scale_manager,
+    WpViewporter => viewporter,
+    XdgActivationV1 => activation,
 ]);
 
 impl MyEnvironment {
@@ -58,6 +73,10 @@ pub struct WaylandConnection {
     pub(crate) pointer: RefCell<PointerDispatcher>,
     pub(crate) keyboard_mapper: RefCell<Option<Keyboard>>,
     pub(crate) keyboard_window_id: RefCell<Option<usize>>,
+    /// The seat of the keyboard that most recently entered one of
+    /// our surfaces; used together with last_serial to demonstrate
+    /// to the compositor that an activation request is legitimate
+    pub(crate) keyboard_seat: RefCell<Option<WlSeat>>,
     pub(crate) surface_to_window_id: RefCell<HashMap<u32, usize>>,
     pub(crate) active_surface_id: RefCell<u32>,
 
@@ -90,6 +109,9 @@ impl WaylandConnection {
             fields = [
                 output_handler: OutputHandler::new(),
                 input_handler: InputHandler::new(),
+                fractional_scale_manager: SimpleGlobal::new(),
+                viewporter: SimpleGlobal::new(),
+                activation: SimpleGlobal::new(),
             ]
         )?;
 
@@ -108,9 +130,10 @@ impl WaylandConnection {
             {
                 if has_kbd {
                     let keyboard = seat.get_keyboard();
-                    keyboard.quick_assign(|keyboard, event, _| {
+                    let kbd_seat = seat.detach();
+                    keyboard.quick_assign(move |keyboard, event, _| {
                         let conn = Connection::get().unwrap().wayland();
-                        if let Err(err) = conn.keyboard_event(keyboard, event) {
+                        if let Err(err) = conn.keyboard_event(&kbd_seat, keyboard, event) {
                             log::error!("keyboard_event: {:#}", err);
                         }
                     });
@@ -152,10 +175,11 @@ impl WaylandConnection {
                         // up handling key events twice.
                         if !seat_keyboards.contains_key(&seat_data.name) {
                             let keyboard = seat.get_keyboard();
+                            let kbd_seat = seat.detach();
 
-                            keyboard.quick_assign(|keyboard, event, _| {
+                            keyboard.quick_assign(move |keyboard, event, _| {
                                 let conn = Connection::get().unwrap().wayland();
-                                if let Err(err) = conn.keyboard_event(keyboard, event) {
+                                if let Err(err) = conn.keyboard_event(&kbd_seat, keyboard, event) {
                                     log::error!("keyboard_event: {:#}", err);
                                 }
                             });
@@ -196,6 +220,7 @@ impl WaylandConnection {
             key_repeat_rate: RefCell::new(25),
             key_repeat_delay: RefCell::new(400),
             keyboard_window_id: RefCell::new(None),
+            keyboard_seat: RefCell::new(None),
             last_serial: RefCell::new(0),
             surface_to_window_id: RefCell::new(HashMap::new()),
             active_surface_id: RefCell::new(0),
@@ -204,6 +229,7 @@ impl WaylandConnection {
 
     fn keyboard_event(
         &self,
+        seat: &WlSeat,
         keyboard: Main<WlKeyboard>,
         event: WlKeyboardEvent,
     ) -> anyhow::Result<()> {
@@ -215,6 +241,7 @@ impl WaylandConnection {
                 *self.active_surface_id.borrow_mut() = surface.as_ref().id();
 
                 *self.last_serial.borrow_mut() = *serial;
+                self.keyboard_seat.borrow_mut().replace(seat.clone());
                 if let Some(&window_id) = self
                     .surface_to_window_id
                     .borrow()
@@ -226,6 +253,7 @@ impl WaylandConnection {
                             env.input_handler.get_text_input_for_keyboard(&keyboard)
                         {
                             input.enable();
+                            input.set_content_type(ContentHint::None, ContentPurpose::Terminal);
                             input.commit();
                         }
                         env.input_handler.advise_surface(&surface, &keyboard);
@@ -296,6 +324,57 @@ impl WaylandConnection {
         }
     }
 
+    /// Asks the compositor to activate `surface` via xdg-activation.
+    /// The token is associated with the most recent input serial, so
+    /// that the compositor can tell whether the request stems from
+    /// something that the user did; without that, most compositors
+    /// mark the window as urgent rather than focusing it.
+    /// Returns false if the compositor doesn't support xdg-activation.
+    pub(crate) fn request_activation(&self, surface: &WlSurface) -> bool {
+        let activation = match self.environment.get_global::<XdgActivationV1>() {
+            Some(activation) => activation,
+            None => return false,
+        };
+        let token = activation.get_activation_token();
+        let target = surface.clone();
+        token.quick_assign(move |token, event, _| {
+            if let ActivationTokenEvent::Done {
+                token: token_string,
+            } = event
+            {
+                Self::activate_with_token(&activation, token_string, &target);
+                token.destroy();
+            }
+        });
+        if let Some(seat) = self.keyboard_seat.borrow().as_ref() {
+            token.set_serial(*self.last_serial.borrow(), seat);
+        }
+        token.set_surface(surface);
+        token.commit();
+        true
+    }
+
+    /// Activates `surface` using the token that we were launched
+    /// with, if any, so that the first window receives the focus
+    /// that the launcher requested on our behalf
+    pub(crate) fn activate_with_startup_token(&self, surface: &WlSurface) {
+        let token = match std::env::var("XDG_ACTIVATION_TOKEN") {
+            Ok(token) => token,
+            Err(_) => return,
+        };
+        // The token may only be used once, and must not leak into
+        // the environment of the programs that we spawn
+        std::env::remove_var("XDG_ACTIVATION_TOKEN");
+        if let Some(activation) = self.environment.get_global::<XdgActivationV1>() {
+            Self::activate_with_token(&activation, token, surface);
+        }
+    }
+
+    fn activate_with_token(activation: &XdgActivationV1, token: String, surface: &WlSurface) {
+        log::trace!("activating surface {} with token", surface.as_ref().id());
+        activation.activate(token, surface);
+    }
+
     pub(crate) fn next_window_id(&self) -> usize {
         self.next_window_id
             .fetch_add(1, ::std::sync::atomic::Ordering::Relaxed)
@@ -11,7 +11,7 @@ use wayland_client::protocol::wl_data_device_manager::WlDataDeviceManager;
 use wayland_client::protocol::wl_data_source::Event as DataSourceEvent;
 
 use crate::connection::ConnectionOps;
-use crate::Clipboard;
+use crate::{Clipboard, ClipboardContent};
 
 #[derive(Default)]
 pub struct CopyAndPaste {
@@ -29,6 +29,24 @@ impl std::fmt::Debug for CopyAndPaste {
 }
 
 pub const TEXT_MIME_TYPE: &str = "text/plain;charset=utf-8";
+pub const HTML_MIME_TYPE: &str = "text/html";
+
+/// Returns the mime types under which `content` is offered
+fn offered_mime_types(content: &ClipboardContent) -> Vec<String> {
+    let mut mime_types = vec![TEXT_MIME_TYPE.to_string()];
+    if content.html.is_some() {
+        mime_types.push(HTML_MIME_TYPE.to_string());
+    }
+    mime_types
+}
+
+/// Returns the representation of `content` for the requested mime type
+fn content_for_mime_type<'a>(content: &'a ClipboardContent, mime_type: &str) -> &'a str {
+    match &content.html {
+        Some(html) if mime_type == HTML_MIME_TYPE => html,
+        _ => &content.text,
+    }
+}
 
 impl CopyAndPaste {
     pub fn create() -> Arc<Mutex<Self>> {
@@ -73,7 +91,7 @@ impl CopyAndPaste {
         }
     }
 
-    pub fn set_clipboard_data(&mut self, clipboard: Clipboard, data: String) {
+    pub fn set_clipboard_data(&mut self, clipboard: Clipboard, data: ClipboardContent) {
         let conn = crate::Connection::get().unwrap().wayland();
         let pointer = conn.pointer.borrow();
         let primary_selection = if let Clipboard::PrimarySelection = clipboard {
@@ -88,7 +106,7 @@ impl CopyAndPaste {
             Some((manager, device)) => {
                 let source = PrimarySelectionSource::new(
                     &manager,
-                    &[TEXT_MIME_TYPE.to_string()],
+                    &offered_mime_types(&data),
                     move |event, _dispatch_data| match event {
                         PrimarySelectionSourceEvent::Cancelled => {
                             crate::Connection::get()
@@ -99,9 +117,9 @@ impl CopyAndPaste {
                                 .data_device
                                 .set_selection(None, 0);
                         }
-                        PrimarySelectionSourceEvent::Send { pipe, .. } => {
+                        PrimarySelectionSourceEvent::Send { pipe, mime_type } => {
                             let fd = unsafe { FileDescriptor::from_raw_fd(pipe.into_raw_fd()) };
-                            write_selection_to_pipe(fd, &data);
+                            write_selection_to_pipe(fd, content_for_mime_type(&data, &mime_type));
                         }
                     },
                 );
@@ -112,13 +130,16 @@ impl CopyAndPaste {
                     .environment
                     .require_global::<WlDataDeviceManager>()
                     .create_data_source();
+                let mime_types = offered_mime_types(&data);
                 source.quick_assign(move |_source, event, _dispatch_data| {
-                    if let DataSourceEvent::Send { fd, .. } = event {
+                    if let DataSourceEvent::Send { fd, mime_type } = event {
                         let fd = unsafe { FileDescriptor::from_raw_fd(fd) };
-                        write_selection_to_pipe(fd, &data);
+                        write_selection_to_pipe(fd, content_for_mime_type(&data, &mime_type));
                     }
                 });
-                source.offer(TEXT_MIME_TYPE.to_string());
+                for mime_type in mime_types {
+                    source.offer(mime_type);
+                }
                 conn.pointer
                     .borrow()
                     .data_device
@@ -150,7 +171,7 @@ impl CopyAndPaste {
     }
 }
 
-fn write_selection_to_pipe(fd: FileDescriptor, text: &str) {
+pub(crate) fn write_selection_to_pipe(fd: FileDescriptor, text: &str) {
     if let Err(e) = write_pipe_with_timeout(fd, text.as_bytes()) {
         log::error!("while sending primary selection to pipe: {}", e);
     }
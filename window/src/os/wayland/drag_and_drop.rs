@@ -1,13 +1,17 @@
+use super::copy_and_paste::{write_selection_to_pipe, TEXT_MIME_TYPE};
 use crate::connection::ConnectionOps;
 use crate::wayland::{read_pipe_with_timeout, WaylandConnection};
+use crate::DragContent;
 use filedescriptor::{FileDescriptor, Pipe};
 use smithay_client_toolkit as toolkit;
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::path::PathBuf;
 use toolkit::reexports::client::protocol::wl_data_device::Event as DataDeviceEvent;
 use toolkit::reexports::client::protocol::wl_data_offer::WlDataOffer;
+use toolkit::reexports::client::protocol::wl_surface::WlSurface;
 use url::Url;
-use wayland_client::protocol::wl_data_device_manager::DndAction;
+use wayland_client::protocol::wl_data_device_manager::{DndAction, WlDataDeviceManager};
+use wayland_client::protocol::wl_data_source::Event as DataSourceEvent;
 
 #[derive(Default)]
 pub struct DragAndDrop {
@@ -117,3 +121,51 @@ impl DragAndDrop {
         }
     }
 }
+
+/// Returns the mime types under which `content` is offered when it
+/// is dragged out of a window, along with the data for each of them
+fn drag_offers(content: &DragContent) -> Vec<(&'static str, String)> {
+    match content {
+        DragContent::Text(text) => vec![(TEXT_MIME_TYPE, text.clone())],
+        DragContent::File(path) => {
+            let mut offers = vec![];
+            if let Ok(url) = Url::from_file_path(path) {
+                offers.push((URI_MIME_TYPE, format!("{}\r\n", url)));
+            }
+            offers.push((TEXT_MIME_TYPE, path.to_string_lossy().into_owned()));
+            offers
+        }
+    }
+}
+
+/// Starts dragging `content` out of `surface`.  `serial` must be the
+/// serial of the pointer button press that started the drag.
+pub fn start_drag(surface: &WlSurface, serial: u32, content: DragContent) {
+    let conn = WaylandConnection::get().unwrap().wayland();
+    let source = conn
+        .environment
+        .require_global::<WlDataDeviceManager>()
+        .create_data_source();
+    let offers = drag_offers(&content);
+    for (mime_type, _) in &offers {
+        source.offer(mime_type.to_string());
+    }
+    source.quick_assign(move |source, event, _dispatch_data| match event {
+        DataSourceEvent::Send { fd, mime_type } => {
+            let fd = unsafe { FileDescriptor::from_raw_fd(fd) };
+            if let Some((_, data)) = offers.iter().find(|(m, _)| *m == mime_type) {
+                write_selection_to_pipe(fd, data);
+            }
+        }
+        DataSourceEvent::Cancelled | DataSourceEvent::DndFinished => {
+            source.destroy();
+        }
+        _ => {}
+    });
+    source.set_actions(DndAction::Copy);
+
+    conn.pointer
+        .borrow()
+        .data_device
+        .start_drag(Some(&source), surface, None, serial);
+}
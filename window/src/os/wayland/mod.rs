@@ -11,6 +11,7 @@ mod copy_and_paste;
 mod drag_and_drop;
 mod frame;
 mod pointer;
+mod protocols;
 
 /// Returns the id of a wayland proxy object, suitable for using
 /// a key into hash maps
@@ -0,0 +1,21 @@
+//! Bindings for wayland protocols that are not yet provided by the
+//! wayland-protocols crate; the code is generated by build.rs
+#![allow(dead_code, non_camel_case_types, unused_unsafe, unused_variables)]
+#![allow(non_upper_case_globals, non_snake_case, unused_imports)]
+#![allow(missing_docs, clippy::all)]
+
+pub mod fractional_scale_v1 {
+    pub mod client {
+        pub(crate) use wayland_client::protocol::wl_surface;
+        pub(crate) use wayland_client::sys;
+        pub(crate) use wayland_client::{AnonymousObject, Attached, Main, Proxy, ProxyMap};
+        pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
+        pub(crate) use wayland_commons::smallvec;
+        pub(crate) use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc};
+        pub(crate) use wayland_commons::{Interface, MessageGroup};
+        include!(concat!(
+            env!("OUT_DIR"),
+            "/fractional_scale_v1_client_api.rs"
+        ));
+    }
+}
@@ -3,12 +3,16 @@ use super::frame::{ConceptConfig, ConceptFrame};
 use super::pointer::*;
 use crate::connection::ConnectionOps;
 use crate::os::wayland::connection::WaylandConnection;
+use crate::os::wayland::protocols::fractional_scale_v1::client::wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1;
+use crate::os::wayland::protocols::fractional_scale_v1::client::wp_fractional_scale_v1::{
+    Event as FractionalScaleEvent, WpFractionalScaleV1,
+};
 use crate::os::wayland::wl_id;
 use crate::os::x11::keyboard::Keyboard;
 use crate::{
-    Appearance, Clipboard, Connection, Dimensions, MouseCursor, Point, Rect,
-    RequestedWindowGeometry, ResolvedGeometry, ScreenPoint, Window, WindowEvent, WindowEventSender,
-    WindowKeyEvent, WindowOps, WindowState,
+    Appearance, Clipboard, ClipboardContent, Connection, Dimensions, DragContent, MouseCursor,
+    Point, Rect, RequestedWindowGeometry, ResolvedGeometry, ScreenPoint, Window, WindowEvent,
+    WindowEventSender, WindowKeyEvent, WindowOps, WindowState,
 };
 use anyhow::{anyhow, bail, Context};
 use async_io::Timer;
@@ -36,6 +40,8 @@ use wayland_client::protocol::wl_callback::WlCallback;
 use wayland_client::protocol::wl_keyboard::{Event as WlKeyboardEvent, KeyState};
 use wayland_client::{Attached, Main};
 use wayland_egl::{is_available as egl_is_available, WlEglSurface};
+use wayland_protocols::viewporter::client::wp_viewport::WpViewport;
+use wayland_protocols::viewporter::client::wp_viewporter::WpViewporter;
 use wezterm_font::FontConfiguration;
 use wezterm_input_types::*;
 
@@ -128,6 +134,14 @@ pub struct WaylandWindowInner {
     pub(crate) events: WindowEventSender,
     surface: Attached<WlSurface>,
     surface_factor: i32,
+    /// When the compositor supports fractional-scale-v1, we render
+    /// at the preferred scale and use the viewport to tell it the
+    /// logical size of the surface, rather than using the integer
+    /// buffer scale, which would leave it to scale our buffer down
+    fractional_scale: Option<Main<WpFractionalScaleV1>>,
+    viewport: Option<Main<WpViewport>>,
+    /// The preferred scale, expressed in 120ths
+    preferred_scale: Option<u32>,
     copy_and_paste: Arc<Mutex<CopyAndPaste>>,
     window: Option<toolkit::window::Window<ConceptFrame>>,
     dimensions: Dimensions,
@@ -165,6 +179,7 @@ struct PendingEvent {
     refresh_decorations: bool,
     configure: Option<(u32, u32)>,
     dpi: Option<i32>,
+    fractional_scale: Option<u32>,
     window_state: Option<WindowState>,
 }
 
@@ -276,6 +291,35 @@ impl WaylandWindow {
             .borrow_mut()
             .insert(surface.as_ref().id(), window_id);
 
+        let (fractional_scale, viewport) = match (
+            conn.environment.get_global::<WpFractionalScaleManagerV1>(),
+            conn.environment.get_global::<WpViewporter>(),
+        ) {
+            (Some(manager), Some(viewporter)) => {
+                let fractional_scale = manager.get_fractional_scale(&surface);
+                let pending_event = Arc::clone(&pending_event);
+                fractional_scale.quick_assign(move |_, event, _| {
+                    if let FractionalScaleEvent::PreferredScale { scale } = event {
+                        log::debug!("window_id={} preferred scale is {}/120", window_id, scale);
+                        pending_event
+                            .lock()
+                            .unwrap()
+                            .fractional_scale
+                            .replace(scale);
+                        WaylandConnection::with_window_inner(window_id, move |inner| {
+                            inner.dispatch_pending_event();
+                            Ok(())
+                        });
+                    }
+                });
+                (
+                    Some(fractional_scale),
+                    Some(viewporter.get_viewport(&surface)),
+                )
+            }
+            _ => (None, None),
+        };
+
         let ResolvedGeometry {
             x: _,
             y: _,
@@ -319,7 +363,7 @@ impl WaylandWindow {
         window.set_title(name.to_string());
         let decorations = config
             .as_ref()
-            .map(|c| c.window_decorations)
+            .map(|c| c.window_decorations - WindowDecorations::INTEGRATED_BUTTONS)
             .unwrap_or(WindowDecorations::default());
 
         window.set_decorate(if decorations == WindowDecorations::NONE {
@@ -355,6 +399,9 @@ impl WaylandWindow {
             events: WindowEventSender::new(event_handler),
             surface,
             surface_factor: 1,
+            fractional_scale,
+            viewport,
+            preferred_scale: None,
             invalidated: false,
             window: Some(window),
             dimensions,
@@ -386,6 +433,8 @@ impl WaylandWindow {
 
         wait_configure.recv().await?;
 
+        conn.activate_with_startup_token(&inner.borrow().surface);
+
         Ok(window_handle)
     }
 }
@@ -486,10 +535,8 @@ impl WaylandWindowInner {
         let pending_mouse = Arc::clone(&self.pending_mouse);
 
         if let Some((x, y)) = PendingMouse::coords(&pending_mouse) {
-            let coords = Point::new(
-                self.surface_to_pixels(x as i32) as isize,
-                self.surface_to_pixels(y as i32) as isize,
-            );
+            let factor = self.get_dpi_factor();
+            let coords = Point::new((x * factor) as isize, (y * factor) as isize);
             self.last_mouse_coords = coords;
             let event = MouseEvent {
                 kind: MouseEventKind::Move,
@@ -536,7 +583,7 @@ impl WaylandWindowInner {
         }
 
         if let Some((value_x, value_y)) = PendingMouse::scroll(&pending_mouse) {
-            let factor = self.get_dpi_factor() as f64;
+            let factor = self.get_dpi_factor();
 
             if value_x.signum() != self.hscroll_remainder.signum() {
                 // reset accumulator when changing scroll direction
@@ -586,19 +633,29 @@ impl WaylandWindowInner {
         }
     }
 
-    fn get_dpi_factor(&self) -> i32 {
-        self.dimensions.dpi as i32 / crate::DEFAULT_DPI as i32
+    fn get_dpi_factor(&self) -> f64 {
+        self.dimensions.dpi as f64 / crate::DEFAULT_DPI
+    }
+
+    /// Returns the scale that the compositor would like us to render at
+    fn surface_scale(&self) -> f64 {
+        match (self.viewport.is_some(), self.preferred_scale) {
+            (true, Some(scale)) => scale as f64 / 120.,
+            _ => get_surface_scale_factor(&self.surface) as f64,
+        }
     }
 
     fn surface_to_pixels(&self, surface: i32) -> i32 {
-        surface * self.get_dpi_factor()
+        // fractional-scale-v1 requires that the buffer size be
+        // rounded halfway away from zero
+        (surface as f64 * self.get_dpi_factor()).round() as i32
     }
 
     fn pixels_to_surface(&self, pixels: i32) -> i32 {
         // Take care to round up, otherwise we can lose a pixel
         // and that can effectively lose the final row of the
         // terminal
-        ((pixels as f64) / (self.get_dpi_factor() as f64)).ceil() as i32
+        ((pixels as f64) / self.get_dpi_factor()).ceil() as i32
     }
 
     fn dispatch_pending_event(&mut self) {
@@ -621,8 +678,12 @@ impl WaylandWindowInner {
             self.window_state = window_state;
         }
 
+        if let Some(scale) = pending.fractional_scale {
+            self.preferred_scale.replace(scale);
+        }
+
         if pending.configure.is_none() {
-            if pending.dpi.is_some() {
+            if pending.dpi.is_some() || pending.fractional_scale.is_some() {
                 // Synthesize a pending configure event for the dpi change
                 pending.configure.replace((
                     self.pixels_to_surface(self.dimensions.pixel_width as i32) as u32,
@@ -635,9 +696,10 @@ impl WaylandWindowInner {
         if let Some((mut w, mut h)) = pending.configure.take() {
             if self.window.is_some() {
                 let factor = get_surface_scale_factor(&self.surface);
+                let dpi = (self.surface_scale() * crate::DEFAULT_DPI).round() as usize;
 
                 // Do this early because this affects surface_to_pixels/pixels_to_surface below!
-                self.dimensions.dpi = factor as usize * crate::DEFAULT_DPI as usize;
+                self.dimensions.dpi = dpi;
 
                 let mut pixel_width = self.surface_to_pixels(w.try_into().unwrap());
                 let mut pixel_height = self.surface_to_pixels(h.try_into().unwrap());
@@ -656,11 +718,17 @@ impl WaylandWindowInner {
                 // Update the window decoration size
                 self.window.as_mut().unwrap().resize(w, h);
 
+                if let Some(viewport) = self.viewport.as_ref() {
+                    // Our buffer is pixel_width x pixel_height; tell the
+                    // compositor to present it at the logical size
+                    viewport.set_destination(w.try_into().unwrap(), h.try_into().unwrap());
+                }
+
                 // Compute the new pixel dimensions
                 let new_dimensions = Dimensions {
                     pixel_width: pixel_width.try_into().unwrap(),
                     pixel_height: pixel_height.try_into().unwrap(),
-                    dpi: factor as usize * crate::DEFAULT_DPI as usize,
+                    dpi,
                 };
 
                 // Only trigger a resize if the new dimensions are different;
@@ -689,7 +757,8 @@ impl WaylandWindowInner {
                         // We do this only if the scale has actually changed,
                         // otherwise interactive window resize will keep removing
                         // the window contents!
-                        if self.surface_factor != factor {
+                        // When we have a viewport, the buffer scale remains 1.
+                        if self.viewport.is_none() && self.surface_factor != factor {
                             let wayland_conn = Connection::get().unwrap().wayland();
                             let mut pool = wayland_conn.mem_pool.borrow_mut();
                             // Make a "fake" buffer with the right dimensions, as
@@ -897,6 +966,13 @@ impl WindowOps for WaylandWindow {
         });
     }
 
+    fn focus(&self) {
+        WaylandConnection::with_window_inner(self.0, |inner| {
+            inner.focus();
+            Ok(())
+        });
+    }
+
     fn set_cursor(&self, cursor: Option<MouseCursor>) {
         WaylandConnection::with_window_inner(self.0, move |inner| {
             inner.set_cursor(cursor);
@@ -985,12 +1061,25 @@ impl WindowOps for WaylandWindow {
     }
 
     fn set_clipboard(&self, clipboard: Clipboard, text: String) {
+        self.set_clipboard_content(clipboard, text.into());
+    }
+
+    fn start_drag(&self, content: DragContent) -> bool {
+        WaylandConnection::with_window_inner(self.0, move |inner| {
+            let serial = inner.copy_and_paste.lock().unwrap().last_serial;
+            super::drag_and_drop::start_drag(&inner.surface, serial, content);
+            Ok(())
+        });
+        true
+    }
+
+    fn set_clipboard_content(&self, clipboard: Clipboard, content: ClipboardContent) {
         WaylandConnection::with_window_inner(self.0, move |inner| {
             inner
                 .copy_and_paste
                 .lock()
                 .unwrap()
-                .set_clipboard_data(clipboard, text);
+                .set_clipboard_data(clipboard, content);
             Ok(())
         });
     }
@@ -1030,9 +1119,25 @@ pub(crate) fn read_pipe_with_timeout(mut file: FileDescriptor) -> anyhow::Result
 impl WaylandWindowInner {
     fn close(&mut self) {
         self.events.dispatch(WindowEvent::Destroyed);
+        if let Some(viewport) = self.viewport.take() {
+            viewport.destroy();
+        }
+        if let Some(fractional_scale) = self.fractional_scale.take() {
+            fractional_scale.destroy();
+        }
         self.window.take();
     }
 
+    fn focus(&mut self) {
+        if self.window.is_none() {
+            return;
+        }
+        let conn = Connection::get().unwrap().wayland();
+        if !conn.request_activation(&self.surface) {
+            log::debug!("compositor doesn't support xdg-activation; cannot focus window");
+        }
+    }
+
     fn hide(&mut self) {
         if let Some(window) = self.window.as_ref() {
             window.set_minimized();
@@ -1118,11 +1223,10 @@ impl WaylandWindowInner {
             window.surface().commit();
         }
 
-        let factor = get_surface_scale_factor(&self.surface);
         Dimensions {
             pixel_width: pixel_width as _,
             pixel_height: pixel_height as _,
-            dpi: factor as usize * crate::DEFAULT_DPI as usize,
+            dpi: self.dimensions.dpi,
         }
     }
 
@@ -1140,7 +1244,7 @@ impl WaylandWindowInner {
         if surface_id == *conn.active_surface_id.borrow() {
             if self.text_cursor.map(|prior| prior != rect).unwrap_or(true) {
                 self.text_cursor.replace(rect);
-                let factor = get_surface_scale_factor(&self.surface);
+                let factor = self.get_dpi_factor();
 
                 conn.environment.with_inner(|env| {
                     if let Some(input) = env
@@ -1148,10 +1252,10 @@ impl WaylandWindowInner {
                         .get_text_input_for_surface(&self.surface)
                     {
                         input.set_cursor_rectangle(
-                            rect.min_x() as i32 / factor,
-                            rect.min_y() as i32 / factor,
-                            rect.width() as i32 / factor,
-                            rect.height() as i32 / factor,
+                            (rect.min_x() as f64 / factor) as i32,
+                            (rect.min_y() as f64 / factor) as i32,
+                            (rect.width() as f64 / factor).ceil() as i32,
+                            (rect.height() as f64 / factor).ceil() as i32,
                         );
                         input.commit();
                     }
@@ -3,7 +3,7 @@ use super::{HWindow, WindowInner};
 use crate::connection::ConnectionOps;
 use crate::screen::{ScreenInfo, Screens};
 use crate::spawn::*;
-use crate::{Appearance, ScreenRect};
+use crate::{Appearance, ScreenRect, ShellMenuGroup};
 use anyhow::Context;
 use std::cell::RefCell;
 use std::collections::HashMap;
@@ -94,6 +94,12 @@ impl ConnectionOps for Connection {
         }
     }
 
+    fn set_shell_menu(&self, groups: Vec<ShellMenuGroup>) {
+        if let Err(err) = super::jumplist::set_jump_list(&groups) {
+            log::error!("Failed to update the taskbar jump list: {:#}", err);
+        }
+    }
+
     fn screens(&self) -> anyhow::Result<Screens> {
         struct Info {
             primary: Option<ScreenInfo>,
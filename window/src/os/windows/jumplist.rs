@@ -0,0 +1,128 @@
+//! Populates the taskbar jump list for the application
+use super::wide_string;
+use crate::{ShellMenuGroup, ShellMenuItem};
+use windows::core::{Interface, GUID, PCWSTR, PWSTR};
+use windows::Win32::System::Com::StructuredStorage::PROPVARIANT;
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED, VT_LPWSTR,
+};
+use windows::Win32::UI::Shell::Common::{IObjectArray, IObjectCollection};
+use windows::Win32::UI::Shell::PropertiesSystem::{IPropertyStore, PROPERTYKEY};
+use windows::Win32::UI::Shell::{
+    DestinationList, EnumerableObjectCollection, ICustomDestinationList, IShellLinkW, ShellLink,
+};
+
+/// PKEY_Title from propkey.h; the jump list displays it as the
+/// label of a link
+const PKEY_TITLE: PROPERTYKEY = PROPERTYKEY {
+    fmtid: GUID::from_u128(0xf29f85e0_4ff9_1068_ab91_08002b27b3d9),
+    pid: 2,
+};
+
+/// Quotes an argument so that it survives the parsing performed
+/// by CommandLineToArgvW
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains(|c| c == ' ' || c == '\t' || c == '"') {
+        return arg.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.extend(std::iter::repeat('\\').take(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            c => {
+                quoted.extend(std::iter::repeat('\\').take(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    quoted.extend(std::iter::repeat('\\').take(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+fn make_link(exe: &str, item: &ShellMenuItem) -> windows::core::Result<IShellLinkW> {
+    let args = item
+        .args
+        .iter()
+        .map(|arg| quote_arg(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    unsafe {
+        let link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER)?;
+        link.SetPath(PCWSTR(wide_string(exe).as_ptr()))?;
+        link.SetArguments(PCWSTR(wide_string(&args).as_ptr()))?;
+        link.SetIconLocation(PCWSTR(wide_string(exe).as_ptr()), 0)?;
+        if let Some(description) = &item.description {
+            link.SetDescription(PCWSTR(wide_string(description).as_ptr()))?;
+        }
+
+        let store: IPropertyStore = link.cast()?;
+        let mut title = wide_string(&item.label);
+        let mut value = PROPVARIANT::default();
+        (*value.Anonymous.Anonymous).vt = VT_LPWSTR.0 as u16;
+        (*value.Anonymous.Anonymous).Anonymous.pwszVal = PWSTR(title.as_mut_ptr());
+        // SetValue copies the value, so `title` continues to own the
+        // string and we must not PropVariantClear it here
+        store.SetValue(&PKEY_TITLE, &value)?;
+        store.Commit()?;
+
+        Ok(link)
+    }
+}
+
+/// Replaces the content of the jump list with `groups`; the untitled
+/// groups are added as tasks, and the others as custom categories
+pub fn set_jump_list(groups: &[ShellMenuGroup]) -> anyhow::Result<()> {
+    let exe = std::env::current_exe()?;
+    let exe = exe.to_string_lossy();
+
+    unsafe {
+        // COM may already have been initialized on this thread, which
+        // is fine for our purposes
+        let _ = CoInitializeEx(std::ptr::null(), COINIT_APARTMENTTHREADED);
+
+        let list: ICustomDestinationList =
+            CoCreateInstance(&DestinationList, None, CLSCTX_INPROC_SERVER)?;
+        let mut max_slots = 0u32;
+        let _removed: IObjectArray = list.BeginList(&mut max_slots)?;
+
+        for group in groups {
+            if group.items.is_empty() {
+                continue;
+            }
+            let collection: IObjectCollection =
+                CoCreateInstance(&EnumerableObjectCollection, None, CLSCTX_INPROC_SERVER)?;
+            for item in &group.items {
+                collection.AddObject(make_link(&exe, item)?)?;
+            }
+            let array: IObjectArray = collection.cast()?;
+
+            match &group.title {
+                Some(title) => {
+                    // This fails if the user has removed an item from
+                    // the category; that shouldn't prevent the rest of
+                    // the list from being updated
+                    if let Err(err) =
+                        list.AppendCategory(PCWSTR(wide_string(title).as_ptr()), array)
+                    {
+                        log::warn!("Failed to add {} to the jump list: {:#}", title, err);
+                    }
+                }
+                None => list.AddUserTasks(array)?,
+            }
+        }
+
+        list.CommitList()?;
+    }
+
+    Ok(())
+}
@@ -1,6 +1,7 @@
 pub mod connection;
 pub mod event;
 mod extra_constants;
+mod jumplist;
 mod keycodes;
 mod wgl;
 pub mod window;
@@ -2,10 +2,10 @@ use super::*;
 use crate::connection::ConnectionOps;
 use crate::parameters::{self, Parameters};
 use crate::{
-    Appearance, Clipboard, DeadKeyStatus, Dimensions, Handled, KeyCode, KeyEvent, Modifiers,
-    MouseButtons, MouseCursor, MouseEvent, MouseEventKind, MousePress, Point, RawKeyEvent, Rect,
-    RequestedWindowGeometry, ResolvedGeometry, ScreenPoint, ULength, WindowDecorations,
-    WindowEvent, WindowEventSender, WindowOps, WindowState,
+    Appearance, Clipboard, ClipboardContent, DeadKeyStatus, Dimensions, Handled, KeyCode, KeyEvent,
+    Modifiers, MouseButtons, MouseCursor, MouseEvent, MouseEventKind, MousePress, Point,
+    RawKeyEvent, Rect, RequestedWindowGeometry, ResolvedGeometry, ScreenPoint, ULength,
+    UserAttentionType, WindowDecorations, WindowEvent, WindowEventSender, WindowOps, WindowState,
 };
 use anyhow::{bail, Context};
 use async_trait::async_trait;
@@ -39,6 +39,7 @@ use winapi::um::sysinfoapi::{GetTickCount, GetVersionExW};
 use winapi::um::uxtheme::{
     CloseThemeData, GetThemeFont, GetThemeSysFont, OpenThemeData, SetWindowTheme,
 };
+use winapi::um::winbase::{GlobalAlloc, GlobalFree, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
 use winapi::um::wingdi::{LOGFONTW, MAKEPOINTS};
 use winapi::um::winnt::OSVERSIONINFOW;
 use winapi::um::winuser::*;
@@ -114,6 +115,90 @@ pub(crate) struct WindowInner {
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Ord, PartialOrd)]
 pub struct Window(HWindow);
 
+/// Wraps a fragment of HTML in the document and header that the
+/// "HTML Format" clipboard format requires
+fn cf_html(fragment: &str) -> String {
+    const PREFIX: &str = "<html><body><!--StartFragment-->";
+    const SUFFIX: &str = "<!--EndFragment--></body></html>";
+    let header =
+        |start_html: usize, end_html: usize, start_fragment: usize, end_fragment: usize| {
+            format!(
+                "Version:0.9\r\nStartHTML:{:010}\r\nEndHTML:{:010}\r\n\
+             StartFragment:{:010}\r\nEndFragment:{:010}\r\n",
+                start_html, end_html, start_fragment, end_fragment
+            )
+        };
+    // The offsets are zero padded, so the length of the header
+    // doesn't depend on their values
+    let start_html = header(0, 0, 0, 0).len();
+    let start_fragment = start_html + PREFIX.len();
+    let end_fragment = start_fragment + fragment.len();
+    let end_html = end_fragment + SUFFIX.len();
+    format!(
+        "{}{}{}{}",
+        header(start_html, end_html, start_fragment, end_fragment),
+        PREFIX,
+        fragment,
+        SUFFIX
+    )
+}
+
+/// Places the text on the clipboard along with the "HTML Format"
+/// and "Rich Text Format" versions of it
+fn set_clipboard_formats(hwnd: HWND, content: &ClipboardContent) -> anyhow::Result<()> {
+    unsafe fn set_data(format: UINT, bytes: &[u8]) -> anyhow::Result<()> {
+        let mem = GlobalAlloc(GMEM_MOVEABLE, bytes.len());
+        if mem.is_null() {
+            bail!("GlobalAlloc failed: {}", IoError::last_os_error());
+        }
+        let ptr = GlobalLock(mem) as *mut u8;
+        if ptr.is_null() {
+            GlobalFree(mem);
+            bail!("GlobalLock failed: {}", IoError::last_os_error());
+        }
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+        GlobalUnlock(mem);
+        if SetClipboardData(format, mem).is_null() {
+            GlobalFree(mem);
+            bail!("SetClipboardData failed: {}", IoError::last_os_error());
+        }
+        Ok(())
+    }
+
+    unsafe fn set_registered(name: &str, data: &str) -> anyhow::Result<()> {
+        let format = RegisterClipboardFormatW(wide_string(name).as_ptr());
+        if format == 0 {
+            bail!("RegisterClipboardFormat({}) failed", name);
+        }
+        let mut bytes = data.as_bytes().to_vec();
+        bytes.push(0);
+        set_data(format, &bytes)
+    }
+
+    let text = wide_string(&content.text);
+    let text_bytes =
+        unsafe { std::slice::from_raw_parts(text.as_ptr() as *const u8, text.len() * 2) };
+
+    unsafe {
+        if OpenClipboard(hwnd) == 0 {
+            bail!("OpenClipboard failed: {}", IoError::last_os_error());
+        }
+        let result = (|| -> anyhow::Result<()> {
+            EmptyClipboard();
+            set_data(CF_UNICODETEXT, text_bytes)?;
+            if let Some(html) = &content.html {
+                set_registered("HTML Format", &cf_html(html))?;
+            }
+            if let Some(rtf) = &content.rtf {
+                set_registered("Rich Text Format", rtf)?;
+            }
+            Ok(())
+        })();
+        CloseClipboard();
+        result
+    }
+}
+
 fn wuicolor_to_linearrgba(color: WUIColor) -> LinearRgba {
     LinearRgba::with_srgba(color.R, color.G, color.B, 255)
 }
@@ -318,6 +403,7 @@ fn apply_decoration_immediate(hwnd: HWND, decorations: WindowDecorations) {
 }
 
 fn decorations_to_style(decorations: WindowDecorations) -> u32 {
+    let decorations = decorations - WindowDecorations::INTEGRATED_BUTTONS;
     if decorations == WindowDecorations::RESIZE {
         WS_OVERLAPPEDWINDOW
     } else if decorations == WindowDecorations::TITLE {
@@ -736,6 +822,25 @@ impl WindowOps for Window {
         });
     }
 
+    fn request_attention(&self, kind: UserAttentionType) {
+        let (flags, count) = match kind {
+            // Flash until the window comes to the foreground
+            UserAttentionType::Critical => (FLASHW_ALL | FLASHW_TIMERNOFG, 0),
+            UserAttentionType::Informational => (FLASHW_TRAY, 3),
+            UserAttentionType::Cancel => (FLASHW_STOP, 0),
+        };
+        let mut info = FLASHWINFO {
+            cbSize: std::mem::size_of::<FLASHWINFO>() as UINT,
+            hwnd: self.0 .0,
+            dwFlags: flags,
+            uCount: count,
+            dwTimeout: 0,
+        };
+        unsafe {
+            FlashWindowEx(&mut info);
+        }
+    }
+
     fn toggle_fullscreen(&self) {
         Connection::with_window_inner(self.0, move |inner| {
             inner.toggle_fullscreen();
@@ -815,6 +920,16 @@ impl WindowOps for Window {
         clipboard_win::set_clipboard_string(&text).ok();
     }
 
+    fn set_clipboard_content(&self, clipboard: Clipboard, content: ClipboardContent) {
+        if content.html.is_none() && content.rtf.is_none() {
+            return self.set_clipboard(clipboard, content.text);
+        }
+        if let Err(err) = set_clipboard_formats(self.0 .0, &content) {
+            log::error!("Failed to set formatted clipboard: {:#}", err);
+            self.set_clipboard(clipboard, content.text);
+        }
+    }
+
     fn set_window_drag_position(&self, coords: ScreenPoint) {
         Connection::with_window_inner(self.0, move |inner| {
             inner.window_drag_position = Some(coords);
@@ -33,6 +33,7 @@ pub struct XConnection {
     pub atom_utf8_string: Atom,
     pub atom_xsel_data: Atom,
     pub atom_targets: Atom,
+    pub atom_text_html: Atom,
     pub atom_clipboard: Atom,
     pub atom_gtk_edge_constraints: Atom,
     pub atom_xsettings_selection: Atom,
@@ -42,6 +43,7 @@ pub struct XConnection {
     pub atom_state_maximized_horz: Atom,
     pub atom_state_hidden: Atom,
     pub atom_state_fullscreen: Atom,
+    pub atom_state_demands_attention: Atom,
     pub atom_net_wm_state: Atom,
     pub atom_motif_wm_hints: Atom,
     pub atom_net_wm_pid: Atom,
@@ -512,6 +514,7 @@ impl XConnection {
         let atom_utf8_string = Self::intern_atom(&conn, "UTF8_STRING")?;
         let atom_xsel_data = Self::intern_atom(&conn, "XSEL_DATA")?;
         let atom_targets = Self::intern_atom(&conn, "TARGETS")?;
+        let atom_text_html = Self::intern_atom(&conn, "text/html")?;
         let atom_clipboard = Self::intern_atom(&conn, "CLIPBOARD")?;
         let atom_gtk_edge_constraints = Self::intern_atom(&conn, "_GTK_EDGE_CONSTRAINTS")?;
         let atom_xsettings_selection =
@@ -522,6 +525,8 @@ impl XConnection {
         let atom_state_maximized_horz = Self::intern_atom(&conn, "_NET_WM_STATE_MAXIMIZED_HORZ")?;
         let atom_state_hidden = Self::intern_atom(&conn, "_NET_WM_STATE_HIDDEN")?;
         let atom_state_fullscreen = Self::intern_atom(&conn, "_NET_WM_STATE_FULLSCREEN")?;
+        let atom_state_demands_attention =
+            Self::intern_atom(&conn, "_NET_WM_STATE_DEMANDS_ATTENTION")?;
         let atom_net_wm_state = Self::intern_atom(&conn, "_NET_WM_STATE")?;
         let atom_motif_wm_hints = Self::intern_atom(&conn, "_MOTIF_WM_HINTS")?;
         let atom_net_wm_pid = Self::intern_atom(&conn, "_NET_WM_PID")?;
@@ -630,6 +635,7 @@ impl XConnection {
             atom_state_maximized_horz,
             atom_state_hidden,
             atom_state_fullscreen,
+            atom_state_demands_attention,
             atom_net_wm_state,
             atom_motif_wm_hints,
             atom_net_wm_pid,
@@ -643,6 +649,7 @@ impl XConnection {
             atom_utf8_string,
             atom_xsel_data,
             atom_targets,
+            atom_text_html,
             windows: RefCell::new(HashMap::new()),
             should_terminate: RefCell::new(false),
             depth,
@@ -3,9 +3,10 @@ use crate::bitmaps::*;
 use crate::connection::ConnectionOps;
 use crate::os::{xkeysyms, Connection, Window};
 use crate::{
-    Appearance, Clipboard, DeadKeyStatus, Dimensions, MouseButtons, MouseCursor, MouseEvent,
-    MouseEventKind, MousePress, Point, Rect, RequestedWindowGeometry, ResolvedGeometry,
-    ScreenPoint, WindowDecorations, WindowEvent, WindowEventSender, WindowOps, WindowState,
+    Appearance, Clipboard, ClipboardContent, DeadKeyStatus, Dimensions, MouseButtons, MouseCursor,
+    MouseEvent, MouseEventKind, MousePress, Point, Rect, RequestedWindowGeometry, ResolvedGeometry,
+    ScreenPoint, UserAttentionType, WindowDecorations, WindowEvent, WindowEventSender, WindowOps,
+    WindowState,
 };
 use anyhow::{anyhow, Context as _};
 use async_trait::async_trait;
@@ -24,22 +25,22 @@ use xcb::{Event, Xid};
 
 #[derive(Default)]
 struct CopyAndPaste {
-    clipboard_owned: Option<String>,
-    primary_selection_owned: Option<String>,
+    clipboard_owned: Option<ClipboardContent>,
+    primary_selection_owned: Option<ClipboardContent>,
     clipboard_request: Option<Promise<String>>,
     selection_request: Option<Promise<String>>,
     time: u32,
 }
 
 impl CopyAndPaste {
-    fn clipboard(&self, clipboard: Clipboard) -> &Option<String> {
+    fn clipboard(&self, clipboard: Clipboard) -> &Option<ClipboardContent> {
         match clipboard {
             Clipboard::PrimarySelection => &self.primary_selection_owned,
             Clipboard::Clipboard => &self.clipboard_owned,
         }
     }
 
-    fn clipboard_mut(&mut self, clipboard: Clipboard) -> &mut Option<String> {
+    fn clipboard_mut(&mut self, clipboard: Clipboard) -> &mut Option<ClipboardContent> {
         match clipboard {
             Clipboard::PrimarySelection => &mut self.primary_selection_owned,
             Clipboard::Clipboard => &mut self.clipboard_owned,
@@ -711,14 +712,21 @@ impl XWindowInner {
 
         let selprop = if request.target() == conn.atom_targets {
             // They want to know which targets we support
-            let atoms: [Atom; 1] = [conn.atom_utf8_string];
+            let has_html = self
+                .selection_atom_to_clipboard(request.selection())
+                .and_then(|clipboard| self.copy_and_paste.clipboard(clipboard).as_ref())
+                .map_or(false, |content| content.html.is_some());
+            let mut atoms = vec![conn.atom_utf8_string];
+            if has_html {
+                atoms.push(conn.atom_text_html);
+            }
             log::trace!("SEL: window_id={window_id:?} requestor wants supported targets");
             conn.send_request_no_reply(&xcb::x::ChangeProperty {
                 mode: PropMode::Replace,
                 window: request.requestor(),
                 property: request.property(),
                 r#type: xcb::x::ATOM_ATOM,
-                data: &atoms,
+                data: atoms.as_slice(),
             })?;
 
             // let the requestor know that we set their property
@@ -732,13 +740,13 @@ impl XWindowInner {
                 // We don't and won't do any conversion from UTF-8 to
                 // whatever STRING represents; let's just assume that
                 // the other end is going to handle it correctly.
-                if let Some(text) = self.copy_and_paste.clipboard(clipboard) {
+                if let Some(content) = self.copy_and_paste.clipboard(clipboard) {
                     conn.send_request_no_reply(&xcb::x::ChangeProperty {
                         mode: PropMode::Replace,
                         window: request.requestor(),
                         property: request.property(),
                         r#type: request.target(),
-                        data: text.as_bytes(),
+                        data: content.text.as_bytes(),
                     })?;
                     // let the requestor know that we set their property
                     request.property()
@@ -749,6 +757,25 @@ impl XWindowInner {
             } else {
                 xcb::x::ATOM_NONE
             }
+        } else if request.target() == conn.atom_text_html {
+            log::trace!("SEL: window_id={window_id:?} requestor wants html data");
+            match self
+                .selection_atom_to_clipboard(request.selection())
+                .and_then(|clipboard| self.copy_and_paste.clipboard(clipboard).as_ref())
+                .and_then(|content| content.html.as_ref())
+            {
+                Some(html) => {
+                    conn.send_request_no_reply(&xcb::x::ChangeProperty {
+                        mode: PropMode::Replace,
+                        window: request.requestor(),
+                        property: request.property(),
+                        r#type: request.target(),
+                        data: html.as_bytes(),
+                    })?;
+                    request.property()
+                }
+                None => xcb::x::ATOM_NONE,
+            }
         } else {
             // We didn't support their request, so there is nothing
             // we can report back to them.
@@ -910,6 +937,14 @@ impl XWindowInner {
         )
     }
 
+    fn set_demands_attention_hint(&mut self, enable: bool) -> anyhow::Result<()> {
+        self.set_wm_state(
+            NetWmStateAction::with_bool(enable),
+            self.conn().atom_state_demands_attention,
+            None,
+        )
+    }
+
     #[allow(clippy::identity_op)]
     fn adjust_decorations(&mut self, decorations: WindowDecorations) -> anyhow::Result<()> {
         // Set the motif hints to disable decorations.
@@ -932,6 +967,8 @@ impl XWindowInner {
         const FUNC_MAXIMIZE: u32 = 1 << 4;
         const FUNC_CLOSE: u32 = 1 << 5;
 
+        // The integrated buttons are drawn by the gui in the tab bar
+        let decorations = decorations - WindowDecorations::INTEGRATED_BUTTONS;
         let decorations = if decorations == WindowDecorations::TITLE | WindowDecorations::RESIZE {
             FUNC_ALL
         } else if decorations == WindowDecorations::RESIZE {
@@ -1255,6 +1292,16 @@ impl XWindowInner {
         self.set_fullscreen_hint(!fullscreen).ok();
     }
 
+    fn request_attention(&mut self, kind: UserAttentionType) {
+        // The window manager clears the hint once the window
+        // is focused, so there is no distinction between the
+        // critical and informational requests
+        let enable = kind != UserAttentionType::Cancel;
+        if let Err(err) = self.set_demands_attention_hint(enable) {
+            log::error!("Failed to request attention: {err:#}");
+        }
+    }
+
     fn config_did_change(&mut self, config: &ConfigHandle) {
         self.config = config.clone();
         let _ = self.adjust_decorations(config.window_decorations);
@@ -1533,6 +1580,13 @@ impl WindowOps for XWindow {
         });
     }
 
+    fn request_attention(&self, kind: UserAttentionType) {
+        XConnection::with_window_inner(self.0, move |inner| {
+            inner.request_attention(kind);
+            Ok(())
+        });
+    }
+
     fn maximize(&self) {
         XConnection::with_window_inner(self.0, |inner| {
             inner.maximize();
@@ -1686,16 +1740,20 @@ impl WindowOps for XWindow {
 
     /// Set some text in the clipboard
     fn set_clipboard(&self, clipboard: Clipboard, text: String) {
+        self.set_clipboard_content(clipboard, text.into());
+    }
+
+    fn set_clipboard_content(&self, clipboard: Clipboard, content: ClipboardContent) {
         let window_id = self.0;
         XConnection::with_window_inner(window_id, move |inner| {
             log::trace!(
                 "SEL: window_id={window_id:?} now owns selection \
-                for {clipboard:?} {text:?}"
+                for {clipboard:?} {content:?}"
             );
             inner
                 .copy_and_paste
                 .clipboard_mut(clipboard)
-                .replace(text.clone());
+                .replace(content.clone());
             inner.update_selection_owner(clipboard)?;
             Ok(())
         });
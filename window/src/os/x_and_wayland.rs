@@ -9,8 +9,8 @@ use crate::os::x11::connection::XConnection;
 use crate::os::x11::window::XWindow;
 use crate::screen::Screens;
 use crate::{
-    Appearance, Clipboard, MouseCursor, Rect, RequestedWindowGeometry, ScreenPoint, WindowEvent,
-    WindowOps,
+    Appearance, Clipboard, ClipboardContent, MouseCursor, Rect, RequestedWindowGeometry,
+    ScreenPoint, UserAttentionType, WindowEvent, WindowOps,
 };
 use async_trait::async_trait;
 use config::ConfigHandle;
@@ -270,6 +270,14 @@ impl WindowOps for Window {
         }
     }
 
+    fn focus(&self) {
+        match self {
+            Self::X11(x) => x.focus(),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(w) => w.focus(),
+        }
+    }
+
     fn set_cursor(&self, cursor: Option<MouseCursor>) {
         match self {
             Self::X11(x) => x.set_cursor(cursor),
@@ -294,6 +302,14 @@ impl WindowOps for Window {
         }
     }
 
+    fn request_attention(&self, kind: UserAttentionType) {
+        match self {
+            Self::X11(x) => x.request_attention(kind),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(w) => w.request_attention(kind),
+        }
+    }
+
     fn set_title(&self, title: &str) {
         match self {
             Self::X11(x) => x.set_title(title),
@@ -380,4 +396,11 @@ impl WindowOps for Window {
             Self::Wayland(w) => w.set_clipboard(clipboard, text),
         }
     }
+    fn set_clipboard_content(&self, clipboard: Clipboard, content: ClipboardContent) {
+        match self {
+            Self::X11(x) => x.set_clipboard_content(clipboard, content),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(w) => w.set_clipboard_content(clipboard, content),
+        }
+    }
 }